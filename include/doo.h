@@ -0,0 +1,55 @@
+/* libdoo: the doo compiler as a C library. Built by cargo build as
+ * target/<profile>/libdoo.so (libdoo.dylib on macOS, doo.dll on Windows). */
+#ifndef DOO_H
+#define DOO_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* What doo_compile_source produces besides diagnostics */
+#define DOO_EMIT_CHECK 0   /* nothing: only check the program */
+#define DOO_EMIT_MIR 1     /* the optimized MIR as text */
+#define DOO_EMIT_LLVM_IR 2 /* the optimized LLVM module as text */
+#define DOO_EMIT_OBJECT 3  /* an object file for the host */
+
+typedef struct DooOptions {
+    const char *file_name; /* name in diagnostics, whose directory imports and includes
+                              are found in; NULL for "main.doo" */
+    int32_t emit;          /* one of DOO_EMIT_* */
+    int32_t opt_level;     /* 0 to 3, as in -O */
+    int32_t release;       /* nonzero to leave out assert checks */
+} DooOptions;
+
+typedef struct DooResult DooResult;
+
+/* Compiles the NUL-terminated UTF-8 program text source. options may be NULL
+ * to check it as main.doo. Never returns NULL; free with doo_result_free. */
+DooResult *doo_compile_source(const char *source, const DooOptions *options);
+
+/* 1 when the program compiled without errors, else 0. */
+int32_t doo_result_success(const DooResult *result);
+
+/* The diagnostics, one JSON object per line, as doo check --message-format=json
+ * prints them; "" when there are none. */
+const char *doo_result_diagnostics(const DooResult *result);
+
+/* The error that stopped the compile before it could report diagnostics, such
+ * as a missing main, or NULL. */
+const char *doo_result_error(const DooResult *result);
+
+/* The artifact asked for, with its size stored in *len unless len is NULL;
+ * NULL when there is none. Text artifacts have no NUL terminator. */
+const uint8_t *doo_result_artifact(const DooResult *result, size_t *len);
+
+/* Frees a result and every string and artifact borrowed from it. */
+void doo_result_free(DooResult *result);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* DOO_H */
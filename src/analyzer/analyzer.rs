@@ -1,8 +1,16 @@
+use crate::analyzer::builtins::BUILTIN_FUNCTIONS;
+use crate::analyzer::files::{DiskFiles, SourceFiles};
+use crate::analyzer::modules::{module_prefix, module_symbol};
+use crate::analyzer::stdlib::{std_module_path, std_module_source};
+use crate::analyzer::structs::method_symbol;
 use crate::analyzer::types::{NamedError, SemanticError};
-use crate::parser::ast::{AstNode, Pattern, TypeNode};
-use std::collections::HashMap;
-use std::fs;
-use std::path::PathBuf;
+use crate::analyzer::warnings::Warning;
+use crate::parser::ast::{AstNode, Pattern, Span, TraitMethod, TypeNode};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 #[derive(Clone, Debug)]
 pub struct SymbolInfo {
@@ -17,22 +25,50 @@ pub struct SymbolInfo {
 pub struct SemanticAnalyzer {
     pub(crate) symbol_table: HashMap<String, SymbolInfo>, // Current scope variables
     pub(crate) function_table: HashMap<String, (Vec<TypeNode>, TypeNode)>, // Function signatures
+    pub(crate) overloads: HashMap<String, Vec<String>>,   // Overloaded name -> mangled member names
+    pub(crate) local_functions: Vec<HashMap<String, String>>, // Per enclosing body: nested name -> qualified name
+    pub(crate) struct_table: HashMap<String, Vec<(String, TypeNode)>>, // Struct name -> declared fields in order
+    pub(crate) enum_table: HashMap<String, Vec<(String, Option<TypeNode>)>>, // Enum name -> declared variants in order
+    pub(crate) trait_table: HashMap<String, Vec<TraitMethod>>, // Trait name -> required methods
+    pub(crate) trait_impls: HashMap<String, Vec<String>>, // Trait name -> structs implementing it
+    pub(crate) generic_functions: HashMap<String, AstNode>, // Generic function name -> its declaration
+    pub(crate) generic_instances: Vec<AstNode>, // Instances created by calls, analyzed after the program
+    pub(crate) c_functions: HashSet<String>, // `extern "C"` and `export "C"` functions; C symbols can't be overloaded
+    pub(crate) library: bool, // Some function is `export "C"`: the program needs no main
 
     pub(crate) outer_symbol_table: Option<HashMap<String, SymbolInfo>>, // For nested scopes
     pub(crate) project_root: PathBuf, // Root directory for module resolution
+    pub(crate) source_dir: PathBuf, // Directory of the file being analyzed, which includeStr paths start from
+    pub files: Rc<dyn SourceFiles>, // Where imported modules and included files are read; the disk by default
     pub(crate) imported_modules: HashMap<String, bool>, // Track imported modules to prevent circular imports
+    pub(crate) module_prefix: Option<String>, // `geo$shapes` while analyzing geo/shapes.doo as an import
+    pub(crate) function_aliases: HashMap<String, String>, // Name in scope (`Area`, `shapes::Area`) -> function it names
+    pub dependencies: HashMap<String, PathBuf>, // Dependency name -> its root module file, which its other modules are next to
+    pub(crate) package: Option<String>, // The dependency the module being analyzed belongs to
     pub imported_functions: Vec<AstNode>, // Store imported function AST nodes for MIR generation
-    pub loop_depth: usize,                // Track loop nesting for break/continue error handling
+    pub loop_depth: usize,              // Track loop nesting for break/continue error handling
     pub scope_stack: Vec<HashMap<String, SymbolInfo>>, // Scope stack for block scoping
-    pub function_depth: usize,            // Track function nesting for return statement validation
-    pub scope_sizes_stack: Vec<usize>,    // Track symbol table size at each scope level
+    pub function_depth: usize,          // Track function nesting for return statement validation
+    pub scope_sizes_stack: Vec<usize>,  // Track symbol table size at each scope level
     pub collected_errors: Vec<SemanticError>, // Collect all errors for reporting
-    pub is_main_module: bool,             // Track if analyzing main program or imported module
+    pub(crate) poisoned_names: HashSet<String>, // Variables and functions whose declaration failed
+    pub statement_spans: Vec<Span>, // Where each statement of the next list analyze_program gets starts
+    pub(crate) statement_span: Span, // Where the function body statement being analyzed starts
+    pub warnings: Vec<Warning>,     // Lints found; they don't fail the analysis
+    pub(crate) allowed_lints: Vec<String>, // Lints `#[allow(..)]` turns off in the enclosing functions
+    pub(crate) declared_variables: Vec<(String, Span)>, // Variables the current function declares
+    pub(crate) used_variables: RefCell<HashSet<String>>, // Variables the current function reads
+    pub is_main_module: bool,              // Track if analyzing main program or imported module
+    pub current_function: Option<(String, TypeNode)>, // Name and return type of the function being analyzed
+    /// Break value types of each enclosing loop; None for loops that are not values
+    pub loop_break_types: Vec<Option<Vec<TypeNode>>>,
 }
 
 impl SemanticAnalyzer {
     /// Lookup a variable by name, searching current scope and then walking up the scope stack.
+    /// Every lookup counts as a use of the variable for the `unused` lint.
     pub fn lookup_variable(&self, name: &str) -> Option<&SymbolInfo> {
+        self.used_variables.borrow_mut().insert(name.to_string());
         if let Some(info) = self.symbol_table.get(name) {
             return Some(info);
         }
@@ -50,23 +86,45 @@ impl SemanticAnalyzer {
     pub fn new(project_root: Option<PathBuf>) -> Self {
         let project_root = project_root
             .unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
-        if cfg!(debug_assertions) {
-            println!("[DEBUG] Project root set to: {:?}", project_root);
-        }
 
         Self {
             symbol_table: HashMap::new(),
             function_table: HashMap::new(),
+            overloads: HashMap::new(),
+            local_functions: Vec::new(),
+            struct_table: HashMap::new(),
+            enum_table: HashMap::new(),
+            trait_table: HashMap::new(),
+            trait_impls: HashMap::new(),
+            generic_functions: HashMap::new(),
+            generic_instances: Vec::new(),
+            c_functions: HashSet::new(),
+            library: false,
             outer_symbol_table: None,
+            source_dir: project_root.clone(),
             project_root,
+            files: Rc::new(DiskFiles),
             imported_modules: HashMap::new(),
+            module_prefix: None,
+            function_aliases: HashMap::new(),
+            dependencies: HashMap::new(),
+            package: None,
             imported_functions: Vec::new(),
             loop_depth: 0,
             scope_stack: Vec::new(),
             function_depth: 0,
             scope_sizes_stack: Vec::new(),
             collected_errors: Vec::new(),
+            poisoned_names: HashSet::new(),
+            statement_spans: Vec::new(),
+            statement_span: Span::default(),
+            warnings: Vec::new(),
+            allowed_lints: Vec::new(),
+            declared_variables: Vec::new(),
+            used_variables: RefCell::new(HashSet::new()),
             is_main_module: true,
+            current_function: None,
+            loop_break_types: Vec::new(),
         }
     }
 
@@ -90,7 +148,22 @@ impl SemanticAnalyzer {
         // FIRST PASS: Process imports and register all function signatures
         // Collect errors but don't stop at first module error
 
-        for node in nodes.iter_mut() {
+        // Only errors in these nodes decide the result; earlier ones are already reported
+        let errors_before = self.collected_errors.len();
+
+        // Struct, enum and trait names can be used in declarations before them
+        self.register_structs(nodes);
+        self.register_enums(nodes);
+        self.register_traits(nodes);
+
+        // Errors are located at the statement they occur in; nested lists have no spans
+        let spans = std::mem::take(&mut self.statement_spans);
+        let locate = |index: usize, error: SemanticError| match spans.get(index) {
+            Some(span) => error.at(*span),
+            None => error,
+        };
+
+        for (index, node) in nodes.iter_mut().enumerate() {
             match node {
                 // Process imports first to load external functions
                 AstNode::Import { path, symbol } => {
@@ -102,30 +175,74 @@ impl SemanticAnalyzer {
                 // Register local function signatures
                 AstNode::FunctionDecl {
                     name,
-                    params,
-                    return_type,
+                    type_params,
+                    export,
                     ..
                 } => {
-                    // Check if function already defined
-                    if self.function_table.contains_key(name) {
-                        self.collected_errors
-                            .push(SemanticError::FunctionRedeclaration(NamedError {
+                    // An imported module's functions are named after it; an
+                    // exported one keeps the C symbol it declares
+                    let symbol = match &self.module_prefix {
+                        Some(prefix) if !*export => module_symbol(prefix, name),
+                        _ => name.clone(),
+                    };
+                    // Builtin names are taken, and so are imported ones
+                    let imported = self
+                        .function_aliases
+                        .get(name.as_str())
+                        .is_some_and(|target| *target != symbol);
+                    if BUILTIN_FUNCTIONS.contains(&name.as_str()) || imported {
+                        self.collected_errors.push(locate(
+                            index,
+                            SemanticError::FunctionRedeclaration(NamedError {
                                 name: name.to_string(),
-                            }));
+                            }),
+                        ));
                         continue;
                     }
-
-                    // Collect parameter types
-                    let param_types: Vec<TypeNode> = params
-                        .iter()
-                        .map(|(_, t)| t.clone().unwrap_or(TypeNode::Int))
-                        .collect();
-
-                    // Register function signature (all functions, not just public ones)
-                    self.function_table.insert(
-                        name.to_string(),
-                        (param_types, return_type.clone().unwrap_or(TypeNode::Void)),
-                    );
+                    if self.module_prefix.is_some() {
+                        self.function_aliases.insert(name.clone(), symbol.clone());
+                        *name = symbol;
+                    }
+                    let name = name.clone();
+                    // Generic functions get a signature per instance instead
+                    let result = if *export {
+                        self.register_export_decl(node)
+                    } else if type_params.is_empty() {
+                        self.register_function_decl(node)
+                    } else {
+                        self.register_generic_function(node)
+                    };
+                    if let Err(e) = result {
+                        self.poisoned_names.insert(name);
+                        self.collected_errors.push(locate(index, e));
+                    }
+                }
+                // Methods are registered as functions named `User::greet`;
+                // an impl for an unknown struct is reported in the second pass
+                AstNode::ImplBlock {
+                    struct_name,
+                    trait_name,
+                    methods,
+                } if self.struct_table.contains_key(struct_name.as_str()) => {
+                    if let Some(trait_name) = trait_name {
+                        self.trait_impls
+                            .entry(trait_name.clone())
+                            .or_default()
+                            .push(struct_name.clone());
+                    }
+                    for method in methods.iter_mut() {
+                        if let AstNode::FunctionDecl { name, .. } = method {
+                            *name = method_symbol(struct_name, name);
+                        }
+                        if let Err(e) = self.register_function_decl(method) {
+                            self.collected_errors.push(locate(index, e));
+                        }
+                    }
+                }
+                AstNode::ExternFunctionDecl { .. } => {
+                    if let Err(e) = self.register_extern_decl(node) {
+                        self.collected_errors.push(locate(index, e));
+                    }
                 }
                 _ => {} // Skip other nodes in first pass
             }
@@ -135,24 +252,64 @@ impl SemanticAnalyzer {
 
         // Skip imports as they're already processed
 
-        for node in nodes {
+        // Statements after a return, break, continue or panic never run;
+        // only the first of them is reported
+        let mut diverged = false;
+        let mut reported_unreachable = false;
+        for (index, node) in nodes.iter_mut().enumerate() {
+            if let Some(span) = spans.get(index) {
+                self.statement_span = *span;
+                if diverged && !reported_unreachable && self.function_depth > 0 {
+                    self.warn(
+                        "unreachable",
+                        "unreachable statement".to_string(),
+                        Some("the statement before it never finishes".to_string()),
+                    );
+                    reported_unreachable = true;
+                }
+            }
+            diverged = diverged || diverges(node);
             if !matches!(node, AstNode::Import { .. }) {
-                if let Err(e) = self.analyze_node(node) {
-                    self.collected_errors.push(e);
+                let result = self.analyze_node(node);
+                if result.is_ok() && self.function_depth > 0 {
+                    self.declare_variables(node);
+                }
+                if let Err(e) = result {
+                    // Variables a failed `let` declares are poisoned rather than
+                    // left undeclared, so their uses don't repeat the error
+                    if let AstNode::LetDecl { pattern, .. } = node {
+                        pattern_names(pattern, &mut self.poisoned_names);
+                    }
+                    if !self.is_cascade(&e) {
+                        self.collected_errors.push(locate(index, e));
+                    }
                 }
             }
         }
 
-        // Check that main() function exists only for the main module
-        if self.is_main_module && !self.function_table.contains_key("main") {
-            self.collected_errors.push(SemanticError::ParseError);
+        // Instances of generic functions join the program once analyzed;
+        // their bodies may call for further instances
+        if self.function_depth == 0 {
+            while !self.generic_instances.is_empty() {
+                for mut instance in std::mem::take(&mut self.generic_instances) {
+                    if let Err(e) = self.analyze_node(&mut instance) {
+                        self.collected_errors.push(e);
+                    }
+                    nodes.push(instance);
+                }
+            }
+        }
+
+        // Check that main() function exists only for the main module; a
+        // library exporting functions to C has none
+        if self.is_main_module && !self.function_table.contains_key("main") && !self.library {
+            self.collected_errors.push(SemanticError::MissingMain);
         }
 
         // If any errors were collected, prioritize reporting a circular import error
-        if !self.collected_errors.is_empty() {
+        if self.collected_errors.len() > errors_before {
             // Prefer to report a circular import error if present
-            if let Some(circular) = self
-                .collected_errors
+            if let Some(circular) = self.collected_errors[errors_before..]
                 .iter()
                 .find(|e| matches!(e, SemanticError::CircularImport { .. }))
             {
@@ -165,12 +322,24 @@ impl SemanticAnalyzer {
                 });
             }
             // Otherwise, report the first error as before
-            return Err(self.collected_errors.remove(0));
+            return Err(self.collected_errors.remove(errors_before));
         } else {
             Ok(())
         }
     }
 
+    /// Whether `error` only follows from an earlier one: the use of a
+    /// variable or function whose declaration failed.
+    fn is_cascade(&self, error: &SemanticError) -> bool {
+        match error.inner() {
+            SemanticError::UndeclaredVariable(NamedError { name })
+            | SemanticError::UndeclaredFunction(NamedError { name }) => {
+                self.poisoned_names.contains(name)
+            }
+            _ => false,
+        }
+    }
+
     /// Determines if a type should use reference counting.
     /// Used for arrays, maps, and strings.
     pub fn should_be_rc(ty: &TypeNode) -> bool {
@@ -193,15 +362,91 @@ impl SemanticAnalyzer {
                 params,
                 return_type,
                 body,
-            } => self.analyze_functional_decl(name, visibility, params, return_type, body),
+                body_spans,
+                type_params,
+                allow,
+                arena,
+                export,
+                test,
+            } => {
+                if *export && self.function_depth > 0 {
+                    return Err(SemanticError::UnexpectedNode {
+                        expected: format!("exported function '{}' at the top level", name),
+                    });
+                }
+                // The test runner calls a test with nothing and ignores what it returns
+                if *test
+                    && (self.function_depth > 0
+                        || !params.is_empty()
+                        || !type_params.is_empty()
+                        || return_type.as_ref().is_some_and(|ty| *ty != TypeNode::Void))
+                {
+                    return Err(SemanticError::UnexpectedNode {
+                        expected: format!(
+                            "test '{}' at the top level, taking and returning nothing",
+                            name
+                        ),
+                    });
+                }
+                if !type_params.is_empty() {
+                    if self.function_depth > 0 {
+                        return Err(SemanticError::UnexpectedNode {
+                            expected: format!("generic function '{}' at the top level", name),
+                        });
+                    }
+                    // Analyzed through its instances
+                    return self.check_generic_bounds(type_params);
+                }
+                let param_types: Vec<TypeNode> = params
+                    .iter()
+                    .map(|(_, t)| t.clone().unwrap_or(TypeNode::Int))
+                    .collect();
+                // Function bodies hoist their own nested declarations under a
+                // qualified name; anything left unqualified sits inside a block
+                if self.function_depth > 0 && !name.contains('.') {
+                    return Err(SemanticError::UnexpectedNode {
+                        expected: format!(
+                            "nested function '{}' directly in a function body, not inside a block",
+                            name
+                        ),
+                    });
+                }
+                self.mangle_overloaded_decl(name, &param_types);
+                self.analyze_functional_decl(
+                    name,
+                    visibility,
+                    params,
+                    return_type,
+                    body,
+                    body_spans,
+                    allow,
+                    *arena,
+                )
+            }
             AstNode::StructDecl { .. } => self.analyze_struct(node),
+            AstNode::ImplBlock { .. } => self.analyze_impl_block(node),
             AstNode::EnumDecl { .. } => self.analyze_enum(node),
+            AstNode::TraitDecl { .. } => self.analyze_trait(node),
+            // Registered in the first pass; C functions are declared at the top level
+            AstNode::ExternFunctionDecl { name, .. } => {
+                if self.function_depth > 0 {
+                    return Err(SemanticError::UnexpectedNode {
+                        expected: format!("extern function '{}' at the top level", name),
+                    });
+                }
+                Ok(())
+            }
 
             // Import statement - already processed in first pass of analyze_program
             AstNode::Import { .. } => Ok(()),
 
             // Statements
             AstNode::Assignment { pattern, value } => self.analyze_assignment(pattern, value),
+            AstNode::FieldAssignment {
+                object,
+                field,
+                value,
+            } => self.analyze_field_assignment(object, field, value),
             AstNode::CompoundAssignment { pattern, op, value } => {
                 self.analyze_compound_assignment(pattern, *op, value)
             }
@@ -213,20 +458,33 @@ impl SemanticAnalyzer {
                     }));
                 }
                 // Check return value types
-                for v in values {
-                    self.infer_type(v)?;
+                let current_function = self.current_function.clone();
+                for v in values.iter_mut() {
+                    self.analyze_value_exprs(v)?;
+                    self.coerce_call_args(v)?;
+                    if let Some((_, ret_ty)) = &current_function {
+                        self.coerce_optional(ret_ty, v)?;
+                    }
+                    // A call's arguments are checked as they are in a `let`
+                    self.infer_rhs_types(v, 1)?;
+                }
+                // Checked here as well, while bindings such as `if let` are still in scope
+                if let Some((fn_name, ret_ty)) = &current_function {
+                    if *ret_ty != TypeNode::Void {
+                        self.verify_single_return(values, ret_ty, fn_name)?;
+                    }
                 }
                 Ok(())
             }
             AstNode::Print { .. } => self.analyze_print(node),
-            AstNode::Break => {
+            AstNode::Break { value } => {
                 // Error if not inside a loop
                 if self.loop_depth == 0 {
                     return Err(SemanticError::UnexpectedNode {
                         expected: "break inside loop".to_string(),
                     });
                 }
-                Ok(())
+                self.analyze_break_value(value.as_deref_mut())
             }
             AstNode::Continue => {
                 // Error if not inside a loop
@@ -242,11 +500,19 @@ impl SemanticAnalyzer {
                 then_block,
                 else_branch,
             } => self.analyze_conditional_stmt(condition, then_block, else_branch),
+            AstNode::IfLet {
+                name,
+                value,
+                then_block,
+                else_branch,
+            } => self.analyze_if_let(name, value, then_block, else_branch),
+            AstNode::Match { value, arms } => self.analyze_match(value, arms),
             AstNode::ForLoopStmt {
                 pattern,
                 iterable,
+                step,
                 body,
-            } => self.analyze_for_stmt(pattern, iterable.as_deref_mut(), body),
+            } => self.analyze_for_stmt(pattern, iterable.as_deref_mut(), step.as_deref(), body),
             AstNode::Block(nodes) => {
                 // Save the current symbol table to restore after block
                 let parent_scope = self.symbol_table.clone();
@@ -271,10 +537,12 @@ impl SemanticAnalyzer {
             // Ensure expressions (literals, binary/unary ops, function calls) are type-correct.
             // Future-proof: new AST node types will still be semantically validated.
             _ => {
+                self.analyze_value_exprs(node)?;
+                self.coerce_call_args(node)?;
                 // Add function call argument count/type checking
                 if let AstNode::FunctionCall { func, args } = node {
                     // Try to extract function name from Identifier node
-                    let func_name = if let AstNode::Identifier(name) = &**func {
+                    let func_name = if let AstNode::Identifier(name, _) = &**func {
                         name
                     } else {
                         return Err(SemanticError::InvalidFunctionCall {
@@ -282,11 +550,16 @@ impl SemanticAnalyzer {
                         });
                     };
 
+                    if let Some(result) = self.check_builtin_call(func_name, args) {
+                        return result.map(|_| ());
+                    }
+
                     let (param_types, _return_type) =
-                        self.function_table.get(func_name).ok_or_else(|| {
-                            SemanticError::UndeclaredFunction(NamedError {
+                        self.call_signature(func_name).ok_or_else(|| {
+                            self.suggest(SemanticError::UndeclaredFunction(NamedError {
                                 name: func_name.clone(),
-                            })
+                            }))
+                            .at_node(func)
                         })?;
 
                     // Check argument count
@@ -304,9 +577,11 @@ impl SemanticAnalyzer {
                         if arg_type != *expected_type {
                             return Err(SemanticError::FunctionArgumentTypeMismatch {
                                 name: func_name.clone(),
-                                expected: expected_type.clone(),
+                                expected: Box::new(expected_type.clone()),
                                 found: arg_type,
-                            });
+                            }
+                            .at_node(arg)
+                            .at_node(func));
                         }
                     }
 
@@ -320,229 +595,417 @@ impl SemanticAnalyzer {
         }
     }
 
-    // Helper to check if currently inside a loop (for break/continue validation)
+    /// Registers the signature of a function declaration, first resolving the
+    /// struct names in it to struct types.
+    pub(crate) fn register_function_decl(
+        &mut self,
+        node: &mut AstNode,
+    ) -> Result<(), SemanticError> {
+        let AstNode::FunctionDecl {
+            name,
+            params,
+            return_type,
+            ..
+        } = node
+        else {
+            return Ok(());
+        };
+
+        // Struct names in the signature become struct types
+        for (_, param_type) in params.iter_mut() {
+            if let Some(ty) = param_type {
+                *ty = self.resolve_type_refs(ty);
+            }
+        }
+        if let Some(ty) = return_type {
+            *ty = self.resolve_type_refs(ty);
+        }
+
+        // Collect parameter types
+        let param_types: Vec<TypeNode> = params
+            .iter()
+            .map(|(_, t)| t.clone().unwrap_or(TypeNode::Int))
+            .collect();
+
+        // Register function signature (all functions, not just public ones);
+        // same name with other parameter types makes an overload set
+        self.register_function(
+            name,
+            param_types,
+            return_type.clone().unwrap_or(TypeNode::Void),
+        )
+    }
+
+    /// Registers the signature of an `extern "C"` function. Its parameters
+    /// are passed to C as they are, so a Str is a `char *`. An LLVM
+    /// intrinsic takes no strings.
+    pub(crate) fn register_extern_decl(&mut self, node: &mut AstNode) -> Result<(), SemanticError> {
+        let AstNode::ExternFunctionDecl {
+            name,
+            params,
+            return_type,
+            intrinsic,
+        } = node
+        else {
+            return Ok(());
+        };
+        if let Some(intrinsic) = intrinsic {
+            if !intrinsic.starts_with("llvm.") {
+                return Err(SemanticError::UnexpectedNode {
+                    expected: format!(
+                        "an LLVM intrinsic name starting with 'llvm.' for '{}', found '{}'",
+                        name, intrinsic
+                    ),
+                });
+            }
+        }
 
-    /// Resolve a module path (e.g., ["http", "Client"]) to a file path
-    /// For import http::Client::Fetchuser, we want http/Client.doo
-    /// The last element before the symbol is the file name
-    fn resolve_module_path(&self, path: &[String], symbol: &Option<String>) -> Option<PathBuf> {
-        let mut buf = self.project_root.clone();
+        // The parser requires parameter types
+        let param_types: Vec<TypeNode> = params
+            .iter()
+            .map(|(_, t)| t.clone().unwrap_or(TypeNode::Int))
+            .collect();
+        let ret_ty = return_type.clone().unwrap_or(TypeNode::Void);
+        self.check_c_signature(name, &param_types, &ret_ty, intrinsic.is_none())?;
 
-        // For imports like http::Client::Fetchuser, we want http/Client.doo
-        // The path will be ["http", "Client"]
-        for part in path {
-            buf.push(part);
+        self.register_function(name, param_types, ret_ty)?;
+        self.c_functions.insert(name.clone());
+        Ok(())
+    }
+
+    /// Registers the signature of an `export "C"` function. C passes its
+    /// strings without the header doo strings have, so it takes them as
+    /// CStr rather than Str.
+    pub(crate) fn register_export_decl(&mut self, node: &mut AstNode) -> Result<(), SemanticError> {
+        let AstNode::FunctionDecl {
+            name,
+            params,
+            return_type,
+            type_params,
+            ..
+        } = node
+        else {
+            return Ok(());
+        };
+        if !type_params.is_empty() {
+            return Err(SemanticError::UnexpectedNode {
+                expected: format!("exported function '{}' without type parameters", name),
+            });
         }
 
-        // Add .doo extension
-        buf.set_extension("doo");
+        let param_types: Vec<TypeNode> = params
+            .iter()
+            .map(|(_, t)| t.clone().unwrap_or(TypeNode::Int))
+            .collect();
+        let ret_ty = return_type.clone().unwrap_or(TypeNode::Void);
+        self.check_c_signature(name, &param_types, &ret_ty, false)?;
+
+        let name = name.clone();
+        self.register_function_decl(node)?;
+        self.c_functions.insert(name);
+        self.library = true;
+        Ok(())
+    }
+
+    /// Checks a function C calls or is called from. Only types with a C
+    /// equivalent cross over: Int, Int64, Float, Bool and CStr, and Str
+    /// parameters when `str_params`. A C function has one symbol, so its
+    /// name can't be overloaded.
+    fn check_c_signature(
+        &self,
+        name: &str,
+        params: &[TypeNode],
+        ret_ty: &TypeNode,
+        str_params: bool,
+    ) -> Result<(), SemanticError> {
+        let taken = BUILTIN_FUNCTIONS.contains(&name)
+            || self.function_table.contains_key(name)
+            || self.overloads.contains_key(name);
+        if taken {
+            return Err(SemanticError::FunctionRedeclaration(NamedError {
+                name: name.to_string(),
+            }));
+        }
+
+        let invalid = |found: &TypeNode| SemanticError::InvalidExternType {
+            function: name.to_string(),
+            found: found.clone(),
+        };
+        for ty in params {
+            match ty {
+                TypeNode::Int
+                | TypeNode::Int64
+                | TypeNode::Float
+                | TypeNode::Bool
+                | TypeNode::CStr => {}
+                TypeNode::String if str_params => {}
+                _ => return Err(invalid(ty)),
+            }
+        }
+        match ret_ty {
+            TypeNode::Int
+            | TypeNode::Int64
+            | TypeNode::Float
+            | TypeNode::Bool
+            | TypeNode::CStr
+            | TypeNode::Void => Ok(()),
+            _ => Err(invalid(ret_ty)),
+        }
+    }
+
+    // Helper to check if currently inside a loop (for break/continue validation)
+
+    /// Resolve a module path (e.g., ["http", "Client"]) to its file,
+    /// http/Client.doo under the project root, and the path naming the module
+    /// program-wide. A dependency's name starts a path into it: `mathlib` is
+    /// its root module and `mathlib::geo` the geo.doo next to that; a
+    /// dependency's own modules are named under it.
+    fn resolve_module_path(&self, path: &[String]) -> Option<(PathBuf, Vec<String>)> {
+        let (first, rest) = path.split_first()?;
+        // `std` is the standard library's, built into the compiler
+        if first == "std" {
+            return std_module_path(path).map(|file| (file, path.to_vec()));
+        }
+
+        let (mut buf, parts, module) = match self.dependencies.get(first) {
+            Some(root) if rest.is_empty() => (root.clone(), rest, path.to_vec()),
+            Some(root) => (
+                root.parent().map(Path::to_path_buf).unwrap_or_default(),
+                rest,
+                path.to_vec(),
+            ),
+            None => (
+                self.project_root.clone(),
+                path,
+                self.package.iter().chain(path).cloned().collect(),
+            ),
+        };
+        if !parts.is_empty() {
+            for part in parts {
+                buf.push(part);
+            }
+            buf.set_extension("doo");
+        }
 
-        if buf.exists() {
-            Some(buf)
+        if self.files.exists(&buf) {
+            Some((buf, module))
         } else {
             None
         }
     }
 
+    /// The source of the module file `path` resolves to.
+    fn read_module(&self, path: &Path) -> io::Result<String> {
+        match std_module_source(path) {
+            Some(source) => Ok(source.to_string()),
+            None => self.files.read_to_string(path),
+        }
+    }
+
+    /// Imports `path::symbol`: the module at `path::symbol` when there is one
+    /// (`import geo::shapes;`), whose public functions are then called as
+    /// `shapes::Area(..)`, or else the public function `symbol` of the module
+    /// at `path` (`import geo::shapes::Area;`). A dependency's root module has
+    /// modules next to it, so `import mathlib::geo;` finds its `geo`.
+    ///
+    /// Every function of the module joins the program under its module's name
+    /// (see `module_symbol`), its private ones and the ones it imports
+    /// included, since its public ones may call them.
     fn import_module(
         &mut self,
         path: &[String],
         symbol: &Option<String>,
         import_stack: &mut Vec<String>,
     ) -> Result<(), SemanticError> {
-        // Create module key for circular import detection
+        let full_path = path.iter().chain(symbol).cloned().collect::<Vec<_>>();
+        let (module, symbol) = match symbol {
+            Some(_)
+                if self.resolve_module_path(&full_path).is_some()
+                    || self.resolve_module_path(path).is_none() =>
+            {
+                (full_path.clone(), None)
+            }
+            _ => (path.to_vec(), symbol.as_ref()),
+        };
 
-        let module_key = path.join("::");
+        let (file_path, qualified) = self
+            .resolve_module_path(&module)
+            .ok_or_else(|| SemanticError::ModuleNotFound(full_path.join("::")))?;
 
-        if cfg!(debug_assertions) {
-            println!(
-                "[DEBUG] Importing module: {} (import stack: {:?})",
-                module_key, import_stack
-            );
-        }
+        // Create module key for circular import detection
+        let module_key = qualified.join("::");
 
         // CIRCULAR DEPENDENCY DETECTION
         if import_stack.contains(&module_key) {
             let mut cycle = import_stack.clone();
             cycle.push(module_key.clone());
-            if cfg!(debug_assertions) {
-                println!("[ERROR] Circular import detected: {}", cycle.join(" -> "));
-            }
             return Err(SemanticError::CircularImport { cycle });
         }
-        import_stack.push(module_key.clone());
-        if cfg!(debug_assertions) {
-            println!("[DEBUG] Import stack updated: {:?}", import_stack);
-        }
 
-        // If importing a specific symbol, check if that symbol is already imported
-        if let Some(sym) = symbol {
-            if self.function_table.contains_key(sym) {
-                import_stack.pop();
-                return Ok(()); // Symbol already imported, skip
+        import_stack.push(module_key.clone());
+        let analyzed = self.analyze_module(&qualified, &file_path, import_stack);
+        import_stack.pop();
+        let (nodes, imported_analyzer) = analyzed?;
+        self.imported_modules.insert(module_key.clone(), true);
+
+        // The C functions the module and its own imports declare come along,
+        // since its functions call them, but stay private to it
+        for node in nodes.iter().chain(&imported_analyzer.imported_functions) {
+            if let AstNode::ExternFunctionDecl { name, .. } = node {
+                let declared = self.imported_functions.iter().any(|n| {
+                    matches!(n, AstNode::ExternFunctionDecl { name: extern_name, .. } if extern_name == name)
+                });
+                if !declared {
+                    self.imported_functions.push(node.clone());
+                }
             }
         }
 
-        // Check if we've already fully analyzed this module (for wildcard imports)
-        let already_analyzed = self.imported_modules.contains_key(&module_key);
-
-        let file_path = self.resolve_module_path(path, symbol).ok_or_else(|| {
-            let full_path = if let Some(sym) = symbol {
-                format!("{}::{}", path.join("::"), sym)
-            } else {
-                path.join("::")
-            };
-            import_stack.pop();
-            SemanticError::ModuleNotFound(full_path)
-        })?;
-
-        // If this module was already analyzed, we can reuse the cached analysis
-
-        // We only need to parse and analyze once per module file
-
-        let (nodes, imported_analyzer) = if already_analyzed {
-            // Module already analyzed, just parse to get the AST nodes
-
-            let code = fs::read_to_string(&file_path)
-                .map_err(|_| SemanticError::ModuleNotFound(file_path.display().to_string()))?;
-            let tokens = crate::lexar::lexer::lex(&code);
-
-            let mut parser = crate::parser::Parser::new(&tokens);
-
-            let ast = parser
-                .parse_program()
-                .map_err(|e| SemanticError::ParseErrorInModule {
-                    file: file_path.display().to_string(),
-                    error: e.to_string(),
-                })?;
-
-            if let crate::parser::ast::AstNode::Program(nodes) = ast {
-                // Create a temporary analyzer and analyze (will be fast since already done)
-                let mut imported_analyzer = SemanticAnalyzer::new(Some(self.project_root.clone()));
-                let mut nodes_mut = nodes.clone();
-                imported_analyzer.is_main_module = false;
-                imported_analyzer.analyze_program_with_stack(&mut nodes_mut, import_stack)?;
-                import_stack.pop();
-                (nodes, imported_analyzer)
-            } else {
-                import_stack.pop();
-                return Ok(());
-            }
-        } else {
-            // First time analyzing this module
-
-            let code = fs::read_to_string(&file_path).map_err(|_| {
-                import_stack.pop();
-                SemanticError::ModuleNotFound(file_path.display().to_string())
-            })?;
-
-            // Mark this module as being imported
-
-            self.imported_modules.insert(module_key, true);
-
-            let tokens = crate::lexar::lexer::lex(&code);
-
-            let mut parser = crate::parser::Parser::new(&tokens);
-
-            let ast = parser.parse_program().map_err(|e| {
-                import_stack.pop();
-                SemanticError::ParseErrorInModule {
-                    file: file_path.display().to_string(),
-                    error: e.to_string(),
-                }
-            })?;
-
-            // Recursively analyze the imported AST
-            if let crate::parser::ast::AstNode::Program(mut nodes) = ast {
-                // Create a temporary analyzer to collect public functions from the imported module
-
-                let mut imported_analyzer = SemanticAnalyzer::new(Some(self.project_root.clone()));
-
-                // Use analyze_program_with_stack for proper two-pass analysis with circular import detection
-                // Pass the current import_stack so recursive imports are detected correctly
-
-                imported_analyzer.is_main_module = false;
-                imported_analyzer.analyze_program_with_stack(&mut nodes, import_stack)?;
-
-                import_stack.pop();
-                (nodes, imported_analyzer)
-            } else {
-                println!(
-                    "[WARNING] [import_module] Parsed AST from {:?} is not a Program variant",
-                    file_path
+        // So do all of its functions and the ones it imports, transitively;
+        // their module-qualified names keep them apart from everything else
+        for node in nodes.iter().chain(&imported_analyzer.imported_functions) {
+            if let AstNode::FunctionDecl { name, .. } = node {
+                let declared = self.imported_functions.iter().any(
+                    |n| matches!(n, AstNode::FunctionDecl { name: fn_name, .. } if fn_name == name),
                 );
-                import_stack.pop();
-                return Ok(());
-            }
-        };
-
-        // Merge public functions from imported module into current function table
-        // AND store the function AST nodes for MIR generation
-        // IMPORTANT: ALWAYS import ALL public functions from the module, regardless of specific symbol
-        // This is because functions may depend on each other within the same module.
-        // For example, if we import ConvertWithLogic, we also need BoolToInt which it calls.
-
-        for node in nodes {
-            if let AstNode::FunctionDecl { name, .. } = &node {
-                // Only import functions that start with uppercase (public convention)
-                if name.chars().next().unwrap_or('a').is_uppercase() {
-                    // Always import all public functions from this module
-                    // This ensures internal module dependencies are available
-                    if !self.imported_functions.iter().any(|n| {
-                        if let AstNode::FunctionDecl { name: fn_name, .. } = n {
-                            fn_name == name
-                        } else {
-                            false
-                        }
-                    }) {
-                        self.imported_functions.push(node.clone());
-                    }
-                    // Copy function signature to current function table
-                    if let Some((params, ret)) = imported_analyzer.function_table.get(name) {
-                        self.function_table
-                            .insert(name.clone(), (params.clone(), ret.clone()));
-                    }
+                if !declared {
+                    self.imported_functions.push(node.clone());
+                }
+                if let Some(signature) = imported_analyzer.function_table.get(name) {
+                    self.function_table
+                        .entry(name.clone())
+                        .or_insert_with(|| signature.clone());
                 }
             }
         }
+        for (name, members) in &imported_analyzer.overloads {
+            self.overloads
+                .entry(name.clone())
+                .or_insert_with(|| members.clone());
+        }
 
-        // TRANSITIVE IMPORTS: Import all transitive dependencies from the imported module
-        // This ensures that if module A imports from module B, and B imports from C,
-        // then A gets C's functions too (just like Rust and Go handle transitive imports)
-        for transitive_node in &imported_analyzer.imported_functions {
-            if let AstNode::FunctionDecl {
-                name: trans_name, ..
-            } = transitive_node
-            {
-                // Add to imported_functions if not already present
-                if !self.imported_functions.iter().any(|n| {
-                    if let AstNode::FunctionDecl { name: fn_name, .. } = n {
-                        fn_name == trans_name
-                    } else {
-                        false
-                    }
-                }) {
-                    self.imported_functions.push(transitive_node.clone());
+        // The module's own functions, by the name it declares them with; only
+        // those starting with an uppercase letter are public
+        let prefix = module_prefix(&qualified);
+        let own = |name: &String, target: &String| {
+            !name.contains("::") && (*target == module_symbol(&prefix, name) || target == name)
+        };
+        let is_public = |name: &str| name.starts_with(char::is_uppercase);
+
+        match symbol {
+            Some(sym) => {
+                let Some(target) = imported_analyzer
+                    .function_aliases
+                    .get(sym)
+                    .filter(|target| own(sym, target))
+                else {
+                    return Err(self.suggest(SemanticError::UndeclaredFunction(NamedError {
+                        name: sym.clone(),
+                    })));
+                };
+                if !is_public(sym) {
+                    return Err(SemanticError::PrivateFunction {
+                        name: sym.clone(),
+                        module: module_key,
+                    });
+                }
+                // Two functions can't be in scope under one name
+                let taken = match self.function_aliases.get(sym) {
+                    Some(existing) => existing != target,
+                    None => self.function_table.contains_key(sym),
+                };
+                if taken {
+                    return Err(SemanticError::FunctionRedeclaration(NamedError {
+                        name: sym.clone(),
+                    }));
                 }
-                // Add to function_table if not already present
-                if !self.function_table.contains_key(trans_name) {
-                    if let Some((params, ret)) = imported_analyzer.function_table.get(trans_name) {
-                        self.function_table
-                            .insert(trans_name.clone(), (params.clone(), ret.clone()));
+                self.function_aliases.insert(sym.clone(), target.clone());
+            }
+            None => {
+                // Called by the module's last name: `shapes::Area(..)`
+                let alias = module.last().expect("a module path is never empty");
+                for (name, target) in &imported_analyzer.function_aliases {
+                    if own(name, target) && is_public(name) {
+                        self.function_aliases
+                            .insert(format!("{}::{}", alias, name), target.clone());
                     }
                 }
             }
         }
+        Ok(())
+    }
+
+    /// Parses and analyzes the module `module`, read from `file_path`, the way
+    /// it is imported: its functions are named after it, and a dependency's
+    /// module imports from the dependency. Returns its analyzed statements and
+    /// the analyzer holding its tables.
+    fn analyze_module(
+        &self,
+        module: &[String],
+        file_path: &Path,
+        import_stack: &mut Vec<String>,
+    ) -> Result<(Vec<AstNode>, SemanticAnalyzer), SemanticError> {
+        let in_module = |error: String| SemanticError::ParseErrorInModule {
+            file: file_path.display().to_string(),
+            error,
+        };
+        let code = self
+            .read_module(file_path)
+            .map_err(|_| SemanticError::ModuleNotFound(file_path.display().to_string()))?;
+        let tokens = crate::lexar::lexer::try_lex(&code)
+            .map_err(|errors| in_module(errors[0].to_string()))?;
+        let mut parser = crate::parser::Parser::new(&tokens);
+        let mut nodes = match parser
+            .parse_program()
+            .map_err(|e| in_module(e.to_string()))?
+        {
+            AstNode::Program(nodes) => nodes,
+            node => vec![node],
+        };
 
-        // If a specific symbol was requested, verify it exists
-        if let Some(sym) = symbol {
-            // Check if the symbol exists in function_table or symbol_table
-            if !self.function_table.contains_key(sym) && !self.symbol_table.contains_key(sym) {
-                return Err(SemanticError::UndeclaredFunction(NamedError {
-                    name: sym.clone(),
-                }));
+        let (project_root, package) = match self.dependencies.get(&module[0]) {
+            Some(root) if module[0] != "std" => (
+                root.parent().map(Path::to_path_buf).unwrap_or_default(),
+                Some(module[0].clone()),
+            ),
+            _ => (self.project_root.clone(), self.package.clone()),
+        };
+        let mut analyzer = SemanticAnalyzer::new(Some(project_root));
+        analyzer.files = Rc::clone(&self.files);
+        analyzer.source_dir = file_path.parent().unwrap_or(&self.project_root).into();
+        analyzer.is_main_module = false;
+        analyzer.module_prefix = Some(module_prefix(module));
+        analyzer.dependencies = self.dependencies.clone();
+        analyzer.package = package;
+        // Pass the current import_stack so recursive imports are detected correctly
+        analyzer.analyze_program_with_stack(&mut nodes, import_stack)?;
+        Ok((nodes, analyzer))
+    }
+}
+
+/// Whether control never continues past `node`: a return, break, continue
+/// or `panic(..)` call.
+fn diverges(node: &AstNode) -> bool {
+    match node {
+        AstNode::Return { .. } | AstNode::Break { .. } | AstNode::Continue => true,
+        AstNode::FunctionCall { func, .. } => {
+            matches!(&**func, AstNode::Identifier(name, _) if name == "panic")
+        }
+        _ => false,
+    }
+}
+
+/// Adds the variable names `pattern` binds to `names`.
+pub(crate) fn pattern_names(pattern: &Pattern, names: &mut HashSet<String>) {
+    match pattern {
+        Pattern::Identifier(name) => {
+            names.insert(name.clone());
+        }
+        Pattern::Tuple(patterns) => {
+            for pattern in patterns {
+                pattern_names(pattern, names);
             }
         }
-        Ok(())
+        Pattern::Wildcard => {}
     }
 }
@@ -0,0 +1,310 @@
+use super::analyzer::{SemanticAnalyzer, SymbolInfo};
+use super::types::{NamedError, SemanticError, TypeMismatch};
+use crate::parser::ast::{AstNode, TypeNode};
+use std::collections::HashMap;
+
+/// Methods of array values: `arr.push(4)`, `arr.pop()`, `arr.insert(0, 4)`,
+/// `arr.remove(0)`, `arr.clear()` and `arr.reverse()`, plus `arr.sort()` on
+/// arrays of numbers. They all change the array in place, so the array must be
+/// held by a mutable variable other than a parameter. `pop` returns `T?`,
+/// Like `arr[i]`, `insert` and `remove` panic when their index is out of range.
+/// Like `arr[i]`, the index of `insert` and `remove` is not bounds checked.
+///
+/// Arrays of numbers also have `arr.sum()`, and `arr.min()` and `arr.max()`
+/// returning `T?` (absent when empty). These only read the array.
+///
+/// `arr.map(|x| x * 2)`, `arr.filter(|x| x > 1)` and `arr.reduce(0, |acc, x| acc + x)`
+/// take a closure and leave the array alone, so any array expression will do.
+/// The closure's parameters are the element (after the accumulator for
+/// `reduce`); `map` returns an array of the body's type, `filter` one of the
+/// elements its Bool body holds for, and `reduce` the accumulator.
+impl SemanticAnalyzer {
+    /// Parameter and return types of array method `method` on `[elem]`, and
+    /// whether it changes the array.
+    fn array_method_signature(
+        elem: &TypeNode,
+        method: &str,
+    ) -> Option<(Vec<TypeNode>, TypeNode, bool)> {
+        let numeric = matches!(elem, TypeNode::Int | TypeNode::Int64 | TypeNode::Float);
+        let elem = elem.clone();
+        match method {
+            "push" => Some((vec![elem], TypeNode::Void, true)),
+            "pop" => Some((vec![], TypeNode::Optional(Box::new(elem)), true)),
+            "insert" => Some((vec![TypeNode::Int, elem], TypeNode::Void, true)),
+            "remove" => Some((vec![TypeNode::Int], elem, true)),
+            "clear" | "reverse" => Some((vec![], TypeNode::Void, true)),
+            "sort" if numeric => Some((vec![], TypeNode::Void, true)),
+            "sum" if numeric => Some((vec![], elem, false)),
+            "min" | "max" if numeric => Some((vec![], TypeNode::Optional(Box::new(elem)), false)),
+            _ => None,
+        }
+    }
+
+    /// Type of `object.method(args)` on an array `[elem]`, once the receiver
+    /// suits the method and the arguments match it.
+    pub(crate) fn infer_array_method(
+        &self,
+        object: &AstNode,
+        elem: &TypeNode,
+        method: &str,
+        args: &[AstNode],
+    ) -> Result<TypeNode, SemanticError> {
+        if matches!(method, "map" | "filter" | "reduce") {
+            return self.infer_closure_method(elem, method, args);
+        }
+        let (params, ret_ty, in_place) =
+            Self::array_method_signature(elem, method).ok_or_else(|| {
+                SemanticError::UnknownMethod {
+                    found: TypeNode::Array(Box::new(elem.clone())),
+                    method: method.to_string(),
+                }
+            })?;
+
+        if in_place {
+            self.check_in_place_receiver(object, "array", method)?;
+        }
+        self.check_method_args(method, &params, args)?;
+        Ok(ret_ty)
+    }
+
+    /// Type of `map`, `filter` or `reduce` on an array `[elem]`, from the type
+    /// of its closure's body recorded by `analyze_closure_arg`.
+    fn infer_closure_method(
+        &self,
+        elem: &TypeNode,
+        method: &str,
+        args: &[AstNode],
+    ) -> Result<TypeNode, SemanticError> {
+        let expected = if method == "reduce" { 2 } else { 1 };
+        if args.len() != expected {
+            return Err(SemanticError::FunctionArgumentMismatch {
+                name: method.to_string(),
+                expected,
+                found: args.len(),
+            });
+        }
+        let Some(AstNode::Closure {
+            value_type: Some(body_type),
+            ..
+        }) = args.last()
+        else {
+            return Err(SemanticError::UnexpectedNode {
+                expected: format!("closure as the last argument of '{}'", method),
+            });
+        };
+
+        match method {
+            "map" if *body_type == TypeNode::Void => Err(SemanticError::UnexpectedNode {
+                expected: "closure of 'map' returning a value".to_string(),
+            }),
+            "map" => Ok(TypeNode::Array(Box::new(body_type.clone()))),
+            "filter" if *body_type != TypeNode::Bool => Err(SemanticError::InvalidConditionType(
+                Box::new(TypeMismatch {
+                    expected: TypeNode::Bool,
+                    found: body_type.clone(),
+                    value: None,
+                    span: None,
+                }),
+            )),
+            "filter" => Ok(TypeNode::Array(Box::new(elem.clone()))),
+            _ => {
+                // The body's value becomes the accumulator of the next element
+                let acc_type = self.infer_type(&args[0])?;
+                if *body_type != acc_type {
+                    return Err(SemanticError::ReturnTypeMismatch {
+                        function: "reduce closure".to_string(),
+                        mismatch: Box::new(TypeMismatch {
+                            expected: acc_type,
+                            found: body_type.clone(),
+                            value: None,
+                            span: None,
+                        }),
+                    });
+                }
+                Ok(acc_type)
+            }
+        }
+    }
+
+    /// Types the closure passed to `map`, `filter` or `reduce` on an array:
+    /// binds its parameters to the element type (the accumulator, typed by
+    /// the initial value, comes first for `reduce`), checks its body in a
+    /// scope of its own and records the body's type on the node.
+    pub(crate) fn analyze_closure_arg(
+        &mut self,
+        object: &mut AstNode,
+        method: &str,
+        args: &mut [AstNode],
+    ) -> Result<(), SemanticError> {
+        let Some((closure @ AstNode::Closure { .. }, rest)) = args.split_last_mut() else {
+            return Ok(());
+        };
+        // Closures passed to other methods are values
+        if !matches!(method, "map" | "filter" | "reduce") {
+            return self.analyze_closure_value(closure);
+        }
+        self.coerce_call_args(object)?;
+        // Closures elsewhere are reported when their call is typed
+        let TypeNode::Array(elem) = self.infer_type(object)? else {
+            return Ok(());
+        };
+        let param_types = match (method, rest) {
+            ("map" | "filter", []) => vec![*elem],
+            ("reduce", [init]) => {
+                self.coerce_call_args(init)?;
+                vec![self.infer_type(init)?, *elem]
+            }
+            _ => return Ok(()),
+        };
+
+        let AstNode::Closure {
+            params,
+            body,
+            value_type,
+            ..
+        } = closure
+        else {
+            return Ok(());
+        };
+        let closure_name = format!("{} closure", method);
+        if params.len() != param_types.len() {
+            return Err(SemanticError::FunctionArgumentMismatch {
+                name: closure_name,
+                expected: param_types.len(),
+                found: params.len(),
+            });
+        }
+
+        let parent_scope = self.symbol_table.clone();
+        self.scope_stack.push(HashMap::new());
+        let scope_size = self.symbol_table.len();
+        self.scope_sizes_stack.push(scope_size);
+
+        let result = self
+            .bind_closure_params(&closure_name, params, &param_types)
+            .and_then(|_| {
+                self.analyze_value_exprs(body)?;
+                self.coerce_call_args(body)?;
+                self.infer_type(body)
+            });
+
+        self.scope_stack.pop();
+        self.scope_sizes_stack.pop();
+        self.symbol_table = parent_scope;
+
+        *value_type = Some(result?);
+        Ok(())
+    }
+
+    /// Declares the parameters of a closure, filling in the types left out.
+    /// Like loop variables, they cannot shadow a variable in scope.
+    pub(crate) fn bind_closure_params(
+        &mut self,
+        closure_name: &str,
+        params: &mut [(String, Option<TypeNode>)],
+        param_types: &[TypeNode],
+    ) -> Result<(), SemanticError> {
+        for ((name, ty), expected) in params.iter_mut().zip(param_types) {
+            if let Some(declared) = ty {
+                let declared = self.resolve_type_refs(declared);
+                if declared != *expected {
+                    return Err(SemanticError::FunctionArgumentTypeMismatch {
+                        name: closure_name.to_string(),
+                        expected: Box::new(expected.clone()),
+                        found: declared,
+                    });
+                }
+            }
+            if self.symbol_table.contains_key(name) {
+                return Err(SemanticError::VariableRedeclaration(NamedError {
+                    name: name.clone(),
+                }));
+            }
+            *ty = Some(expected.clone());
+            self.symbol_table.insert(
+                name.clone(),
+                SymbolInfo {
+                    ty: expected.clone(),
+                    mutable: false,
+                    is_ref_counted: Self::should_be_rc(expected),
+                    is_parameter: false,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Methods changing a collection in place need a mutable variable holding it.
+    pub(crate) fn check_in_place_receiver(
+        &self,
+        object: &AstNode,
+        kind: &str,
+        method: &str,
+    ) -> Result<(), SemanticError> {
+        match object {
+            AstNode::Identifier(name, _) => match self.lookup_variable(name) {
+                Some(info) if !info.mutable => Err(SemanticError::InvalidAssignmentTarget {
+                    target: format!("Cannot {} immutable {} '{}'", method, kind, name),
+                }),
+                // The caller's variable would see the change (or a freed block)
+                Some(info) if info.is_parameter => Err(SemanticError::InvalidAssignmentTarget {
+                    target: format!(
+                        "Cannot {} parameter '{}'; copy it into a `let mut` variable first",
+                        method, name
+                    ),
+                }),
+                _ => Ok(()),
+            },
+            _ => Err(SemanticError::InvalidAssignmentTarget {
+                target: format!(
+                    "'{}' changes the {} in place and needs a variable holding it",
+                    method, kind
+                ),
+            }),
+        }
+    }
+
+    /// Checks the arguments of a built-in method against its parameters.
+    pub(crate) fn check_method_args(
+        &self,
+        method: &str,
+        params: &[TypeNode],
+        args: &[AstNode],
+    ) -> Result<(), SemanticError> {
+        if args.len() != params.len() {
+            return Err(SemanticError::FunctionArgumentMismatch {
+                name: method.to_string(),
+                expected: params.len(),
+                found: args.len(),
+            });
+        }
+        for (arg, param) in args.iter().zip(params) {
+            let found = self.infer_type(arg)?;
+            if !Self::args_coerce_to(std::slice::from_ref(param), std::slice::from_ref(&found)) {
+                return Err(SemanticError::FunctionArgumentTypeMismatch {
+                    name: method.to_string(),
+                    expected: Box::new(param.clone()),
+                    found,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Coerces the arguments of an array method call to its parameters
+    /// (`none` pushed onto a `[Int?]`, for example).
+    pub(crate) fn coerce_array_method(
+        &self,
+        elem: &TypeNode,
+        method: &str,
+        args: &mut [AstNode],
+    ) -> Result<(), SemanticError> {
+        let Some((params, _, _)) = Self::array_method_signature(elem, method) else {
+            return Ok(());
+        };
+        for (arg, param_ty) in args.iter_mut().zip(params.iter()) {
+            self.coerce_optional(param_ty, arg)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+use super::analyzer::SemanticAnalyzer;
+use super::types::SemanticError;
+use crate::parser::ast::{AstNode, TypeNode};
+
+/// Functions provided by the compiler rather than declared in source.
+/// Their names are reserved: declaring a function with one is a redeclaration.
+pub const BUILTIN_FUNCTIONS: &[&str] = &[
+    "toStr",
+    "toInt",
+    "toFloat",
+    "toInt64",
+    "format",
+    "parseInt",
+    "parseFloat",
+    "panic",
+    "assert",
+    "cStr",
+    "toCStr",
+    "fromCStr",
+    "takeCStr",
+    "includeStr",
+    "includeBytes",
+];
+
+/// Argument types accepted by each parameter of a builtin and the type it
+/// returns. The first accepted type is the one reported in mismatch errors.
+fn builtin_signature(name: &str) -> Option<(&'static [&'static [TypeNode]], TypeNode)> {
+    const TO_STR: &[&[TypeNode]] = &[&[
+        TypeNode::Int,
+        TypeNode::Int64,
+        TypeNode::Float,
+        TypeNode::Bool,
+        TypeNode::String,
+    ]];
+    const TO_INT: &[&[TypeNode]] = &[&[TypeNode::String, TypeNode::Float, TypeNode::Int]];
+    const TO_FLOAT: &[&[TypeNode]] = &[&[TypeNode::String, TypeNode::Int, TypeNode::Float]];
+    const TO_INT64: &[&[TypeNode]] = &[&[TypeNode::Int, TypeNode::Float, TypeNode::Int64]];
+    const FORMAT: &[&[TypeNode]] = &[&[TypeNode::Float], &[TypeNode::Int]];
+    const PARSE: &[&[TypeNode]] = &[&[TypeNode::String]];
+    const PANIC: &[&[TypeNode]] = &[&[TypeNode::String]];
+    const ASSERT: &[&[TypeNode]] = &[&[TypeNode::Bool], &[TypeNode::String]];
+    const TO_C: &[&[TypeNode]] = &[&[TypeNode::String]];
+    const FROM_C: &[&[TypeNode]] = &[&[TypeNode::CStr]];
+    const INCLUDE: &[&[TypeNode]] = &[&[TypeNode::String]];
+    match name {
+        "toStr" => Some((TO_STR, TypeNode::String)),
+        "toInt" => Some((TO_INT, TypeNode::Int)),
+        "toFloat" => Some((TO_FLOAT, TypeNode::Float)),
+        "toInt64" => Some((TO_INT64, TypeNode::Int64)),
+        "format" => Some((FORMAT, TypeNode::String)),
+        "parseInt" => Some((PARSE, TypeNode::Optional(Box::new(TypeNode::Int)))),
+        "parseFloat" => Some((PARSE, TypeNode::Optional(Box::new(TypeNode::Float)))),
+        // Stops the program, so a call can stand in for a value of any type
+        "panic" => Some((PANIC, TypeNode::Never)),
+        // Called for its effect only; release builds leave it out
+        "assert" => Some((ASSERT, TypeNode::Void)),
+        // The string's own bytes (borrowed), or a copy C code frees (owned)
+        "cStr" | "toCStr" => Some((TO_C, TypeNode::CStr)),
+        // A copy of a C string, absent for NULL; `takeCStr` also frees it
+        "fromCStr" | "takeCStr" => Some((FROM_C, TypeNode::Optional(Box::new(TypeNode::String)))),
+        // Replaced by the file's contents before its type is needed (see `expand_include`)
+        "includeStr" => Some((INCLUDE, TypeNode::String)),
+        "includeBytes" => Some((INCLUDE, TypeNode::Array(Box::new(TypeNode::Int)))),
+        _ => None,
+    }
+}
+
+impl SemanticAnalyzer {
+    /// Type checks a call to a builtin function.
+    /// Returns None when `name` is not a builtin, otherwise the call's
+    /// return type or the argument error.
+    pub(crate) fn check_builtin_call(
+        &self,
+        name: &str,
+        args: &[AstNode],
+    ) -> Option<Result<TypeNode, SemanticError>> {
+        let (params, ret_ty) = builtin_signature(name)?;
+        if args.len() != params.len() {
+            return Some(Err(SemanticError::FunctionArgumentMismatch {
+                name: name.to_string(),
+                expected: params.len(),
+                found: args.len(),
+            }));
+        }
+        for (arg, accepted) in args.iter().zip(params) {
+            let arg_ty = match self.infer_type(arg) {
+                Ok(ty) => ty,
+                Err(err) => return Some(Err(err)),
+            };
+            if !accepted.contains(&arg_ty) {
+                return Some(Err(SemanticError::FunctionArgumentTypeMismatch {
+                    name: name.to_string(),
+                    expected: Box::new(accepted[0].clone()),
+                    found: arg_ty,
+                }));
+            }
+        }
+        Some(Ok(ret_ty))
+    }
+}
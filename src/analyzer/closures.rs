@@ -0,0 +1,106 @@
+use super::analyzer::SemanticAnalyzer;
+use super::types::{NamedError, SemanticError};
+use crate::parser::ast::{AstNode, TypeNode};
+use std::collections::HashMap;
+
+/// Closures as values: `let add = |x: Int| x + n;` gives `add` the type
+/// `fn(Int) -> Int`, and `add(2)` calls it. Unlike the closures `map`, `filter`
+/// and `reduce` inline, a closure value can outlive the scope it is written
+/// in, so the variables of that scope its body reads (its captures) are copied
+/// into it when it is created. The body can read them but not assign them.
+/// Closure values are made inside functions, their parameters need types and
+/// their body can't be a tuple.
+impl SemanticAnalyzer {
+    /// Types a closure used as a value: binds its parameters, checks its body
+    /// in a scope of its own and records the body's type and the variables it
+    /// captures on the node.
+    pub(crate) fn analyze_closure_value(
+        &mut self,
+        closure: &mut AstNode,
+    ) -> Result<(), SemanticError> {
+        let AstNode::Closure {
+            params,
+            body,
+            value_type,
+            captures,
+        } = closure
+        else {
+            return Ok(());
+        };
+        if self.current_function.is_none() {
+            return Err(SemanticError::UnexpectedNode {
+                expected: "closure value inside a function".to_string(),
+            });
+        }
+        let mut param_types = Vec::new();
+        for (name, ty) in params.iter() {
+            let declared = ty.as_ref().ok_or_else(|| {
+                SemanticError::MissingParamType(NamedError { name: name.clone() })
+            })?;
+            param_types.push(self.resolve_type_refs(declared));
+        }
+
+        let parent_scope = self.symbol_table.clone();
+        for info in self.symbol_table.values_mut() {
+            info.mutable = false;
+        }
+        self.scope_stack.push(HashMap::new());
+        let scope_size = self.symbol_table.len();
+        self.scope_sizes_stack.push(scope_size);
+        // What the body reads is collected on its own, then counts as read here too
+        let enclosing_used = self.used_variables.take();
+
+        let result = self
+            .bind_closure_params("closure", params, &param_types)
+            .and_then(|_| {
+                self.analyze_value_exprs(body)?;
+                self.coerce_call_args(body)?;
+                self.infer_type(body)
+            });
+
+        let used = self.used_variables.replace(enclosing_used);
+        self.used_variables
+            .borrow_mut()
+            .extend(used.iter().cloned());
+        self.scope_stack.pop();
+        self.scope_sizes_stack.pop();
+        self.symbol_table = parent_scope;
+
+        let body_type = result?;
+        if matches!(body_type, TypeNode::Tuple(_)) {
+            return Err(SemanticError::UnexpectedNode {
+                expected: "closure returning a single value".to_string(),
+            });
+        }
+        let mut captured: Vec<(String, TypeNode)> = used
+            .into_iter()
+            .filter(|name| !params.iter().any(|(param, _)| param == name))
+            .filter_map(|name| {
+                let ty = self.symbol_table.get(&name)?.ty.clone();
+                Some((name, ty))
+            })
+            .collect();
+        captured.sort_by(|a, b| a.0.cmp(&b.0));
+        *captures = captured;
+        *value_type = Some(body_type);
+        Ok(())
+    }
+
+    /// Parameter and return types of the function value held by the variable
+    /// `name`, if it holds one. Reading it counts as a use of the variable.
+    pub(crate) fn closure_signature(&self, name: &str) -> Option<(Vec<TypeNode>, TypeNode)> {
+        let TypeNode::Function(params, ret) = &self.symbol_table.get(name)?.ty else {
+            return None;
+        };
+        let signature = (params.clone(), (**ret).clone());
+        self.lookup_variable(name);
+        Some(signature)
+    }
+
+    /// Parameter and return types of what `name(..)` calls: the closure a
+    /// variable of that name holds, or else the function of that name.
+    pub(crate) fn call_signature(&self, name: &str) -> Option<(Vec<TypeNode>, TypeNode)> {
+        self.closure_signature(name)
+            .or_else(|| self.function_table.get(name).cloned())
+    }
+}
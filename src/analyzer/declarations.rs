@@ -3,7 +3,7 @@ use std::collections::HashMap;
 
 use super::types::{NamedError, SemanticError, TypeMismatch};
 use crate::analyzer::analyzer::SymbolInfo;
-use crate::parser::ast::{AstNode, TypeNode};
+use crate::parser::ast::{AstNode, Span, TypeNode};
 
 impl SemanticAnalyzer {
     /// Analyze a variable declaration (`let` statement).
@@ -25,27 +25,67 @@ impl SemanticAnalyzer {
                 value,
                 is_ref_counted,
             } => {
+                if let Some(annotated_type) = type_annotation.as_mut() {
+                    *annotated_type = self.resolve_type_refs(annotated_type);
+                }
+
+                // An unsuffixed integer literal annotated as Int64 is widened in place,
+                // so `let x: Int64 = 5;` doesn't need the `L` suffix.
+                if let (Some(TypeNode::Int64), AstNode::NumberLiteral(n)) =
+                    (type_annotation.as_ref(), &**value)
+                {
+                    **value = AstNode::Int64Literal(*n as i64);
+                }
+
+                // Plain values and null flowing into an optional slot get wrapped
+                self.analyze_value_exprs(value)?;
+                self.coerce_call_args(value)?;
+                if let Some(annotated_type) = type_annotation.clone() {
+                    self.coerce_optional(&annotated_type, value)?;
+                }
+
                 // Use infer_rhs_types to ensure function call argument checks are performed
                 let rhs_types_vec = self.infer_rhs_types(value, 1)?;
-                let rhs_type = rhs_types_vec.get(0).cloned().ok_or_else(|| {
-                    SemanticError::VarTypeMismatch(TypeMismatch {
-                        expected: type_annotation.clone().unwrap_or(TypeNode::Int),
-                        found: TypeNode::Void,
-                        value: Some(value.clone()),
-                        line: None,
-                        col: None,
-                    })
-                })?;
+                let rhs_type = match rhs_types_vec.as_slice() {
+                    // Several values (a call returning a tuple) form one tuple
+                    [_, _, ..] => TypeNode::Tuple(rhs_types_vec.clone()),
+                    _ => rhs_types_vec.first().cloned().ok_or_else(|| {
+                        SemanticError::VarTypeMismatch(Box::new(TypeMismatch {
+                            expected: type_annotation.clone().unwrap_or(TypeNode::Int),
+                            found: TypeNode::Void,
+                            value: Some(value.clone()),
+                            span: None,
+                        }))
+                    })?,
+                };
+                // An empty `[]` or `{}` takes its element types from the annotation
+                let rhs_type = match (&**value, type_annotation.as_ref()) {
+                    (AstNode::ArrayLiteral(elements), Some(annotated @ TypeNode::Array(_)))
+                        if elements.is_empty() =>
+                    {
+                        annotated.clone()
+                    }
+                    (AstNode::MapLiteral(pairs), Some(annotated @ TypeNode::Map(..)))
+                        if pairs.is_empty() =>
+                    {
+                        annotated.clone()
+                    }
+                    _ => rhs_type,
+                };
+
+                // `let x = null;` gives no way to pick the payload type
+                if type_annotation.is_none() && Self::is_untyped_null(&rhs_type) {
+                    return Err(SemanticError::UntypedNull);
+                }
 
                 if let Some(annotated_type) = type_annotation.as_ref() {
                     if rhs_type != *annotated_type {
-                        return Err(SemanticError::VarTypeMismatch(TypeMismatch {
+                        return Err(SemanticError::VarTypeMismatch(Box::new(TypeMismatch {
                             expected: annotated_type.clone(),
                             found: rhs_type,
                             value: Some(value.clone()),
-                            line: None,
-                            col: None,
-                        }));
+                            span: None,
+                        })));
                     }
                 }
 
@@ -144,6 +184,7 @@ impl SemanticAnalyzer {
     /// - Creates a local scope for parameters and analyzes the function body in isolation.
     /// - If no return type is specified, marks as `Void` and ensures no return values are present.
     /// - Appends an implicit empty return if needed.
+    /// - Rejects an `#[arena]` function returning a value that owns memory.
     /// - Checks for required return statements and verifies their types.
     /// - Restores the outer symbol table after analysis.
     /// - Returns semantic errors for any violations.
@@ -154,14 +195,19 @@ impl SemanticAnalyzer {
         params: &mut Vec<(String, Option<TypeNode>)>,
         return_type: &mut Option<TypeNode>,
         body: &mut Vec<AstNode>,
+        body_spans: &[Span],
+        allow: &[String],
+        arena: bool,
     ) -> Result<(), SemanticError> {
         // Function signature is already registered in analyze_program's first pass
         // No need to check for redeclaration or add to function_table here
 
         // Is public or private function
-        // Enforce public function naming convention.
+        // Enforce public function naming convention, on the name it's
+        // declared with rather than its module-qualified one.
         if visibility == "Public" {
-            if let Some(first_char) = name.chars().next() {
+            let declared = name.rsplit_once('$').map_or(name, |(_, declared)| declared);
+            if let Some(first_char) = declared.chars().next() {
                 if !first_char.is_uppercase() {
                     return Err(SemanticError::InvalidPublicName(NamedError {
                         name: name.to_string(),
@@ -173,7 +219,13 @@ impl SemanticAnalyzer {
         // Create a local scope for function parameters.
         let mut local_scope: HashMap<String, SymbolInfo> = HashMap::new();
 
-        for (param_name, param_type) in params.iter() {
+        for (param_name, param_type) in params.iter_mut() {
+            // Inside the body a variadic `T...` parameter is the array of its arguments;
+            // callers still see `T...` through the function table.
+            if let Some(TypeNode::Variadic(elem_type)) = param_type {
+                *param_type = Some(TypeNode::Array(elem_type.clone()));
+            }
+
             // Type is mandatory for parameters. Check type exists.
             let param_type = param_type.as_ref().ok_or_else(|| {
                 SemanticError::MissingParamType(NamedError {
@@ -223,35 +275,96 @@ impl SemanticAnalyzer {
             }
         }
 
-        // Save outer symbol table and switch to local scope for function analysis.
-        let outer_symbol_table = Some(self.symbol_table.clone());
-        self.outer_symbol_table = outer_symbol_table;
-        self.symbol_table = local_scope; // only params visible
+        // What an arena function allocates is gone once it returns; only values
+        // that own no memory can leave it
+        if arena {
+            fn owns_nothing(ty: &TypeNode) -> bool {
+                match ty {
+                    TypeNode::Int | TypeNode::Int64 | TypeNode::Float | TypeNode::Bool => true,
+                    TypeNode::Void => true,
+                    TypeNode::Tuple(items) => items.iter().all(owns_nothing),
+                    _ => false,
+                }
+            }
+            if let Some(ret_type) = return_type.as_ref().filter(|ty| !owns_nothing(ty)) {
+                return Err(SemanticError::ArenaReturnType {
+                    function: name.to_string(),
+                    found: ret_type.clone(),
+                });
+            }
+        }
+
+        // Nested function declarations become `outer.inner`, callable only from this body.
+        let nested_functions = Self::qualify_nested_functions(name, body);
 
         // Check for required return statements (but don't verify types yet - need body analyzed first).
-        if let Some(ret_type) = return_type.as_ref() {
-            if *ret_type != TypeNode::Void {
-                self.ensure_has_return(body, name)?;
+        // A missing return is reported after the body, whose errors are wanted too.
+        let missing_return = match return_type.as_ref() {
+            Some(ret_type) if *ret_type != TypeNode::Void => {
+                self.ensure_has_return(body, name).err()
             }
-        }
+            _ => None,
+        };
+
+        // Save outer symbol table and switch to local scope for function analysis.
+        // When this function is nested, the enclosing function's own outer table
+        // and block scopes are set aside until its analysis resumes.
+        let enclosing_outer = self.outer_symbol_table.replace(self.symbol_table.clone());
+        let enclosing_scopes = std::mem::take(&mut self.scope_stack);
+        let enclosing_scope_sizes = std::mem::take(&mut self.scope_sizes_stack);
+        let enclosing_loop_depth = std::mem::take(&mut self.loop_depth);
+        self.symbol_table = local_scope; // only params visible
 
         self.function_depth += 1;
+        // Returns inside the body coerce their values against the declared type
+        let outer_function = self.current_function.replace((
+            name.to_string(),
+            return_type.clone().unwrap_or(TypeNode::Void),
+        ));
         // Analyze function body with isolated scope.
-        self.analyze_program(body)?;
+        // Its variables are checked for use on their own, as are a nested function's
+        self.local_functions.push(nested_functions);
+        self.statement_spans = body_spans.to_vec();
+        let enclosing_allowed = self.allowed_lints.len();
+        self.allowed_lints.extend(allow.iter().cloned());
+        let enclosing_declared = std::mem::take(&mut self.declared_variables);
+        let enclosing_used = self.used_variables.take();
+        let enclosing_span = self.statement_span;
+        let body_result = self.analyze_program(body);
+        let declared = std::mem::replace(&mut self.declared_variables, enclosing_declared);
+        let used = self.used_variables.replace(enclosing_used);
+        self.warn_unused_variables(declared, &used);
+        self.statement_span = enclosing_span;
+        self.allowed_lints.truncate(enclosing_allowed);
+        self.local_functions.pop();
+        self.current_function = outer_function;
+        self.scope_stack = enclosing_scopes;
+        self.scope_sizes_stack = enclosing_scope_sizes;
+        self.loop_depth = enclosing_loop_depth;
 
         // Now verify return types after body has been analyzed and local variables are in scope.
-        if let Some(ret_type) = return_type.as_ref() {
-            if *ret_type != TypeNode::Void {
-                self.verify_return_types(body, ret_type, name)?;
+        let result = body_result.and_then(|()| match return_type.as_ref() {
+            Some(ret_type) if *ret_type != TypeNode::Void => {
+                self.verify_return_types(body, ret_type, name)
             }
-        }
+            _ => Ok(()),
+        });
 
-        // Restore outer scope after function analysis.
-        if let Some(outer) = self.outer_symbol_table.take() {
-        self.function_depth -= 1;
+        // Restore outer scope after function analysis, whether or not it succeeded,
+        // so the functions after this one are analyzed in the right scope.
+        if let Some(outer) = std::mem::replace(&mut self.outer_symbol_table, enclosing_outer) {
+            self.function_depth -= 1;
             self.symbol_table = outer;
         }
 
+        if let Some(missing_return) = missing_return {
+            if let Err(e) = result {
+                self.collected_errors.push(e);
+            }
+            return Err(missing_return);
+        }
+        result?;
+
         // println!(
         //     "{} {:?} {:?} {:?} {:?}",
         //     name, visibility, params, return_type, body
@@ -260,6 +373,36 @@ impl SemanticAnalyzer {
         Ok(())
     }
 
+    /// Renames the function declarations directly in `body` to `outer.inner` and
+    /// returns the declared name -> qualified name map for calls inside the body.
+    /// `.` can't appear in identifiers, so the hoisted functions can't be named elsewhere.
+    fn qualify_nested_functions(outer: &str, body: &mut [AstNode]) -> HashMap<String, String> {
+        let mut nested = HashMap::new();
+        for stmt in body.iter_mut() {
+            if let AstNode::FunctionDecl { name, .. } = stmt {
+                let qualified = format!("{}.{}", outer, name);
+                nested.insert(name.clone(), qualified.clone());
+                *name = qualified;
+            }
+        }
+        nested
+    }
+
+    /// Points a call to a nested function at its qualified name, searching the
+    /// innermost enclosing body first.
+    pub(crate) fn resolve_nested_call(&self, func: &mut AstNode) {
+        if let AstNode::Identifier(name, _) = func {
+            if let Some(qualified) = self
+                .local_functions
+                .iter()
+                .rev()
+                .find_map(|nested| nested.get(name.as_str()))
+            {
+                *name = qualified.clone();
+            }
+        }
+    }
+
     /// Ensure function has at least one return statement
     /// Ensures that a function body contains at least one return statement.
     ///
@@ -281,6 +424,10 @@ impl SemanticAnalyzer {
         for node in nodes {
             match node {
                 AstNode::Return { .. } => return true,
+                // `panic(..)` never returns, so nothing has to follow it
+                AstNode::FunctionCall { func, .. } if matches!(&**func, AstNode::Identifier(name, _) if name == "panic") => {
+                    return true
+                }
                 AstNode::ConditionalStmt {
                     then_block,
                     else_branch,
@@ -296,11 +443,35 @@ impl SemanticAnalyzer {
                         return true;
                     }
                 }
+                AstNode::IfLet {
+                    then_block,
+                    else_branch,
+                    ..
+                } => {
+                    let then_has = self.has_return_statement(then_block);
+                    let else_has = else_branch
+                        .as_ref()
+                        .map(|b| self.has_return_statement(&vec![*b.clone()]))
+                        .unwrap_or(false);
+                    if then_has && else_has {
+                        return true;
+                    }
+                }
                 AstNode::Block(inner_nodes) => {
                     if self.has_return_statement(inner_nodes) {
                         return true;
                     }
                 }
+                AstNode::Match { arms, .. } => {
+                    // Matches are exhaustive, so every arm returning is enough
+                    let all_return = arms.iter().all(|arm| match &*arm.body {
+                        AstNode::Block(body) => self.has_return_statement(body),
+                        _ => false,
+                    });
+                    if all_return {
+                        return true;
+                    }
+                }
                 _ => {}
             }
         }
@@ -343,6 +514,17 @@ impl SemanticAnalyzer {
                 AstNode::Block(inner_nodes) => {
                     self.verify_return_types(inner_nodes, expected, fn_name)?;
                 }
+                AstNode::Match { arms, .. } => {
+                    for arm in arms {
+                        // Arms binding a payload were checked while the binding was in scope
+                        if self.arm_payload_binding(arm).is_some() {
+                            continue;
+                        }
+                        if let AstNode::Block(body) = &*arm.body {
+                            self.verify_return_types(body, expected, fn_name)?;
+                        }
+                    }
+                }
                 _ => {}
             }
         }
@@ -352,7 +534,7 @@ impl SemanticAnalyzer {
     /// Verifies a single return statement matches the expected type.
     /// Handles both tuple and single-value returns. Returns an error if the number of returned
     /// values or their types do not match the function's declared return type.
-    fn verify_single_return(
+    pub(crate) fn verify_single_return(
         &self,
         values: &Vec<AstNode>,
         expected: &TypeNode,
@@ -364,7 +546,7 @@ impl SemanticAnalyzer {
                 if values.len() != expected_vec.len() {
                     return Err(SemanticError::ReturnTypeMismatch {
                         function: fn_name.to_string(),
-                        mismatch: TypeMismatch {
+                        mismatch: Box::new(TypeMismatch {
                             expected: expected.clone(),
                             found: TypeNode::Tuple(
                                 values
@@ -373,9 +555,8 @@ impl SemanticAnalyzer {
                                     .collect::<Result<Vec<_>, _>>()?,
                             ),
                             value: None,
-                            line: None,
-                            col: None,
-                        },
+                            span: None,
+                        }),
                     });
                 }
                 for (value, expected_type) in values.iter().zip(expected_vec.iter()) {
@@ -383,13 +564,12 @@ impl SemanticAnalyzer {
                     if &value_type != expected_type {
                         return Err(SemanticError::ReturnTypeMismatch {
                             function: fn_name.to_string(),
-                            mismatch: TypeMismatch {
+                            mismatch: Box::new(TypeMismatch {
                                 expected: expected_type.clone(),
                                 found: value_type,
                                 value: None,
-                                line: None,
-                                col: None,
-                            },
+                                span: None,
+                            }),
                         });
                     }
                 }
@@ -400,7 +580,7 @@ impl SemanticAnalyzer {
                 if values.len() != 1 {
                     return Err(SemanticError::ReturnTypeMismatch {
                         function: fn_name.to_string(),
-                        mismatch: TypeMismatch {
+                        mismatch: Box::new(TypeMismatch {
                             expected: expected.clone(),
                             found: TypeNode::Tuple(
                                 values
@@ -409,22 +589,20 @@ impl SemanticAnalyzer {
                                     .collect::<Result<Vec<_>, _>>()?,
                             ),
                             value: None,
-                            line: None,
-                            col: None,
-                        },
+                            span: None,
+                        }),
                     });
                 }
                 let value_type = self.infer_type(&values[0])?;
                 if &value_type != expected {
                     return Err(SemanticError::ReturnTypeMismatch {
                         function: fn_name.to_string(),
-                        mismatch: TypeMismatch {
+                        mismatch: Box::new(TypeMismatch {
                             expected: expected.clone(),
                             found: value_type,
                             value: None,
-                            line: None,
-                            col: None,
-                        },
+                            span: None,
+                        }),
                     });
                 }
             }
@@ -436,7 +614,12 @@ impl SemanticAnalyzer {
     /// ensures no duplicate fields, and adds the struct type to the symbol table.
     /// Returns semantic errors for any violations.
     pub fn analyze_struct(&mut self, node: &AstNode) -> Result<(), SemanticError> {
-        if let AstNode::StructDecl { name, fields } = node {
+        if let AstNode::StructDecl {
+            name,
+            fields,
+            weak_fields,
+        } = node
+        {
             // Prevent redeclaration of struct names.
             if self.symbol_table.contains_key(name) {
                 return Err(SemanticError::StructRedeclaration(NamedError {
@@ -453,6 +636,31 @@ impl SemanticAnalyzer {
                         field: field_name.clone(),
                     });
                 }
+                // Struct-typed fields must name a declared struct or enum.
+                if let TypeNode::TypeRef(type_name) = field_type {
+                    if !self.struct_table.contains_key(type_name)
+                        && !self.enum_table.contains_key(type_name)
+                    {
+                        return Err(self.suggest(SemanticError::UndeclaredStruct(NamedError {
+                            name: type_name.clone(),
+                        })));
+                    }
+                }
+                // Weak fields become null when their value is freed
+                if weak_fields.contains(field_name) {
+                    let is_struct = matches!(
+                        field_type,
+                        TypeNode::Optional(inner)
+                            if matches!(inner.as_ref(), TypeNode::TypeRef(n) if self.struct_table.contains_key(n))
+                    );
+                    if !is_struct {
+                        return Err(SemanticError::WeakFieldType {
+                            struct_name: name.clone(),
+                            field: field_name.clone(),
+                            found: Box::new(field_type.clone()),
+                        });
+                    }
+                }
                 field_map.insert(field_name.clone(), field_type.clone());
             }
 
@@ -492,6 +700,16 @@ impl SemanticAnalyzer {
                         variant: variant_name.clone(),
                     });
                 }
+                // Payloads named by type must be a declared struct or enum.
+                if let Some(TypeNode::TypeRef(type_name)) = variant_type {
+                    if !self.struct_table.contains_key(type_name)
+                        && !self.enum_table.contains_key(type_name)
+                    {
+                        return Err(self.suggest(SemanticError::UndeclaredStruct(NamedError {
+                            name: type_name.clone(),
+                        })));
+                    }
+                }
                 variant_map.insert(variant_name.clone(), variant_type.clone());
             }
 
@@ -0,0 +1,207 @@
+use super::analyzer::SemanticAnalyzer;
+use super::types::{NamedError, SemanticError, TypeMismatch};
+use crate::parser::ast::{AstNode, MatchArm, MatchPattern, TypeNode};
+
+/// Enum values: `Color::Red` and `Shape::Circle(2.0)`, taken apart with
+/// `match s { Shape::Circle(r) => ..., Shape::Square(_) => ... }`.
+/// An enum name used as a type (`TypeRef("Shape")`) is resolved to
+/// `TypeNode::Enum` carrying the declared variants; struct payloads stay
+/// `TypeRef`s there and are resolved when bound.
+impl SemanticAnalyzer {
+    /// Records the variants of every enum declared in `nodes`, so enum names
+    /// can appear in signatures and values before their declaration.
+    pub(crate) fn register_enums(&mut self, nodes: &[AstNode]) {
+        for node in nodes {
+            if let AstNode::EnumDecl { name, variants } = node {
+                self.enum_table
+                    .entry(name.clone())
+                    .or_insert_with(|| variants.clone());
+            }
+        }
+    }
+
+    /// The type of values of enum `name`, or None if no such enum is declared.
+    pub(crate) fn enum_type(&self, name: &str) -> Option<TypeNode> {
+        let variants = self.enum_table.get(name)?;
+        Some(TypeNode::Enum(
+            name.to_string(),
+            variants.iter().cloned().collect(),
+        ))
+    }
+
+    /// Payload type of `enum_name::variant` (None for unit variants).
+    pub(crate) fn variant_payload(
+        &self,
+        enum_name: &str,
+        variant: &str,
+    ) -> Result<Option<TypeNode>, SemanticError> {
+        let variants = self.enum_table.get(enum_name).ok_or_else(|| {
+            self.suggest(SemanticError::UndeclaredEnum(NamedError {
+                name: enum_name.to_string(),
+            }))
+        })?;
+        let (_, payload) = variants
+            .iter()
+            .find(|(name, _)| name == variant)
+            .ok_or_else(|| SemanticError::UnknownVariant {
+                enum_name: enum_name.to_string(),
+                variant: variant.to_string(),
+            })?;
+        Ok(payload.as_ref().map(|ty| self.resolve_type_refs(ty)))
+    }
+
+    /// Checks `Enum::Variant` or `Enum::Variant(payload)`: the variant exists
+    /// and the payload is given exactly when the variant declares one, with its type.
+    pub(crate) fn infer_enum_value(
+        &self,
+        enum_name: &str,
+        variant: &str,
+        payload: Option<&AstNode>,
+    ) -> Result<TypeNode, SemanticError> {
+        let expected = self.variant_payload(enum_name, variant)?;
+        match (expected, payload) {
+            (None, None) => {}
+            (Some(expected), Some(value)) => {
+                let found = self.infer_type(value)?;
+                if found != expected {
+                    return Err(SemanticError::VarTypeMismatch(Box::new(TypeMismatch {
+                        expected,
+                        found,
+                        value: Some(Box::new(value.clone())),
+                        span: None,
+                    })));
+                }
+            }
+            (expected, _) => {
+                return Err(SemanticError::VariantPayloadMismatch {
+                    enum_name: enum_name.to_string(),
+                    variant: variant.to_string(),
+                    expected: expected.map(Box::new),
+                });
+            }
+        }
+        Ok(self
+            .enum_type(enum_name)
+            .expect("variant_payload found the enum"))
+    }
+
+    /// Wraps a payload that goes into an optional payload slot.
+    pub(crate) fn coerce_enum_payload(
+        &self,
+        enum_name: &str,
+        variant: &str,
+        payload: &mut AstNode,
+    ) -> Result<(), SemanticError> {
+        self.coerce_call_args(payload)?;
+        if let Ok(Some(expected)) = self.variant_payload(enum_name, variant) {
+            self.coerce_optional(&expected, payload)?;
+        }
+        Ok(())
+    }
+
+    /// Checks the patterns of a match on a value of enum `enum_name`.
+    /// - Every pattern must be a variant of that enum (or `_`), and a payload
+    ///   binding needs a variant with a payload and an arm with a single pattern.
+    /// - An arm is unreachable after a `_` arm, or when all of its variants
+    ///   already appeared in earlier arms.
+    /// - The arms must cover every variant, or end with `_`.
+    pub(crate) fn check_enum_match_arms(
+        &self,
+        enum_name: &str,
+        value_type: &TypeNode,
+        arms: &[MatchArm],
+    ) -> Result<(), SemanticError> {
+        let variants = &self.enum_table[enum_name];
+        let mut seen: Vec<&str> = Vec::new();
+        let mut has_wildcard = false;
+
+        for (index, arm) in arms.iter().enumerate() {
+            let covered_before = has_wildcard || seen.len() == variants.len();
+            let mut adds_values = false;
+            for pattern in &arm.patterns {
+                match pattern {
+                    MatchPattern::Wildcard => {
+                        has_wildcard = true;
+                        adds_values = true;
+                    }
+                    MatchPattern::Literal(literal) => {
+                        return Err(SemanticError::MatchPatternTypeMismatch(Box::new(
+                            TypeMismatch {
+                                expected: value_type.clone(),
+                                found: self.infer_type(literal)?,
+                                value: Some(Box::new(literal.clone())),
+                                span: None,
+                            },
+                        )));
+                    }
+                    MatchPattern::Variant {
+                        enum_name: pattern_enum,
+                        variant,
+                        binding,
+                    } => {
+                        if pattern_enum != enum_name {
+                            return Err(SemanticError::MatchPatternTypeMismatch(Box::new(
+                                TypeMismatch {
+                                    expected: value_type.clone(),
+                                    found: self
+                                        .enum_type(pattern_enum)
+                                        .unwrap_or_else(|| TypeNode::TypeRef(pattern_enum.clone())),
+                                    value: None,
+                                    span: None,
+                                },
+                            )));
+                        }
+                        let payload = self.variant_payload(enum_name, variant)?;
+                        if binding.is_some() {
+                            if payload.is_none() {
+                                return Err(SemanticError::VariantPayloadMismatch {
+                                    enum_name: enum_name.to_string(),
+                                    variant: variant.clone(),
+                                    expected: None,
+                                });
+                            }
+                            if arm.patterns.len() > 1 {
+                                return Err(SemanticError::UnexpectedNode {
+                                    expected: "a single pattern in an arm binding a payload"
+                                        .to_string(),
+                                });
+                            }
+                        }
+                        if !seen.contains(&variant.as_str()) {
+                            seen.push(variant);
+                            adds_values = true;
+                        }
+                    }
+                }
+            }
+            if covered_before || !adds_values {
+                return Err(SemanticError::UnreachableMatchArm { arm: index + 1 });
+            }
+        }
+
+        if !has_wildcard {
+            if let Some((missing, _)) = variants.iter().find(|(v, _)| !seen.contains(&v.as_str())) {
+                return Err(SemanticError::NonExhaustiveMatch {
+                    missing: format!("{}::{}", enum_name, missing),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// The payload binding of a match arm (`Shape::Circle(r)`) with its type.
+    /// check_enum_match_arms has made sure the variant carries a payload.
+    pub(crate) fn arm_payload_binding(&self, arm: &MatchArm) -> Option<(String, TypeNode)> {
+        match arm.patterns.as_slice() {
+            [MatchPattern::Variant {
+                enum_name,
+                variant,
+                binding: Some(binding),
+            }] => {
+                let payload = self.variant_payload(enum_name, variant).ok().flatten()?;
+                Some((binding.clone(), payload))
+            }
+            _ => None,
+        }
+    }
+}
@@ -3,25 +3,24 @@ use super::types::{NamedError, SemanticError, TypeMismatch};
 use crate::lexar::token::TokenType;
 use crate::parser::ast::{AstNode, TypeNode};
 
-/// Helper to extract line/col from an AstNode
-/// For now, returns None since parser hasn't been updated yet
-fn get_node_location(_node: &AstNode) -> (Option<usize>, Option<usize>) {
-    // TODO: Once parser is updated to include line/col in AST nodes,
-    // implement proper extraction here
-    (None, None)
-}
-
 impl SemanticAnalyzer {
     /// Infers the type of an AST node (expression).
     /// This is the core type inference function for all expressions in the language.
     /// - Returns the type of literals directly.
     /// - Looks up identifiers in the symbol table.
     /// - Checks types for binary/unary expressions, function calls, arrays, maps, etc.
-    /// - Returns errors for undeclared variables, type mismatches, or invalid operations.
+    /// - Returns errors for undeclared variables, type mismatches, or invalid operations,
+    ///   located at the innermost expression with a span.
     pub fn infer_type(&self, node: &AstNode) -> Result<TypeNode, SemanticError> {
+        self.infer_node_type(node).map_err(|e| e.at_node(node))
+    }
+
+    fn infer_node_type(&self, node: &AstNode) -> Result<TypeNode, SemanticError> {
         match node {
             // Integer literal: always Int type
             AstNode::NumberLiteral(_) => Ok(TypeNode::Int),
+            // Suffixed integer literal (5L): always Int64 type
+            AstNode::Int64Literal(_) => Ok(TypeNode::Int64),
             // Float literal: always Float type
             AstNode::FloatLiteral(_) => Ok(TypeNode::Float),
             // String literal: always String type
@@ -36,36 +35,70 @@ impl SemanticAnalyzer {
             }
             // Boolean literal: always Bool type
             AstNode::BoolLiteral(_) => Ok(TypeNode::Bool),
+            // null: an optional whose payload type comes from the context
+            AstNode::NullLiteral => Ok(TypeNode::Optional(Box::new(TypeNode::Void))),
+            // Already coerced into an optional slot
+            AstNode::OptionalWrap { ty, .. } => Ok(ty.clone()),
 
             // Identifier (variable name): look up in symbol table (with shadowing support)
-            AstNode::Identifier(name) => {
+            AstNode::Identifier(name, _) => {
                 if let Some(info) = self.lookup_variable(name) {
                     Ok(info.ty.clone())
                 } else if let Some(outer) = &self.outer_symbol_table {
+                    // Nested functions don't capture: the enclosing function's locals
+                    // are the outer table while one is analyzed
+                    if outer.contains_key(name) && self.function_depth > 1 {
+                        return Err(SemanticError::CapturedOuterVariable {
+                            name: name.clone(),
+                            function: self
+                                .current_function
+                                .as_ref()
+                                .map(|(f, _)| f.clone())
+                                .unwrap_or_default(),
+                        });
+                    }
                     if outer.contains_key(name) {
                         return Err(SemanticError::OutOfScopeVariable(NamedError {
                             name: name.clone(),
                         }));
                     }
-                    Err(SemanticError::UndeclaredVariable(NamedError {
+                    Err(self.suggest(SemanticError::UndeclaredVariable(NamedError {
                         name: name.clone(),
-                    }))
+                    })))
                 } else {
-                    Err(SemanticError::UndeclaredVariable(NamedError {
+                    Err(self.suggest(SemanticError::UndeclaredVariable(NamedError {
                         name: name.clone(),
-                    }))
+                    })))
                 }
             }
 
             // Binary expressions (e.g., arithmetic, comparison, logical, range)
             // Ex., let is_equal = x == y;
             // TODO: check llvm handled for this or not
-            AstNode::BinaryExpr { left, op, right } => {
+            AstNode::BinaryExpr {
+                left, op, right, ..
+            } => {
                 // Infer types of both sides
                 let left_type = self.infer_type(left)?;
                 let right_type = self.infer_type(right)?;
 
                 match op {
+                    // Null coalescing: `opt ?? fallback` unwraps T? to T
+                    TokenType::QuestionQuestion => match &left_type {
+                        TypeNode::Optional(inner) if **inner == right_type => Ok(right_type),
+                        _ => {
+                            let span = left.span().or(node.span());
+                            Err(SemanticError::OperatorTypeMismatch(Box::new(
+                                TypeMismatch {
+                                    expected: TypeNode::Optional(Box::new(right_type)),
+                                    found: left_type,
+                                    value: None,
+                                    span,
+                                },
+                            )))
+                        }
+                    },
+
                     // Comparison operators (==, !=, >, <, etc.)
                     TokenType::EqEq
                     | TokenType::EqEqEq
@@ -77,14 +110,15 @@ impl SemanticAnalyzer {
                     | TokenType::LtEq => {
                         // Both sides must be the same type
                         if left_type != right_type {
-                            let (line, col) = get_node_location(node);
-                            return Err(SemanticError::OperatorTypeMismatch(TypeMismatch {
-                                expected: left_type,
-                                found: right_type,
-                                value: None,
-                                line,
-                                col,
-                            }));
+                            let span = right.span().or(node.span());
+                            return Err(SemanticError::OperatorTypeMismatch(Box::new(
+                                TypeMismatch {
+                                    expected: left_type,
+                                    found: right_type,
+                                    value: None,
+                                    span,
+                                },
+                            )));
                         }
                         // Comparison always returns Bool
                         Ok(TypeNode::Bool)
@@ -96,18 +130,20 @@ impl SemanticAnalyzer {
                     TokenType::RangeExc | TokenType::RangeInc => {
                         // Both start and end must be Int
                         if left_type != TypeNode::Int || right_type != TypeNode::Int {
-                            let (line, col) = get_node_location(node);
-                            return Err(SemanticError::OperatorTypeMismatch(TypeMismatch {
-                                expected: TypeNode::Int,
-                                found: if left_type != TypeNode::Int {
-                                    left_type
-                                } else {
-                                    right_type
+                            let (operand, found) = if left_type != TypeNode::Int {
+                                (left, left_type)
+                            } else {
+                                (right, right_type)
+                            };
+                            let span = operand.span().or(node.span());
+                            return Err(SemanticError::OperatorTypeMismatch(Box::new(
+                                TypeMismatch {
+                                    expected: TypeNode::Int,
+                                    found,
+                                    value: None,
+                                    span,
                                 },
-                                value: None,
-                                line,
-                                col,
-                            }));
+                            )));
                         }
                         // Determine if range is inclusive or exclusive
                         let inclusive = matches!(op, TokenType::RangeInc);
@@ -126,18 +162,20 @@ impl SemanticAnalyzer {
                     TokenType::AndAnd | TokenType::OrOr => {
                         // Both sides must be Bool
                         if left_type != TypeNode::Bool || right_type != TypeNode::Bool {
-                            let (line, col) = get_node_location(node);
-                            return Err(SemanticError::OperatorTypeMismatch(TypeMismatch {
-                                expected: TypeNode::Bool,
-                                found: if left_type != TypeNode::Bool {
-                                    left_type
-                                } else {
-                                    right_type
+                            let (operand, found) = if left_type != TypeNode::Bool {
+                                (left, left_type)
+                            } else {
+                                (right, right_type)
+                            };
+                            let span = operand.span().or(node.span());
+                            return Err(SemanticError::OperatorTypeMismatch(Box::new(
+                                TypeMismatch {
+                                    expected: TypeNode::Bool,
+                                    found,
+                                    value: None,
+                                    span,
                                 },
-                                value: None,
-                                line,
-                                col,
-                            }));
+                            )));
                         }
                         Ok(TypeNode::Bool)
                     }
@@ -153,20 +191,28 @@ impl SemanticAnalyzer {
                     | TokenType::Percent => match (left_type.clone(), right_type.clone()) {
                         // both lhs and rhs should match type
                         (TypeNode::Int, TypeNode::Int) => Ok(TypeNode::Int),
+                        (TypeNode::Int64, TypeNode::Int64) => Ok(TypeNode::Int64),
                         // String concatenation
                         (TypeNode::String, TypeNode::String) => Ok(TypeNode::String),
+                        // Array concatenation: `[1, 2] + [3]` builds a new array
+                        (TypeNode::Array(l), TypeNode::Array(r))
+                            if *op == TokenType::Plus && l == r =>
+                        {
+                            Ok(TypeNode::Array(l))
+                        }
                         // Float arithmetic (if supported)
                         (TypeNode::Float, TypeNode::Float) => Ok(TypeNode::Float),
                         // Any other type combination is invalid
                         _ => {
-                            let (line, col) = get_node_location(node);
-                            Err(SemanticError::OperatorTypeMismatch(TypeMismatch {
-                                expected: left_type,
-                                found: right_type,
-                                value: None,
-                                line,
-                                col,
-                            }))
+                            let span = right.span().or(node.span());
+                            Err(SemanticError::OperatorTypeMismatch(Box::new(
+                                TypeMismatch {
+                                    expected: left_type,
+                                    found: right_type,
+                                    value: None,
+                                    span,
+                                },
+                            )))
                         }
                     },
 
@@ -179,34 +225,36 @@ impl SemanticAnalyzer {
             // Ex., let neg = -x;
             // Ex., let not = !flag;
             // TODO: check llvm handled for this or not
-            AstNode::UnaryExpr { op, expr } => {
+            AstNode::UnaryExpr { op, expr, .. } => {
                 let expr_type = self.infer_type(expr)?;
                 match op {
                     TokenType::Minus => match expr_type {
-                        TypeNode::Int | TypeNode::Float => Ok(expr_type),
+                        TypeNode::Int | TypeNode::Int64 | TypeNode::Float => Ok(expr_type),
                         _ => {
-                            let (line, col) = get_node_location(expr);
-                            Err(SemanticError::OperatorTypeMismatch(TypeMismatch {
-                                expected: TypeNode::Int,
-                                found: expr_type,
-                                value: None,
-                                line,
-                                col,
-                            }))
+                            let span = expr.span();
+                            Err(SemanticError::OperatorTypeMismatch(Box::new(
+                                TypeMismatch {
+                                    expected: TypeNode::Int,
+                                    found: expr_type,
+                                    value: None,
+                                    span,
+                                },
+                            )))
                         }
                     },
                     TokenType::Bang => {
                         if expr_type == TypeNode::Bool {
                             Ok(TypeNode::Bool)
                         } else {
-                            let (line, col) = get_node_location(expr);
-                            Err(SemanticError::OperatorTypeMismatch(TypeMismatch {
-                                expected: TypeNode::Bool,
-                                found: expr_type,
-                                value: None,
-                                line,
-                                col,
-                            }))
+                            let span = expr.span();
+                            Err(SemanticError::OperatorTypeMismatch(Box::new(
+                                TypeMismatch {
+                                    expected: TypeNode::Bool,
+                                    found: expr_type,
+                                    value: None,
+                                    span,
+                                },
+                            )))
                         }
                     }
                     _ => Err(SemanticError::UnexpectedNode {
@@ -217,25 +265,35 @@ impl SemanticAnalyzer {
 
             // Function call: infer return type from function signature
             // Ex., let result = myFunction(1, "abc");
-            AstNode::FunctionCall { func, args: _ } => {
+            AstNode::FunctionCall { func, args } => {
                 // Function must be an identifier
                 // - Allowed: `myFunction(1, 2)`
                 // - Not allowed: `(some_expr)(1, 2)` or `foo.bar(1, 2)`
-                let name = if let AstNode::Identifier(n) = &**func {
+                let name = if let AstNode::Identifier(n, _) = &**func {
                     n
                 } else {
                     return Err(SemanticError::InvalidFunctionCall {
                         func: format!("{:?}", func),
                     });
                 };
-                // Look up function in function table
-                if let Some((_param_types, ret_ty)) = self.function_table.get(name) {
+                // Builtins: toStr(x), toInt(s), format(x, digits), parseInt(s), ...
+                if let Some(result) = self.check_builtin_call(name, args) {
+                    return result;
+                }
+                // Look up function in function table. The arguments are
+                // inferred too, which counts the variables they read as used
+                if let Some((_param_types, ret_ty)) = self.call_signature(name) {
+                    for arg in args {
+                        self.infer_type(arg)?;
+                    }
                     Ok(ret_ty.clone())
+                } else if let Some(err) = Self::bare_result_constructor(name) {
+                    Err(err)
                 } else {
                     // Function not found
-                    Err(SemanticError::UndeclaredFunction(NamedError {
+                    Err(self.suggest(SemanticError::UndeclaredFunction(NamedError {
                         name: name.clone(),
-                    }))
+                    })))
                 }
             }
 
@@ -257,14 +315,13 @@ impl SemanticAnalyzer {
                 for el in elements.iter() {
                     let t = self.infer_type(el)?;
                     if t != first_type {
-                        let (line, col) = get_node_location(el);
-                        return Err(SemanticError::VarTypeMismatch(TypeMismatch {
+                        let span = el.span();
+                        return Err(SemanticError::VarTypeMismatch(Box::new(TypeMismatch {
                             expected: first_type.clone(),
                             found: t,
                             value: None,
-                            line,
-                            col,
-                        }));
+                            span,
+                        })));
                     }
                 }
                 // All elements are the same type: return Array of that type
@@ -294,10 +351,10 @@ impl SemanticAnalyzer {
                     _ => {
                         return Err(SemanticError::InvalidMapKeyType {
                             found: key_type.clone(),
-                            expected: TypeNode::Map(
+                            expected: Box::new(TypeNode::Map(
                                 Box::new(TypeNode::Int),
                                 Box::new(TypeNode::String),
-                            ),
+                            )),
                         });
                     }
                 }
@@ -307,24 +364,22 @@ impl SemanticAnalyzer {
                     let kt = self.infer_type(k)?;
                     let vt = self.infer_type(v)?;
                     if kt != key_type {
-                        let (line, col) = get_node_location(k);
-                        return Err(SemanticError::VarTypeMismatch(TypeMismatch {
+                        let span = k.span();
+                        return Err(SemanticError::VarTypeMismatch(Box::new(TypeMismatch {
                             expected: key_type.clone(),
                             found: kt,
                             value: None,
-                            line,
-                            col,
-                        }));
+                            span,
+                        })));
                     }
                     if vt != value_type {
-                        let (line, col) = get_node_location(v);
-                        return Err(SemanticError::VarTypeMismatch(TypeMismatch {
+                        let span = v.span();
+                        return Err(SemanticError::VarTypeMismatch(Box::new(TypeMismatch {
                             expected: value_type.clone(),
                             found: vt,
                             value: None,
-                            line,
-                            col,
-                        }));
+                            span,
+                        })));
                     }
                 }
 
@@ -334,16 +389,18 @@ impl SemanticAnalyzer {
 
             // Element access: arr[index] or map[key]
             // Infer type of the array/map and the index/key
-            AstNode::ElementAccess { array, index } => {
+            AstNode::ElementAccess { array, index, .. } => {
                 let array_type = self.infer_type(array)?;
                 let index_type = self.infer_type(index)?;
 
                 // Reject negative indices for arrays
-                if let AstNode::UnaryExpr {
-                    op: TokenType::Minus,
-                    expr: _,
-                } = &**index
-                {
+                if matches!(
+                    &**index,
+                    AstNode::UnaryExpr {
+                        op: TokenType::Minus,
+                        ..
+                    } | AstNode::NumberLiteral(i32::MIN..=-1)
+                ) {
                     return Err(SemanticError::InvalidAssignmentTarget {
                         target: "Array indices cannot be negative".to_string(),
                     });
@@ -354,46 +411,181 @@ impl SemanticAnalyzer {
                     TypeNode::Array(element_type) => {
                         // Index must be an Int
                         if index_type != TypeNode::Int {
-                            let (line, col) = get_node_location(index);
-                            return Err(SemanticError::OperatorTypeMismatch(TypeMismatch {
-                                expected: TypeNode::Int,
-                                found: index_type,
-                                value: None,
-                                line,
-                                col,
-                            }));
+                            let span = index.span();
+                            return Err(SemanticError::OperatorTypeMismatch(Box::new(
+                                TypeMismatch {
+                                    expected: TypeNode::Int,
+                                    found: index_type,
+                                    value: None,
+                                    span,
+                                },
+                            )));
                         }
                         // Return the element type
                         Ok(*element_type)
                     }
-                    // Map element access: map[Key] -> Value
+                    // Map element access: map[Key] -> Value?, absent for a missing key
                     TypeNode::Map(key_type, value_type) => {
                         // Index must match the key type
                         if index_type != *key_type {
-                            let (line, col) = get_node_location(index);
-                            return Err(SemanticError::OperatorTypeMismatch(TypeMismatch {
-                                expected: *key_type,
-                                found: index_type,
-                                value: None,
-                                line,
-                                col,
-                            }));
+                            let span = index.span();
+                            return Err(SemanticError::OperatorTypeMismatch(Box::new(
+                                TypeMismatch {
+                                    expected: *key_type,
+                                    found: index_type,
+                                    value: None,
+                                    span,
+                                },
+                            )));
                         }
-                        // Return the value type
-                        Ok(*value_type)
+                        Ok(TypeNode::Optional(value_type))
                     }
                     // Element access on non-indexable type
                     _ => {
-                        let (line, col) = get_node_location(array);
-                        Err(SemanticError::OperatorTypeMismatch(TypeMismatch {
-                            expected: TypeNode::Array(Box::new(TypeNode::Int)),
-                            found: array_type,
+                        let span = array.span();
+                        Err(SemanticError::OperatorTypeMismatch(Box::new(
+                            TypeMismatch {
+                                expected: TypeNode::Array(Box::new(TypeNode::Int)),
+                                found: array_type,
+                                value: None,
+                                span,
+                            },
+                        )))
+                    }
+                }
+            }
+
+            // Struct literal: every declared field given once with its type
+            AstNode::StructLiteral { name, fields } => self.infer_struct_literal(name, fields),
+
+            // Field access: `u.name` has the declared type of the field
+            AstNode::FieldAccess { object, field, .. } => {
+                let object_type = self.infer_type(object)?;
+                self.field_type(&object_type, field)
+            }
+
+            // Enum value: `Shape::Circle(2.0)` has the enum's type
+            AstNode::EnumValue {
+                enum_name,
+                variant,
+                payload,
+            } => self.infer_enum_value(enum_name, variant, payload.as_deref()),
+
+            // `value?` has the type of the value's Ok payload
+            AstNode::Try { expr_type, .. } => self.infer_try(expr_type.as_ref()),
+
+            // Method call: `u.greet()` has the return type of `User::greet`
+            AstNode::MethodCall {
+                object,
+                method,
+                args,
+                ..
+            } => self.infer_method_call(object, method, args),
+
+            // `if` used as a value: a Bool condition and branches of the same type,
+            // or one branch that panics
+            AstNode::IfExpr {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let cond_type = self.infer_type(condition)?;
+                if cond_type != TypeNode::Bool {
+                    return Err(SemanticError::InvalidConditionType(Box::new(
+                        TypeMismatch {
+                            expected: TypeNode::Bool,
+                            found: cond_type,
                             value: None,
-                            line,
-                            col,
-                        }))
+                            span: None,
+                        },
+                    )));
+                }
+                let then_type = self.infer_type(then_branch)?;
+                let else_type = self.infer_type(else_branch)?;
+                // A branch that panics takes the type of the other one
+                if then_type == TypeNode::Never {
+                    return Ok(else_type);
+                }
+                if else_type != TypeNode::Never && then_type != else_type {
+                    return Err(SemanticError::IfBranchTypeMismatch(Box::new(
+                        TypeMismatch {
+                            expected: then_type,
+                            found: else_type,
+                            value: Some(else_branch.clone()),
+                            span: None,
+                        },
+                    )));
+                }
+                Ok(then_type)
+            }
+
+            // Arguments packed for a variadic parameter match its `T...` type
+            AstNode::VariadicArgs { elem_type, .. } => {
+                Ok(TypeNode::Variadic(Box::new(elem_type.clone())))
+            }
+
+            // Loop and block used as values: typed by analyze_value_exprs beforehand
+            AstNode::LoopExpr { value_type, .. } | AstNode::BlockExpr { value_type, .. } => {
+                value_type
+                    .clone()
+                    .ok_or_else(|| SemanticError::UnexpectedNode {
+                        expected: "loop or block value in a let, assignment, print or return"
+                            .to_string(),
+                    })
+            }
+
+            // A closure value is a function of its parameters. Closures passed to
+            // `map`, `filter` or `reduce` are typed along with the call taking them
+            AstNode::Closure {
+                params,
+                value_type: Some(body_type),
+                ..
+            } if params.iter().all(|(_, ty)| ty.is_some()) => Ok(TypeNode::Function(
+                params.iter().filter_map(|(_, ty)| ty.clone()).collect(),
+                Box::new(body_type.clone()),
+            )),
+            AstNode::Closure { .. } => Err(SemanticError::UnexpectedNode {
+                expected: "closure as the argument of 'map', 'filter' or 'reduce'".to_string(),
+            }),
+
+            // Match used as a value: every arm must be an expression of the same type,
+            // apart from arms that panic
+            AstNode::Match { value, arms } => {
+                let value_type = self.infer_type(value)?;
+                self.check_match_arms(&value_type, arms)?;
+
+                let mut result_type: Option<TypeNode> = None;
+                for arm in arms {
+                    if self.arm_payload_binding(arm).is_some() {
+                        return Err(SemanticError::UnexpectedNode {
+                            expected: "match statement for arms binding a variant payload"
+                                .to_string(),
+                        });
+                    }
+                    if let AstNode::Block(_) = *arm.body {
+                        return Err(SemanticError::UnexpectedNode {
+                            expected: "expression in match arm used as a value".to_string(),
+                        });
+                    }
+                    let arm_type = self.infer_type(&arm.body)?;
+                    match &result_type {
+                        _ if arm_type == TypeNode::Never => {}
+                        None => result_type = Some(arm_type),
+                        Some(expected) if *expected != arm_type => {
+                            return Err(SemanticError::MatchArmTypeMismatch(Box::new(
+                                TypeMismatch {
+                                    expected: expected.clone(),
+                                    found: arm_type,
+                                    value: Some(arm.body.clone()),
+                                    span: None,
+                                },
+                            )));
+                        }
+                        Some(_) => {}
                     }
                 }
+                // check_match_arms guarantees at least one arm; all of them panic otherwise
+                Ok(result_type.unwrap_or(TypeNode::Never))
             }
 
             // Any other AST node (usually statements): return Void type.
@@ -401,4 +593,164 @@ impl SemanticAnalyzer {
             _ => Ok(TypeNode::Void),
         }
     }
+
+    /// Returns true for the type of a bare `null` (an optional with no payload type).
+    pub(crate) fn is_untyped_null(ty: &TypeNode) -> bool {
+        matches!(ty, TypeNode::Optional(inner) if **inner == TypeNode::Void)
+    }
+
+    /// Prepares `value` for storage in a slot of type `expected`.
+    /// When `expected` is `T?` and the value is a plain `T` or `null`, the value is
+    /// wrapped in an `OptionalWrap` node so later stages see the optional explicitly.
+    /// When `expected` is a Result, `Ok(x)` and `Err(e)` become its variants.
+    /// Any other combination is left untouched for the caller's own type check.
+    pub(crate) fn coerce_optional(
+        &self,
+        expected: &TypeNode,
+        value: &mut AstNode,
+    ) -> Result<(), SemanticError> {
+        self.coerce_result_constructor(expected, value)?;
+        let TypeNode::Optional(inner) = expected else {
+            return Ok(());
+        };
+        let found = self.infer_type(value)?;
+        if Self::is_untyped_null(&found) {
+            *value = AstNode::OptionalWrap {
+                value: None,
+                ty: expected.clone(),
+            };
+        } else if found == **inner {
+            let payload = std::mem::replace(value, AstNode::NullLiteral);
+            *value = AstNode::OptionalWrap {
+                value: Some(Box::new(payload)),
+                ty: expected.clone(),
+            };
+        }
+        Ok(())
+    }
+
+    /// Packs the arguments a call passes to a trailing `T...` parameter into a
+    /// single `VariadicArgs` node, checking each against `T`. Calls with fewer
+    /// arguments than fixed parameters, or already packed, are left untouched.
+    pub(crate) fn pack_variadic_args(
+        &self,
+        name: &str,
+        param_types: &[TypeNode],
+        args: &mut Vec<AstNode>,
+    ) -> Result<(), SemanticError> {
+        let Some(TypeNode::Variadic(elem_type)) = param_types.last() else {
+            return Ok(());
+        };
+        let fixed = param_types.len() - 1;
+        if args.len() < fixed || matches!(args.get(fixed), Some(AstNode::VariadicArgs { .. })) {
+            return Ok(());
+        }
+
+        let mut values = args.split_off(fixed);
+        for value in values.iter_mut() {
+            self.coerce_optional(elem_type, value)?;
+            let found = self.infer_type(value)?;
+            if found != **elem_type {
+                return Err(SemanticError::FunctionArgumentTypeMismatch {
+                    name: name.to_string(),
+                    expected: Box::new((**elem_type).clone()),
+                    found,
+                }
+                .at_node(value));
+            }
+        }
+        args.push(AstNode::VariadicArgs {
+            values,
+            elem_type: (**elem_type).clone(),
+        });
+        Ok(())
+    }
+
+    /// Walks an expression and coerces function call arguments against the
+    /// parameter types of the called function (see `coerce_optional`).
+    /// Include calls are replaced by the file's contents (see `expand_include`).
+    pub(crate) fn coerce_call_args(&self, node: &mut AstNode) -> Result<(), SemanticError> {
+        if let Some(contents) = self.expand_include(node)? {
+            *node = contents;
+            return Ok(());
+        }
+        self.resolve_module_call(node);
+        match node {
+            AstNode::FunctionCall { func, args } => {
+                for arg in args.iter_mut() {
+                    self.coerce_call_args(arg)?;
+                }
+                self.resolve_nested_call(func);
+                self.resolve_imported_call(func);
+                self.resolve_overloaded_call(func, args)?;
+                let param_types = match &**func {
+                    AstNode::Identifier(name, _) => self.call_signature(name).map(|f| f.0),
+                    _ => None,
+                };
+                if let Some(param_types) = param_types {
+                    if let AstNode::Identifier(name, _) = &**func {
+                        self.pack_variadic_args(name, &param_types, args)?;
+                    }
+                    for (arg, param_ty) in args.iter_mut().zip(param_types.iter()) {
+                        self.coerce_optional(param_ty, arg)?;
+                    }
+                }
+            }
+            AstNode::BinaryExpr { left, right, .. } => {
+                self.coerce_call_args(left)?;
+                self.coerce_call_args(right)?;
+            }
+            AstNode::UnaryExpr { expr, .. } => self.coerce_call_args(expr)?,
+            AstNode::ElementAccess { array, index, .. } => {
+                self.coerce_call_args(array)?;
+                self.coerce_call_args(index)?;
+            }
+            AstNode::ArrayLiteral(elements) | AstNode::TupleLiteral(elements) => {
+                for el in elements.iter_mut() {
+                    self.coerce_call_args(el)?;
+                }
+            }
+            AstNode::MapLiteral(pairs) => {
+                for (k, v) in pairs.iter_mut() {
+                    self.coerce_call_args(k)?;
+                    self.coerce_call_args(v)?;
+                }
+            }
+            AstNode::StructLiteral { name, fields } => {
+                for (_, value) in fields.iter_mut() {
+                    self.coerce_call_args(value)?;
+                }
+                self.coerce_struct_fields(name, fields)?;
+            }
+            AstNode::FieldAccess { object, .. } => self.coerce_call_args(object)?,
+            AstNode::EnumValue {
+                enum_name,
+                variant,
+                payload: Some(payload),
+            } => self.coerce_enum_payload(enum_name, variant, payload)?,
+            AstNode::MethodCall {
+                object,
+                method,
+                args,
+                ..
+            } => self.coerce_method_call(object, method, args)?,
+            AstNode::IfExpr {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.coerce_call_args(condition)?;
+                self.coerce_call_args(then_branch)?;
+                self.coerce_call_args(else_branch)?;
+            }
+            AstNode::Match { value, arms } => {
+                self.coerce_call_args(value)?;
+                for arm in arms.iter_mut() {
+                    self.coerce_call_args(&mut arm.body)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,45 @@
+use super::analyzer::SemanticAnalyzer;
+use super::types::SemanticError;
+use crate::parser::ast::AstNode;
+
+/// `includeStr("file")` and `includeBytes("file")`: the contents of a file,
+/// read when the program is compiled. The path is a string literal relative
+/// to the directory of the source file the call is in. The call is replaced
+/// by a literal of the contents, a `Str` or a `[Int]` of the bytes, so the
+/// rest of the compiler sees a constant like any other.
+impl SemanticAnalyzer {
+    /// The literal an include call stands for, or None when `node` isn't one.
+    pub(crate) fn expand_include(&self, node: &AstNode) -> Result<Option<AstNode>, SemanticError> {
+        let AstNode::FunctionCall { func, args } = node else {
+            return Ok(None);
+        };
+        let name = match &**func {
+            AstNode::Identifier(name, _) if name == "includeStr" || name == "includeBytes" => name,
+            _ => return Ok(None),
+        };
+        let [AstNode::StringLiteral(path)] = args.as_slice() else {
+            return Err(SemanticError::UnexpectedNode {
+                expected: format!("a string literal naming the file {} reads", name),
+            });
+        };
+
+        let failed = |reason: String| SemanticError::IncludeFailed {
+            path: path.clone(),
+            reason,
+        };
+        let bytes = self
+            .files
+            .read(&self.source_dir.join(path))
+            .map_err(|e| failed(e.to_string()))?;
+        if name == "includeBytes" {
+            let bytes = bytes
+                .into_iter()
+                .map(|byte| AstNode::NumberLiteral(i32::from(byte)))
+                .collect();
+            return Ok(Some(AstNode::ArrayLiteral(bytes)));
+        }
+        let text = String::from_utf8(bytes)
+            .map_err(|_| failed("not UTF-8 text; includeBytes reads any file".to_string()))?;
+        Ok(Some(AstNode::StringLiteral(text)))
+    }
+}
@@ -0,0 +1,70 @@
+use super::analyzer::SemanticAnalyzer;
+use super::types::SemanticError;
+use crate::parser::ast::{AstNode, TypeNode};
+
+/// Methods of map values. `m.insert(k, v)` adds or replaces the value of a
+/// key, `m.remove(k)` takes it out and `m.clear()` empties the map; these
+/// change the map in place, so it must be held by a mutable variable other
+/// than a parameter. `m.containsKey(k)`, `m.keys()` and `m.values()` only
+/// read it. Like `m[k]`, `remove` returns `V?`, absent when the key is missing.
+impl SemanticAnalyzer {
+    /// Parameter and return types of map method `method` on `{key: value}`,
+    /// and whether it changes the map.
+    fn map_method_signature(
+        key: &TypeNode,
+        value: &TypeNode,
+        method: &str,
+    ) -> Option<(Vec<TypeNode>, TypeNode, bool)> {
+        let (key, value) = (key.clone(), value.clone());
+        match method {
+            "insert" => Some((vec![key, value], TypeNode::Void, true)),
+            "remove" => Some((vec![key], TypeNode::Optional(Box::new(value)), true)),
+            "clear" => Some((vec![], TypeNode::Void, true)),
+            "containsKey" => Some((vec![key], TypeNode::Bool, false)),
+            "keys" => Some((vec![], TypeNode::Array(Box::new(key)), false)),
+            "values" => Some((vec![], TypeNode::Array(Box::new(value)), false)),
+            _ => None,
+        }
+    }
+
+    /// Type of `object.method(args)` on a map `{key: value}`, once the
+    /// receiver and the arguments suit the method.
+    pub(crate) fn infer_map_method(
+        &self,
+        object: &AstNode,
+        key: &TypeNode,
+        value: &TypeNode,
+        method: &str,
+        args: &[AstNode],
+    ) -> Result<TypeNode, SemanticError> {
+        let (params, ret_ty, in_place) = Self::map_method_signature(key, value, method)
+            .ok_or_else(|| SemanticError::UnknownMethod {
+                found: TypeNode::Map(Box::new(key.clone()), Box::new(value.clone())),
+                method: method.to_string(),
+            })?;
+
+        if in_place {
+            self.check_in_place_receiver(object, "map", method)?;
+        }
+        self.check_method_args(method, &params, args)?;
+        Ok(ret_ty)
+    }
+
+    /// Coerces the arguments of a map method call to its parameters
+    /// (`null` inserted into a `{Str: Int?}`, for example).
+    pub(crate) fn coerce_map_method(
+        &self,
+        key: &TypeNode,
+        value: &TypeNode,
+        method: &str,
+        args: &mut [AstNode],
+    ) -> Result<(), SemanticError> {
+        let Some((params, _, _)) = Self::map_method_signature(key, value, method) else {
+            return Ok(());
+        };
+        for (arg, param_ty) in args.iter_mut().zip(params.iter()) {
+            self.coerce_optional(param_ty, arg)?;
+        }
+        Ok(())
+    }
+}
@@ -1,10 +1,27 @@
 pub mod analyzer;
+pub mod arrays;
+pub mod builtins;
+pub mod closures;
 pub mod declarations;
+pub mod enums;
 pub mod expressions;
+pub mod files;
+pub mod includes;
+pub mod maps;
+pub mod modules;
+pub mod overloads;
+pub mod results;
 pub mod statements;
+pub mod stdlib;
+pub mod strings;
+pub mod structs;
+pub mod suggestions;
+pub mod traits;
 pub mod types;
+pub mod warnings;
 
 pub use analyzer::SemanticAnalyzer;
+pub use files::{DiskFiles, MemoryFiles, SourceFiles};
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,57 @@
+use super::analyzer::SemanticAnalyzer;
+use crate::parser::ast::{AstNode, Span};
+
+/// Name of function `name` of the module `prefix` names, e.g. `geo$shapes$Area`
+/// for `Area` in geo/shapes.doo. `$` can't appear in identifiers, so functions of
+/// different modules never clash with each other or with a declared name.
+pub fn module_symbol(prefix: &str, name: &str) -> String {
+    format!("{}${}", prefix, name)
+}
+
+/// The prefix of the functions of the module at `path`: `geo$shapes`.
+pub fn module_prefix(path: &[String]) -> String {
+    path.join("$")
+}
+
+impl SemanticAnalyzer {
+    /// Points a call by a name in scope at the function it names: `Area` or
+    /// `shapes::Area` once imported, and a module's own functions while the
+    /// module is analyzed.
+    pub(crate) fn resolve_imported_call(&self, func: &mut AstNode) {
+        if let AstNode::Identifier(name, _) = func {
+            if let Some(symbol) = self.function_aliases.get(name.as_str()) {
+                *name = symbol.clone();
+            }
+        }
+    }
+
+    /// `shapes::Area(2)` parses as an enum value; when `shapes` is an imported
+    /// module rather than an enum, it's turned into a call of the function.
+    pub(crate) fn resolve_module_call(&self, node: &mut AstNode) {
+        let AstNode::EnumValue {
+            enum_name,
+            variant,
+            payload,
+        } = node
+        else {
+            return;
+        };
+        // A module's private function is called the same way, and reported
+        // as undeclared
+        let module = format!("{}::", enum_name);
+        if self.enum_table.contains_key(enum_name.as_str())
+            || !self
+                .function_aliases
+                .keys()
+                .any(|name| name.starts_with(&module))
+        {
+            return;
+        }
+        let qualified = format!("{}{}", module, variant);
+        let args = payload.take().map(|arg| vec![*arg]).unwrap_or_default();
+        *node = AstNode::FunctionCall {
+            func: Box::new(AstNode::Identifier(qualified, Span::default())),
+            args,
+        };
+    }
+}
@@ -0,0 +1,137 @@
+use super::analyzer::SemanticAnalyzer;
+use super::types::{NamedError, SemanticError};
+use crate::parser::ast::{AstNode, TypeNode};
+
+/// Name given to one member of an overload set, e.g. `add__Int_Int`.
+/// `_` can't appear in user identifiers, so it never clashes with a declared name.
+pub fn mangle_overload(name: &str, params: &[TypeNode]) -> String {
+    let tags: Vec<String> = params
+        .iter()
+        .map(|ty| {
+            ty.to_string()
+                .chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect()
+        })
+        .collect();
+    format!("{}__{}", name, tags.join("_"))
+}
+
+impl SemanticAnalyzer {
+    /// Registers a function signature. A second function with the same name but
+    /// different parameter types turns the name into an overload set: every member
+    /// is then stored under its mangled name. Same parameter types are a redeclaration.
+    pub(crate) fn register_function(
+        &mut self,
+        name: &str,
+        params: Vec<TypeNode>,
+        ret_ty: TypeNode,
+    ) -> Result<(), SemanticError> {
+        let redeclared = || {
+            SemanticError::FunctionRedeclaration(NamedError {
+                name: name.to_string(),
+            })
+        };
+
+        if self.c_functions.contains(name) {
+            return Err(redeclared());
+        }
+
+        if let Some(existing) = self.function_table.remove(name) {
+            if existing.0 == params {
+                self.function_table.insert(name.to_string(), existing);
+                return Err(redeclared());
+            }
+            let mangled = mangle_overload(name, &existing.0);
+            self.function_table.insert(mangled.clone(), existing);
+            self.overloads.insert(name.to_string(), vec![mangled]);
+        }
+
+        match self.overloads.get_mut(name) {
+            Some(members) => {
+                let mangled = mangle_overload(name, &params);
+                if members.contains(&mangled) {
+                    return Err(redeclared());
+                }
+                members.push(mangled.clone());
+                self.function_table.insert(mangled, (params, ret_ty));
+            }
+            None => {
+                self.function_table
+                    .insert(name.to_string(), (params, ret_ty));
+            }
+        }
+        Ok(())
+    }
+
+    /// Renames the declaration of an overload set member to its mangled name,
+    /// so MIR and codegen see one ordinary function per signature.
+    pub(crate) fn mangle_overloaded_decl(&self, name: &mut String, params: &[TypeNode]) {
+        if self.overloads.contains_key(name.as_str()) {
+            *name = mangle_overload(name, params);
+        }
+    }
+
+    /// Points a call to an overloaded name at the member its arguments select:
+    /// an exact match on the argument types first, otherwise the first member
+    /// the arguments coerce to (optionals, variadic parameters).
+    pub(crate) fn resolve_overloaded_call(
+        &self,
+        func: &mut AstNode,
+        args: &[AstNode],
+    ) -> Result<(), SemanticError> {
+        let AstNode::Identifier(name, _) = func else {
+            return Ok(());
+        };
+        let Some(members) = self.overloads.get(name.as_str()) else {
+            return Ok(());
+        };
+
+        let arg_types = args
+            .iter()
+            .map(|arg| self.infer_type(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        let params_of = |member: &String| &self.function_table[member].0;
+        let chosen = members
+            .iter()
+            .find(|m| *params_of(m) == arg_types)
+            .or_else(|| {
+                members
+                    .iter()
+                    .find(|m| Self::args_coerce_to(params_of(m), &arg_types))
+            });
+
+        match chosen {
+            Some(member) => {
+                *name = member.clone();
+                Ok(())
+            }
+            None => Err(SemanticError::NoMatchingOverload {
+                name: name.clone(),
+                found: arg_types,
+            }),
+        }
+    }
+
+    /// Whether arguments of `arg_types` can be passed to `params` once plain values
+    /// are wrapped into optionals and trailing arguments packed for a `T...` parameter.
+    pub(crate) fn args_coerce_to(params: &[TypeNode], arg_types: &[TypeNode]) -> bool {
+        let accepts = |param: &TypeNode, arg: &TypeNode| match param {
+            TypeNode::Optional(inner) => {
+                arg == param || arg == &**inner || Self::is_untyped_null(arg)
+            }
+            _ => arg == param,
+        };
+        match params.split_last() {
+            Some((TypeNode::Variadic(elem), fixed)) => {
+                arg_types.len() >= fixed.len()
+                    && fixed.iter().zip(arg_types).all(|(p, a)| accepts(p, a))
+                    && arg_types[fixed.len()..].iter().all(|a| accepts(elem, a))
+            }
+            _ => {
+                params.len() == arg_types.len()
+                    && params.iter().zip(arg_types).all(|(p, a)| accepts(p, a))
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use super::analyzer::SemanticAnalyzer;
+use super::types::SemanticError;
+use crate::parser::ast::{AstNode, MatchArm, MatchPattern, TypeNode};
+
+/// `Result<T, E>` values: enums with the variants `Ok(T)` and `Err(E)` that the
+/// parser declares for each Result type a program names (see
+/// `Parser::parse_result_type`). `Ok(x)` and `Err(e)` build one where a Result
+/// is expected, `match` takes one apart with `Ok(v)` and `Err(e)` patterns, and
+/// `value?` yields the Ok payload or returns the Err from the enclosing function.
+impl SemanticAnalyzer {
+    /// The enum name of a Result type (`Result<Int, Str>`), or None for other types.
+    pub(crate) fn result_enum_name(ty: &TypeNode) -> Option<&str> {
+        match ty {
+            TypeNode::Enum(name, _) if name.starts_with("Result<") => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The error for a call of `Ok` or `Err` that no Result type was expected
+    /// for (and that no function of the program is named), None for other names.
+    pub(crate) fn bare_result_constructor(name: &str) -> Option<SemanticError> {
+        matches!(name, "Ok" | "Err").then(|| SemanticError::UnexpectedNode {
+            expected: format!("'{}(..)' where a Result type is expected", name),
+        })
+    }
+
+    /// Turns `Ok(x)` or `Err(e)` stored into a slot of Result type `expected`
+    /// into the value of that Result enum. Calls of a function the program
+    /// declares with that name are left alone.
+    pub(crate) fn coerce_result_constructor(
+        &self,
+        expected: &TypeNode,
+        value: &mut AstNode,
+    ) -> Result<(), SemanticError> {
+        let expected = self.resolve_type_refs(expected);
+        let Some(enum_name) = Self::result_enum_name(&expected) else {
+            return Ok(());
+        };
+        let AstNode::FunctionCall { func, args } = value else {
+            return Ok(());
+        };
+        let AstNode::Identifier(variant, _) = &**func else {
+            return Ok(());
+        };
+        if !matches!(variant.as_str(), "Ok" | "Err") || self.function_table.contains_key(variant) {
+            return Ok(());
+        }
+        if args.len() > 1 {
+            return Err(SemanticError::FunctionArgumentMismatch {
+                name: variant.clone(),
+                expected: 1,
+                found: args.len(),
+            });
+        }
+
+        let variant = variant.clone();
+        let mut payload = args.pop().map(Box::new);
+        if let Some(payload) = payload.as_mut() {
+            self.coerce_enum_payload(enum_name, &variant, payload)?;
+        }
+        *value = AstNode::EnumValue {
+            enum_name: enum_name.to_string(),
+            variant,
+            payload,
+        };
+        Ok(())
+    }
+
+    /// Names the Result enum in the `Ok(v)` and `Err(e)` patterns of a match
+    /// on a value of Result type `value_type`.
+    pub(crate) fn resolve_result_patterns(&self, value_type: &TypeNode, arms: &mut [MatchArm]) {
+        let Some(result_name) = Self::result_enum_name(value_type) else {
+            return;
+        };
+        for arm in arms.iter_mut() {
+            for pattern in arm.patterns.iter_mut() {
+                if let MatchPattern::Variant { enum_name, .. } = pattern {
+                    if enum_name == "Result" {
+                        *enum_name = result_name.to_string();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks `value?`: the value must be a Result, and the enclosing function
+    /// must return a Result with the same error type, which the Err is
+    /// returned as. Records both Result types on the node.
+    pub(crate) fn analyze_try(&mut self, node: &mut AstNode) -> Result<(), SemanticError> {
+        let AstNode::Try {
+            expr,
+            expr_type,
+            return_type,
+        } = node
+        else {
+            return Ok(());
+        };
+        if expr_type.is_some() {
+            return Ok(());
+        }
+
+        self.analyze_value_exprs(expr)?;
+        self.coerce_call_args(expr)?;
+        let found = self.infer_type(expr)?;
+        let Some(result_name) = Self::result_enum_name(&found) else {
+            return Err(SemanticError::InvalidTryOperand { found });
+        };
+        let err_type = self.variant_payload(result_name, "Err")?;
+
+        let Some((function, ret_ty)) = self.current_function.clone() else {
+            return Err(SemanticError::UnexpectedNode {
+                expected: "'?' inside a function returning a Result".to_string(),
+            });
+        };
+        let returns_err = match Self::result_enum_name(&ret_ty) {
+            Some(ret_name) => self.variant_payload(ret_name, "Err")? == err_type,
+            None => false,
+        };
+        if !returns_err {
+            return Err(SemanticError::TryReturnTypeMismatch {
+                function,
+                found,
+                return_type: Box::new(ret_ty),
+            });
+        }
+
+        *expr_type = Some(found);
+        *return_type = Some(ret_ty);
+        Ok(())
+    }
+
+    /// Type of `value?`: the Ok payload of the value's Result, Void for a unit `Ok`.
+    pub(crate) fn infer_try(
+        &self,
+        expr_type: Option<&TypeNode>,
+    ) -> Result<TypeNode, SemanticError> {
+        let result_name = expr_type.and_then(Self::result_enum_name).ok_or_else(|| {
+            SemanticError::UnexpectedNode {
+                expected: "'?' in a let, assignment, print, return or expression statement"
+                    .to_string(),
+            }
+        })?;
+        Ok(self
+            .variant_payload(result_name, "Ok")?
+            .unwrap_or(TypeNode::Void))
+    }
+}
@@ -2,7 +2,7 @@ use super::analyzer::SemanticAnalyzer;
 use super::types::{NamedError, SemanticError, TypeMismatch};
 use crate::analyzer::analyzer::SymbolInfo;
 use crate::lexar::token::TokenType;
-use crate::parser::ast::{AstNode, Pattern, TypeNode};
+use crate::parser::ast::{AstNode, MatchArm, MatchPattern, Pattern, TypeNode};
 use std::collections::HashMap;
 
 impl SemanticAnalyzer {
@@ -13,15 +13,42 @@ impl SemanticAnalyzer {
     pub fn analyze_assignment(
         &mut self,
         pattern: &Pattern,
-        value: &AstNode,
+        value: &mut AstNode,
     ) -> Result<(), SemanticError> {
+        self.analyze_value_exprs(value)?;
+
         // Flatten the LHS pattern (tuple destructuring) and validate identifiers
         let targets = self.collect_and_validate_targets(pattern)?;
         let lhs_count = targets.len();
 
+        // Assigning into an optional variable wraps plain values and null
+        self.coerce_call_args(value)?;
+        if let [Pattern::Identifier(name)] = targets.as_slice() {
+            if let Some(info) = self.lookup_variable(name) {
+                let target_ty = info.ty.clone();
+                self.coerce_optional(&target_ty, value)?;
+            }
+        }
+
         // Infer the RHS expression into a list of types
         let rhs_types = self.infer_rhs_types(value, lhs_count)?;
 
+        // null can only be assigned to optional variables (handled above)
+        if let Some(null_ty) = rhs_types.iter().find(|t| Self::is_untyped_null(t)) {
+            return Err(SemanticError::VarTypeMismatch(Box::new(TypeMismatch {
+                expected: match targets.as_slice() {
+                    [Pattern::Identifier(name)] => self
+                        .lookup_variable(name)
+                        .map(|info| info.ty.clone())
+                        .unwrap_or(TypeNode::Void),
+                    _ => TypeNode::Void,
+                },
+                found: null_ty.clone(),
+                value: None,
+                span: None,
+            })));
+        }
+
         // Ensure LHS and RHS have the same number of elements
         if rhs_types.len() != lhs_count {
             return Err(SemanticError::TupleAssignmentMismatch {
@@ -42,9 +69,9 @@ impl SemanticAnalyzer {
                         }
                     }
                     None => {
-                        return Err(SemanticError::UndeclaredVariable(NamedError {
+                        return Err(self.suggest(SemanticError::UndeclaredVariable(NamedError {
                             name: name.clone(),
-                        }));
+                        })));
                     }
                 }
             }
@@ -81,9 +108,9 @@ impl SemanticAnalyzer {
         let var_info = match self.symbol_table.get(var_name) {
             Some(info) => info.clone(),
             None => {
-                return Err(SemanticError::UndeclaredVariable(NamedError {
+                return Err(self.suggest(SemanticError::UndeclaredVariable(NamedError {
                     name: var_name.clone(),
-                }));
+                })));
             }
         };
 
@@ -106,17 +133,19 @@ impl SemanticAnalyzer {
             | TokenType::SlashEq
             | TokenType::PercentEq => match (&var_info.ty, &rhs_type) {
                 (TypeNode::Int, TypeNode::Int) => Ok(TypeNode::Int),
+                (TypeNode::Int64, TypeNode::Int64) => Ok(TypeNode::Int64),
                 (TypeNode::Float, TypeNode::Float) => Ok(TypeNode::Float),
                 (TypeNode::String, TypeNode::String) if matches!(op, TokenType::PlusEq) => {
                     Ok(TypeNode::String)
                 }
-                _ => Err(SemanticError::OperatorTypeMismatch(TypeMismatch {
-                    expected: var_info.ty.clone(),
-                    found: rhs_type.clone(),
-                    value: None,
-                    line: None,
-                    col: None,
-                })),
+                _ => Err(SemanticError::OperatorTypeMismatch(Box::new(
+                    TypeMismatch {
+                        expected: var_info.ty.clone(),
+                        found: rhs_type.clone(),
+                        value: None,
+                        span: None,
+                    },
+                ))),
             },
             _ => {
                 return Err(SemanticError::UnexpectedNode {
@@ -127,13 +156,12 @@ impl SemanticAnalyzer {
 
         // The result type should match the variable's type
         if result_type != var_info.ty {
-            return Err(SemanticError::VarTypeMismatch(TypeMismatch {
+            return Err(SemanticError::VarTypeMismatch(Box::new(TypeMismatch {
                 expected: var_info.ty.clone(),
                 found: result_type,
                 value: None,
-                line: None,
-                col: None,
-            }));
+                span: None,
+            })));
         }
 
         Ok(())
@@ -210,7 +238,9 @@ impl SemanticAnalyzer {
     ) -> Result<Vec<TypeNode>, SemanticError> {
         match value {
             // Function call: check validity and return types
-            AstNode::FunctionCall { func, args } => self.check_function_call(func, args),
+            AstNode::FunctionCall { func, args } => self
+                .check_function_call(func, args)
+                .map_err(|e| e.at_node(func)),
 
             // Tuple literal: infer each element's type
             AstNode::TupleLiteral(elements) => {
@@ -237,7 +267,7 @@ impl SemanticAnalyzer {
         args: &[AstNode],
     ) -> Result<Vec<TypeNode>, SemanticError> {
         // Ensure the call target is a simple identifier (not `foo.bar` or similar yet)
-        let name = if let AstNode::Identifier(n) = &*func {
+        let name = if let AstNode::Identifier(n, _) = &*func {
             n
         } else {
             return Err(SemanticError::InvalidFunctionCall {
@@ -245,8 +275,12 @@ impl SemanticAnalyzer {
             });
         };
 
+        if let Some(result) = self.check_builtin_call(name, args) {
+            return result.map(|ty| vec![ty]);
+        }
+
         // Look up function definition in the table
-        if let Some((param_types, ret_ty)) = self.function_table.get(name.as_str()) {
+        if let Some((param_types, ret_ty)) = self.call_signature(name) {
             // Check number of arguments
             if args.len() != param_types.len() {
                 return Err(SemanticError::FunctionArgumentMismatch {
@@ -262,9 +296,10 @@ impl SemanticAnalyzer {
                 if &arg_ty != expected_ty {
                     return Err(SemanticError::FunctionArgumentTypeMismatch {
                         name: name.clone(),
-                        expected: expected_ty.clone(),
+                        expected: Box::new(expected_ty.clone()),
                         found: arg_ty,
-                    });
+                    }
+                    .at_node(arg));
                 }
             }
 
@@ -273,10 +308,12 @@ impl SemanticAnalyzer {
                 TypeNode::Tuple(types) => types.clone(), // multi-value
                 t => vec![t.clone()],                    // single value
             })
+        } else if let Some(err) = Self::bare_result_constructor(name) {
+            Err(err)
         } else {
-            Err(SemanticError::UndeclaredFunction(NamedError {
+            Err(self.suggest(SemanticError::UndeclaredFunction(NamedError {
                 name: name.clone(),
-            }))
+            })))
         }
     }
 
@@ -285,8 +322,8 @@ impl SemanticAnalyzer {
     fn is_valid_identifier(name: &str) -> bool {
         // List of reserved keywords (sync with your lexer)
         const KEYWORDS: &[&str] = &[
-            "let", "fn", "import", "struct", "enum", "map", "if", "else", "for", "in", "return",
-            "break", "continue", "print", "true", "false",
+            "let", "fn", "import", "struct", "enum", "impl", "map", "if", "else", "for", "in",
+            "return", "break", "continue", "print", "true", "false", "null",
         ];
         // Disallow empty, reserved, or starts with digit
         if name.is_empty() || KEYWORDS.contains(&name) {
@@ -303,15 +340,17 @@ impl SemanticAnalyzer {
 
     /// - Checks that each expression in the print statement is of a printable type.
     /// - Infers the type of each expression and ensures it's allowed
-    /// (int, float, bool, string, array, map, tuple).
-    /// Note: Float not supported yet as type yet, TODO later
+    ///   (int, float, bool, string, array, map, tuple).
     pub fn analyze_print(&mut self, node: &mut AstNode) -> Result<(), SemanticError> {
         if let AstNode::Print { exprs } = node {
             for expr in exprs.iter_mut() {
+                self.analyze_value_exprs(expr)?;
+                self.coerce_call_args(expr)?;
                 let ty = self.infer_type(expr)?;
                 // Only allow printing of supported types.
                 match ty {
                     TypeNode::Int
+                    | TypeNode::Int64
                     | TypeNode::Float
                     | TypeNode::Bool
                     | TypeNode::String
@@ -350,16 +389,18 @@ impl SemanticAnalyzer {
         else_branch: &mut Option<Box<AstNode>>,
     ) -> Result<(), SemanticError> {
         // The condition of an if/else must always be a boolean.
+        self.coerce_call_args(condition)?;
         let cond_type = self.infer_type(condition)?;
         if cond_type != TypeNode::Bool {
             // If the condition is not a boolean, return an error.
-            return Err(SemanticError::InvalidConditionType(TypeMismatch {
-                expected: TypeNode::Bool,
-                found: cond_type,
-                value: None,
-                line: None,
-                col: None,
-            }));
+            return Err(SemanticError::InvalidConditionType(Box::new(
+                TypeMismatch {
+                    expected: TypeNode::Bool,
+                    found: cond_type,
+                    value: None,
+                    span: None,
+                },
+            )));
         }
 
         // Create a new scope for the 'then' block
@@ -396,11 +437,414 @@ impl SemanticAnalyzer {
         Ok(())
     }
 
+    /// Analyze an `if let name = value { ... } else { ... }` statement.
+    /// - The value must be optional (`T?`); `name` is bound to the `T` payload
+    ///   inside the then block only.
+    /// - The else branch is analyzed like a regular conditional's else branch.
+    pub fn analyze_if_let(
+        &mut self,
+        name: &str,
+        value: &mut AstNode,
+        then_block: &mut Vec<AstNode>,
+        else_branch: &mut Option<Box<AstNode>>,
+    ) -> Result<(), SemanticError> {
+        self.coerce_call_args(value)?;
+        let value_type = self.infer_type(value)?;
+        let payload_type = match value_type {
+            TypeNode::Optional(inner) if *inner != TypeNode::Void => *inner,
+            other => {
+                return Err(SemanticError::InvalidConditionType(Box::new(
+                    TypeMismatch {
+                        expected: TypeNode::Optional(Box::new(other.clone())),
+                        found: other,
+                        value: None,
+                        span: None,
+                    },
+                )));
+            }
+        };
+
+        // Then block gets its own scope with the unwrapped binding
+        let then_parent_scope = self.symbol_table.clone();
+        self.scope_stack.push(HashMap::new());
+        let scope_size = self.symbol_table.len();
+        self.scope_sizes_stack.push(scope_size);
+        self.symbol_table.insert(
+            name.to_string(),
+            SymbolInfo {
+                ty: payload_type,
+                mutable: false,
+                is_ref_counted: false, // borrowed from the optional
+                is_parameter: false,
+            },
+        );
+
+        let then_result = self.analyze_program(then_block);
+
+        self.scope_stack.pop();
+        self.scope_sizes_stack.pop();
+        self.symbol_table = then_parent_scope;
+        then_result?;
+
+        if let Some(else_node) = else_branch {
+            let else_parent_scope = self.symbol_table.clone();
+            self.scope_stack.push(HashMap::new());
+            let scope_size = self.symbol_table.len();
+            self.scope_sizes_stack.push(scope_size);
+
+            let else_result = self.analyze_node(else_node);
+
+            self.scope_stack.pop();
+            self.scope_sizes_stack.pop();
+            self.symbol_table = else_parent_scope;
+            else_result?;
+        }
+
+        Ok(())
+    }
+
+    /// Analyzes the loops and blocks used as values anywhere in an expression.
+    /// Their bodies need a mutable pass before `infer_type` can type them,
+    /// so this runs ahead of type inference for each value expression.
+    pub fn analyze_value_exprs(&mut self, node: &mut AstNode) -> Result<(), SemanticError> {
+        match node {
+            AstNode::LoopExpr { .. } => self.analyze_loop_expr(node),
+            AstNode::BlockExpr { .. } => self.analyze_block_expr(node),
+            AstNode::FunctionCall { func, args } => {
+                for arg in args.iter_mut() {
+                    self.analyze_value_exprs(arg)?;
+                }
+                self.resolve_imported_call(func);
+                self.instantiate_generic_call(func, args)
+            }
+            AstNode::ArrayLiteral(items) | AstNode::TupleLiteral(items) => {
+                for item in items.iter_mut() {
+                    self.analyze_value_exprs(item)?;
+                }
+                Ok(())
+            }
+            AstNode::MapLiteral(pairs) => {
+                for (k, v) in pairs.iter_mut() {
+                    self.analyze_value_exprs(k)?;
+                    self.analyze_value_exprs(v)?;
+                }
+                Ok(())
+            }
+            AstNode::BinaryExpr { left, right, .. } => {
+                self.analyze_value_exprs(left)?;
+                self.analyze_value_exprs(right)
+            }
+            AstNode::UnaryExpr { expr, .. } => self.analyze_value_exprs(expr),
+            AstNode::ElementAccess { array, index, .. } => {
+                self.analyze_value_exprs(array)?;
+                self.analyze_value_exprs(index)
+            }
+            AstNode::StructLiteral { fields, .. } => {
+                for (_, value) in fields.iter_mut() {
+                    self.analyze_value_exprs(value)?;
+                }
+                Ok(())
+            }
+            AstNode::FieldAccess { object, .. } => self.analyze_value_exprs(object),
+            AstNode::EnumValue {
+                payload: Some(payload),
+                ..
+            } => self.analyze_value_exprs(payload),
+            AstNode::MethodCall {
+                object,
+                method,
+                args,
+                ..
+            } => {
+                self.analyze_value_exprs(object)?;
+                // A closure ending the arguments is typed by `analyze_closure_arg`
+                let inline_closure = matches!(args.last(), Some(AstNode::Closure { .. }));
+                let values = args.len() - usize::from(inline_closure);
+                for arg in args[..values].iter_mut() {
+                    self.analyze_value_exprs(arg)?;
+                }
+                self.analyze_closure_arg(object, method, args)
+            }
+            AstNode::IfExpr {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.analyze_value_exprs(condition)?;
+                self.analyze_value_exprs(then_branch)?;
+                self.analyze_value_exprs(else_branch)
+            }
+            AstNode::Match { value, arms } => {
+                self.analyze_value_exprs(value)?;
+                if let Ok(value_type) = self.infer_type(value) {
+                    self.resolve_result_patterns(&value_type, arms);
+                }
+                for arm in arms.iter_mut() {
+                    self.analyze_value_exprs(&mut arm.body)?;
+                }
+                Ok(())
+            }
+            AstNode::Try { .. } => self.analyze_try(node),
+            AstNode::Closure { .. } => self.analyze_closure_value(node),
+            _ => Ok(()),
+        }
+    }
+
+    /// Analyze a block used as a value, e.g. `let y = { let t = a * a; t + 1 };`.
+    /// The statements and the final value share a scope of their own; the type
+    /// of the value becomes the block's type and is recorded on the node.
+    pub fn analyze_block_expr(&mut self, node: &mut AstNode) -> Result<(), SemanticError> {
+        let AstNode::BlockExpr {
+            body,
+            value,
+            value_type,
+        } = node
+        else {
+            return Ok(());
+        };
+
+        let parent_scope = self.symbol_table.clone();
+        self.scope_stack.push(HashMap::new());
+        let scope_size = self.symbol_table.len();
+        self.scope_sizes_stack.push(scope_size);
+
+        let result = self.analyze_program(body).and_then(|_| {
+            self.analyze_value_exprs(value)?;
+            self.coerce_call_args(value)?;
+            self.infer_type(value)
+        });
+
+        self.scope_stack.pop();
+        self.scope_sizes_stack.pop();
+        self.symbol_table = parent_scope;
+
+        *value_type = Some(result?);
+        Ok(())
+    }
+
+    /// Analyze a `for { ... }` loop used as a value, e.g. `let v = for { break 42; };`.
+    /// Every `break` of the loop must carry a value and all values must share a type,
+    /// which becomes the loop's type and is recorded on the node. Does nothing for
+    /// other nodes.
+    pub fn analyze_loop_expr(&mut self, node: &mut AstNode) -> Result<(), SemanticError> {
+        let AstNode::LoopExpr { body, value_type } = node else {
+            return Ok(());
+        };
+
+        let parent_scope = self.symbol_table.clone();
+        self.scope_stack.push(HashMap::new());
+        let scope_size = self.symbol_table.len();
+        self.scope_sizes_stack.push(scope_size);
+        self.loop_depth += 1;
+        self.loop_break_types.push(Some(Vec::new()));
+
+        let body_result = self.analyze_program(body);
+
+        let break_types = self.loop_break_types.pop().flatten().unwrap_or_default();
+        self.loop_depth -= 1;
+        self.scope_stack.pop();
+        self.scope_sizes_stack.pop();
+        self.symbol_table = parent_scope;
+        body_result?;
+
+        match break_types.into_iter().next() {
+            Some(ty) => {
+                *value_type = Some(ty);
+                Ok(())
+            }
+            None => Err(SemanticError::UnexpectedNode {
+                expected: "break with a value in a loop used as a value".to_string(),
+            }),
+        }
+    }
+
+    /// Checks the value of a `break` against the innermost loop: loops used as
+    /// values need one, of the same type as the loop's other break values,
+    /// and statement loops must not have one.
+    pub(crate) fn analyze_break_value(
+        &mut self,
+        value: Option<&mut AstNode>,
+    ) -> Result<(), SemanticError> {
+        let is_value_loop = matches!(self.loop_break_types.last(), Some(Some(_)));
+        match value {
+            Some(value) if is_value_loop => {
+                self.coerce_call_args(value)?;
+                let ty = self.infer_type(value)?;
+                if let Some(Some(types)) = self.loop_break_types.last_mut() {
+                    if let Some(expected) = types.first() {
+                        if *expected != ty {
+                            return Err(SemanticError::BreakValueTypeMismatch(Box::new(
+                                TypeMismatch {
+                                    expected: expected.clone(),
+                                    found: ty,
+                                    value: Some(Box::new(value.clone())),
+                                    span: None,
+                                },
+                            )));
+                        }
+                    }
+                    types.push(ty);
+                }
+                Ok(())
+            }
+            None if is_value_loop => Err(SemanticError::UnexpectedNode {
+                expected: "break with a value in a loop used as a value".to_string(),
+            }),
+            Some(_) => Err(SemanticError::UnexpectedNode {
+                expected: "break without a value outside a loop used as a value".to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Analyze a `match value { ... }` statement.
+    /// - Checks the arm patterns against the value (see `check_match_arms`).
+    /// - An arm binding a variant payload sees the binding in its body only.
+    /// - Block arms get their own scope; expression arms are checked like
+    ///   expression statements and their values are discarded.
+    pub fn analyze_match(
+        &mut self,
+        value: &mut AstNode,
+        arms: &mut [MatchArm],
+    ) -> Result<(), SemanticError> {
+        self.coerce_call_args(value)?;
+        let value_type = self.infer_type(value)?;
+        self.resolve_result_patterns(&value_type, arms);
+        self.check_match_arms(&value_type, arms)?;
+        for arm in arms.iter_mut() {
+            let Some((binding, payload_type)) = self.arm_payload_binding(arm) else {
+                self.analyze_node(&mut arm.body)?;
+                continue;
+            };
+
+            // The payload binding is visible in its arm only
+            let parent_scope = self.symbol_table.clone();
+            self.scope_stack.push(HashMap::new());
+            let scope_size = self.symbol_table.len();
+            self.scope_sizes_stack.push(scope_size);
+            self.symbol_table.insert(
+                binding,
+                SymbolInfo {
+                    ty: payload_type,
+                    mutable: false,
+                    is_ref_counted: false, // borrowed from the enum value
+                    is_parameter: false,
+                },
+            );
+
+            let result = self.analyze_node(&mut arm.body);
+
+            self.scope_stack.pop();
+            self.scope_sizes_stack.pop();
+            self.symbol_table = parent_scope;
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Checks the patterns of a match on a value of type `value_type`.
+    /// - Only Int, Int64, String and Bool values can be matched, and every
+    ///   pattern must have the value's type.
+    /// - An arm is unreachable after a `_` arm, or when all of its patterns
+    ///   already appeared in earlier arms.
+    /// - The arms must be exhaustive: a Bool match needs `true` and `false`
+    ///   (or `_`), every other type needs `_`.
+    ///
+    /// Enum values are checked by `check_enum_match_arms`.
+    pub(crate) fn check_match_arms(
+        &self,
+        value_type: &TypeNode,
+        arms: &[MatchArm],
+    ) -> Result<(), SemanticError> {
+        if let TypeNode::Enum(enum_name, _) = value_type {
+            return self.check_enum_match_arms(enum_name, value_type, arms);
+        }
+        if !matches!(
+            value_type,
+            TypeNode::Int | TypeNode::Int64 | TypeNode::String | TypeNode::Bool
+        ) {
+            return Err(SemanticError::InvalidMatchType {
+                found: value_type.clone(),
+            });
+        }
+
+        // Literals already covered, compared by their printed form
+        let mut seen: Vec<String> = Vec::new();
+        let mut has_wildcard = false;
+        let all_covered = |seen: &Vec<String>, has_wildcard: bool| {
+            has_wildcard || (*value_type == TypeNode::Bool && seen.len() == 2)
+        };
+
+        for (index, arm) in arms.iter().enumerate() {
+            let covered_before = all_covered(&seen, has_wildcard);
+            let mut adds_values = false;
+            for pattern in &arm.patterns {
+                match pattern {
+                    MatchPattern::Wildcard => {
+                        has_wildcard = true;
+                        adds_values = true;
+                    }
+                    MatchPattern::Literal(literal) => {
+                        let pattern_type = self.infer_type(literal)?;
+                        if pattern_type != *value_type {
+                            return Err(SemanticError::MatchPatternTypeMismatch(Box::new(
+                                TypeMismatch {
+                                    expected: value_type.clone(),
+                                    found: pattern_type,
+                                    value: Some(Box::new(literal.clone())),
+                                    span: None,
+                                },
+                            )));
+                        }
+                        let key = format!("{:?}", literal);
+                        if !seen.contains(&key) {
+                            seen.push(key);
+                            adds_values = true;
+                        }
+                    }
+                    MatchPattern::Variant { enum_name, .. } => {
+                        return Err(SemanticError::MatchPatternTypeMismatch(Box::new(
+                            TypeMismatch {
+                                expected: value_type.clone(),
+                                found: self
+                                    .enum_type(enum_name)
+                                    .unwrap_or_else(|| TypeNode::TypeRef(enum_name.clone())),
+                                value: None,
+                                span: None,
+                            },
+                        )));
+                    }
+                }
+            }
+            if covered_before || !adds_values {
+                return Err(SemanticError::UnreachableMatchArm { arm: index + 1 });
+            }
+        }
+
+        if !all_covered(&seen, has_wildcard) {
+            let missing = match value_type {
+                TypeNode::Bool if seen.len() == 1 => {
+                    if seen[0] == format!("{:?}", AstNode::BoolLiteral(true)) {
+                        "false"
+                    } else {
+                        "true"
+                    }
+                }
+                _ => "_",
+            };
+            return Err(SemanticError::NonExhaustiveMatch {
+                missing: missing.to_string(),
+            });
+        }
+        Ok(())
+    }
+
     /// - Sets up a new scope for loop variables.
     /// - Checks the type of the iterable expression.
-    /// - For arrays: expects a single variable pattern.
+    /// - For arrays: expects a single variable, or an (index, item) tuple pattern.
     /// - For maps: expects a tuple pattern (key, value).
-    /// - For ranges: expects a single variable or wildcard.
+    /// - For ranges: expects a single variable or wildcard, and an optional
+    ///   Int `step` that must not be a literal zero.
     /// - For infinite loops (no iterable): only allows wildcard.
     /// - Binds loop variables to their types in the symbol table.
     /// - Restores the outer symbol table after the loop.
@@ -409,6 +853,7 @@ impl SemanticAnalyzer {
         &mut self,
         pattern: &mut Pattern,
         iterable: Option<&mut AstNode>,
+        step: Option<&AstNode>,
         body: &mut Vec<AstNode>,
     ) -> Result<(), SemanticError> {
         // Create a new scope for the loop body
@@ -419,19 +864,49 @@ impl SemanticAnalyzer {
 
         if let Some(iter_node) = iterable {
             // Infer the type of the iterable expression.
+            self.coerce_call_args(iter_node)?;
             let iter_type = self.infer_type(iter_node)?;
 
+            if let Some(step) = step {
+                if !matches!(iter_type, TypeNode::Range(_, _, _)) {
+                    return Err(SemanticError::UnexpectedNode {
+                        expected: "range before 'step'".to_string(),
+                    });
+                }
+                let step_type = self.infer_type(step)?;
+                if step_type != TypeNode::Int {
+                    return Err(SemanticError::RangeIterationTypeMismatch {
+                        expected: Box::new(TypeNode::Int),
+                        found: step_type,
+                    });
+                }
+                if let AstNode::NumberLiteral(0) = step {
+                    return Err(SemanticError::ZeroRangeStep);
+                }
+                if let AstNode::UnaryExpr { expr, .. } = step {
+                    if let AstNode::NumberLiteral(0) = expr.as_ref() {
+                        return Err(SemanticError::ZeroRangeStep);
+                    }
+                }
+            }
+
             match iter_type {
                 TypeNode::Array(elem_type) => {
-                    // For arrays, only a single variable pattern is allowed.
+                    // For arrays, a single variable or an (index, item) pair is allowed.
                     if let Pattern::Tuple(patterns) = pattern {
-                        if patterns.len() != 1 {
-                            return Err(SemanticError::InvalidAssignmentTarget {
-                                target: "Cannot use tuple pattern when iterating an array"
-                                    .to_string(),
-                            });
+                        match patterns.len() {
+                            1 => self.bind_pattern_to_type(&mut patterns[0], &*elem_type)?,
+                            2 => {
+                                self.bind_pattern_to_type(&mut patterns[0], &TypeNode::Int)?;
+                                self.bind_pattern_to_type(&mut patterns[1], &elem_type)?;
+                            }
+                            _ => {
+                                return Err(SemanticError::InvalidAssignmentTarget {
+                                    target: "Expected (index, item) when iterating an array"
+                                        .to_string(),
+                                });
+                            }
                         }
-                        self.bind_pattern_to_type(&mut patterns[0], &*elem_type)?;
                     } else {
                         self.bind_pattern_to_type(pattern, &*elem_type)?;
                     }
@@ -489,10 +964,13 @@ impl SemanticAnalyzer {
 
         // Increment loop depth before analyzing the loop body
         self.loop_depth += 1;
+        self.loop_break_types.push(None);
         // Analyze the loop body for semantic correctness.
-        self.analyze_program(body)?;
+        let body_result = self.analyze_program(body);
         // Decrement loop depth after analyzing the loop body
+        self.loop_break_types.pop();
         self.loop_depth -= 1;
+        body_result?;
         // Pop scope and restore symbol table
         self.scope_sizes_stack.pop();
         if let Some(prev_scope) = self.scope_stack.pop() {
@@ -0,0 +1,34 @@
+use std::path::{Path, PathBuf};
+
+/// The standard library: modules under `std`, built into the compiler so
+/// programs import them the same way wherever the compiler runs. Each is
+/// the name after `std::` and its source, from the `std` directory.
+const MODULES: &[(&str, &str)] = &[("os", include_str!("../../std/os.doo"))];
+
+/// The directory standard modules are named in, so diagnostics say
+/// `<std>/os.doo`. No file on disk is ever read from it.
+const STD_DIR: &str = "<std>";
+
+/// The path of the standard module `path` (`["std", "os"]`) names, or None
+/// when it isn't one.
+pub(crate) fn std_module_path(path: &[String]) -> Option<PathBuf> {
+    let [std, name] = path else {
+        return None;
+    };
+    if std != "std" || !MODULES.iter().any(|(module, _)| module == name) {
+        return None;
+    }
+    Some(Path::new(STD_DIR).join(format!("{}.doo", name)))
+}
+
+/// The source of the standard module at `path`, from `std_module_path`.
+pub(crate) fn std_module_source(path: &Path) -> Option<&'static str> {
+    if path.parent() != Some(Path::new(STD_DIR)) {
+        return None;
+    }
+    let name = path.file_stem()?.to_str()?;
+    MODULES
+        .iter()
+        .find(|(module, _)| *module == name)
+        .map(|(_, source)| *source)
+}
@@ -0,0 +1,40 @@
+use super::analyzer::SemanticAnalyzer;
+use super::types::SemanticError;
+use crate::parser::ast::{AstNode, TypeNode};
+
+/// Methods of string values: `s.len()`, `s.contains(part)`, `s.split(sep)`,
+/// `s.trim()`, `s.replace(from, to)` and `s.substring(start, end)`. None of
+/// them change the string; those returning a string return a new one, and
+/// `split` returns a `[Str]` of the pieces between the separators. Lengths
+/// and positions count bytes.
+impl SemanticAnalyzer {
+    /// Parameter and return types of string method `method`.
+    fn string_method_signature(method: &str) -> Option<(Vec<TypeNode>, TypeNode)> {
+        let string = TypeNode::String;
+        match method {
+            "len" => Some((vec![], TypeNode::Int)),
+            "contains" => Some((vec![string], TypeNode::Bool)),
+            "split" => Some((vec![string.clone()], TypeNode::Array(Box::new(string)))),
+            "trim" => Some((vec![], string)),
+            "replace" => Some((vec![string.clone(), string.clone()], string)),
+            "substring" => Some((vec![TypeNode::Int, TypeNode::Int], string)),
+            _ => None,
+        }
+    }
+
+    /// Type of `object.method(args)` on a string, once the arguments match
+    /// the method.
+    pub(crate) fn infer_string_method(
+        &self,
+        method: &str,
+        args: &[AstNode],
+    ) -> Result<TypeNode, SemanticError> {
+        let (params, ret_ty) =
+            Self::string_method_signature(method).ok_or_else(|| SemanticError::UnknownMethod {
+                found: TypeNode::String,
+                method: method.to_string(),
+            })?;
+        self.check_method_args(method, &params, args)?;
+        Ok(ret_ty)
+    }
+}
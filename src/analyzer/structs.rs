@@ -0,0 +1,327 @@
+use super::analyzer::SemanticAnalyzer;
+use super::types::{NamedError, SemanticError, TypeMismatch};
+use crate::parser::ast::{AstNode, Span, TypeNode};
+
+/// Name of the function implementing method `method` of struct `struct_name`,
+/// e.g. `User::greet`. `:` can't appear in identifiers, so it never clashes
+/// with a declared function.
+pub fn method_symbol(struct_name: &str, method: &str) -> String {
+    format!("{}::{}", struct_name, method)
+}
+
+/// Struct values: `User { name: "A", age: 3 }`, `u.name` and `u.age = 4;`.
+/// A struct name used as a type (`TypeRef("User")`) is resolved to
+/// `TypeNode::Struct` carrying the declared fields; nested struct fields stay
+/// `TypeRef`s there and are resolved when accessed.
+///
+/// Methods from `impl User { ... }` are ordinary functions named `User::greet`
+/// whose first parameter is `self`; `u.greet(x)` calls `User::greet(u, x)`.
+impl SemanticAnalyzer {
+    /// Records the fields of every struct declared in `nodes`, so struct names
+    /// can appear in signatures and literals before their declaration.
+    pub(crate) fn register_structs(&mut self, nodes: &[AstNode]) {
+        for node in nodes {
+            if let AstNode::StructDecl { name, fields, .. } = node {
+                self.struct_table
+                    .entry(name.clone())
+                    .or_insert_with(|| fields.clone());
+            }
+        }
+    }
+
+    /// The type of values of struct `name`, or None if no such struct is declared.
+    pub(crate) fn struct_type(&self, name: &str) -> Option<TypeNode> {
+        let fields = self.struct_table.get(name)?;
+        Some(TypeNode::Struct(
+            name.to_string(),
+            fields.iter().cloned().collect(),
+        ))
+    }
+
+    /// Replaces references to declared structs and enums anywhere inside `ty`
+    /// with their struct or enum type. Unknown names are left for the caller's type checks.
+    pub(crate) fn resolve_type_refs(&self, ty: &TypeNode) -> TypeNode {
+        let resolve = |inner: &TypeNode| Box::new(self.resolve_type_refs(inner));
+        match ty {
+            TypeNode::TypeRef(name) => self
+                .struct_type(name)
+                .or_else(|| self.enum_type(name))
+                .unwrap_or_else(|| ty.clone()),
+            TypeNode::Array(elem) => TypeNode::Array(resolve(elem)),
+            TypeNode::Map(key, value) => TypeNode::Map(resolve(key), resolve(value)),
+            TypeNode::Optional(inner) => TypeNode::Optional(resolve(inner)),
+            TypeNode::Variadic(elem) => TypeNode::Variadic(resolve(elem)),
+            TypeNode::Tuple(elems) => {
+                TypeNode::Tuple(elems.iter().map(|t| self.resolve_type_refs(t)).collect())
+            }
+            _ => ty.clone(),
+        }
+    }
+
+    /// Type of `field` on a value of type `object_ty`.
+    pub(crate) fn field_type(
+        &self,
+        object_ty: &TypeNode,
+        field: &str,
+    ) -> Result<TypeNode, SemanticError> {
+        let TypeNode::Struct(struct_name, fields) = object_ty else {
+            return Err(SemanticError::InvalidFieldAccess {
+                found: object_ty.clone(),
+                field: field.to_string(),
+            });
+        };
+        fields
+            .get(field)
+            .map(|ty| self.resolve_type_refs(ty))
+            .ok_or_else(|| SemanticError::UnknownField {
+                struct_name: struct_name.clone(),
+                field: field.to_string(),
+            })
+    }
+
+    /// Checks a struct literal: the struct exists and every declared field is
+    /// given exactly once with a value of its type.
+    pub(crate) fn infer_struct_literal(
+        &self,
+        name: &str,
+        fields: &[(String, AstNode)],
+    ) -> Result<TypeNode, SemanticError> {
+        let struct_ty = self.struct_type(name).ok_or_else(|| {
+            self.suggest(SemanticError::UndeclaredStruct(NamedError {
+                name: name.to_string(),
+            }))
+        })?;
+
+        for (i, (field, value)) in fields.iter().enumerate() {
+            if fields[..i].iter().any(|(seen, _)| seen == field) {
+                return Err(SemanticError::DuplicateField {
+                    struct_name: name.to_string(),
+                    field: field.clone(),
+                });
+            }
+            let expected = self.field_type(&struct_ty, field)?;
+            let found = self.infer_type(value)?;
+            if found != expected {
+                return Err(SemanticError::VarTypeMismatch(Box::new(TypeMismatch {
+                    expected,
+                    found,
+                    value: Some(Box::new(value.clone())),
+                    span: None,
+                })));
+            }
+        }
+
+        for (field, _) in &self.struct_table[name] {
+            if !fields.iter().any(|(given, _)| given == field) {
+                return Err(SemanticError::MissingField {
+                    struct_name: name.to_string(),
+                    field: field.clone(),
+                });
+            }
+        }
+        Ok(struct_ty)
+    }
+
+    /// Wraps struct literal values that go into optional fields.
+    pub(crate) fn coerce_struct_fields(
+        &self,
+        name: &str,
+        fields: &mut [(String, AstNode)],
+    ) -> Result<(), SemanticError> {
+        let Some(struct_ty) = self.struct_type(name) else {
+            return Ok(());
+        };
+        for (field, value) in fields.iter_mut() {
+            if let Ok(expected) = self.field_type(&struct_ty, field) {
+                self.coerce_optional(&expected, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Analyze the methods of an impl block. Their signatures were registered
+    /// under their `User::greet` names in the first pass; a trait impl must
+    /// match the trait first.
+    pub fn analyze_impl_block(&mut self, node: &mut AstNode) -> Result<(), SemanticError> {
+        let AstNode::ImplBlock {
+            struct_name,
+            trait_name,
+            methods,
+        } = node
+        else {
+            return Ok(());
+        };
+        if self.function_depth > 0 {
+            return Err(SemanticError::UnexpectedNode {
+                expected: format!("impl block for '{}' at the top level", struct_name),
+            });
+        }
+        if !self.struct_table.contains_key(struct_name.as_str()) {
+            return Err(self.suggest(SemanticError::UndeclaredStruct(NamedError {
+                name: struct_name.clone(),
+            })));
+        }
+        if let Some(trait_name) = trait_name {
+            self.check_trait_impl(trait_name, struct_name, methods)?;
+        }
+        for method in methods.iter_mut() {
+            self.analyze_node(method)?;
+        }
+        Ok(())
+    }
+
+    /// The function a method call runs: `User::greet` for `u.greet(..)` on a
+    /// `User`, or the overload its arguments select. Calls already resolved by
+    /// `coerce_call_args` name their function directly.
+    pub(crate) fn method_function(
+        &self,
+        object: &AstNode,
+        method: &str,
+        args: &[AstNode],
+    ) -> Result<String, SemanticError> {
+        if method.contains("::") {
+            return Ok(method.to_string());
+        }
+        let object_ty = self.infer_type(object)?;
+        let unknown = |found: TypeNode| SemanticError::UnknownMethod {
+            found,
+            method: method.to_string(),
+        };
+        let TypeNode::Struct(struct_name, _) = &object_ty else {
+            return Err(unknown(object_ty));
+        };
+
+        let symbol = method_symbol(struct_name, method);
+        if !self.function_table.contains_key(&symbol) && !self.overloads.contains_key(&symbol) {
+            return Err(unknown(object_ty));
+        }
+        // Overloads are picked with `self` as the first argument
+        let mut func = AstNode::Identifier(symbol, Span::default());
+        let self_and_args: Vec<AstNode> = std::iter::once(object.clone())
+            .chain(args.iter().cloned())
+            .collect();
+        self.resolve_overloaded_call(&mut func, &self_and_args)?;
+        match func {
+            AstNode::Identifier(name, _) => Ok(name),
+            _ => unreachable!("resolve_overloaded_call keeps the identifier"),
+        }
+    }
+
+    /// Type of `object.method(args)`: the method's return type, once the
+    /// arguments match its parameters after `self`.
+    pub(crate) fn infer_method_call(
+        &self,
+        object: &AstNode,
+        method: &str,
+        args: &[AstNode],
+    ) -> Result<TypeNode, SemanticError> {
+        match self.infer_type(object)? {
+            TypeNode::Array(elem) => return self.infer_array_method(object, &elem, method, args),
+            TypeNode::Map(key, value) => {
+                return self.infer_map_method(object, &key, &value, method, args)
+            }
+            TypeNode::String => return self.infer_string_method(method, args),
+            _ => {}
+        }
+        let function = self.method_function(object, method, args)?;
+        let (param_types, ret_ty) = &self.function_table[&function];
+        let params = &param_types[1..];
+
+        let arg_types = args
+            .iter()
+            .map(|arg| self.infer_type(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        // Arguments are either still plain or already coerced to the parameters
+        if params == arg_types.as_slice() || Self::args_coerce_to(params, &arg_types) {
+            return Ok(ret_ty.clone());
+        }
+        let variadic = matches!(params.last(), Some(TypeNode::Variadic(_)));
+        let mismatch = params.iter().zip(&arg_types).find(|(param, arg)| {
+            !Self::args_coerce_to(std::slice::from_ref(param), std::slice::from_ref(arg))
+        });
+        match mismatch {
+            Some((expected, found)) if variadic || params.len() == args.len() => {
+                Err(SemanticError::FunctionArgumentTypeMismatch {
+                    name: function,
+                    expected: Box::new(expected.clone()),
+                    found: found.clone(),
+                })
+            }
+            _ => Err(SemanticError::FunctionArgumentMismatch {
+                name: function,
+                expected: params.len(),
+                found: args.len(),
+            }),
+        }
+    }
+
+    /// Resolves `object.method(args)` to its function and coerces the
+    /// arguments against the parameters after `self`.
+    pub(crate) fn coerce_method_call(
+        &self,
+        object: &mut AstNode,
+        method: &mut String,
+        args: &mut Vec<AstNode>,
+    ) -> Result<(), SemanticError> {
+        self.coerce_call_args(object)?;
+        for arg in args.iter_mut() {
+            self.coerce_call_args(arg)?;
+        }
+        match self.infer_type(object)? {
+            TypeNode::Array(elem) => return self.coerce_array_method(&elem, method, args),
+            TypeNode::Map(key, value) => return self.coerce_map_method(&key, &value, method, args),
+            // String methods take no optional parameters
+            TypeNode::String => return Ok(()),
+            _ => {}
+        }
+        *method = self.method_function(object, method, args)?;
+        let params = self.function_table[method.as_str()].0[1..].to_vec();
+        self.pack_variadic_args(method, &params, args)?;
+        for (arg, param_ty) in args.iter_mut().zip(params.iter()) {
+            self.coerce_optional(param_ty, arg)?;
+        }
+        Ok(())
+    }
+
+    /// Analyze `object.field = value;`. The variable holding the struct must be
+    /// mutable and the value must have the field's type.
+    pub fn analyze_field_assignment(
+        &mut self,
+        object: &mut AstNode,
+        field: &str,
+        value: &mut AstNode,
+    ) -> Result<(), SemanticError> {
+        self.analyze_value_exprs(object)?;
+        self.coerce_call_args(object)?;
+        let expected = self.field_type(&self.infer_type(object)?, field)?;
+
+        // `a.b.c = v` and `users[0].name = v` mutate the value stored in `a` / `users`
+        let mut root = &*object;
+        while let AstNode::FieldAccess { object: inner, .. }
+        | AstNode::ElementAccess { array: inner, .. } = root
+        {
+            root = inner.as_ref();
+        }
+        if let AstNode::Identifier(name, _) = root {
+            if self.lookup_variable(name).is_some_and(|info| !info.mutable) {
+                return Err(SemanticError::InvalidAssignmentTarget {
+                    target: format!("Cannot assign to immutable variable '{}'", name),
+                });
+            }
+        }
+
+        self.analyze_value_exprs(value)?;
+        self.coerce_call_args(value)?;
+        self.coerce_optional(&expected, value)?;
+        let found = self.infer_type(value)?;
+        if found != expected {
+            return Err(SemanticError::VarTypeMismatch(Box::new(TypeMismatch {
+                expected,
+                found,
+                value: Some(Box::new(value.clone())),
+                span: None,
+            })));
+        }
+        Ok(())
+    }
+}
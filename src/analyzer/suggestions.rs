@@ -0,0 +1,107 @@
+use super::analyzer::SemanticAnalyzer;
+use super::builtins::BUILTIN_FUNCTIONS;
+use super::types::{NamedError, SemanticError};
+
+/// "Did you mean" suggestions: an error for a variable, function or type name
+/// that isn't declared names the declared one closest to it, when one is close
+/// enough to be a likely typo.
+impl SemanticAnalyzer {
+    /// `error` with the nearest declared name of the same kind attached as a
+    /// suggestion, if it is an error for an undeclared name and one is near.
+    pub(crate) fn suggest(&self, error: SemanticError) -> SemanticError {
+        let (name, candidates) = match &error {
+            SemanticError::UndeclaredVariable(NamedError { name }) => (name, self.variable_names()),
+            SemanticError::UndeclaredFunction(NamedError { name }) => (name, self.function_names()),
+            SemanticError::UndeclaredStruct(NamedError { name }) => (
+                name,
+                self.struct_table
+                    .keys()
+                    .chain(self.enum_table.keys())
+                    .cloned()
+                    .collect(),
+            ),
+            SemanticError::UndeclaredEnum(NamedError { name }) => {
+                (name, self.enum_table.keys().cloned().collect())
+            }
+            SemanticError::UndeclaredTrait(NamedError { name }) => {
+                (name, self.trait_table.keys().cloned().collect())
+            }
+            _ => return error,
+        };
+        match nearest_name(name, candidates) {
+            Some(suggestion) => SemanticError::DidYouMean {
+                suggestion,
+                error: Box::new(error),
+            },
+            None => error,
+        }
+    }
+
+    /// Names of the variables in scope.
+    fn variable_names(&self) -> Vec<String> {
+        self.symbol_table
+            .keys()
+            .chain(self.scope_stack.iter().flat_map(|scope| scope.keys()))
+            .cloned()
+            .collect()
+    }
+
+    /// Names a call can use: declared, imported and builtin functions, leaving
+    /// out methods (`User::greet`), mangled overloads and generic instances, and
+    /// the module-qualified names imported functions have (`utils$Twice`).
+    fn function_names(&self) -> Vec<String> {
+        let nested = self.local_functions.iter().flat_map(|names| names.keys());
+        self.function_table
+            .keys()
+            .chain(self.overloads.keys())
+            .filter(|name| !name.contains("::") && !name.contains("__") && !name.contains('$'))
+            .chain(nested)
+            .chain(self.function_aliases.keys())
+            .cloned()
+            .chain(BUILTIN_FUNCTIONS.iter().map(|name| name.to_string()))
+            .collect()
+    }
+}
+
+/// The candidate closest to `name` by edit distance, if within a third of
+/// the name's length (at least 1). Ties go to the alphabetically first.
+fn nearest_name(name: &str, mut candidates: Vec<String>) -> Option<String> {
+    let limit = (name.chars().count() / 3).max(1);
+    candidates.sort();
+    candidates.dedup();
+    candidates
+        .into_iter()
+        .filter(|candidate| candidate != name)
+        .map(|candidate| (edit_distance(name, &candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Edit distance counting swapped neighbours as one edit: the fewest
+/// single-character insertions, deletions, substitutions and adjacent
+/// transpositions that turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // d[i][j]: distance between the first i chars of a and the first j of b
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
@@ -1,8 +1,12 @@
 #[cfg(test)]
 mod analyzer_tests {
+    use crate::analyzer::files::MemoryFiles;
     use crate::analyzer::SemanticAnalyzer;
+    use crate::frontend::{front_end, CompileResult, FrontEndOptions, Lowered};
     use crate::lexar::lexer::lex;
     use crate::parser::Parser;
+    use std::path::Path;
+    use std::rc::Rc;
 
     fn analyze_code(input: &str) -> Result<(), String> {
         let tokens = lex(input);
@@ -24,6 +28,22 @@ mod analyzer_tests {
         }
     }
 
+    /// Runs the whole front end on a program named `main.doo`, reading the
+    /// files it imports and includes from `files` only.
+    fn front_end_in_memory(
+        files: MemoryFiles,
+        opts: FrontEndOptions,
+    ) -> impl Fn(&str) -> (CompileResult, Option<Lowered>) {
+        let files = Rc::new(files);
+        move |source| front_end(Path::new("main.doo"), source, &opts, files.clone())
+    }
+
+    #[test]
+    fn test_missing_main() {
+        let error = analyze_code("fn helper() -> Int { return 42; }").unwrap_err();
+        assert!(error.contains("MissingMain"), "{}", error);
+    }
+
     // =====================
     // Variable Declarations
     // =====================
@@ -33,6 +53,86 @@ mod analyzer_tests {
         assert!(analyze_code(input).is_ok());
     }
 
+    #[test]
+    fn test_float_variable_declaration() {
+        let input = r#"
+            fn half(x: Float) -> Float { return x / 2.0; }
+            fn main() {
+                let mut x: Float = 3.14;
+                x += 1.5;
+                let y = half(x) * -2.0;
+                let big = y > 1.0;
+                print(x, y, big);
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+    }
+
+    #[test]
+    fn test_int64_variable_declaration() {
+        let input = r#"
+            fn twice(x: Long) -> Int64 { return x * 2L; }
+            fn main() {
+                let mut total: Int64 = 0;
+                total += 3000000000L;
+                let doubled = twice(total);
+                print(doubled, doubled > total);
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_int64_int_mix() {
+        assert!(analyze_code("fn main() { let x = 1L + 2; }").is_err());
+        assert!(analyze_code("fn main() { let x: Int = 5L; }").is_err());
+    }
+
+    #[test]
+    fn test_invalid_float_int_mix() {
+        assert!(analyze_code("fn main() { let x = 1.5 + 2; }").is_err());
+        assert!(analyze_code("fn main() { let x: Float = 2; }").is_err());
+    }
+
+    #[test]
+    fn test_optional_declarations() {
+        let input = r#"
+            fn find(limit: Int) -> Int? {
+                if limit > 3 {
+                    return limit;
+                }
+                return null;
+            }
+            fn main() {
+                let mut a: Int? = null;
+                a = 5;
+                let name: Str? = "doo";
+                let b = find(7) ?? 0;
+                if let n = find(2) {
+                    print(n + b);
+                } else {
+                    print(name ?? "none");
+                }
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_optional_usage() {
+        // null needs an annotation to know which optional it is
+        let err = analyze_code("fn main() { let x = null; }").unwrap_err();
+        assert!(err.contains("UntypedNull"));
+        // an optional can't be used as its payload type without unwrapping
+        assert!(analyze_code("fn main() { let x: Int? = 1; let y: Int = x; }").is_err());
+        assert!(analyze_code("fn main() { let x: Int? = 1; let y = x + 1; }").is_err());
+        // null can't go into a non-optional
+        assert!(analyze_code("fn main() { let x: Int = null; }").is_err());
+        // if let requires an optional, and ?? a matching default
+        assert!(analyze_code("fn main() { let x = 1; if let y = x { print(y); } }").is_err());
+        assert!(analyze_code(r#"fn main() { let x: Int? = 1; let y = x ?? "a"; }"#).is_err());
+    }
+
     #[test]
     fn test_mutable_assignment() {
         let input = "fn main() { let mut x = 5; x = 10; }";
@@ -98,6 +198,35 @@ mod analyzer_tests {
         assert!(analyze_code(input).is_ok());
     }
 
+    #[test]
+    fn test_variadic_function_calls() {
+        let input = r#"
+            fn sum(args: Int...) -> Int {
+                let mut total = 0;
+                for a in args { total += a; }
+                return total;
+            }
+            fn join(sep: Str, parts: Str...) -> Int { return 0; }
+            fn main() {
+                let none = sum();
+                let one = sum(1);
+                let many: Int = sum(1, 2, 3);
+                join(",", "a", "b");
+                if sum(1, 2) > 2 { print(many); }
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+
+        let err = |code: &str| analyze_code(code).unwrap_err();
+        let sum = "fn sum(args: Int...) -> Int { return 0; }";
+        assert!(err(&format!("{} fn main() {{ sum(1, \"a\"); }}", sum))
+            .contains("FunctionArgumentTypeMismatch"));
+        assert!(
+            err("fn join(sep: Str, parts: Str...) {} fn main() { join(); }")
+                .contains("FunctionArgumentMismatch")
+        );
+    }
+
     #[test]
     fn test_function_with_map_param() {
         let input =
@@ -348,6 +477,47 @@ mod analyzer_tests {
         assert!(analyze_code(input).is_ok());
     }
 
+    #[test]
+    fn test_for_loop_range_with_step() {
+        let input = r#"
+            fn main() {
+                let n = 3;
+                for i in 10..0 step -2 { print(i); }
+                for i in 0..=20 step n { print(i); }
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+
+        let err = |code: &str| analyze_code(code).unwrap_err();
+        assert!(err("fn main() { for i in 0..10 step 0 { print(i); } }").contains("ZeroRangeStep"));
+        assert!(err("fn main() { for i in 0..10 step \"a\" { print(i); } }")
+            .contains("RangeIterationTypeMismatch"));
+        assert!(
+            err("fn main() { let a = [1, 2]; for x in a step 2 { print(x); } }")
+                .contains("UnexpectedNode")
+        );
+    }
+
+    #[test]
+    fn test_for_loop_index_and_item() {
+        let input = r#"
+            fn main() {
+                let names = ["a", "b"];
+                for i, name in names {
+                    let n: Int = i + 1;
+                    let s: Str = name;
+                }
+                for (_, v) in [1, 2] { print(v); }
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+        assert!(
+            analyze_code("fn main() { let a = [1]; for i, x, y in a { print(x); } }")
+                .unwrap_err()
+                .contains("InvalidAssignmentTarget")
+        );
+    }
+
     #[test]
     fn test_for_loop_over_map_destructuring() {
         let input =
@@ -453,6 +623,233 @@ mod analyzer_tests {
         assert!(analyze_code(input).is_ok());
     }
 
+    #[test]
+    fn test_nested_array_types() {
+        let input = r#"
+            fn rowSum(row: [Int]) -> Int {
+                let mut total = 0;
+                for x in row {
+                    total += x;
+                }
+                return total;
+            }
+            fn main() {
+                let grid: [[Int]] = [[1, 2], [3]];
+                let corner: Int = grid[1][0];
+                for row in grid {
+                    print(rowSum(row), row);
+                }
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+        assert!(analyze_code("fn main() { let grid: [[Int]] = [1, 2]; }").is_err());
+        assert!(analyze_code("fn main() { let grid = [[1], [\"a\"]]; }").is_err());
+    }
+
+    #[test]
+    fn test_map_collection_values() {
+        let input = r#"
+            fn main() {
+                let scores: {Str: [Int]} = {"ann": [90, 85], "bob": [70]};
+                let nested: {Str: {Str: Int}} = {"a": {"x": 1}, "b": {"y": 2, "z": 3}};
+                for (name, marks) in scores {
+                    for m in marks {
+                        print(name, m);
+                    }
+                }
+                print(scores, nested);
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+        assert!(analyze_code("fn main() { let m: {Str: [Int]} = {\"a\": 1}; }").is_err());
+        assert!(analyze_code("fn main() { let m = {\"a\": [1], \"b\": [\"x\"]}; }").is_err());
+    }
+
+    #[test]
+    fn test_conversion_builtins() {
+        let input = r#"
+            fn main() {
+                let label: Str = "count: " + toStr(42);
+                let big: Str = toStr(5L);
+                let flag: Str = toStr(1 < 2);
+                let n: Int = toInt("17") + toInt(2.5);
+                let ratio: Float = toFloat("0.5") + toFloat(n);
+                let wide: Int64 = toInt64(n) + toInt64(2.5) + toInt64(5L);
+                print(label, big, flag, ratio, toStr(ratio), wide);
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+        assert!(analyze_code("fn main() { let n: Int = toStr(1); }").is_err());
+        assert!(analyze_code("fn main() { let n = toInt([1]); }").is_err());
+        assert!(analyze_code("fn main() { let s = toStr(1, 2); }").is_err());
+        assert!(analyze_code("fn toStr(x: Int) -> Str { return \"\"; } fn main() { }").is_err());
+    }
+
+    #[test]
+    fn test_format_and_parse_builtins() {
+        let input = r#"
+            fn main() {
+                let pi: Str = format(3.14159, 2);
+                let n: Int? = parseInt("42");
+                let x = parseFloat(" 2.5") ?? 0.0;
+                print(pi, n ?? 0, x);
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+
+        let cases = [
+            (
+                "fn main() { let s = format(3, 2); }",
+                "FunctionArgumentTypeMismatch",
+            ),
+            (
+                "fn main() { let s = format(3.5); }",
+                "FunctionArgumentMismatch",
+            ),
+            (
+                "fn main() { let n: Int = parseInt(\"1\"); }",
+                "VarTypeMismatch",
+            ),
+            (
+                "fn main() { let n = parseFloat(1.5); }",
+                "FunctionArgumentTypeMismatch",
+            ),
+            (
+                "fn parseInt(s: Str) -> Int { return 0; } fn main() { }",
+                "Redeclaration",
+            ),
+        ];
+        for (input, expected) in cases {
+            let err = analyze_code(input).unwrap_err();
+            assert!(err.contains(expected), "{}: {}", input, err);
+        }
+    }
+
+    #[test]
+    fn test_match_exhaustiveness() {
+        let input = r#"
+            fn describe(n: Int) -> Str {
+                match n {
+                    0 => { return "zero"; }
+                    1 | 2 => { return "small"; }
+                    _ => { return "many"; }
+                }
+            }
+            fn main() {
+                let flag = 1 < 2;
+                let word: Str = match flag { true => "yes", false => "no" };
+                let code = match word { "yes" => 1, _ => 0 };
+                match code {
+                    -1 => { print("negative"); }
+                    _ => { print(describe(code)); }
+                }
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+
+        let err = |code: &str| analyze_code(code).unwrap_err();
+        assert!(
+            err("fn main() { let n = 1; match n { 1 => { print(1); } } }")
+                .contains("NonExhaustiveMatch")
+        );
+        assert!(
+            err("fn main() { let b = true; match b { true => { print(1); } } }")
+                .contains("NonExhaustiveMatch")
+        );
+        assert!(
+            err("fn main() { let n = 1; match n { _ => { print(1); }, 2 => { print(2); } } }")
+                .contains("UnreachableMatchArm")
+        );
+        assert!(
+            err("fn main() { let n = 1; match n { 1 => { print(1); }, 1 => { print(2); }, _ => { print(3); } } }")
+                .contains("UnreachableMatchArm")
+        );
+        assert!(err(
+            "fn main() { let n = 1; match n { \"a\" => { print(1); }, _ => { print(2); } } }"
+        )
+        .contains("MatchPatternTypeMismatch"));
+        assert!(
+            err("fn main() { let n = 1; let s = match n { 1 => \"a\", _ => 2 }; }")
+                .contains("MatchArmTypeMismatch")
+        );
+        assert!(
+            err("fn main() { let f = 1.5; match f { _ => { print(f); } } }")
+                .contains("InvalidMatchType")
+        );
+    }
+
+    #[test]
+    fn test_if_expression() {
+        let input = r#"
+            fn pick(n: Int) -> Str {
+                return if n > 0 { "positive" } else if n < 0 { "negative" } else { "zero" };
+            }
+            fn main() {
+                let n: Int = if 1 < 2 { 10 } else { 20 };
+                print(pick(n), n + if n > 5 { 1 } else { 0 });
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+        assert!(analyze_code("fn main() { let x = if 1 { 1 } else { 2 }; }")
+            .unwrap_err()
+            .contains("InvalidConditionType"));
+        assert!(
+            analyze_code("fn main() { let x = if true { 1 } else { \"a\" }; }")
+                .unwrap_err()
+                .contains("IfBranchTypeMismatch")
+        );
+    }
+
+    #[test]
+    fn test_block_expression_values() {
+        let input = r#"
+            fn main() {
+                let a = 3;
+                let y: Int = { let t = a * a; t + 1 };
+                let mut s = "";
+                s = { let base = "x"; base + "y" };
+                print({ let n = 2; n * y }, s);
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+
+        let err = |code: &str| analyze_code(code).unwrap_err();
+        // Variables declared in the block don't outlive it
+        assert!(
+            err("fn main() { let y = { let t = 1; t }; print(t); }").contains("UndeclaredVariable")
+        );
+        assert!(err("fn main() { let y: Str = { let t = 1; t }; }").contains("VarTypeMismatch"));
+    }
+
+    #[test]
+    fn test_loop_break_values() {
+        let input = r#"
+            fn main() {
+                let mut i = 0;
+                let found: Int = for {
+                    i += 1;
+                    for j in 0..3 {
+                        if j == 1 { break; }
+                    }
+                    if i * i > 20 { break i; }
+                };
+                let mut word = "";
+                word = for { break "done"; };
+                print(found, word);
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+
+        let err = |code: &str| analyze_code(code).unwrap_err();
+        assert!(err("fn main() { let v = for { break; }; }").contains("UnexpectedNode"));
+        assert!(err("fn main() { let v = for { print(1); }; }").contains("UnexpectedNode"));
+        assert!(err("fn main() { for { break 1; } }").contains("UnexpectedNode"));
+        assert!(
+            err("fn main() { let b = true; let v = for { if b { break 1; } break \"a\"; }; }")
+                .contains("BreakValueTypeMismatch")
+        );
+    }
+
     // Invalid type/misc cases
     #[test]
     fn test_type_mismatch() {
@@ -487,6 +884,1345 @@ mod analyzer_tests {
         assert!(analyze_code(input).is_err());
     }
 
+    #[test]
+    fn test_function_overloads() {
+        let input = r#"
+            fn add(a: Int, b: Int) -> Int { return a + b; }
+            fn add(a: Float, b: Float) -> Float { return a + b; }
+            fn add(a: Str, b: Str) -> Str { return a + b; }
+            fn main() {
+                let i: Int = add(1, 2);
+                let f: Float = add(1.5, 2.5);
+                let s: Str = add("a", add("b", "c"));
+                print(add(i, i));
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+
+        let overloads = "fn add(a: Int, b: Int) -> Int { return a + b; } \
+                         fn add(a: Float, b: Float) -> Float { return a + b; }";
+        assert!(
+            analyze_code(&format!("{} fn main() {{ add(1, 2.5); }}", overloads))
+                .unwrap_err()
+                .contains("NoMatchingOverload")
+        );
+        assert!(analyze_code(&format!(
+            "{} fn add(x: Int, y: Int) -> Int {{ return x; }} fn main() {{ }}",
+            overloads
+        ))
+        .unwrap_err()
+        .contains("FunctionRedeclaration"));
+    }
+
+    #[test]
+    fn test_multiple_return_values() {
+        let divmod = "fn divmod(a: Int, b: Int) -> (Int, Int) { return a / b, a % b; }";
+        assert!(analyze_code(&format!(
+            "{} fn main() {{ let (q, r) = divmod(7, 2); print(q + r); }}",
+            divmod
+        ))
+        .is_ok());
+        assert!(analyze_code(&format!(
+            "{} fn main() {{ let (q, r, x) = divmod(7, 2); }}",
+            divmod
+        ))
+        .unwrap_err()
+        .contains("TupleAssignmentMismatch"));
+        assert!(analyze_code(
+            "fn divmod(a: Int, b: Int) -> (Int, Int) { return a / b; } fn main() { }"
+        )
+        .unwrap_err()
+        .contains("ReturnTypeMismatch"));
+    }
+
+    #[test]
+    fn test_nested_functions() {
+        let input = r#"
+            fn outer(x: Int) -> Int {
+                fn square(n: Int) -> Int { return n * n; }
+                fn twice(n: Int) -> Int { return square(n) + square(n); }
+                return twice(x) + 1;
+            }
+            fn main() { print(outer(3)); }
+        "#;
+        assert!(analyze_code(input).is_ok());
+
+        // Nested functions aren't visible outside their enclosing function
+        let hidden = r#"
+            fn outer() { fn inner() { } inner(); }
+            fn main() { inner(); }
+        "#;
+        assert!(analyze_code(hidden)
+            .unwrap_err()
+            .contains("UndeclaredFunction"));
+
+        // ...and don't capture its locals
+        let captured = r#"
+            fn outer() -> Int {
+                let base = 10;
+                fn inner(n: Int) -> Int { return n + base; }
+                return inner(1);
+            }
+            fn main() { }
+        "#;
+        assert!(analyze_code(captured)
+            .unwrap_err()
+            .contains("CapturedOuterVariable"));
+    }
+
+    #[test]
+    fn test_struct_values() {
+        let input = r#"
+            fn older(u: User) -> User {
+                let mut next = u;
+                next.age = u.age + 1;
+                return next;
+            }
+            struct Address { city: Str }
+            struct User { name: Str, age: Int, home: Address }
+            fn main() {
+                let mut u = User { name: "A", age: 3, home: Address { city: "Oslo" } };
+                u.home.city = "Bergen";
+                let v: User = older(u);
+                print(v.name, v.home.city);
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+    }
+
+    #[test]
+    fn test_struct_value_errors() {
+        let with_main = |body: &str| {
+            format!(
+                "struct User {{ name: Str, age: Int }} fn main() {{ {} }}",
+                body
+            )
+        };
+        let cases = [
+            (r#"let u = Player { name: "A" };"#, "UndeclaredStruct"),
+            (r#"let u = User { name: "A" };"#, "MissingField"),
+            (
+                r#"let u = User { name: "A", age: 3, email: "x" };"#,
+                "UnknownField",
+            ),
+            (
+                r#"let u = User { name: "A", age: "3" };"#,
+                "VarTypeMismatch",
+            ),
+            (
+                r#"let u = User { name: "A", age: 3 }; print(u.email);"#,
+                "UnknownField",
+            ),
+            ("let n = 5; print(n.age);", "InvalidFieldAccess"),
+            (
+                r#"let u = User { name: "A", age: 3 }; u.age = 4;"#,
+                "InvalidAssignmentTarget",
+            ),
+            (
+                r#"let mut u = User { name: "A", age: 3 }; u.age = "4";"#,
+                "VarTypeMismatch",
+            ),
+        ];
+        for (body, expected) in cases {
+            let err = analyze_code(&with_main(body)).unwrap_err();
+            assert!(err.contains(expected), "{}: {}", body, err);
+        }
+
+        let undeclared_field_type = "struct User { home: Address } fn main() { }";
+        assert!(analyze_code(undeclared_field_type)
+            .unwrap_err()
+            .contains("UndeclaredStruct"));
+    }
+
+    #[test]
+    fn test_struct_methods() {
+        let input = r#"
+            struct User { name: Str, age: Int }
+            impl User {
+                fn greet(self, greeting: Str) -> Str { return greeting + ", " + self.name; }
+                fn older(self, years: Int) -> User {
+                    return User { name: self.name, age: self.age + years };
+                }
+                fn birthday(self) { self.age = self.age + 1; }
+            }
+            // A free function can share a method's name
+            fn greet() -> Str { return "hi"; }
+            fn main() {
+                let u = User { name: "A", age: 3 };
+                u.birthday();
+                let msg: Str = u.older(2).greet("Hello");
+                print(msg, greet());
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+    }
+
+    #[test]
+    fn test_weak_struct_fields() {
+        let input = r#"
+            struct Node { name: Str, weak parent: Node? }
+            fn main() {
+                let root = Node { name: "root", parent: null };
+                let child = Node { name: "leaf", parent: root };
+                if let p = child.parent { print(p.name); }
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+
+        for field in ["weak parent: Node", "weak count: Int?"] {
+            let input = format!("struct Node {{ {} }} fn main() {{ }}", field);
+            let err = analyze_code(&input).unwrap_err();
+            assert!(format!("{:?}", err).contains("WeakFieldType"), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_arena_function_return_types() {
+        let input = r#"
+            #[arena]
+            fn total(n: Int) -> Int {
+                let mut parts = [0];
+                for i in 0..n { parts.push(i); }
+                return parts.sum();
+            }
+            #[arena]
+            fn report(name: Str) { print("hello " + name); }
+            fn main() { print(total(3)); report("doo"); }
+        "#;
+        assert!(analyze_code(input).is_ok());
+
+        for ret in ["Str", "[Int]", "(Int, Str)"] {
+            let input = format!(
+                "#[arena] fn f() -> {} {{ panic(\"no\"); }} fn main() {{ }}",
+                ret
+            );
+            let err = analyze_code(&input).unwrap_err();
+            assert!(
+                format!("{:?}", err).contains("ArenaReturnType"),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_extern_functions() {
+        let input = r#"
+            extern "C" fn puts(s: Str) -> Int;
+            extern "C" fn fabs(x: Float) -> Float;
+            fn main() { let n = puts("hi"); print(n + 1, fabs(-2.5)); }
+        "#;
+        assert!(analyze_code(input).is_ok());
+
+        let cases = [
+            (r#"extern "C" fn f(xs: [Int]);"#, "InvalidExternType"),
+            (r#"extern "C" fn f() -> Str;"#, "InvalidExternType"),
+            (r#"extern "C" fn panic(x: Int);"#, "FunctionRedeclaration"),
+            (
+                r#"extern "C" fn f(x: Int); fn f(x: Str) { }"#,
+                "FunctionRedeclaration",
+            ),
+            (
+                r#"fn f(x: Str) { } extern "C" fn f(x: Int);"#,
+                "FunctionRedeclaration",
+            ),
+            (
+                r#"extern "C" fn f(x: Int); fn g() { f("a"); }"#,
+                "FunctionArgumentTypeMismatch",
+            ),
+        ];
+        for (decl, expected) in cases {
+            let input = format!("{} fn main() {{ }}", decl);
+            let err = analyze_code(&input).unwrap_err();
+            assert!(format!("{:?}", err).contains(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_intrinsic_functions() {
+        let input = r#"
+            #[intrinsic("llvm.ctlz.i32")] extern fn clz(x: Int, zeroIsPoison: Bool) -> Int;
+            #[intrinsic("llvm.sqrt.f64")] extern fn sqrt(x: Float) -> Float;
+            fn main() { print(clz(8, false), sqrt(2.0)); }
+        "#;
+        assert!(analyze_code(input).is_ok());
+
+        let cases = [
+            (
+                r#"#[intrinsic("ctpop")] extern fn f(x: Int) -> Int;"#,
+                "UnexpectedNode",
+            ),
+            (
+                r#"#[intrinsic("llvm.foo")] extern fn f(s: Str);"#,
+                "InvalidExternType",
+            ),
+        ];
+        for (decl, expected) in cases {
+            let input = format!("{} fn main() {{ }}", decl);
+            let err = analyze_code(&input).unwrap_err();
+            assert!(format!("{:?}", err).contains(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_c_string_builtins() {
+        let input = r#"
+            extern "C" fn getenv(name: CStr) -> CStr;
+            extern "C" fn strdup(s: CStr) -> CStr;
+            export "C" fn greet(name: CStr) -> CStr { return name; }
+            fn main() {
+                let home: Str? = fromCStr(getenv(cStr("HOME")));
+                let copy: Str = takeCStr(strdup(toCStr("doo"))) ?? "";
+                print(home ?? "", copy);
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+
+        let cases = [
+            // Strings go to a CStr parameter through cStr or toCStr only
+            (
+                r#"extern "C" fn getenv(name: CStr) -> CStr; fn main() { getenv("HOME"); }"#,
+                "FunctionArgumentTypeMismatch",
+            ),
+            (
+                r#"fn main() { let s: Str = fromCStr(cStr("a")); }"#,
+                "TypeMismatch",
+            ),
+            (
+                r#"fn main() { print(fromCStr("a")); }"#,
+                "FunctionArgumentTypeMismatch",
+            ),
+        ];
+        for (input, expected) in cases {
+            let err = analyze_code(input).unwrap_err();
+            assert!(format!("{:?}", err).contains(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_include_files() {
+        let dir = std::env::temp_dir().join(format!("doo_include_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("data")).unwrap();
+        std::fs::write(dir.join("data/page.html"), "<p>hi</p>\n").unwrap();
+        std::fs::write(dir.join("data/blob.bin"), [0xff, 0x00, 0x41]).unwrap();
+        let analyze = |input: &str| {
+            let tokens = lex(input);
+            let mut ast = Parser::new(&tokens).parse_program().unwrap();
+            let mut analyzer = SemanticAnalyzer::new(Some(dir.clone()));
+            let crate::parser::ast::AstNode::Program(ref mut nodes) = ast else {
+                unreachable!("parse_program returns a Program");
+            };
+            analyzer
+                .analyze_program(nodes)
+                .map(|_| format!("{:?}", nodes))
+                .map_err(|e| format!("{:?}", e))
+        };
+
+        // Paths start from the source file's directory; the calls become literals
+        let nodes = analyze(
+            r#"fn main() {
+                let page: Str = includeStr("data/page.html");
+                let blob: [Int] = includeBytes("data/blob.bin");
+                print(page, blob);
+            }"#,
+        )
+        .unwrap();
+        assert!(
+            nodes.contains(r#"StringLiteral("<p>hi</p>\n")"#),
+            "{}",
+            nodes
+        );
+        assert!(nodes
+            .contains("ArrayLiteral([NumberLiteral(255), NumberLiteral(0), NumberLiteral(65)])"));
+        assert!(!nodes.contains("include"));
+
+        let cases = [
+            (r#"includeStr("data/missing.txt")"#, "IncludeFailed"),
+            (r#"includeStr("data/blob.bin")"#, "IncludeFailed"),
+            (r#"includeBytes("data/" + "page.html")"#, "UnexpectedNode"),
+        ];
+        for (call, expected) in cases {
+            let err = analyze(&format!("fn main() {{ let x = {}; }}", call)).unwrap_err();
+            assert!(err.contains(expected), "{}: {}", call, err);
+        }
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_memory_files() {
+        // Imports and includes come from the files handed over, not the disk
+        let mut files = MemoryFiles::new();
+        files.insert("math.doo", "fn Square(x: Int) -> Int { return x * x; }\n");
+        files.insert("greeting.txt", "hello");
+        let compile = front_end_in_memory(files, Default::default());
+
+        let (result, lowered) = compile(
+            r#"import math::Square;
+            fn main() {
+                print(includeStr("greeting.txt"), Square(4));
+            }"#,
+        );
+        assert!(result.success, "{:?}", result.error_count);
+        let mir = lowered.unwrap().program.to_string();
+        assert!(mir.contains("fn math$Square("), "{}", mir);
+
+        let (result, lowered) = compile("import geometry::Area;\nfn main() { }");
+        assert!(!result.success);
+        assert_eq!(result.diagnostics[0].filename, "main.doo");
+        assert!(lowered.is_none());
+    }
+
+    #[test]
+    fn test_std_os_module() {
+        // The standard library is built in: no file is read for it
+        let compile = front_end_in_memory(MemoryFiles::new(), Default::default());
+        let (result, lowered) = compile(
+            r#"import std::os;
+            fn main() {
+                let fd = os::Open("notes.txt", 0);
+                let text = os::Read(fd, 64) ?? "";
+                os::Write(1, text);
+                os::Close(fd);
+                print(os::GetEnv("HOME") ?? "/");
+                os::Exit(0);
+            }"#,
+        );
+        assert!(result.success, "{:?}", result.error_count);
+        let mir = lowered.unwrap().program.to_string();
+        // The C functions come along with the functions calling them
+        assert!(
+            mir.contains("extern fn read(fd: Int, buf: CStr, count: Int64) -> Int64"),
+            "{}",
+            mir
+        );
+        assert!(mir.contains("fn std$os$GetEnv(name: String) -> String? {"));
+
+        // They stay private to the module
+        let (result, _) = compile("import std::os::Read;\nfn main() { close(0); }");
+        assert_eq!(result.diagnostics[0].code, "E0104");
+        let (result, _) = compile("import std::net::Connect;\nfn main() { }");
+        assert!(result.diagnostics[0].message.contains("std::net::Connect"));
+    }
+
+    #[test]
+    fn test_module_imports() {
+        use crate::mir::Interpreter;
+
+        let mut files = MemoryFiles::new();
+        files.insert(
+            "utils.doo",
+            "fn helper(x: Int) -> Int { return x * 2; }\n\
+             fn Twice(x: Int) -> Int { return helper(x); }\n\
+             fn Add(a: Int, b: Int) -> Int { return a + b; }\n",
+        );
+        files.insert(
+            "geo/shapes.doo",
+            "import utils;\n\
+             fn helper(x: Int) -> Int { return x * x; }\n\
+             fn Area(x: Int) -> Int { return helper(x); }\n\
+             fn Perimeter(x: Int) -> Int { return utils::Twice(utils::Add(x, x)); }\n",
+        );
+        let compile = front_end_in_memory(files, Default::default());
+
+        // A whole module is called through its name, a function imported by
+        // name directly; each module's `helper` stays its own
+        let (result, lowered) = compile(
+            r#"import utils;
+            import geo::shapes::Area;
+            import geo::shapes;
+            fn helper() -> Int { return 1000; }
+            fn main() {
+                print(Area(3), utils::Twice(4), utils::Add(1, 2));
+                print(shapes::Perimeter(5), helper());
+            }"#,
+        );
+        assert!(result.success, "{:?}", result.diagnostics);
+        let program = lowered.unwrap().program;
+        let mir = program.to_string();
+        assert!(
+            mir.contains("fn geo$shapes$helper(x: Int) -> Int {"),
+            "{}",
+            mir
+        );
+        assert!(mir.contains("fn utils$helper(x: Int) -> Int {"), "{}", mir);
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = Interpreter::new(&program, &mut out, &mut err).run();
+        assert_eq!(code, Ok(0));
+        assert_eq!(String::from_utf8(out).unwrap(), "9 8 3\n20 1000\n");
+
+        // Private functions can't be imported or called from outside
+        let (result, _) = compile("import utils::helper;\nfn main() { }");
+        assert_eq!(result.diagnostics[0].code, "E0706");
+        let (result, _) = compile("import utils;\nfn main() { print(utils::helper(1)); }");
+        assert_eq!(result.diagnostics[0].code, "E0104");
+        // Only what is imported is in scope
+        let (result, _) = compile("import utils::Twice;\nfn main() { print(Add(1, 2)); }");
+        assert_eq!(result.diagnostics[0].code, "E0104");
+        // An imported name can't be declared again
+        let (result, _) =
+            compile("import utils::Twice;\nfn Twice(x: Int) -> Int { return x; }\nfn main() { }");
+        assert_eq!(result.diagnostics[0].code, "E0101");
+        let (result, _) = compile("import tools;\nfn main() { }");
+        assert!(result.diagnostics[0]
+            .message
+            .contains("module not found: tools"));
+    }
+
+    #[test]
+    fn test_dependency_imports() {
+        use crate::mir::Interpreter;
+        use std::path::PathBuf;
+
+        // The dependency `mathlib` imports its own module `geo`, and so does
+        // the program
+        let mut files = MemoryFiles::new();
+        files.insert(
+            "deps/mathlib/lib.doo",
+            "import geo;\n\
+             fn Square(x: Int) -> Int { return geo::Area(x); }\n",
+        );
+        files.insert(
+            "deps/mathlib/geo.doo",
+            "fn Area(x: Int) -> Int { return x * x; }\n",
+        );
+        files.insert("geo.doo", "fn Area(x: Int) -> Int { return x + 1; }\n");
+        let opts = FrontEndOptions {
+            dependencies: vec![("mathlib".to_string(), PathBuf::from("deps/mathlib/lib.doo"))],
+            ..FrontEndOptions::default()
+        };
+        let compile = front_end_in_memory(files, opts);
+
+        let (result, lowered) = compile(
+            r#"import mathlib;
+            import mathlib::geo;
+            import geo::Area;
+            fn main() { print(mathlib::Square(3), geo::Area(4), Area(5)); }"#,
+        );
+        assert!(result.success, "{:?}", result.diagnostics);
+        let program = lowered.unwrap().program;
+        let mir = program.to_string();
+        assert!(
+            mir.contains("fn mathlib$Square(x: Int) -> Int {"),
+            "{}",
+            mir
+        );
+        assert!(
+            mir.contains("fn mathlib$geo$Area(x: Int) -> Int {"),
+            "{}",
+            mir
+        );
+        assert!(mir.contains("fn geo$Area(x: Int) -> Int {"), "{}", mir);
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = Interpreter::new(&program, &mut out, &mut err).run();
+        assert_eq!(code, Ok(0));
+        assert_eq!(String::from_utf8(out).unwrap(), "9 16 6\n");
+
+        // Nothing named `tools` in the dependency: neither module nor function
+        let (result, _) = compile("import mathlib::tools;\nfn main() { }");
+        assert_eq!(result.diagnostics[0].code, "E0104");
+    }
+
+    #[test]
+    fn test_test_functions() {
+        use crate::analyzer::files::MemoryFiles;
+        use crate::frontend::{front_end, FrontEndOptions};
+        use crate::testing::{report, run_interpreted};
+        use std::path::Path;
+        use std::rc::Rc;
+
+        let input = r#"
+            fn double(x: Int) -> Int { return x * 2; }
+            #[test] fn doublesSmall() { assert(double(2) == 4, "2 doubles to 4"); }
+            #[test] fn doublesThree() {
+                let n = double(3);
+                print("checking", n);
+                assert(n == 7, "6 is not 7");
+            }
+            fn main() { print(double(3)); }
+        "#;
+        let opts = FrontEndOptions {
+            test: true,
+            ..Default::default()
+        };
+        let (result, lowered) = front_end(
+            Path::new("main.doo"),
+            input,
+            &opts,
+            Rc::new(MemoryFiles::new()),
+        );
+        assert!(result.success, "{:?}", result.diagnostics);
+        let lowered = lowered.unwrap();
+        assert_eq!(lowered.tests, ["doublesSmall", "doublesThree"]);
+        // The tests stand in for the program's main
+        assert!(lowered.program.functions.iter().all(|f| f.name != "main"));
+
+        let outcomes = run_interpreted(&lowered.program, &lowered.tests, false);
+        assert!(outcomes[0].passed);
+        assert!(!outcomes[1].passed);
+        assert_eq!(
+            outcomes[1].output,
+            "checking 6\npanic: assertion failed: 6 is not 7\n"
+        );
+        let summary = report(&outcomes);
+        assert!(summary.contains("test doublesSmall ... ok\n"));
+        assert!(summary.contains("test doublesThree ... FAILED\n"));
+        assert!(summary.ends_with("test result: FAILED. 1 passed; 1 failed\n"));
+
+        // Other builds keep main
+        assert!(analyze_code(input).is_ok());
+
+        // The runner calls a test with no arguments and drops what it returns
+        for input in [
+            "#[test] fn f(x: Int) { }",
+            "#[test] fn f() -> Int { return 1; }",
+            "#[test] fn f<T: Shape>() { }",
+            "fn main() { #[test] fn inner() { } }",
+        ] {
+            assert!(analyze_code(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_export_functions() {
+        let input = r#"
+            export "C" fn add(a: Int, b: Int) -> Int { return a + b; }
+            export "C" fn scale(x: Float, big: Bool) -> Float {
+                return if big { x * 10.0 } else { x };
+            }
+            export "C" fn reset() { }
+        "#;
+        assert!(analyze_code(input).is_ok());
+
+        let cases = [
+            (r#"export "C" fn f(s: Str) { }"#, "InvalidExternType"),
+            (
+                r#"export "C" fn f() -> [Int] { return [1]; }"#,
+                "InvalidExternType",
+            ),
+            (
+                r#"export "C" fn f(x: Int) { } fn f(x: Float) { }"#,
+                "FunctionRedeclaration",
+            ),
+            (
+                r#"fn f(x: Float) { } export "C" fn f(x: Int) { }"#,
+                "FunctionRedeclaration",
+            ),
+            (r#"export "C" fn f<T>(x: T) { }"#, "UnexpectedNode"),
+            (
+                r#"fn outer() { export "C" fn inner() { } }"#,
+                "UnexpectedNode",
+            ),
+        ];
+        for (decl, expected) in cases {
+            let input = format!("{} fn main() {{ }}", decl);
+            let err = analyze_code(&input).unwrap_err();
+            assert!(format!("{:?}", err).contains(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_struct_method_errors() {
+        let with_user = |rest: &str| {
+            format!(
+                "struct User {{ name: Str }} impl User {{ fn greet(self, n: Int) -> Str {{ return self.name; }} }} {}",
+                rest
+            )
+        };
+        let cases = [
+            (
+                r#"fn main() { let u = User { name: "A" }; u.wave(); }"#,
+                "UnknownMethod",
+            ),
+            ("fn main() { let n = 5; n.greet(1); }", "UnknownMethod"),
+            (
+                r#"fn main() { let u = User { name: "A" }; u.greet(); }"#,
+                "FunctionArgumentMismatch",
+            ),
+            (
+                r#"fn main() { let u = User { name: "A" }; u.greet("1"); }"#,
+                "FunctionArgumentTypeMismatch",
+            ),
+            (
+                r#"fn main() { let u = User { name: "A" }; let n: Int = u.greet(1); }"#,
+                "VarTypeMismatch",
+            ),
+            (
+                "impl Player { fn play(self) { } } fn main() { }",
+                "UndeclaredStruct",
+            ),
+            (
+                "impl User { fn greet(self, n: Int) -> Str { return self.name; } } fn main() { }",
+                "FunctionRedeclaration",
+            ),
+        ];
+        for (rest, expected) in cases {
+            let err = analyze_code(&with_user(rest)).unwrap_err();
+            assert!(err.contains(expected), "{}: {}", rest, err);
+        }
+    }
+
+    #[test]
+    fn test_enum_values_and_match() {
+        let input = r#"
+            struct Point { x: Int, y: Int }
+            enum Shape { Circle(Float), Label(Str), At(Point), Empty }
+            fn describe(s: Shape) -> Str {
+                match s {
+                    Shape::Circle(r) => { return toStr(r); }
+                    Shape::Label(text) => { return text; }
+                    Shape::At(p) => { return toStr(p.x + p.y); }
+                    Shape::Empty => { return "empty"; }
+                }
+                return "";
+            }
+            fn main() {
+                let shapes = [Shape::Circle(2.0), Shape::Label("hi"), Shape::Empty];
+                let s: Shape = Shape::At(Point { x: 1, y: 2 });
+                print(describe(s), describe(shapes[0]));
+                let n = match s {
+                    Shape::Circle | Shape::Label => 1,
+                    _ => 0,
+                };
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+    }
+
+    #[test]
+    fn test_enum_errors() {
+        let with_shape = |rest: &str| format!("enum Shape {{ Circle(Float), Empty }} {}", rest);
+        let cases = [
+            ("fn main() { let s = Color::Red; }", "UndeclaredEnum"),
+            ("fn main() { let s = Shape::Square; }", "UnknownVariant"),
+            ("fn main() { let s = Shape::Circle; }", "VariantPayloadMismatch"),
+            ("fn main() { let s = Shape::Empty(1); }", "VariantPayloadMismatch"),
+            ("fn main() { let s = Shape::Circle(1); }", "VarTypeMismatch"),
+            (
+                "fn main() { let s = Shape::Empty; match s { Shape::Circle(r) => { print(r); } } }",
+                "NonExhaustiveMatch",
+            ),
+            (
+                "fn main() { let s = Shape::Empty; match s { Shape::Empty(e) => { print(1); } _ => { print(2); } } }",
+                "VariantPayloadMismatch",
+            ),
+            (
+                "fn main() { let s = Shape::Empty; match s { Shape::Empty => { print(1); } Shape::Empty => { print(2); } _ => { print(3); } } }",
+                "UnreachableMatchArm",
+            ),
+            (
+                "fn main() { let s = Shape::Empty; match s { 1 => { print(1); } _ => { print(2); } } }",
+                "MatchPatternTypeMismatch",
+            ),
+            (
+                "fn main() { match 1 { Shape::Empty => { print(1); } _ => { print(2); } } }",
+                "MatchPatternTypeMismatch",
+            ),
+            (
+                "fn main() { let s = Shape::Empty; let x = match s { Shape::Circle(r) => r, _ => 0.0, }; }",
+                "UnexpectedNode",
+            ),
+        ];
+        for (rest, expected) in cases {
+            let err = analyze_code(&with_shape(rest)).unwrap_err();
+            assert!(err.contains(expected), "{}: {}", rest, err);
+        }
+    }
+
+    #[test]
+    fn test_result_and_try() {
+        let input = r#"
+            fn parse(s: Str) -> Result<Int, Str> {
+                if s == "" {
+                    return Err("empty");
+                }
+                return Ok(s.len());
+            }
+            fn double(s: Str) -> Result<Int, Str> {
+                let n = parse(s)? * 2;
+                return Ok(n);
+            }
+            fn check(s: Str) -> Result<Void, Str> {
+                parse(s)?;
+                return Ok();
+            }
+            fn main() {
+                let r: Result<Int, Str> = Ok(1);
+                match double("abc") {
+                    Ok(v) => { print(v); }
+                    Err(e) => { print(e); }
+                }
+                match check("") {
+                    Ok => { print("ok"); }
+                    Err(_) => { print("failed"); }
+                }
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+    }
+
+    #[test]
+    fn test_result_and_try_errors() {
+        let with_parse = |rest: &str| {
+            let main = if rest.contains("fn main") {
+                ""
+            } else {
+                "fn main() {}"
+            };
+            format!(
+                "fn parse(s: Str) -> Result<Int, Str> {{ return Ok(1); }} {} {}",
+                rest, main
+            )
+        };
+        let cases = [
+            ("fn main() { let r = Ok(1); }", "UnexpectedNode"),
+            (
+                "fn f() -> Result<Int, Str> { return Ok(\"one\"); }",
+                "VarTypeMismatch",
+            ),
+            (
+                "fn f() -> Result<Void, Str> { return Ok(1); }",
+                "VariantPayloadMismatch",
+            ),
+            (
+                "fn f() -> Result<Int, Str> { return Ok(1, 2); }",
+                "FunctionArgumentMismatch",
+            ),
+            (
+                "fn f(n: Int) -> Result<Int, Str> { n?; return Ok(n); }",
+                "InvalidTryOperand",
+            ),
+            (
+                "fn f() -> Int { parse(\"1\")?; return 1; }",
+                "TryReturnTypeMismatch",
+            ),
+            (
+                "fn f() -> Result<Int, Int> { parse(\"1\")?; return Ok(1); }",
+                "TryReturnTypeMismatch",
+            ),
+            ("fn main() { parse(\"1\")?; }", "TryReturnTypeMismatch"),
+            (
+                "fn f() -> Result<Str, Str> { let n: Str = parse(\"1\")?; return Ok(\"\"); }",
+                "VarTypeMismatch",
+            ),
+        ];
+        for (rest, expected) in cases {
+            let err = analyze_code(&with_parse(rest)).unwrap_err();
+            assert!(err.contains(expected), "{}: {}", rest, err);
+        }
+    }
+
+    #[test]
+    fn test_panic() {
+        let input = r#"
+            fn half(n: Int) -> Int {
+                if n < 0 {
+                    panic("negative");
+                }
+                return if n % 2 == 0 { n / 2 } else { panic("odd: " + toStr(n)) };
+            }
+            fn name(n: Int) -> Str {
+                match n {
+                    1 => { return "one"; }
+                    _ => { panic("unknown"); }
+                }
+            }
+            fn todo() -> Int {
+                panic("not implemented");
+            }
+            fn main() {
+                let s = match half(4) {
+                    2 => "two",
+                    _ => panic("unexpected"),
+                };
+                print(s, name(1), todo());
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+
+        let cases = [
+            ("fn main() { panic(1); }", "FunctionArgumentTypeMismatch"),
+            ("fn main() { panic(); }", "FunctionArgumentMismatch"),
+            (
+                "fn panic(s: Str) { print(s); } fn main() { }",
+                "Redeclaration",
+            ),
+        ];
+        for (input, expected) in cases {
+            let err = analyze_code(input).unwrap_err();
+            assert!(err.contains(expected), "{}: {}", input, err);
+        }
+    }
+
+    #[test]
+    fn test_assert() {
+        let input = r#"
+            fn half(n: Int) -> Int {
+                assert(n % 2 == 0, "odd: " + toStr(n));
+                return n / 2;
+            }
+            fn main() {
+                let ok = half(4) == 2;
+                assert(ok, "wrong half");
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+
+        let cases = [
+            (
+                "fn main() { assert(1, \"one\"); }",
+                "FunctionArgumentTypeMismatch",
+            ),
+            (
+                "fn main() { assert(true, 1); }",
+                "FunctionArgumentTypeMismatch",
+            ),
+            ("fn main() { assert(true); }", "FunctionArgumentMismatch"),
+        ];
+        for (input, expected) in cases {
+            let err = analyze_code(input).unwrap_err();
+            assert!(err.contains(expected), "{}: {}", input, err);
+        }
+    }
+
+    #[test]
+    fn test_traits_and_generic_functions() {
+        let input = r#"
+            trait Shape {
+                fn area(self) -> Float;
+                fn scaled(self, by: Float) -> Self;
+            }
+            struct Circle { r: Float }
+            impl Shape for Circle {
+                fn area(self) -> Float { return self.r * self.r * 3.14; }
+                fn scaled(self, by: Float) -> Circle { return Circle { r: self.r * by }; }
+            }
+            fn total<T: Shape>(a: T, b: T) -> Float {
+                return a.area() + b.scaled(2.0).area();
+            }
+            fn first<T>(items: [T]) -> T {
+                return items[0];
+            }
+            fn main() {
+                let c = Circle { r: 1.0 };
+                let t: Float = total(c, c);
+                let n: Int = first([1, 2]);
+                let s: Str = first(["a"]);
+            }
+        "#;
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        let mut ast = parser.parse_program().unwrap();
+        let mut analyzer = SemanticAnalyzer::new(None);
+        let crate::parser::ast::AstNode::Program(ref mut nodes) = ast else {
+            panic!("Not a program");
+        };
+        analyzer.analyze_program(nodes).unwrap();
+
+        // One ordinary function per instance joins the program
+        let names: Vec<&str> = nodes
+            .iter()
+            .filter_map(|n| match n {
+                crate::parser::ast::AstNode::FunctionDecl {
+                    name, type_params, ..
+                } if type_params.is_empty() => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert!(names.contains(&"total__StructCircle"));
+        assert!(names.contains(&"first__Int"));
+        assert!(names.contains(&"first__String"));
+    }
+
+    #[test]
+    fn test_trait_errors() {
+        let with_shape = |rest: &str| {
+            format!(
+                "trait Shape {{ fn area(self) -> Float; }} struct Circle {{ r: Float }} {}",
+                rest
+            )
+        };
+        let cases = [
+            ("impl Shape for Circle { } fn main() { }", "MissingTraitMethod"),
+            (
+                "impl Shape for Circle { fn area(self) -> Int { return 1; } } fn main() { }",
+                "TraitMethodMismatch",
+            ),
+            (
+                "impl Shape for Circle { fn area(self) -> Float { return 1.0; } fn name(self) -> Str { return \"c\"; } } fn main() { }",
+                "TraitMethodMismatch",
+            ),
+            ("impl Drawable for Circle { } fn main() { }", "UndeclaredTrait"),
+            ("trait Shape { } fn main() { }", "TraitRedeclaration"),
+            (
+                "trait Named { fn name(self) -> Str; fn name(self) -> Str; } fn main() { }",
+                "DuplicateTraitMethod",
+            ),
+            (
+                "fn area<T: Shape>(s: T) -> Float { return s.area(); } fn main() { let a = area(Circle { r: 1.0 }); }",
+                "UnsatisfiedTraitBound",
+            ),
+            (
+                "fn area<T: Shape>(s: T) -> Float { return s.area(); } fn main() { let a = area(1); }",
+                "UnsatisfiedTraitBound",
+            ),
+            (
+                "fn area<T: Drawable>(s: T) -> Float { return 1.0; } fn main() { }",
+                "UndeclaredTrait",
+            ),
+            (
+                "fn make<T>() -> Int { return 1; } fn main() { let a = make(); }",
+                "UninferredTypeParam",
+            ),
+            (
+                "fn area<T>(s: T) -> Float { return s.area(); } fn main() { let a = area(1); }",
+                "UnknownMethod",
+            ),
+        ];
+        for (rest, expected) in cases {
+            let err = analyze_code(&with_shape(rest)).unwrap_err();
+            assert!(err.contains(expected), "{}: {}", rest, err);
+        }
+    }
+
+    #[test]
+    fn test_array_methods() {
+        let input = r#"
+            fn main() {
+                let mut nums = [1, 2];
+                let mut names: [Str] = [];
+                let mut maybe: [Int?] = [];
+                nums.push(3);
+                nums.insert(0, 4);
+                let last: Int? = nums.pop();
+                let first: Int = nums.remove(0);
+                names.push("a");
+                maybe.push(null);
+                nums.clear();
+                print(last ?? 0, first, names);
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+    }
+
+    #[test]
+    fn test_array_method_errors() {
+        let cases = [
+            (
+                "fn main() { let nums = [1]; nums.push(2); }",
+                "InvalidAssignmentTarget",
+            ),
+            (
+                "fn main() { let mut nums = [1]; nums.push(\"a\"); }",
+                "FunctionArgumentTypeMismatch",
+            ),
+            (
+                "fn main() { let mut nums = [1]; nums.insert(0); }",
+                "FunctionArgumentMismatch",
+            ),
+            (
+                "fn main() { let mut nums = [1]; let n: Int = nums.pop(); }",
+                "VarTypeMismatch",
+            ),
+            (
+                "fn main() { let mut nums = [1]; nums.shuffle(); }",
+                "UnknownMethod",
+            ),
+            (
+                "fn make() -> [Int] { return [1]; } fn main() { make().push(2); }",
+                "InvalidAssignmentTarget",
+            ),
+        ];
+        for (input, expected) in cases {
+            let err = analyze_code(input).unwrap_err();
+            assert!(err.contains(expected), "{}: {}", input, err);
+        }
+    }
+
+    #[test]
+    fn test_array_concat() {
+        let input = r#"
+            fn main() {
+                let a = [1, 2];
+                let b: [Int] = a + [3];
+                let names = ["x"] + ["y", "z"];
+                print(b, names);
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+
+        let mismatched = "fn main() { let c = [1] + [\"a\"]; }";
+        let err = analyze_code(mismatched).unwrap_err();
+        assert!(err.contains("OperatorTypeMismatch"), "{}", err);
+        let subtracted = "fn main() { let c = [1] - [2]; }";
+        assert!(analyze_code(subtracted).is_err());
+    }
+
+    #[test]
+    fn test_numeric_array_builtins() {
+        let input = r#"
+            fn main() {
+                let mut nums = [3, 1, 2];
+                nums.sort();
+                nums.reverse();
+                let total: Int = nums.sum();
+                let low: Int? = nums.min();
+                let high = [1.5, 2.5].max() ?? 0.0;
+                let mut names = ["b", "a"];
+                names.reverse();
+                print(total, low ?? 0, high, [1L, 2L].sum(), names);
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+
+        let cases = [
+            (
+                "fn main() { let a = [3, 1]; a.sort(); }",
+                "InvalidAssignmentTarget",
+            ),
+            (
+                "fn main() { let mut a = [\"b\"]; a.sort(); }",
+                "UnknownMethod",
+            ),
+            (
+                "fn main() { let a = [\"b\"]; let s = a.sum(); }",
+                "UnknownMethod",
+            ),
+            (
+                "fn main() { let a = [1]; let m: Int = a.max(); }",
+                "VarTypeMismatch",
+            ),
+            (
+                "fn main() { let a = [1]; let s = a.sum(1); }",
+                "FunctionArgumentMismatch",
+            ),
+        ];
+        for (input, expected) in cases {
+            let err = analyze_code(input).unwrap_err();
+            assert!(err.contains(expected), "{}: {}", input, err);
+        }
+    }
+
+    #[test]
+    fn test_closure_array_methods() {
+        let input = r#"
+            fn main() {
+                let nums = [1, 2, 3];
+                let doubled: [Int] = nums.map(|x| x * 2);
+                let big: [Int] = nums.filter(|x| x > 1);
+                let total: Int = nums.reduce(0, |acc, x| acc + x);
+                let labels: [Str] = nums.map(|x: Int| toStr(x)).filter(|s| s != "2");
+                let sums = nums.map(|x| nums.reduce(x, |acc, y| acc + y));
+                print(doubled, big, total, labels, sums);
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+    }
+
+    #[test]
+    fn test_closure_array_method_errors() {
+        let cases = [
+            (
+                "fn main() { let a = [1]; let b = a.filter(|x| x + 1); }",
+                "InvalidConditionType",
+            ),
+            (
+                "fn main() { let a = [1]; let s = a.reduce(0.0, |acc, x| acc + x); }",
+                "OperatorTypeMismatch",
+            ),
+            (
+                "fn main() { let a = [1]; let s = a.reduce(0, |acc, x| acc > x); }",
+                "ReturnTypeMismatch",
+            ),
+            (
+                "fn main() { let a = [1]; let b = a.map(|x, y| x); }",
+                "FunctionArgumentMismatch",
+            ),
+            (
+                "fn main() { let a = [1]; let b = a.map(|x: Str| x); }",
+                "FunctionArgumentTypeMismatch",
+            ),
+            (
+                "fn main() { let x = 1; let a = [1]; let b = a.map(|x| x); }",
+                "VariableRedeclaration",
+            ),
+            (
+                "fn main() { let a = [1]; let b = a.map(a); }",
+                "UnexpectedNode",
+            ),
+            ("fn main() { let f = |x| x; }", "MissingParamType"),
+            (
+                "fn main() { let m = {\"a\": 1}; let b = m.map(|x| x); }",
+                "UnknownMethod",
+            ),
+        ];
+        for (input, expected) in cases {
+            let err = analyze_code(input).unwrap_err();
+            assert!(err.contains(expected), "{}: {}", input, err);
+        }
+    }
+
+    #[test]
+    fn test_closure_values() {
+        let input = r#"
+            fn apply(f: fn(Int) -> Int, v: Int) -> Int {
+                return f(v);
+            }
+
+            fn adder(n: Int) -> fn(Int) -> Int {
+                return |x: Int| x + n;
+            }
+
+            fn main() {
+                let base = 10;
+                let add = |x: Int| x + base;
+                let add3 = adder(3);
+                let total: Int = add(5) + apply(add, 1) + add3(4);
+                let greet: fn(Str) -> Str = |name: Str| "hi " + name;
+                let twice = |x: Int| apply(add, apply(add, x));
+                print(total, greet("doo"), twice(1));
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+    }
+
+    #[test]
+    fn test_closure_value_errors() {
+        let cases = [
+            (
+                "fn main() { let mut n = 1; let f = |x: Int| { n = x; n }; }",
+                "Cannot assign to immutable variable 'n'",
+            ),
+            (
+                "fn main() { let f = |x: Int| x; let s: Str = f(1); }",
+                "TypeMismatch",
+            ),
+            (
+                "fn main() { let f = |x: Int| x; print(f(1, 2)); }",
+                "FunctionArgumentMismatch",
+            ),
+            (
+                "fn main() { let f = |x: Int| x; print(f(\"1\")); }",
+                "FunctionArgumentTypeMismatch",
+            ),
+            (
+                "fn main() { let x = 1; let f = |x: Int| x; }",
+                "VariableRedeclaration",
+            ),
+            ("let f = |x: Int| x;", "UnexpectedNode"),
+        ];
+        for (input, expected) in cases {
+            let err = analyze_code(input).unwrap_err();
+            assert!(err.contains(expected), "{}: {}", input, err);
+        }
+    }
+
+    #[test]
+    fn test_string_comparisons() {
+        let input = r#"
+            fn main() {
+                let a = "apple";
+                let b = "banana";
+                let same: Bool = a == b;
+                let before: Bool = a < b;
+                print(same, before, a >= b, a != "pear");
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+        let err = analyze_code(r#"fn main() { let x = "a" < 1; }"#).unwrap_err();
+        assert!(err.contains("OperatorTypeMismatch"), "{}", err);
+    }
+
+    #[test]
+    fn test_string_methods() {
+        let input = r#"
+            fn main() {
+                let line = "  a,b,c  ";
+                let n: Int = line.len();
+                let found: Bool = line.contains("b");
+                let parts: [Str] = line.trim().split(",");
+                let fixed: Str = line.replace(",", ";");
+                let head: Str = line.substring(0, 3);
+                print(n, found, parts, fixed, head);
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+
+        let cases = [
+            (
+                "fn main() { let s = \"a\"; let t = s.upper(); }",
+                "UnknownMethod",
+            ),
+            (
+                "fn main() { let s = \"a\"; let n = s.len(1); }",
+                "FunctionArgumentMismatch",
+            ),
+            (
+                "fn main() { let s = \"a\"; let b = s.contains(1); }",
+                "FunctionArgumentTypeMismatch",
+            ),
+            (
+                "fn main() { let s = \"a\"; let t = s.substring(\"0\", 1); }",
+                "FunctionArgumentTypeMismatch",
+            ),
+            (
+                "fn main() { let s = \"a\"; let parts: Str = s.split(\",\"); }",
+                "VarTypeMismatch",
+            ),
+        ];
+        for (input, expected) in cases {
+            let err = analyze_code(input).unwrap_err();
+            assert!(err.contains(expected), "{}: {}", input, err);
+        }
+    }
+
+    #[test]
+    fn test_map_methods() {
+        let input = r#"
+            fn main() {
+                let mut ages: {Str: Int} = {};
+                ages.insert("ann", 30);
+                let a: Int? = ages["ann"];
+                let gone: Int? = ages.remove("bob");
+                let known: Bool = ages.containsKey("ann");
+                let names: [Str] = ages.keys();
+                let years: [Int] = ages.values();
+                ages.clear();
+                print(a ?? 0, gone ?? 0, known, names, years);
+            }
+        "#;
+        assert!(analyze_code(input).is_ok());
+    }
+
+    #[test]
+    fn test_map_method_errors() {
+        let cases = [
+            (
+                "fn main() { let m = {\"a\": 1}; m.insert(\"b\", 2); }",
+                "InvalidAssignmentTarget",
+            ),
+            (
+                "fn main() { let mut m = {\"a\": 1}; m.insert(1, 2); }",
+                "FunctionArgumentTypeMismatch",
+            ),
+            (
+                "fn main() { let m = {\"a\": 1}; let n: Int = m[\"a\"]; }",
+                "VarTypeMismatch",
+            ),
+            (
+                "fn main() { let m = {\"a\": 1}; m.sort(); }",
+                "UnknownMethod",
+            ),
+            (
+                "fn add(m: {Str: Int}) { m.insert(\"b\", 2); } fn main() { add({\"a\": 1}); }",
+                "InvalidAssignmentTarget",
+            ),
+        ];
+        for (input, expected) in cases {
+            let err = analyze_code(input).unwrap_err();
+            assert!(err.contains(expected), "{}: {}", input, err);
+        }
+    }
+
     #[test]
     fn test_duplicate_function_error() {
         let input = r#"
@@ -695,4 +2431,245 @@ mod analyzer_tests {
         "#;
         assert!(analyze_code(input).is_ok());
     }
+
+    #[test]
+    fn test_errors_are_located() {
+        let input = "fn f() -> Int {\n    print(1);\n}\nfn main() {\n    let x = 1;\n    if x > 0 {\n        print(y);\n    }\n}";
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        let crate::parser::ast::AstNode::Program(mut nodes) = parser.parse_program().unwrap()
+        else {
+            panic!("Expected Program");
+        };
+        let mut analyzer = SemanticAnalyzer::new(None);
+        analyzer.statement_spans = parser.statement_spans.clone();
+        let first = analyzer.analyze_program(&mut nodes).unwrap_err();
+        let mut errors = vec![first];
+        errors.append(&mut analyzer.collected_errors);
+        let span_of = |name: &str| {
+            let error = errors
+                .iter()
+                .find(|e| format!("{:?}", e).contains(name))
+                .unwrap();
+            let span = error.span().unwrap();
+            (span.line, span.col)
+        };
+
+        // Errors about a declaration point at the declaration
+        assert_eq!(span_of("MissingFunctionReturn"), (1, 1));
+        // An error in an expression points at the variable it's about
+        assert_eq!(span_of("UndeclaredVariable"), (7, 15));
+    }
+
+    #[test]
+    fn test_errors_point_at_operands() {
+        let input = "fn f(n: Int) -> Int {\n    return n;\n}\n\
+            fn a() {\n    let n = 1;\n    let s = \"x\" + n;\n}\n\
+            fn b() {\n    let t = 1 + \"x\";\n}\n\
+            fn c() {\n    let on = true;\n    let r = f(on);\n}\n\
+            fn main() {\n    let v = 2 + missing(1);\n}";
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        let crate::parser::ast::AstNode::Program(mut nodes) = parser.parse_program().unwrap()
+        else {
+            panic!("Expected Program");
+        };
+        let mut analyzer = SemanticAnalyzer::new(None);
+        analyzer.statement_spans = parser.statement_spans.clone();
+        let first = analyzer.analyze_program(&mut nodes).unwrap_err();
+        let located: Vec<(&str, usize, usize)> = std::iter::once(&first)
+            .chain(&analyzer.collected_errors)
+            .map(|e| {
+                let span = e.span().unwrap();
+                (e.code(), span.line, span.col)
+            })
+            .collect();
+
+        assert_eq!(
+            located,
+            vec![
+                // The operand of the wrong type, or the operator when it's a literal
+                ("E0201", 6, 19),
+                ("E0201", 9, 15),
+                // The argument of the wrong type
+                ("E0107", 13, 15),
+                // The name of a function that doesn't exist
+                ("E0104", 16, 17),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_did_you_mean_suggestions() {
+        let cases = [
+            (
+                "fn main() { let count = 1; print(cuont); }",
+                "UndeclaredVariable",
+                "count",
+            ),
+            (
+                "fn square(n: Int) -> Int { return n * n; } fn main() { let a = sqare(2); }",
+                "UndeclaredFunction",
+                "square",
+            ),
+            (
+                "fn main() { let n = toInnt(\"1\"); }",
+                "UndeclaredFunction",
+                "toInt",
+            ),
+            (
+                "struct Point { x: Int } fn main() { let p = Pont { x: 1 }; }",
+                "UndeclaredStruct",
+                "Point",
+            ),
+            (
+                "enum Color { Red } fn main() { let c = Colour::Red; }",
+                "UndeclaredEnum",
+                "Color",
+            ),
+            (
+                "trait Shape { } fn area<T: Shap>(s: T) -> Int { return 1; } fn main() { }",
+                "UndeclaredTrait",
+                "Shape",
+            ),
+        ];
+        for (input, expected, suggestion) in cases {
+            let err = analyze_code(input).unwrap_err();
+            assert!(err.contains(expected), "{}: {}", input, err);
+            assert!(
+                err.contains(&format!("suggestion: \"{}\"", suggestion)),
+                "{}: {}",
+                input,
+                err
+            );
+        }
+
+        // Names far from every declared one get no suggestion
+        let err = analyze_code("fn main() { let count = 1; print(total); }").unwrap_err();
+        assert!(err.contains("UndeclaredVariable"));
+        assert!(!err.contains("DidYouMean"), "{}", err);
+    }
+
+    #[test]
+    fn test_analysis_continues_after_errors() {
+        let all_errors = |input: &str| {
+            let tokens = lex(input);
+            let mut parser = Parser::new(&tokens);
+            let crate::parser::ast::AstNode::Program(mut nodes) = parser.parse_program().unwrap()
+            else {
+                panic!("Expected Program");
+            };
+            let mut analyzer = SemanticAnalyzer::new(None);
+            let mut errors: Vec<String> = analyzer
+                .analyze_program(&mut nodes)
+                .err()
+                .into_iter()
+                .map(|e| format!("{:?}", e.inner()))
+                .collect();
+            errors.extend(
+                analyzer
+                    .collected_errors
+                    .iter()
+                    .map(|e| format!("{:?}", e.inner())),
+            );
+            errors
+        };
+
+        // Errors in every function are reported, and a failed body doesn't
+        // leave the next function looking nested
+        let errors = all_errors(
+            "fn f() -> Int { let a: Int = \"s\"; return a; }\n\
+             fn g() -> Int { print(1); }\n\
+             fn h() { let b = 1 + true; }\n\
+             fn main() { print(zz); }",
+        );
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+        assert!(errors[0].starts_with("VarTypeMismatch"), "{:?}", errors);
+        assert!(
+            errors[1].starts_with("MissingFunctionReturn"),
+            "{:?}",
+            errors
+        );
+        assert!(
+            errors[2].starts_with("OperatorTypeMismatch"),
+            "{:?}",
+            errors
+        );
+        assert!(errors[3].starts_with("UndeclaredVariable"), "{:?}", errors);
+
+        // A function missing its return still has its body checked
+        let errors = all_errors("fn g() -> Int { print(zz); } fn main() { }");
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+
+        // Uses of a variable or function whose declaration failed aren't errors again
+        let errors = all_errors(
+            "fn k(p: Nope) -> Int { return 1; }\n\
+             fn main() { let x = 1 + \"a\"; print(x); let y = x; let n = k(1); }",
+        );
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+    }
+
+    #[test]
+    fn test_return_call_arguments() {
+        // The arguments of a returned call are checked like those of any call
+        let error = analyze_code(
+            "fn dbl(x: Int) -> Int { return x * 2; }\n\
+             fn f() -> Int { let h = \"s\"; return dbl(h); }\n\
+             fn main() { print(f()); }",
+        )
+        .unwrap_err();
+        assert!(error.contains("FunctionArgumentTypeMismatch"), "{}", error);
+    }
+
+    #[test]
+    fn test_lint_warnings() {
+        let warnings = |input: &str| {
+            let tokens = lex(input);
+            let mut parser = Parser::new(&tokens);
+            let crate::parser::ast::AstNode::Program(mut nodes) = parser.parse_program().unwrap()
+            else {
+                panic!("Expected Program");
+            };
+            let mut analyzer = SemanticAnalyzer::new(None);
+            analyzer.statement_spans = parser.statement_spans.clone();
+            analyzer.analyze_program(&mut nodes).unwrap();
+            analyzer
+                .warnings
+                .iter()
+                .map(|w| format!("{} {} {}", w.lint, w.message, w.span.line))
+                .collect::<Vec<_>>()
+        };
+
+        // Variables never read are reported at their declaring statement
+        assert_eq!(
+            warnings(
+                "fn main() {\n    let a = 1;\n    let b = 2;\n    let c = b;\n    print(c);\n    if true {\n        let d = 4;\n    }\n}"
+            ),
+            vec![
+                "unused unused variable 'a' 2",
+                "unused unused variable 'd' 6"
+            ]
+        );
+
+        // Reading a variable in the arguments of a returned call is a use
+        assert!(warnings(
+            "fn dbl(x: Int) -> Int { return x * 2; }\n\
+             fn f(x: Int) -> Int {\n    let h = x + 1;\n    return dbl(h);\n}\n\
+             fn main() { let n = 3; print(f(dbl(n) + 1)); }"
+        )
+        .is_empty());
+
+        // Only the first statement after a return is reported
+        assert_eq!(
+            warnings("fn f() -> Int {\n    return 1;\n    print(2);\n    print(3);\n}\nfn main() { print(f()); }"),
+            vec!["unreachable unreachable statement 3"]
+        );
+
+        // `#[allow(..)]` turns lints off in the function, including its nested functions
+        assert!(warnings(
+            "#[allow(unused, unreachable)]\n\
+             fn main() { fn inner() { let x = 1; } inner(); panic(\"stop\"); print(1); }"
+        )
+        .is_empty());
+    }
 }
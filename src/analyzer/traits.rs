@@ -0,0 +1,325 @@
+use super::analyzer::SemanticAnalyzer;
+use super::overloads::mangle_overload;
+use super::structs::method_symbol;
+use super::types::{NamedError, SemanticError};
+use crate::parser::ast::{AstNode, TraitMethod, TypeNode};
+use std::collections::HashMap;
+
+/// Traits and generic functions, resolved entirely at compile time.
+///
+/// `trait Shape { fn area(self) -> Float; }` lists methods a struct provides
+/// through `impl Shape for Circle { ... }`; those are ordinary methods named
+/// `Circle::area`. A generic function `fn describe<T: Shape>(s: T)` is a
+/// template: each call infers `T` from its arguments, checks the bound and
+/// points at an instance with `T` replaced by the concrete type, named like
+/// an overload set member (`describe__StructCircle`). Instances are analyzed
+/// once the program is, and appended to it, so no vtables are needed.
+impl SemanticAnalyzer {
+    /// Records the methods of every trait declared in `nodes`, so impls and
+    /// bounds can name traits declared after them.
+    pub(crate) fn register_traits(&mut self, nodes: &[AstNode]) {
+        for node in nodes {
+            if let AstNode::TraitDecl { name, methods } = node {
+                if self.trait_table.contains_key(name) {
+                    self.collected_errors
+                        .push(SemanticError::TraitRedeclaration(NamedError {
+                            name: name.clone(),
+                        }));
+                    continue;
+                }
+                self.trait_table.insert(name.clone(), methods.clone());
+            }
+        }
+    }
+
+    /// Checks a trait declaration: no method is listed twice.
+    pub fn analyze_trait(&mut self, node: &AstNode) -> Result<(), SemanticError> {
+        let AstNode::TraitDecl { name, methods } = node else {
+            return Ok(());
+        };
+        for (i, method) in methods.iter().enumerate() {
+            if methods[..i].iter().any(|seen| seen.name == method.name) {
+                return Err(SemanticError::DuplicateTraitMethod {
+                    trait_name: name.clone(),
+                    method: method.name.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks `impl Trait for Struct`: every method of the trait is
+    /// implemented with the trait's signature (`self` being the struct), and
+    /// nothing else is. Method names are already `Struct::method`.
+    pub(crate) fn check_trait_impl(
+        &self,
+        trait_name: &str,
+        struct_name: &str,
+        methods: &[AstNode],
+    ) -> Result<(), SemanticError> {
+        let required = self.trait_table.get(trait_name).ok_or_else(|| {
+            self.suggest(SemanticError::UndeclaredTrait(NamedError {
+                name: trait_name.to_string(),
+            }))
+        })?;
+
+        for method in methods {
+            if let AstNode::FunctionDecl { name, .. } = method {
+                let short = name.rsplit("::").next().unwrap_or(name);
+                if !required.iter().any(|m| m.name == short) {
+                    return Err(SemanticError::TraitMethodMismatch {
+                        trait_name: trait_name.to_string(),
+                        method: short.to_string(),
+                    });
+                }
+            }
+        }
+
+        for trait_method in required {
+            let symbol = method_symbol(struct_name, &trait_method.name);
+            let implemented = methods.iter().find_map(|m| match m {
+                AstNode::FunctionDecl {
+                    name,
+                    params,
+                    return_type,
+                    ..
+                } if *name == symbol => Some((params, return_type)),
+                _ => None,
+            });
+            let Some((params, return_type)) = implemented else {
+                return Err(SemanticError::MissingTraitMethod {
+                    trait_name: trait_name.to_string(),
+                    struct_name: struct_name.to_string(),
+                    method: trait_method.name.clone(),
+                });
+            };
+            let (expected_params, expected_ret) =
+                self.trait_method_signature(trait_method, struct_name);
+            let found_params: Vec<TypeNode> = params
+                .iter()
+                .map(|(_, t)| t.clone().unwrap_or(TypeNode::Int))
+                .collect();
+            let found_ret = return_type.clone().unwrap_or(TypeNode::Void);
+            if found_params != expected_params || found_ret != expected_ret {
+                return Err(SemanticError::TraitMethodMismatch {
+                    trait_name: trait_name.to_string(),
+                    method: trait_method.name.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Parameter and return types a trait method has when implemented by `struct_name`.
+    fn trait_method_signature(
+        &self,
+        method: &TraitMethod,
+        struct_name: &str,
+    ) -> (Vec<TypeNode>, TypeNode) {
+        let self_ty = HashMap::from([(
+            "Self".to_string(),
+            TypeNode::TypeRef(struct_name.to_string()),
+        )]);
+        let resolve = |ty: &TypeNode| self.resolve_type_refs(&substitute_type_params(ty, &self_ty));
+        let params = method
+            .params
+            .iter()
+            .map(|(_, t)| resolve(t.as_ref().unwrap_or(&TypeNode::Int)))
+            .collect();
+        let ret = resolve(method.return_type.as_ref().unwrap_or(&TypeNode::Void));
+        (params, ret)
+    }
+
+    /// Keeps a generic function aside as a template: it is only analyzed
+    /// through the instances its calls create.
+    pub(crate) fn register_generic_function(
+        &mut self,
+        node: &AstNode,
+    ) -> Result<(), SemanticError> {
+        let AstNode::FunctionDecl { name, .. } = node else {
+            return Ok(());
+        };
+        if self.function_table.contains_key(name) || self.generic_functions.contains_key(name) {
+            return Err(SemanticError::FunctionRedeclaration(NamedError {
+                name: name.clone(),
+            }));
+        }
+        self.generic_functions.insert(name.clone(), node.clone());
+        Ok(())
+    }
+
+    /// Checks that the bounds of a generic function name declared traits.
+    pub(crate) fn check_generic_bounds(
+        &self,
+        type_params: &[(String, Option<String>)],
+    ) -> Result<(), SemanticError> {
+        for bound in type_params.iter().filter_map(|(_, bound)| bound.as_ref()) {
+            if !self.trait_table.contains_key(bound) {
+                return Err(self.suggest(SemanticError::UndeclaredTrait(NamedError {
+                    name: bound.clone(),
+                })));
+            }
+        }
+        Ok(())
+    }
+
+    /// Points a call to a generic function at the instance for its argument
+    /// types, creating that instance on first use.
+    pub(crate) fn instantiate_generic_call(
+        &mut self,
+        func: &mut AstNode,
+        args: &[AstNode],
+    ) -> Result<(), SemanticError> {
+        let AstNode::Identifier(name, _) = func else {
+            return Ok(());
+        };
+        let Some(template) = self.generic_functions.get(name.as_str()).cloned() else {
+            return Ok(());
+        };
+        let AstNode::FunctionDecl {
+            params,
+            type_params,
+            ..
+        } = &template
+        else {
+            return Ok(());
+        };
+
+        let variadic = matches!(params.last(), Some((_, Some(TypeNode::Variadic(_)))));
+        if args.len() != params.len() && !(variadic && args.len() + 1 >= params.len()) {
+            return Err(SemanticError::FunctionArgumentMismatch {
+                name: name.clone(),
+                expected: params.len(),
+                found: args.len(),
+            });
+        }
+
+        // Each type parameter takes the type of the first argument passed for it
+        let mut bindings = HashMap::new();
+        for (i, arg) in args.iter().enumerate() {
+            let param_ty = match params.get(i).or(params.last()) {
+                Some((_, Some(TypeNode::Variadic(elem)))) => &**elem,
+                Some((_, Some(ty))) => ty,
+                _ => continue,
+            };
+            let arg_ty = self.infer_type(arg)?;
+            bind_type_params(param_ty, &arg_ty, type_params, &mut bindings);
+        }
+
+        let mut type_args = Vec::new();
+        for (param, bound) in type_params {
+            let ty =
+                bindings
+                    .get(param)
+                    .cloned()
+                    .ok_or_else(|| SemanticError::UninferredTypeParam {
+                        function: name.clone(),
+                        type_param: param.clone(),
+                    })?;
+            if let Some(trait_name) = bound {
+                self.check_trait_bound(&ty, trait_name)?;
+            }
+            type_args.push(ty);
+        }
+
+        let instance_name = mangle_overload(name, &type_args);
+        if !self.function_table.contains_key(&instance_name) {
+            let mut instance = template.clone();
+            if let AstNode::FunctionDecl {
+                name,
+                params,
+                return_type,
+                type_params,
+                ..
+            } = &mut instance
+            {
+                *name = instance_name.clone();
+                for ty in params.iter_mut().filter_map(|(_, t)| t.as_mut()) {
+                    *ty = substitute_type_params(ty, &bindings);
+                }
+                if let Some(ty) = return_type {
+                    *ty = substitute_type_params(ty, &bindings);
+                }
+                type_params.clear();
+            }
+            self.register_function_decl(&mut instance)?;
+            self.generic_instances.push(instance);
+        }
+        *name = instance_name;
+        Ok(())
+    }
+
+    /// Checks that `ty` is a struct with an `impl trait_name for ...`.
+    fn check_trait_bound(&self, ty: &TypeNode, trait_name: &str) -> Result<(), SemanticError> {
+        if !self.trait_table.contains_key(trait_name) {
+            return Err(self.suggest(SemanticError::UndeclaredTrait(NamedError {
+                name: trait_name.to_string(),
+            })));
+        }
+        let implemented = match ty {
+            TypeNode::Struct(struct_name, _) => self
+                .trait_impls
+                .get(trait_name)
+                .is_some_and(|structs| structs.contains(struct_name)),
+            _ => false,
+        };
+        if implemented {
+            Ok(())
+        } else {
+            Err(SemanticError::UnsatisfiedTraitBound {
+                trait_name: trait_name.to_string(),
+                found: ty.clone(),
+            })
+        }
+    }
+}
+
+/// Binds the type parameters appearing in `param` to the matching parts of
+/// `arg`. Parameters already bound keep their type; a conflicting argument is
+/// reported by the instance's ordinary argument checks.
+fn bind_type_params(
+    param: &TypeNode,
+    arg: &TypeNode,
+    type_params: &[(String, Option<String>)],
+    bindings: &mut HashMap<String, TypeNode>,
+) {
+    let mut bind = |p: &TypeNode, a: &TypeNode| bind_type_params(p, a, type_params, bindings);
+    match (param, arg) {
+        (TypeNode::TypeRef(name), _) if type_params.iter().any(|(tp, _)| tp == name) => {
+            bindings.entry(name.clone()).or_insert_with(|| arg.clone());
+        }
+        (TypeNode::Array(p), TypeNode::Array(a)) => bind(p, a),
+        (TypeNode::Map(pk, pv), TypeNode::Map(ak, av)) => {
+            bind(pk, ak);
+            bind(pv, av);
+        }
+        (TypeNode::Optional(p), TypeNode::Optional(a)) => bind(p, a),
+        // A plain value passed for an optional parameter
+        (TypeNode::Optional(p), a) => bind(p, a),
+        (TypeNode::Tuple(ps), TypeNode::Tuple(args)) if ps.len() == args.len() => {
+            for (p, a) in ps.iter().zip(args) {
+                bind(p, a);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `ty` with every type parameter in `bindings` replaced by its type.
+fn substitute_type_params(ty: &TypeNode, bindings: &HashMap<String, TypeNode>) -> TypeNode {
+    let sub = |inner: &TypeNode| Box::new(substitute_type_params(inner, bindings));
+    match ty {
+        TypeNode::TypeRef(name) => bindings.get(name).cloned().unwrap_or_else(|| ty.clone()),
+        TypeNode::Array(elem) => TypeNode::Array(sub(elem)),
+        TypeNode::Map(key, value) => TypeNode::Map(sub(key), sub(value)),
+        TypeNode::Optional(inner) => TypeNode::Optional(sub(inner)),
+        TypeNode::Variadic(elem) => TypeNode::Variadic(sub(elem)),
+        TypeNode::Tuple(elems) => TypeNode::Tuple(
+            elems
+                .iter()
+                .map(|t| substitute_type_params(t, bindings))
+                .collect(),
+        ),
+        _ => ty.clone(),
+    }
+}
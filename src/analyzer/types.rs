@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 
-use crate::parser::ast::{AstNode, Pattern, TypeNode};
+use crate::parser::ast::{AstNode, Pattern, Span, TypeNode};
 use std::fmt;
 
 #[derive(Debug)]
@@ -8,8 +8,7 @@ pub struct TypeMismatch {
     pub expected: TypeNode,
     pub found: TypeNode,
     pub value: Option<Box<AstNode>>,
-    pub line: Option<usize>,
-    pub col: Option<usize>,
+    pub span: Option<Span>, // the mismatched operand, or the operator when it has no span
 }
 
 #[derive(Debug)]
@@ -22,7 +21,7 @@ pub enum SemanticError {
     // Variable Declaration/Assignment Errors
     VariableRedeclaration(NamedError),
     UndeclaredVariable(NamedError),
-    VarTypeMismatch(TypeMismatch),
+    VarTypeMismatch(Box<TypeMismatch>),
     TupleAssignmentMismatch {
         expected: usize,
         found: usize,
@@ -31,9 +30,13 @@ pub enum SemanticError {
         target: String,
     },
     OutOfScopeVariable(NamedError),
+    CapturedOuterVariable {
+        name: String,
+        function: String,
+    },
     InvalidMapKeyType {
         found: TypeNode,
-        expected: TypeNode,
+        expected: Box<TypeNode>,
     },
 
     // Function Declaration/Call Errors
@@ -51,7 +54,7 @@ pub enum SemanticError {
     },
     FunctionArgumentTypeMismatch {
         name: String,
-        expected: TypeNode,
+        expected: Box<TypeNode>,
         found: TypeNode,
     },
     MissingFunctionReturn {
@@ -62,14 +65,28 @@ pub enum SemanticError {
     },
     ReturnTypeMismatch {
         function: String,
-        mismatch: TypeMismatch,
+        mismatch: Box<TypeMismatch>,
     },
     InvalidPublicName(NamedError),
+    NoMatchingOverload {
+        name: String,
+        found: Vec<TypeNode>,
+    },
+    ArenaReturnType {
+        function: String,
+        found: TypeNode,
+    },
+    InvalidExternType {
+        function: String,
+        found: TypeNode,
+    },
 
     // Type/Operator Errors
-    OperatorTypeMismatch(TypeMismatch),
-    EmptyCollectionTypeInferenceError(TypeMismatch),
-    InvalidConditionType(TypeMismatch),
+    OperatorTypeMismatch(Box<TypeMismatch>),
+    EmptyCollectionTypeInferenceError(Box<TypeMismatch>),
+    InvalidConditionType(Box<TypeMismatch>),
+    UntypedNull,
+    IfBranchTypeMismatch(Box<TypeMismatch>),
 
     // Print
     InvalidPrintType {
@@ -94,9 +111,11 @@ pub enum SemanticError {
         pattern: Pattern,
     },
     RangeIterationTypeMismatch {
-        expected: TypeNode,
+        expected: Box<TypeNode>,
         found: TypeNode,
     },
+    BreakValueTypeMismatch(Box<TypeMismatch>),
+    ZeroRangeStep,
 
     // Struct
     StructRedeclaration(NamedError),
@@ -104,6 +123,28 @@ pub enum SemanticError {
         struct_name: String,
         field: String,
     },
+    UndeclaredStruct(NamedError),
+    UnknownField {
+        struct_name: String,
+        field: String,
+    },
+    MissingField {
+        struct_name: String,
+        field: String,
+    },
+    InvalidFieldAccess {
+        found: TypeNode,
+        field: String,
+    },
+    UnknownMethod {
+        found: TypeNode,
+        method: String,
+    },
+    WeakFieldType {
+        struct_name: String,
+        field: String,
+        found: Box<TypeNode>,
+    },
 
     // Enum
     EnumRedeclaration(NamedError),
@@ -112,6 +153,27 @@ pub enum SemanticError {
         enum_name: String,
         variant: String,
     },
+    UndeclaredEnum(NamedError),
+    UnknownVariant {
+        enum_name: String,
+        variant: String,
+    },
+    /// A payload given to a unit variant or missing for a payload variant
+    VariantPayloadMismatch {
+        enum_name: String,
+        variant: String,
+        expected: Option<Box<TypeNode>>,
+    },
+    /// `value?` on a value that isn't a Result
+    InvalidTryOperand {
+        found: TypeNode,
+    },
+    /// `value?` in a function that doesn't return a Result with the value's error type
+    TryReturnTypeMismatch {
+        function: String,
+        found: TypeNode,
+        return_type: Box<TypeNode>,
+    },
 
     // --- Module Import Errors ---
     ModuleNotFound(String),
@@ -119,12 +181,75 @@ pub enum SemanticError {
     CircularImport {
         cycle: Vec<String>,
     },
-    ParseError,
+    /// The program has no `main` function to start from
+    MissingMain,
 
     ParseErrorInModule {
         file: String,
         error: String,
     },
+    /// `includeStr` or `includeBytes` couldn't read its file
+    IncludeFailed {
+        path: String,
+        reason: String,
+    },
+    /// `import utils::helper;` of a function the module keeps to itself
+    PrivateFunction {
+        name: String,
+        module: String,
+    },
+
+    // Match
+    InvalidMatchType {
+        found: TypeNode,
+    },
+    MatchPatternTypeMismatch(Box<TypeMismatch>),
+    NonExhaustiveMatch {
+        missing: String,
+    },
+    UnreachableMatchArm {
+        arm: usize, // 1-based position of the arm
+    },
+    MatchArmTypeMismatch(Box<TypeMismatch>),
+
+    // Trait
+    TraitRedeclaration(NamedError),
+    UndeclaredTrait(NamedError),
+    DuplicateTraitMethod {
+        trait_name: String,
+        method: String,
+    },
+    MissingTraitMethod {
+        trait_name: String,
+        struct_name: String,
+        method: String,
+    },
+    /// An impl method whose signature differs from the trait's, or that the trait doesn't list
+    TraitMethodMismatch {
+        trait_name: String,
+        method: String,
+    },
+    UnsatisfiedTraitBound {
+        trait_name: String,
+        found: TypeNode,
+    },
+    /// A type parameter no argument of the call determines
+    UninferredTypeParam {
+        function: String,
+        type_param: String,
+    },
+
+    /// An error for an undeclared name, with a declared name it may be a typo of
+    DidYouMean {
+        suggestion: String,
+        error: Box<SemanticError>,
+    },
+
+    /// Another error, with the start of the statement it occurred in
+    Located {
+        span: Span,
+        error: Box<SemanticError>,
+    },
 }
 
 impl fmt::Display for TypeNode {
@@ -132,7 +257,9 @@ impl fmt::Display for TypeNode {
         match self {
             TypeNode::Float => write!(f, "Float"),
             TypeNode::Int => write!(f, "Int"),
+            TypeNode::Int64 => write!(f, "Int64"),
             TypeNode::String => write!(f, "String"),
+            TypeNode::CStr => write!(f, "CStr"),
             TypeNode::Bool => write!(f, "Bool"),
             TypeNode::Array(t) => write!(f, "Array<{}>", t),
             TypeNode::Map(k, v) => write!(f, "Map<{}, {}>", k, v),
@@ -151,6 +278,18 @@ impl fmt::Display for TypeNode {
                 if *inclusive { ", inclusive" } else { "" }
             ),
             TypeNode::TypeRef(s) => write!(f, "{}", s),
+            // A bare `null` has no payload type yet
+            TypeNode::Optional(t) if **t == TypeNode::Void => write!(f, "null"),
+            TypeNode::Optional(t) => write!(f, "{}?", t),
+            TypeNode::Variadic(t) => write!(f, "{}...", t),
+            TypeNode::Never => write!(f, "Never"),
+            TypeNode::Function(params, ret) => {
+                let parts: Vec<String> = params.iter().map(|t| t.to_string()).collect();
+                match &**ret {
+                    TypeNode::Void => write!(f, "fn({})", parts.join(", ")),
+                    ret => write!(f, "fn({}) -> {}", parts.join(", "), ret),
+                }
+            }
         }
     }
 }
@@ -168,8 +307,53 @@ impl fmt::Display for NamedError {
 }
 
 impl SemanticError {
+    /// This error located at `span`: the expression or statement it occurred
+    /// in. Errors that already have a location keep it, so the innermost
+    /// one wins, and made-up code has none.
+    pub fn at(self, span: Span) -> SemanticError {
+        match self {
+            _ if self.span().is_some() => self,
+            _ if span == Span::default() => self,
+            _ => SemanticError::Located {
+                span,
+                error: Box::new(self),
+            },
+        }
+    }
+
+    /// The error itself, without its location or suggestion.
+    pub fn inner(&self) -> &SemanticError {
+        match self {
+            SemanticError::Located { error, .. } | SemanticError::DidYouMean { error, .. } => {
+                error.inner()
+            }
+            _ => self,
+        }
+    }
+
+    /// This error located at the name or operator of `node`, if it has one.
+    pub fn at_node(self, node: &AstNode) -> SemanticError {
+        match node.span() {
+            Some(span) => self.at(span),
+            None => self,
+        }
+    }
+
+    /// Where the error occurred, if known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            SemanticError::Located { span, .. } => Some(*span),
+            // A mismatched operand carries its own span
+            SemanticError::OperatorTypeMismatch(m) | SemanticError::VarTypeMismatch(m) => m.span,
+            _ => None,
+        }
+    }
+
     pub fn code(&self) -> &'static str {
         match self {
+            SemanticError::Located { error, .. } | SemanticError::DidYouMean { error, .. } => {
+                error.code()
+            }
             // Variable Declaration/Assignment Errors
             SemanticError::VariableRedeclaration(_) => "E0001",
             SemanticError::UndeclaredVariable(_) => "E0002",
@@ -178,6 +362,7 @@ impl SemanticError {
             SemanticError::InvalidAssignmentTarget { .. } => "E0005",
             SemanticError::OutOfScopeVariable(_) => "E0006",
             SemanticError::InvalidMapKeyType { .. } => "E0007",
+            SemanticError::CapturedOuterVariable { .. } => "E0008",
 
             // Function Declaration/Call Errors
             SemanticError::FunctionRedeclaration(_) => "E0101",
@@ -191,11 +376,16 @@ impl SemanticError {
             SemanticError::InvalidReturnInVoidFunction { .. } => "E0109",
             SemanticError::ReturnTypeMismatch { .. } => "E0110",
             SemanticError::InvalidPublicName(_) => "E0111",
+            SemanticError::NoMatchingOverload { .. } => "E0112",
+            SemanticError::ArenaReturnType { .. } => "E0113",
+            SemanticError::InvalidExternType { .. } => "E0114",
 
             // Type/Operator Errors
             SemanticError::OperatorTypeMismatch(_) => "E0201",
             SemanticError::EmptyCollectionTypeInferenceError(_) => "E0202",
             SemanticError::InvalidConditionType(_) => "E0203",
+            SemanticError::UntypedNull => "E0204",
+            SemanticError::IfBranchTypeMismatch(_) => "E0205",
 
             // Print
             SemanticError::InvalidPrintType { .. } => "E0301",
@@ -208,21 +398,129 @@ impl SemanticError {
             SemanticError::NonIterableType { .. } => "E0404",
             SemanticError::InfiniteLoopWithPattern { .. } => "E0405",
             SemanticError::RangeIterationTypeMismatch { .. } => "E0406",
+            SemanticError::BreakValueTypeMismatch(_) => "E0407",
+            SemanticError::ZeroRangeStep => "E0408",
 
             // Struct
             SemanticError::StructRedeclaration(_) => "E0501",
             SemanticError::DuplicateField { .. } => "E0502",
+            SemanticError::UndeclaredStruct(_) => "E0503",
+            SemanticError::UnknownField { .. } => "E0504",
+            SemanticError::MissingField { .. } => "E0505",
+            SemanticError::InvalidFieldAccess { .. } => "E0506",
+            SemanticError::UnknownMethod { .. } => "E0507",
+            SemanticError::WeakFieldType { .. } => "E0508",
 
             // Enum
             SemanticError::EnumRedeclaration(_) => "E0601",
             SemanticError::DuplicateEnumVariant { .. } => "E0602",
+            SemanticError::UndeclaredEnum(_) => "E0603",
+            SemanticError::UnknownVariant { .. } => "E0604",
+            SemanticError::VariantPayloadMismatch { .. } => "E0605",
+            SemanticError::InvalidTryOperand { .. } => "E0606",
+            SemanticError::TryReturnTypeMismatch { .. } => "E0607",
 
             // Module Import / Parse
             SemanticError::ModuleNotFound(_) => "E0701",
-            SemanticError::ParseError => "E0702",
+            SemanticError::MissingMain => "E0702",
 
             SemanticError::ParseErrorInModule { .. } => "E0703",
             SemanticError::CircularImport { .. } => "E0704",
+            SemanticError::IncludeFailed { .. } => "E0705",
+            SemanticError::PrivateFunction { .. } => "E0706",
+
+            // Match
+            SemanticError::InvalidMatchType { .. } => "E0801",
+            SemanticError::MatchPatternTypeMismatch(_) => "E0802",
+            SemanticError::NonExhaustiveMatch { .. } => "E0803",
+            SemanticError::UnreachableMatchArm { .. } => "E0804",
+            SemanticError::MatchArmTypeMismatch(_) => "E0805",
+
+            // Trait
+            SemanticError::TraitRedeclaration(_) => "E0901",
+            SemanticError::UndeclaredTrait(_) => "E0902",
+            SemanticError::DuplicateTraitMethod { .. } => "E0903",
+            SemanticError::MissingTraitMethod { .. } => "E0904",
+            SemanticError::TraitMethodMismatch { .. } => "E0905",
+            SemanticError::UnsatisfiedTraitBound { .. } => "E0906",
+            SemanticError::UninferredTypeParam { .. } => "E0907",
+        }
+    }
+}
+
+impl SemanticError {
+    /// Why the error happened, when the message alone doesn't say.
+    pub fn note(&self) -> Option<String> {
+        use SemanticError as E;
+        match self {
+            E::Located { error, .. } | E::DidYouMean { error, .. } => error.note(),
+            E::OutOfScopeVariable(n) => Some(format!(
+                "'{}' was declared in a block that ends before this statement",
+                n
+            )),
+            E::UnreachableMatchArm { .. } => {
+                Some("earlier arms already match every value this arm matches".to_string())
+            }
+            E::ZeroRangeStep => Some("a step of 0 never reaches the end of the range".to_string()),
+            E::UninferredTypeParam { type_param, .. } => Some(format!(
+                "no argument of the call has a type that mentions '{}'",
+                type_param
+            )),
+            E::InvalidTryOperand { .. } => {
+                Some("'?' takes apart values of a Result<T, E> type".to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// How to fix the error, when there is a usual way.
+    pub fn help(&self) -> Option<String> {
+        use SemanticError as E;
+        match self {
+            E::Located { error, .. } => error.help(),
+            E::DidYouMean { suggestion, .. } => Some(format!("did you mean '{}'?", suggestion)),
+            E::UndeclaredVariable(n) => Some(format!("declare it first with 'let {} = ...;'", n)),
+            E::VariableRedeclaration(n) => Some(format!(
+                "declare '{}' with 'let mut' and assign to it, or pick another name",
+                n
+            )),
+            E::MissingFunctionReturn { function } => Some(format!(
+                "end every path through '{}' with 'return', or 'panic(..)' where it can't go on",
+                function
+            )),
+            E::InvalidReturnInVoidFunction { function } => Some(format!(
+                "declare the return type: 'fn {}(..) -> Type'",
+                function
+            )),
+            E::NonExhaustiveMatch { missing } => {
+                Some(format!("add an arm for {}, or a '_' arm", missing))
+            }
+            E::MissingTraitMethod {
+                trait_name,
+                struct_name,
+                method,
+            } => Some(format!(
+                "add 'fn {}' to 'impl {} for {}'",
+                method, trait_name, struct_name
+            )),
+            E::MissingMain => Some("the program starts at 'fn main() { .. }'".to_string()),
+            E::CircularImport { .. } => {
+                Some("move what the modules share into a module neither imports".to_string())
+            }
+            E::PrivateFunction { name, .. } => {
+                let mut chars = name.chars();
+                let public: String = chars
+                    .next()
+                    .into_iter()
+                    .flat_map(char::to_uppercase)
+                    .chain(chars)
+                    .collect();
+                Some(format!(
+                    "functions starting with an uppercase letter are public: rename it '{}'",
+                    public
+                ))
+            }
+            _ => None,
         }
     }
 }
@@ -231,6 +529,7 @@ impl fmt::Display for SemanticError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use SemanticError as E;
         match self {
+            E::Located { error, .. } | E::DidYouMean { error, .. } => write!(f, "{}", error),
             E::CircularImport { cycle } => {
                 write!(
                     f,
@@ -272,6 +571,13 @@ impl fmt::Display for SemanticError {
                 self.code(),
                 n
             ),
+            E::CapturedOuterVariable { name, function } => write!(
+                f,
+                "error[{}]: nested function '{}' cannot use '{}' of its enclosing function; pass it as a parameter",
+                self.code(),
+                function,
+                name
+            ),
             E::InvalidMapKeyType { found, expected } => write!(
                 f,
                 "error[{}]: invalid map key type: expected {}, found {}",
@@ -357,6 +663,30 @@ impl fmt::Display for SemanticError {
                 self.code(),
                 n
             ),
+            E::NoMatchingOverload { name, found } => {
+                let args: Vec<String> = found.iter().map(|t| t.to_string()).collect();
+                write!(
+                    f,
+                    "error[{}]: no overload of '{}' takes arguments ({})",
+                    self.code(),
+                    name,
+                    args.join(", ")
+                )
+            }
+            E::ArenaReturnType { function, found } => write!(
+                f,
+                "error[{}]: arena function '{}' can't return {}, which it would free",
+                self.code(),
+                function,
+                found
+            ),
+            E::InvalidExternType { function, found } => write!(
+                f,
+                "error[{}]: function '{}' can't pass {} to or from C",
+                self.code(),
+                function,
+                found
+            ),
 
             // Type/Operator Errors
             E::OperatorTypeMismatch(m) => {
@@ -371,6 +701,17 @@ impl fmt::Display for SemanticError {
             E::InvalidConditionType(m) => {
                 write!(f, "error[{}]: invalid condition type: {}", self.code(), m)
             }
+            E::UntypedNull => write!(
+                f,
+                "error[{}]: cannot infer the type of null; add an optional type annotation (e.g. Int?)",
+                self.code()
+            ),
+            E::IfBranchTypeMismatch(m) => write!(
+                f,
+                "error[{}]: if and else branches have different types: {}",
+                self.code(),
+                m
+            ),
 
             // Print
             E::InvalidPrintType { found } => write!(
@@ -423,6 +764,13 @@ impl fmt::Display for SemanticError {
                 expected,
                 found
             ),
+            E::BreakValueTypeMismatch(m) => write!(
+                f,
+                "error[{}]: break values of the loop have different types: {}",
+                self.code(),
+                m
+            ),
+            E::ZeroRangeStep => write!(f, "error[{}]: range step cannot be zero", self.code()),
 
             // Struct
             E::StructRedeclaration(n) => {
@@ -435,6 +783,49 @@ impl fmt::Display for SemanticError {
                 struct_name,
                 field
             ),
+            E::UndeclaredStruct(n) => {
+                write!(f, "error[{}]: undeclared struct '{}'", self.code(), n)
+            }
+            E::UnknownField { struct_name, field } => write!(
+                f,
+                "error[{}]: struct '{}' has no field '{}'",
+                self.code(),
+                struct_name,
+                field
+            ),
+            E::MissingField { struct_name, field } => write!(
+                f,
+                "error[{}]: missing field '{}' in '{}' literal",
+                self.code(),
+                field,
+                struct_name
+            ),
+            E::InvalidFieldAccess { found, field } => write!(
+                f,
+                "error[{}]: cannot access field '{}' on {}",
+                self.code(),
+                field,
+                found
+            ),
+            E::UnknownMethod { found, method } => write!(
+                f,
+                "error[{}]: no method '{}' on {}",
+                self.code(),
+                method,
+                found
+            ),
+            E::WeakFieldType {
+                struct_name,
+                field,
+                found,
+            } => write!(
+                f,
+                "error[{}]: weak field '{}' of '{}' must be an optional struct, found {}",
+                self.code(),
+                field,
+                struct_name,
+                found
+            ),
 
             // Enum
             E::EnumRedeclaration(n) => write!(f, "error[{}]: enum '{}' redeclared", self.code(), n),
@@ -445,14 +836,149 @@ impl fmt::Display for SemanticError {
                 enum_name,
                 variant
             ),
+            E::UndeclaredEnum(n) => write!(f, "error[{}]: undeclared enum '{}'", self.code(), n),
+            E::UnknownVariant { enum_name, variant } => write!(
+                f,
+                "error[{}]: enum '{}' has no variant '{}'",
+                self.code(),
+                enum_name,
+                variant
+            ),
+            E::VariantPayloadMismatch {
+                enum_name,
+                variant,
+                expected: Some(ty),
+            } => write!(
+                f,
+                "error[{}]: variant '{}::{}' takes a payload of type {}",
+                self.code(),
+                enum_name,
+                variant,
+                ty
+            ),
+            E::VariantPayloadMismatch {
+                enum_name,
+                variant,
+                expected: None,
+            } => write!(
+                f,
+                "error[{}]: variant '{}::{}' takes no payload",
+                self.code(),
+                enum_name,
+                variant
+            ),
+            E::InvalidTryOperand { found } => write!(
+                f,
+                "error[{}]: '?' needs a Result value, found {}",
+                self.code(),
+                found
+            ),
+            E::TryReturnTypeMismatch {
+                function,
+                found,
+                return_type,
+            } => write!(
+                f,
+                "error[{}]: '?' on {} needs '{}' to return a Result with the same error type, found {}",
+                self.code(),
+                found,
+                function,
+                return_type
+            ),
 
             // Module Import / Parse
             E::ModuleNotFound(p) => write!(f, "error[{}]: module not found: {}", self.code(), p),
-            E::ParseError => write!(f, "error[{}]: parse error in imported module", self.code()),
+            E::MissingMain => write!(f, "error[{}]: no 'main' function", self.code()),
 
             E::ParseErrorInModule { file, error } => {
                 write!(f, "error[{}] in {}: {}", self.code(), file, error)
             }
+            E::IncludeFailed { path, reason } => write!(
+                f,
+                "error[{}]: can't include '{}': {}",
+                self.code(),
+                path,
+                reason
+            ),
+            E::PrivateFunction { name, module } => write!(
+                f,
+                "error[{}]: function '{}' is private to module {}",
+                self.code(),
+                name,
+                module
+            ),
+
+            // Match
+            E::InvalidMatchType { found } => write!(
+                f,
+                "error[{}]: cannot match on a value of type {}",
+                self.code(),
+                found
+            ),
+            E::MatchPatternTypeMismatch(m) => {
+                write!(f, "error[{}]: match pattern type mismatch: {}", self.code(), m)
+            }
+            E::NonExhaustiveMatch { missing } => write!(
+                f,
+                "error[{}]: non-exhaustive match: pattern {} not covered",
+                self.code(),
+                missing
+            ),
+            E::UnreachableMatchArm { arm } => {
+                write!(f, "error[{}]: match arm {} is unreachable", self.code(), arm)
+            }
+            E::MatchArmTypeMismatch(m) => {
+                write!(f, "error[{}]: match arms have different types: {}", self.code(), m)
+            }
+
+            // Trait
+            E::TraitRedeclaration(n) => {
+                write!(f, "error[{}]: trait '{}' redeclared", self.code(), n)
+            }
+            E::UndeclaredTrait(n) => write!(f, "error[{}]: undeclared trait '{}'", self.code(), n),
+            E::DuplicateTraitMethod { trait_name, method } => write!(
+                f,
+                "error[{}]: trait '{}' has duplicate method '{}'",
+                self.code(),
+                trait_name,
+                method
+            ),
+            E::MissingTraitMethod {
+                trait_name,
+                struct_name,
+                method,
+            } => write!(
+                f,
+                "error[{}]: impl of '{}' for '{}' is missing method '{}'",
+                self.code(),
+                trait_name,
+                struct_name,
+                method
+            ),
+            E::TraitMethodMismatch { trait_name, method } => write!(
+                f,
+                "error[{}]: method '{}' does not match any method of trait '{}'",
+                self.code(),
+                method,
+                trait_name
+            ),
+            E::UnsatisfiedTraitBound { trait_name, found } => write!(
+                f,
+                "error[{}]: {} does not implement trait '{}'",
+                self.code(),
+                found,
+                trait_name
+            ),
+            E::UninferredTypeParam {
+                function,
+                type_param,
+            } => write!(
+                f,
+                "error[{}]: cannot infer type parameter '{}' of '{}' from the arguments",
+                self.code(),
+                type_param,
+                function
+            ),
         }
     }
 }
@@ -0,0 +1,106 @@
+use std::collections::HashSet;
+
+use super::analyzer::SemanticAnalyzer;
+use crate::parser::ast::{AstNode, Span};
+
+/// The lints the analyzer reports. Each is a warning unless the command line
+/// allows or denies it, or `#[allow(..)]` turns it off for a function.
+pub const LINTS: &[&str] = &["unreachable", "unused"];
+
+/// A lint reported by the analyzer. Unlike an error it doesn't stop compilation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub lint: &'static str,
+    pub message: String,
+    pub help: Option<String>,
+    pub span: Span,
+}
+
+/// How a lint is reported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// Lint levels set on the command line: `-A`, `-W` and `-D` with a lint
+/// name, and `--deny-warnings`. Deny wins over warn, and warn over allow.
+#[derive(Debug, Clone, Default)]
+pub struct LintLevels {
+    pub allow: Vec<String>,
+    pub warn: Vec<String>,
+    pub deny: Vec<String>,
+    pub deny_warnings: bool, // Every lint that warns becomes an error
+}
+
+impl LintLevels {
+    pub fn level(&self, lint: &str) -> LintLevel {
+        let named = |lints: &[String]| lints.iter().any(|name| name == lint);
+        if named(&self.deny) {
+            LintLevel::Deny
+        } else if named(&self.warn) || !named(&self.allow) {
+            if self.deny_warnings {
+                LintLevel::Deny
+            } else {
+                LintLevel::Warn
+            }
+        } else {
+            LintLevel::Allow
+        }
+    }
+}
+
+impl SemanticAnalyzer {
+    /// Records a warning unless an enclosing function allows its lint. Generic
+    /// functions are analyzed once per instance, so repeats are dropped.
+    pub(crate) fn warn(&mut self, lint: &'static str, message: String, help: Option<String>) {
+        if self.allowed_lints.iter().any(|allowed| allowed == lint) {
+            return;
+        }
+        let warning = Warning {
+            lint,
+            message,
+            help,
+            span: self.statement_span,
+        };
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+
+    /// Records the variables a `let` inside a function declares, to warn
+    /// about the ones never read.
+    pub(crate) fn declare_variables(&mut self, node: &AstNode) {
+        let AstNode::LetDecl { pattern, .. } = node else {
+            return;
+        };
+        let mut names = HashSet::new();
+        super::analyzer::pattern_names(pattern, &mut names);
+        let mut names: Vec<String> = names.into_iter().collect();
+        names.sort();
+        for name in names {
+            self.declared_variables.push((name, self.statement_span));
+        }
+    }
+
+    /// Warns about the variables in `declared` that were never read.
+    pub(crate) fn warn_unused_variables(
+        &mut self,
+        declared: Vec<(String, Span)>,
+        used: &HashSet<String>,
+    ) {
+        for (name, span) in declared {
+            if used.contains(&name) {
+                continue;
+            }
+            let statement_span = std::mem::replace(&mut self.statement_span, span);
+            self.warn(
+                "unused",
+                format!("unused variable '{}'", name),
+                Some("remove it, or put #[allow(unused)] on the function".to_string()),
+            );
+            self.statement_span = statement_span;
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! The compiler as a C library, `libdoo`, for editors, build tools and other
+//! languages to embed. `include/doo.h` declares these functions.
+//!
+//! `doo_compile_source` compiles program text held in memory and returns a
+//! `DooResult` owning everything it produced: whether it compiled, the
+//! diagnostics as JSON lines (the format of `doo check
+//! --message-format=json`), an error that stopped the compile, and the
+//! artifact asked for. Strings and bytes borrowed from a result live until `doo_result_free`.
+
+use crate::compiler::{compile_source, Artifact, CompileOptions, OptLevel};
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+
+/// Only check the program: diagnostics, no artifact.
+pub const DOO_EMIT_CHECK: i32 = 0;
+/// The optimized MIR as text.
+pub const DOO_EMIT_MIR: i32 = 1;
+/// The optimized LLVM module as text.
+pub const DOO_EMIT_LLVM_IR: i32 = 2;
+/// An object file for the host.
+pub const DOO_EMIT_OBJECT: i32 = 3;
+
+/// How `doo_compile_source` compiles. A null `DooOptions` pointer means all
+/// fields zero: check `main.doo`.
+#[repr(C)]
+pub struct DooOptions {
+    /// The name diagnostics give the source, whose directory imports and
+    /// includes are found in; null for `main.doo`.
+    pub file_name: *const c_char,
+    /// One of the `DOO_EMIT_*` constants.
+    pub emit: i32,
+    /// 0 to 3, as in `-O`.
+    pub opt_level: i32,
+    /// Nonzero to leave out `assert` checks, as `--release` does.
+    pub release: i32,
+}
+
+/// What a compile produced. Opaque to C.
+pub struct DooResult {
+    success: bool,
+    diagnostics: CString,
+    error: Option<CString>,
+    artifact: Vec<u8>,
+}
+
+impl DooResult {
+    /// A compile that stopped with `message` instead of diagnostics.
+    fn failed(message: String) -> Self {
+        DooResult {
+            success: false,
+            diagnostics: CString::default(),
+            error: Some(c_string(message)),
+            artifact: Vec::new(),
+        }
+    }
+}
+
+/// `text` for C, which can't hold NUL bytes inside a string.
+fn c_string(text: String) -> CString {
+    CString::new(text.replace('\0', "")).unwrap_or_default()
+}
+
+/// Compiles the NUL-terminated UTF-8 program text `source`. Never returns
+/// null; free the result with `doo_result_free`.
+///
+/// # Safety
+///
+/// `source` must point to a NUL-terminated string, and `options` must be
+/// null or point to a `DooOptions` whose `file_name` is null or a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn doo_compile_source(
+    source: *const c_char,
+    options: *const DooOptions,
+) -> *mut DooResult {
+    let result = compile(source, options.as_ref());
+    Box::into_raw(Box::new(result))
+}
+
+unsafe fn compile(source: *const c_char, options: Option<&DooOptions>) -> DooResult {
+    if source.is_null() {
+        return DooResult::failed("Error: source is null".to_string());
+    }
+    let Ok(source) = CStr::from_ptr(source).to_str() else {
+        return DooResult::failed("Error: source is not UTF-8".to_string());
+    };
+
+    let (file_name, emit, opt_level, release) = match options {
+        Some(options) => {
+            let file_name = if options.file_name.is_null() {
+                None
+            } else {
+                match CStr::from_ptr(options.file_name).to_str() {
+                    Ok(name) => Some(name),
+                    Err(_) => {
+                        return DooResult::failed("Error: file_name is not UTF-8".to_string())
+                    }
+                }
+            };
+            (
+                file_name,
+                options.emit,
+                options.opt_level,
+                options.release != 0,
+            )
+        }
+        None => (None, DOO_EMIT_CHECK, 0, false),
+    };
+    let artifact = match emit {
+        DOO_EMIT_CHECK => None,
+        DOO_EMIT_MIR => Some(Artifact::Mir),
+        DOO_EMIT_LLVM_IR => Some(Artifact::LlvmIr),
+        DOO_EMIT_OBJECT => Some(Artifact::Object),
+        _ => return DooResult::failed(format!("Error: unknown emit kind {}", emit)),
+    };
+    let Some(opt_level) = OptLevel::from_name(&opt_level.to_string()) else {
+        return DooResult::failed(format!("Error: unknown optimization level {}", opt_level));
+    };
+
+    let input_path = PathBuf::from(file_name.unwrap_or("main.doo"));
+    let opts = CompileOptions {
+        input_path: input_path.clone(),
+        dev_mode: false,
+        check_only: artifact.is_none(),
+        release,
+        opt_level: Some(opt_level),
+        artifact,
+        ..Default::default()
+    };
+
+    // A panic must not unwind into C
+    let compiled = panic::catch_unwind(AssertUnwindSafe(|| {
+        compile_source(&input_path, source, opts)
+    }));
+    match compiled {
+        Ok(Ok(result)) => {
+            let diagnostics: String = result
+                .diagnostics
+                .iter()
+                .map(|d| d.to_json() + "\n")
+                .collect();
+            DooResult {
+                success: result.success,
+                diagnostics: c_string(diagnostics),
+                error: None,
+                artifact: result.artifact.unwrap_or_default(),
+            }
+        }
+        Ok(Err(message)) => DooResult::failed(message),
+        Err(_) => DooResult::failed("Error: internal compiler error".to_string()),
+    }
+}
+
+/// 1 when the program compiled without errors, else 0.
+///
+/// # Safety
+///
+/// `result` must come from `doo_compile_source` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn doo_result_success(result: *const DooResult) -> i32 {
+    result.as_ref().is_some_and(|result| result.success) as i32
+}
+
+/// The diagnostics, one JSON object per line; empty when there are none.
+///
+/// # Safety
+///
+/// `result` must come from `doo_compile_source` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn doo_result_diagnostics(result: *const DooResult) -> *const c_char {
+    match result.as_ref() {
+        Some(result) => result.diagnostics.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// The error that stopped the compile before it could report diagnostics,
+/// such as a missing `main`, or null.
+///
+/// # Safety
+///
+/// `result` must come from `doo_compile_source` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn doo_result_error(result: *const DooResult) -> *const c_char {
+    match result.as_ref().and_then(|result| result.error.as_ref()) {
+        Some(error) => error.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// The artifact asked for, its size stored in `len`; null when there is
+/// none. MIR and LLVM IR are text without a NUL terminator.
+///
+/// # Safety
+///
+/// `result` must come from `doo_compile_source` and not be freed yet, and
+/// `len` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn doo_result_artifact(
+    result: *const DooResult,
+    len: *mut usize,
+) -> *const u8 {
+    let artifact = result
+        .as_ref()
+        .map_or(&[][..], |result| &result.artifact[..]);
+    if let Some(len) = len.as_mut() {
+        *len = artifact.len();
+    }
+    if artifact.is_empty() {
+        ptr::null()
+    } else {
+        artifact.as_ptr()
+    }
+}
+
+/// Frees a result and everything borrowed from it. Null is ignored.
+///
+/// # Safety
+///
+/// `result` must be null or come from `doo_compile_source` and not be freed
+/// yet.
+#[no_mangle]
+pub unsafe extern "C" fn doo_result_free(result: *mut DooResult) {
+    if !result.is_null() {
+        drop(Box::from_raw(result));
+    }
+}
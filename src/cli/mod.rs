@@ -1,4 +1,7 @@
-use clap::{Parser, Subcommand};
+use clap::builder::PossibleValuesParser;
+use clap::{Args, Parser, Subcommand};
+use doo::analyzer::warnings::{LintLevels, LINTS};
+use doo::compiler::{LinkLib, LinkOptions};
 use std::path::PathBuf;
 
 /// CLI definition for the doo language tool.
@@ -9,6 +12,10 @@ use std::path::PathBuf;
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
+
+    /// When to color output: `auto` colors it on a terminal unless NO_COLOR is set
+    #[arg(long, global = true, default_value = "auto", value_parser = ["always", "auto", "never"])]
+    pub color: String,
 }
 
 /// Supported subcommands for the doo CLI.
@@ -16,17 +23,67 @@ pub struct Cli {
 pub enum Commands {
     /// Build the project to a persistent binary
     Build {
-        /// Path to the project directory or .doo file
+        /// Path to the project directory (with a doo.toml, or a main.doo), .doo file, or .mir file to build without the front end
         #[arg(default_value = ".")]
         path: PathBuf,
 
-        /// Name of the output binary
+        /// Name of the output binary; without it, the one doo.toml names, or `output`
         #[arg(short, long, default_value = "output")]
         output: String,
 
         /// Keep the generated LLVM IR (.ll) file
         #[arg(long)]
         keep_ll: bool,
+
+        /// Keep the MIR (.mir) file, which can be edited and built again
+        #[arg(long)]
+        keep_mir: bool,
+
+        /// Also write the target's assembly next to the binary as `<output>.s`, or a C header declaring the `export "C"` functions as `<output>.h`
+        #[arg(long, value_name = "KIND", value_parser = ["asm", "header"])]
+        emit: Option<String>,
+
+        /// Build for release: leave out `assert` checks
+        #[arg(long)]
+        release: bool,
+
+        /// Panic when Int arithmetic overflows instead of wrapping around
+        #[arg(long)]
+        overflow_checks: bool,
+
+        /// Change reference counts with atomic instructions, for hosts that share values between threads
+        #[arg(long)]
+        atomic_rc: bool,
+
+        /// Generate the functions with LLVM on several threads, a module each, linked into one before optimizing; not with --debug
+        #[arg(long)]
+        parallel_codegen: bool,
+
+        /// Emit DWARF debug info and skip optimizations, for stepping through in gdb or lldb
+        #[arg(long)]
+        debug: bool,
+
+        /// Optimization level: 0, 1, 2, 3 or s (size). Defaults to 2, or 0 with --debug
+        #[arg(short = 'O', value_name = "LEVEL", value_parser = ["0", "1", "2", "3", "s"])]
+        opt_level: Option<String>,
+
+        /// Build for another platform, e.g. `x86_64-unknown-linux-musl` or `aarch64-unknown-linux-gnu`
+        #[arg(long, value_name = "TRIPLE")]
+        target: Option<String>,
+
+        /// Link-time optimization: ThinLTO through the linker (needs clang and lld)
+        #[arg(long)]
+        lto: bool,
+
+        /// What builds the program: `llvm`, or `cranelift` (experimental) for much faster unoptimized builds of the scalar subset of the language
+        #[arg(long, default_value = "llvm", value_parser = ["llvm", "cranelift"])]
+        backend: String,
+
+        #[command(flatten)]
+        link: LinkArgs,
+
+        #[command(flatten)]
+        lints: LintArgs,
     },
 
     /// Compile and run immediately (auto-cleanup)
@@ -39,6 +96,33 @@ pub enum Commands {
         #[arg(long)]
         keep_ll: bool,
 
+        /// Build for release: leave out `assert` checks
+        #[arg(long)]
+        release: bool,
+
+        /// Panic when Int arithmetic overflows instead of wrapping around
+        #[arg(long)]
+        overflow_checks: bool,
+
+        /// Change reference counts with atomic instructions, for hosts that share values between threads
+        #[arg(long)]
+        atomic_rc: bool,
+
+        /// Run the program with the MIR interpreter instead of building it with LLVM
+        #[arg(long)]
+        interp: bool,
+
+        /// What builds the program: `llvm`, or `cranelift` (experimental) for much faster unoptimized builds of the scalar subset of the language
+        #[arg(long, default_value = "llvm", value_parser = ["llvm", "cranelift"])]
+        backend: String,
+
+        /// Optimization level: 0, 1, 2, 3 or s (size). Defaults to 2, or 0 with --debug
+        #[arg(short = 'O', value_name = "LEVEL", value_parser = ["0", "1", "2", "3", "s"])]
+        opt_level: Option<String>,
+
+        #[command(flatten)]
+        lints: LintArgs,
+
         /// Arguments to pass to the program
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
@@ -49,15 +133,160 @@ pub enum Commands {
         /// Path to the project directory or main.doo file
         #[arg(default_value = ".")]
         path: PathBuf,
+
+        /// How to print diagnostics: `human` text on stderr, or one `json` object per line on stdout
+        #[arg(long, default_value = "human", value_parser = ["human", "json"])]
+        message_format: String,
+
+        /// Print the AST as JSON on stdout: `ast-json` as parsed, `typed-ast-json` once analyzed
+        #[arg(long, value_name = "KIND", value_parser = ["ast-json", "typed-ast-json"])]
+        emit: Option<String>,
+
+        #[command(flatten)]
+        lints: LintArgs,
     },
+
+    /// Run the `#[test]` functions, each on its own, and report which failed
+    Test {
+        /// Path to the project directory or main.doo file
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        /// Build for release: leave out `assert` checks
+        #[arg(long)]
+        release: bool,
+
+        /// Panic when Int arithmetic overflows instead of wrapping around
+        #[arg(long)]
+        overflow_checks: bool,
+
+        /// Run the tests with the MIR interpreter instead of building them with LLVM
+        #[arg(long)]
+        interp: bool,
+
+        #[command(flatten)]
+        lints: LintArgs,
+    },
+
+    /// Format .doo files in the canonical style
+    Fmt {
+        /// Files or directories to format, searching directories for .doo files; `-` formats standard input to standard output
+        #[arg(default_value = ".")]
+        paths: Vec<PathBuf>,
+
+        /// Change no files: list those that aren't formatted, and fail if there are any
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// Add a dependency to the project's doo.toml, fetching it if it's a git one
+    #[command(group = clap::ArgGroup::new("source").required(true).args(["path", "git"]))]
+    Add {
+        /// The name the project imports it as
+        name: String,
+
+        /// The dependency's directory, relative to the project
+        #[arg(long)]
+        path: Option<PathBuf>,
+
+        /// The git repository to fetch it from
+        #[arg(long)]
+        git: Option<String>,
+
+        /// The branch, tag or commit of `--git` to use; its default branch without it
+        #[arg(long, requires = "git")]
+        rev: Option<String>,
+
+        /// The project directory, holding its doo.toml
+        #[arg(long, default_value = ".")]
+        manifest_dir: PathBuf,
+    },
+
+    /// Explain an error code, such as E0002
+    Explain {
+        /// The error code shown in `error[E0002]`
+        code: String,
+    },
+}
+
+/// Flags for the linker, so extern functions can be linked from their libraries.
+#[derive(Args)]
+pub struct LinkArgs {
+    /// Link a library: `foo`, `static=foo` for its archive, or `dylib=foo`
+    #[arg(short = 'l', value_name = "[KIND=]NAME")]
+    libs: Vec<String>,
+
+    /// Look for libraries in a directory
+    #[arg(short = 'L', value_name = "DIR")]
+    search_paths: Vec<PathBuf>,
+
+    /// Pass an argument on to the linker as it is
+    #[arg(long = "link-arg", value_name = "ARG", allow_hyphen_values = true)]
+    args: Vec<String>,
+
+    /// Link a static executable, C library included
+    #[arg(long = "static")]
+    static_link: bool,
+}
+
+impl TryFrom<LinkArgs> for LinkOptions {
+    type Error = String;
+
+    fn try_from(args: LinkArgs) -> Result<Self, String> {
+        Ok(LinkOptions {
+            libs: args
+                .libs
+                .iter()
+                .map(|lib| LinkLib::from_arg(lib))
+                .collect::<Result<_, _>>()?,
+            search_paths: args.search_paths,
+            args: args.args,
+            static_link: args.static_link,
+        })
+    }
+}
+
+/// Lint level flags shared by the commands that analyze a program.
+#[derive(Args)]
+pub struct LintArgs {
+    /// Don't report a lint
+    #[arg(short = 'A', long = "allow", value_name = "LINT", value_parser = PossibleValuesParser::new(LINTS))]
+    allow: Vec<String>,
+
+    /// Report a lint as a warning, even if allowed
+    #[arg(short = 'W', long = "warn", value_name = "LINT", value_parser = PossibleValuesParser::new(LINTS))]
+    warn: Vec<String>,
+
+    /// Report a lint as an error
+    #[arg(short = 'D', long = "deny", value_name = "LINT", value_parser = PossibleValuesParser::new(LINTS))]
+    deny: Vec<String>,
+
+    /// Report every warning as an error
+    #[arg(long)]
+    deny_warnings: bool,
+}
+
+impl From<LintArgs> for LintLevels {
+    fn from(args: LintArgs) -> Self {
+        LintLevels {
+            allow: args.allow,
+            warn: args.warn,
+            deny: args.deny,
+            deny_warnings: args.deny_warnings,
+        }
+    }
 }
 
 /// Entrypoint for CLI logic.
 /// Returns exit code (0 for success, nonzero for error).
 pub fn run_cli(cli: Cli) -> i32 {
-    use doo::compiler::{compile_project, CompileOptions};
+    use doo::compiler::{compile_project, Backend, CompileOptions, Emit, OptLevel};
+    use doo::output::{bold_green, bold_red};
     use std::process::Command;
 
+    // Without `-O`, the level is the manifest profile's or the default
+    let opt_level_from = |name: Option<String>| name.as_deref().and_then(OptLevel::from_name);
+
     match cli.command {
         None => {
             println!("🎉 doo CLI - doo language tool");
@@ -68,7 +297,27 @@ pub fn run_cli(cli: Cli) -> i32 {
             path,
             output,
             keep_ll,
+            keep_mir,
+            emit,
+            release,
+            overflow_checks,
+            atomic_rc,
+            parallel_codegen,
+            debug,
+            opt_level,
+            target,
+            lto,
+            backend,
+            link,
+            lints,
         }) => {
+            let link = match LinkOptions::try_from(link) {
+                Ok(link) => link,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return 1;
+                }
+            };
             let opts = CompileOptions {
                 input_path: path.clone(),
                 output_name: output.clone(),
@@ -76,20 +325,52 @@ pub fn run_cli(cli: Cli) -> i32 {
                 print_ast: false,
                 print_mir: false,
                 keep_ll,
+                keep_mir,
                 keep_obj: false,
                 check_only: false,
+                interpret: false,
+                backend: Backend::from_name(&backend).unwrap_or_default(),
+                release,
+                debug,
+                opt_level: opt_level_from(opt_level),
+                lto,
+                target,
+                overflow_checks,
+                atomic_rc,
+                parallel_codegen,
+                lint_levels: lints.into(),
+                emit: emit
+                    .iter()
+                    .filter_map(|name| Emit::from_name(name))
+                    .collect(),
+                link,
+                artifact: None,
+                test: false,
+                dependencies: Vec::new(),
             };
 
             match compile_project(opts) {
                 Ok(result) => {
+                    result.print_diagnostics(false);
                     if result.error_count > 0 {
-                        eprintln!("Build failed with {} errors", result.error_count);
+                        eprintln!(
+                            "{} with {} errors",
+                            bold_red("Build failed"),
+                            result.error_count
+                        );
                         return 1;
                     } else if result.success {
-                        println!("✓ Build successful: {}", output);
+                        // The manifest may have named the output
+                        let built = result
+                            .exe_path
+                            .as_deref()
+                            .or(result.obj_path.as_deref())
+                            .and_then(|path| path.file_name())
+                            .map_or(output, |name| name.to_string_lossy().into_owned());
+                        println!("{} Build successful: {}", bold_green("✓"), built);
                         return 0;
                     } else {
-                        eprintln!("Build failed");
+                        eprintln!("{}", bold_red("Build failed"));
                         return 1;
                     }
                 }
@@ -102,6 +383,13 @@ pub fn run_cli(cli: Cli) -> i32 {
         Some(Commands::Run {
             path,
             keep_ll,
+            release,
+            overflow_checks,
+            atomic_rc,
+            interp,
+            backend,
+            opt_level,
+            lints,
             args,
         }) => {
             // Generate unique temp binary name
@@ -116,18 +404,43 @@ pub fn run_cli(cli: Cli) -> i32 {
                 print_ast: false,
                 print_mir: false,
                 keep_ll,
+                keep_mir: false,
                 keep_obj: false,
                 check_only: false,
+                interpret: interp,
+                backend: Backend::from_name(&backend).unwrap_or_default(),
+                release,
+                debug: false,
+                opt_level: opt_level_from(opt_level),
+                lto: false,
+                target: None,
+                overflow_checks,
+                atomic_rc,
+                parallel_codegen: false,
+                lint_levels: lints.into(),
+                emit: Vec::new(),
+                link: Default::default(),
+                artifact: None,
+                test: false,
+                dependencies: Vec::new(),
             };
 
             // Actually compile
             match compile_project(opts) {
                 Ok(result) => {
+                    result.print_diagnostics(false);
                     if result.error_count > 0 || !result.success {
-                        eprintln!("Compilation failed with {} errors", result.error_count);
+                        eprintln!(
+                            "{} with {} errors",
+                            bold_red("Compilation failed"),
+                            result.error_count
+                        );
                         let _ = std::fs::remove_file(&temp_name);
                         return 1;
                     }
+                    if let Some(code) = result.exit_code {
+                        return code;
+                    }
                 }
                 Err(e) => {
                     eprintln!("Failed to compile: {}", e);
@@ -177,7 +490,19 @@ pub fn run_cli(cli: Cli) -> i32 {
             let _ = std::fs::remove_file(&exe_path);
             code
         }
-        Some(Commands::Check { path }) => {
+        Some(Commands::Check {
+            path,
+            message_format,
+            emit,
+            lints,
+        }) => {
+            let json = message_format == "json";
+            let emit: Vec<Emit> = emit
+                .iter()
+                .filter_map(|name| Emit::from_name(name))
+                .collect();
+            // The AST takes stdout, so the summary line is left out
+            let quiet = json || !emit.is_empty();
             let opts = CompileOptions {
                 input_path: path.clone(),
                 output_name: "output".to_string(),
@@ -185,17 +510,49 @@ pub fn run_cli(cli: Cli) -> i32 {
                 print_ast: false,
                 print_mir: false,
                 keep_ll: false,
+                keep_mir: false,
                 keep_obj: false,
                 check_only: true,
+                interpret: false,
+                backend: Backend::Llvm,
+                release: false,
+                debug: false,
+                opt_level: None,
+                lto: false,
+                target: None,
+                overflow_checks: false,
+                atomic_rc: false,
+                parallel_codegen: false,
+                lint_levels: lints.into(),
+                emit,
+                link: Default::default(),
+                artifact: None,
+                test: false,
+                dependencies: Vec::new(),
             };
 
             match compile_project(opts) {
                 Ok(result) => {
+                    result.print_diagnostics(json);
+                    if let Some(ast_json) = &result.ast_json {
+                        println!("{}", ast_json);
+                    }
+                    // JSON output keeps stdout to the diagnostics and the AST; the exit code tells the result
                     if result.error_count > 0 {
-                        println!("Found {} errors", result.error_count);
+                        if !quiet {
+                            println!("{} {} errors", bold_red("Found"), result.error_count);
+                        }
                         return 1;
                     } else {
-                        println!("✓ No errors found");
+                        if !quiet && result.warning_count > 0 {
+                            println!(
+                                "{} No errors found, {} warnings",
+                                bold_green("✓"),
+                                result.warning_count
+                            );
+                        } else if !quiet {
+                            println!("{} No errors found", bold_green("✓"));
+                        }
                         return 0;
                     }
                 }
@@ -205,5 +562,195 @@ pub fn run_cli(cli: Cli) -> i32 {
                 }
             }
         }
+        Some(Commands::Test {
+            path,
+            release,
+            overflow_checks,
+            interp,
+            lints,
+        }) => {
+            // The test build is removed once its tests ran
+            let temp_name = format!("temp_doo_test_{}", std::process::id());
+            let opts = CompileOptions {
+                input_path: path.clone(),
+                output_name: temp_name,
+                dev_mode: false,
+                interpret: interp,
+                release,
+                opt_level: Some(OptLevel::O0),
+                overflow_checks,
+                lint_levels: lints.into(),
+                test: true,
+                dependencies: Vec::new(),
+                ..Default::default()
+            };
+
+            match compile_project(opts) {
+                Ok(result) => {
+                    result.print_diagnostics(false);
+                    if let Some(exe_path) = &result.exe_path {
+                        let _ = std::fs::remove_file(exe_path);
+                    }
+                    if result.error_count > 0 || !result.success {
+                        eprintln!(
+                            "{} with {} errors",
+                            bold_red("Compilation failed"),
+                            result.error_count
+                        );
+                        return 1;
+                    }
+                    print!("{}", doo::testing::report(&result.tests));
+                    if result.tests.iter().all(|outcome| outcome.passed) {
+                        0
+                    } else {
+                        1
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to compile: {}", e);
+                    1
+                }
+            }
+        }
+        Some(Commands::Fmt { paths, check }) => run_fmt(&paths, check),
+        Some(Commands::Add {
+            name,
+            path,
+            git,
+            rev,
+            manifest_dir,
+        }) => {
+            use doo::output::bold_green;
+            use doo::project::{add_dependency, Dependency, DependencySource, MANIFEST_FILE};
+            let source = match (path, git) {
+                (Some(path), _) => DependencySource::Path(path),
+                (None, Some(url)) => DependencySource::Git { url, rev },
+                (None, None) => unreachable!("clap requires --path or --git"),
+            };
+            let dependency = Dependency { name, source };
+            match add_dependency(&manifest_dir, &dependency) {
+                Ok(()) => {
+                    println!(
+                        "{} Added {} to {}",
+                        bold_green("✓"),
+                        dependency.name,
+                        MANIFEST_FILE
+                    );
+                    0
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    1
+                }
+            }
+        }
+        Some(Commands::Explain { code }) => match doo::diagnostics::codes::explain(&code) {
+            Some(info) => {
+                println!("{}: {}\n", info.code, info.title);
+                println!("{}", info.explanation);
+                0
+            }
+            None => {
+                eprintln!("Unknown error code: {}", code);
+                1
+            }
+        },
+    }
+}
+
+/// `doo fmt`: formats each file `paths` names in place, or with `check` lists
+/// the ones formatting would change. Files with syntax errors are reported
+/// and left alone. Returns the exit code.
+fn run_fmt(paths: &[PathBuf], check: bool) -> i32 {
+    use doo::diagnostics::print_grouped;
+    use doo::formatter::{format_source, source_files};
+    use doo::output::{bold_green, bold_red};
+    use std::collections::HashMap;
+    use std::io::{Read, Write};
+
+    let mut files = Vec::new();
+    for path in paths {
+        if path.as_os_str() == "-" {
+            files.push(path.clone());
+            continue;
+        }
+        match source_files(path) {
+            Ok(found) => files.extend(found),
+            Err(e) => {
+                eprintln!("Error: {}: {}", path.display(), e);
+                return 1;
+            }
+        }
+    }
+
+    let mut failed = 0;
+    let mut unformatted = 0;
+    for file in &files {
+        let stdin = file.as_os_str() == "-";
+        let name = if stdin {
+            "<stdin>".to_string()
+        } else {
+            file.display().to_string()
+        };
+        let mut source = String::new();
+        let read = if stdin {
+            std::io::stdin().read_to_string(&mut source).map(|_| ())
+        } else {
+            std::fs::read_to_string(file).map(|text| source = text)
+        };
+        if let Err(e) = read {
+            eprintln!("Error: {}: {}", name, e);
+            failed += 1;
+            continue;
+        }
+
+        let formatted = match format_source(&source, &name) {
+            Ok(formatted) => formatted,
+            Err(diagnostics) => {
+                print_grouped(&diagnostics, &HashMap::from([(name, source)]));
+                failed += 1;
+                continue;
+            }
+        };
+        if formatted == source {
+            if stdin && !check {
+                print!("{}", formatted);
+            }
+            continue;
+        }
+        unformatted += 1;
+        if check {
+            println!("{} is not formatted", name);
+        } else if stdin {
+            print!("{}", formatted);
+        } else if let Err(e) = std::fs::write(file, &formatted) {
+            eprintln!("Error: {}: {}", name, e);
+            failed += 1;
+        } else {
+            println!("Formatted {}", name);
+        }
+    }
+    let _ = std::io::stdout().flush();
+
+    if failed > 0 {
+        eprintln!(
+            "{} {} files could not be formatted",
+            bold_red("Error:"),
+            failed
+        );
+        1
+    } else if check && unformatted > 0 {
+        eprintln!(
+            "{} {} of {} files are not formatted; run `doo fmt` to format them",
+            bold_red("Found"),
+            unformatted,
+            files.len()
+        );
+        1
+    } else {
+        if check {
+            println!("{} {} files formatted", bold_green("✓"), files.len());
+        }
+        0
     }
 }
@@ -1,6 +1,5 @@
 use crate::codegen::core::{CodeGen, Symbol};
-use crate::mir::MirInstr;
-use inkwell::types::{BasicType, BasicTypeEnum};
+use crate::mir::{MirInstr, MirType};
 use inkwell::values::BasicValueEnum;
 use inkwell::IntPredicate;
 
@@ -11,7 +10,8 @@ impl<'ctx> CodeGen<'ctx> {
     pub fn generate_instr(&mut self, instr: &MirInstr) -> Option<BasicValueEnum<'ctx>> {
         match instr {
             // Constants
-            MirInstr::ConstInt { name, value } => self.generate_const_int(name, *value),
+            MirInstr::ConstInt { name, value, .. } => self.generate_const_int(name, *value),
+            MirInstr::ConstInt64 { name, value } => self.generate_const_int64(name, *value),
             MirInstr::ConstFloat { name, value } => self.generate_const_float(name, *value),
             MirInstr::ConstBool { name, value } => self.generate_const_bool(name, *value),
             MirInstr::ConstString { name, value } => self.generate_const_string(name, value),
@@ -26,7 +26,7 @@ impl<'ctx> CodeGen<'ctx> {
             }
 
             // Arithmetic
-            MirInstr::BinaryOp(op, dst, lhs, rhs) => self.generate_binary_op(op, dst, lhs, rhs),
+            MirInstr::BinaryOp(op, dst, lhs, rhs, _) => self.generate_binary_op(op, dst, lhs, rhs),
 
             // Collection operations
             MirInstr::LoadArrayElement { dest, array, index } => {
@@ -40,13 +40,106 @@ impl<'ctx> CodeGen<'ctx> {
             } => self.generate_load_map_pair(key_dest, val_dest, map, index),
 
             // Control flow
-            MirInstr::Print { values } => {
-                self.generate_print(values);
+            MirInstr::Print { values, types } => {
+                self.generate_print(values, types);
                 None
             }
 
-            MirInstr::Call { dest, func, args } => self.generate_call(dest, func, args),
+            MirInstr::Call {
+                dest, func, args, ..
+            } => self.generate_call(dest, func, args),
+            MirInstr::MakeClosure {
+                name,
+                func,
+                captures,
+            } => self.generate_make_closure(name, func, captures),
+            MirInstr::CallClosure {
+                dest,
+                closure,
+                args,
+                ty,
+            } => self.generate_call_closure(dest, closure, args, ty),
             MirInstr::ArrayLen { name, array } => self.generate_array_len(name, array),
+            MirInstr::ArrayConcat {
+                name,
+                left,
+                right,
+                ty,
+            } => self.generate_array_concat(name, left, right, ty),
+            MirInstr::ArrayPush { array, value, ty } => self.generate_array_push(array, value, ty),
+            MirInstr::ArrayPop { name, array, ty } => self.generate_array_pop(name, array, ty),
+            MirInstr::ArrayInsert {
+                array,
+                index,
+                value,
+                ty,
+            } => self.generate_array_insert(array, index, value, ty),
+            MirInstr::ArrayRemove {
+                name,
+                array,
+                index,
+                ty,
+            } => self.generate_array_remove(name, array, index, ty),
+            MirInstr::ArrayClear { array, ty } => self.generate_array_clear(array, ty),
+            MirInstr::ArraySort { array, ty } => self.generate_array_sort(array, ty),
+            MirInstr::ArrayReverse { array, ty } => self.generate_array_reverse(array, ty),
+            MirInstr::ArraySum { name, array, ty } => self.generate_array_sum(name, array, ty),
+            MirInstr::ArrayMin { name, array, ty } => {
+                self.generate_array_extreme(name, array, ty, "min")
+            }
+            MirInstr::ArrayMax { name, array, ty } => {
+                self.generate_array_extreme(name, array, ty, "max")
+            }
+            MirInstr::MapInsert {
+                map,
+                key,
+                value,
+                ty,
+            } => self.generate_map_insert(map, key, value, ty),
+            MirInstr::MapRemove { name, map, key, ty } => {
+                self.generate_map_remove(name, map, key, ty)
+            }
+            MirInstr::MapClear { map, ty } => self.generate_map_clear(map, ty),
+            MirInstr::MapContains { name, map, key, ty } => {
+                self.generate_map_contains(name, map, key, ty)
+            }
+            MirInstr::MapKeys { name, map, ty } => self.generate_map_column(name, map, ty, 0),
+            MirInstr::MapValues { name, map, ty } => self.generate_map_column(name, map, ty, 1),
+            MirInstr::StrLen { name, string } => self.generate_str_len(name, string),
+            MirInstr::StrContains { name, string, part } => {
+                self.generate_str_contains(name, string, part)
+            }
+            MirInstr::StrSplit { name, string, sep } => self.generate_str_split(name, string, sep),
+            MirInstr::StrTrim { name, string } => self.generate_str_trim(name, string),
+            MirInstr::StrReplace {
+                name,
+                string,
+                from,
+                to,
+            } => self.generate_str_replace(name, string, from, to),
+            MirInstr::StrSubstring {
+                name,
+                string,
+                start,
+                end,
+            } => self.generate_str_substring(name, string, start, end),
+
+            // Optionals
+            MirInstr::OptionalWrap { name, value, ty } => {
+                self.generate_optional_wrap(name, value.as_deref(), ty)
+            }
+            MirInstr::OptionalIsSome { name, optional } => {
+                self.generate_optional_is_some(name, optional)
+            }
+            MirInstr::OptionalUnwrap { name, optional, .. } => {
+                self.generate_optional_unwrap(name, optional)
+            }
+            MirInstr::OptionalUnwrapOr {
+                name,
+                optional,
+                default,
+                ..
+            } => self.generate_optional_unwrap_or(name, optional, default),
 
             // ===== LOOP INSTRUCTIONS =====
             MirInstr::ForRange { .. }
@@ -67,13 +160,12 @@ impl<'ctx> CodeGen<'ctx> {
             }
 
             // ===== EXISTING INSTRUCTIONS =====
-            MirInstr::Assign {
-                name,
-                value,
-                mutable: _,
-            } => {
+            MirInstr::Assign { name, value, .. } => {
                 let val = self.resolve_value(value);
 
+                // Struct values move out of temporaries and are shared between variables
+                self.assign_struct(name, value);
+
                 // Check if this value came from ArrayGet - if so, it's a loop iteration variable
                 // and should NEVER have array/map metadata propagated to it
                 let is_from_arrayget = self.arrayget_sources.contains_key(value);
@@ -96,6 +188,25 @@ impl<'ctx> CodeGen<'ctx> {
                 let value_is_heap_array = self.heap_arrays.contains(value);
                 let value_is_heap_map = self.heap_maps.contains(value);
 
+                // Rows of nested arrays keep their shape through assignments
+                // (including loop variables, which never get array metadata)
+                match self.runtime_array_shapes.get(value).cloned() {
+                    Some(shape) => {
+                        self.runtime_array_shapes.insert(name.clone(), shape);
+                    }
+                    None => {
+                        self.runtime_array_shapes.remove(name);
+                    }
+                }
+                match self.runtime_map_shapes.get(value).cloned() {
+                    Some(shape) => {
+                        self.runtime_map_shapes.insert(name.clone(), shape);
+                    }
+                    None => {
+                        self.runtime_map_shapes.remove(name);
+                    }
+                }
+
                 if let Some(ptrs) = self.composite_string_ptrs.remove(value) {
                     self.composite_string_ptrs.insert(name.clone(), ptrs);
                 }
@@ -109,21 +220,7 @@ impl<'ctx> CodeGen<'ctx> {
                     if name_was_heap_array || name_was_heap_map {
                         if let Some(old_str_ptrs) = self.composite_string_ptrs.get(name) {
                             for str_ptr in old_str_ptrs {
-                                let data_ptr = str_ptr.into_pointer_value();
-                                let rc_header = unsafe {
-                                    self.builder.build_in_bounds_gep(
-                                        self.context.i8_type(),
-                                        data_ptr,
-                                        &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                                        "rc_header",
-                                    )
-                                }
-                                .unwrap();
-
-                                let decref = self.decref_fn.unwrap();
-                                self.builder
-                                    .build_call(decref, &[rc_header.into()], "")
-                                    .unwrap();
+                                self.decref_value(str_ptr.into_pointer_value());
                             }
                         }
                     }
@@ -206,9 +303,9 @@ impl<'ctx> CodeGen<'ctx> {
                                         // Try to determine element type and count
                                         // This is a heuristic - we assume string arrays if we can't find metadata
                                         let element_type = if self.heap_strings.contains(value) {
-                                            "Str"
+                                            MirType::Str
                                         } else {
-                                            "Int"
+                                            MirType::Int
                                         };
 
                                         // For dynamically allocated arrays, try to infer size from usage
@@ -236,8 +333,9 @@ impl<'ctx> CodeGen<'ctx> {
                                         if max_index > 0 {
                                             found_metadata = Some(crate::codegen::ArrayMetadata {
                                                 length: max_index + 1,
-                                                element_type: element_type.to_string(),
-                                                contains_strings: element_type == "Str",
+                                                contains_strings: element_type == MirType::Str,
+                                                element_type,
+                                                element_metadata: None,
                                             });
                                         }
                                     }
@@ -306,6 +404,13 @@ impl<'ctx> CodeGen<'ctx> {
                     self.builder.position_at_end(current_block);
 
                     self.builder.build_store(alloca, val).unwrap();
+                    self.declare_debug_variable(
+                        name,
+                        alloca,
+                        val.get_type(),
+                        self.heap_strings.contains(value),
+                        None,
+                    );
 
                     self.symbols.insert(
                         name.clone(),
@@ -367,11 +472,10 @@ impl<'ctx> CodeGen<'ctx> {
 
                         // LAST RESORT: Try to extract array length from LLVM value directly
                         if found_metadata.is_none() {
-                            // Check if the value itself has array information
-                            let element_type = if value.contains("str") || value.contains("Str") {
-                                "Str"
+                            let element_type = if self.heap_strings.contains(value) {
+                                MirType::Str
                             } else {
-                                "Int"
+                                MirType::Int
                             };
 
                             // Try to infer from element count in temp_values
@@ -387,8 +491,9 @@ impl<'ctx> CodeGen<'ctx> {
                             if elem_count > 0 {
                                 found_metadata = Some(crate::codegen::ArrayMetadata {
                                     length: elem_count,
-                                    element_type: element_type.to_string(),
-                                    contains_strings: element_type == "Str",
+                                    contains_strings: element_type == MirType::Str,
+                                    element_type,
+                                    element_metadata: None,
                                 });
                             }
                         }
@@ -448,6 +553,25 @@ impl<'ctx> CodeGen<'ctx> {
                 // that reuse the same variable name (e.g., multiple loops with variable 'n')
                 self.arrayget_sources.remove(name);
 
+                // Arrays that grow or shrink (and copies of them) have no static length
+                if let Some(ty) = self
+                    .growable_arrays
+                    .get(name)
+                    .or_else(|| self.growable_arrays.get(value))
+                    .cloned()
+                {
+                    self.track_growable_array(name, &ty);
+                }
+                // Likewise for maps that gain or lose pairs
+                if let Some(ty) = self
+                    .growable_maps
+                    .get(name)
+                    .or_else(|| self.growable_maps.get(value))
+                    .cloned()
+                {
+                    self.track_growable_map(name, &ty);
+                }
+
                 Some(val)
             }
 
@@ -456,6 +580,43 @@ impl<'ctx> CodeGen<'ctx> {
                 None
             }
 
+            MirInstr::StructInit {
+                name,
+                struct_name,
+                fields,
+            } => self.generate_struct_init(name, struct_name, fields),
+            MirInstr::StructGet {
+                name,
+                struct_instance,
+                struct_name,
+                field,
+            } => self.generate_struct_get(name, struct_instance, struct_name, field),
+            MirInstr::StructSet {
+                struct_instance,
+                struct_name,
+                field,
+                value,
+            } => self.generate_struct_set(struct_instance, struct_name, field, value),
+
+            MirInstr::EnumInit {
+                name,
+                enum_name,
+                variant,
+                value,
+            } => self.generate_enum_init(name, enum_name, variant, value),
+            MirInstr::EnumMatch {
+                name,
+                enum_instance,
+                enum_name,
+                variant,
+            } => self.generate_enum_match(name, enum_instance, enum_name, variant),
+            MirInstr::EnumPayload {
+                name,
+                enum_instance,
+                enum_name,
+                variant,
+            } => self.generate_enum_payload(name, enum_instance, enum_name, variant),
+
             MirInstr::DecRef { value } => {
                 self.emit_decref(value);
                 None
@@ -469,7 +630,7 @@ impl<'ctx> CodeGen<'ctx> {
                 self.arrayget_sources.insert(name.clone(), array.clone());
 
                 // Check if this is actually a map iteration (map metadata exists for this array)
-                if let Some(map_metadata) = self.map_metadata.get(array) {
+                if let Some(map_metadata) = self.map_shape(array) {
                     // This is a map being iterated as an array - extract the key-value pair
                     let (key_type, val_type) = self.get_map_types(array);
                     let pair_type = self.context.struct_type(&[key_type, val_type], false);
@@ -499,7 +660,11 @@ impl<'ctx> CodeGen<'ctx> {
                     return Some(pair_ptr.into());
                 }
 
-                // Normal array element access
+                // Normal array element access, panicking outside the array
+                if self.collection_ptr(array).is_some() || self.array_metadata.contains_key(array) {
+                    let length = self.get_array_length(array);
+                    self.build_bounds_check(index_val, length);
+                }
                 let elem_type = self.get_array_element_type(array);
 
                 // Use direct pointer arithmetic with single index for runtime arrays
@@ -519,6 +684,15 @@ impl<'ctx> CodeGen<'ctx> {
                 // Store in temp_values for immediate use
                 self.temp_values.insert(name.clone(), elem_val);
 
+                // Rows of a nested array borrow from the outer array (no RC); remember
+                // their element shape so they can be indexed, iterated and printed
+                if let Some(row_shape) = self
+                    .array_shape(array)
+                    .and_then(|shape| shape.element_metadata)
+                {
+                    self.runtime_array_shapes.insert(name.clone(), *row_shape);
+                }
+
                 // If this temp was pre-allocated as a symbol (cross-block usage), store it there too
                 if let Some(sym) = self.symbols.get(name) {
                     self.builder.build_store(sym.ptr, elem_val).unwrap();
@@ -536,26 +710,18 @@ impl<'ctx> CodeGen<'ctx> {
 
                     // Increment reference count when loading a string from an array
                     // This is critical for loop iterations where the same variable is reused
-                    let str_ptr = elem_val.into_pointer_value();
-                    let rc_header = unsafe {
-                        self.builder.build_in_bounds_gep(
-                            self.context.i8_type(),
-                            str_ptr,
-                            &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                            "rc_header",
-                        )
-                    }
-                    .unwrap();
-
-                    let incref = self.incref_fn.unwrap();
-                    self.builder
-                        .build_call(incref, &[rc_header.into()], "")
-                        .unwrap();
+                    self.incref_value(elem_val.into_pointer_value());
                 }
 
                 Some(elem_val)
             }
 
+            MirInstr::TupleExtract {
+                name,
+                source,
+                index,
+            } => self.generate_tuple_extract(name, source, *index),
+
             MirInstr::TupleGet { name, tuple, index } => {
                 // Get the tuple/pair value (should be a pointer to a pair struct from ArrayGet)
                 let tuple_val = self.resolve_value(tuple);
@@ -577,7 +743,11 @@ impl<'ctx> CodeGen<'ctx> {
                 // Strategy 1: Look up the source array from ArrayGet tracking
                 if let Some(source_array) = self.arrayget_sources.get(tuple) {
                     search_log.push(format!("Strategy 1: ArrayGet source = '{}'", source_array));
-                    if let Some(metadata) = self.map_metadata.get(source_array) {
+                    if let Some(metadata) = self
+                        .map_metadata
+                        .get(source_array)
+                        .or_else(|| self.runtime_map_shapes.get(source_array))
+                    {
                         found_metadata = Some(metadata);
                         search_log.push(format!(
                             "  ✓ Found metadata for '{}': {}:{}",
@@ -663,31 +833,16 @@ impl<'ctx> CodeGen<'ctx> {
                     }
                 }
 
-                let (key_type, val_type, key_is_string, val_is_string) =
+                let (key_type, val_type, key_is_string, val_is_string, value_shapes) =
                     if let Some(metadata) = found_metadata {
-                        let k_type = match metadata.key_type.as_str() {
-                            "Str" => self
-                                .context
-                                .ptr_type(inkwell::AddressSpace::default())
-                                .into(),
-                            "Int" => self.context.i32_type().into(),
-                            "Bool" => self.context.bool_type().into(),
-                            _ => self.context.i32_type().into(),
-                        };
-                        let v_type = match metadata.value_type.as_str() {
-                            "Str" => self
-                                .context
-                                .ptr_type(inkwell::AddressSpace::default())
-                                .into(),
-                            "Int" => self.context.i32_type().into(),
-                            "Bool" => self.context.bool_type().into(),
-                            _ => self.context.i32_type().into(),
-                        };
+                        let k_type = self.struct_field_type(&metadata.key_type);
+                        let v_type = self.struct_field_type(&metadata.value_type);
                         (
                             k_type,
                             v_type,
                             metadata.key_is_string,
                             metadata.value_is_string,
+                            (metadata.value_array.clone(), metadata.value_map.clone()),
                         )
                     } else {
                         // Return dummy values to avoid crash, but this will produce incorrect IR
@@ -721,6 +876,12 @@ impl<'ctx> CodeGen<'ctx> {
                 // Store in temp_values
                 self.temp_values.insert(name.clone(), field_val);
 
+                // Collection values are borrowed from the map (no RC) and only
+                // know their length at runtime
+                if *index == 1 {
+                    self.register_map_value_shape(name, value_shapes);
+                }
+
                 // Store into existing symbol (allocated by generate_for_map)
                 // or create a new one if this is not a loop variable
                 if let Some(sym) = self.symbols.get(name) {
@@ -741,59 +902,9 @@ impl<'ctx> CodeGen<'ctx> {
                                 .build_load(field_type, sym.ptr, &format!("{}_old", name))
                                 .unwrap();
 
+                            // A null old value (first iteration) is ignored
                             if old_val.is_pointer_value() {
-                                let old_ptr = old_val.into_pointer_value();
-
-                                // Check if pointer is not null before decref
-                                let null_ptr = field_type.into_pointer_type().const_null();
-                                let old_int = self
-                                    .builder
-                                    .build_ptr_to_int(old_ptr, self.context.i64_type(), "old_int")
-                                    .unwrap();
-                                let null_int = self
-                                    .builder
-                                    .build_ptr_to_int(null_ptr, self.context.i64_type(), "null_int")
-                                    .unwrap();
-                                let is_not_null = self
-                                    .builder
-                                    .build_int_compare(
-                                        inkwell::IntPredicate::NE,
-                                        old_int,
-                                        null_int,
-                                        "is_not_null",
-                                    )
-                                    .unwrap();
-
-                                let current_bb = self.builder.get_insert_block().unwrap();
-                                let func = current_bb.get_parent().unwrap();
-                                let decref_bb = self.context.append_basic_block(func, "decref_old");
-                                let store_bb = self.context.append_basic_block(func, "store_new");
-
-                                self.builder
-                                    .build_conditional_branch(is_not_null, decref_bb, store_bb)
-                                    .unwrap();
-
-                                // Decref old value
-                                self.builder.position_at_end(decref_bb);
-                                let rc_header = unsafe {
-                                    self.builder.build_in_bounds_gep(
-                                        self.context.i8_type(),
-                                        old_ptr,
-                                        &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                                        &format!("{}_old_rc", name),
-                                    )
-                                }
-                                .unwrap();
-
-                                let decref_fn = self.decref_fn.unwrap();
-                                self.builder
-                                    .build_call(decref_fn, &[rc_header.into()], "")
-                                    .unwrap();
-
-                                self.builder.build_unconditional_branch(store_bb).unwrap();
-
-                                // Continue with store
-                                self.builder.position_at_end(store_bb);
+                                self.decref_value(old_val.into_pointer_value());
                             }
                         }
                     }
@@ -843,106 +954,43 @@ impl<'ctx> CodeGen<'ctx> {
                     self.heap_strings.insert(name.clone());
 
                     // Apply RC increment for string keys/values
-                    let str_ptr = field_val.into_pointer_value();
-                    let rc_header = unsafe {
-                        self.builder.build_in_bounds_gep(
-                            self.context.i8_type(),
-                            str_ptr,
-                            &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                            &format!("{}_rc_header", name),
-                        )
-                    }
-                    .unwrap();
-
-                    let incref_fn = self.incref_fn.unwrap();
-                    self.builder
-                        .build_call(incref_fn, &[rc_header.into()], "")
-                        .unwrap();
+                    self.incref_value(field_val.into_pointer_value());
                 }
 
                 Some(field_val)
             }
 
-            MirInstr::MapGet { name, map, key } => {
-                let map_ptr = self.resolve_value(map).into_pointer_value();
-                let key_val = self.resolve_value(key);
-
-                // Get map metadata to determine key and value types
-                if let Some(map_metadata_clone) = self.map_metadata.get(map).cloned() {
-                    let value_type_str = map_metadata_clone.value_type.clone();
-                    let value_is_string = map_metadata_clone.value_is_string;
-
-                    let value_type: BasicTypeEnum = match value_type_str.as_str() {
-                        "Str" => self
-                            .context
-                            .ptr_type(inkwell::AddressSpace::default())
-                            .into(),
-                        "Int" => self.context.i32_type().into(),
-                        "Bool" => self.context.bool_type().into(),
-                        _ => self.context.i32_type().into(),
-                    };
-
-                    // For now, simplified implementation: use the key_val as an index into the values array
-                    // This assumes integer keys for simplicity
-                    let index_val = key_val.into_int_value();
-
-                    // Direct indexing into map values array
-                    // For integer-keyed maps, we can directly use the index
-                    let elem_ptr = unsafe {
-                        self.builder.build_in_bounds_gep(
-                            value_type,
-                            map_ptr,
-                            &[index_val],
-                            "elem_ptr",
-                        )
-                    }
-                    .unwrap();
-
-                    let elem_val = self
-                        .builder
-                        .build_load(value_type, elem_ptr, "elem_val")
-                        .unwrap();
-
-                    let result_val = elem_val;
-
-                    // Handle RC for string values
-                    if value_is_string && value_type.is_pointer_type() {
-                        self.heap_strings.insert(name.clone());
-                        let str_ptr = result_val.into_pointer_value();
-                        let rc_header = unsafe {
-                            self.builder.build_in_bounds_gep(
-                                self.context.i8_type(),
-                                str_ptr,
-                                &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                                "rc_header",
-                            )
-                        }
-                        .unwrap();
+            MirInstr::MapGet { name, map, key, ty } => self.generate_map_get(name, map, key, ty),
 
-                        if let Some(incref_fn) = self.incref_fn {
-                            self.builder
-                                .build_call(incref_fn, &[rc_header.into()], "")
-                                .unwrap();
-                        }
-                    }
-
-                    // Store in temp_values
-                    self.temp_values.insert(name.clone(), result_val);
-
-                    if let Some(sym) = self.symbols.get(name) {
-                        self.builder.build_store(sym.ptr, result_val).unwrap();
-                    }
+            _ => None,
+        }
+    }
 
-                    Some(result_val)
-                } else {
-                    // Fallback: return 0
-                    let default = self.context.i32_type().const_int(0, false);
-                    self.temp_values.insert(name.clone(), default.into());
-                    Some(default.into())
-                }
+    /// Records the runtime shape of a value read out of a map whose values are
+    /// arrays or maps, and clears any stale shape when they are not.
+    fn register_map_value_shape(
+        &mut self,
+        name: &str,
+        (value_array, value_map): (
+            Option<crate::codegen::ArrayMetadata>,
+            Option<Box<crate::codegen::MapMetadata>>,
+        ),
+    ) {
+        match value_array {
+            Some(shape) => {
+                self.runtime_array_shapes.insert(name.to_string(), shape);
+            }
+            None => {
+                self.runtime_array_shapes.remove(name);
+            }
+        }
+        match value_map {
+            Some(shape) => {
+                self.runtime_map_shapes.insert(name.to_string(), *shape);
+            }
+            None => {
+                self.runtime_map_shapes.remove(name);
             }
-
-            _ => None,
         }
     }
 
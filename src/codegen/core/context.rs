@@ -1,9 +1,11 @@
+use crate::codegen::debug_info::DebugInfo;
+use crate::lexar::token::Span;
+use crate::mir::MirType;
 use inkwell::{
     builder::Builder,
     context::Context,
     module::Module,
-    passes::PassManager,
-    types::BasicTypeEnum,
+    types::{BasicTypeEnum, StructType},
     values::{BasicValueEnum, FunctionValue, PointerValue},
 };
 use std::collections::HashMap;
@@ -20,18 +22,27 @@ pub struct Symbol<'ctx> {
 #[derive(Debug, Clone)]
 pub struct ArrayMetadata {
     pub length: usize,
-    pub element_type: String, // "Int", "Str", etc.
+    pub element_type: MirType,
     pub contains_strings: bool,
+    /// Shape of the element arrays when element_type is an array (e.g. `[[Int]]`).
+    /// Only the element type information is meaningful: rows may differ in length,
+    /// so their lengths are always read from the heap header.
+    pub element_metadata: Option<Box<ArrayMetadata>>,
 }
 
 /// Metadata for tracking map information
 #[derive(Debug, Clone)]
 pub struct MapMetadata {
     pub length: usize,
-    pub key_type: String,
-    pub value_type: String,
+    pub key_type: MirType,
+    pub value_type: MirType,
     pub key_is_string: bool,
     pub value_is_string: bool,
+    /// Shape of the values when value_type is an array (e.g. `{Str: [Int]}`).
+    pub value_array: Option<ArrayMetadata>,
+    /// Shape of the values when value_type is a map. As with rows of nested
+    /// arrays, their lengths are read from the heap header.
+    pub value_map: Option<Box<MapMetadata>>,
 }
 
 /// Loop type enumeration
@@ -65,12 +76,12 @@ pub struct CodeGen<'ctx> {
     pub context: &'ctx Context,
     pub module: Module<'ctx>, // The container for all generated code (globals, functions, types)
     pub builder: Builder<'ctx>, // The tool used to insert instructions into blocks
-    pub fpm: PassManager<FunctionValue<'ctx>>, // Function Pass Manager for optimization (e.g., dead code elimination)
     pub symbols: HashMap<String, Symbol<'ctx>>, // Symbol table for local variables (maps names to stack pointers)
     pub temp_values: HashMap<String, BasicValueEnum<'ctx>>, // Stores temporary constant values (used for building complex constants)
     pub globals: Vec<crate::mir::mir::MirInstr>, // List of Intermediate Representation instructions for global definitions
     pub temp_strings: HashMap<String, String>, // Stores original Rust string values (used during string concatenation/definition)
     pub strings_to_concat: std::collections::HashSet<String>, // Tracks strings that need concatenation logic
+    pub interned_strings: HashMap<String, PointerValue<'ctx>>, // String literal -> its one static copy (see intern_string)
 
     // NEW: RC runtime functions
     pub incref_fn: Option<FunctionValue<'ctx>>,
@@ -79,22 +90,46 @@ pub struct CodeGen<'ctx> {
     pub heap_strings: std::collections::HashSet<String>,
 
     pub heap_arrays: std::collections::HashSet<String>,
+    pub stack_arrays: std::collections::HashSet<String>, // Array literals of the current function that live on its stack (see mir::escape)
     pub heap_maps: std::collections::HashSet<String>,
+    pub heap_structs: HashMap<String, String>, // Variables and temps owning a struct or enum value -> its name
 
     pub composite_strings: HashMap<String, Vec<String>>,
     pub composite_string_ptrs: HashMap<String, Vec<BasicValueEnum<'ctx>>>,
 
     pub array_metadata: HashMap<String, ArrayMetadata>,
+    pub runtime_array_shapes: HashMap<String, ArrayMetadata>, // Element shape of arrays whose length is only known at runtime (rows of nested arrays)
+    pub growable_arrays: HashMap<String, MirType>, // Array variables the current function pushes to, pops from, ... -> their type
     pub map_metadata: HashMap<String, MapMetadata>,
+    pub runtime_map_shapes: HashMap<String, MapMetadata>, // Shape of maps whose length is only known at runtime (values of nested maps)
+    pub growable_maps: HashMap<String, MirType>, // Map variables the current function inserts into, removes from, ... -> their type
     pub loop_stack: Vec<LoopContext>,
     pub loop_local_vars: std::collections::HashSet<String>, // Track variables allocated inside loop bodies (must not be cleaned up at function level)
     pub arrayget_sources: HashMap<String, String>, // Maps ArrayGet result names to their source array names
-    pub current_function_params: Vec<(String, Option<String>)>, // Track current function parameters (name, type) for RC on return
-    pub function_return_types: HashMap<String, String>, // Track function return types for proper RC handling on call results
-    pub functions_returning_heap: std::collections::HashSet<String>, // Track functions that return heap-allocated values
+    pub current_function_params: Vec<(String, Option<MirType>)>, // Track current function parameters (name, type) for RC on return
+    pub function_return_types: HashMap<String, MirType>, // LLVM function name -> return type, for RC handling on call results
+    pub functions_returning_heap: std::collections::HashSet<String>, // LLVM names of functions that return heap-allocated values
+    pub struct_layouts: HashMap<String, Vec<(String, MirType)>>, // Struct name -> (field, type) in declaration order
+    pub enum_layouts: HashMap<String, Vec<(String, Option<MirType>)>>, // Enum name -> (variant, payload type) in declaration order
+    pub weak_fields: HashMap<String, Vec<String>>, // Struct name -> fields declared `weak`
+    pub closure_captures: HashMap<String, Vec<MirType>>, // LLVM name of the function a closure body was lifted into -> types of the values it captures
 
     pub declared_functions: std::collections::HashSet<String>,
     pub external_modules: HashMap<String, Vec<String>>,
+    pub overflow_checks: bool, // Int add/sub/mul panic on overflow instead of wrapping
+    pub atomic_rc: bool, // Reference counts change with atomic instructions, for values shared between threads
+    pub arena_runtime: bool, // The program has `#[arena]` functions, so allocations go through the arena (see memory::allocator)
+    pub in_arena: bool,      // The current function is an `#[arena]` function
+    pub debug_info: Option<DebugInfo<'ctx>>, // With `doo build --debug`
+    pub source_file: Option<String>, // The program's file, which panics name with the line they happened on
+    pub imported_functions: std::collections::HashSet<String>, // Built from other files, so their statements have no line in `source_file`
+    pub statement_spans: HashMap<(String, usize), Span>, // (block label, instruction index) -> statement, in the current function
+    pub statement_line: u32, // Line of the statement being generated, 0 if unknown
+    pub init_function: Option<FunctionValue<'ctx>>, // `doo.init`, which main calls to run the global-scope statements
+    pub declare_only: bool, // The globals, `doo.init` and the RC runtime are declared, another module of a parallel build defines them
+    pub sret_result: Option<(PointerValue<'ctx>, StructType<'ctx>)>, // Where a tuple-returning function stores its result
+    pub pointer_bits: u32,  // Pointer width of the target, and so of C's size_t
+    pub tests: Vec<String>, // With `doo test`: the `#[test]` functions, one of which main runs
 }
 
 impl<'ctx> CodeGen<'ctx> {
@@ -102,40 +137,63 @@ impl<'ctx> CodeGen<'ctx> {
     pub fn new(module_name: &str, context: &'ctx Context) -> Self {
         let module = context.create_module(module_name);
         let builder = context.create_builder();
-        let fpm: PassManager<FunctionValue> = PassManager::create(&module);
 
         Self {
             context,
             module,
             builder,
-            fpm,
             symbols: HashMap::new(),
             temp_values: HashMap::new(),
             globals: Vec::new(),
             temp_strings: HashMap::new(),
             strings_to_concat: std::collections::HashSet::new(),
+            interned_strings: HashMap::new(),
 
             incref_fn: None,
             decref_fn: None,
 
             heap_strings: std::collections::HashSet::new(),
             heap_arrays: std::collections::HashSet::new(),
+            stack_arrays: std::collections::HashSet::new(),
             heap_maps: std::collections::HashSet::new(),
+            heap_structs: HashMap::new(),
 
             composite_strings: HashMap::new(),
             composite_string_ptrs: HashMap::new(),
 
             array_metadata: HashMap::new(),
+            runtime_array_shapes: HashMap::new(),
+            growable_arrays: HashMap::new(),
             map_metadata: HashMap::new(),
+            runtime_map_shapes: HashMap::new(),
+            growable_maps: HashMap::new(),
             loop_stack: Vec::new(),
             loop_local_vars: std::collections::HashSet::new(),
             arrayget_sources: HashMap::new(),
             current_function_params: Vec::new(),
             function_return_types: HashMap::new(),
             functions_returning_heap: std::collections::HashSet::new(),
+            struct_layouts: HashMap::new(),
+            enum_layouts: HashMap::new(),
+            weak_fields: HashMap::new(),
+            closure_captures: HashMap::new(),
 
             declared_functions: std::collections::HashSet::new(),
             external_modules: HashMap::new(),
+            overflow_checks: false,
+            atomic_rc: false,
+            arena_runtime: false,
+            in_arena: false,
+            debug_info: None,
+            source_file: None,
+            imported_functions: std::collections::HashSet::new(),
+            statement_spans: HashMap::new(),
+            statement_line: 0,
+            init_function: None,
+            declare_only: false,
+            sret_result: None,
+            pointer_bits: 64,
+            tests: Vec::new(),
         }
     }
 
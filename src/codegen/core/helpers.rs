@@ -1,7 +1,7 @@
 use crate::codegen::core::CodeGen;
 use inkwell::types::BasicTypeEnum;
 use inkwell::values::FunctionValue;
-use inkwell::values::{BasicValueEnum, PointerValue};
+use inkwell::values::{BasicValueEnum, IntValue, PointerValue};
 use inkwell::AddressSpace;
 
 impl<'ctx> CodeGen<'ctx> {
@@ -72,12 +72,35 @@ impl<'ctx> CodeGen<'ctx> {
     pub fn get_llvm_type(&self, type_name: &str) -> BasicTypeEnum<'ctx> {
         match type_name {
             "Int" => self.context.i32_type().into(), // Only i32 for integers
-            "Bool" => self.context.bool_type().into(),
-            "Str" => self.context.ptr_type(AddressSpace::default()).into(),
+            "Int64" => self.context.i64_type().into(),
+            "Float" => self.context.f64_type().into(),
+            "Bool" => self.context.i32_type().into(),
+            "Str" | "CStr" => self.context.ptr_type(AddressSpace::default()).into(),
             _ => self.context.i32_type().into(),
         }
     }
 
+    /// A `Bool` value from the i1 a comparison or test yields. Bools are i32
+    /// wherever they are held, stored, passed or returned; only branches take
+    /// an i1 (see `build_condition`).
+    pub(crate) fn bool_value(&self, flag: IntValue<'ctx>) -> IntValue<'ctx> {
+        self.builder
+            .build_int_z_extend(flag, self.context.i32_type(), "bool")
+            .unwrap()
+    }
+
+    /// The i1 a branch takes for the `Bool` value `value`.
+    pub(crate) fn build_condition(&self, value: IntValue<'ctx>) -> IntValue<'ctx> {
+        self.builder
+            .build_int_compare(
+                inkwell::IntPredicate::NE,
+                value,
+                value.get_type().const_zero(),
+                "cond",
+            )
+            .unwrap()
+    }
+
     /// Get or declare printf function for print statements
     pub fn get_or_declare_printf(&self) -> FunctionValue<'ctx> {
         if let Some(func) = self.module.get_function("printf") {
@@ -0,0 +1,344 @@
+use crate::codegen::core::CodeGen;
+use crate::mir::{MirFunction, MirType};
+use inkwell::debug_info::{
+    AsDIScope, DICompileUnit, DIFile, DIFlags, DIFlagsConstants, DILocation, DIScope, DIType,
+    DWARFEmissionKind, DWARFSourceLanguage, DebugInfoBuilder,
+};
+use inkwell::module::FlagBehavior;
+use inkwell::types::BasicTypeEnum;
+use inkwell::values::{AsValueRef, FunctionValue, PointerValue};
+use llvm_sys::debuginfo::LLVMInstructionSetDebugLoc;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+// DWARF base type encodings (DW_ATE_*)
+const DW_ATE_BOOLEAN: u32 = 0x02;
+const DW_ATE_FLOAT: u32 = 0x04;
+const DW_ATE_SIGNED: u32 = 0x05;
+const DW_ATE_SIGNED_CHAR: u32 = 0x06;
+const DW_ATE_UNSIGNED_CHAR: u32 = 0x08;
+
+/// DWARF debug info for `doo build --debug`: a subprogram per function, a line
+/// for each statement and the names of local variables and parameters.
+pub struct DebugInfo<'ctx> {
+    builder: DebugInfoBuilder<'ctx>,
+    unit: DICompileUnit<'ctx>,
+    file: DIFile<'ctx>,
+    skipped: HashSet<String>, // Functions from other source files, whose spans aren't in `file`
+    scope: Option<DIScope<'ctx>>, // Subprogram of the function being generated
+    location: Option<DILocation<'ctx>>,
+}
+
+impl<'ctx> CodeGen<'ctx> {
+    /// Describes `source` in DWARF from here on. The functions in `skipped`
+    /// were built from other files and are left without debug info.
+    pub fn enable_debug_info(&mut self, source: &Path, skipped: HashSet<String>) {
+        let filename = source
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let directory = source
+            .parent()
+            .and_then(|dir| dir.canonicalize().ok())
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_else(|| ".".to_string());
+
+        let i32_type = self.context.i32_type();
+        self.module.add_basic_value_flag(
+            "Debug Info Version",
+            FlagBehavior::Warning,
+            i32_type.const_int(3, false),
+        );
+        self.module.add_basic_value_flag(
+            "Dwarf Version",
+            FlagBehavior::Warning,
+            i32_type.const_int(4, false),
+        );
+
+        let (builder, unit) = self.module.create_debug_info_builder(
+            true,
+            DWARFSourceLanguage::C, // No DWARF code for doo; C keeps gdb's expressions working
+            &filename,
+            &directory,
+            "doo",
+            false,
+            "",
+            0,
+            "",
+            DWARFEmissionKind::Full,
+            0,
+            false,
+            false,
+            "",
+            "",
+        );
+        self.debug_info = Some(DebugInfo {
+            builder,
+            file: unit.get_file(),
+            unit,
+            skipped,
+            scope: None,
+            location: None,
+        });
+    }
+
+    /// Starts the subprogram of `func` and puts the builder at its first line.
+    /// Called with the builder in the function's entry block.
+    pub(crate) fn begin_debug_function(
+        &mut self,
+        func: &MirFunction,
+        llvm_func: FunctionValue<'ctx>,
+    ) {
+        self.builder.unset_current_debug_location();
+        let param_types: Vec<Option<DIType<'ctx>>> = func
+            .param_types
+            .iter()
+            .map(|ty| ty.as_ref().and_then(|ty| self.mir_debug_type(ty)))
+            .collect();
+        let return_type = func
+            .return_type
+            .as_ref()
+            .and_then(|ty| self.mir_debug_type(ty));
+        let Some(debug) = self.debug_info.as_mut() else {
+            return;
+        };
+        debug.scope = None;
+        debug.location = None;
+        if debug.skipped.contains(&func.name) {
+            return;
+        }
+
+        let line = func
+            .spans
+            .first()
+            .map_or(0, |(_, _, span)| span.line as u32);
+        // A parameter of a type with no description would shift the others:
+        // leave them all out, the variables still carry their types
+        let param_types: Vec<DIType<'ctx>> = param_types
+            .into_iter()
+            .collect::<Option<_>>()
+            .unwrap_or_default();
+        let subroutine_type = debug.builder.create_subroutine_type(
+            debug.file,
+            return_type,
+            &param_types,
+            DIFlags::ZERO,
+        );
+        let subprogram = debug.builder.create_function(
+            debug.unit.as_debug_info_scope(),
+            &func.name,
+            Some(&Self::llvm_function_name(&func.name)),
+            debug.file,
+            line,
+            subroutine_type,
+            false,
+            true,
+            line,
+            DIFlags::ZERO,
+            false,
+        );
+        llvm_func.set_subprogram(subprogram);
+        debug.scope = Some(subprogram.as_debug_info_scope());
+        let location = debug.builder.create_debug_location(
+            self.context,
+            line,
+            0,
+            subprogram.as_debug_info_scope(),
+            None,
+        );
+        debug.location = Some(location);
+        self.builder.set_current_debug_location(location);
+    }
+
+    /// Looks up the statements of `func` by where they start. Functions from
+    /// other source files get none, their lines would point into this one.
+    pub(crate) fn begin_statements(&mut self, func: &MirFunction) {
+        self.statement_line = 0;
+        self.statement_spans = if self.imported_functions.contains(&func.name) {
+            HashMap::new()
+        } else {
+            func.spans
+                .iter()
+                .map(|(label, index, span)| ((label.clone(), *index), *span))
+                .collect()
+        };
+    }
+
+    /// Moves the line the builder is on, and the line panics report, to the
+    /// statement starting at instruction `index` of `block`, if one does.
+    pub(crate) fn set_statement_location(&mut self, block: &str, index: usize) {
+        let Some(&span) = self.statement_spans.get(&(block.to_string(), index)) else {
+            return;
+        };
+        self.statement_line = span.line as u32;
+        let Some(debug) = self.debug_info.as_mut() else {
+            return;
+        };
+        let Some(scope) = debug.scope else {
+            return;
+        };
+        let location = debug.builder.create_debug_location(
+            self.context,
+            span.line as u32,
+            span.col as u32,
+            scope,
+            None,
+        );
+        debug.location = Some(location);
+        self.builder.set_current_debug_location(location);
+    }
+
+    /// Names the stack slot of a variable for the debugger. `arg_no` is the
+    /// 1-based position of a parameter. Temps and internal names are skipped.
+    pub(crate) fn declare_debug_variable(
+        &self,
+        name: &str,
+        storage: PointerValue<'ctx>,
+        ty: BasicTypeEnum<'ctx>,
+        is_string: bool,
+        arg_no: Option<u32>,
+    ) {
+        let Some(debug) = self.debug_info.as_ref() else {
+            return;
+        };
+        let (Some(scope), Some(location)) = (debug.scope, debug.location) else {
+            return;
+        };
+        if name.starts_with('%') || name.contains("__") {
+            return;
+        }
+        let Some(di_type) = self.llvm_debug_type(ty, is_string) else {
+            return;
+        };
+        let line = location.get_line();
+        let variable = match arg_no {
+            Some(arg_no) => debug.builder.create_parameter_variable(
+                scope,
+                name,
+                arg_no,
+                debug.file,
+                line,
+                di_type,
+                true,
+                DIFlags::ZERO,
+            ),
+            None => debug.builder.create_auto_variable(
+                scope,
+                name,
+                debug.file,
+                line,
+                di_type,
+                true,
+                DIFlags::ZERO,
+                0,
+            ),
+        };
+        // Right after the alloca, wherever it was put
+        let Some(alloca) = storage.as_instruction() else {
+            return;
+        };
+        match alloca.get_next_instruction() {
+            Some(next) => {
+                debug.builder.insert_declare_before_instruction(
+                    storage,
+                    Some(variable),
+                    None,
+                    location,
+                    next,
+                );
+            }
+            None => {
+                if let Some(block) = alloca.get_parent() {
+                    debug.builder.insert_declare_at_end(
+                        storage,
+                        Some(variable),
+                        None,
+                        location,
+                        block,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Ends the current function's debug info: code generated next is not in it.
+    pub(crate) fn end_debug_function(&mut self) {
+        self.builder.unset_current_debug_location();
+        self.statement_spans.clear();
+        self.statement_line = 0;
+        if let Some(debug) = self.debug_info.as_mut() {
+            debug.scope = None;
+            debug.location = None;
+        }
+    }
+
+    /// Resolves the debug info once the module is complete. Runtime helpers
+    /// built while a function was being generated picked up its current line;
+    /// functions without a subprogram can't have lines, so theirs are dropped.
+    pub(crate) fn finish_debug_info(&self) {
+        let Some(debug) = self.debug_info.as_ref() else {
+            return;
+        };
+        for function in self.module.get_functions() {
+            if function.get_subprogram().is_some() {
+                continue;
+            }
+            for block in function.get_basic_blocks() {
+                let mut instr = block.get_first_instruction();
+                while let Some(current) = instr {
+                    unsafe {
+                        LLVMInstructionSetDebugLoc(current.as_value_ref(), std::ptr::null_mut())
+                    };
+                    instr = current.get_next_instruction();
+                }
+            }
+        }
+        debug.builder.finalize();
+    }
+
+    /// Debug description of a value of MIR type `ty`, as stored in LLVM.
+    fn mir_debug_type(&self, ty: &MirType) -> Option<DIType<'ctx>> {
+        if matches!(ty, MirType::Void | MirType::Never) {
+            return None;
+        }
+        self.llvm_debug_type(self.struct_field_type(ty), *ty == MirType::Str)
+    }
+
+    /// Debug description of an LLVM value type. Strings point to their
+    /// characters so debuggers print the text; other pointers point to bytes.
+    fn llvm_debug_type(&self, ty: BasicTypeEnum<'ctx>, is_string: bool) -> Option<DIType<'ctx>> {
+        let debug = self.debug_info.as_ref()?;
+        let basic = |name: &str, bits: u64, encoding: u32| {
+            debug
+                .builder
+                .create_basic_type(name, bits, encoding, DIFlags::ZERO)
+                .ok()
+                .map(|basic| basic.as_type())
+        };
+        match ty {
+            BasicTypeEnum::IntType(int) => match int.get_bit_width() {
+                1 => basic("Bool", 8, DW_ATE_BOOLEAN),
+                32 => basic("Int", 32, DW_ATE_SIGNED),
+                64 => basic("Int64", 64, DW_ATE_SIGNED),
+                _ => None,
+            },
+            BasicTypeEnum::FloatType(_) => basic("Float", 64, DW_ATE_FLOAT),
+            BasicTypeEnum::PointerType(_) => {
+                let (name, pointee) = if is_string {
+                    ("Str", basic("char", 8, DW_ATE_SIGNED_CHAR)?)
+                } else {
+                    ("ptr", basic("byte", 8, DW_ATE_UNSIGNED_CHAR)?)
+                };
+                let pointer = debug.builder.create_pointer_type(
+                    name,
+                    pointee,
+                    self.pointer_bits as u64,
+                    0,
+                    inkwell::AddressSpace::default(),
+                );
+                Some(pointer.as_type())
+            }
+            _ => None,
+        }
+    }
+}
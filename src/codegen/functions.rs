@@ -1,6 +1,7 @@
 use crate::codegen::core::CodeGen;
 use crate::mir::mir::{CodegenBlock, MirBlock, MirFunction, MirInstr, MirProgram, MirTerminator};
-use inkwell::types::{BasicMetadataTypeEnum, BasicTypeEnum, StructType};
+use crate::mir::MirType;
+use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FunctionType};
 use inkwell::values::{BasicValueEnum, FunctionValue};
 use inkwell::AddressSpace;
 use std::collections::HashMap;
@@ -11,17 +12,71 @@ impl<'ctx> CodeGen<'ctx> {
     /// into LLVM IR, handling global variables, functions, and the main entry point.
     /// It also initializes reference counting runtime and applies optimization passes.
     pub fn generate_program(&mut self, program: &MirProgram) {
+        self.declare_program(program);
+
+        // --- FUNCTION GENERATION ---
+        // Generate LLVM IR for all user-defined functions. They are optimized
+        // as a module once complete (see `OptLevel`).
+        for func in &program.functions {
+            self.generate_function(func);
+        }
+
+        self.generate_entry_point(program);
+        self.finish_debug_info();
+    }
+
+    /// What any function of `program` is generated against: the RC runtime,
+    /// the struct and enum layouts, a declaration of every function, the
+    /// globals and `doo.init`. Each module of a parallel build starts with it
+    /// (see `generate_parallel`); with `declare_only` the RC runtime, globals
+    /// and `doo.init` are only declared.
+    pub(crate) fn declare_program(&mut self, program: &MirProgram) {
+        // Arena functions route every allocation and free through the arena
+        self.arena_runtime = program.functions.iter().any(|func| func.arena);
+
         // Initialize RC runtime FIRST to ensure reference counting functions are available.
         self.init_rc_runtime();
 
         // Store the global instructions for later use (e.g., initialization).
         self.globals = program.globals.clone();
+        // Struct and enum layouts are needed to build their types in any function
+        self.struct_layouts = program.structs.clone();
+        self.enum_layouts = program.enums.clone();
+        self.weak_fields = program.weak_fields.clone();
+        // Lifted closure functions take the captured values first
+        for instr in program
+            .functions
+            .iter()
+            .flat_map(|f| &f.blocks)
+            .flat_map(|b| &b.instrs)
+        {
+            if let MirInstr::MakeClosure { func, captures, .. } = instr {
+                let Some(lifted) = program.functions.iter().find(|f| f.name == *func) else {
+                    continue;
+                };
+                let capture_types = lifted.param_types[..captures.len()]
+                    .iter()
+                    .map(|ty| ty.clone().unwrap_or(MirType::Int))
+                    .collect();
+                self.closure_captures
+                    .insert(Self::llvm_function_name(func), capture_types);
+            }
+        }
 
         // Pre-scan and declare all functions for forward references
         // This allows functions to call each other regardless of definition order
         for func in &program.functions {
             self.predeclare_function(func);
         }
+        // `extern "C"` functions are only declared; the linker finds their
+        // definitions. Their types are all passed as C passes them.
+        for func in &program.externs {
+            if func.name.starts_with("llvm.") {
+                self.predeclare_intrinsic(func);
+            } else {
+                self.predeclare_function(func);
+            }
+        }
 
         // --- PRE-PROCESSING ---
         // Scan all global instructions to identify strings involved in concatenation.
@@ -38,22 +93,29 @@ impl<'ctx> CodeGen<'ctx> {
         for g in &program.globals {
             self.generate_global(g);
         }
-
-        // --- FUNCTION GENERATION ---
-        // Generate LLVM IR for all user-defined functions and apply optimizations.
-        for func in &program.functions {
-            let llvm_func = self.generate_function(func);
-            // Apply registered optimization passes (like O1, O2, O3) to the generated function.
-            self.fpm.run_on(&llvm_func);
+        self.init_function = self.generate_init();
+        if self.declare_only {
+            self.declare_globals();
         }
+    }
 
-        // --- MAIN ENTRY POINT ---
-        // For non-main-entry files (imported modules), generate a default main if needed
-        if !program.is_main_entry && self.module.get_function("main").is_none() {
+    /// The `main` the program doesn't define itself: a test build's main runs
+    /// the tests, the program's own main left out, and a file that isn't the
+    /// main entry (an imported module) gets a default one if it has none.
+    pub(crate) fn generate_entry_point(&mut self, program: &MirProgram) {
+        if !self.tests.is_empty() {
+            self.generate_test_main();
+        } else if !program.is_main_entry && self.module.get_function("main").is_none() {
             self.generate_default_main();
         }
     }
 
+    /// LLVM symbol of a MIR function. Methods are named `User::greet` in MIR
+    /// and emitted as `User_greet`, which no user identifier can spell.
+    pub(crate) fn llvm_function_name(name: &str) -> String {
+        name.replace("::", "_")
+    }
+
     // ADD THIS NEW METHOD:
     fn predeclare_function(&mut self, func: &MirFunction) {
         if self.declared_functions.contains(&func.name) {
@@ -67,87 +129,164 @@ impl<'ctx> CodeGen<'ctx> {
             .map(|type_opt| self.map_type_to_llvm(type_opt))
             .collect();
 
-        // Determine return type
-        let fn_type = if func.name == "main" {
-            // Force main to be i32 () for C/Clang compatibility
-            self.context.i32_type().fn_type(&param_types, false)
-        } else if let Some(ref ret_type_str) = func.return_type {
-            if ret_type_str.contains("Void") {
-                self.context.void_type().fn_type(&param_types, false)
-            } else if ret_type_str.contains("String") || ret_type_str.contains("Str") {
-                self.context
-                    .ptr_type(AddressSpace::default())
-                    .fn_type(&param_types, false)
-            } else if ret_type_str.contains("Array") || ret_type_str.contains("Map") {
-                self.context
-                    .ptr_type(AddressSpace::default())
-                    .fn_type(&param_types, false)
-            } else {
-                self.context.i32_type().fn_type(&param_types, false)
-            }
-        } else {
-            self.context.void_type().fn_type(&param_types, false)
-        };
+        // Callers before the callee's definition still need its return type for RC
+        if let Some(ref ret_type_str) = func.return_type {
+            self.function_return_types
+                .insert(Self::llvm_function_name(&func.name), ret_type_str.clone());
+        }
+
+        let fn_type = self.function_llvm_type(func, &param_types);
 
         // Declare function
+        let function =
+            self.module
+                .add_function(&Self::llvm_function_name(&func.name), fn_type, None);
+        if let Some(struct_type) = self.sret_return_type(&func.name) {
+            self.add_sret_attributes(function, struct_type);
+        }
+        self.declared_functions.insert(func.name.clone());
+    }
+
+    /// Declares the LLVM intrinsic `func` is named after. Intrinsics take and
+    /// return `i1` flags where doo passes a `Bool` as an `i32`; calls convert
+    /// between them (see `generate_call`).
+    fn predeclare_intrinsic(&mut self, func: &MirFunction) {
+        if self.declared_functions.contains(&func.name) {
+            return;
+        }
+        let llvm_type = |ty: &MirType| -> BasicTypeEnum<'ctx> {
+            match ty {
+                MirType::Bool => self.context.bool_type().into(),
+                _ => self.struct_field_type(ty),
+            }
+        };
+        let param_types: Vec<BasicMetadataTypeEnum> = func
+            .param_types
+            .iter()
+            .map(|ty| llvm_type(ty.as_ref().unwrap_or(&MirType::Int)).into())
+            .collect();
+        let fn_type = match &func.return_type {
+            None | Some(MirType::Void) => self.context.void_type().fn_type(&param_types, false),
+            Some(ty) => llvm_type(ty).fn_type(&param_types, false),
+        };
         self.module.add_function(&func.name, fn_type, None);
         self.declared_functions.insert(func.name.clone());
     }
 
-    fn map_type_to_llvm(&self, type_opt: &Option<String>) -> BasicMetadataTypeEnum<'ctx> {
-        if let Some(type_str) = type_opt {
-            if type_str.contains("String") || type_str.contains("Str") {
-                self.context.ptr_type(AddressSpace::default()).into()
-            } else if type_str.contains("Array") || type_str.contains("Map") {
-                self.context.ptr_type(AddressSpace::default()).into()
-            } else {
-                self.context.i32_type().into()
+    fn map_type_to_llvm(&self, type_opt: &Option<MirType>) -> BasicMetadataTypeEnum<'ctx> {
+        match type_opt {
+            // Parameters are passed like struct fields: pointers for heap
+            // types, `{ i1, T }` for scalar optionals, numbers by value
+            Some(ty) => self.struct_field_type(ty).into(),
+            None => self.context.i32_type().into(),
+        }
+    }
+
+    /// LLVM signature of `func` given its parameter types. Tuple results are
+    /// returned through a hidden first `ptr` parameter instead.
+    fn function_llvm_type(
+        &self,
+        func: &MirFunction,
+        param_types: &[BasicMetadataTypeEnum<'ctx>],
+    ) -> FunctionType<'ctx> {
+        if func.name == "main" {
+            // Force main to be i32 () for C/Clang compatibility
+            return self.context.i32_type().fn_type(param_types, false);
+        }
+        match &func.return_type {
+            None | Some(MirType::Void) => self.context.void_type().fn_type(param_types, false),
+            Some(MirType::Tuple(_)) => {
+                let out = self.context.ptr_type(AddressSpace::default()).into();
+                let params: Vec<BasicMetadataTypeEnum> = std::iter::once(out)
+                    .chain(param_types.iter().copied())
+                    .collect();
+                self.context.void_type().fn_type(&params, false)
             }
-        } else {
-            self.context.i32_type().into()
+            Some(ty) => self.struct_field_type(ty).fn_type(param_types, false),
         }
     }
 
     /// Creates a minimal `main` function (`i32 ()`) that returns 0.
     /// This is a fallback to guarantee the presence of a valid entry point in the generated binary.
-    /// Also executes any global-scope runtime statements (like print).
+    /// Like any `main`, it first runs the global-scope statements (see `generate_init`).
     pub fn generate_default_main(&mut self) {
         let main_type = self.context.i32_type().fn_type(&[], false);
         let main_func = self.module.add_function("main", main_type, None);
 
         let entry_bb = self.context.append_basic_block(main_func, "entry");
         self.builder.position_at_end(entry_bb);
-
-        // Execute any runtime instructions from global scope (like Print, BinaryOp for runtime values)
-        // NOTE: Skip ConstString instructions - they are already processed in generate_global
-        // and stored as static constants. Processing them here would cause duplicate heap allocations.
-        for instr in &self.globals.clone() {
-            match instr {
-                MirInstr::ConstString { .. } => {
-                    // Skip - these are already defined as module-level constants in generate_global
-                    // Reprocessing would cause memory leaks from duplicate malloc calls
-                }
-                MirInstr::Print { .. } => {
-                    self.generate_instr(instr);
-                }
-                MirInstr::BinaryOp(_, _, _, _) => {
-                    // Generate runtime binary operations that weren't constant-folded
-                    self.generate_instr(instr);
-                }
-                _ => {
-                    // Other instructions are already handled in generate_global
-                }
-            }
-        }
-
-        // Perform cleanup before returning from main
-        self.generate_function_exit_cleanup();
+        self.build_init_call();
 
         let zero = self.context.i32_type().const_int(0, false);
         // Generates the `ret i32 0` instruction.
         self.builder.build_return(Some(&zero)).unwrap();
     }
 
+    /// Creates the `main` of a test build (`i32 (i32, ptr)`): it runs the
+    /// global-scope statements, then the test in `tests` its first argument
+    /// numbers, as in `./tests 2` for the third. The test runner always passes
+    /// one. A test that returns exits with 0, and one that panics with 101.
+    pub fn generate_test_main(&mut self) {
+        let i32_type = self.context.i32_type();
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let main_type = i32_type.fn_type(&[i32_type.into(), ptr_type.into()], false);
+        let main_func = self.module.add_function("main", main_type, None);
+
+        let entry_bb = self.context.append_basic_block(main_func, "entry");
+        self.builder.position_at_end(entry_bb);
+        self.build_init_call();
+        let argv = main_func.get_nth_param(1).unwrap().into_pointer_value();
+        let arg_ptr = unsafe {
+            self.builder.build_in_bounds_gep(
+                ptr_type,
+                argv,
+                &[i32_type.const_int(1, false)],
+                "arg_ptr",
+            )
+        }
+        .unwrap();
+        let arg = self.builder.build_load(ptr_type, arg_ptr, "arg").unwrap();
+        let atoi = self.get_or_declare_atoi();
+        let index = self
+            .builder
+            .build_call(atoi, &[arg.into()], "index")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+
+        let done = self.context.append_basic_block(main_func, "done");
+        let mut cases = Vec::new();
+        for (i, name) in self.tests.iter().enumerate() {
+            let Some(test) = self.module.get_function(&Self::llvm_function_name(name)) else {
+                continue;
+            };
+            let case = self
+                .context
+                .append_basic_block(main_func, &format!("test_{}", i));
+            self.builder.position_at_end(case);
+            self.builder.build_call(test, &[], "").unwrap();
+            self.builder.build_unconditional_branch(done).unwrap();
+            cases.push((i32_type.const_int(i as u64, false), case));
+        }
+        self.builder.position_at_end(entry_bb);
+        self.builder.build_switch(index, done, &cases).unwrap();
+
+        self.builder.position_at_end(done);
+        self.builder
+            .build_return(Some(&i32_type.const_int(0, false)))
+            .unwrap();
+    }
+
+    /// Runs the global-scope statements, if there are any, at the current
+    /// position: the first thing `main` does.
+    fn build_init_call(&self) {
+        if let Some(init) = self.init_function {
+            self.builder.build_call(init, &[], "").unwrap();
+        }
+    }
+
     /// Generates the LLVM structure and code for a single MIR function.
     /// Generates LLVM IR for a user-defined function.
     /// This method:
@@ -164,15 +303,19 @@ impl<'ctx> CodeGen<'ctx> {
         self.heap_strings.clear();
         self.heap_arrays.clear();
         self.heap_maps.clear();
+        self.heap_structs.clear();
         self.array_metadata.clear();
+        self.runtime_array_shapes.clear();
+        self.runtime_map_shapes.clear();
         self.map_metadata.clear();
         self.composite_string_ptrs.clear();
         self.composite_strings.clear();
+        self.temp_strings.clear();
 
         // Store function return type for RC tracking when this function is called
         if let Some(ref ret_type_str) = func.return_type {
             self.function_return_types
-                .insert(func.name.clone(), ret_type_str.clone());
+                .insert(Self::llvm_function_name(&func.name), ret_type_str.clone());
         }
 
         // Track function parameters for RC handling on return
@@ -187,53 +330,13 @@ impl<'ctx> CodeGen<'ctx> {
         let param_types: Vec<BasicMetadataTypeEnum> = func
             .param_types
             .iter()
-            .map(|type_opt| {
-                if let Some(type_str) = type_opt {
-                    // Map MIR type strings to LLVM types
-                    if type_str.contains("String") || type_str.contains("Str") {
-                        self.context.ptr_type(AddressSpace::default()).into()
-                    } else if type_str.contains("Array") {
-                        self.context.ptr_type(AddressSpace::default()).into()
-                    } else if type_str.contains("Map") {
-                        self.context.ptr_type(AddressSpace::default()).into()
-                    } else {
-                        self.context.i32_type().into()
-                    }
-                } else {
-                    self.context.i32_type().into()
-                }
-            })
+            .map(|type_opt| self.map_type_to_llvm(type_opt))
             .collect();
-
-        // Determine return type and create function signature
-        let fn_type = if func.name == "main" {
-            // Force main to be i32 () for C/Clang compatibility
-            self.context.i32_type().fn_type(&param_types, false)
-        } else if let Some(ref ret_type_str) = func.return_type {
-            // Map MIR type strings to LLVM types
-            if ret_type_str.contains("Void") {
-                self.context.void_type().fn_type(&param_types, false)
-            } else if ret_type_str.contains("String") || ret_type_str.contains("Str") {
-                self.context
-                    .ptr_type(AddressSpace::default())
-                    .fn_type(&param_types, false)
-            } else if ret_type_str.contains("Array") {
-                self.context
-                    .ptr_type(AddressSpace::default())
-                    .fn_type(&param_types, false)
-            } else if ret_type_str.contains("Map") {
-                self.context
-                    .ptr_type(AddressSpace::default())
-                    .fn_type(&param_types, false)
-            } else {
-                self.context.i32_type().fn_type(&param_types, false)
-            }
-        } else {
-            self.context.void_type().fn_type(&param_types, false)
-        };
+        let fn_type = self.function_llvm_type(func, &param_types);
 
         // Check if function was already declared (for forward references/imports)
-        let llvm_func = if let Some(existing_func) = self.module.get_function(&func.name) {
+        let symbol = Self::llvm_function_name(&func.name);
+        let llvm_func = if let Some(existing_func) = self.module.get_function(&symbol) {
             // Verify signature matches
             if existing_func.get_type() == fn_type {
                 existing_func
@@ -245,15 +348,29 @@ impl<'ctx> CodeGen<'ctx> {
                 eprintln!("  Declared: {:?}", existing_func.get_type());
                 eprintln!("  Expected: {:?}", fn_type);
                 // Create new function with correct signature
-                self.module.add_function(&func.name, fn_type, None)
+                self.module.add_function(&symbol, fn_type, None)
             }
         } else {
-            self.module.add_function(&func.name, fn_type, None)
+            self.module.add_function(&symbol, fn_type, None)
         };
+        let sret = self.sret_return_type(&func.name);
+        if let Some(struct_type) = sret {
+            self.add_sret_attributes(llvm_func, struct_type);
+        }
+        self.sret_result = sret.map(|struct_type| {
+            let out = llvm_func.get_nth_param(0).unwrap().into_pointer_value();
+            (out, struct_type)
+        });
+        let first_param = sret.is_some() as u32;
 
         // Create a separate entry block for parameter allocation
         let entry_block = self.context.append_basic_block(llvm_func, "entry");
         self.builder.position_at_end(entry_block);
+        self.begin_statements(func);
+        self.begin_debug_function(func, llvm_func);
+        if func.name == "main" {
+            self.build_init_call();
+        }
 
         // Create all necessary basic blocks within the function (e.g., entry, if.then, loop.body).
         let mut bb_map = HashMap::new();
@@ -266,35 +383,54 @@ impl<'ctx> CodeGen<'ctx> {
         // Each function must have completely fresh scope with no interference from previous functions
         self.symbols.clear();
         self.array_metadata.clear();
+        self.runtime_array_shapes.clear();
+        self.runtime_map_shapes.clear();
         self.map_metadata.clear();
         self.arrayget_sources.clear();
         self.temp_values.clear();
         self.heap_strings.clear();
         self.heap_arrays.clear();
         self.heap_maps.clear();
+        self.heap_structs.clear();
         self.composite_string_ptrs.clear();
         self.loop_stack.clear();
         self.loop_local_vars.clear();
+        self.stack_arrays = crate::mir::escape::stack_arrays(func, &self.globals);
+        self.in_arena = func.arena;
+
+        // Arrays and maps changed in place read their length from the header
+        // everywhere in the function, including code generated before the change
+        self.growable_arrays.clear();
+        self.growable_maps.clear();
+        for instr in func.blocks.iter().flat_map(|block| &block.instrs) {
+            match instr {
+                crate::mir::MirInstr::ArrayPush { array, ty, .. }
+                | crate::mir::MirInstr::ArrayPop { array, ty, .. }
+                | crate::mir::MirInstr::ArrayInsert { array, ty, .. }
+                | crate::mir::MirInstr::ArrayRemove { array, ty, .. }
+                | crate::mir::MirInstr::ArrayClear { array, ty }
+                | crate::mir::MirInstr::ArraySort { array, ty }
+                | crate::mir::MirInstr::ArrayReverse { array, ty } => {
+                    self.growable_arrays.insert(array.clone(), ty.clone());
+                }
+                crate::mir::MirInstr::MapInsert { map, ty, .. }
+                | crate::mir::MirInstr::MapRemove { map, ty, .. }
+                | crate::mir::MirInstr::MapClear { map, ty } => {
+                    self.growable_maps.insert(map.clone(), ty.clone());
+                }
+                _ => {}
+            }
+        }
 
         // Allocate space for parameters and store their incoming values in the entry block.
         // This ensures parameters are available as local variables in the function scope.
         for (i, param) in func.params.iter().enumerate() {
-            let param_val = llvm_func.get_nth_param(i as u32).unwrap();
+            let param_val = llvm_func.get_nth_param(first_param + i as u32).unwrap();
 
             // Get the correct type for this parameter
-            let param_type = if let Some(Some(ref type_str)) = func.param_types.get(i) {
-                // Map MIR type strings to LLVM types
-                if type_str.contains("String") || type_str.contains("Str") {
-                    self.context.ptr_type(AddressSpace::default()).into()
-                } else if type_str.contains("Array") {
-                    self.context.ptr_type(AddressSpace::default()).into()
-                } else if type_str.contains("Map") {
-                    self.context.ptr_type(AddressSpace::default()).into()
-                } else {
-                    self.context.i32_type().into()
-                }
-            } else {
-                self.context.i32_type().into()
+            let param_type: BasicTypeEnum<'ctx> = match func.param_types.get(i) {
+                Some(Some(ty)) => self.struct_field_type(ty),
+                _ => self.context.i32_type().into(),
             };
 
             let alloca = self
@@ -303,6 +439,8 @@ impl<'ctx> CodeGen<'ctx> {
                 .expect("Failed to allocate function parameter");
 
             self.builder.build_store(alloca, param_val);
+            let is_string = matches!(func.param_types.get(i), Some(Some(MirType::Str)));
+            self.declare_debug_variable(param, alloca, param_type, is_string, Some(i as u32 + 1));
 
             // Register the parameter in the symbol table for future lookups.
             self.symbols.insert(
@@ -312,7 +450,17 @@ impl<'ctx> CodeGen<'ctx> {
                     ty: param_type,
                 },
             );
+
+            // Nested array parameters only know their shape from the signature
+            if let Some(Some(ty @ MirType::Array(elem))) = func.param_types.get(i) {
+                if matches!(**elem, MirType::Array(_)) {
+                    if let Some(shape) = Self::array_shape_from_type(ty) {
+                        self.runtime_array_shapes.insert(param.clone(), shape);
+                    }
+                }
+            }
         }
+        self.emit_arena_enter();
 
         // Pre-allocate variables that are used across multiple blocks
         // This is necessary for proper SSA form and cross-block variable access
@@ -337,7 +485,7 @@ impl<'ctx> CodeGen<'ctx> {
                             block_uses.insert(value.clone());
                         }
                     }
-                    crate::mir::MirInstr::BinaryOp(_, _, left, right) => {
+                    crate::mir::MirInstr::BinaryOp(_, _, left, right, _) => {
                         if !left.starts_with('%')
                             && !left.parse::<i32>().is_ok()
                             && left != "true"
@@ -366,6 +514,158 @@ impl<'ctx> CodeGen<'ctx> {
                             block_uses.insert(index.clone());
                         }
                     }
+                    crate::mir::MirInstr::OptionalIsSome { optional, .. }
+                    | crate::mir::MirInstr::OptionalUnwrap { optional, .. }
+                        if !optional.starts_with('%') =>
+                    {
+                        block_uses.insert(optional.clone());
+                    }
+                    crate::mir::MirInstr::ArrayConcat { left, right, .. } => {
+                        for used in [left, right] {
+                            if !used.starts_with('%') {
+                                block_uses.insert(used.clone());
+                            }
+                        }
+                    }
+                    crate::mir::MirInstr::ArrayPush { array, value, .. }
+                    | crate::mir::MirInstr::ArrayInsert { array, value, .. } => {
+                        for used in [array, value] {
+                            if !used.starts_with('%') && used.parse::<i32>().is_err() {
+                                block_uses.insert(used.clone());
+                            }
+                        }
+                    }
+                    crate::mir::MirInstr::ArrayPop { array, .. }
+                    | crate::mir::MirInstr::ArrayRemove { array, .. }
+                    | crate::mir::MirInstr::ArrayClear { array, .. }
+                    | crate::mir::MirInstr::ArraySort { array, .. }
+                    | crate::mir::MirInstr::ArrayReverse { array, .. } => {
+                        block_uses.insert(array.clone());
+                    }
+                    crate::mir::MirInstr::ArraySum { array, .. }
+                    | crate::mir::MirInstr::ArrayMin { array, .. }
+                    | crate::mir::MirInstr::ArrayMax { array, .. }
+                        if !array.starts_with('%') =>
+                    {
+                        block_uses.insert(array.clone());
+                    }
+                    crate::mir::MirInstr::MapGet { map, key, .. }
+                    | crate::mir::MirInstr::MapRemove { map, key, .. }
+                    | crate::mir::MirInstr::MapContains { map, key, .. } => {
+                        for used in [map, key] {
+                            if !used.starts_with('%') && used.parse::<i32>().is_err() {
+                                block_uses.insert(used.clone());
+                            }
+                        }
+                    }
+                    crate::mir::MirInstr::MapInsert {
+                        map, key, value, ..
+                    } => {
+                        for used in [map, key, value] {
+                            if !used.starts_with('%') && used.parse::<i32>().is_err() {
+                                block_uses.insert(used.clone());
+                            }
+                        }
+                    }
+                    crate::mir::MirInstr::MapClear { map, .. }
+                    | crate::mir::MirInstr::MapKeys { map, .. }
+                    | crate::mir::MirInstr::MapValues { map, .. }
+                        if !map.starts_with('%') =>
+                    {
+                        block_uses.insert(map.clone());
+                    }
+                    crate::mir::MirInstr::StrLen { string, .. }
+                    | crate::mir::MirInstr::StrTrim { string, .. }
+                        if !string.starts_with('%') =>
+                    {
+                        block_uses.insert(string.clone());
+                    }
+                    crate::mir::MirInstr::StrContains {
+                        string, part: arg, ..
+                    }
+                    | crate::mir::MirInstr::StrSplit {
+                        string, sep: arg, ..
+                    } => {
+                        for used in [string, arg] {
+                            if !used.starts_with('%') {
+                                block_uses.insert(used.clone());
+                            }
+                        }
+                    }
+                    crate::mir::MirInstr::StrReplace {
+                        string,
+                        from: first,
+                        to: second,
+                        ..
+                    }
+                    | crate::mir::MirInstr::StrSubstring {
+                        string,
+                        start: first,
+                        end: second,
+                        ..
+                    } => {
+                        for used in [string, first, second] {
+                            if !used.starts_with('%') && used.parse::<i32>().is_err() {
+                                block_uses.insert(used.clone());
+                            }
+                        }
+                    }
+                    crate::mir::MirInstr::StructGet {
+                        struct_instance, ..
+                    } if !struct_instance.starts_with('%') => {
+                        block_uses.insert(struct_instance.clone());
+                    }
+                    crate::mir::MirInstr::StructSet {
+                        struct_instance,
+                        value,
+                        ..
+                    } => {
+                        for used in [struct_instance, value] {
+                            if !used.starts_with('%') && used.parse::<i32>().is_err() {
+                                block_uses.insert(used.clone());
+                            }
+                        }
+                    }
+                    crate::mir::MirInstr::StructInit { fields, .. } => {
+                        for (_, value) in fields {
+                            if !value.starts_with('%') && value.parse::<i32>().is_err() {
+                                block_uses.insert(value.clone());
+                            }
+                        }
+                    }
+                    crate::mir::MirInstr::MakeClosure { captures, .. } => {
+                        for value in captures {
+                            block_uses.insert(value.clone());
+                        }
+                    }
+                    crate::mir::MirInstr::CallClosure { closure, args, .. } => {
+                        for used in std::iter::once(closure).chain(args) {
+                            if !used.starts_with('%') && used.parse::<i32>().is_err() {
+                                block_uses.insert(used.clone());
+                            }
+                        }
+                    }
+                    crate::mir::MirInstr::EnumInit {
+                        value: Some(value), ..
+                    } if !value.starts_with('%') && value.parse::<i32>().is_err() => {
+                        block_uses.insert(value.clone());
+                    }
+                    crate::mir::MirInstr::EnumMatch { enum_instance, .. }
+                    | crate::mir::MirInstr::EnumPayload { enum_instance, .. }
+                        if !enum_instance.starts_with('%') =>
+                    {
+                        block_uses.insert(enum_instance.clone());
+                    }
+                    crate::mir::MirInstr::OptionalUnwrapOr {
+                        optional, default, ..
+                    } => {
+                        if !optional.starts_with('%') {
+                            block_uses.insert(optional.clone());
+                        }
+                        if !default.starts_with('%') && default.parse::<i32>().is_err() {
+                            block_uses.insert(default.clone());
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -373,6 +673,9 @@ impl<'ctx> CodeGen<'ctx> {
             // Check terminator for variable uses
             if let Some(term) = &block.terminator {
                 match term {
+                    crate::mir::MirInstr::Panic { message } if !message.starts_with('%') => {
+                        block_uses.insert(message.clone());
+                    }
                     crate::mir::MirInstr::CondJump { cond, .. } => {
                         if !cond.starts_with('%')
                             && !cond.parse::<i32>().is_ok()
@@ -434,37 +737,202 @@ impl<'ctx> CodeGen<'ctx> {
                             self.context.ptr_type(AddressSpace::default()).into(),
                         );
                     }
-                    // Variables with "_array" or "_map" suffix are pointers
-                    // BUT: exclude index variables (ending with __index)
-                    crate::mir::MirInstr::Assign { name, value, .. } => {
-                        // Index variables are always i32
-                        if name.ends_with("__index") || name.ends_with("_end") {
-                            var_types.insert(name.clone(), self.context.i32_type().into());
-                        } else if name.ends_with("_array")
-                            || name.ends_with("_map")
-                            || name.ends_with("item_array")
-                            || name.ends_with("_ptr")
+                    // Variables take the type of the value assigned to them, or else
+                    // the MIR's type for them
+                    crate::mir::MirInstr::Assign {
+                        name, value, ty, ..
+                    } => {
+                        let assigned = var_types
+                            .get(value)
+                            .copied()
+                            .or_else(|| (*ty != MirType::Void).then(|| self.struct_field_type(ty)));
+                        if let Some(assigned) = assigned {
+                            var_types.insert(name.clone(), assigned);
+                        }
+                    }
+                    // Conversion builtins have fixed result types
+                    crate::mir::MirInstr::Call { dest, func, ty, .. } => {
+                        if let (Some(name), Some((builtin, _))) =
+                            (dest.first(), Self::builtin_conversion(func))
                         {
-                            // Only mark as pointer if it's NOT an index variable
+                            var_types.insert(name.clone(), self.builtin_return_type(builtin));
+                        } else if let Some(name) = dest.first() {
+                            // Tuple returns are structs, struct values are pointers
+                            let returns_struct_value = self
+                                .function_return_types
+                                .get(&Self::llvm_function_name(func))
+                                .is_some_and(|t| t.struct_name().is_some());
+                            let ret_type = match self.sret_return_type(func) {
+                                Some(struct_type) => Some(struct_type.into()),
+                                None => self
+                                    .module
+                                    .get_function(&Self::llvm_function_name(func))
+                                    .and_then(|f| f.get_type().get_return_type())
+                                    .filter(|t| t.is_struct_type() || returns_struct_value),
+                            };
+                            if let Some(ret_type) = ret_type {
+                                var_types.insert(name.clone(), ret_type);
+                            } else if *ty != MirType::Void {
+                                var_types.insert(name.clone(), self.struct_field_type(ty));
+                            }
+                        }
+                    }
+                    // Tuple elements take the type of their struct field
+                    crate::mir::MirInstr::TupleExtract {
+                        name,
+                        source,
+                        index,
+                    } => {
+                        let field = var_types
+                            .get(source)
+                            .filter(|t| t.is_struct_type())
+                            .and_then(|t| {
+                                t.into_struct_type().get_field_type_at_index(*index as u32)
+                            });
+                        if let Some(field) = field {
+                            var_types.insert(name.clone(), field);
+                        }
+                    }
+                    // Struct values are pointers, fields have their declared type
+                    crate::mir::MirInstr::StructInit { name, .. } => {
+                        var_types.insert(
+                            name.clone(),
+                            self.context.ptr_type(AddressSpace::default()).into(),
+                        );
+                    }
+                    crate::mir::MirInstr::StructGet {
+                        name,
+                        struct_name,
+                        field,
+                        ..
+                    } => {
+                        let field_type = self
+                            .struct_layouts
+                            .get(struct_name)
+                            .and_then(|fields| fields.iter().find(|(f, _)| f == field))
+                            .map(|(_, ty)| self.optional_payload_type(ty));
+                        if let Some(field_type) = field_type {
+                            var_types.insert(name.clone(), field_type);
+                        }
+                    }
+                    // Closure values are pointers, their calls return what the closure does
+                    crate::mir::MirInstr::MakeClosure { name, .. } => {
+                        var_types.insert(
+                            name.clone(),
+                            self.context.ptr_type(AddressSpace::default()).into(),
+                        );
+                    }
+                    crate::mir::MirInstr::CallClosure {
+                        dest,
+                        ty: MirType::Function(_, ret),
+                        ..
+                    } => {
+                        if let (Some(name), false) = (dest.first(), **ret == MirType::Void) {
+                            var_types.insert(name.clone(), self.struct_field_type(ret));
+                        }
+                    }
+                    // Enum values are pointers, payloads have their declared type
+                    crate::mir::MirInstr::EnumInit { name, .. } => {
+                        var_types.insert(
+                            name.clone(),
+                            self.context.ptr_type(AddressSpace::default()).into(),
+                        );
+                    }
+                    crate::mir::MirInstr::EnumPayload {
+                        name,
+                        enum_name,
+                        variant,
+                        ..
+                    } => {
+                        let payload_type = self
+                            .enum_layouts
+                            .get(enum_name)
+                            .and_then(|variants| variants.iter().find(|(v, _)| v == variant))
+                            .and_then(|(_, ty)| ty.as_ref())
+                            .map(|ty| self.struct_field_type(ty));
+                        if let Some(payload_type) = payload_type {
+                            var_types.insert(name.clone(), payload_type);
+                        }
+                    }
+                    // ArrayLen results are i32
+                    crate::mir::MirInstr::ArrayLen { name, .. } => {
+                        var_types.insert(name.clone(), self.context.i32_type().into());
+                    }
+                    crate::mir::MirInstr::ArrayConcat { name, .. } => {
+                        var_types.insert(
+                            name.clone(),
+                            self.context.ptr_type(AddressSpace::default()).into(),
+                        );
+                    }
+                    // Popping yields an optional element, removing the element itself
+                    crate::mir::MirInstr::ArrayPop { name, ty, .. } => {
+                        let elem = ty.element();
+                        if let Some(elem) = elem {
                             var_types.insert(
                                 name.clone(),
-                                self.context.ptr_type(AddressSpace::default()).into(),
+                                self.optional_llvm_type(&MirType::Optional(Box::new(elem.clone()))),
                             );
                         }
-                        // If assigned from a known pointer type, it's also a pointer
-                        // BUT: not if this is an index variable
-                        else if !name.ends_with("__index") && !name.ends_with("_end") {
-                            if let Some(val_type) = var_types.get(value) {
-                                if val_type.is_pointer_type() {
-                                    var_types.insert(name.clone(), *val_type);
-                                }
-                            }
+                    }
+                    // Sums are numbers of the element type, min and max optional ones
+                    crate::mir::MirInstr::ArraySum { name, ty, .. } => {
+                        let elem = ty.element();
+                        if let Some(elem) = elem {
+                            var_types.insert(name.clone(), self.struct_field_type(elem));
                         }
                     }
-                    // ArrayLen results are i32
-                    crate::mir::MirInstr::ArrayLen { name, .. } => {
+                    crate::mir::MirInstr::ArrayMin { name, ty, .. }
+                    | crate::mir::MirInstr::ArrayMax { name, ty, .. } => {
+                        let elem = ty.element();
+                        if let Some(elem) = elem {
+                            var_types.insert(
+                                name.clone(),
+                                self.optional_llvm_type(&MirType::Optional(Box::new(elem.clone()))),
+                            );
+                        }
+                    }
+                    crate::mir::MirInstr::ArrayRemove { name, ty, .. } => {
+                        let elem = ty.element();
+                        if let Some(elem) = elem {
+                            var_types.insert(name.clone(), self.struct_field_type(elem));
+                        }
+                    }
+                    // Map lookups yield an optional value
+                    crate::mir::MirInstr::MapGet { name, ty, .. }
+                    | crate::mir::MirInstr::MapRemove { name, ty, .. } => {
+                        if let Some((_, value)) = ty.map_parts() {
+                            var_types.insert(
+                                name.clone(),
+                                self.optional_llvm_type(&MirType::Optional(Box::new(
+                                    value.clone(),
+                                ))),
+                            );
+                        }
+                    }
+                    crate::mir::MirInstr::MapContains { name, .. } => {
+                        var_types.insert(name.clone(), self.context.i32_type().into());
+                    }
+                    crate::mir::MirInstr::MapKeys { name, .. }
+                    | crate::mir::MirInstr::MapValues { name, .. } => {
+                        var_types.insert(
+                            name.clone(),
+                            self.context.ptr_type(AddressSpace::default()).into(),
+                        );
+                    }
+                    // String lengths are i32, `contains` a Bool (i32), the rest new strings or arrays
+                    crate::mir::MirInstr::StrLen { name, .. }
+                    | crate::mir::MirInstr::StrContains { name, .. } => {
                         var_types.insert(name.clone(), self.context.i32_type().into());
                     }
+                    crate::mir::MirInstr::StrSplit { name, .. }
+                    | crate::mir::MirInstr::StrTrim { name, .. }
+                    | crate::mir::MirInstr::StrReplace { name, .. }
+                    | crate::mir::MirInstr::StrSubstring { name, .. } => {
+                        var_types.insert(
+                            name.clone(),
+                            self.context.ptr_type(AddressSpace::default()).into(),
+                        );
+                    }
                     // MapLen results are i32
                     crate::mir::MirInstr::MapLen { name, .. } => {
                         var_types.insert(name.clone(), self.context.i32_type().into());
@@ -473,13 +941,30 @@ impl<'ctx> CodeGen<'ctx> {
                     crate::mir::MirInstr::ConstInt { name, .. } => {
                         var_types.insert(name.clone(), self.context.i32_type().into());
                     }
+                    // 64-bit integer constants are i64
+                    crate::mir::MirInstr::ConstInt64 { name, .. } => {
+                        var_types.insert(name.clone(), self.context.i64_type().into());
+                    }
+                    // Float constants are f64
+                    crate::mir::MirInstr::ConstFloat { name, .. } => {
+                        var_types.insert(name.clone(), self.context.f64_type().into());
+                    }
                     // Boolean constants are i32
                     crate::mir::MirInstr::ConstBool { name, .. } => {
                         var_types.insert(name.clone(), self.context.i32_type().into());
                     }
-                    // Binary operations produce i32
-                    crate::mir::MirInstr::BinaryOp(_, name, ..) => {
-                        var_types.insert(name.clone(), self.context.i32_type().into());
+                    // Comparisons are Bools (i32), arithmetic has its operands' type
+                    crate::mir::MirInstr::BinaryOp(_, name, _, _, ty) => {
+                        var_types.insert(name.clone(), self.struct_field_type(ty));
+                    }
+                    // Optionals are a {i1, T} pair or a nullable pointer
+                    crate::mir::MirInstr::OptionalWrap { name, ty, .. } => {
+                        var_types.insert(name.clone(), self.optional_llvm_type(ty));
+                    }
+                    // Unwrapping yields the payload type
+                    crate::mir::MirInstr::OptionalUnwrap { name, ty, .. }
+                    | crate::mir::MirInstr::OptionalUnwrapOr { name, ty, .. } => {
+                        var_types.insert(name.clone(), self.optional_payload_type(ty));
                     }
                     _ => {}
                 }
@@ -495,26 +980,10 @@ impl<'ctx> CodeGen<'ctx> {
                 }
 
                 // Determine the correct type for this variable
-                let var_type = var_types.get(var).copied().unwrap_or_else(|| {
-                    // Index and end variables are always i32
-                    if var.ends_with("__index")
-                        || var.ends_with("_end")
-                        || var == "i"
-                        || var == "counter"
-                    {
-                        self.context.i32_type().into()
-                    }
-                    // Default heuristic: if name suggests array/map/string, use ptr, otherwise i32
-                    else if var.ends_with("_array")
-                        || var.ends_with("_map")
-                        || var.ends_with("item_array")
-                        || var.ends_with("_ptr")
-                    {
-                        self.context.ptr_type(AddressSpace::default()).into()
-                    } else {
-                        self.context.i32_type().into()
-                    }
-                });
+                let var_type = var_types
+                    .get(var)
+                    .copied()
+                    .unwrap_or_else(|| self.context.i32_type().into());
 
                 let alloca = self
                     .builder
@@ -529,13 +998,18 @@ impl<'ctx> CodeGen<'ctx> {
                         .ptr_type(AddressSpace::default())
                         .const_null()
                         .into()
+                } else if var_type.is_float_type() {
+                    self.context.f64_type().const_float(0.0).into()
                 } else if var_type.is_int_type() {
-                    self.context.i32_type().const_int(0, false).into()
+                    var_type.into_int_type().const_zero().into()
+                } else if var_type.is_struct_type() {
+                    var_type.into_struct_type().const_zero().into()
                 } else {
                     self.context.i32_type().const_int(0, false).into()
                 };
 
                 self.builder.build_store(alloca, default_val).unwrap();
+                self.declare_debug_variable(var, alloca, var_type, false, None);
 
                 self.symbols.insert(
                     var.clone(),
@@ -568,6 +1042,9 @@ impl<'ctx> CodeGen<'ctx> {
                     Some(MirInstr::Return { values }) => Some(MirTerminator::Return {
                         values: values.clone(),
                     }),
+                    Some(MirInstr::Panic { message }) => Some(MirTerminator::Panic {
+                        message: message.clone(),
+                    }),
                     Some(MirInstr::Jump { target }) => Some(MirTerminator::Jump {
                         target: target.clone(),
                     }),
@@ -589,40 +1066,26 @@ impl<'ctx> CodeGen<'ctx> {
         for block in &func.blocks {
             self.generate_block_with_loops(block, llvm_func, &bb_map);
         }
+        self.end_debug_function();
 
         llvm_func
     }
 
     /// Generate cleanup for all RC variables at function exit
     /// This ensures variables in conditional blocks are properly cleaned up
-    fn generate_function_exit_cleanup(&mut self) {
+    pub(crate) fn generate_function_exit_cleanup(&mut self) {
         // OWNERSHIP MODEL:
         // Only cleanup heap objects that:
         // 1. Have a symbol (alloca in entry block) - these are guaranteed valid across all blocks
         // 2. Are not loop-local (loop vars are cleaned in loop exit)
-        // 3. Are not compiler temporaries (temps like %123, data_ptr45, etc.)
+        // 3. Are not MIR temporaries (`%N`), which the variables they're assigned to own
         //
         // We NEVER cleanup:
         // - Temporary GEP results (only exist in one block)
         // - Values in temp_values that have no corresponding symbol
         // - Block-local SSA values
 
-        // Helper to check if a name is a compiler temporary
-        let is_compiler_temp = |name: &str| -> bool {
-            name.starts_with('%')
-                || name.starts_with("data_ptr")
-                || name.starts_with("heap_")
-                || name.starts_with("rc_")
-                || name.starts_with("concat_")
-                || name.starts_with("temp_")
-                || name.contains("_ptr")
-                || name.contains("_header")
-                || name.contains("_val")
-                || name.contains("elem_")
-                || name.contains("pair_")
-                || name.contains("key_")
-                || name.contains("loaded")
-        };
+        let is_compiler_temp = |name: &str| name.starts_with('%');
 
         // Collect heap strings from symbols (user variables only)
         let mut heap_strings: Vec<String> = self
@@ -677,21 +1140,7 @@ impl<'ctx> CodeGen<'ctx> {
 
             // Safe to cleanup: parent is a valid symbol
             for str_ptr in str_ptrs {
-                let data_ptr = str_ptr.into_pointer_value();
-                let rc_header = unsafe {
-                    self.builder.build_in_bounds_gep(
-                        self.context.i8_type(),
-                        data_ptr,
-                        &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                        "rc_header",
-                    )
-                }
-                .unwrap();
-
-                let decref = self.decref_fn.unwrap();
-                self.builder
-                    .build_call(decref, &[rc_header.into()], "")
-                    .unwrap();
+                self.decref_value(str_ptr.into_pointer_value());
             }
         }
 
@@ -731,21 +1180,7 @@ impl<'ctx> CodeGen<'ctx> {
             // For temps, we need to get the pointer from temp_values and decref
             if let Some(val) = self.temp_values.get(&temp_name) {
                 if val.is_pointer_value() {
-                    let data_ptr = val.into_pointer_value();
-                    let rc_header = unsafe {
-                        self.builder.build_in_bounds_gep(
-                            self.context.i8_type(),
-                            data_ptr,
-                            &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                            "rc_header",
-                        )
-                    }
-                    .unwrap();
-
-                    let decref = self.decref_fn.unwrap();
-                    self.builder
-                        .build_call(decref, &[rc_header.into()], "")
-                        .unwrap();
+                    self.decref_value(val.into_pointer_value());
                 }
             }
         }
@@ -965,6 +1400,9 @@ impl<'ctx> CodeGen<'ctx> {
                 MirInstr::Return { values } => MirTerminator::Return {
                     values: values.clone(),
                 },
+                MirInstr::Panic { message } => MirTerminator::Panic {
+                    message: message.clone(),
+                },
                 MirInstr::Jump { target } => MirTerminator::Jump {
                     target: target.clone(),
                 },
@@ -1025,41 +1463,19 @@ impl<'ctx> CodeGen<'ctx> {
 
                     // Now it's safe to decref the string pointers in this composite
                     for str_ptr in str_ptrs {
-                        let data_ptr = str_ptr.into_pointer_value();
-                        let rc_header = unsafe {
-                            self.builder.build_in_bounds_gep(
-                                self.context.i8_type(),
-                                data_ptr,
-                                &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                                "rc_header",
-                            )
-                        }
-                        .unwrap();
-
-                        let decref = self.decref_fn.unwrap();
-                        self.builder
-                            .build_call(decref, &[rc_header.into()], "")
-                            .unwrap();
+                        self.decref_value(str_ptr.into_pointer_value());
                     }
                 }
 
                 // 2. Cleanup composite strings tracked via composite_strings map
 
-                // Determine what value is being returned (if any) to exclude it from cleanup
-                let return_value_name = if !values.is_empty() {
-                    Some(values[0].as_str())
-                } else {
-                    None
-                };
+                // Returned values (all of them for a tuple return) are excluded from cleanup
 
                 // 2. Free arrays (exclude return value)
                 let mut heap_array_vars: Vec<String> = self
                     .symbols
                     .keys()
-                    .filter(|name| {
-                        self.heap_arrays.contains(*name)
-                            && return_value_name.map_or(true, |ret| ret != *name)
-                    })
+                    .filter(|name| self.heap_arrays.contains(*name) && !values.contains(*name))
                     .cloned()
                     .collect();
                 heap_array_vars.reverse();
@@ -1072,10 +1488,7 @@ impl<'ctx> CodeGen<'ctx> {
                 let mut heap_map_vars: Vec<String> = self
                     .symbols
                     .keys()
-                    .filter(|name| {
-                        self.heap_maps.contains(*name)
-                            && return_value_name.map_or(true, |ret| ret != *name)
-                    })
+                    .filter(|name| self.heap_maps.contains(*name) && !values.contains(*name))
                     .cloned()
                     .collect();
                 heap_map_vars.reverse();
@@ -1088,10 +1501,7 @@ impl<'ctx> CodeGen<'ctx> {
                 let mut heap_str_vars: Vec<String> = self
                     .symbols
                     .keys()
-                    .filter(|name| {
-                        self.heap_strings.contains(*name)
-                            && return_value_name.map_or(true, |ret| ret != *name)
-                    })
+                    .filter(|name| self.heap_strings.contains(*name) && !values.contains(*name))
                     .cloned()
                     .collect();
                 heap_str_vars.reverse();
@@ -1100,7 +1510,22 @@ impl<'ctx> CodeGen<'ctx> {
                     self.emit_decref(&var_name);
                 }
 
-                // 5. Free temporary heap strings (intermediate concat results, etc.)
+                // 5. Release structs held by variables (exclude return value)
+                let struct_vars: Vec<(String, String)> = self
+                    .heap_structs
+                    .iter()
+                    .filter(|(name, _)| {
+                        self.symbols.contains_key(*name)
+                            && !self.loop_local_vars.contains(*name)
+                            && !values.contains(*name)
+                    })
+                    .map(|(name, struct_name)| (name.clone(), struct_name.clone()))
+                    .collect();
+                for (var_name, struct_name) in struct_vars {
+                    self.release_struct_var(&var_name, &struct_name);
+                }
+
+                // 6. Free temporary heap strings (intermediate concat results, etc.)
                 // These are heap-allocated strings that are NOT in symbols (no alloca)
                 // but ARE tracked in heap_strings (e.g., intermediate concat results)
                 let mut temp_heap_strs: Vec<String> = self
@@ -1110,7 +1535,7 @@ impl<'ctx> CodeGen<'ctx> {
                         // Only temps (not in symbols), and not the return value
                         !self.symbols.contains_key(*name)
                             && !self.loop_local_vars.contains(*name)
-                            && return_value_name.map_or(true, |ret| ret != *name)
+                            && !values.contains(*name)
                             && self.temp_values.contains_key(*name)
                     })
                     .cloned()
@@ -1121,26 +1546,13 @@ impl<'ctx> CodeGen<'ctx> {
                     // For temps, we need to get the pointer from temp_values and decref
                     if let Some(val) = self.temp_values.get(&temp_name) {
                         if val.is_pointer_value() {
-                            let data_ptr = val.into_pointer_value();
-                            let rc_header = unsafe {
-                                self.builder.build_in_bounds_gep(
-                                    self.context.i8_type(),
-                                    data_ptr,
-                                    &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                                    "rc_header",
-                                )
-                            }
-                            .unwrap();
-
-                            let decref = self.decref_fn.unwrap();
-                            self.builder
-                                .build_call(decref, &[rc_header.into()], "")
-                                .unwrap();
+                            self.decref_value(val.into_pointer_value());
                         }
                     }
                 }
 
                 if values.is_empty() {
+                    self.emit_arena_exit();
                     // Check if this is the main function - it must return i32 0
                     let fn_name = func.get_name().to_str().unwrap();
                     if fn_name == "main" {
@@ -1150,6 +1562,12 @@ impl<'ctx> CodeGen<'ctx> {
                         // Void return - no value
                         self.builder.build_return(None).unwrap();
                     }
+                } else if values.len() > 1 {
+                    // Tuple return: store the values in the caller's sret slot
+                    let (out, struct_type) = self.sret_result.expect("tuple return needs sret");
+                    self.build_tuple_return(out, struct_type, values);
+                    self.emit_arena_exit();
+                    self.builder.build_return(None).unwrap();
                 } else {
                     let return_value_name = &values[0];
 
@@ -1157,7 +1575,8 @@ impl<'ctx> CodeGen<'ctx> {
                     let fn_name = func.get_name().to_str().unwrap();
                     let is_heap_return = self.heap_strings.contains(return_value_name)
                         || self.heap_arrays.contains(return_value_name)
-                        || self.heap_maps.contains(return_value_name);
+                        || self.heap_maps.contains(return_value_name)
+                        || self.heap_structs.contains_key(return_value_name);
 
                     if is_heap_return {
                         self.functions_returning_heap.insert(fn_name.to_string());
@@ -1170,15 +1589,27 @@ impl<'ctx> CodeGen<'ctx> {
                             .any(|(param_name, param_type)| {
                                 if param_name == return_value_name {
                                     // Check if this parameter is RC-typed
-                                    if let Some(type_str) = param_type {
-                                        return type_str.contains("String")
-                                            || type_str.contains("Str")
-                                            || type_str.contains("Array")
-                                            || type_str.contains("Map");
+                                    if let Some(ty) = param_type {
+                                        return ty.optional_payload().is_heap();
                                     }
                                 }
                                 false
                             });
+                    // Struct results are always owned by the caller: a returned field
+                    // or parameter gets its own reference. So are the heap results
+                    // of closures, whose callers can't know where they come from
+                    let needs_incref = needs_incref
+                        || (self
+                            .function_return_types
+                            .get(fn_name)
+                            .is_some_and(|t| t.struct_name().is_some())
+                            && !self.heap_structs.contains_key(return_value_name))
+                        || (self.closure_captures.contains_key(fn_name)
+                            && self
+                                .function_return_types
+                                .get(fn_name)
+                                .is_some_and(Self::closure_result_is_owned)
+                            && !is_heap_return);
 
                     let val = self.resolve_value(return_value_name);
 
@@ -1190,26 +1621,15 @@ impl<'ctx> CodeGen<'ctx> {
                     }
 
                     if needs_incref && val.is_pointer_value() {
-                        let ptr = val.into_pointer_value();
-                        let rc_header = unsafe {
-                            self.builder.build_in_bounds_gep(
-                                self.context.i8_type(),
-                                ptr,
-                                &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                                "return_rc_header",
-                            )
-                        }
-                        .unwrap();
-
-                        let incref_fn = self.incref_fn.unwrap();
-                        self.builder
-                            .build_call(incref_fn, &[rc_header.into()], "")
-                            .unwrap();
+                        self.incref_value(val.into_pointer_value());
                     }
 
+                    self.emit_arena_exit();
                     self.builder.build_return(Some(&val)).unwrap();
                 }
             }
+            // Handles `panic(message)`: the runtime routine never returns.
+            MirTerminator::Panic { message } => self.generate_panic(message),
             // Handles unconditional jump (goto).
             MirTerminator::Jump { target } => {
                 let target_bb = bb_map.get(target).expect("Target BB not found");
@@ -1223,29 +1643,12 @@ impl<'ctx> CodeGen<'ctx> {
                 else_block,
             } => {
                 let cond_val = self.resolve_value(cond);
-
-                // Check if condition is already i1 (from comparison) or i32 (from bool variable)
+                // Bools are i32; the branch is where one becomes an i1
                 let cond_i1 = if cond_val.is_int_value() {
-                    let int_val = cond_val.into_int_value();
-                    let int_type = int_val.get_type();
-
-                    if int_type.get_bit_width() == 1 {
-                        // Already i1, use directly
-                        int_val
-                    } else {
-                        // i32 boolean, convert to i1
-                        self.builder
-                            .build_int_compare(
-                                inkwell::IntPredicate::NE,
-                                int_val,
-                                self.context.i32_type().const_zero(),
-                                "cond_i1",
-                            )
-                            .unwrap()
-                    }
+                    self.build_condition(cond_val.into_int_value())
                 } else {
                     debug_assert!(false, "Condition value is not an integer type");
-                    self.context.i32_type().const_zero()
+                    self.context.bool_type().const_zero()
                 };
 
                 let then_bb = bb_map.get(then_block).expect("Then BB not found");
@@ -1297,7 +1700,8 @@ impl<'ctx> CodeGen<'ctx> {
         }
 
         // Generate all instructions in the block.
-        for instr in &block.instrs {
+        for (index, instr) in block.instrs.iter().enumerate() {
+            self.set_statement_location(&block.label, index);
             // Check for loop-related instructions and handle accordingly.
             match instr {
                 MirInstr::ForRange { .. }
@@ -1395,11 +1799,15 @@ impl<'ctx> CodeGen<'ctx> {
         }
 
         // Handle terminator if present (return, jump, cond jump).
+        self.set_statement_location(&block.label, block.instrs.len());
         if let Some(instr) = &block.terminator {
             let term = match instr {
                 MirInstr::Return { values } => crate::mir::mir::MirTerminator::Return {
                     values: values.clone(),
                 },
+                MirInstr::Panic { message } => crate::mir::mir::MirTerminator::Panic {
+                    message: message.clone(),
+                },
                 MirInstr::Jump { target } => crate::mir::mir::MirTerminator::Jump {
                     target: target.clone(),
                 },
@@ -1423,6 +1831,7 @@ impl<'ctx> CodeGen<'ctx> {
             if fn_name == "main" {
                 // Main function must return i32 0
                 self.generate_function_exit_cleanup();
+                self.emit_arena_exit();
                 let zero = self.context.i32_type().const_int(0, false);
                 self.builder.build_return(Some(&zero)).unwrap();
             } else {
@@ -1430,9 +1839,10 @@ impl<'ctx> CodeGen<'ctx> {
                 let fn_type = func.get_type();
                 let return_type = fn_type.get_return_type();
 
-                if return_type.is_none() {
+                if return_type.is_none() && self.sret_result.is_none() {
                     // Void function - add cleanup and return void
                     self.generate_function_exit_cleanup();
+                    self.emit_arena_exit();
                     self.builder.build_return(None).unwrap();
                 } else {
                     // Non-void function without terminator - unreachable
@@ -1459,21 +1869,7 @@ impl<'ctx> CodeGen<'ctx> {
                 // Clean up strings in array elements if needed.
                 if let Some(str_ptrs) = self.composite_string_ptrs.get(var) {
                     for str_ptr in str_ptrs {
-                        let data_ptr = str_ptr.into_pointer_value();
-                        let rc_header = unsafe {
-                            self.builder.build_in_bounds_gep(
-                                self.context.i8_type(),
-                                data_ptr,
-                                &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                                "rc_header",
-                            )
-                        }
-                        .unwrap();
-
-                        let decref = self.decref_fn.unwrap();
-                        self.builder
-                            .build_call(decref, &[rc_header.into()], "")
-                            .unwrap();
+                        self.decref_value(str_ptr.into_pointer_value());
                     }
                 }
                 self.emit_decref(var);
@@ -1484,21 +1880,7 @@ impl<'ctx> CodeGen<'ctx> {
                     for str_name in str_names {
                         if let Some(val) = self.temp_values.get(str_name) {
                             if val.is_pointer_value() {
-                                let data_ptr = val.into_pointer_value();
-                                let rc_header = unsafe {
-                                    self.builder.build_in_bounds_gep(
-                                        self.context.i8_type(),
-                                        data_ptr,
-                                        &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                                        "rc_header",
-                                    )
-                                }
-                                .unwrap();
-
-                                let decref = self.decref_fn.unwrap();
-                                self.builder
-                                    .build_call(decref, &[rc_header.into()], "")
-                                    .unwrap();
+                                self.decref_value(val.into_pointer_value());
                             }
                         }
                     }
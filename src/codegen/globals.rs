@@ -1,14 +1,15 @@
 use crate::codegen::core::{CodeGen, Symbol};
 use crate::mir::mir::MirInstr;
+use inkwell::module::Linkage;
 use inkwell::types::{AsTypeRef, BasicType, BasicTypeEnum};
-use inkwell::values::{AsValueRef, BasicValue, BasicValueEnum};
+use inkwell::values::{AsValueRef, BasicValue, BasicValueEnum, FunctionValue};
 
 /// This module provides functions for generating LLVM IR for global variables, constants, arrays, maps, and string operations.
 /// It handles the translation of MIR instructions into LLVM global definitions, including constant folding and compile-time string concatenation.
 /// The code here is essential for setting up the global state of a program before function-level code generation begins.
 /// External function from the LLVM C API needed to create constant arrays
 /// of complex types (like arrays of structs, or nested arrays).
-use llvm_sys::core::LLVMConstArray;
+use llvm_sys::core::{LLVMConstArray, LLVMSetInitializer};
 use llvm_sys::prelude::LLVMValueRef;
 
 /// Implements global code generation logic for the CodeGen struct.
@@ -45,10 +46,20 @@ impl<'ctx> CodeGen<'ctx> {
     pub fn generate_global(&mut self, instr: &MirInstr) {
         match instr {
             // Integer constant global (only i32 for integers)
-            MirInstr::ConstInt { name, value } => {
+            MirInstr::ConstInt { name, value, .. } => {
                 let val = self.context.i32_type().const_int(*value as u64, true);
                 self.temp_values.insert(name.clone(), val.into());
             }
+            // 64-bit integer constant global
+            MirInstr::ConstInt64 { name, value } => {
+                let val = self.context.i64_type().const_int(*value as u64, true);
+                self.temp_values.insert(name.clone(), val.into());
+            }
+            // Float constant global (f64)
+            MirInstr::ConstFloat { name, value } => {
+                let val = self.context.f64_type().const_float(*value);
+                self.temp_values.insert(name.clone(), val.into());
+            }
             // Boolean constant global
             MirInstr::ConstBool { name, value } => {
                 // Use i32 instead of i1 for consistency with rest of codegen
@@ -73,7 +84,7 @@ impl<'ctx> CodeGen<'ctx> {
                 }
             }
             // Handles constant-time binary operations (e.g., global `a = 5 + 2`).
-            MirInstr::BinaryOp(op, dst, lhs, rhs) => {
+            MirInstr::BinaryOp(op, dst, lhs, rhs, _) => {
                 // Resolve the constant values of the operands.
                 let lhs_val = self.resolve_global_value(lhs).into_int_value();
                 let rhs_val = self.resolve_global_value(rhs).into_int_value();
@@ -97,11 +108,34 @@ impl<'ctx> CodeGen<'ctx> {
                 // Store the result as a new constant value.
                 self.temp_values.insert(dst.clone(), res.into());
             }
+            // Global optional: a constant {i1, T} pair, or a (possibly null) pointer.
+            MirInstr::OptionalWrap { name, value, ty } => {
+                let val: BasicValueEnum = match self.scalar_optional_type(ty) {
+                    Some(st) => {
+                        let (present, payload) = match value {
+                            Some(v) => (1, self.resolve_global_value(v)),
+                            None => (0, self.optional_payload_type(ty).const_zero()),
+                        };
+                        let tag = self.context.bool_type().const_int(present, false);
+                        st.const_named_struct(&[tag.into(), payload]).into()
+                    }
+                    None => match value {
+                        Some(v) => self.resolve_global_value(v),
+                        None => self
+                            .context
+                            .ptr_type(inkwell::AddressSpace::default())
+                            .const_null()
+                            .into(),
+                    },
+                };
+                self.temp_values.insert(name.clone(), val);
+            }
             // Handles the final assignment of a constant/variable to its named global location.
             MirInstr::Assign {
                 name,
                 value,
                 mutable,
+                ..
             } => {
                 // Special handling for constant strings to rename the temporary global
                 // created by ConstString, avoiding redundant memory allocation.
@@ -193,14 +227,7 @@ impl<'ctx> CodeGen<'ctx> {
                 let elem_type = first_val.get_type();
                 let _array_type = elem_type.array_type(elements.len() as u32);
 
-                // Determine element type name and if it contains strings
-                let element_type_name = if elem_type.is_int_type() {
-                    "Int"
-                } else if elem_type.is_pointer_type() {
-                    "Str"
-                } else {
-                    "Unknown"
-                };
+                // Determine if it contains strings
                 let contains_strings = elem_type.is_pointer_type();
 
                 // Create the constant array initializer based on element type.
@@ -225,8 +252,9 @@ impl<'ctx> CodeGen<'ctx> {
                 // Create and store metadata for the array
                 let metadata = crate::codegen::ArrayMetadata {
                     length: elements.len(),
-                    element_type: element_type_name.to_string(),
+                    element_type: Self::literal_element_type(elem_type),
                     contains_strings,
+                    element_metadata: None,
                 };
                 self.array_metadata.insert(name.clone(), metadata);
             }
@@ -240,22 +268,6 @@ impl<'ctx> CodeGen<'ctx> {
                 // Define the structure type {KeyType, ValueType}.
                 let pair_type = self.context.struct_type(&[key_type, val_type], false);
 
-                // Determine type names for metadata
-                let key_type_name = if key_type.is_int_type() {
-                    "Int"
-                } else if key_type.is_pointer_type() {
-                    "Str"
-                } else {
-                    "Unknown"
-                };
-                let value_type_name = if val_type.is_int_type() {
-                    "Int"
-                } else if val_type.is_pointer_type() {
-                    "Str"
-                } else {
-                    "Unknown"
-                };
-
                 // Build ALL struct entries using the defined pair type.
                 let struct_values: Vec<BasicValueEnum<'ctx>> = entries
                     .iter()
@@ -279,10 +291,12 @@ impl<'ctx> CodeGen<'ctx> {
                 // Create and store metadata for the map
                 let metadata = crate::codegen::MapMetadata {
                     length: entries.len(),
-                    key_type: key_type_name.to_string(),
-                    value_type: value_type_name.to_string(),
+                    key_type: Self::literal_element_type(key_type),
+                    value_type: Self::literal_element_type(val_type),
                     key_is_string: key_type.is_pointer_type(),
                     value_is_string: val_type.is_pointer_type(),
+                    value_array: None,
+                    value_map: None,
                 };
                 self.map_metadata.insert(name.clone(), metadata);
             }
@@ -291,6 +305,62 @@ impl<'ctx> CodeGen<'ctx> {
         }
     }
 
+    /// Emits `doo.init`, which runs the global-scope statements that do
+    /// something at run time (prints, the functions wrapping top-level loops
+    /// and conditionals, and the arithmetic they use) in source order.
+    /// `main` calls it first, so they run exactly once before the program.
+    ///
+    /// Must run right after `generate_global`, while the global constants are
+    /// still in `temp_values`. Returns None when there is nothing to run.
+    pub fn generate_init(&mut self) -> Option<FunctionValue<'ctx>> {
+        let statements: Vec<MirInstr> = self
+            .globals
+            .iter()
+            .filter(|instr| {
+                matches!(
+                    instr,
+                    MirInstr::Print { .. } | MirInstr::Call { .. } | MirInstr::BinaryOp(..)
+                )
+            })
+            .cloned()
+            .collect();
+        if !statements
+            .iter()
+            .any(|instr| !matches!(instr, MirInstr::BinaryOp(..)))
+        {
+            return None;
+        }
+
+        let init_type = self.context.void_type().fn_type(&[], false);
+        if self.declare_only {
+            return Some(self.module.add_function("doo.init", init_type, None));
+        }
+        let init = self
+            .module
+            .add_function("doo.init", init_type, Some(Linkage::Internal));
+        let entry = self.context.append_basic_block(init, "entry");
+        self.builder.position_at_end(entry);
+        for instr in &statements {
+            self.generate_instr(instr);
+        }
+        self.builder.build_return(None).unwrap();
+        Some(init)
+    }
+
+    /// Turns the globals `generate_global` defined into declarations of the
+    /// same globals, which another module of a parallel build defines. Their
+    /// symbols and metadata stay.
+    pub(crate) fn declare_globals(&self) {
+        for global in self.module.get_globals() {
+            if global.is_declaration() {
+                continue;
+            }
+            // A null initializer makes the global a declaration
+            unsafe { LLVMSetInitializer(global.as_value_ref(), std::ptr::null_mut()) };
+            global.set_linkage(Linkage::External);
+        }
+    }
+
     /// Resolves a name (which can be a temp variable or a literal) to its LLVM constant value.
     /// This is used recursively for building nested constants.
     ///
@@ -1,6 +1,7 @@
 use crate::codegen::core::CodeGen;
 use crate::mir::MirInstr;
-use inkwell::values::BasicValueEnum;
+use inkwell::intrinsics::Intrinsic;
+use inkwell::values::{BasicValueEnum, IntValue};
 
 use inkwell::{FloatPredicate, IntPredicate};
 
@@ -31,6 +32,18 @@ impl<'ctx> CodeGen<'ctx> {
         let op_name = parts[0];
         let op_type = parts.get(1).copied().unwrap_or("int");
 
+        // String comparisons compare contents, not pointers
+        if op_type == "string" && matches!(op_name, "eq" | "ne" | "lt" | "le" | "gt" | "ge") {
+            return self.generate_string_compare(op_name, dst, lhs, rhs);
+        }
+
+        // Enum comparisons compare variants and payloads: "eq:enum:<Name>"
+        if op_type == "enum" && matches!(op_name, "eq" | "ne") {
+            if let Some(enum_name) = parts.get(2) {
+                return self.generate_enum_compare(op_name, dst, lhs, rhs, enum_name);
+            }
+        }
+
         // String concatenation for pointers
         if op_name == "add" && lhs_val.is_pointer_value() && rhs_val.is_pointer_value() {
             return self.generate_instr(&crate::mir::MirInstr::StringConcat {
@@ -74,6 +87,7 @@ impl<'ctx> CodeGen<'ctx> {
                 );
                 return Some(self.context.i32_type().const_int(0, false).into());
             };
+            let result = self.bool_value(result);
 
             self.temp_values.insert(dst.to_string(), result.into());
             if let Some(sym) = self.symbols.get(dst) {
@@ -107,6 +121,11 @@ impl<'ctx> CodeGen<'ctx> {
                         .build_float_div(lhs_float, rhs_float, "fdiv_tmp")
                         .unwrap()
                         .into(),
+                    "mod" => self
+                        .builder
+                        .build_float_rem(lhs_float, rhs_float, "fmod_tmp")
+                        .unwrap()
+                        .into(),
                     "eq" => self
                         .builder
                         .build_float_compare(FloatPredicate::OEQ, lhs_float, rhs_float, "feq_tmp")
@@ -158,6 +177,11 @@ impl<'ctx> CodeGen<'ctx> {
                 let lhs_int = lhs_val.into_int_value();
                 let rhs_int = rhs_val.into_int_value();
                 match op_name {
+                    "add" | "sub" | "mul"
+                        if self.overflow_checks && lhs_int.get_type().get_bit_width() > 1 =>
+                    {
+                        self.build_checked_int_op(op_name, lhs_int, rhs_int).into()
+                    }
                     "add" => self
                         .builder
                         .build_int_add(lhs_int, rhs_int, "add_tmp")
@@ -173,16 +197,20 @@ impl<'ctx> CodeGen<'ctx> {
                         .build_int_mul(lhs_int, rhs_int, "mul_tmp")
                         .unwrap()
                         .into(),
-                    "div" => self
-                        .builder
-                        .build_int_signed_div(lhs_int, rhs_int, "div_tmp")
-                        .unwrap()
-                        .into(),
-                    "mod" => self
-                        .builder
-                        .build_int_signed_rem(lhs_int, rhs_int, "mod_tmp")
-                        .unwrap()
-                        .into(),
+                    "div" => {
+                        let rhs_int = self.build_division_check(lhs_int, rhs_int);
+                        self.builder
+                            .build_int_signed_div(lhs_int, rhs_int, "div_tmp")
+                            .unwrap()
+                            .into()
+                    }
+                    "mod" => {
+                        let rhs_int = self.build_division_check(lhs_int, rhs_int);
+                        self.builder
+                            .build_int_signed_rem(lhs_int, rhs_int, "mod_tmp")
+                            .unwrap()
+                            .into()
+                    }
                     "eq" => self
                         .builder
                         .build_int_compare(IntPredicate::EQ, lhs_int, rhs_int, "eq_tmp")
@@ -241,10 +269,102 @@ impl<'ctx> CodeGen<'ctx> {
             }
         };
 
-        self.temp_values.insert(dst.to_string(), res.into());
+        // Comparisons yield an i1, held as a Bool like any other
+        let res = match res {
+            BasicValueEnum::IntValue(flag) if flag.get_type().get_bit_width() == 1 => {
+                self.bool_value(flag).into()
+            }
+            res => res,
+        };
+
+        self.temp_values.insert(dst.to_string(), res);
         if let Some(sym) = self.symbols.get(dst) {
             self.builder.build_store(sym.ptr, res).unwrap();
         }
-        Some(res.into())
+        Some(res)
+    }
+
+    /// Panics with "division by zero" when `divisor` is 0, before an Int `/`
+    /// or `%` would trap, and returns the divisor to divide by. The minimum
+    /// value divided by -1 overflows, which traps too: with overflow checks it
+    /// panics with "integer overflow", and otherwise divides by 1 instead, the
+    /// same as wrapping. The builder continues in a new block.
+    fn build_division_check(
+        &mut self,
+        dividend: IntValue<'ctx>,
+        divisor: IntValue<'ctx>,
+    ) -> IntValue<'ctx> {
+        let int_type = divisor.get_type();
+        let nonzero = self
+            .builder
+            .build_int_compare(IntPredicate::NE, divisor, int_type.const_zero(), "nonzero")
+            .unwrap();
+        self.build_panic_unless(nonzero, "division by zero");
+
+        let min = int_type.const_int(1 << (int_type.get_bit_width() - 1), false);
+        let is_min = self
+            .builder
+            .build_int_compare(IntPredicate::EQ, dividend, min, "is_min")
+            .unwrap();
+        let is_minus_one = self
+            .builder
+            .build_int_compare(
+                IntPredicate::EQ,
+                divisor,
+                int_type.const_all_ones(),
+                "is_minus_one",
+            )
+            .unwrap();
+        let overflows = self
+            .builder
+            .build_and(is_min, is_minus_one, "div_overflows")
+            .unwrap();
+        if self.overflow_checks {
+            let fits = self.builder.build_not(overflows, "div_fits").unwrap();
+            self.build_panic_unless(fits, "integer overflow");
+            return divisor;
+        }
+        self.builder
+            .build_select(overflows, int_type.const_int(1, false), divisor, "divisor")
+            .unwrap()
+            .into_int_value()
+    }
+
+    /// `lhs op rhs` for `op` "add", "sub" or "mul" through the matching
+    /// `llvm.s<op>.with.overflow` intrinsic, panicking with "integer overflow"
+    /// when the result doesn't fit. The builder continues in a new block.
+    fn build_checked_int_op(
+        &mut self,
+        op_name: &str,
+        lhs: IntValue<'ctx>,
+        rhs: IntValue<'ctx>,
+    ) -> IntValue<'ctx> {
+        let intrinsic = Intrinsic::find(&format!("llvm.s{}.with.overflow", op_name))
+            .expect("overflow intrinsic exists");
+        let checked_fn = intrinsic
+            .get_declaration(&self.module, &[lhs.get_type().into()])
+            .expect("overflow intrinsic takes an int type");
+        let pair = self
+            .builder
+            .build_call(checked_fn, &[lhs.into(), rhs.into()], "checked")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_struct_value();
+        let result = self
+            .builder
+            .build_extract_value(pair, 0, "checked_result")
+            .unwrap()
+            .into_int_value();
+        let overflowed = self
+            .builder
+            .build_extract_value(pair, 1, "overflowed")
+            .unwrap()
+            .into_int_value();
+
+        let no_overflow = self.builder.build_not(overflowed, "no_overflow").unwrap();
+        self.build_panic_unless(no_overflow, "integer overflow");
+        result
     }
 }
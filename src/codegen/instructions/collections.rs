@@ -1,5 +1,4 @@
 use crate::codegen::core::{CodeGen, Symbol};
-use inkwell::types::BasicType;
 impl<'ctx> CodeGen<'ctx> {
     pub fn generate_load_array_element(
         &mut self,
@@ -13,33 +12,10 @@ impl<'ctx> CodeGen<'ctx> {
         let is_string = self.array_contains_strings(array);
         let elem_type = self.get_array_element_type(array);
 
-        // Get array metadata to know the array type
-        let array_len = if let Some(metadata) = self.array_metadata.get(array) {
-            metadata.length as u32
-        } else {
-            0
-        };
-
-        let array_type = elem_type.array_type(array_len);
-
-        // Cast data pointer to array pointer
-        let typed_array_ptr = self
-            .builder
-            .build_pointer_cast(
-                array_ptr,
-                self.context.ptr_type(inkwell::AddressSpace::default()),
-                "array_ptr_typed",
-            )
-            .unwrap();
-
-        // GEP to get element pointer
+        // Element `index` of the data, whatever the array's length
         let elem_ptr = unsafe {
-            self.builder.build_gep(
-                array_type,
-                typed_array_ptr,
-                &[self.context.i32_type().const_zero(), index_val],
-                "elem_ptr",
-            )
+            self.builder
+                .build_gep(elem_type, array_ptr, &[index_val], "elem_ptr")
         }
         .unwrap();
 
@@ -51,21 +27,7 @@ impl<'ctx> CodeGen<'ctx> {
 
         // If it's a heap-allocated string, increment RC
         if is_string && elem_val.is_pointer_value() {
-            let str_ptr = elem_val.into_pointer_value();
-            let rc_header = unsafe {
-                self.builder.build_in_bounds_gep(
-                    self.context.i8_type(),
-                    str_ptr,
-                    &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                    "rc_header",
-                )
-            }
-            .unwrap();
-
-            let incref = self.incref_fn.unwrap();
-            self.builder
-                .build_call(incref, &[rc_header.into()], "")
-                .unwrap();
+            self.incref_value(elem_val.into_pointer_value());
 
             // Mark this variable as heap string for cleanup
             self.heap_strings.insert(dest.to_string());
@@ -105,33 +67,10 @@ impl<'ctx> CodeGen<'ctx> {
         let (key_type, val_type) = self.get_map_types(map);
         let pair_type = self.context.struct_type(&[key_type, val_type], false);
 
-        // Get map length for array type
-        let map_len = if let Some(metadata) = self.map_metadata.get(map) {
-            metadata.length as u32
-        } else {
-            0
-        };
-
-        let map_array_type = pair_type.array_type(map_len);
-
-        // Cast to typed map pointer
-        let typed_map_ptr = self
-            .builder
-            .build_pointer_cast(
-                map_ptr,
-                self.context.ptr_type(inkwell::AddressSpace::default()),
-                "map_ptr_typed",
-            )
-            .unwrap();
-
-        // GEP to get pair pointer
+        // Pair `index` of the data, whatever the map's length
         let pair_ptr = unsafe {
-            self.builder.build_gep(
-                map_array_type,
-                typed_map_ptr,
-                &[self.context.i32_type().const_zero(), index_val],
-                "pair_ptr",
-            )
+            self.builder
+                .build_gep(pair_type, map_ptr, &[index_val], "pair_ptr")
         }
         .unwrap();
 
@@ -151,39 +90,13 @@ impl<'ctx> CodeGen<'ctx> {
 
         // Handle RC for key if string
         if key_is_string && key_val.is_pointer_value() {
-            let str_ptr = key_val.into_pointer_value();
-            let rc_header = unsafe {
-                self.builder.build_in_bounds_gep(
-                    self.context.i8_type(),
-                    str_ptr,
-                    &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                    "rc_header",
-                )
-            }
-            .unwrap();
-            let incref = self.incref_fn.unwrap();
-            self.builder
-                .build_call(incref, &[rc_header.into()], "")
-                .unwrap();
+            self.incref_value(key_val.into_pointer_value());
             self.heap_strings.insert(key_dest.to_string());
         }
 
         // Handle RC for value if string
         if val_is_string && val_val.is_pointer_value() {
-            let str_ptr = val_val.into_pointer_value();
-            let rc_header = unsafe {
-                self.builder.build_in_bounds_gep(
-                    self.context.i8_type(),
-                    str_ptr,
-                    &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                    "rc_header",
-                )
-            }
-            .unwrap();
-            let incref = self.incref_fn.unwrap();
-            self.builder
-                .build_call(incref, &[rc_header.into()], "")
-                .unwrap();
+            self.incref_value(val_val.into_pointer_value());
             self.heap_strings.insert(val_dest.to_string());
         }
 
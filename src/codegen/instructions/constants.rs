@@ -1,6 +1,8 @@
 use crate::codegen::core::CodeGen;
+use crate::codegen::memory::rc_runtime::STATIC_RC;
 use crate::mir::MirInstr;
-use inkwell::values::BasicValueEnum;
+use inkwell::module::Linkage;
+use inkwell::values::{BasicValueEnum, PointerValue};
 use inkwell::AddressSpace;
 
 impl<'ctx> CodeGen<'ctx> {
@@ -14,6 +16,15 @@ impl<'ctx> CodeGen<'ctx> {
         Some(val.into())
     }
 
+    pub fn generate_const_int64(&mut self, name: &str, value: i64) -> Option<BasicValueEnum<'ctx>> {
+        let val = self.context.i64_type().const_int(value as u64, true);
+        if let Some(sym) = self.symbols.get(name) {
+            self.builder.build_store(sym.ptr, val).unwrap();
+        }
+        self.temp_values.insert(name.to_string(), val.into());
+        Some(val.into())
+    }
+
     pub fn generate_const_float(&mut self, name: &str, value: f64) -> Option<BasicValueEnum<'ctx>> {
         let val = self.context.f64_type().const_float(value);
         if let Some(sym) = self.symbols.get(name) {
@@ -39,20 +50,54 @@ impl<'ctx> CodeGen<'ctx> {
         name: &str,
         value: &str,
     ) -> Option<BasicValueEnum<'ctx>> {
-        // String constants should be module-level static constants, not heap allocations.
-        // This avoids memory leaks and unnecessary malloc/free overhead.
-        // The string data is stored in the read-only data section of the binary.
-
-        let str_global = self
-            .builder
-            .build_global_string_ptr(value, &format!("str_const_{}", name))
-            .expect("Failed to create string constant");
-
-        let data_ptr = str_global.as_pointer_value();
+        // Literals with the same text share one static copy
+        let data_ptr = self.intern_string(value);
 
         // Store in temp_values so it can be resolved by name
         self.temp_values.insert(name.to_string(), data_ptr.into());
+        // Keep the literal text so comparisons against it can use its known length
+        self.temp_strings
+            .insert(name.to_string(), value.to_string());
 
         Some(data_ptr.into())
     }
+
+    /// The one static copy of the string literal `value` in the module: the
+    /// text after an 8-byte header like a heap string's, with a `STATIC_RC`
+    /// count so RC never writes to or frees it. Every use of the same literal
+    /// shares it.
+    pub(crate) fn intern_string(&mut self, value: &str) -> PointerValue<'ctx> {
+        if let Some(ptr) = self.interned_strings.get(value) {
+            return *ptr;
+        }
+
+        let i32_type = self.context.i32_type();
+        let text = self.context.const_string(value.as_bytes(), true);
+        let header = [
+            i32_type.const_int(STATIC_RC, false).into(),
+            i32_type.const_int(value.len() as u64, false).into(),
+        ];
+        let init = self
+            .context
+            .const_struct(&[header[0], header[1], text.into()], false);
+        let global = self.module.add_global(
+            init.get_type(),
+            None,
+            &format!("str.{}", self.interned_strings.len()),
+        );
+        global.set_initializer(&init);
+        global.set_constant(true);
+        global.set_linkage(Linkage::Private);
+        global.set_unnamed_addr(true);
+        global.set_alignment(8);
+
+        let data_ptr = unsafe {
+            global.as_pointer_value().const_in_bounds_gep(
+                self.context.i8_type(),
+                &[self.context.i64_type().const_int(8, false)],
+            )
+        };
+        self.interned_strings.insert(value.to_string(), data_ptr);
+        data_ptr
+    }
 }
@@ -1,5 +1,12 @@
 use crate::codegen::core::CodeGen;
 use crate::mir::MirInstr;
+use crate::mir::MirType;
+use inkwell::types::BasicMetadataTypeEnum;
+use inkwell::values::{
+    BasicMetadataValueEnum, BasicValueEnum, FunctionValue, IntValue, PointerValue,
+};
+use inkwell::AddressSpace;
+use inkwell::IntPredicate;
 impl<'ctx> CodeGen<'ctx> {
     pub fn generate_call(
         &mut self,
@@ -7,38 +14,100 @@ impl<'ctx> CodeGen<'ctx> {
         func: &str,
         args: &[String],
     ) -> Option<inkwell::values::BasicValueEnum<'ctx>> {
-        let callee = self.module.get_function(func).expect(&format!(
+        if let Some((builtin, tag)) = Self::builtin_conversion(func) {
+            return self.generate_conversion(dest, builtin, tag, args);
+        }
+
+        let symbol = Self::llvm_function_name(func);
+        let callee = self.module.get_function(&symbol).expect(&format!(
             "Function '{}' not found. Make sure it's declared before calling.",
             func
         ));
 
-        let arg_values: Vec<inkwell::values::BasicMetadataValueEnum<'ctx>> = args
+        let mut arg_values: Vec<inkwell::values::BasicMetadataValueEnum<'ctx>> = args
             .iter()
             .map(|arg| self.resolve_value(arg).into())
             .collect();
 
+        // Intrinsics take `i1` flags where a `Bool` is an `i32`
+        let param_types = callee.get_type().get_param_types();
+        for (value, param_type) in arg_values.iter_mut().zip(param_types) {
+            if let (BasicMetadataValueEnum::IntValue(int), BasicMetadataTypeEnum::IntType(ty)) =
+                (*value, param_type)
+            {
+                if ty.get_bit_width() == 1 && int.get_type().get_bit_width() != 1 {
+                    *value = self.build_condition(int).into();
+                }
+            }
+        }
+
+        // Tuple results come back through a slot in this frame
+        let sret = self
+            .sret_return_type(func)
+            .map(|struct_type| (self.sret_slot(struct_type), struct_type));
+        if let Some((slot, _)) = sret {
+            arg_values.insert(0, slot.into());
+        }
+
         let call_result = self
             .builder
             .build_call(callee, &arg_values, "call_result")
             .unwrap();
+        let result = match sret {
+            Some((slot, struct_type)) => {
+                self.add_sret_call_attribute(call_result, struct_type);
+                Some(
+                    self.builder
+                        .build_load(struct_type, slot, "call_result")
+                        .unwrap(),
+                )
+            }
+            // and return them where a `Bool` is expected
+            None => call_result
+                .try_as_basic_value()
+                .left()
+                .map(|value| match value {
+                    BasicValueEnum::IntValue(flag) if flag.get_type().get_bit_width() == 1 => {
+                        self.bool_value(flag).into()
+                    }
+                    _ => value,
+                }),
+        };
 
-        if let Some(result) = call_result.try_as_basic_value().left() {
+        if let Some(result) = result {
             if !dest.is_empty() {
                 let dest_name = &dest[0];
                 self.temp_values.insert(dest_name.clone(), result);
 
+                // Struct results come with their own reference
+                let llvm_name = Self::llvm_function_name(func);
+                if let Some(struct_name) = self
+                    .function_return_types
+                    .get(&llvm_name)
+                    .and_then(|ty| ty.struct_name())
+                {
+                    self.heap_structs
+                        .insert(dest_name.clone(), struct_name.to_string());
+                }
+
                 // Check if this function is known to return heap-allocated values
-                if self.functions_returning_heap.contains(func) {
+                if self.functions_returning_heap.contains(&llvm_name)
+                    && !self.heap_structs.contains_key(dest_name)
+                {
                     if result.is_pointer_value() {
                         // Mark the result as heap-allocated based on return type
-                        if let Some(return_type_str) = self.function_return_types.get(func) {
-                            if return_type_str.contains("Str") || return_type_str.contains("String")
-                            {
-                                self.heap_strings.insert(dest_name.clone());
-                            } else if return_type_str.contains("Array") {
-                                self.heap_arrays.insert(dest_name.clone());
-                            } else if return_type_str.contains("Map") {
-                                self.heap_maps.insert(dest_name.clone());
+                        if let Some(return_type) = self.function_return_types.get(&llvm_name) {
+                            match return_type.optional_payload() {
+                                MirType::Str => {
+                                    self.heap_strings.insert(dest_name.clone());
+                                }
+                                MirType::Array(_) => {
+                                    self.heap_arrays.insert(dest_name.clone());
+                                }
+                                MirType::Map(_, _) => {
+                                    self.heap_maps.insert(dest_name.clone());
+                                }
+                                _ => {}
                             }
                         }
                     }
@@ -51,7 +120,9 @@ impl<'ctx> CodeGen<'ctx> {
         None
     }
 
-    pub fn generate_print(&mut self, values: &[String]) {
+    /// Prints `values` separated by spaces, then a newline. Arrays and maps
+    /// given a type in `types` go through their runtime printer.
+    pub fn generate_print(&mut self, values: &[String], types: &[Option<MirType>]) {
         let printf_fn = self.get_or_declare_printf();
 
         for (idx, value) in values.iter().enumerate() {
@@ -62,28 +133,26 @@ impl<'ctx> CodeGen<'ctx> {
 
             // Check if this value is an array or map by looking at metadata
             // But NEVER treat loop iteration variables as arrays/maps
-            let is_array = !is_loop_var
-                && (self.array_metadata.contains_key(value) || self.heap_arrays.contains(value));
-            let is_map = !is_loop_var
-                && (self.map_metadata.contains_key(value) || self.heap_maps.contains(value));
+            let is_array = (!is_loop_var
+                && (self.array_metadata.contains_key(value) || self.heap_arrays.contains(value)))
+                || self.runtime_array_shapes.contains_key(value);
+            let is_map = (!is_loop_var
+                && (self.map_metadata.contains_key(value) || self.heap_maps.contains(value)))
+                || self.runtime_map_shapes.contains_key(value);
+
+            let formatted = match types.get(idx) {
+                Some(Some(ty)) => self.print_formatted(value, ty),
+                _ => false,
+            };
 
-            if is_array {
-                self.print_array(value);
-                if idx < values.len() - 1 {
-                    let space_fmt = self
-                        .builder
-                        .build_global_string_ptr(" ", "space_fmt")
-                        .unwrap();
-                    self.builder
-                        .build_call(
-                            printf_fn,
-                            &[space_fmt.as_pointer_value().into()],
-                            "space_call",
-                        )
-                        .unwrap();
+            if formatted || is_array || is_map {
+                if formatted {
+                    // Already printed by the runtime printer
+                } else if is_array {
+                    self.print_array(value);
+                } else {
+                    self.print_map(value);
                 }
-            } else if is_map {
-                self.print_map(value);
                 if idx < values.len() - 1 {
                     let space_fmt = self
                         .builder
@@ -150,6 +219,26 @@ impl<'ctx> CodeGen<'ctx> {
                     self.builder
                         .build_call(printf_fn, &[selected_str.into()], "print_bool")
                         .unwrap();
+                } else if val.is_int_value()
+                    && val.into_int_value().get_type().get_bit_width() == 64
+                {
+                    let format_str = if idx < values.len() - 1 {
+                        "%lld "
+                    } else {
+                        "%lld"
+                    };
+                    let format_global = self
+                        .builder
+                        .build_global_string_ptr(format_str, "print_fmt_i64")
+                        .unwrap();
+
+                    self.builder
+                        .build_call(
+                            printf_fn,
+                            &[format_global.as_pointer_value().into(), val.into()],
+                            "print_i64_call",
+                        )
+                        .unwrap();
                 } else if val.is_int_value() {
                     let format_str = if idx < values.len() - 1 { "%d " } else { "%d" };
                     let format_global = self
@@ -209,100 +298,293 @@ impl<'ctx> CodeGen<'ctx> {
             .unwrap();
     }
 
+    /// `len` of an array or map, read from its heap header.
     pub fn generate_array_len(
         &mut self,
         name: &str,
         array: &str,
     ) -> Option<inkwell::values::BasicValueEnum<'ctx>> {
-        let array_name = array;
-
-        if let Some(metadata) = self.array_metadata.get(array_name) {
-            let len_val = self
-                .context
-                .i32_type()
-                .const_int(metadata.length as u64, false);
-            self.temp_values.insert(name.to_string(), len_val.into());
-            if let Some(sym) = self.symbols.get(name) {
-                self.builder.build_store(sym.ptr, len_val).unwrap();
-            }
-            return Some(len_val.into());
+        let len_val = if self.array_metadata.contains_key(array) || self.map_shape(array).is_none()
+        {
+            self.get_array_length(array)
+        } else {
+            self.get_map_length(array)
+        };
+        self.temp_values.insert(name.to_string(), len_val.into());
+        if let Some(sym) = self.symbols.get(name) {
+            self.builder.build_store(sym.ptr, len_val).unwrap();
         }
+        Some(len_val.into())
+    }
 
-        if let Some(metadata) = self.map_metadata.get(array_name) {
-            let len_val = self
-                .context
-                .i32_type()
-                .const_int(metadata.length as u64, false);
-            self.temp_values.insert(name.to_string(), len_val.into());
-            if let Some(sym) = self.symbols.get(name) {
-                self.builder.build_store(sym.ptr, len_val).unwrap();
-            }
-            return Some(len_val.into());
-        }
+    /// Ends the current block with `panic(message)`.
+    pub fn generate_panic(&mut self, message: &str) {
+        let message = self.resolve_value(message).into_pointer_value();
+        self.build_panic(message);
+    }
 
-        let array_ptr_opt = if let Some(val) = self.temp_values.get(array_name) {
-            if val.is_pointer_value() {
-                Some(val.into_pointer_value())
-            } else {
-                None
-            }
-        } else if let Some(sym) = self.symbols.get(array_name) {
-            if let Ok(loaded) =
-                self.builder
-                    .build_load(sym.ty, sym.ptr, &format!("{}_ptr", array_name))
-            {
-                if loaded.is_pointer_value() {
-                    Some(loaded.into_pointer_value())
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        } else {
-            None
+    /// Ends the current block with a call to `doo_panic`, which does not
+    /// return, naming the statement being generated as where it happened.
+    pub(crate) fn build_panic(&mut self, message: PointerValue<'ctx>) {
+        let (file, line) = self.panic_location();
+        let panic_fn = self.get_or_create_panic_fn();
+        self.builder
+            .build_call(panic_fn, &[message.into(), file.into(), line.into()], "")
+            .unwrap();
+        self.builder.build_unreachable().unwrap();
+    }
+
+    /// Panics with `message` unless `ok` holds, then carries on in a new block.
+    pub(crate) fn build_panic_unless(&mut self, ok: IntValue<'ctx>, message: &str) {
+        let function = self
+            .builder
+            .get_insert_block()
+            .and_then(|block| block.get_parent())
+            .unwrap();
+        let panic_block = self.context.append_basic_block(function, "panic");
+        let ok_block = self.context.append_basic_block(function, "checked");
+        self.builder
+            .build_conditional_branch(ok, ok_block, panic_block)
+            .unwrap();
+
+        self.builder.position_at_end(panic_block);
+        let message = self.intern_string(message);
+        self.build_panic(message);
+
+        self.builder.position_at_end(ok_block);
+    }
+
+    /// Panics unless `index` is within an array of `length` elements.
+    pub(crate) fn build_bounds_check(&mut self, index: IntValue<'ctx>, length: IntValue<'ctx>) {
+        self.build_index_check(index, length, IntPredicate::ULT);
+    }
+
+    /// Panics unless `index` is where an element can be inserted into an
+    /// array of `length` elements: within it, or at its end.
+    pub(crate) fn build_insert_bounds_check(
+        &mut self,
+        index: IntValue<'ctx>,
+        length: IntValue<'ctx>,
+    ) {
+        self.build_index_check(index, length, IntPredicate::ULE);
+    }
+
+    /// Panics with the bounds message unless `index` compares to `length`
+    /// by `predicate`.
+    fn build_index_check(
+        &mut self,
+        index: IntValue<'ctx>,
+        length: IntValue<'ctx>,
+        predicate: IntPredicate,
+    ) {
+        let i64_type = self.context.i64_type();
+        let index = self
+            .builder
+            .build_int_s_extend_or_bit_cast(index, i64_type, "index")
+            .unwrap();
+        let length = self
+            .builder
+            .build_int_z_extend_or_bit_cast(length, i64_type, "length")
+            .unwrap();
+        // Unsigned, a negative index is past any length
+        let in_bounds = self
+            .builder
+            .build_int_compare(predicate, index, length, "in_bounds")
+            .unwrap();
+
+        let function = self
+            .builder
+            .get_insert_block()
+            .and_then(|block| block.get_parent())
+            .unwrap();
+        let panic_block = self.context.append_basic_block(function, "out_of_bounds");
+        let ok_block = self.context.append_basic_block(function, "in_bounds");
+        self.builder
+            .build_conditional_branch(in_bounds, ok_block, panic_block)
+            .unwrap();
+
+        self.builder.position_at_end(panic_block);
+        let (file, line) = self.panic_location();
+        let bounds_fn = self.get_or_create_bounds_panic_fn();
+        self.builder
+            .build_call(
+                bounds_fn,
+                &[index.into(), length.into(), file.into(), line.into()],
+                "",
+            )
+            .unwrap();
+        self.builder.build_unreachable().unwrap();
+
+        self.builder.position_at_end(ok_block);
+    }
+
+    /// The file and line `doo_panic` reports: null and 0 where the statement
+    /// being generated has no line in the program's file.
+    fn panic_location(&mut self) -> (PointerValue<'ctx>, IntValue<'ctx>) {
+        let line = self.statement_line;
+        let file = match &self.source_file {
+            Some(file) if line > 0 => self.intern_string(&file.clone()),
+            _ => self.context.ptr_type(AddressSpace::default()).const_null(),
         };
+        (file, self.context.i32_type().const_int(line as u64, false))
+    }
 
-        if let Some(array_ptr) = array_ptr_opt {
-            let len_ptr_result = unsafe {
-                self.builder.build_in_bounds_gep(
-                    self.context.i8_type(),
-                    array_ptr,
-                    &[self.context.i32_type().const_int((-4_i32) as u64, true)],
-                    &format!("{}_len_ptr", array_name),
-                )
-            };
+    /// Emits (once) `void doo_panic(ptr message, ptr file, i32 line)`: writes
+    /// "panic: <message>" to stderr, followed by "  at <file>:<line>" when
+    /// `file` isn't null, and exits with status 101. Buffered stdout is
+    /// flushed by exit.
+    pub(crate) fn get_or_create_panic_fn(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("doo_panic") {
+            return func;
+        }
 
-            if let Ok(len_ptr) = len_ptr_result {
-                let len_ptr_cast_result = self.builder.build_pointer_cast(
-                    len_ptr,
-                    self.context.ptr_type(inkwell::AddressSpace::default()),
-                    &format!("{}_len_cast", array_name),
-                );
-
-                if let Ok(len_ptr_cast) = len_ptr_cast_result {
-                    if let Ok(runtime_len) = self.builder.build_load(
-                        self.context.i32_type(),
-                        len_ptr_cast,
-                        &format!("{}_runtime_len", array_name),
-                    ) {
-                        let len_val = runtime_len.into_int_value();
-                        self.temp_values.insert(name.to_string(), len_val.into());
-                        if let Some(sym) = self.symbols.get(name) {
-                            self.builder.build_store(sym.ptr, len_val).unwrap();
-                        }
-                        return Some(len_val.into());
-                    }
-                }
-            }
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let i32_type = self.context.i32_type();
+        let function = self.module.add_function(
+            "doo_panic",
+            self.context
+                .void_type()
+                .fn_type(&[ptr_type.into(), ptr_type.into(), i32_type.into()], false),
+            None,
+        );
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        let located = self.context.append_basic_block(function, "located");
+        let exit_block = self.context.append_basic_block(function, "exit");
+        self.builder.position_at_end(entry);
+
+        // int dprintf(int fd, const char *fmt, ...) and void exit(int)
+        let dprintf = self.module.get_function("dprintf").unwrap_or_else(|| {
+            self.module.add_function(
+                "dprintf",
+                i32_type.fn_type(&[i32_type.into(), ptr_type.into()], true),
+                None,
+            )
+        });
+        let exit = self.module.get_function("exit").unwrap_or_else(|| {
+            self.module.add_function(
+                "exit",
+                self.context.void_type().fn_type(&[i32_type.into()], false),
+                None,
+            )
+        });
+
+        let message = function.get_nth_param(0).unwrap();
+        let file = function.get_nth_param(1).unwrap().into_pointer_value();
+        let line = function.get_nth_param(2).unwrap();
+        let stderr = i32_type.const_int(2, false);
+
+        let fmt = self
+            .builder
+            .build_global_string_ptr("panic: %s\n", "panic_fmt")
+            .unwrap();
+        self.builder
+            .build_call(
+                dprintf,
+                &[stderr.into(), fmt.as_pointer_value().into(), message.into()],
+                "",
+            )
+            .unwrap();
+        let has_file = self.builder.build_is_not_null(file, "has_file").unwrap();
+        self.builder
+            .build_conditional_branch(has_file, located, exit_block)
+            .unwrap();
+
+        self.builder.position_at_end(located);
+        let fmt = self
+            .builder
+            .build_global_string_ptr("  at %s:%d\n", "panic_location_fmt")
+            .unwrap();
+        self.builder
+            .build_call(
+                dprintf,
+                &[
+                    stderr.into(),
+                    fmt.as_pointer_value().into(),
+                    file.into(),
+                    line.into(),
+                ],
+                "",
+            )
+            .unwrap();
+        self.builder.build_unconditional_branch(exit_block).unwrap();
+
+        self.builder.position_at_end(exit_block);
+        self.builder
+            .build_call(exit, &[i32_type.const_int(101, false).into()], "")
+            .unwrap();
+        self.builder.build_unreachable().unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
         }
+        function
+    }
 
-        let len_val = self.context.i32_type().const_int(0, false);
-        self.temp_values.insert(name.to_string(), len_val.into());
-        if let Some(sym) = self.symbols.get(name) {
-            self.builder.build_store(sym.ptr, len_val).unwrap();
+    /// Emits (once) `void __panic_bounds(i64 index, i64 length, ptr file,
+    /// i32 line)`: `doo_panic` with "index <index> is out of bounds for
+    /// length <length>".
+    fn get_or_create_bounds_panic_fn(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("__panic_bounds") {
+            return func;
         }
-        Some(len_val.into())
+
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let i32_type = self.context.i32_type();
+        let i64_type = self.context.i64_type();
+        let function = self.module.add_function(
+            "__panic_bounds",
+            self.context.void_type().fn_type(
+                &[
+                    i64_type.into(),
+                    i64_type.into(),
+                    ptr_type.into(),
+                    i32_type.into(),
+                ],
+                false,
+            ),
+            None,
+        );
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+
+        let buffer_type = self.context.i8_type().array_type(96);
+        let buffer = self.builder.build_alloca(buffer_type, "message").unwrap();
+        let fmt = self
+            .builder
+            .build_global_string_ptr("index %lld is out of bounds for length %lld", "bounds_fmt")
+            .unwrap();
+        self.builder
+            .build_call(
+                self.get_or_declare_snprintf(),
+                &[
+                    buffer.into(),
+                    self.size_type().const_int(96, false).into(),
+                    fmt.as_pointer_value().into(),
+                    function.get_nth_param(0).unwrap().into(),
+                    function.get_nth_param(1).unwrap().into(),
+                ],
+                "",
+            )
+            .unwrap();
+        self.builder
+            .build_call(
+                self.get_or_create_panic_fn(),
+                &[
+                    buffer.into(),
+                    function.get_nth_param(2).unwrap().into(),
+                    function.get_nth_param(3).unwrap().into(),
+                ],
+                "",
+            )
+            .unwrap();
+        self.builder.build_unreachable().unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
     }
 
     /// Check if a variable represents a boolean value (0 or 1)
@@ -0,0 +1,614 @@
+use crate::analyzer::builtins::BUILTIN_FUNCTIONS;
+use crate::codegen::core::CodeGen;
+use crate::mir::MirType;
+use inkwell::types::BasicTypeEnum;
+use inkwell::values::{BasicValueEnum, FunctionValue};
+use inkwell::AddressSpace;
+
+/// Conversion builtins: `toStr(x)`, `toInt(s)`, `toFloat(s)` and `toInt64(x)`, plus
+/// `format(x, digits)`, `parseInt(s)` and `parseFloat(s)`.
+/// MIR tags each call with its first argument's type ("toStr:float"); untagged
+/// calls fall back to the LLVM type of the argument.
+/// Strings produced by `toStr` and `format` are fresh heap strings with RC = 1.
+/// `toInt`/`toFloat` parse like C's atoi/strtod: text that is not a number yields 0.
+/// `parseInt`/`parseFloat` parse with strtoll/strtod instead and return an
+/// optional that is absent unless the whole text is the number.
+/// The C string builtins (`cStr`, `fromCStr`, ...) are generated in `c_strings`.
+impl<'ctx> CodeGen<'ctx> {
+    /// Splits a call target into the builtin name and its argument type tag,
+    /// or returns None for regular functions.
+    pub fn builtin_conversion(func: &str) -> Option<(&str, Option<&str>)> {
+        let (name, tag) = match func.split_once(':') {
+            Some((name, tag)) => (name, Some(tag)),
+            None => (func, None),
+        };
+        if BUILTIN_FUNCTIONS.contains(&name) {
+            Some((name, tag))
+        } else {
+            None
+        }
+    }
+
+    /// LLVM type returned by a conversion builtin.
+    pub fn builtin_return_type(&self, name: &str) -> BasicTypeEnum<'ctx> {
+        match name {
+            "toStr" | "format" | "cStr" | "toCStr" | "fromCStr" | "takeCStr" => {
+                self.context.ptr_type(AddressSpace::default()).into()
+            }
+            "toFloat" => self.context.f64_type().into(),
+            "toInt64" => self.context.i64_type().into(),
+            "parseInt" => self.optional_llvm_type(&MirType::Optional(Box::new(MirType::Int))),
+            "parseFloat" => self.optional_llvm_type(&MirType::Optional(Box::new(MirType::Float))),
+            _ => self.context.i32_type().into(),
+        }
+    }
+
+    pub fn generate_conversion(
+        &mut self,
+        dest: &[String],
+        name: &str,
+        tag: Option<&str>,
+        args: &[String],
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let i32_type = self.context.i32_type();
+        let val = self.resolve_value(&args[0]);
+        let tag = tag.unwrap_or(if val.is_float_value() {
+            "float"
+        } else if val.is_pointer_value() {
+            "string"
+        } else if val.into_int_value().get_type().get_bit_width() == 64 {
+            "int64"
+        } else {
+            "int"
+        });
+
+        let result: BasicValueEnum<'ctx> = match (name, tag) {
+            ("toStr", _) => {
+                let (helper, param_ty, fmt): (&str, BasicTypeEnum<'ctx>, &str) = match tag {
+                    "float" => ("__float_to_str", self.context.f64_type().into(), "%f"),
+                    "int64" => ("__int64_to_str", self.context.i64_type().into(), "%lld"),
+                    "bool" => ("__bool_to_str", i32_type.into(), "%s"),
+                    "string" => (
+                        "__str_to_str",
+                        self.context.ptr_type(AddressSpace::default()).into(),
+                        "%s",
+                    ),
+                    _ => ("__int_to_str", i32_type.into(), "%d"),
+                };
+                let helper_fn = self.get_or_create_to_str_fn(helper, param_ty, fmt, tag == "bool");
+                self.builder
+                    .build_call(helper_fn, &[val.into()], "to_str")
+                    .unwrap()
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap()
+            }
+            ("format", _) => {
+                let digits = self.resolve_value(&args[1]);
+                self.builder
+                    .build_call(
+                        self.get_or_create_format_fn(),
+                        &[val.into(), digits.into()],
+                        "formatted",
+                    )
+                    .unwrap()
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap()
+            }
+            ("parseInt", _) | ("parseFloat", _) => self
+                .builder
+                .build_call(
+                    self.get_or_create_parse_fn(name == "parseFloat"),
+                    &[val.into()],
+                    "parsed",
+                )
+                .unwrap()
+                .try_as_basic_value()
+                .left()
+                .unwrap(),
+            ("cStr", _) | ("toCStr", _) | ("fromCStr", _) | ("takeCStr", _) => {
+                self.generate_c_string(name, val)
+            }
+            ("toInt", "string") => {
+                let atoi_fn = self.get_or_declare_atoi();
+                self.builder
+                    .build_call(atoi_fn, &[val.into()], "to_int")
+                    .unwrap()
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap()
+            }
+            ("toInt", "float") => self
+                .builder
+                .build_float_to_signed_int(val.into_float_value(), i32_type, "to_int")
+                .unwrap()
+                .into(),
+            ("toFloat", "string") => {
+                let strtod_fn = self.get_or_declare_strtod();
+                let end_ptr = self.context.ptr_type(AddressSpace::default()).const_null();
+                self.builder
+                    .build_call(strtod_fn, &[val.into(), end_ptr.into()], "to_float")
+                    .unwrap()
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap()
+            }
+            ("toFloat", "int") | ("toFloat", "bool") => self
+                .builder
+                .build_signed_int_to_float(
+                    val.into_int_value(),
+                    self.context.f64_type(),
+                    "to_float",
+                )
+                .unwrap()
+                .into(),
+            ("toInt64", "int") | ("toInt64", "bool") => self
+                .builder
+                .build_int_s_extend(val.into_int_value(), self.context.i64_type(), "to_int64")
+                .unwrap()
+                .into(),
+            ("toInt64", "float") => self
+                .builder
+                .build_float_to_signed_int(
+                    val.into_float_value(),
+                    self.context.i64_type(),
+                    "to_int64",
+                )
+                .unwrap()
+                .into(),
+            // toInt(Int), toFloat(Float) and toInt64(Int64) are the value itself
+            _ => val,
+        };
+
+        if let Some(dest_name) = dest.first() {
+            self.temp_values.insert(dest_name.clone(), result);
+            if let Some(sym) = self.symbols.get(dest_name) {
+                self.builder.build_store(sym.ptr, result).unwrap();
+            }
+            if name == "toStr" || name == "format" {
+                self.heap_strings.insert(dest_name.clone());
+            }
+        }
+        Some(result)
+    }
+
+    /// Emits (once) `ptr helper(T)` that formats its argument with `fmt` into a
+    /// new heap string: [RC: 4 bytes][padding: 4 bytes][chars...\0].
+    /// Bool helpers format "true"/"false" from an i32 argument.
+    pub(crate) fn get_or_create_to_str_fn(
+        &self,
+        helper: &str,
+        param_ty: BasicTypeEnum<'ctx>,
+        fmt: &str,
+        is_bool: bool,
+    ) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function(helper) {
+            return func;
+        }
+
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let i32_type = self.context.i32_type();
+        let i64_type = self.context.i64_type();
+        let function =
+            self.module
+                .add_function(helper, ptr_type.fn_type(&[param_ty.into()], false), None);
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+
+        let mut value: BasicValueEnum<'ctx> = function.get_nth_param(0).unwrap();
+        if is_bool {
+            let is_true = self
+                .builder
+                .build_int_compare(
+                    inkwell::IntPredicate::NE,
+                    value.into_int_value(),
+                    i32_type.const_zero(),
+                    "is_true",
+                )
+                .unwrap();
+            let true_str = self
+                .builder
+                .build_global_string_ptr("true", "true_str")
+                .unwrap();
+            let false_str = self
+                .builder
+                .build_global_string_ptr("false", "false_str")
+                .unwrap();
+            value = self
+                .builder
+                .build_select(
+                    is_true,
+                    true_str.as_pointer_value(),
+                    false_str.as_pointer_value(),
+                    "bool_str",
+                )
+                .unwrap();
+        }
+        let fmt_ptr = self
+            .builder
+            .build_global_string_ptr(fmt, "to_str_fmt")
+            .unwrap()
+            .as_pointer_value();
+
+        // First pass measures the text, second pass writes it after the header
+        let snprintf_fn = self.get_or_declare_snprintf();
+        let len = self
+            .builder
+            .build_call(
+                snprintf_fn,
+                &[
+                    ptr_type.const_null().into(),
+                    self.size_type().const_zero().into(),
+                    fmt_ptr.into(),
+                    value.into(),
+                ],
+                "str_len",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let len_with_null = self
+            .builder
+            .build_int_add(
+                self.builder
+                    .build_int_z_extend(len, i64_type, "str_len_i64")
+                    .unwrap(),
+                i64_type.const_int(1, false),
+                "len_with_null",
+            )
+            .unwrap();
+        let total_size = self
+            .builder
+            .build_int_add(len_with_null, i64_type.const_int(8, false), "total_size")
+            .unwrap();
+
+        let malloc_fn = self.get_or_declare_malloc();
+        let heap_ptr = self
+            .builder
+            .build_call(malloc_fn, &[self.to_size_t(total_size).into()], "str_heap")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value();
+        self.builder
+            .build_store(heap_ptr, i32_type.const_int(1, false))
+            .unwrap();
+        let data_ptr = unsafe {
+            self.builder.build_gep(
+                self.context.i8_type(),
+                heap_ptr,
+                &[i32_type.const_int(8, false)],
+                "data_ptr",
+            )
+        }
+        .unwrap();
+        self.builder
+            .build_call(
+                snprintf_fn,
+                &[
+                    data_ptr.into(),
+                    self.to_size_t(len_with_null).into(),
+                    fmt_ptr.into(),
+                    value.into(),
+                ],
+                "",
+            )
+            .unwrap();
+        self.builder.build_return(Some(&data_ptr)).unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// Emits (once) `ptr __format_float(double x, i32 digits)`: `x` with
+    /// `digits` decimals (none when negative) in a new heap string, like
+    /// printf's "%.*f".
+    fn get_or_create_format_fn(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("__format_float") {
+            return func;
+        }
+
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let i32_type = self.context.i32_type();
+        let i64_type = self.context.i64_type();
+        let function = self.module.add_function(
+            "__format_float",
+            ptr_type.fn_type(&[self.context.f64_type().into(), i32_type.into()], false),
+            None,
+        );
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+
+        let value = function.get_nth_param(0).unwrap();
+        let digits = function.get_nth_param(1).unwrap().into_int_value();
+        let negative = self
+            .builder
+            .build_int_compare(
+                inkwell::IntPredicate::SLT,
+                digits,
+                i32_type.const_zero(),
+                "negative",
+            )
+            .unwrap();
+        let digits = self
+            .builder
+            .build_select(negative, i32_type.const_zero(), digits, "digits")
+            .unwrap();
+        let fmt_ptr = self
+            .builder
+            .build_global_string_ptr("%.*f", "format_fmt")
+            .unwrap()
+            .as_pointer_value();
+
+        // First pass measures the text, second pass writes it after the header
+        let snprintf_fn = self.get_or_declare_snprintf();
+        let len = self
+            .builder
+            .build_call(
+                snprintf_fn,
+                &[
+                    ptr_type.const_null().into(),
+                    self.size_type().const_zero().into(),
+                    fmt_ptr.into(),
+                    digits.into(),
+                    value.into(),
+                ],
+                "str_len",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let len_with_null = self
+            .builder
+            .build_int_add(
+                self.builder
+                    .build_int_z_extend(len, i64_type, "str_len_i64")
+                    .unwrap(),
+                i64_type.const_int(1, false),
+                "len_with_null",
+            )
+            .unwrap();
+        let total_size = self
+            .builder
+            .build_int_add(len_with_null, i64_type.const_int(8, false), "total_size")
+            .unwrap();
+        let heap_ptr = self
+            .builder
+            .build_call(
+                self.get_or_declare_malloc(),
+                &[self.to_size_t(total_size).into()],
+                "str_heap",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value();
+        self.builder
+            .build_store(heap_ptr, i32_type.const_int(1, false))
+            .unwrap();
+        let data_ptr = unsafe {
+            self.builder.build_gep(
+                self.context.i8_type(),
+                heap_ptr,
+                &[i32_type.const_int(8, false)],
+                "data_ptr",
+            )
+        }
+        .unwrap();
+        self.builder
+            .build_call(
+                snprintf_fn,
+                &[
+                    data_ptr.into(),
+                    self.to_size_t(len_with_null).into(),
+                    fmt_ptr.into(),
+                    digits.into(),
+                    value.into(),
+                ],
+                "",
+            )
+            .unwrap();
+        self.builder.build_return(Some(&data_ptr)).unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// Emits (once) `__parse_int(ptr)` returning an `Int?`, or with `float`
+    /// `__parse_float(ptr)` returning a `Float?`. The number is read with
+    /// strtoll/strtod, which skip leading whitespace; it is absent when no
+    /// digits were read, when anything follows them, or when an integer
+    /// doesn't fit in 32 bits.
+    fn get_or_create_parse_fn(&self, float: bool) -> FunctionValue<'ctx> {
+        let (helper, payload) = if float {
+            ("__parse_float", MirType::Float)
+        } else {
+            ("__parse_int", MirType::Int)
+        };
+        if let Some(func) = self.module.get_function(helper) {
+            return func;
+        }
+
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let i32_type = self.context.i32_type();
+        let optional_type = self
+            .scalar_optional_type(&MirType::Optional(Box::new(payload)))
+            .expect("numbers have scalar optionals");
+        let function = self.module.add_function(
+            helper,
+            optional_type.fn_type(&[ptr_type.into()], false),
+            None,
+        );
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+
+        let text = function.get_nth_param(0).unwrap().into_pointer_value();
+        let end_slot = self.builder.build_alloca(ptr_type, "end_slot").unwrap();
+        let (value, fits): (BasicValueEnum<'ctx>, _) = if float {
+            let parsed = self
+                .builder
+                .build_call(
+                    self.get_or_declare_strtod(),
+                    &[text.into(), end_slot.into()],
+                    "parsed",
+                )
+                .unwrap()
+                .try_as_basic_value()
+                .left()
+                .unwrap();
+            (parsed, self.context.bool_type().const_int(1, false))
+        } else {
+            let parsed = self
+                .builder
+                .build_call(
+                    self.get_or_declare_strtoll(),
+                    &[
+                        text.into(),
+                        end_slot.into(),
+                        i32_type.const_int(10, false).into(),
+                    ],
+                    "parsed",
+                )
+                .unwrap()
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_int_value();
+            let narrow = self
+                .builder
+                .build_int_truncate(parsed, i32_type, "narrow")
+                .unwrap();
+            let widened = self
+                .builder
+                .build_int_s_extend(narrow, self.context.i64_type(), "widened")
+                .unwrap();
+            let fits = self
+                .builder
+                .build_int_compare(inkwell::IntPredicate::EQ, widened, parsed, "fits")
+                .unwrap();
+            (narrow.into(), fits)
+        };
+
+        // Some digits were read and nothing follows them
+        let end = self
+            .builder
+            .build_load(ptr_type, end_slot, "end")
+            .unwrap()
+            .into_pointer_value();
+        let read_some = self
+            .builder
+            .build_int_compare(
+                inkwell::IntPredicate::NE,
+                self.builder
+                    .build_ptr_to_int(end, self.context.i64_type(), "end_addr")
+                    .unwrap(),
+                self.builder
+                    .build_ptr_to_int(text, self.context.i64_type(), "text_addr")
+                    .unwrap(),
+                "read_some",
+            )
+            .unwrap();
+        let rest = self
+            .builder
+            .build_load(self.context.i8_type(), end, "rest")
+            .unwrap()
+            .into_int_value();
+        let at_end = self
+            .builder
+            .build_int_compare(
+                inkwell::IntPredicate::EQ,
+                rest,
+                self.context.i8_type().const_zero(),
+                "at_end",
+            )
+            .unwrap();
+        let whole = self.builder.build_and(read_some, at_end, "whole").unwrap();
+        let is_some = self.builder.build_and(whole, fits, "is_some").unwrap();
+        let with_tag = self
+            .builder
+            .build_insert_value(optional_type.get_undef(), is_some, 0, "opt_tag")
+            .unwrap();
+        let result = self
+            .builder
+            .build_insert_value(with_tag, value, 1, "opt")
+            .unwrap()
+            .into_struct_value();
+        self.builder.build_return(Some(&result)).unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    pub(crate) fn get_or_declare_snprintf(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("snprintf") {
+            return func;
+        }
+
+        // Declare snprintf: int snprintf(char *buf, size_t n, const char *fmt, ...)
+        let i8_ptr = self.context.ptr_type(AddressSpace::default());
+        let fn_type = self.context.i32_type().fn_type(
+            &[i8_ptr.into(), self.size_type().into(), i8_ptr.into()],
+            true,
+        );
+
+        self.module.add_function("snprintf", fn_type, None)
+    }
+
+    pub(crate) fn get_or_declare_atoi(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("atoi") {
+            return func;
+        }
+
+        // Declare atoi: int atoi(const char *s)
+        let i8_ptr = self.context.ptr_type(AddressSpace::default());
+        let fn_type = self.context.i32_type().fn_type(&[i8_ptr.into()], false);
+
+        self.module.add_function("atoi", fn_type, None)
+    }
+
+    fn get_or_declare_strtod(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("strtod") {
+            return func;
+        }
+
+        // Declare strtod: double strtod(const char *s, char **end)
+        let i8_ptr = self.context.ptr_type(AddressSpace::default());
+        let fn_type = self
+            .context
+            .f64_type()
+            .fn_type(&[i8_ptr.into(), i8_ptr.into()], false);
+
+        self.module.add_function("strtod", fn_type, None)
+    }
+
+    fn get_or_declare_strtoll(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("strtoll") {
+            return func;
+        }
+
+        // Declare strtoll: long long strtoll(const char *s, char **end, int base)
+        let i8_ptr = self.context.ptr_type(AddressSpace::default());
+        let fn_type = self.context.i64_type().fn_type(
+            &[i8_ptr.into(), i8_ptr.into(), self.context.i32_type().into()],
+            false,
+        );
+
+        self.module.add_function("strtoll", fn_type, None)
+    }
+}
@@ -2,3 +2,6 @@ pub mod arithmetic;
 pub mod collections;
 pub mod constants;
 pub mod control_flow;
+pub mod conversions;
+pub mod optionals;
+pub mod tuples;
@@ -0,0 +1,189 @@
+use crate::codegen::core::CodeGen;
+use crate::mir::MirType;
+use inkwell::types::{BasicTypeEnum, StructType};
+use inkwell::values::BasicValueEnum;
+use inkwell::AddressSpace;
+
+/// Optional values come in two shapes:
+/// - scalars (Int, Int64, Float, Bool) are a `{ i1 present, T payload }` pair
+/// - heap types (Str, arrays, maps, structs) are the pointer itself, null when absent
+impl<'ctx> CodeGen<'ctx> {
+    /// LLVM type of a value stored inside an optional of type `ty`.
+    pub fn optional_payload_type(&self, ty: &MirType) -> BasicTypeEnum<'ctx> {
+        match ty.optional_payload() {
+            payload if payload.is_heap() => self.context.ptr_type(AddressSpace::default()).into(),
+            MirType::CStr => self.context.ptr_type(AddressSpace::default()).into(),
+            MirType::Float => self.context.f64_type().into(),
+            MirType::Int64 => self.context.i64_type().into(),
+            // Int and Bool (bools are i32 throughout codegen)
+            _ => self.context.i32_type().into(),
+        }
+    }
+
+    /// The `{ i1, T }` struct for optional scalars, or None when `ty` is not an
+    /// optional or its payload is a heap type (those use a nullable pointer).
+    pub fn scalar_optional_type(&self, ty: &MirType) -> Option<StructType<'ctx>> {
+        if !ty.is_scalar_optional() {
+            return None;
+        }
+        let payload = self.optional_payload_type(ty);
+        Some(
+            self.context
+                .struct_type(&[self.context.bool_type().into(), payload], false),
+        )
+    }
+
+    /// LLVM type of an optional as a whole.
+    pub fn optional_llvm_type(&self, ty: &MirType) -> BasicTypeEnum<'ctx> {
+        match self.scalar_optional_type(ty) {
+            Some(st) => st.into(),
+            None => self.context.ptr_type(AddressSpace::default()).into(),
+        }
+    }
+
+    fn store_optional_result(&mut self, name: &str, val: BasicValueEnum<'ctx>) {
+        if let Some(sym) = self.symbols.get(name) {
+            self.builder.build_store(sym.ptr, val).unwrap();
+        }
+        self.temp_values.insert(name.to_string(), val);
+    }
+
+    /// Builds an optional holding `value`, or an absent one when `value` is None.
+    pub fn generate_optional_wrap(
+        &mut self,
+        name: &str,
+        value: Option<&str>,
+        ty: &MirType,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let val: BasicValueEnum<'ctx> = match self.scalar_optional_type(ty) {
+            Some(st) => {
+                let payload_ty = self.optional_payload_type(ty);
+                let (present, payload) = match value {
+                    Some(v) => (1, self.resolve_value(v)),
+                    None => (0, payload_ty.const_zero()),
+                };
+                let tag = self.context.bool_type().const_int(present, false);
+                let with_tag = self
+                    .builder
+                    .build_insert_value(st.get_undef(), tag, 0, "opt_tag")
+                    .unwrap();
+                self.builder
+                    .build_insert_value(with_tag, payload, 1, "opt")
+                    .unwrap()
+                    .into_struct_value()
+                    .into()
+            }
+            None => match value {
+                Some(v) => {
+                    // The optional takes over (or shares) the pointer's reference
+                    let is_heap = self.heap_strings.contains(v)
+                        || self.heap_arrays.contains(v)
+                        || self.heap_maps.contains(v);
+                    if is_heap && !v.starts_with('%') {
+                        self.emit_incref(v);
+                    }
+                    for set in [
+                        &mut self.heap_strings,
+                        &mut self.heap_arrays,
+                        &mut self.heap_maps,
+                    ] {
+                        if set.contains(v) {
+                            if v.starts_with('%') {
+                                set.remove(v);
+                            }
+                            set.insert(name.to_string());
+                        }
+                    }
+                    self.propagate_metadata(name, v);
+                    self.resolve_value(v)
+                }
+                None => self
+                    .context
+                    .ptr_type(AddressSpace::default())
+                    .const_null()
+                    .into(),
+            },
+        };
+
+        self.store_optional_result(name, val);
+        Some(val)
+    }
+
+    /// Produces a Bool that is true when the optional holds a value.
+    pub fn generate_optional_is_some(
+        &mut self,
+        name: &str,
+        optional: &str,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let opt = self.resolve_value(optional);
+        let present = if opt.is_struct_value() {
+            self.builder
+                .build_extract_value(opt.into_struct_value(), 0, "opt_present")
+                .unwrap()
+                .into_int_value()
+        } else {
+            self.builder
+                .build_is_not_null(opt.into_pointer_value(), "opt_present")
+                .unwrap()
+        };
+        let present = self.bool_value(present);
+        self.temp_values.insert(name.to_string(), present.into());
+        Some(present.into())
+    }
+
+    /// Extracts the payload of an optional already known to be present (`if let`).
+    /// The result borrows from the optional, so it is not tracked as a heap value.
+    pub fn generate_optional_unwrap(
+        &mut self,
+        name: &str,
+        optional: &str,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let opt = self.resolve_value(optional);
+        let payload = if opt.is_struct_value() {
+            self.builder
+                .build_extract_value(opt.into_struct_value(), 1, "opt_payload")
+                .unwrap()
+        } else {
+            self.propagate_metadata(name, optional);
+            opt
+        };
+        self.store_optional_result(name, payload);
+        Some(payload)
+    }
+
+    /// `optional ?? default`: the payload when present, `default` otherwise.
+    pub fn generate_optional_unwrap_or(
+        &mut self,
+        name: &str,
+        optional: &str,
+        default: &str,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let opt = self.resolve_value(optional);
+        let default_val = self.resolve_value(default);
+        let (present, payload) = if opt.is_struct_value() {
+            let st = opt.into_struct_value();
+            let present = self
+                .builder
+                .build_extract_value(st, 0, "opt_present")
+                .unwrap()
+                .into_int_value();
+            let payload = self
+                .builder
+                .build_extract_value(st, 1, "opt_payload")
+                .unwrap();
+            (present, payload)
+        } else {
+            let present = self
+                .builder
+                .build_is_not_null(opt.into_pointer_value(), "opt_present")
+                .unwrap();
+            (present, opt)
+        };
+        let result = self
+            .builder
+            .build_select(present, payload, default_val, "opt_or")
+            .unwrap();
+        self.store_optional_result(name, result);
+        Some(result)
+    }
+}
@@ -0,0 +1,138 @@
+use crate::codegen::core::CodeGen;
+use crate::mir::MirType;
+use inkwell::attributes::{Attribute, AttributeLoc};
+use inkwell::types::{AnyType, BasicTypeEnum, StructType};
+use inkwell::values::{BasicValueEnum, CallSiteValue, FunctionValue, PointerValue};
+
+/// Functions with a tuple return type (`-> (Int, Int)`) return an LLVM struct
+/// with one field per element through a hidden first parameter marked `sret`:
+/// the caller passes a slot in its own frame and the callee, which returns
+/// void, stores the struct there. The caller loads it back and destructures it
+/// with TupleExtract.
+impl<'ctx> CodeGen<'ctx> {
+    /// LLVM type of one tuple element.
+    fn tuple_element_type(&self, ty: &MirType) -> BasicTypeEnum<'ctx> {
+        if matches!(ty, MirType::Optional(_)) {
+            self.optional_llvm_type(ty)
+        } else {
+            // Same mapping as an optional's payload: pointers for heap types,
+            // f64/i64 for Float/Int64 and i32 for Int and Bool
+            self.optional_payload_type(ty)
+        }
+    }
+
+    /// The struct returned by a function whose MIR return type is a tuple,
+    /// or None for any other return type.
+    pub fn tuple_return_type(&self, ty: &MirType) -> Option<StructType<'ctx>> {
+        let fields: Vec<BasicTypeEnum<'ctx>> = ty
+            .tuple_elements()?
+            .iter()
+            .map(|elem| self.tuple_element_type(elem))
+            .collect();
+        Some(self.context.struct_type(&fields, false))
+    }
+
+    /// The struct the function `func` returns through its `sret` parameter,
+    /// or None when it returns its value directly.
+    pub fn sret_return_type(&self, func: &str) -> Option<StructType<'ctx>> {
+        self.function_return_types
+            .get(&Self::llvm_function_name(func))
+            .and_then(|ty| self.tuple_return_type(ty))
+    }
+
+    /// The `sret(<struct>)` attribute of the hidden result parameter.
+    fn sret_attribute(&self, struct_type: StructType<'ctx>) -> Attribute {
+        self.context.create_type_attribute(
+            Attribute::get_named_enum_kind_id("sret"),
+            struct_type.as_any_type_enum(),
+        )
+    }
+
+    /// Marks the first parameter of `function` as where it stores its
+    /// `struct_type` result: `sret`, and not reachable through any other pointer.
+    pub fn add_sret_attributes(
+        &self,
+        function: FunctionValue<'ctx>,
+        struct_type: StructType<'ctx>,
+    ) {
+        let noalias = self
+            .context
+            .create_enum_attribute(Attribute::get_named_enum_kind_id("noalias"), 0);
+        function.add_attribute(AttributeLoc::Param(0), self.sret_attribute(struct_type));
+        function.add_attribute(AttributeLoc::Param(0), noalias);
+    }
+
+    /// Marks the first argument of `call` as the `sret` slot it passes.
+    pub fn add_sret_call_attribute(
+        &self,
+        call: CallSiteValue<'ctx>,
+        struct_type: StructType<'ctx>,
+    ) {
+        call.add_attribute(AttributeLoc::Param(0), self.sret_attribute(struct_type));
+    }
+
+    /// A slot for a `struct_type` call result in the current function's frame,
+    /// allocated in its entry block so a call in a loop reuses it.
+    pub fn sret_slot(&self, struct_type: StructType<'ctx>) -> PointerValue<'ctx> {
+        let entry = self
+            .builder
+            .get_insert_block()
+            .and_then(|block| block.get_parent())
+            .and_then(|function| function.get_first_basic_block())
+            .unwrap();
+        let entry_builder = self.context.create_builder();
+        match entry.get_first_instruction() {
+            Some(first) => entry_builder.position_before(&first),
+            None => entry_builder.position_at_end(entry),
+        }
+        entry_builder
+            .build_alloca(struct_type, "sret_slot")
+            .unwrap()
+    }
+
+    /// Packs the values of `return a, b;` into the `struct_type` result and
+    /// stores it through the function's `sret` parameter `out`.
+    pub fn build_tuple_return(
+        &self,
+        out: PointerValue<'ctx>,
+        struct_type: StructType<'ctx>,
+        values: &[String],
+    ) {
+        let mut tuple = struct_type.get_undef();
+        for (i, value) in values.iter().enumerate() {
+            let val = self.resolve_value(value);
+            tuple = self
+                .builder
+                .build_insert_value(tuple, val, i as u32, "ret_tuple")
+                .unwrap()
+                .into_struct_value();
+        }
+        self.builder.build_store(out, tuple).unwrap();
+    }
+
+    /// Reads element `index` of a tuple returned by a call.
+    pub fn generate_tuple_extract(
+        &mut self,
+        name: &str,
+        source: &str,
+        index: usize,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let tuple_val = self.resolve_value(source);
+        if !tuple_val.is_struct_value() {
+            // Not a tuple - return a dummy value
+            let dummy = self.context.i32_type().const_int(0, false);
+            self.temp_values.insert(name.to_string(), dummy.into());
+            return Some(dummy.into());
+        }
+
+        let elem = self
+            .builder
+            .build_extract_value(tuple_val.into_struct_value(), index as u32, "tuple_elem")
+            .unwrap();
+        if let Some(sym) = self.symbols.get(name) {
+            self.builder.build_store(sym.ptr, elem).unwrap();
+        }
+        self.temp_values.insert(name.to_string(), elem);
+        Some(elem)
+    }
+}
@@ -327,20 +327,7 @@ impl<'ctx> CodeGen<'ctx> {
 
                 // Decref block
                 self.builder.position_at_end(decref_block);
-                let old_rc_header = unsafe {
-                    self.builder.build_in_bounds_gep(
-                        self.context.i8_type(),
-                        old_ptr,
-                        &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                        "old_rc_header",
-                    )
-                }
-                .unwrap();
-
-                let decref_fn = self.decref_fn.unwrap();
-                self.builder
-                    .build_call(decref_fn, &[old_rc_header.into()], "")
-                    .unwrap();
+                self.decref_value(old_ptr);
 
                 self.builder
                     .build_unconditional_branch(skip_decref_block)
@@ -374,21 +361,7 @@ impl<'ctx> CodeGen<'ctx> {
             self.builder.build_store(item_alloca, str_val).unwrap();
 
             // Increment RC for loaded string
-            let str_ptr = str_val.into_pointer_value();
-            let rc_header = unsafe {
-                self.builder.build_in_bounds_gep(
-                    self.context.i8_type(),
-                    str_ptr,
-                    &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                    "rc_header",
-                )
-            }
-            .unwrap();
-
-            let incref_fn = self.incref_fn.unwrap();
-            self.builder
-                .build_call(incref_fn, &[rc_header.into()], "")
-                .unwrap();
+            self.incref_value(str_val.into_pointer_value());
 
             // Track for cleanup
             self.heap_strings.insert(var.to_string());
@@ -598,41 +571,13 @@ impl<'ctx> CodeGen<'ctx> {
 
         // Handle RC for strings
         if key_is_string {
-            let key_str_ptr = key_val.into_pointer_value();
-            let rc_header = unsafe {
-                self.builder.build_in_bounds_gep(
-                    self.context.i8_type(),
-                    key_str_ptr,
-                    &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                    "key_rc_header",
-                )
-            }
-            .unwrap();
-
-            let incref_fn = self.incref_fn.unwrap();
-            self.builder
-                .build_call(incref_fn, &[rc_header.into()], "")
-                .unwrap();
+            self.incref_value(key_val.into_pointer_value());
 
             self.heap_strings.insert(key_var.to_string());
         }
 
         if val_is_string {
-            let val_str_ptr = val_val.into_pointer_value();
-            let rc_header = unsafe {
-                self.builder.build_in_bounds_gep(
-                    self.context.i8_type(),
-                    val_str_ptr,
-                    &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                    "val_rc_header",
-                )
-            }
-            .unwrap();
-
-            let incref_fn = self.incref_fn.unwrap();
-            self.builder
-                .build_call(incref_fn, &[rc_header.into()], "")
-                .unwrap();
+            self.incref_value(val_val.into_pointer_value());
 
             self.heap_strings.insert(value_var.to_string());
         }
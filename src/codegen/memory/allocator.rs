@@ -0,0 +1,666 @@
+/// Where the generated code gets its memory from.
+///
+/// Every allocation goes through `doo_alloc`, `doo_realloc` and `doo_free`.
+/// They are defined with weak linkage over malloc, realloc and free, so a
+/// host linking in its own definitions replaces them for all doo code.
+///
+/// Programs with `#[arena]` functions go through `__arena_alloc`,
+/// `__arena_realloc` and `__arena_free` instead. While an arena function is
+/// running they carve blocks out of large chunks, and ignore frees of those
+/// blocks; when the outermost arena function returns, its chunks are handed
+/// back to `doo_free` all at once. Outside of arena functions they pass
+/// straight through to the `doo_*` functions.
+use crate::codegen::core::CodeGen;
+use inkwell::module::Linkage;
+use inkwell::types::StructType;
+use inkwell::values::{FunctionValue, GlobalValue, IntValue, PointerValue};
+use inkwell::{AddressSpace, IntPredicate};
+
+/// Bytes before the first block of an arena chunk: its `{next, cursor, end}`
+/// pointers, rounded up so blocks stay 8-byte aligned.
+const CHUNK_HEADER: u64 = 32;
+
+/// Smallest chunk an arena asks `doo_alloc` for; larger blocks get a chunk
+/// of their own size.
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+impl<'ctx> CodeGen<'ctx> {
+    /// The function allocating memory: `ptr doo_alloc(size_t)`, or its
+    /// arena-aware wrapper in programs with arena functions.
+    pub fn get_or_declare_malloc(&self) -> FunctionValue<'ctx> {
+        if self.arena_runtime {
+            return self.get_or_create_arena_fn("__arena_alloc");
+        }
+        self.get_or_create_default_allocator("doo_alloc", "malloc")
+    }
+
+    /// The function freeing memory: `void doo_free(ptr)`, or its arena-aware
+    /// wrapper in programs with arena functions.
+    pub(crate) fn get_or_declare_free(&self) -> FunctionValue<'ctx> {
+        if self.arena_runtime {
+            return self.get_or_create_arena_fn("__arena_free");
+        }
+        self.get_or_create_default_allocator("doo_free", "free")
+    }
+
+    /// The function resizing an allocation: `ptr doo_realloc(ptr, size_t)`,
+    /// or its arena-aware wrapper in programs with arena functions.
+    pub fn get_or_declare_realloc(&self) -> FunctionValue<'ctx> {
+        if self.arena_runtime {
+            return self.get_or_create_arena_fn("__arena_realloc");
+        }
+        self.get_or_create_default_allocator("doo_realloc", "realloc")
+    }
+
+    /// The C library's own `malloc` or `free`, for memory C code frees or
+    /// has allocated: the C strings of `toCStr` and `takeCStr`.
+    pub(crate) fn get_or_declare_libc_allocator(&self, name: &str) -> FunctionValue<'ctx> {
+        self.module.get_function(name).unwrap_or_else(|| {
+            self.module
+                .add_function(name, self.allocator_fn_type(name), None)
+        })
+    }
+
+    /// Emits the start of an arena function's body: allocations are taken
+    /// from the arena until the matching `emit_arena_exit`.
+    pub(crate) fn emit_arena_enter(&self) {
+        if self.in_arena {
+            let enter_fn = self.get_or_create_arena_fn("__arena_enter");
+            self.builder.build_call(enter_fn, &[], "").unwrap();
+        }
+    }
+
+    /// Emits the end of an arena function, just before it returns: the
+    /// outermost one frees everything the arena holds.
+    pub(crate) fn emit_arena_exit(&self) {
+        if self.in_arena {
+            let exit_fn = self.get_or_create_arena_fn("__arena_exit");
+            self.builder.build_call(exit_fn, &[], "").unwrap();
+        }
+    }
+
+    /// `ptr(size_t)`, `void(ptr)` or `ptr(ptr, size_t)`, whichever `name`
+    /// (a `doo_*`, `__arena_*` or C library allocator) has.
+    fn allocator_fn_type(&self, name: &str) -> inkwell::types::FunctionType<'ctx> {
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let size_t = self.size_type();
+        match name {
+            "doo_alloc" | "malloc" | "__arena_alloc" | "__arena_bump" => {
+                ptr_type.fn_type(&[size_t.into()], false)
+            }
+            "doo_realloc" | "realloc" | "__arena_realloc" => {
+                ptr_type.fn_type(&[ptr_type.into(), size_t.into()], false)
+            }
+            "__arena_owns" => self.context.bool_type().fn_type(&[ptr_type.into()], false),
+            "__arena_enter" | "__arena_exit" => self.context.void_type().fn_type(&[], false),
+            _ => self.context.void_type().fn_type(&[ptr_type.into()], false),
+        }
+    }
+
+    /// Defines `name` (`doo_alloc`, `doo_free` or `doo_realloc`) as a weak
+    /// function passing its arguments on to the C library's `libc_name`.
+    fn get_or_create_default_allocator(&self, name: &str, libc_name: &str) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function(name) {
+            return func;
+        }
+
+        let fn_type = self.allocator_fn_type(name);
+        let libc_fn = self.module.get_function(libc_name).unwrap_or_else(|| {
+            self.module
+                .add_function(libc_name, self.allocator_fn_type(libc_name), None)
+        });
+        let function = self
+            .module
+            .add_function(name, fn_type, Some(Linkage::WeakAny));
+        self.define_runtime_fn(function, || {
+            let args: Vec<_> = function.get_param_iter().map(|arg| arg.into()).collect();
+            let result = self
+                .builder
+                .build_call(libc_fn, &args, "result")
+                .unwrap()
+                .try_as_basic_value()
+                .left();
+            match result {
+                Some(value) => self.builder.build_return(Some(&value)).unwrap(),
+                None => self.builder.build_return(None).unwrap(),
+            };
+        });
+        function
+    }
+
+    /// Builds the body of `function` with `build`, starting in its entry
+    /// block, then puts the builder back where it was. The body is runtime
+    /// code, and is left out of the debug info of the function being
+    /// generated.
+    fn define_runtime_fn(&self, function: FunctionValue<'ctx>, build: impl FnOnce()) {
+        let saved_block = self.builder.get_insert_block();
+        let saved_location = self.builder.get_current_debug_location();
+        self.builder.unset_current_debug_location();
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+        build();
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        if let Some(location) = saved_location {
+            self.builder.set_current_debug_location(location);
+        }
+    }
+
+    /// `{next, cursor, end}`: the pointers at the start of an arena chunk.
+    /// Its blocks run from `CHUNK_HEADER` bytes in up to `end`; `cursor` is
+    /// where the next one goes.
+    fn arena_chunk_type(&self) -> StructType<'ctx> {
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        self.context
+            .struct_type(&[ptr_type.into(), ptr_type.into(), ptr_type.into()], false)
+    }
+
+    /// The arena's state: `__arena_depth`, how many arena functions are
+    /// running, or `__arena_chunks`, the last chunk it allocated.
+    fn arena_global(&self, name: &str) -> GlobalValue<'ctx> {
+        if let Some(global) = self.module.get_global(name) {
+            return global;
+        }
+        let global = if name == "__arena_depth" {
+            let i32_type = self.context.i32_type();
+            let global = self.module.add_global(i32_type, None, name);
+            global.set_initializer(&i32_type.const_zero());
+            global
+        } else {
+            let ptr_type = self.context.ptr_type(AddressSpace::default());
+            let global = self.module.add_global(ptr_type, None, name);
+            global.set_initializer(&ptr_type.const_null());
+            global
+        };
+        global.set_linkage(Linkage::Internal);
+        global
+    }
+
+    /// Whether an arena function is running: `__arena_depth != 0`.
+    fn arena_active(&self) -> IntValue<'ctx> {
+        let i32_type = self.context.i32_type();
+        let depth = self
+            .builder
+            .build_load(
+                i32_type,
+                self.arena_global("__arena_depth").as_pointer_value(),
+                "depth",
+            )
+            .unwrap()
+            .into_int_value();
+        self.builder
+            .build_int_compare(IntPredicate::NE, depth, i32_type.const_zero(), "active")
+            .unwrap()
+    }
+
+    /// Field `index` of the arena chunk at `chunk`.
+    fn chunk_field(&self, chunk: PointerValue<'ctx>, index: u32) -> PointerValue<'ctx> {
+        self.builder
+            .build_struct_gep(self.arena_chunk_type(), chunk, index, "chunk_field")
+            .unwrap()
+    }
+
+    /// `ptr + offset` bytes.
+    fn byte_offset(&self, ptr: PointerValue<'ctx>, offset: IntValue<'ctx>) -> PointerValue<'ctx> {
+        unsafe {
+            self.builder
+                .build_in_bounds_gep(self.context.i8_type(), ptr, &[offset], "offset")
+                .unwrap()
+        }
+    }
+
+    /// `ptr` as a 64-bit address, to compare with others.
+    fn address(&self, ptr: PointerValue<'ctx>) -> IntValue<'ctx> {
+        self.builder
+            .build_ptr_to_int(ptr, self.context.i64_type(), "addr")
+            .unwrap()
+    }
+
+    /// Creates one of the arena runtime functions:
+    /// - `__arena_enter()` / `__arena_exit()`: an arena function starts or
+    ///   returns. Leaving the outermost frees every chunk.
+    /// - `__arena_bump(size)`: a block from the current chunk, after a new
+    ///   chunk if it's full. The block's size is kept in the 8 bytes before it.
+    /// - `__arena_owns(ptr)`: whether `ptr` is a block of one of the chunks.
+    /// - `__arena_alloc`, `__arena_free`, `__arena_realloc`: `doo_alloc`,
+    ///   `doo_free` and `doo_realloc`, or the arena while one is active.
+    fn get_or_create_arena_fn(&self, name: &str) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function(name) {
+            return func;
+        }
+
+        let function = self
+            .module
+            .add_function(name, self.allocator_fn_type(name), None);
+        self.define_runtime_fn(function, || match name {
+            "__arena_enter" | "__arena_exit" => self.build_arena_enter_exit(function, name),
+            "__arena_bump" => self.build_arena_bump(function),
+            "__arena_owns" => self.build_arena_owns(function),
+            "__arena_alloc" => self.build_arena_alloc(function),
+            "__arena_free" => self.build_arena_free(function),
+            _ => self.build_arena_realloc(function),
+        });
+        function
+    }
+
+    fn build_arena_enter_exit(&self, function: FunctionValue<'ctx>, name: &str) {
+        let i32_type = self.context.i32_type();
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let depth_ptr = self.arena_global("__arena_depth").as_pointer_value();
+        let depth = self
+            .builder
+            .build_load(i32_type, depth_ptr, "depth")
+            .unwrap()
+            .into_int_value();
+        let one = i32_type.const_int(1, false);
+        if name == "__arena_enter" {
+            let depth = self.builder.build_int_add(depth, one, "depth").unwrap();
+            self.builder.build_store(depth_ptr, depth).unwrap();
+            self.builder.build_return(None).unwrap();
+            return;
+        }
+
+        let depth = self.builder.build_int_sub(depth, one, "depth").unwrap();
+        self.builder.build_store(depth_ptr, depth).unwrap();
+        let release = self.context.append_basic_block(function, "release");
+        let free_chunk = self.context.append_basic_block(function, "free_chunk");
+        let done = self.context.append_basic_block(function, "done");
+        let exit = self.context.append_basic_block(function, "exit");
+        let outermost = self
+            .builder
+            .build_int_compare(IntPredicate::EQ, depth, i32_type.const_zero(), "outermost")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(outermost, release, exit)
+            .unwrap();
+
+        // Walk the chunks from the last one allocated, freeing each
+        self.builder.position_at_end(release);
+        let chunks_ptr = self.arena_global("__arena_chunks").as_pointer_value();
+        let first = self
+            .builder
+            .build_load(ptr_type, chunks_ptr, "chunk")
+            .unwrap()
+            .into_pointer_value();
+        let check = self.context.append_basic_block(function, "check");
+        self.builder.build_unconditional_branch(check).unwrap();
+
+        self.builder.position_at_end(check);
+        let chunk = self.builder.build_phi(ptr_type, "chunk").unwrap();
+        let chunk_ptr = chunk.as_basic_value().into_pointer_value();
+        let is_null = self.builder.build_is_null(chunk_ptr, "is_null").unwrap();
+        self.builder
+            .build_conditional_branch(is_null, done, free_chunk)
+            .unwrap();
+
+        self.builder.position_at_end(free_chunk);
+        let next = self
+            .builder
+            .build_load(ptr_type, self.chunk_field(chunk_ptr, 0), "next")
+            .unwrap()
+            .into_pointer_value();
+        let doo_free = self.get_or_create_default_allocator("doo_free", "free");
+        self.builder
+            .build_call(doo_free, &[chunk_ptr.into()], "")
+            .unwrap();
+        self.builder.build_unconditional_branch(check).unwrap();
+        chunk.add_incoming(&[(&first, release), (&next, free_chunk)]);
+
+        self.builder.position_at_end(done);
+        self.builder
+            .build_store(chunks_ptr, ptr_type.const_null())
+            .unwrap();
+        self.builder.build_unconditional_branch(exit).unwrap();
+
+        self.builder.position_at_end(exit);
+        self.builder.build_return(None).unwrap();
+    }
+
+    fn build_arena_bump(&self, function: FunctionValue<'ctx>) {
+        let i64_type = self.context.i64_type();
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let size = function.get_nth_param(0).unwrap().into_int_value();
+        let size = self
+            .builder
+            .build_int_z_extend_or_bit_cast(size, i64_type, "size")
+            .unwrap();
+        // The size, then the block rounded up to keep the next one aligned
+        let rounded = self
+            .builder
+            .build_and(
+                self.builder
+                    .build_int_add(size, i64_type.const_int(7, false), "size")
+                    .unwrap(),
+                i64_type.const_int(!7_u64, false),
+                "rounded",
+            )
+            .unwrap();
+        let needed = self
+            .builder
+            .build_int_add(rounded, i64_type.const_int(8, false), "needed")
+            .unwrap();
+
+        let chunks_ptr = self.arena_global("__arena_chunks").as_pointer_value();
+        let head = self
+            .builder
+            .build_load(ptr_type, chunks_ptr, "head")
+            .unwrap()
+            .into_pointer_value();
+        let check_room = self.context.append_basic_block(function, "check_room");
+        let grow = self.context.append_basic_block(function, "grow");
+        let bump = self.context.append_basic_block(function, "bump");
+        let is_null = self.builder.build_is_null(head, "is_null").unwrap();
+        self.builder
+            .build_conditional_branch(is_null, grow, check_room)
+            .unwrap();
+
+        self.builder.position_at_end(check_room);
+        let cursor = self
+            .builder
+            .build_load(ptr_type, self.chunk_field(head, 1), "cursor")
+            .unwrap()
+            .into_pointer_value();
+        let end = self
+            .builder
+            .build_load(ptr_type, self.chunk_field(head, 2), "end")
+            .unwrap()
+            .into_pointer_value();
+        let room = self
+            .builder
+            .build_int_sub(self.address(end), self.address(cursor), "room")
+            .unwrap();
+        let fits = self
+            .builder
+            .build_int_compare(IntPredicate::ULE, needed, room, "fits")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(fits, bump, grow)
+            .unwrap();
+
+        // A new chunk, in front of the others
+        self.builder.position_at_end(grow);
+        let min_size = i64_type.const_int(CHUNK_SIZE, false);
+        let is_large = self
+            .builder
+            .build_int_compare(IntPredicate::UGT, needed, min_size, "is_large")
+            .unwrap();
+        let capacity = self
+            .builder
+            .build_select(is_large, needed, min_size, "capacity")
+            .unwrap()
+            .into_int_value();
+        let header_size = i64_type.const_int(CHUNK_HEADER, false);
+        let chunk_size = self
+            .builder
+            .build_int_add(capacity, header_size, "chunk_size")
+            .unwrap();
+        let doo_alloc = self.get_or_create_default_allocator("doo_alloc", "malloc");
+        let chunk = self
+            .builder
+            .build_call(doo_alloc, &[self.to_size_t(chunk_size).into()], "chunk")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value();
+        let first_block = self.byte_offset(chunk, header_size);
+        let chunk_end = self.byte_offset(chunk, chunk_size);
+        self.builder
+            .build_store(self.chunk_field(chunk, 0), head)
+            .unwrap();
+        self.builder
+            .build_store(self.chunk_field(chunk, 1), first_block)
+            .unwrap();
+        self.builder
+            .build_store(self.chunk_field(chunk, 2), chunk_end)
+            .unwrap();
+        self.builder.build_store(chunks_ptr, chunk).unwrap();
+        self.builder.build_unconditional_branch(bump).unwrap();
+
+        self.builder.position_at_end(bump);
+        let current = self.builder.build_phi(ptr_type, "current").unwrap();
+        current.add_incoming(&[(&head, check_room), (&chunk, grow)]);
+        let current = current.as_basic_value().into_pointer_value();
+        let cursor_ptr = self.chunk_field(current, 1);
+        let cursor = self
+            .builder
+            .build_load(ptr_type, cursor_ptr, "cursor")
+            .unwrap()
+            .into_pointer_value();
+        let next_cursor = self.byte_offset(cursor, needed);
+        self.builder.build_store(cursor_ptr, next_cursor).unwrap();
+        self.builder.build_store(cursor, size).unwrap();
+        let block = self.byte_offset(cursor, i64_type.const_int(8, false));
+        self.builder.build_return(Some(&block)).unwrap();
+    }
+
+    fn build_arena_owns(&self, function: FunctionValue<'ctx>) {
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let bool_type = self.context.bool_type();
+        let ptr = function.get_nth_param(0).unwrap().into_pointer_value();
+        let addr = self.address(ptr);
+        let entry = self.builder.get_insert_block().unwrap();
+        let first = self
+            .builder
+            .build_load(
+                ptr_type,
+                self.arena_global("__arena_chunks").as_pointer_value(),
+                "chunk",
+            )
+            .unwrap()
+            .into_pointer_value();
+        let check = self.context.append_basic_block(function, "check");
+        let in_chunk = self.context.append_basic_block(function, "in_chunk");
+        let next_chunk = self.context.append_basic_block(function, "next_chunk");
+        let exit = self.context.append_basic_block(function, "exit");
+        self.builder.build_unconditional_branch(check).unwrap();
+
+        self.builder.position_at_end(check);
+        let chunk = self.builder.build_phi(ptr_type, "chunk").unwrap();
+        let chunk_ptr = chunk.as_basic_value().into_pointer_value();
+        let is_null = self.builder.build_is_null(chunk_ptr, "is_null").unwrap();
+        self.builder
+            .build_conditional_branch(is_null, exit, in_chunk)
+            .unwrap();
+
+        self.builder.position_at_end(in_chunk);
+        let start = self.byte_offset(
+            chunk_ptr,
+            self.context.i64_type().const_int(CHUNK_HEADER, false),
+        );
+        let end = self
+            .builder
+            .build_load(ptr_type, self.chunk_field(chunk_ptr, 2), "end")
+            .unwrap()
+            .into_pointer_value();
+        let after_start = self
+            .builder
+            .build_int_compare(IntPredicate::UGE, addr, self.address(start), "after_start")
+            .unwrap();
+        let before_end = self
+            .builder
+            .build_int_compare(IntPredicate::ULT, addr, self.address(end), "before_end")
+            .unwrap();
+        let inside = self
+            .builder
+            .build_and(after_start, before_end, "inside")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(inside, exit, next_chunk)
+            .unwrap();
+
+        self.builder.position_at_end(next_chunk);
+        let next = self
+            .builder
+            .build_load(ptr_type, self.chunk_field(chunk_ptr, 0), "next")
+            .unwrap()
+            .into_pointer_value();
+        self.builder.build_unconditional_branch(check).unwrap();
+        chunk.add_incoming(&[(&first, entry), (&next, next_chunk)]);
+
+        self.builder.position_at_end(exit);
+        let owns = self.builder.build_phi(bool_type, "owns").unwrap();
+        owns.add_incoming(&[
+            (&bool_type.const_zero(), check),
+            (&bool_type.const_all_ones(), in_chunk),
+        ]);
+        self.builder
+            .build_return(Some(&owns.as_basic_value()))
+            .unwrap();
+    }
+
+    fn build_arena_alloc(&self, function: FunctionValue<'ctx>) {
+        let size = function.get_nth_param(0).unwrap();
+        let from_arena = self.context.append_basic_block(function, "from_arena");
+        let from_heap = self.context.append_basic_block(function, "from_heap");
+        let active = self.arena_active();
+        self.builder
+            .build_conditional_branch(active, from_arena, from_heap)
+            .unwrap();
+
+        for (block, callee) in [
+            (from_arena, self.get_or_create_arena_fn("__arena_bump")),
+            (
+                from_heap,
+                self.get_or_create_default_allocator("doo_alloc", "malloc"),
+            ),
+        ] {
+            self.builder.position_at_end(block);
+            let ptr = self
+                .builder
+                .build_call(callee, &[size.into()], "ptr")
+                .unwrap()
+                .try_as_basic_value()
+                .left()
+                .unwrap();
+            self.builder.build_return(Some(&ptr)).unwrap();
+        }
+    }
+
+    fn build_arena_free(&self, function: FunctionValue<'ctx>) {
+        let ptr = function.get_nth_param(0).unwrap();
+        let check_owner = self.context.append_basic_block(function, "check_owner");
+        let to_heap = self.context.append_basic_block(function, "to_heap");
+        let exit = self.context.append_basic_block(function, "exit");
+        let active = self.arena_active();
+        self.builder
+            .build_conditional_branch(active, check_owner, to_heap)
+            .unwrap();
+
+        // The arena's own blocks are freed with their chunk
+        self.builder.position_at_end(check_owner);
+        let owns = self
+            .builder
+            .build_call(
+                self.get_or_create_arena_fn("__arena_owns"),
+                &[ptr.into()],
+                "owns",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        self.builder
+            .build_conditional_branch(owns, exit, to_heap)
+            .unwrap();
+
+        self.builder.position_at_end(to_heap);
+        let doo_free = self.get_or_create_default_allocator("doo_free", "free");
+        self.builder
+            .build_call(doo_free, &[ptr.into()], "")
+            .unwrap();
+        self.builder.build_unconditional_branch(exit).unwrap();
+
+        self.builder.position_at_end(exit);
+        self.builder.build_return(None).unwrap();
+    }
+
+    fn build_arena_realloc(&self, function: FunctionValue<'ctx>) {
+        let i64_type = self.context.i64_type();
+        let ptr = function.get_nth_param(0).unwrap().into_pointer_value();
+        let size = function.get_nth_param(1).unwrap().into_int_value();
+        let check_owner = self.context.append_basic_block(function, "check_owner");
+        let move_block = self.context.append_basic_block(function, "move");
+        let to_heap = self.context.append_basic_block(function, "to_heap");
+        let active = self.arena_active();
+        self.builder
+            .build_conditional_branch(active, check_owner, to_heap)
+            .unwrap();
+
+        self.builder.position_at_end(check_owner);
+        let owns = self
+            .builder
+            .build_call(
+                self.get_or_create_arena_fn("__arena_owns"),
+                &[ptr.into()],
+                "owns",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        self.builder
+            .build_conditional_branch(owns, move_block, to_heap)
+            .unwrap();
+
+        // An arena block can't grow in place: copy it to a new one
+        self.builder.position_at_end(move_block);
+        let new_ptr = self
+            .builder
+            .build_call(
+                self.get_or_create_arena_fn("__arena_bump"),
+                &[size.into()],
+                "new_ptr",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value();
+        let old_size_ptr = self.byte_offset(ptr, i64_type.const_int((-8_i64) as u64, true));
+        let old_size = self
+            .builder
+            .build_load(i64_type, old_size_ptr, "old_size")
+            .unwrap()
+            .into_int_value();
+        let new_size = self
+            .builder
+            .build_int_z_extend_or_bit_cast(size, i64_type, "new_size")
+            .unwrap();
+        let grows = self
+            .builder
+            .build_int_compare(IntPredicate::ULT, old_size, new_size, "grows")
+            .unwrap();
+        let copied = self
+            .builder
+            .build_select(grows, old_size, new_size, "copied")
+            .unwrap();
+        self.builder
+            .build_call(
+                self.get_or_declare_memcpy(),
+                &[
+                    new_ptr.into(),
+                    ptr.into(),
+                    copied.into(),
+                    self.context.bool_type().const_zero().into(),
+                ],
+                "",
+            )
+            .unwrap();
+        self.builder.build_return(Some(&new_ptr)).unwrap();
+
+        self.builder.position_at_end(to_heap);
+        let doo_realloc = self.get_or_create_default_allocator("doo_realloc", "realloc");
+        let result = self
+            .builder
+            .build_call(doo_realloc, &[ptr.into(), size.into()], "result")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap();
+        self.builder.build_return(Some(&result)).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+use crate::codegen::core::CodeGen;
+use inkwell::values::{BasicValueEnum, FunctionValue};
+use inkwell::AddressSpace;
+
+/// C string builtins: `cStr`, `toCStr`, `fromCStr` and `takeCStr`.
+///
+/// A doo string already points at NUL-terminated bytes, with its RC header in
+/// front of them, so `cStr` is the pointer itself. The other three call a
+/// routine generated once per module. `__str_to_c` copies a string into a
+/// block from the C library's `malloc`, which C code can `free`;
+/// `__str_from_c` copies a C string into a new heap string of RC = 1, or
+/// gives null (an absent `Str?`) for a null pointer. `takeCStr` then hands
+/// the C string to `free`.
+impl<'ctx> CodeGen<'ctx> {
+    /// The result of the C string builtin `name` for the argument `val`.
+    pub(crate) fn generate_c_string(
+        &self,
+        name: &str,
+        val: BasicValueEnum<'ctx>,
+    ) -> BasicValueEnum<'ctx> {
+        match name {
+            "toCStr" => self.call_str_fn(self.get_or_create_str_to_c_fn(), &[val.into()], "c_str"),
+            "fromCStr" | "takeCStr" => {
+                let copy =
+                    self.call_str_fn(self.get_or_create_str_from_c_fn(), &[val.into()], "from_c");
+                if name == "takeCStr" {
+                    self.builder
+                        .build_call(
+                            self.get_or_declare_libc_allocator("free"),
+                            &[val.into()],
+                            "",
+                        )
+                        .unwrap();
+                }
+                copy
+            }
+            // cStr: the string's own bytes
+            _ => val,
+        }
+    }
+
+    /// `__str_to_c(s)`: a copy of `s` and its terminator in memory from `malloc`.
+    fn get_or_create_str_to_c_fn(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("__str_to_c") {
+            return func;
+        }
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let function = self.module.add_function(
+            "__str_to_c",
+            ptr_type.fn_type(&[ptr_type.into()], false),
+            None,
+        );
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+
+        let s = function.get_nth_param(0).unwrap().into_pointer_value();
+        let len = self
+            .call_str_fn(self.get_or_declare_strlen(), &[s.into()], "len")
+            .into_int_value();
+        let size = self
+            .builder
+            .build_int_add(len, self.context.i32_type().const_int(1, false), "size")
+            .unwrap();
+        let copy = self
+            .call_str_fn(
+                self.get_or_declare_libc_allocator("malloc"),
+                &[self.to_size_t(size).into()],
+                "copy",
+            )
+            .into_pointer_value();
+        self.copy_str_bytes(copy, s, size);
+        self.builder.build_return(Some(&copy)).unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// `__str_from_c(c)`: a new heap string holding the C string `c`, or null
+    /// when `c` is null.
+    fn get_or_create_str_from_c_fn(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("__str_from_c") {
+            return func;
+        }
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let function = self.module.add_function(
+            "__str_from_c",
+            ptr_type.fn_type(&[ptr_type.into()], false),
+            None,
+        );
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        let copy_block = self.context.append_basic_block(function, "copy");
+        let null_block = self.context.append_basic_block(function, "null");
+
+        self.builder.position_at_end(entry);
+        let c = function.get_nth_param(0).unwrap().into_pointer_value();
+        let is_null = self.builder.build_is_null(c, "is_null").unwrap();
+        self.builder
+            .build_conditional_branch(is_null, null_block, copy_block)
+            .unwrap();
+
+        self.builder.position_at_end(null_block);
+        self.builder
+            .build_return(Some(&ptr_type.const_null()))
+            .unwrap();
+
+        self.builder.position_at_end(copy_block);
+        let len = self
+            .call_str_fn(self.get_or_declare_strlen(), &[c.into()], "len")
+            .into_int_value();
+        let copy = self.build_str_copy(c, len);
+        self.builder.build_return(Some(&copy)).unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+}
@@ -1,2 +1,5 @@
+pub mod allocator;
+pub mod c_strings;
 pub mod rc_runtime;
+pub mod string_methods;
 pub mod strings;
@@ -1,23 +1,105 @@
 /// This module implements reference counting (RC) runtime support for memory management.
 /// It provides functions for incrementing and decrementing reference counts,
-/// and for declaring or retrieving standard memory functions (malloc, free, memcpy).
+/// and for declaring or retrieving memcpy; allocation itself lives in `allocator`.
 /// All logic is designed to work with LLVM IR via the inkwell library.
 use crate::codegen::core::CodeGen;
-use inkwell::values::FunctionValue;
-use inkwell::AddressSpace;
+use inkwell::types::IntType;
+use inkwell::values::{FunctionValue, IntValue, PointerValue};
+use inkwell::{AddressSpace, AtomicOrdering, AtomicRMWBinOp};
+
+/// Reference count of values RC must never free or write to: string literals
+/// and arrays in a stack frame. `__incref` saturates one below it, so no heap
+/// value's count ever reaches it, and `__decref` leaves it alone.
+pub(crate) const STATIC_RC: u64 = i32::MAX as u64;
 
 /// Implements RC runtime logic for the CodeGen context.
 /// All methods here are used to generate LLVM IR for reference counting and memory operations.
 impl<'ctx> CodeGen<'ctx> {
     /// Initializes the RC runtime by creating the incref and decref functions.
     /// These functions are stored in the CodeGen context for later use.
+    /// `doo_rc_incref_value` and `doo_rc_decref_value` wrap them for callers
+    /// holding a value rather than its header.
     pub fn init_rc_runtime(&mut self) {
+        if self.declare_only {
+            let ptr_type = self.context.ptr_type(AddressSpace::default());
+            let fn_type = self.context.void_type().fn_type(&[ptr_type.into()], false);
+            self.incref_fn = Some(self.module.add_function("__incref", fn_type, None));
+            self.decref_fn = Some(self.module.add_function("__decref", fn_type, None));
+            self.module
+                .add_function("doo_rc_incref_value", fn_type, None);
+            self.module
+                .add_function("doo_rc_decref_value", fn_type, None);
+            return;
+        }
         self.incref_fn = Some(self.create_incref_function());
         self.decref_fn = Some(self.create_decref_function());
+        self.create_value_rc_function("doo_rc_incref_value", self.incref_fn.unwrap());
+        self.create_value_rc_function("doo_rc_decref_value", self.decref_fn.unwrap());
+    }
+
+    /// Creates `void name(ptr value)`: calls `header_fn` on the RC header 8
+    /// bytes before `value`. A null value is ignored.
+    fn create_value_rc_function(&self, name: &str, header_fn: FunctionValue<'ctx>) {
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let fn_type = self.context.void_type().fn_type(&[ptr_type.into()], false);
+        let function = self.module.add_function(name, fn_type, None);
+        let entry = self.context.append_basic_block(function, "entry");
+        let call_block = self.context.append_basic_block(function, "call");
+        let exit_block = self.context.append_basic_block(function, "exit");
+
+        self.builder.position_at_end(entry);
+        let data_ptr = function.get_nth_param(0).unwrap().into_pointer_value();
+        let is_null = self.builder.build_is_null(data_ptr, "is_null").unwrap();
+        self.builder
+            .build_conditional_branch(is_null, exit_block, call_block)
+            .unwrap();
+
+        self.builder.position_at_end(call_block);
+        let rc_header = unsafe {
+            self.builder.build_in_bounds_gep(
+                self.context.i8_type(),
+                data_ptr,
+                &[self.context.i32_type().const_int((-8_i32) as u64, true)],
+                "rc_header",
+            )
+        }
+        .unwrap();
+        self.builder
+            .build_call(header_fn, &[rc_header.into()], "")
+            .unwrap();
+        self.builder.build_unconditional_branch(exit_block).unwrap();
+
+        self.builder.position_at_end(exit_block);
+        self.builder.build_return(None).unwrap();
+    }
+
+    /// Adds a reference to the string, array, map or struct value `data_ptr`.
+    pub(crate) fn incref_value(&self, data_ptr: PointerValue<'ctx>) {
+        // A constant is a string literal or null, which RC leaves alone
+        if data_ptr.is_const() {
+            return;
+        }
+        let incref = self.module.get_function("doo_rc_incref_value").unwrap();
+        self.builder
+            .build_call(incref, &[data_ptr.into()], "")
+            .unwrap();
+    }
+
+    /// Drops a reference to the string, array, map or struct value `data_ptr`,
+    /// freeing it with the last one.
+    pub(crate) fn decref_value(&self, data_ptr: PointerValue<'ctx>) {
+        if data_ptr.is_const() {
+            return;
+        }
+        let decref = self.module.get_function("doo_rc_decref_value").unwrap();
+        self.builder
+            .build_call(decref, &[data_ptr.into()], "")
+            .unwrap();
     }
 
     /// Creates the LLVM function for incrementing the reference count (incref).
     /// This function takes a pointer to the RC header and increments its count.
+    /// A null header (an absent optional) is ignored, as in decref.
     /// Returns the LLVM FunctionValue for later use.
     fn create_incref_function(&self) -> FunctionValue<'ctx> {
         // Define the function signature: void(i8*)
@@ -28,6 +110,8 @@ impl<'ctx> CodeGen<'ctx> {
         // Add the function to the module
         let function = self.module.add_function("__incref", fn_type, None);
         let entry = self.context.append_basic_block(function, "entry");
+        let increment_block = self.context.append_basic_block(function, "increment");
+        let exit_block = self.context.append_basic_block(function, "exit");
 
         // Position builder at the entry block
         self.builder.position_at_end(entry);
@@ -35,6 +119,12 @@ impl<'ctx> CodeGen<'ctx> {
         // Get the RC header pointer from the function parameter
         let rc_ptr = function.get_nth_param(0).unwrap().into_pointer_value();
 
+        let is_null = self.builder.build_is_null(rc_ptr, "is_null").unwrap();
+        self.builder
+            .build_conditional_branch(is_null, exit_block, increment_block)
+            .unwrap();
+        self.builder.position_at_end(increment_block);
+
         // Cast the RC header pointer to i32* (reference count is stored as i32)
         let i32_ptr_type = self.context.i32_type().ptr_type(AddressSpace::default());
         let rc_ptr_typed = self
@@ -48,16 +138,51 @@ impl<'ctx> CodeGen<'ctx> {
             .build_load(self.context.i32_type(), rc_ptr_typed, "rc")
             .unwrap()
             .into_int_value();
+        if self.atomic_rc {
+            let load = rc.as_instruction().unwrap();
+            load.set_atomic_ordering(AtomicOrdering::Monotonic).unwrap();
+            load.set_alignment(4).unwrap();
+        }
 
-        // Increment the reference count by 1
-        let new_rc = self
+        // Statics may sit in read-only memory: their count is never written.
+        // Others stop one below STATIC_RC, leaking rather than turning static.
+        let is_static = self
             .builder
-            .build_int_add(rc, self.context.i32_type().const_int(1, false), "new_rc")
+            .build_int_compare(
+                inkwell::IntPredicate::SGE,
+                rc,
+                self.context.i32_type().const_int(STATIC_RC - 1, false),
+                "is_static",
+            )
+            .unwrap();
+        let bump_block = self.context.append_basic_block(function, "bump");
+        self.builder
+            .build_conditional_branch(is_static, exit_block, bump_block)
             .unwrap();
+        self.builder.position_at_end(bump_block);
+
+        let one = self.context.i32_type().const_int(1, false);
+        if self.atomic_rc {
+            // Taking a reference orders nothing: whoever passed the value holds one
+            self.builder
+                .build_atomicrmw(
+                    AtomicRMWBinOp::Add,
+                    rc_ptr_typed,
+                    one,
+                    AtomicOrdering::Monotonic,
+                )
+                .unwrap();
+        } else {
+            // Increment the reference count by 1
+            let new_rc = self.builder.build_int_add(rc, one, "new_rc").unwrap();
+
+            // Store the new reference count back to memory
+            self.builder.build_store(rc_ptr_typed, new_rc).unwrap();
+        }
+        self.builder.build_unconditional_branch(exit_block).unwrap();
 
-        // Store the new reference count back to memory
-        self.builder.build_store(rc_ptr_typed, new_rc).unwrap();
         // Return void
+        self.builder.position_at_end(exit_block);
         self.builder.build_return(None).unwrap();
 
         function
@@ -110,9 +235,14 @@ impl<'ctx> CodeGen<'ctx> {
             .build_load(self.context.i32_type(), rc_ptr_typed, "rc")
             .unwrap()
             .into_int_value();
+        if self.atomic_rc {
+            let load = rc.as_instruction().unwrap();
+            load.set_atomic_ordering(AtomicOrdering::Monotonic).unwrap();
+            load.set_alignment(4).unwrap();
+        }
 
-        // SAFETY CHECK: RC count should be positive and less than a reasonable max
-        // (e.g., 1-1000000). If not, this is likely a global constant pointer, skip it.
+        // SAFETY CHECK: RC count should be positive and below STATIC_RC.
+        // If not, this is a static (a string literal or stack array), skip it.
         let is_positive = self
             .builder
             .build_int_compare(
@@ -128,7 +258,7 @@ impl<'ctx> CodeGen<'ctx> {
             .build_int_compare(
                 inkwell::IntPredicate::SLT,
                 rc,
-                self.context.i32_type().const_int(1000000, false),
+                self.context.i32_type().const_int(STATIC_RC, false),
                 "is_reasonable",
             )
             .unwrap();
@@ -148,13 +278,27 @@ impl<'ctx> CodeGen<'ctx> {
         self.builder.position_at_end(do_decrement);
 
         // Decrement the reference count by 1
-        let new_rc = self
-            .builder
-            .build_int_sub(rc, self.context.i32_type().const_int(1, false), "new_rc")
-            .unwrap();
+        let one = self.context.i32_type().const_int(1, false);
+        let new_rc = if self.atomic_rc {
+            // The count before: the thread taking it to zero sees every write
+            // made through the other references before it frees the value
+            let old_rc = self
+                .builder
+                .build_atomicrmw(
+                    AtomicRMWBinOp::Sub,
+                    rc_ptr_typed,
+                    one,
+                    AtomicOrdering::AcquireRelease,
+                )
+                .unwrap();
+            self.builder.build_int_sub(old_rc, one, "new_rc").unwrap()
+        } else {
+            let new_rc = self.builder.build_int_sub(rc, one, "new_rc").unwrap();
 
-        // Store the new reference count back to memory
-        self.builder.build_store(rc_ptr_typed, new_rc).unwrap();
+            // Store the new reference count back to memory
+            self.builder.build_store(rc_ptr_typed, new_rc).unwrap();
+            new_rc
+        };
 
         // Check if the reference count is zero (should free memory)
         let should_free = self
@@ -187,38 +331,17 @@ impl<'ctx> CodeGen<'ctx> {
         function
     }
 
-    /// Retrieves the LLVM function for freeing memory (free).
-    /// If not already declared, declares it in the module.
-    /// Returns the LLVM FunctionValue for free.
-    fn get_or_declare_free(&self) -> FunctionValue<'ctx> {
-        // Check if the function is already declared
-        if let Some(func) = self.module.get_function("free") {
-            return func;
-        }
-
-        // Declare the function: void(i8*)
-        let i8_ptr = self.context.ptr_type(AddressSpace::default());
-        let void_type = self.context.void_type();
-        let fn_type = void_type.fn_type(&[i8_ptr.into()], false);
-
-        self.module.add_function("free", fn_type, None)
+    /// C's `size_t` on the target: 64 bits, or 32 on wasm32.
+    pub(crate) fn size_type(&self) -> IntType<'ctx> {
+        self.context.custom_width_int_type(self.pointer_bits)
     }
 
-    /// Retrieves the LLVM function for allocating memory (malloc).
-    /// If not already declared, declares it in the module.
-    /// Returns the LLVM FunctionValue for malloc.
-    pub fn get_or_declare_malloc(&self) -> FunctionValue<'ctx> {
-        // Check if the function is already declared
-        if let Some(func) = self.module.get_function("malloc") {
-            return func;
-        }
-
-        // Declare the function: i8*(i64)
-        let i64_type = self.context.i64_type();
-        let i8_ptr = self.context.ptr_type(AddressSpace::default());
-        let fn_type = i8_ptr.fn_type(&[i64_type.into()], false);
-
-        self.module.add_function("malloc", fn_type, None)
+    /// Converts a byte count, computed in any integer width, to the `size_t`
+    /// the C library takes.
+    pub(crate) fn to_size_t(&self, size: IntValue<'ctx>) -> IntValue<'ctx> {
+        self.builder
+            .build_int_cast_sign_flag(size, self.size_type(), false, "size")
+            .unwrap()
     }
 
     /// Retrieves the LLVM function for copying memory (memcpy).
@@ -250,6 +373,27 @@ impl<'ctx> CodeGen<'ctx> {
     /// Emits code to increment the reference count for a variable.
     /// Looks up the symbol, loads its pointer, computes the RC header,
     /// and calls the incref function.
+    /// Computes the RC header pointer by subtracting 8 bytes from the data pointer.
+    /// A null data pointer (an absent optional) maps to a null header, which the
+    /// runtime functions ignore.
+    pub(crate) fn rc_header_ptr(&self, data_ptr: PointerValue<'ctx>) -> PointerValue<'ctx> {
+        let header = unsafe {
+            self.builder.build_in_bounds_gep(
+                self.context.i8_type(),
+                data_ptr,
+                &[self.context.i32_type().const_int((-8_i32) as u64, true)],
+                "rc_header",
+            )
+        }
+        .unwrap();
+        let is_null = self.builder.build_is_null(data_ptr, "rc_is_null").unwrap();
+        let null = self.context.ptr_type(AddressSpace::default()).const_null();
+        self.builder
+            .build_select(is_null, null, header, "rc_header_or_null")
+            .unwrap()
+            .into_pointer_value()
+    }
+
     pub fn emit_incref(&self, var_name: &str) {
         if let Some(symbol) = self.symbols.get(var_name) {
             // Load the value from the symbol
@@ -264,24 +408,7 @@ impl<'ctx> CodeGen<'ctx> {
                 return;
             }
 
-            let data_ptr = loaded_value.into_pointer_value();
-
-            // Compute the RC header pointer by subtracting 8 bytes
-            let rc_header = unsafe {
-                self.builder.build_in_bounds_gep(
-                    self.context.i8_type(),
-                    data_ptr,
-                    &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                    "rc_header",
-                )
-            }
-            .unwrap();
-
-            // Call the incref function with the RC header pointer
-            let incref = self.incref_fn.unwrap();
-            self.builder
-                .build_call(incref, &[rc_header.into()], "")
-                .unwrap();
+            self.incref_value(loaded_value.into_pointer_value());
         }
     }
 
@@ -302,24 +429,7 @@ impl<'ctx> CodeGen<'ctx> {
                 return;
             }
 
-            let data_ptr = loaded_value.into_pointer_value();
-
-            // Compute the RC header pointer by subtracting 8 bytes
-            let rc_header = unsafe {
-                self.builder.build_in_bounds_gep(
-                    self.context.i8_type(),
-                    data_ptr,
-                    &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                    "rc_header",
-                )
-            }
-            .unwrap();
-
-            // Call the decref function with the RC header pointer
-            let decref = self.decref_fn.unwrap();
-            self.builder
-                .build_call(decref, &[rc_header.into()], "")
-                .unwrap();
+            self.decref_value(loaded_value.into_pointer_value());
         }
     }
 }
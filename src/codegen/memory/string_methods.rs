@@ -0,0 +1,728 @@
+use crate::codegen::core::CodeGen;
+use crate::mir::MirType;
+use inkwell::values::{
+    BasicMetadataValueEnum, BasicValueEnum, FunctionValue, IntValue, PointerValue,
+};
+use inkwell::{AddressSpace, IntPredicate};
+
+/// String methods: `len`, `contains`, `split`, `trim`, `replace` and
+/// `substring`.
+///
+/// `len` and `contains` are a call to libc's `strlen` and `strstr`. The others
+/// call a routine generated once per module (`__str_trim`, `__str_split`, ...)
+/// that builds its result in new heap strings of RC = 1, allocated by
+/// `__str_alloc` with the layout of `generate_string_concat`. `split` returns
+/// a new array of such strings; like every array, it never releases them.
+impl<'ctx> CodeGen<'ctx> {
+    /// Calls `function` and returns its result.
+    pub(crate) fn call_str_fn(
+        &self,
+        function: FunctionValue<'ctx>,
+        args: &[BasicMetadataValueEnum<'ctx>],
+        name: &str,
+    ) -> BasicValueEnum<'ctx> {
+        self.builder
+            .build_call(function, args, name)
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+    }
+
+    /// Pointer to the byte at `offset` in the string at `s`.
+    fn str_offset(&self, s: PointerValue<'ctx>, offset: IntValue<'ctx>) -> PointerValue<'ctx> {
+        unsafe {
+            self.builder
+                .build_in_bounds_gep(self.context.i8_type(), s, &[offset], "str_at")
+        }
+        .unwrap()
+    }
+
+    /// Number of bytes from `from` up to `to`, in the same string.
+    fn str_distance(&self, from: PointerValue<'ctx>, to: PointerValue<'ctx>) -> IntValue<'ctx> {
+        let i64_type = self.context.i64_type();
+        let from = self
+            .builder
+            .build_ptr_to_int(from, i64_type, "from_addr")
+            .unwrap();
+        let to = self
+            .builder
+            .build_ptr_to_int(to, i64_type, "to_addr")
+            .unwrap();
+        let distance = self.builder.build_int_sub(to, from, "distance").unwrap();
+        self.builder
+            .build_int_truncate(distance, self.context.i32_type(), "distance_i32")
+            .unwrap()
+    }
+
+    /// Copies `len` bytes from `src` to `dest`.
+    pub(crate) fn copy_str_bytes(
+        &self,
+        dest: PointerValue<'ctx>,
+        src: PointerValue<'ctx>,
+        len: IntValue<'ctx>,
+    ) {
+        let len = self
+            .builder
+            .build_int_z_extend(len, self.context.i64_type(), "len_i64")
+            .unwrap();
+        self.builder
+            .build_call(
+                self.get_or_declare_memcpy(),
+                &[
+                    dest.into(),
+                    src.into(),
+                    len.into(),
+                    self.context.bool_type().const_zero().into(),
+                ],
+                "",
+            )
+            .unwrap();
+    }
+
+    /// A new heap string holding the `len` bytes at `src`.
+    pub(crate) fn build_str_copy(
+        &self,
+        src: PointerValue<'ctx>,
+        len: IntValue<'ctx>,
+    ) -> PointerValue<'ctx> {
+        let copy = self
+            .call_str_fn(self.get_or_create_str_alloc_fn(), &[len.into()], "str_copy")
+            .into_pointer_value();
+        self.copy_str_bytes(copy, src, len);
+        copy
+    }
+
+    /// Whether the byte `c` is a space, tab, newline, vertical tab, form feed
+    /// or carriage return.
+    fn is_space_byte(&self, c: IntValue<'ctx>) -> IntValue<'ctx> {
+        let i8_type = self.context.i8_type();
+        let is_blank = self
+            .builder
+            .build_int_compare(
+                IntPredicate::EQ,
+                c,
+                i8_type.const_int(b' ' as u64, false),
+                "is_blank",
+            )
+            .unwrap();
+        // '\t' through '\r' are 9 to 13
+        let from_tab = self
+            .builder
+            .build_int_sub(c, i8_type.const_int(9, false), "from_tab")
+            .unwrap();
+        let is_control = self
+            .builder
+            .build_int_compare(
+                IntPredicate::ULT,
+                from_tab,
+                i8_type.const_int(5, false),
+                "is_control",
+            )
+            .unwrap();
+        self.builder
+            .build_or(is_blank, is_control, "is_space")
+            .unwrap()
+    }
+
+    /// Declares libc's `strstr(haystack, needle)`.
+    fn get_or_declare_strstr(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("strstr") {
+            return func;
+        }
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let fn_type = ptr_type.fn_type(&[ptr_type.into(), ptr_type.into()], false);
+        self.module.add_function("strstr", fn_type, None)
+    }
+
+    /// `__str_alloc(len)`: a new heap string with room for `len` bytes and
+    /// its terminator, which is already written. RC = 1.
+    fn get_or_create_str_alloc_fn(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("__str_alloc") {
+            return func;
+        }
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let i32_type = self.context.i32_type();
+        let i64_type = self.context.i64_type();
+        let function = self.module.add_function(
+            "__str_alloc",
+            ptr_type.fn_type(&[i32_type.into()], false),
+            None,
+        );
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+
+        let len = function.get_nth_param(0).unwrap().into_int_value();
+        let len_i64 = self
+            .builder
+            .build_int_z_extend(len, i64_type, "len_i64")
+            .unwrap();
+        // [RC: 4 bytes][padding: 4 bytes][bytes...\0]
+        let total_size = self
+            .builder
+            .build_int_add(len_i64, i64_type.const_int(9, false), "total_size")
+            .unwrap();
+        let heap_ptr = self
+            .call_str_fn(
+                self.get_or_declare_malloc(),
+                &[self.to_size_t(total_size).into()],
+                "str_heap",
+            )
+            .into_pointer_value();
+        self.builder
+            .build_store(heap_ptr, i32_type.const_int(1, false))
+            .unwrap();
+        let data_ptr = self.str_offset(heap_ptr, i32_type.const_int(8, false));
+        self.builder
+            .build_store(
+                self.str_offset(data_ptr, len),
+                self.context.i8_type().const_zero(),
+            )
+            .unwrap();
+        self.builder.build_return(Some(&data_ptr)).unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// `__str_trim(s)`: a copy of `s` without the whitespace at either end.
+    fn get_or_create_str_trim_fn(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("__str_trim") {
+            return func;
+        }
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let i8_type = self.context.i8_type();
+        let i32_type = self.context.i32_type();
+        let function = self.module.add_function(
+            "__str_trim",
+            ptr_type.fn_type(&[ptr_type.into()], false),
+            None,
+        );
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        let lead = self.context.append_basic_block(function, "lead");
+        let lead_next = self.context.append_basic_block(function, "lead_next");
+        let trail = self.context.append_basic_block(function, "trail");
+        let trail_check = self.context.append_basic_block(function, "trail_check");
+        let done = self.context.append_basic_block(function, "done");
+        let s = function.get_nth_param(0).unwrap().into_pointer_value();
+        let one = i32_type.const_int(1, false);
+
+        self.builder.position_at_end(entry);
+        let len = self
+            .call_str_fn(self.get_or_declare_strlen(), &[s.into()], "len")
+            .into_int_value();
+        self.builder.build_unconditional_branch(lead).unwrap();
+
+        // Skip leading whitespace; the terminator stops the scan
+        self.builder.position_at_end(lead);
+        let start = self.builder.build_phi(i32_type, "start").unwrap();
+        let start_val = start.as_basic_value().into_int_value();
+        let c = self
+            .builder
+            .build_load(i8_type, self.str_offset(s, start_val), "c")
+            .unwrap()
+            .into_int_value();
+        let space = self.is_space_byte(c);
+        self.builder
+            .build_conditional_branch(space, lead_next, trail)
+            .unwrap();
+
+        self.builder.position_at_end(lead_next);
+        let next_start = self
+            .builder
+            .build_int_add(start_val, one, "next_start")
+            .unwrap();
+        self.builder.build_unconditional_branch(lead).unwrap();
+        start.add_incoming(&[(&i32_type.const_zero(), entry), (&next_start, lead_next)]);
+
+        // Drop trailing whitespace, never going back past `start`
+        self.builder.position_at_end(trail);
+        let end = self.builder.build_phi(i32_type, "end").unwrap();
+        let end_val = end.as_basic_value().into_int_value();
+        let non_empty = self
+            .builder
+            .build_int_compare(IntPredicate::SGT, end_val, start_val, "non_empty")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(non_empty, trail_check, done)
+            .unwrap();
+
+        self.builder.position_at_end(trail_check);
+        let prev = self.builder.build_int_sub(end_val, one, "prev").unwrap();
+        let c = self
+            .builder
+            .build_load(i8_type, self.str_offset(s, prev), "c")
+            .unwrap()
+            .into_int_value();
+        let space = self.is_space_byte(c);
+        self.builder
+            .build_conditional_branch(space, trail, done)
+            .unwrap();
+        end.add_incoming(&[(&len, lead), (&prev, trail_check)]);
+
+        self.builder.position_at_end(done);
+        let trimmed_len = self
+            .builder
+            .build_int_sub(end_val, start_val, "trimmed_len")
+            .unwrap();
+        let trimmed = self.build_str_copy(self.str_offset(s, start_val), trimmed_len);
+        self.builder.build_return(Some(&trimmed)).unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// `__str_substring(s, start, end)`: a copy of the bytes of `s` from
+    /// `start` up to `end`. Both are clamped to the string, and an `end`
+    /// before `start` gives an empty string.
+    fn get_or_create_str_substring_fn(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("__str_substring") {
+            return func;
+        }
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let i32_type = self.context.i32_type();
+        let function = self.module.add_function(
+            "__str_substring",
+            ptr_type.fn_type(&[ptr_type.into(), i32_type.into(), i32_type.into()], false),
+            None,
+        );
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+
+        let s = function.get_nth_param(0).unwrap().into_pointer_value();
+        let start = function.get_nth_param(1).unwrap().into_int_value();
+        let end = function.get_nth_param(2).unwrap().into_int_value();
+        let len = self
+            .call_str_fn(self.get_or_declare_strlen(), &[s.into()], "len")
+            .into_int_value();
+        let clamp = |value: IntValue<'ctx>, low: IntValue<'ctx>, high: IntValue<'ctx>| {
+            let below = self
+                .builder
+                .build_int_compare(IntPredicate::SLT, value, low, "below")
+                .unwrap();
+            let raised = self
+                .builder
+                .build_select(below, low, value, "raised")
+                .unwrap()
+                .into_int_value();
+            let above = self
+                .builder
+                .build_int_compare(IntPredicate::SGT, raised, high, "above")
+                .unwrap();
+            self.builder
+                .build_select(above, high, raised, "clamped")
+                .unwrap()
+                .into_int_value()
+        };
+        let start = clamp(start, i32_type.const_zero(), len);
+        let end = clamp(end, start, len);
+        let sub_len = self.builder.build_int_sub(end, start, "sub_len").unwrap();
+        let sub = self.build_str_copy(self.str_offset(s, start), sub_len);
+        self.builder.build_return(Some(&sub)).unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// `__str_replace(s, from, to)`: a copy of `s` with every occurrence of
+    /// `from` replaced by `to`, scanning left to right. An empty `from`
+    /// replaces nothing.
+    fn get_or_create_str_replace_fn(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("__str_replace") {
+            return func;
+        }
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let i32_type = self.context.i32_type();
+        let function = self.module.add_function(
+            "__str_replace",
+            ptr_type.fn_type(&[ptr_type.into(), ptr_type.into(), ptr_type.into()], false),
+            None,
+        );
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        let count = self.context.append_basic_block(function, "count");
+        let count_next = self.context.append_basic_block(function, "count_next");
+        let alloc = self.context.append_basic_block(function, "alloc");
+        let copy = self.context.append_basic_block(function, "copy");
+        let copy_next = self.context.append_basic_block(function, "copy_next");
+        let copy_tail = self.context.append_basic_block(function, "copy_tail");
+        let s = function.get_nth_param(0).unwrap().into_pointer_value();
+        let from = function.get_nth_param(1).unwrap().into_pointer_value();
+        let to = function.get_nth_param(2).unwrap().into_pointer_value();
+        let strlen_fn = self.get_or_declare_strlen();
+        let strstr_fn = self.get_or_declare_strstr();
+
+        self.builder.position_at_end(entry);
+        let len = self
+            .call_str_fn(strlen_fn, &[s.into()], "len")
+            .into_int_value();
+        let from_len = self
+            .call_str_fn(strlen_fn, &[from.into()], "from_len")
+            .into_int_value();
+        let to_len = self
+            .call_str_fn(strlen_fn, &[to.into()], "to_len")
+            .into_int_value();
+        let from_empty = self
+            .builder
+            .build_int_compare(
+                IntPredicate::EQ,
+                from_len,
+                i32_type.const_zero(),
+                "from_empty",
+            )
+            .unwrap();
+        self.builder
+            .build_conditional_branch(from_empty, alloc, count)
+            .unwrap();
+
+        // Count the occurrences to size the result
+        self.builder.position_at_end(count);
+        let scan = self.builder.build_phi(ptr_type, "scan").unwrap();
+        let found = self.builder.build_phi(i32_type, "found").unwrap();
+        let scan_val = scan.as_basic_value().into_pointer_value();
+        let found_val = found.as_basic_value().into_int_value();
+        let hit = self
+            .call_str_fn(strstr_fn, &[scan_val.into(), from.into()], "hit")
+            .into_pointer_value();
+        let has_hit = self.builder.build_is_not_null(hit, "has_hit").unwrap();
+        self.builder
+            .build_conditional_branch(has_hit, count_next, alloc)
+            .unwrap();
+
+        self.builder.position_at_end(count_next);
+        let after_hit = self.str_offset(hit, from_len);
+        let next_found = self
+            .builder
+            .build_int_add(found_val, i32_type.const_int(1, false), "next_found")
+            .unwrap();
+        self.builder.build_unconditional_branch(count).unwrap();
+        scan.add_incoming(&[(&s, entry), (&after_hit, count_next)]);
+        found.add_incoming(&[(&i32_type.const_zero(), entry), (&next_found, count_next)]);
+
+        self.builder.position_at_end(alloc);
+        let total = self.builder.build_phi(i32_type, "total").unwrap();
+        total.add_incoming(&[(&i32_type.const_zero(), entry), (&found_val, count)]);
+        let growth = self
+            .builder
+            .build_int_sub(to_len, from_len, "growth")
+            .unwrap();
+        let extra = self
+            .builder
+            .build_int_mul(total.as_basic_value().into_int_value(), growth, "extra")
+            .unwrap();
+        let result_len = self
+            .builder
+            .build_int_add(len, extra, "result_len")
+            .unwrap();
+        let result = self
+            .call_str_fn(
+                self.get_or_create_str_alloc_fn(),
+                &[result_len.into()],
+                "result",
+            )
+            .into_pointer_value();
+        self.builder
+            .build_conditional_branch(from_empty, copy_tail, copy)
+            .unwrap();
+
+        // Copy the text before each occurrence, then `to` in its place
+        self.builder.position_at_end(copy);
+        let src = self.builder.build_phi(ptr_type, "src").unwrap();
+        let dest = self.builder.build_phi(ptr_type, "dest").unwrap();
+        let src_val = src.as_basic_value().into_pointer_value();
+        let dest_val = dest.as_basic_value().into_pointer_value();
+        let hit = self
+            .call_str_fn(strstr_fn, &[src_val.into(), from.into()], "hit")
+            .into_pointer_value();
+        let has_hit = self.builder.build_is_not_null(hit, "has_hit").unwrap();
+        self.builder
+            .build_conditional_branch(has_hit, copy_next, copy_tail)
+            .unwrap();
+
+        self.builder.position_at_end(copy_next);
+        let before_len = self.str_distance(src_val, hit);
+        self.copy_str_bytes(dest_val, src_val, before_len);
+        let to_dest = self.str_offset(dest_val, before_len);
+        self.copy_str_bytes(to_dest, to, to_len);
+        let next_dest = self.str_offset(to_dest, to_len);
+        let next_src = self.str_offset(hit, from_len);
+        self.builder.build_unconditional_branch(copy).unwrap();
+        src.add_incoming(&[(&s, alloc), (&next_src, copy_next)]);
+        dest.add_incoming(&[(&result, alloc), (&next_dest, copy_next)]);
+
+        // The rest after the last occurrence, or all of `s`
+        self.builder.position_at_end(copy_tail);
+        let tail_src = self.builder.build_phi(ptr_type, "tail_src").unwrap();
+        let tail_dest = self.builder.build_phi(ptr_type, "tail_dest").unwrap();
+        tail_src.add_incoming(&[(&s, alloc), (&src_val, copy)]);
+        tail_dest.add_incoming(&[(&result, alloc), (&dest_val, copy)]);
+        let tail_src = tail_src.as_basic_value().into_pointer_value();
+        let tail_len = self
+            .call_str_fn(strlen_fn, &[tail_src.into()], "tail_len")
+            .into_int_value();
+        self.copy_str_bytes(
+            tail_dest.as_basic_value().into_pointer_value(),
+            tail_src,
+            tail_len,
+        );
+        self.builder.build_return(Some(&result)).unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// `__str_split(s, sep)`: a new array of the pieces of `s` between the
+    /// occurrences of `sep`, so one more piece than there are separators. An
+    /// empty `sep` gives `s` as the only piece.
+    fn get_or_create_str_split_fn(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("__str_split") {
+            return func;
+        }
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let i32_type = self.context.i32_type();
+        let function = self.module.add_function(
+            "__str_split",
+            ptr_type.fn_type(&[ptr_type.into(), ptr_type.into()], false),
+            None,
+        );
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        let count = self.context.append_basic_block(function, "count");
+        let count_next = self.context.append_basic_block(function, "count_next");
+        let alloc = self.context.append_basic_block(function, "alloc");
+        let fill = self.context.append_basic_block(function, "fill");
+        let fill_next = self.context.append_basic_block(function, "fill_next");
+        let last = self.context.append_basic_block(function, "last");
+        let s = function.get_nth_param(0).unwrap().into_pointer_value();
+        let sep = function.get_nth_param(1).unwrap().into_pointer_value();
+        let strlen_fn = self.get_or_declare_strlen();
+        let strstr_fn = self.get_or_declare_strstr();
+        let one = i32_type.const_int(1, false);
+
+        self.builder.position_at_end(entry);
+        let sep_len = self
+            .call_str_fn(strlen_fn, &[sep.into()], "sep_len")
+            .into_int_value();
+        let sep_empty = self
+            .builder
+            .build_int_compare(
+                IntPredicate::EQ,
+                sep_len,
+                i32_type.const_zero(),
+                "sep_empty",
+            )
+            .unwrap();
+        self.builder
+            .build_conditional_branch(sep_empty, alloc, count)
+            .unwrap();
+
+        // Count the separators to size the array
+        self.builder.position_at_end(count);
+        let scan = self.builder.build_phi(ptr_type, "scan").unwrap();
+        let found = self.builder.build_phi(i32_type, "found").unwrap();
+        let scan_val = scan.as_basic_value().into_pointer_value();
+        let found_val = found.as_basic_value().into_int_value();
+        let hit = self
+            .call_str_fn(strstr_fn, &[scan_val.into(), sep.into()], "hit")
+            .into_pointer_value();
+        let has_hit = self.builder.build_is_not_null(hit, "has_hit").unwrap();
+        self.builder
+            .build_conditional_branch(has_hit, count_next, alloc)
+            .unwrap();
+
+        self.builder.position_at_end(count_next);
+        let after_hit = self.str_offset(hit, sep_len);
+        let next_found = self
+            .builder
+            .build_int_add(found_val, one, "next_found")
+            .unwrap();
+        self.builder.build_unconditional_branch(count).unwrap();
+        scan.add_incoming(&[(&s, entry), (&after_hit, count_next)]);
+        found.add_incoming(&[(&i32_type.const_zero(), entry), (&next_found, count_next)]);
+
+        self.builder.position_at_end(alloc);
+        let seps = self.builder.build_phi(i32_type, "seps").unwrap();
+        seps.add_incoming(&[(&i32_type.const_zero(), entry), (&found_val, count)]);
+        let seps = seps.as_basic_value().into_int_value();
+        let pieces = self.builder.build_int_add(seps, one, "pieces").unwrap();
+        // A null block makes `__array_reserve` allocate an array with room for `pieces`
+        let array = self.reserve_block(ptr_type.const_null(), pieces, ptr_type.size_of(), 0, 0);
+        let len_ptr = self.str_offset(array, i32_type.const_int((-4_i32) as u64, true));
+        self.builder.build_store(len_ptr, pieces).unwrap();
+        self.builder.build_unconditional_branch(fill).unwrap();
+
+        // Copy the piece before each separator into the array
+        self.builder.position_at_end(fill);
+        let src = self.builder.build_phi(ptr_type, "src").unwrap();
+        let index = self.builder.build_phi(i32_type, "index").unwrap();
+        let src_val = src.as_basic_value().into_pointer_value();
+        let index_val = index.as_basic_value().into_int_value();
+        let at_last = self
+            .builder
+            .build_int_compare(IntPredicate::EQ, index_val, seps, "at_last")
+            .unwrap();
+        let piece_ptr = unsafe {
+            self.builder
+                .build_in_bounds_gep(ptr_type, array, &[index_val], "piece_ptr")
+        }
+        .unwrap();
+        self.builder
+            .build_conditional_branch(at_last, last, fill_next)
+            .unwrap();
+
+        self.builder.position_at_end(fill_next);
+        let hit = self
+            .call_str_fn(strstr_fn, &[src_val.into(), sep.into()], "hit")
+            .into_pointer_value();
+        let piece_len = self.str_distance(src_val, hit);
+        let piece = self.build_str_copy(src_val, piece_len);
+        self.builder.build_store(piece_ptr, piece).unwrap();
+        let next_src = self.str_offset(hit, sep_len);
+        let next_index = self
+            .builder
+            .build_int_add(index_val, one, "next_index")
+            .unwrap();
+        self.builder.build_unconditional_branch(fill).unwrap();
+        src.add_incoming(&[(&s, alloc), (&next_src, fill_next)]);
+        index.add_incoming(&[(&i32_type.const_zero(), alloc), (&next_index, fill_next)]);
+
+        // The rest after the last separator
+        self.builder.position_at_end(last);
+        let rest_len = self
+            .call_str_fn(strlen_fn, &[src_val.into()], "rest_len")
+            .into_int_value();
+        let piece = self.build_str_copy(src_val, rest_len);
+        self.builder.build_store(piece_ptr, piece).unwrap();
+        self.builder.build_return(Some(&array)).unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// Stores the result `val` of a string method into `name`.
+    fn string_method_result(&mut self, name: &str, val: BasicValueEnum<'ctx>) {
+        if let Some(sym) = self.symbols.get(name) {
+            self.builder.build_store(sym.ptr, val).unwrap();
+        }
+        self.temp_values.insert(name.to_string(), val);
+    }
+
+    /// `s.len()`: the number of bytes in the string.
+    pub fn generate_str_len(&mut self, name: &str, string: &str) -> Option<BasicValueEnum<'ctx>> {
+        let s = self.resolve_value(string);
+        let len = self.call_str_fn(self.get_or_declare_strlen(), &[s.into()], "str_len");
+        self.string_method_result(name, len);
+        Some(len)
+    }
+
+    /// `s.contains(part)`: whether `part` occurs in the string.
+    pub fn generate_str_contains(
+        &mut self,
+        name: &str,
+        string: &str,
+        part: &str,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let s = self.resolve_value(string);
+        let part = self.resolve_value(part);
+        let hit = self
+            .call_str_fn(
+                self.get_or_declare_strstr(),
+                &[s.into(), part.into()],
+                "hit",
+            )
+            .into_pointer_value();
+        let has_hit = self.builder.build_is_not_null(hit, "has_hit").unwrap();
+        // Bool values are i32
+        let val: BasicValueEnum<'ctx> = self
+            .builder
+            .build_int_z_extend(has_hit, self.context.i32_type(), "contains")
+            .unwrap()
+            .into();
+        self.string_method_result(name, val);
+        Some(val)
+    }
+
+    /// `s.split(sep)`: a new `[Str]` of the pieces between the separators.
+    pub fn generate_str_split(
+        &mut self,
+        name: &str,
+        string: &str,
+        sep: &str,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let s = self.resolve_value(string);
+        let sep = self.resolve_value(sep);
+        let array = self.call_str_fn(
+            self.get_or_create_str_split_fn(),
+            &[s.into(), sep.into()],
+            "split",
+        );
+        self.heap_arrays.insert(name.to_string());
+        if let Some(shape) = Self::array_shape_from_type(&MirType::Array(Box::new(MirType::Str))) {
+            self.runtime_array_shapes.insert(name.to_string(), shape);
+        }
+        self.string_method_result(name, array);
+        Some(array)
+    }
+
+    /// `s.trim()`, `s.replace(from, to)` or `s.substring(start, end)`: calls
+    /// the routine `function` on the string and `args`, giving a new string.
+    fn generate_str_transform(
+        &mut self,
+        name: &str,
+        function: FunctionValue<'ctx>,
+        string: &str,
+        args: &[&str],
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let call_args: Vec<BasicMetadataValueEnum<'ctx>> = std::iter::once(string)
+            .chain(args.iter().copied())
+            .map(|operand| self.resolve_value(operand).into())
+            .collect();
+        let val = self.call_str_fn(function, &call_args, "str_result");
+        self.heap_strings.insert(name.to_string());
+        self.string_method_result(name, val);
+        Some(val)
+    }
+
+    /// `s.trim()`: a new string without the whitespace at either end.
+    pub fn generate_str_trim(&mut self, name: &str, string: &str) -> Option<BasicValueEnum<'ctx>> {
+        let function = self.get_or_create_str_trim_fn();
+        self.generate_str_transform(name, function, string, &[])
+    }
+
+    /// `s.replace(from, to)`: a new string with every `from` replaced by `to`.
+    pub fn generate_str_replace(
+        &mut self,
+        name: &str,
+        string: &str,
+        from: &str,
+        to: &str,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let function = self.get_or_create_str_replace_fn();
+        self.generate_str_transform(name, function, string, &[from, to])
+    }
+
+    /// `s.substring(start, end)`: a new string of the bytes from `start` up to `end`.
+    pub fn generate_str_substring(
+        &mut self,
+        name: &str,
+        string: &str,
+        start: &str,
+        end: &str,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let function = self.get_or_create_str_substring_fn();
+        self.generate_str_transform(name, function, string, &[start, end])
+    }
+}
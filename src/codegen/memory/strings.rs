@@ -1,6 +1,6 @@
 use crate::codegen::core::CodeGen;
-use inkwell::values::FunctionValue;
-use inkwell::AddressSpace;
+use inkwell::values::{BasicValueEnum, FunctionValue, IntValue, PointerValue};
+use inkwell::{AddressSpace, IntPredicate};
 
 impl<'ctx> CodeGen<'ctx> {
     pub fn generate_string_concat(
@@ -56,7 +56,11 @@ impl<'ctx> CodeGen<'ctx> {
         let malloc_fn = self.get_or_declare_malloc();
         let heap_ptr = self
             .builder
-            .build_call(malloc_fn, &[total_size.into()], "concat_heap")
+            .build_call(
+                malloc_fn,
+                &[self.to_size_t(total_size).into()],
+                "concat_heap",
+            )
             .unwrap()
             .try_as_basic_value()
             .left()
@@ -142,6 +146,267 @@ impl<'ctx> CodeGen<'ctx> {
         Some(data_ptr.into())
     }
 
+    /// Generates `eq`/`ne` for two strings by comparing their contents.
+    /// When one side is a string literal its length and first byte are known at
+    /// compile time, so a mismatch is rejected by a length check and a single byte
+    /// load; only candidates that pass both reach `memcmp`. This keeps chains of
+    /// `if cmd == "..."` comparisons cheap instead of running a full strcmp per arm.
+    ///
+    /// `lt`, `le`, `gt` and `ge` order strings byte by byte, like strcmp, so a
+    /// prefix comes before the longer string.
+    pub fn generate_string_compare(
+        &mut self,
+        op: &str,
+        name: &str,
+        left: &str,
+        right: &str,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let left_ptr = self.resolve_value(left).into_pointer_value();
+        let right_ptr = self.resolve_value(right).into_pointer_value();
+
+        let left_lit = self.temp_strings.get(left).cloned();
+        let right_lit = self.temp_strings.get(right).cloned();
+
+        if op != "eq" && op != "ne" {
+            let result = self.build_string_order(op, left_ptr, right_ptr, left_lit, right_lit);
+            let result = self.bool_value(result);
+            self.temp_values.insert(name.to_string(), result.into());
+            if let Some(sym) = self.symbols.get(name) {
+                self.builder.build_store(sym.ptr, result).unwrap();
+            }
+            return Some(result.into());
+        }
+
+        let is_equal = match (left_lit, right_lit) {
+            // Both sides are literals: fold at compile time
+            (Some(l), Some(r)) => self.context.bool_type().const_int((l == r) as u64, false),
+            (None, Some(lit)) => self.build_literal_compare(left_ptr, right_ptr, &lit),
+            (Some(lit), None) => self.build_literal_compare(right_ptr, left_ptr, &lit),
+            (None, None) => {
+                let strcmp_fn = self.get_or_declare_strcmp();
+                let cmp = self
+                    .builder
+                    .build_call(strcmp_fn, &[left_ptr.into(), right_ptr.into()], "strcmp")
+                    .unwrap()
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap()
+                    .into_int_value();
+                self.builder
+                    .build_int_compare(
+                        IntPredicate::EQ,
+                        cmp,
+                        self.context.i32_type().const_zero(),
+                        "str_eq",
+                    )
+                    .unwrap()
+            }
+        };
+
+        let result = if op == "ne" {
+            self.builder.build_not(is_equal, "str_ne").unwrap()
+        } else {
+            is_equal
+        };
+        let result = self.bool_value(result);
+
+        self.temp_values.insert(name.to_string(), result.into());
+        if let Some(sym) = self.symbols.get(name) {
+            self.builder.build_store(sym.ptr, result).unwrap();
+        }
+        Some(result.into())
+    }
+
+    /// `left op right` for the ordering `op` ("lt", "le", "gt" or "ge") of two
+    /// strings, folded when both are literals.
+    fn build_string_order(
+        &mut self,
+        op: &str,
+        left_ptr: PointerValue<'ctx>,
+        right_ptr: PointerValue<'ctx>,
+        left_lit: Option<String>,
+        right_lit: Option<String>,
+    ) -> IntValue<'ctx> {
+        if let (Some(l), Some(r)) = (left_lit, right_lit) {
+            let holds = match op {
+                "lt" => l < r,
+                "le" => l <= r,
+                "gt" => l > r,
+                _ => l >= r,
+            };
+            return self.context.bool_type().const_int(holds as u64, false);
+        }
+        let predicate = match op {
+            "lt" => IntPredicate::SLT,
+            "le" => IntPredicate::SLE,
+            "gt" => IntPredicate::SGT,
+            _ => IntPredicate::SGE,
+        };
+        let strcmp_fn = self.get_or_declare_strcmp();
+        let cmp = self
+            .builder
+            .build_call(strcmp_fn, &[left_ptr.into(), right_ptr.into()], "strcmp")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        self.builder
+            .build_int_compare(
+                predicate,
+                cmp,
+                self.context.i32_type().const_zero(),
+                "str_order",
+            )
+            .unwrap()
+    }
+
+    /// Compares a runtime string against a literal of known contents.
+    /// Emits: length check -> first byte check -> memcmp of the full literal,
+    /// joined with a phi so the result is a single i1.
+    fn build_literal_compare(
+        &mut self,
+        subject: PointerValue<'ctx>,
+        literal_ptr: PointerValue<'ctx>,
+        literal: &str,
+    ) -> IntValue<'ctx> {
+        let bool_type = self.context.bool_type();
+        let i8_type = self.context.i8_type();
+        let i32_type = self.context.i32_type();
+
+        // Empty literal: equal only if the first byte is the terminator
+        if literal.is_empty() {
+            let first = self
+                .builder
+                .build_load(i8_type, subject, "str_first")
+                .unwrap()
+                .into_int_value();
+            return self
+                .builder
+                .build_int_compare(IntPredicate::EQ, first, i8_type.const_zero(), "str_empty")
+                .unwrap();
+        }
+
+        let current_bb = self.builder.get_insert_block().unwrap();
+        let func = current_bb.get_parent().unwrap();
+        let first_byte_bb = self.context.append_basic_block(func, "str_first_byte");
+        let memcmp_bb = self.context.append_basic_block(func, "str_memcmp");
+        let done_bb = self.context.append_basic_block(func, "str_cmp_done");
+
+        // 1. Length check
+        let strlen_fn = self.get_or_declare_strlen();
+        let len = self
+            .builder
+            .build_call(strlen_fn, &[subject.into()], "str_len")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let len_ok = self
+            .builder
+            .build_int_compare(
+                IntPredicate::EQ,
+                len,
+                i32_type.const_int(literal.len() as u64, false),
+                "str_len_eq",
+            )
+            .unwrap();
+        self.builder
+            .build_conditional_branch(len_ok, first_byte_bb, done_bb)
+            .unwrap();
+
+        // 2. First byte check
+        self.builder.position_at_end(first_byte_bb);
+        let first = self
+            .builder
+            .build_load(i8_type, subject, "str_first")
+            .unwrap()
+            .into_int_value();
+        let first_ok = self
+            .builder
+            .build_int_compare(
+                IntPredicate::EQ,
+                first,
+                i8_type.const_int(literal.as_bytes()[0] as u64, false),
+                "str_first_eq",
+            )
+            .unwrap();
+        self.builder
+            .build_conditional_branch(first_ok, memcmp_bb, done_bb)
+            .unwrap();
+
+        // 3. Full compare (only reached when length and first byte match)
+        self.builder.position_at_end(memcmp_bb);
+        let memcmp_ok = if literal.len() == 1 {
+            bool_type.const_int(1, false)
+        } else {
+            let memcmp_fn = self.get_or_declare_memcmp();
+            let cmp = self
+                .builder
+                .build_call(
+                    memcmp_fn,
+                    &[
+                        subject.into(),
+                        literal_ptr.into(),
+                        self.size_type()
+                            .const_int(literal.len() as u64, false)
+                            .into(),
+                    ],
+                    "memcmp",
+                )
+                .unwrap()
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_int_value();
+            self.builder
+                .build_int_compare(IntPredicate::EQ, cmp, i32_type.const_zero(), "str_bytes_eq")
+                .unwrap()
+        };
+        self.builder.build_unconditional_branch(done_bb).unwrap();
+
+        self.builder.position_at_end(done_bb);
+        let phi = self.builder.build_phi(bool_type, "str_eq").unwrap();
+        let false_val = bool_type.const_zero();
+        phi.add_incoming(&[
+            (&false_val, current_bb),
+            (&false_val, first_byte_bb),
+            (&memcmp_ok, memcmp_bb),
+        ]);
+        phi.as_basic_value().into_int_value()
+    }
+
+    pub fn get_or_declare_strcmp(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("strcmp") {
+            return func;
+        }
+
+        // Declare strcmp: int strcmp(const char *a, const char *b)
+        let i8_ptr = self.context.ptr_type(AddressSpace::default());
+        let fn_type = self
+            .context
+            .i32_type()
+            .fn_type(&[i8_ptr.into(), i8_ptr.into()], false);
+
+        self.module.add_function("strcmp", fn_type, None)
+    }
+
+    pub fn get_or_declare_memcmp(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("memcmp") {
+            return func;
+        }
+
+        // Declare memcmp: int memcmp(const void *a, const void *b, size_t n)
+        let i8_ptr = self.context.ptr_type(AddressSpace::default());
+        let fn_type = self.context.i32_type().fn_type(
+            &[i8_ptr.into(), i8_ptr.into(), self.size_type().into()],
+            false,
+        );
+
+        self.module.add_function("memcmp", fn_type, None)
+    }
+
     pub fn get_or_declare_strlen(&self) -> FunctionValue<'ctx> {
         if let Some(func) = self.module.get_function("strlen") {
             return func;
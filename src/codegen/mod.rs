@@ -10,9 +10,11 @@ pub mod types;
 pub mod memory;
 // Main modules
 pub mod builder;
+pub mod debug_info;
 pub mod functions;
 pub mod globals;
 pub mod loops;
+pub mod parallel;
 
 #[cfg(test)]
 mod tests;
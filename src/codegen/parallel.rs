@@ -0,0 +1,231 @@
+use crate::codegen::core::CodeGen;
+use crate::mir::mir::{MirFunction, MirInstr, MirProgram};
+use inkwell::context::Context;
+use inkwell::memory_buffer::MemoryBuffer;
+use inkwell::module::{Linkage, Module};
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+/// Parallel code generation (`doo build --parallel-codegen`): every function
+/// of the program is generated into a module of its own, in an LLVM context of
+/// its own, on the rayon thread pool (`RAYON_NUM_THREADS` sets its size), and
+/// the modules are linked into one at the end. Nothing a function is
+/// generated with is shared between threads: each module gets a `CodeGen` of
+/// its own.
+///
+/// Each module starts with `declare_program`. The module of the entry point
+/// defines the RC runtime, the globals and `doo.init`; the others only declare
+/// them (see `declare_only`). The runtime helpers built on first use
+/// (`__release_fn`, `__eq_<Name>`, ...) are in every module that uses them:
+/// until the modules are linked those are `linkonce_odr`, of which linking
+/// keeps one, while closure trampolines stay each module's own. The arena's
+/// internal state is shared the same way, so its functions all use one copy
+/// of it.
+impl<'ctx> CodeGen<'ctx> {
+    /// Generates `program` as `generate_program` does, a module per function
+    /// linked into `self.module`. `configure` sets up the code generator of
+    /// each module (target, options, debug info) before it starts.
+    pub fn generate_parallel(
+        &mut self,
+        program: &MirProgram,
+        configure: &(dyn Fn(&mut CodeGen) + Sync),
+    ) -> Result<(), String> {
+        let mut returning_heap = HashSet::new();
+        let mut modules = Vec::new();
+        for wave in waves(program) {
+            let generated: Vec<GeneratedModule> = wave
+                .par_iter()
+                .map(|&index| {
+                    generate_module(
+                        program,
+                        Some(&program.functions[index]),
+                        &returning_heap,
+                        configure,
+                    )
+                })
+                .collect();
+            for module in &generated {
+                returning_heap.extend(module.returning_heap.iter().cloned());
+            }
+            modules.extend(generated);
+        }
+        modules.push(generate_module(program, None, &returning_heap, configure));
+
+        let mut internal = HashSet::new();
+        for (index, generated) in modules.iter().enumerate() {
+            internal.extend(generated.internal.iter().cloned());
+            let buffer = MemoryBuffer::create_from_memory_range(
+                &generated.bitcode,
+                &format!("module_{}", index),
+            );
+            let module = Module::parse_bitcode_from_buffer(&buffer, self.context)
+                .map_err(|e| format!("Failed to read generated module: {}", e))?;
+            if index == 0 {
+                // The first module brings the target and module flags
+                self.module = module;
+            } else {
+                self.module
+                    .link_in_module(module)
+                    .map_err(|e| format!("Failed to link generated modules: {}", e))?;
+            }
+        }
+
+        // One of each shared definition is left, the program's own again
+        for function in self.module.get_functions() {
+            let name = function.get_name().to_string_lossy();
+            if internal.contains(name.as_ref()) {
+                function.set_linkage(Linkage::Internal);
+            } else if function.get_linkage() == Linkage::LinkOnceODR {
+                function.set_linkage(Linkage::External);
+            }
+        }
+        for global in self.module.get_globals() {
+            let name = global.get_name().to_string_lossy();
+            if internal.contains(name.as_ref()) {
+                global.set_linkage(Linkage::Internal);
+            } else if global.get_linkage() == Linkage::LinkOnceODR {
+                global.set_linkage(Linkage::External);
+            }
+        }
+        Ok(())
+    }
+
+    /// Names of what the module defines so far.
+    fn definitions(&self) -> HashSet<String> {
+        let functions = self
+            .module
+            .get_functions()
+            .filter(|function| !function.as_global_value().is_declaration())
+            .map(|function| function.get_name().to_string_lossy().to_string());
+        let globals = self
+            .module
+            .get_globals()
+            .filter(|global| !global.is_declaration())
+            .map(|global| global.get_name().to_string_lossy().to_string());
+        functions.chain(globals).collect()
+    }
+
+    /// Readies the module's definitions for linking with the other modules
+    /// of a parallel build. Those in `own`, which only this module has, are
+    /// external, so the other modules' declarations find them; the rest
+    /// (helpers built on first use, the arena's state) are `linkonce_odr`.
+    /// Returns the names of the internal variables and functions made
+    /// visible, which are internal again once linked.
+    fn share_definitions(&self, own: &HashSet<String>) -> HashSet<String> {
+        let mut internal = HashSet::new();
+        for function in self.module.get_functions() {
+            if function.as_global_value().is_declaration() {
+                continue;
+            }
+            let name = function.get_name().to_string_lossy().to_string();
+            match (own.contains(&name), function.get_linkage()) {
+                (true, Linkage::Internal) => {
+                    function.set_linkage(Linkage::External);
+                    internal.insert(name);
+                }
+                (false, Linkage::External) => function.set_linkage(Linkage::LinkOnceODR),
+                _ => {}
+            }
+        }
+        for global in self.module.get_globals() {
+            if global.is_declaration() {
+                continue;
+            }
+            let name = global.get_name().to_string_lossy().to_string();
+            let linkage = if own.contains(&name) {
+                Linkage::External
+            } else {
+                Linkage::LinkOnceODR
+            };
+            match global.get_linkage() {
+                Linkage::External => global.set_linkage(linkage),
+                Linkage::Internal if !global.is_constant() || own.contains(&name) => {
+                    global.set_linkage(linkage);
+                    internal.insert(name);
+                }
+                _ => {}
+            }
+        }
+        internal
+    }
+}
+
+/// A module generated on the thread pool.
+struct GeneratedModule {
+    bitcode: Vec<u8>, // The module itself can't leave its thread's context
+    returning_heap: HashSet<String>, // Functions known to return heap values after it
+    internal: HashSet<String>, // Internal definitions made visible for linking (see `share_definitions`)
+}
+
+/// Generates `func` into a module of its own, or with `None` the `main` the
+/// program doesn't define itself (see `generate_entry_point`), knowing the
+/// functions in `returning_heap` return heap values.
+fn generate_module(
+    program: &MirProgram,
+    func: Option<&MirFunction>,
+    returning_heap: &HashSet<String>,
+    configure: &(dyn Fn(&mut CodeGen) + Sync),
+) -> GeneratedModule {
+    let context = Context::create();
+    let mut codegen = CodeGen::new(func.map_or("main_module", |f| &f.name), &context);
+    configure(&mut codegen);
+    codegen.declare_only = func.is_some();
+    codegen.declare_program(program);
+    // As in one module, `doo.init` is generated before any of that is known
+    codegen.functions_returning_heap = returning_heap.clone();
+    let mut own = HashSet::new();
+    match func {
+        Some(func) => {
+            codegen.generate_function(func);
+            own.insert(CodeGen::llvm_function_name(&func.name));
+        }
+        None => {
+            own = codegen.definitions();
+            codegen.generate_entry_point(program);
+            own.insert("main".to_string());
+        }
+    }
+    codegen.finish_debug_info();
+    let internal = codegen.share_definitions(&own);
+    GeneratedModule {
+        bitcode: codegen.module.write_bitcode_to_memory().as_slice().to_vec(),
+        returning_heap: codegen.functions_returning_heap,
+        internal,
+    }
+}
+
+/// The functions of `program` by the wave they are generated in. Generating
+/// a function tells the ones after it whether it returns a heap value (see
+/// `functions_returning_heap`), which in one module is known to the functions
+/// after it in `program`. A function calling such a function comes a wave
+/// after it, so the code of each function is the same however many modules
+/// it is split into.
+fn waves(program: &MirProgram) -> Vec<Vec<usize>> {
+    let mut index = HashMap::new();
+    for (i, func) in program.functions.iter().enumerate() {
+        index
+            .entry(CodeGen::llvm_function_name(&func.name))
+            .or_insert(i);
+    }
+    let mut wave_of: Vec<usize> = Vec::with_capacity(program.functions.len());
+    for (i, func) in program.functions.iter().enumerate() {
+        let wave = func
+            .blocks
+            .iter()
+            .flat_map(|block| &block.instrs)
+            .filter_map(|instr| match instr {
+                MirInstr::Call { func, .. } => index.get(&CodeGen::llvm_function_name(func)),
+                _ => None,
+            })
+            .filter(|&&callee| callee < i)
+            .map(|&callee| wave_of[callee] + 1)
+            .max()
+            .unwrap_or(0);
+        wave_of.push(wave);
+    }
+    let mut waves = vec![Vec::new(); wave_of.iter().max().map_or(0, |max| max + 1)];
+    for (i, wave) in wave_of.into_iter().enumerate() {
+        waves[wave].push(i);
+    }
+    waves
+}
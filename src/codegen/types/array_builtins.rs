@@ -0,0 +1,409 @@
+use crate::codegen::core::CodeGen;
+use crate::mir::MirType;
+use inkwell::types::{BasicType, BasicTypeEnum};
+use inkwell::values::{BasicValueEnum, FunctionValue, IntValue};
+use inkwell::AddressSpace;
+use inkwell::{FloatPredicate, IntPredicate};
+
+/// Array builtins: `sort`, `sum`, `min` and `max` on arrays of numbers, and
+/// `reverse` on any array.
+///
+/// `sum`, `min` and `max` call a small routine generated once per element
+/// type (`__array_sum_int`, `__array_min_float`, ...) that takes the data
+/// pointer and the length. `sort` hands the elements to libc's `qsort` with
+/// a comparison routine of the element type (`__compare_int`, ...), and
+/// `reverse` swaps the elements in a loop. Both change the array in place
+/// through `__array_reserve`, like `push`, so an array shared with another
+/// variable is copied first.
+impl<'ctx> CodeGen<'ctx> {
+    /// Suffix of the routines for the number type `elem_ty` (`Int` -> "int").
+    fn numeric_suffix(elem_ty: &MirType) -> &'static str {
+        match elem_ty {
+            MirType::Int64 => "int64",
+            MirType::Float => "float",
+            _ => "int",
+        }
+    }
+
+    /// `a < b` on numbers of type `elem_type`; `a > b` when `greater`.
+    fn compare_numbers(
+        &self,
+        elem_type: BasicTypeEnum<'ctx>,
+        a: BasicValueEnum<'ctx>,
+        b: BasicValueEnum<'ctx>,
+        greater: bool,
+    ) -> IntValue<'ctx> {
+        if elem_type.is_float_type() {
+            let predicate = if greater {
+                FloatPredicate::OGT
+            } else {
+                FloatPredicate::OLT
+            };
+            self.builder
+                .build_float_compare(predicate, a.into_float_value(), b.into_float_value(), "cmp")
+                .unwrap()
+        } else {
+            let predicate = if greater {
+                IntPredicate::SGT
+            } else {
+                IntPredicate::SLT
+            };
+            self.builder
+                .build_int_compare(predicate, a.into_int_value(), b.into_int_value(), "cmp")
+                .unwrap()
+        }
+    }
+
+    /// `__array_{op}_{suffix}(data, len)` for `op` "sum", "min" or "max":
+    /// folds the `len` numbers at `data`. `min` and `max` of an empty array
+    /// return zero; callers only use the value when there are elements.
+    fn get_or_create_array_fold_fn(&self, op: &str, elem_ty: &MirType) -> FunctionValue<'ctx> {
+        let fn_name = format!("__array_{}_{}", op, Self::numeric_suffix(elem_ty));
+        if let Some(func) = self.module.get_function(&fn_name) {
+            return func;
+        }
+        let elem_type = self.struct_field_type(elem_ty);
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let i32_type = self.context.i32_type();
+        let function = self.module.add_function(
+            &fn_name,
+            elem_type.fn_type(&[ptr_type.into(), i32_type.into()], false),
+            None,
+        );
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        let empty = self.context.append_basic_block(function, "empty");
+        let seed = self.context.append_basic_block(function, "seed");
+        let cond = self.context.append_basic_block(function, "cond");
+        let body = self.context.append_basic_block(function, "body");
+        let done = self.context.append_basic_block(function, "done");
+        let data = function.get_nth_param(0).unwrap().into_pointer_value();
+        let len = function.get_nth_param(1).unwrap().into_int_value();
+        let zero = elem_type.const_zero();
+        let load_at = |index: IntValue<'ctx>, name: &str| {
+            let elem_ptr = unsafe {
+                self.builder
+                    .build_in_bounds_gep(elem_type, data, &[index], "elem_ptr")
+            }
+            .unwrap();
+            self.builder.build_load(elem_type, elem_ptr, name).unwrap()
+        };
+
+        self.builder.position_at_end(entry);
+        let has_elems = self
+            .builder
+            .build_int_compare(IntPredicate::SGT, len, i32_type.const_zero(), "has_elems")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(has_elems, seed, empty)
+            .unwrap();
+
+        self.builder.position_at_end(empty);
+        self.builder.build_return(Some(&zero)).unwrap();
+
+        // A sum starts from zero, min and max from the first element
+        self.builder.position_at_end(seed);
+        let (start, first) = if op == "sum" {
+            (zero, i32_type.const_zero())
+        } else {
+            (
+                load_at(i32_type.const_zero(), "first"),
+                i32_type.const_int(1, false),
+            )
+        };
+        self.builder.build_unconditional_branch(cond).unwrap();
+
+        self.builder.position_at_end(cond);
+        let index = self.builder.build_phi(i32_type, "i").unwrap();
+        let acc = self.builder.build_phi(elem_type, "acc").unwrap();
+        let index_val = index.as_basic_value().into_int_value();
+        let acc_val = acc.as_basic_value();
+        let in_bounds = self
+            .builder
+            .build_int_compare(IntPredicate::SLT, index_val, len, "in_bounds")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(in_bounds, body, done)
+            .unwrap();
+
+        self.builder.position_at_end(body);
+        let elem = load_at(index_val, "elem");
+        let next_acc: BasicValueEnum<'ctx> = match op {
+            "sum" if elem_type.is_float_type() => self
+                .builder
+                .build_float_add(acc_val.into_float_value(), elem.into_float_value(), "sum")
+                .unwrap()
+                .into(),
+            "sum" => self
+                .builder
+                .build_int_add(acc_val.into_int_value(), elem.into_int_value(), "sum")
+                .unwrap()
+                .into(),
+            _ => {
+                let better = self.compare_numbers(elem_type, elem, acc_val, op == "max");
+                self.builder
+                    .build_select(better, elem, acc_val, "best")
+                    .unwrap()
+            }
+        };
+        let next_index = self
+            .builder
+            .build_int_add(index_val, i32_type.const_int(1, false), "next")
+            .unwrap();
+        self.builder.build_unconditional_branch(cond).unwrap();
+        index.add_incoming(&[(&first, seed), (&next_index, body)]);
+        acc.add_incoming(&[(&start, seed), (&next_acc, body)]);
+
+        self.builder.position_at_end(done);
+        self.builder.build_return(Some(&acc_val)).unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// `__compare_{suffix}(a, b)`: the `qsort` comparison of the numbers at
+    /// `a` and `b`, -1, 0 or 1.
+    fn get_or_create_compare_fn(&self, elem_ty: &MirType) -> FunctionValue<'ctx> {
+        let fn_name = format!("__compare_{}", Self::numeric_suffix(elem_ty));
+        if let Some(func) = self.module.get_function(&fn_name) {
+            return func;
+        }
+        let elem_type = self.struct_field_type(elem_ty);
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let i32_type = self.context.i32_type();
+        let function = self.module.add_function(
+            &fn_name,
+            i32_type.fn_type(&[ptr_type.into(), ptr_type.into()], false),
+            None,
+        );
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+        let a_ptr = function.get_nth_param(0).unwrap().into_pointer_value();
+        let b_ptr = function.get_nth_param(1).unwrap().into_pointer_value();
+        let a = self.builder.build_load(elem_type, a_ptr, "a").unwrap();
+        let b = self.builder.build_load(elem_type, b_ptr, "b").unwrap();
+
+        // (a > b) - (a < b)
+        let to_i32 = |flag: IntValue<'ctx>, name: &str| {
+            self.builder
+                .build_int_z_extend(flag, i32_type, name)
+                .unwrap()
+        };
+        let greater = to_i32(self.compare_numbers(elem_type, a, b, true), "greater");
+        let less = to_i32(self.compare_numbers(elem_type, a, b, false), "less");
+        let order = self.builder.build_int_sub(greater, less, "order").unwrap();
+        self.builder.build_return(Some(&order)).unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// Declares libc's `qsort(base, count, size, compare)`.
+    fn get_or_declare_qsort(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("qsort") {
+            return func;
+        }
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let size_t = self.size_type();
+        let fn_type = self.context.void_type().fn_type(
+            &[
+                ptr_type.into(),
+                size_t.into(),
+                size_t.into(),
+                ptr_type.into(),
+            ],
+            false,
+        );
+        self.module.add_function("qsort", fn_type, None)
+    }
+
+    /// Stores the result `val` of an array builtin into `name`.
+    fn array_builtin_result(&mut self, name: &str, val: BasicValueEnum<'ctx>) {
+        if let Some(sym) = self.symbols.get(name) {
+            self.builder.build_store(sym.ptr, val).unwrap();
+        }
+        self.temp_values.insert(name.to_string(), val);
+    }
+
+    /// `arr.sort()`: puts the numbers in ascending order.
+    pub fn generate_array_sort(
+        &mut self,
+        array: &str,
+        ty: &MirType,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let elem_ty = Self::growable_elem_type(ty).clone();
+        let (data, len, elem_type) = self.reserve_array(array, ty, |_, len| len);
+        let count = self
+            .builder
+            .build_int_z_extend(len, self.size_type(), "count")
+            .unwrap();
+        let compare = self.get_or_create_compare_fn(&elem_ty);
+        self.builder
+            .build_call(
+                self.get_or_declare_qsort(),
+                &[
+                    data.into(),
+                    count.into(),
+                    self.to_size_t(elem_type.size_of().unwrap()).into(),
+                    compare.as_global_value().as_pointer_value().into(),
+                ],
+                "",
+            )
+            .unwrap();
+        self.finish_array_update(array, ty, data, len);
+        None
+    }
+
+    /// `arr.reverse()`: swaps the elements from both ends towards the middle.
+    pub fn generate_array_reverse(
+        &mut self,
+        array: &str,
+        ty: &MirType,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let (data, len, elem_type) = self.reserve_array(array, ty, |_, len| len);
+        let i32_type = self.context.i32_type();
+        let one = i32_type.const_int(1, false);
+        let current_func = self
+            .builder
+            .get_insert_block()
+            .unwrap()
+            .get_parent()
+            .unwrap();
+        let start_block = self.builder.get_insert_block().unwrap();
+        let cond_block = self
+            .context
+            .append_basic_block(current_func, "array.reverse.cond");
+        let body_block = self
+            .context
+            .append_basic_block(current_func, "array.reverse.body");
+        let exit_block = self
+            .context
+            .append_basic_block(current_func, "array.reverse.exit");
+        let last = self.builder.build_int_sub(len, one, "last").unwrap();
+        self.builder.build_unconditional_branch(cond_block).unwrap();
+
+        self.builder.position_at_end(cond_block);
+        let low = self.builder.build_phi(i32_type, "low").unwrap();
+        let high = self.builder.build_phi(i32_type, "high").unwrap();
+        let low_val = low.as_basic_value().into_int_value();
+        let high_val = high.as_basic_value().into_int_value();
+        let apart = self
+            .builder
+            .build_int_compare(IntPredicate::SLT, low_val, high_val, "apart")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(apart, body_block, exit_block)
+            .unwrap();
+
+        self.builder.position_at_end(body_block);
+        let low_ptr = self.growable_elem_ptr(elem_type, data, low_val);
+        let high_ptr = self.growable_elem_ptr(elem_type, data, high_val);
+        let low_elem = self
+            .builder
+            .build_load(elem_type, low_ptr, "low_elem")
+            .unwrap();
+        let high_elem = self
+            .builder
+            .build_load(elem_type, high_ptr, "high_elem")
+            .unwrap();
+        self.builder.build_store(low_ptr, high_elem).unwrap();
+        self.builder.build_store(high_ptr, low_elem).unwrap();
+        let next_low = self
+            .builder
+            .build_int_add(low_val, one, "next_low")
+            .unwrap();
+        let next_high = self
+            .builder
+            .build_int_sub(high_val, one, "next_high")
+            .unwrap();
+        self.builder.build_unconditional_branch(cond_block).unwrap();
+        low.add_incoming(&[
+            (&i32_type.const_zero(), start_block),
+            (&next_low, body_block),
+        ]);
+        high.add_incoming(&[(&last, start_block), (&next_high, body_block)]);
+
+        self.builder.position_at_end(exit_block);
+        self.finish_array_update(array, ty, data, len);
+        None
+    }
+
+    /// `arr.sum()`: the sum of the numbers, zero for an empty array.
+    pub fn generate_array_sum(
+        &mut self,
+        name: &str,
+        array: &str,
+        ty: &MirType,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let elem_ty = Self::growable_elem_type(ty).clone();
+        let data = self.resolve_value(array).into_pointer_value();
+        let len = self.load_runtime_array_length(data);
+        let sum = self
+            .builder
+            .build_call(
+                self.get_or_create_array_fold_fn("sum", &elem_ty),
+                &[data.into(), len.into()],
+                "sum",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap();
+        self.array_builtin_result(name, sum);
+        Some(sum)
+    }
+
+    /// `arr.min()` (`op` "min") or `arr.max()` ("max"): the smallest or
+    /// largest number, as an optional that is absent when the array is empty.
+    pub fn generate_array_extreme(
+        &mut self,
+        name: &str,
+        array: &str,
+        ty: &MirType,
+        op: &str,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let elem_ty = Self::growable_elem_type(ty).clone();
+        let data = self.resolve_value(array).into_pointer_value();
+        let len = self.load_runtime_array_length(data);
+        let best = self
+            .builder
+            .build_call(
+                self.get_or_create_array_fold_fn(op, &elem_ty),
+                &[data.into(), len.into()],
+                op,
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap();
+        let is_some = self
+            .builder
+            .build_int_compare(
+                IntPredicate::SGT,
+                len,
+                self.context.i32_type().const_zero(),
+                "is_some",
+            )
+            .unwrap();
+        let optional_type = self
+            .scalar_optional_type(&MirType::Optional(Box::new(elem_ty)))
+            .expect("numbers have scalar optionals");
+        let with_tag = self
+            .builder
+            .build_insert_value(optional_type.get_undef(), is_some, 0, "opt_tag")
+            .unwrap();
+        let val: BasicValueEnum<'ctx> = self
+            .builder
+            .build_insert_value(with_tag, best, 1, "opt")
+            .unwrap()
+            .into_struct_value()
+            .into();
+        self.array_builtin_result(name, val);
+        Some(val)
+    }
+}
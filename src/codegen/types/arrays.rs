@@ -1,8 +1,16 @@
 use crate::codegen::core::{ArrayMetadata, CodeGen};
-use inkwell::types::BasicType;
-use inkwell::values::BasicValueEnum;
+use crate::codegen::memory::rc_runtime::STATIC_RC;
+use crate::codegen::types::growable_arrays::array_capacity;
+use crate::mir::MirType;
+use inkwell::module::Linkage;
+use inkwell::types::{ArrayType, BasicType, BasicTypeEnum};
+use inkwell::values::{BasicValueEnum, IntValue, PointerValue};
 use inkwell::AddressSpace;
 
+/// Array literals with at least this many elements, all of them constant
+/// numbers, are copied from a constant instead of stored one by one.
+const CONSTANT_ARRAY_LEN: usize = 16;
+
 impl<'ctx> CodeGen<'ctx> {
     pub fn generate_array_with_metadata(
         &mut self,
@@ -31,24 +39,52 @@ impl<'ctx> CodeGen<'ctx> {
 
         let contains_strings = !str_ptrs.is_empty();
 
-        if contains_strings {
+        // Elements that are arrays themselves (the rows of `[[1, 2], [3]]`)
+        let element_metadata = if !elements.is_empty()
+            && elem_type.is_pointer_type()
+            && elements.iter().all(|el| self.array_shape(el).is_some())
+        {
+            self.array_shape(&elements[0]).map(Box::new)
+        } else {
+            None
+        };
+
+        // The outer array owns its rows: temporaries hand over their reference
+        // (and the strings they own), variables are shared with an extra one
+        let mut owned_ptrs = str_ptrs;
+        if element_metadata.is_some() {
+            for (el, val) in elements.iter().zip(&element_values) {
+                if !self.heap_arrays.contains(el) {
+                    continue;
+                }
+                if self.symbols.contains_key(el) && !el.starts_with('%') {
+                    self.emit_incref(el);
+                } else {
+                    self.heap_arrays.remove(el);
+                    if let Some(inner) = self.composite_string_ptrs.remove(el) {
+                        owned_ptrs.extend(inner);
+                    }
+                }
+                owned_ptrs.push(*val);
+            }
+        }
+
+        if !owned_ptrs.is_empty() {
             self.composite_string_ptrs
-                .insert(name.to_string(), str_ptrs);
+                .insert(name.to_string(), owned_ptrs);
         }
 
         // Store metadata
-        let element_type_name = if elem_type.is_int_type() {
-            "Int"
-        } else if elem_type.is_pointer_type() {
-            "Str"
-        } else {
-            "Unknown"
+        let element_type = match &element_metadata {
+            Some(row) => MirType::Array(Box::new(row.element_type.clone())),
+            None => Self::literal_element_type(elem_type),
         };
 
         let metadata = crate::codegen::ArrayMetadata {
             length: elements.len(),
-            element_type: element_type_name.to_string(),
+            element_type,
             contains_strings,
+            element_metadata,
         };
 
         // Register metadata under EXTENSIVE name variations for better lookup
@@ -70,24 +106,40 @@ impl<'ctx> CodeGen<'ctx> {
             self.array_metadata.insert(variation, metadata.clone());
         }
 
-        // HEAP ALLOCATE with RC header and length field
-        // Layout: [RC: 4 bytes][Length: 4 bytes][data...]
-        let malloc_fn = self.get_or_declare_malloc();
-        let array_size = array_type.size_of().unwrap();
-        let header_size = self.context.i64_type().const_int(8, false); // RC + Length = 8 bytes (use i64)
-        let total_size = self
-            .builder
-            .build_int_add(header_size, array_size, "total_size")
-            .unwrap();
+        // Arrays that never leave the function live in its frame, with an RC
+        // `__decref` ignores; they are never resized so need no spare capacity
+        let on_stack = self.stack_arrays.contains(name);
+        let heap_ptr = if on_stack {
+            self.stack_array_alloca(elem_type.array_type(elements.len() as u32))
+        } else {
+            // HEAP ALLOCATE with RC header and length field
+            // Layout: [RC: 4 bytes][Length: 4 bytes][data...]
+            // The data has room for the capacity implied by the length, so the
+            // array can grow in place (see growable_arrays.rs)
+            let malloc_fn = self.get_or_declare_malloc();
+            let array_size = elem_type
+                .array_type(array_capacity(elements.len()) as u32)
+                .size_of()
+                .unwrap();
+            let header_size = self.context.i64_type().const_int(8, false); // RC + Length = 8 bytes (use i64)
+            let total_size = self
+                .builder
+                .build_int_add(header_size, array_size, "total_size")
+                .unwrap();
 
-        let heap_ptr = self
-            .builder
-            .build_call(malloc_fn, &[total_size.into()], "heap_array")
-            .unwrap()
-            .try_as_basic_value()
-            .left()
-            .unwrap()
-            .into_pointer_value();
+            self.builder
+                .build_call(
+                    malloc_fn,
+                    &[self.to_size_t(total_size).into()],
+                    "heap_array",
+                )
+                .unwrap()
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_pointer_value()
+        };
+        let rc = if on_stack { STATIC_RC } else { 1 };
 
         // Store RC = 1 at offset 0
         let rc_ptr = self
@@ -99,7 +151,7 @@ impl<'ctx> CodeGen<'ctx> {
             )
             .unwrap();
         self.builder
-            .build_store(rc_ptr, self.context.i32_type().const_int(1, false))
+            .build_store(rc_ptr, self.context.i32_type().const_int(rc, false))
             .unwrap();
 
         // Store array length at offset 4
@@ -155,20 +207,36 @@ impl<'ctx> CodeGen<'ctx> {
         // Store the array pointer in temp_values IMMEDIATELY for metadata tracking
         self.temp_values.insert(name.to_string(), data_ptr.into());
 
-        // Store elements
-        for (i, val) in element_values.iter().enumerate() {
-            let idx = self.context.i32_type().const_int(i as u64, false);
-            let elem_ptr = unsafe {
-                self.builder
-                    .build_gep(
-                        array_type,
-                        array_ptr,
-                        &[self.context.i32_type().const_zero(), idx],
-                        &format!("elem_{}", i),
-                    )
-                    .unwrap()
-            };
-            self.builder.build_store(elem_ptr, *val).unwrap();
+        // Store elements. Long literals of numbers, like the bytes of an
+        // `includeBytes` file, are copied from a constant in one go instead
+        let constants: Option<Vec<IntValue<'ctx>>> = if elements.len() >= CONSTANT_ARRAY_LEN {
+            element_values
+                .iter()
+                .map(|val| match val {
+                    BasicValueEnum::IntValue(int) if int.is_const() => Some(*int),
+                    _ => None,
+                })
+                .collect()
+        } else {
+            None
+        };
+        if let Some(constants) = constants {
+            self.copy_constant_elements(array_ptr, array_type, &constants);
+        } else {
+            for (i, val) in element_values.iter().enumerate() {
+                let idx = self.context.i32_type().const_int(i as u64, false);
+                let elem_ptr = unsafe {
+                    self.builder
+                        .build_gep(
+                            array_type,
+                            array_ptr,
+                            &[self.context.i32_type().const_zero(), idx],
+                            &format!("elem_{}", i),
+                        )
+                        .unwrap()
+                };
+                self.builder.build_store(elem_ptr, *val).unwrap();
+            }
         }
 
         // CRITICAL: Remove element strings from heap_strings - they're now owned by the array
@@ -180,7 +248,9 @@ impl<'ctx> CodeGen<'ctx> {
         }
 
         self.temp_values.insert(name.to_string(), data_ptr.into());
-        self.heap_arrays.insert(name.to_string());
+        if !on_stack {
+            self.heap_arrays.insert(name.to_string());
+        }
 
         // CRITICAL: Also store element count in temp_values for later inference
         for i in 0..elements.len() {
@@ -198,110 +268,83 @@ impl<'ctx> CodeGen<'ctx> {
         Some(data_ptr.into())
     }
 
-    /// Helper implementations for array and map operations with RC
-    pub fn get_array_length(&self, array_name: &str) -> inkwell::values::IntValue<'ctx> {
-        // STEP 1: Direct metadata lookup
-        if let Some(metadata) = self.array_metadata.get(array_name) {
-            return self
-                .context
-                .i32_type()
-                .const_int(metadata.length as u64, false);
-        }
-
-        // STEP 2: Try name variations
-        let search_names = vec![
-            array_name.trim_end_matches("_array").to_string(),
-            format!("{}_array", array_name),
-            array_name.trim_start_matches('%').to_string(),
-        ];
-
-        for search_name in &search_names {
-            if let Some(metadata) = self.array_metadata.get(search_name) {
-                return self
-                    .context
-                    .i32_type()
-                    .const_int(metadata.length as u64, false);
-            }
+    /// Room for the header and `data_type` in the current function's frame,
+    /// allocated in its entry block so a literal in a loop reuses it.
+    fn stack_array_alloca(&self, data_type: ArrayType<'ctx>) -> PointerValue<'ctx> {
+        let i32_type = self.context.i32_type();
+        let frame_type = self
+            .context
+            .struct_type(&[i32_type.into(), i32_type.into(), data_type.into()], false);
+        let entry = self
+            .builder
+            .get_insert_block()
+            .and_then(|block| block.get_parent())
+            .and_then(|function| function.get_first_basic_block())
+            .unwrap();
+        let entry_builder = self.context.create_builder();
+        match entry.get_first_instruction() {
+            Some(first) => entry_builder.position_before(&first),
+            None => entry_builder.position_at_end(entry),
         }
-
-        // STEP 3: Try pointer equality matching
-        if let Some(sym) = self.symbols.get(array_name) {
-            if let Ok(loaded) = self.builder.build_load(sym.ty, sym.ptr, "check_ptr") {
-                if loaded.is_pointer_value() {
-                    let ptr_val = loaded.into_pointer_value();
-
-                    for (other_name, metadata) in &self.array_metadata {
-                        if let Some(other_val) = self.temp_values.get(other_name) {
-                            if other_val.is_pointer_value()
-                                && other_val.into_pointer_value() == ptr_val
-                            {
-                                return self
-                                    .context
-                                    .i32_type()
-                                    .const_int(metadata.length as u64, false);
-                            }
-                        }
-                    }
-                }
-            }
+        let alloca = entry_builder
+            .build_alloca(frame_type, "stack_array")
+            .unwrap();
+        if let Some(instr) = alloca.as_instruction() {
+            instr.set_alignment(8).unwrap();
         }
+        alloca
+    }
 
-        // STEP 4: CRITICAL - Runtime length extraction from heap header
-        // For dynamically created arrays (like innerarr), extract length at runtime
-
-        if let Some(sym) = self.symbols.get(array_name) {
-            if let Ok(loaded) = self.builder.build_load(sym.ty, sym.ptr, "runtime_load") {
-                if loaded.is_pointer_value() {
-                    let arr_ptr = loaded.into_pointer_value();
-
-                    // Array layout: [RC: 4 bytes][Length: 4 bytes][data at offset 8]
-                    // arr_ptr points to data, so length is at offset -4
-                    let len_ptr_result = unsafe {
-                        self.builder.build_in_bounds_gep(
-                            self.context.i8_type(),
-                            arr_ptr,
-                            &[self.context.i32_type().const_int((-4_i32) as u64, true)],
-                            &format!("{}_runtime_len_ptr", array_name),
-                        )
-                    };
+    /// Fills the array at `array_ptr` with `constants` by copying them from a
+    /// private global holding them.
+    fn copy_constant_elements(
+        &self,
+        array_ptr: PointerValue<'ctx>,
+        array_type: ArrayType<'ctx>,
+        constants: &[IntValue<'ctx>],
+    ) {
+        let init = self.module.add_global(array_type, None, "array_init");
+        init.set_initializer(&constants[0].get_type().const_array(constants));
+        init.set_constant(true);
+        init.set_linkage(Linkage::Private);
+        init.set_unnamed_addr(true);
+        self.builder
+            .build_call(
+                self.get_or_declare_memcpy(),
+                &[
+                    array_ptr.into(),
+                    init.as_pointer_value().into(),
+                    array_type.size_of().unwrap().into(),
+                    self.context.bool_type().const_zero().into(),
+                ],
+                "",
+            )
+            .unwrap();
+    }
 
-                    if let Ok(len_ptr) = len_ptr_result {
-                        let len_ptr_cast_result = self.builder.build_pointer_cast(
-                            len_ptr,
-                            self.context.ptr_type(inkwell::AddressSpace::default()),
-                            &format!("{}_len_ptr_cast", array_name),
-                        );
-
-                        if let Ok(len_ptr_cast) = len_ptr_cast_result {
-                            if let Ok(runtime_len) = self.builder.build_load(
-                                self.context.i32_type(),
-                                len_ptr_cast,
-                                &format!("{}_runtime_len", array_name),
-                            ) {
-                                eprintln!(
-                                    "[SUCCESS] Extracted runtime length for '{}'",
-                                    array_name
-                                );
-                                return runtime_len.into_int_value();
-                            }
-                        }
-                    }
-                }
-            }
+    /// Length of the array `array_name`, read from its heap header. Global
+    /// array constants have no header: theirs comes from their metadata.
+    pub fn get_array_length(&self, array_name: &str) -> inkwell::values::IntValue<'ctx> {
+        if let Some(array_ptr) = self.collection_ptr(array_name) {
+            return self.load_runtime_array_length(array_ptr);
         }
+        let length = self.array_metadata.get(array_name).map_or(0, |m| m.length);
+        self.context.i32_type().const_int(length as u64, false)
+    }
 
-        // FINAL FALLBACK: Return 0 to skip loop safely
-        self.context.i32_type().const_int(0, false)
+    /// The data pointer an array or map variable or temp holds, if it holds
+    /// one: every heap collection does, global constants are values.
+    pub(crate) fn collection_ptr(&self, name: &str) -> Option<inkwell::values::PointerValue<'ctx>> {
+        if !self.temp_values.contains_key(name) && !self.symbols.contains_key(name) {
+            return None;
+        }
+        let value = self.resolve_value(name);
+        value.is_pointer_value().then(|| value.into_pointer_value())
     }
 
     pub fn get_array_element_type(&self, array_name: &str) -> inkwell::types::BasicTypeEnum<'ctx> {
-        if let Some(metadata) = self.array_metadata.get(array_name) {
-            match metadata.element_type.as_str() {
-                "Int" => self.context.i32_type().into(), // Only i32 for integers
-                "Bool" => self.context.bool_type().into(),
-                "Str" => self.context.ptr_type(AddressSpace::default()).into(),
-                _ => self.context.i32_type().into(),
-            }
+        if let Some(metadata) = self.array_shape(array_name) {
+            self.struct_field_type(&metadata.element_type)
         } else {
             self.context.i32_type().into()
         }
@@ -309,13 +352,67 @@ impl<'ctx> CodeGen<'ctx> {
 
     /// Returns true if the array contains string elements.
     pub fn array_contains_strings(&self, array_name: &str) -> bool {
-        if let Some(metadata) = self.array_metadata.get(array_name) {
+        if let Some(metadata) = self.array_shape(array_name) {
             metadata.contains_strings
         } else {
             false
         }
     }
 
+    /// Looks up what is known about an array's elements: its own metadata,
+    /// or the shape it was given as a row of a nested array.
+    pub fn array_shape(&self, array_name: &str) -> Option<ArrayMetadata> {
+        self.array_metadata
+            .get(array_name)
+            .or_else(|| self.runtime_array_shapes.get(array_name))
+            .cloned()
+    }
+
+    /// Builds the element shape of an array from its MIR type, e.g.
+    /// `Array<Array<String>>`. Returns None for non-array types.
+    pub fn array_shape_from_type(ty: &MirType) -> Option<ArrayMetadata> {
+        let elem = ty.element()?;
+        let element_metadata = Self::array_shape_from_type(elem).map(Box::new);
+        Some(ArrayMetadata {
+            length: 0,
+            element_type: elem.clone(),
+            contains_strings: *elem == MirType::Str,
+            element_metadata,
+        })
+    }
+
+    /// The element type of a collection literal, read off the LLVM type its
+    /// elements were built with. Pointers are strings unless the caller knows
+    /// the elements to be nested collections.
+    pub fn literal_element_type(ty: BasicTypeEnum<'ctx>) -> MirType {
+        match ty {
+            BasicTypeEnum::PointerType(_) => MirType::Str,
+            BasicTypeEnum::FloatType(_) => MirType::Float,
+            BasicTypeEnum::IntType(int) if int.get_bit_width() == 64 => MirType::Int64,
+            _ => MirType::Int,
+        }
+    }
+
+    /// Reads an array's length from its heap header (stored 4 bytes before the data).
+    pub fn load_runtime_array_length(
+        &self,
+        array_ptr: inkwell::values::PointerValue<'ctx>,
+    ) -> inkwell::values::IntValue<'ctx> {
+        let len_ptr = unsafe {
+            self.builder.build_in_bounds_gep(
+                self.context.i8_type(),
+                array_ptr,
+                &[self.context.i32_type().const_int((-4_i32) as u64, true)],
+                "row_len_ptr",
+            )
+        }
+        .unwrap();
+        self.builder
+            .build_load(self.context.i32_type(), len_ptr, "row_len")
+            .unwrap()
+            .into_int_value()
+    }
+
     /// Load array element with proper RC management for strings
     pub fn load_array_element_with_rc(
         &mut self,
@@ -343,21 +440,7 @@ impl<'ctx> CodeGen<'ctx> {
 
         // If it's a heap-allocated string, increment RC
         if is_string {
-            let str_ptr = elem_val.into_pointer_value();
-            let rc_header = unsafe {
-                self.builder.build_in_bounds_gep(
-                    self.context.i8_type(),
-                    str_ptr,
-                    &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                    "rc_header",
-                )
-            }
-            .unwrap();
-
-            let incref = self.incref_fn.unwrap();
-            self.builder
-                .build_call(incref, &[rc_header.into()], "")
-                .unwrap();
+            self.incref_value(elem_val.into_pointer_value());
         }
 
         elem_val
@@ -382,24 +465,7 @@ impl<'ctx> CodeGen<'ctx> {
                         for str_name in str_names.clone() {
                             if let Some(val) = self.temp_values.get(&str_name) {
                                 if val.is_pointer_value() {
-                                    let data_ptr = val.into_pointer_value();
-                                    let rc_header = unsafe {
-                                        self.builder.build_in_bounds_gep(
-                                            self.context.i8_type(),
-                                            data_ptr,
-                                            &[self
-                                                .context
-                                                .i32_type()
-                                                .const_int((-8_i32) as u64, true)],
-                                            "rc_header",
-                                        )
-                                    }
-                                    .unwrap();
-
-                                    let decref = self.decref_fn.unwrap();
-                                    self.builder
-                                        .build_call(decref, &[rc_header.into()], "")
-                                        .unwrap();
+                                    self.decref_value(val.into_pointer_value());
                                 }
                             }
                         }
@@ -424,6 +490,22 @@ impl<'ctx> CodeGen<'ctx> {
             .build_call(printf_fn, &[open_bracket.as_pointer_value().into()], "")
             .unwrap();
 
+        // Arrays print as many elements as their header says; only global
+        // constants, which have none, are unrolled from their metadata
+        if let Some(shape) = self.array_shape(array_name) {
+            if let Some(array_ptr) = self.collection_ptr(array_name) {
+                self.print_array_elements_runtime(array_ptr, &shape);
+                let close_bracket = self
+                    .builder
+                    .build_global_string_ptr("]", "close_bracket")
+                    .unwrap();
+                self.builder
+                    .build_call(printf_fn, &[close_bracket.as_pointer_value().into()], "")
+                    .unwrap();
+                return;
+            }
+        }
+
         // Get array metadata
         let metadata = self.array_metadata.get(array_name).cloned();
 
@@ -445,13 +527,7 @@ impl<'ctx> CodeGen<'ctx> {
                 // For temporary arrays, resolve_value should work
                 self.resolve_value(array_name).into_pointer_value()
             };
-            let elem_type = if metadata.element_type == "Str" {
-                self.context
-                    .ptr_type(AddressSpace::default())
-                    .as_basic_type_enum()
-            } else {
-                self.context.i32_type().as_basic_type_enum()
-            };
+            let elem_type = self.struct_field_type(&metadata.element_type);
 
             let array_type = elem_type.array_type(metadata.length as u32);
             let typed_array_ptr = self
@@ -482,7 +558,24 @@ impl<'ctx> CodeGen<'ctx> {
                     .unwrap();
 
                 // Print the element based on its type
-                if metadata.element_type == "Str" {
+                if let Some(row_shape) = &metadata.element_metadata {
+                    let open_row = self
+                        .builder
+                        .build_global_string_ptr("[", "open_bracket")
+                        .unwrap();
+                    self.builder
+                        .build_call(printf_fn, &[open_row.as_pointer_value().into()], "")
+                        .unwrap();
+                    self.print_array_elements_runtime(elem_val.into_pointer_value(), row_shape);
+                    let close_fmt = if i < metadata.length - 1 { "], " } else { "]" };
+                    let close_row = self
+                        .builder
+                        .build_global_string_ptr(close_fmt, "close_bracket")
+                        .unwrap();
+                    self.builder
+                        .build_call(printf_fn, &[close_row.as_pointer_value().into()], "")
+                        .unwrap();
+                } else if metadata.element_type == MirType::Str {
                     let format_str = if i < metadata.length - 1 {
                         "\"%s\", "
                     } else {
@@ -529,4 +622,285 @@ impl<'ctx> CodeGen<'ctx> {
             .build_call(printf_fn, &[close_bracket.as_pointer_value().into()], "")
             .unwrap();
     }
+
+    /// Prints the elements of an array whose length is only known at runtime
+    /// (a row of a nested array), comma separated and without brackets.
+    /// Emits a loop over the length stored in the array's header; deeper rows recurse.
+    pub fn print_array_elements_runtime(
+        &mut self,
+        array_ptr: inkwell::values::PointerValue<'ctx>,
+        shape: &ArrayMetadata,
+    ) {
+        let printf_fn = self.get_or_declare_printf();
+        let i32_type = self.context.i32_type();
+        let current_func = self
+            .builder
+            .get_insert_block()
+            .unwrap()
+            .get_parent()
+            .unwrap();
+        let cond_block = self
+            .context
+            .append_basic_block(current_func, "row.print.cond");
+        let body_block = self
+            .context
+            .append_basic_block(current_func, "row.print.body");
+        let exit_block = self
+            .context
+            .append_basic_block(current_func, "row.print.exit");
+
+        let len = self.load_runtime_array_length(array_ptr);
+        let index_alloca = self.builder.build_alloca(i32_type, "row_index").unwrap();
+        self.builder
+            .build_store(index_alloca, i32_type.const_zero())
+            .unwrap();
+        self.builder.build_unconditional_branch(cond_block).unwrap();
+
+        // while index < len
+        self.builder.position_at_end(cond_block);
+        let index = self
+            .builder
+            .build_load(i32_type, index_alloca, "row_i")
+            .unwrap()
+            .into_int_value();
+        let in_bounds = self
+            .builder
+            .build_int_compare(inkwell::IntPredicate::SLT, index, len, "row_in_bounds")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(in_bounds, body_block, exit_block)
+            .unwrap();
+
+        // Separator before every element but the first
+        self.builder.position_at_end(body_block);
+        let is_first = self
+            .builder
+            .build_int_compare(
+                inkwell::IntPredicate::EQ,
+                index,
+                i32_type.const_zero(),
+                "row_first",
+            )
+            .unwrap();
+        let none = self
+            .builder
+            .build_global_string_ptr("", "row_sep_none")
+            .unwrap();
+        let comma = self
+            .builder
+            .build_global_string_ptr(", ", "row_sep")
+            .unwrap();
+        let sep = self
+            .builder
+            .build_select(
+                is_first,
+                none.as_pointer_value(),
+                comma.as_pointer_value(),
+                "row_sep_sel",
+            )
+            .unwrap();
+        let sep_fmt = self
+            .builder
+            .build_global_string_ptr("%s", "row_sep_fmt")
+            .unwrap();
+        self.builder
+            .build_call(
+                printf_fn,
+                &[sep_fmt.as_pointer_value().into(), sep.into()],
+                "",
+            )
+            .unwrap();
+
+        let elem_type = self.struct_field_type(&shape.element_type);
+        let elem_ptr = unsafe {
+            self.builder
+                .build_in_bounds_gep(elem_type, array_ptr, &[index], "row_elem_ptr")
+        }
+        .unwrap();
+        let elem_val = self
+            .builder
+            .build_load(elem_type, elem_ptr, "row_elem")
+            .unwrap();
+
+        if let Some(inner_shape) = &shape.element_metadata {
+            let open = self
+                .builder
+                .build_global_string_ptr("[", "open_bracket")
+                .unwrap();
+            self.builder
+                .build_call(printf_fn, &[open.as_pointer_value().into()], "")
+                .unwrap();
+            // Leaves the builder in the inner loop's exit block
+            self.print_array_elements_runtime(elem_val.into_pointer_value(), inner_shape);
+            let close = self
+                .builder
+                .build_global_string_ptr("]", "close_bracket")
+                .unwrap();
+            self.builder
+                .build_call(printf_fn, &[close.as_pointer_value().into()], "")
+                .unwrap();
+        } else {
+            let fmt = if shape.element_type == MirType::Str {
+                "\"%s\""
+            } else {
+                "%d"
+            };
+            let fmt_global = self
+                .builder
+                .build_global_string_ptr(fmt, "array_elem_fmt")
+                .unwrap();
+            self.builder
+                .build_call(
+                    printf_fn,
+                    &[fmt_global.as_pointer_value().into(), elem_val.into()],
+                    "",
+                )
+                .unwrap();
+        }
+
+        let next = self
+            .builder
+            .build_int_add(index, i32_type.const_int(1, false), "row_next")
+            .unwrap();
+        self.builder.build_store(index_alloca, next).unwrap();
+        self.builder.build_unconditional_branch(cond_block).unwrap();
+
+        self.builder.position_at_end(exit_block);
+    }
+
+    /// `left + right` on arrays: a new array holding the elements of `left`,
+    /// then those of `right`. Heap elements gain a reference held by it.
+    pub fn generate_array_concat(
+        &mut self,
+        name: &str,
+        left: &str,
+        right: &str,
+        ty: &MirType,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let elem_ty = ty.element().unwrap_or(ty);
+        let elem_type = self.struct_field_type(elem_ty);
+        let elem_size = elem_type.size_of().unwrap();
+        let i64_type = self.context.i64_type();
+        let left_ptr = self.resolve_value(left).into_pointer_value();
+        let right_ptr = self.resolve_value(right).into_pointer_value();
+        let left_len = self.load_runtime_array_length(left_ptr);
+        let right_len = self.load_runtime_array_length(right_ptr);
+        let total = self
+            .builder
+            .build_int_add(left_len, right_len, "concat_len")
+            .unwrap();
+
+        // A null block makes `__array_reserve` allocate an empty array with room for `total`
+        let null = self.context.ptr_type(AddressSpace::default()).const_null();
+        let data = self.reserve_block(null, total, elem_size, 0, 0);
+        let byte_size = |len: inkwell::values::IntValue<'ctx>| {
+            let len = self
+                .builder
+                .build_int_z_extend(len, i64_type, "concat_count")
+                .unwrap();
+            self.builder
+                .build_int_mul(len, elem_size, "concat_size")
+                .unwrap()
+        };
+        self.builder
+            .build_memcpy(data, 1, left_ptr, 1, byte_size(left_len))
+            .unwrap();
+        let right_dest = unsafe {
+            self.builder
+                .build_in_bounds_gep(elem_type, data, &[left_len], "concat_right")
+        }
+        .unwrap();
+        self.builder
+            .build_memcpy(right_dest, 1, right_ptr, 1, byte_size(right_len))
+            .unwrap();
+        let len_ptr = unsafe {
+            self.builder.build_in_bounds_gep(
+                self.context.i8_type(),
+                data,
+                &[self.context.i32_type().const_int((-4_i32) as u64, true)],
+                "concat_len_ptr",
+            )
+        }
+        .unwrap();
+        self.builder.build_store(len_ptr, total).unwrap();
+        if Self::field_is_rc(elem_ty) {
+            self.incref_array_elements(data, total);
+        }
+
+        let val: BasicValueEnum<'ctx> = data.into();
+        self.heap_arrays.insert(name.to_string());
+        if let Some(shape) = Self::array_shape_from_type(ty) {
+            self.runtime_array_shapes.insert(name.to_string(), shape);
+        }
+        if let Some(sym) = self.symbols.get(name) {
+            self.builder.build_store(sym.ptr, val).unwrap();
+        }
+        self.temp_values.insert(name.to_string(), val);
+        Some(val)
+    }
+
+    /// Increfs each of the `len` pointer elements of the array `data`.
+    fn incref_array_elements(
+        &mut self,
+        data: inkwell::values::PointerValue<'ctx>,
+        len: inkwell::values::IntValue<'ctx>,
+    ) {
+        let i32_type = self.context.i32_type();
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let current_func = self
+            .builder
+            .get_insert_block()
+            .unwrap()
+            .get_parent()
+            .unwrap();
+        let cond_block = self
+            .context
+            .append_basic_block(current_func, "array.incref.cond");
+        let body_block = self
+            .context
+            .append_basic_block(current_func, "array.incref.body");
+        let exit_block = self
+            .context
+            .append_basic_block(current_func, "array.incref.exit");
+        let index_alloca = self.builder.build_alloca(i32_type, "incref_index").unwrap();
+        self.builder
+            .build_store(index_alloca, i32_type.const_zero())
+            .unwrap();
+        self.builder.build_unconditional_branch(cond_block).unwrap();
+
+        self.builder.position_at_end(cond_block);
+        let index = self
+            .builder
+            .build_load(i32_type, index_alloca, "incref_i")
+            .unwrap()
+            .into_int_value();
+        let in_bounds = self
+            .builder
+            .build_int_compare(inkwell::IntPredicate::SLT, index, len, "incref_in_bounds")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(in_bounds, body_block, exit_block)
+            .unwrap();
+
+        self.builder.position_at_end(body_block);
+        let elem_ptr = unsafe {
+            self.builder
+                .build_in_bounds_gep(ptr_type, data, &[index], "incref_elem_ptr")
+        }
+        .unwrap();
+        let elem = self
+            .builder
+            .build_load(ptr_type, elem_ptr, "incref_elem")
+            .unwrap()
+            .into_pointer_value();
+        self.incref_value(elem);
+        let next = self
+            .builder
+            .build_int_add(index, i32_type.const_int(1, false), "incref_next")
+            .unwrap();
+        self.builder.build_store(index_alloca, next).unwrap();
+        self.builder.build_unconditional_branch(cond_block).unwrap();
+
+        self.builder.position_at_end(exit_block);
+    }
 }
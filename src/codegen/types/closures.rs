@@ -0,0 +1,297 @@
+use crate::codegen::core::CodeGen;
+use crate::mir::types::CLOSURE_ENV;
+use crate::mir::MirType;
+use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FunctionType, StructType};
+use inkwell::values::{BasicMetadataValueEnum, BasicValueEnum, FunctionValue, PointerValue};
+use inkwell::AddressSpace;
+
+/// Closure values live on the heap like struct values, as their environment:
+/// Layout: [RC: 4 bytes][weak count: 4 bytes][trampoline][drop][captures...]
+/// A closure value is the pointer to its environment. The trampoline
+/// `<func>.tramp` takes the environment ahead of the closure's arguments and
+/// calls the lifted function `func` with the captured values first; callers
+/// only know the closure's type, so every call goes through it. Captures hold
+/// their own reference like struct fields, and `<func>.drop` releases them
+/// when `__release_fn` drops the last reference to the environment. A closure
+/// call's heap result is always owned by the caller.
+impl<'ctx> CodeGen<'ctx> {
+    /// The LLVM struct of an environment capturing values of `captures`.
+    fn closure_env_type(&self, captures: &[MirType]) -> StructType<'ctx> {
+        let ptr_type: BasicTypeEnum<'ctx> = self.context.ptr_type(AddressSpace::default()).into();
+        let fields: Vec<BasicTypeEnum<'ctx>> = [ptr_type, ptr_type]
+            .into_iter()
+            .chain(captures.iter().map(|ty| self.struct_field_type(ty)))
+            .collect();
+        self.context.struct_type(&fields, false)
+    }
+
+    /// Signature of the trampolines of closures of type `ty`.
+    fn closure_call_type(&self, ty: &MirType) -> FunctionType<'ctx> {
+        let MirType::Function(params, ret) = ty else {
+            unreachable!("closure calls have a function type");
+        };
+        let param_types: Vec<BasicMetadataTypeEnum<'ctx>> =
+            std::iter::once(self.context.ptr_type(AddressSpace::default()).into())
+                .chain(params.iter().map(|ty| self.struct_field_type(ty).into()))
+                .collect();
+        match **ret {
+            MirType::Void => self.context.void_type().fn_type(&param_types, false),
+            ref ret => self.struct_field_type(ret).fn_type(&param_types, false),
+        }
+    }
+
+    /// `make_closure func(captures)`: allocates the environment with RC = 1
+    /// and stores the trampoline, the drop function and the captured values.
+    pub fn generate_make_closure(
+        &mut self,
+        name: &str,
+        func: &str,
+        captures: &[String],
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let capture_types = self
+            .closure_captures
+            .get(&Self::llvm_function_name(func))
+            .cloned()
+            .unwrap_or_default();
+        let env_type = self.closure_env_type(&capture_types);
+        let env = self.alloc_rc_data(env_type);
+
+        let trampoline = self.get_or_create_trampoline(func, &capture_types);
+        let drop = self.get_or_create_closure_drop(func, &capture_types);
+        let slots =
+            [trampoline, drop].map(|function| function.as_global_value().as_pointer_value().into());
+        let values = slots
+            .into_iter()
+            .chain(
+                captures
+                    .iter()
+                    .zip(&capture_types)
+                    .map(|(capture, ty)| self.owned_field_value(ty, capture)),
+            )
+            .collect::<Vec<BasicValueEnum<'ctx>>>();
+        for (index, value) in values.into_iter().enumerate() {
+            let slot = self
+                .builder
+                .build_struct_gep(env_type, env, index as u32, "env_slot")
+                .unwrap();
+            self.builder.build_store(slot, value).unwrap();
+        }
+
+        if let Some(sym) = self.symbols.get(name) {
+            self.builder.build_store(sym.ptr, env).unwrap();
+        }
+        self.temp_values.insert(name.to_string(), env.into());
+        self.heap_structs
+            .insert(name.to_string(), CLOSURE_ENV.to_string());
+        Some(env.into())
+    }
+
+    /// `call_closure closure(args) : ty`: calls the closure's trampoline with its
+    /// environment and the arguments. A heap result is owned by `dest`.
+    pub fn generate_call_closure(
+        &mut self,
+        dest: &[String],
+        closure: &str,
+        args: &[String],
+        ty: &MirType,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let env = self.resolve_value(closure).into_pointer_value();
+        // The trampoline is the environment's first slot
+        let trampoline = self
+            .builder
+            .build_load(ptr_type, env, "trampoline")
+            .unwrap()
+            .into_pointer_value();
+        let arg_values: Vec<BasicMetadataValueEnum<'ctx>> = std::iter::once(env.into())
+            .chain(args.iter().map(|arg| self.resolve_value(arg).into()))
+            .collect();
+        let result = self
+            .builder
+            .build_indirect_call(
+                self.closure_call_type(ty),
+                trampoline,
+                &arg_values,
+                "closure_result",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()?;
+
+        let dest = dest.first()?;
+        self.temp_values.insert(dest.clone(), result);
+        let MirType::Function(_, ret) = ty else {
+            return Some(result);
+        };
+        if let Some(struct_name) = ret.struct_name() {
+            self.heap_structs
+                .insert(dest.clone(), struct_name.to_string());
+        } else {
+            match ret.optional_payload() {
+                MirType::Str => {
+                    self.heap_strings.insert(dest.clone());
+                }
+                MirType::Array(_) => {
+                    self.heap_arrays.insert(dest.clone());
+                }
+                MirType::Map(_, _) => {
+                    self.heap_maps.insert(dest.clone());
+                }
+                _ => {}
+            }
+        }
+        Some(result)
+    }
+
+    /// Whether a closure returning `ty` hands its caller a reference of its own.
+    pub fn closure_result_is_owned(ty: &MirType) -> bool {
+        ty.struct_name().is_some()
+            || matches!(
+                ty.optional_payload(),
+                MirType::Str | MirType::Array(_) | MirType::Map(_, _)
+            )
+    }
+
+    /// Emits (once) `<func>.tramp(ptr env, params...)`: loads the captured
+    /// values from the environment and calls `func` with them first.
+    fn get_or_create_trampoline(
+        &self,
+        func: &str,
+        capture_types: &[MirType],
+    ) -> FunctionValue<'ctx> {
+        let lifted_name = Self::llvm_function_name(func);
+        let fn_name = format!("{}.tramp", lifted_name);
+        if let Some(function) = self.module.get_function(&fn_name) {
+            return function;
+        }
+        let lifted = self
+            .module
+            .get_function(&lifted_name)
+            .expect("closure functions are declared up front");
+        let lifted_type = lifted.get_type();
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let param_types: Vec<BasicMetadataTypeEnum<'ctx>> = std::iter::once(ptr_type.into())
+            .chain(
+                lifted_type.get_param_types()[capture_types.len()..]
+                    .iter()
+                    .copied(),
+            )
+            .collect();
+        let fn_type = match lifted_type.get_return_type() {
+            Some(ret) => ret.fn_type(&param_types, false),
+            None => self.context.void_type().fn_type(&param_types, false),
+        };
+        let function =
+            self.module
+                .add_function(&fn_name, fn_type, Some(inkwell::module::Linkage::Internal));
+
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+        let env = function.get_nth_param(0).unwrap().into_pointer_value();
+        let env_type = self.closure_env_type(capture_types);
+        let mut args: Vec<BasicMetadataValueEnum<'ctx>> = capture_types
+            .iter()
+            .enumerate()
+            .map(|(index, ty)| {
+                let slot = self
+                    .builder
+                    .build_struct_gep(env_type, env, index as u32 + 2, "capture_ptr")
+                    .unwrap();
+                self.builder
+                    .build_load(self.struct_field_type(ty), slot, "capture")
+                    .unwrap()
+                    .into()
+            })
+            .collect();
+        args.extend(
+            function
+                .get_param_iter()
+                .skip(1)
+                .map(BasicMetadataValueEnum::from),
+        );
+        let result = self
+            .builder
+            .build_call(lifted, &args, "result")
+            .unwrap()
+            .try_as_basic_value()
+            .left();
+        match result {
+            Some(result) => self.builder.build_return(Some(&result)).unwrap(),
+            None => self.builder.build_return(None).unwrap(),
+        };
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// Emits (once) `void <func>.drop(ptr env)`, which releases the captured values.
+    fn get_or_create_closure_drop(
+        &self,
+        func: &str,
+        capture_types: &[MirType],
+    ) -> FunctionValue<'ctx> {
+        let fn_name = format!("{}.drop", Self::llvm_function_name(func));
+        if let Some(function) = self.module.get_function(&fn_name) {
+            return function;
+        }
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let function = self.module.add_function(
+            &fn_name,
+            self.context.void_type().fn_type(&[ptr_type.into()], false),
+            Some(inkwell::module::Linkage::Internal),
+        );
+
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+        let env = function.get_nth_param(0).unwrap().into_pointer_value();
+        let env_type = self.closure_env_type(capture_types);
+        for (index, ty) in capture_types.iter().enumerate() {
+            if !Self::field_is_rc(ty) {
+                continue;
+            }
+            let slot = self
+                .builder
+                .build_struct_gep(env_type, env, index as u32 + 2, "capture_ptr")
+                .unwrap();
+            let val = self
+                .builder
+                .build_load(self.struct_field_type(ty), slot, "capture")
+                .unwrap();
+            self.release_field_value(ty, val);
+        }
+        self.builder.build_return(None).unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// Releases the captured values of the closure environment `env` through
+    /// the drop function stored in it.
+    pub fn release_closure_captures(&self, env: PointerValue<'ctx>) {
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let env_type = self.closure_env_type(&[]);
+        let slot = self
+            .builder
+            .build_struct_gep(env_type, env, 1, "drop_ptr")
+            .unwrap();
+        let drop = self
+            .builder
+            .build_load(ptr_type, slot, "drop")
+            .unwrap()
+            .into_pointer_value();
+        self.builder
+            .build_indirect_call(
+                self.context.void_type().fn_type(&[ptr_type.into()], false),
+                drop,
+                &[env.into()],
+                "",
+            )
+            .unwrap();
+    }
+}
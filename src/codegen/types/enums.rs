@@ -0,0 +1,404 @@
+use crate::codegen::core::CodeGen;
+use crate::mir::MirType;
+use inkwell::types::{BasicTypeEnum, StructType};
+use inkwell::values::{BasicValueEnum, FunctionValue, IntValue, PointerValue};
+use inkwell::{AddressSpace, FloatPredicate, IntPredicate};
+
+/// Enum values live on the heap like struct values:
+/// Layout: [RC: 4 bytes][padding: 4 bytes][tag: i32][payload]
+/// The tag is the variant's position in the declaration. Each variant has its
+/// own LLVM struct `{ i32, payload }` (just `{ i32 }` for unit variants), and a
+/// value is allocated with the size of the variant it holds: enum values are
+/// never changed in place. Heap payloads are owned by the enum value and released
+/// with it through `__release_<Name>`, like struct fields. Two values are
+/// equal when they hold the same variant with equal payloads (`__eq_<Name>`).
+impl<'ctx> CodeGen<'ctx> {
+    /// Tag and payload type of `enum_name::variant`.
+    fn enum_variant(&self, enum_name: &str, variant: &str) -> Option<(u32, Option<MirType>)> {
+        let variants = self.enum_layouts.get(enum_name)?;
+        let index = variants.iter().position(|(v, _)| v == variant)?;
+        Some((index as u32, variants[index].1.clone()))
+    }
+
+    /// The named LLVM struct of one variant: the tag, then the payload if any.
+    fn enum_variant_type(&self, enum_name: &str, variant: &str) -> StructType<'ctx> {
+        let llvm_name = format!("enum.{}.{}", enum_name, variant);
+        if let Some(st) = self.module.get_struct_type(&llvm_name) {
+            return st;
+        }
+        let st = self.context.opaque_struct_type(&llvm_name);
+        let mut fields: Vec<BasicTypeEnum<'ctx>> = vec![self.context.i32_type().into()];
+        if let Some((_, Some(payload))) = self.enum_variant(enum_name, variant) {
+            fields.push(self.struct_field_type(&payload));
+        }
+        st.set_body(&fields, false);
+        st
+    }
+
+    /// Loads the tag of the enum value `data_ptr`.
+    fn load_enum_tag(&self, data_ptr: PointerValue<'ctx>) -> IntValue<'ctx> {
+        self.builder
+            .build_load(self.context.i32_type(), data_ptr, "enum_tag")
+            .unwrap()
+            .into_int_value()
+    }
+
+    /// `Shape::Circle(2.0)`: allocates the value with RC = 1 and stores the tag
+    /// and the payload, which holds its own reference like a struct field.
+    pub fn generate_enum_init(
+        &mut self,
+        name: &str,
+        enum_name: &str,
+        variant: &str,
+        value: &Option<String>,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let (tag, payload_type) = self.enum_variant(enum_name, variant)?;
+        let variant_type = self.enum_variant_type(enum_name, variant);
+        let data_ptr = self.alloc_rc_data(variant_type);
+        self.builder
+            .build_store(
+                data_ptr,
+                self.context.i32_type().const_int(tag as u64, false),
+            )
+            .unwrap();
+
+        if let (Some(ty), Some(value)) = (payload_type, value) {
+            let val = self.owned_field_value(&ty, value);
+            let payload_ptr = self
+                .builder
+                .build_struct_gep(variant_type, data_ptr, 1, "payload_ptr")
+                .unwrap();
+            self.builder.build_store(payload_ptr, val).unwrap();
+        }
+
+        if let Some(sym) = self.symbols.get(name) {
+            self.builder.build_store(sym.ptr, data_ptr).unwrap();
+        }
+        self.temp_values.insert(name.to_string(), data_ptr.into());
+        self.heap_structs
+            .insert(name.to_string(), enum_name.to_string());
+        Some(data_ptr.into())
+    }
+
+    /// `Shape::Circle(..)` as a match pattern: a Bool telling whether the value holds that variant.
+    pub fn generate_enum_match(
+        &mut self,
+        name: &str,
+        instance: &str,
+        enum_name: &str,
+        variant: &str,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let (tag, _) = self.enum_variant(enum_name, variant)?;
+        let data_ptr = self.resolve_value(instance);
+        if !data_ptr.is_pointer_value() {
+            return None;
+        }
+        let value_tag = self.load_enum_tag(data_ptr.into_pointer_value());
+        let is_variant = self
+            .builder
+            .build_int_compare(
+                inkwell::IntPredicate::EQ,
+                value_tag,
+                self.context.i32_type().const_int(tag as u64, false),
+                "is_variant",
+            )
+            .unwrap();
+        let is_variant = self.bool_value(is_variant);
+        self.temp_values.insert(name.to_string(), is_variant.into());
+        Some(is_variant.into())
+    }
+
+    /// The payload bound by `Shape::Circle(r) => ...`. It borrows the enum value's reference.
+    pub fn generate_enum_payload(
+        &mut self,
+        name: &str,
+        instance: &str,
+        enum_name: &str,
+        variant: &str,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let (_, Some(ty)) = self.enum_variant(enum_name, variant)? else {
+            return None;
+        };
+        let data_ptr = self.resolve_value(instance);
+        if !data_ptr.is_pointer_value() {
+            return None;
+        }
+        let payload_ptr = self
+            .builder
+            .build_struct_gep(
+                self.enum_variant_type(enum_name, variant),
+                data_ptr.into_pointer_value(),
+                1,
+                "payload_ptr",
+            )
+            .unwrap();
+        let val = self
+            .builder
+            .build_load(self.struct_field_type(&ty), payload_ptr, "payload")
+            .unwrap();
+        if let Some(sym) = self.symbols.get(name) {
+            self.builder.build_store(sym.ptr, val).unwrap();
+        }
+        self.temp_values.insert(name.to_string(), val);
+        Some(val)
+    }
+
+    /// Releases the payload of the enum value `data_ptr` inside its release
+    /// function: one test of the tag per variant with a heap payload. The
+    /// builder ends up in the block after the last test.
+    pub fn release_enum_payload(
+        &self,
+        function: FunctionValue<'ctx>,
+        enum_name: &str,
+        data_ptr: PointerValue<'ctx>,
+    ) {
+        let variants = self
+            .enum_layouts
+            .get(enum_name)
+            .cloned()
+            .unwrap_or_default();
+        let tag = self.load_enum_tag(data_ptr);
+        for (index, (variant, payload)) in variants.iter().enumerate() {
+            let Some(ty) = payload.as_ref().filter(|ty| Self::field_is_rc(ty)) else {
+                continue;
+            };
+            let release_bb = self
+                .context
+                .append_basic_block(function, &format!("release_{}", variant));
+            let next_bb = self.context.append_basic_block(function, "next_variant");
+            let is_variant = self
+                .builder
+                .build_int_compare(
+                    inkwell::IntPredicate::EQ,
+                    tag,
+                    self.context.i32_type().const_int(index as u64, false),
+                    "is_variant",
+                )
+                .unwrap();
+            self.builder
+                .build_conditional_branch(is_variant, release_bb, next_bb)
+                .unwrap();
+
+            self.builder.position_at_end(release_bb);
+            let payload_ptr = self
+                .builder
+                .build_struct_gep(
+                    self.enum_variant_type(enum_name, variant),
+                    data_ptr,
+                    1,
+                    "payload_ptr",
+                )
+                .unwrap();
+            let val = self
+                .builder
+                .build_load(self.struct_field_type(ty), payload_ptr, "payload")
+                .unwrap();
+            self.release_field_value(ty, val);
+            self.builder.build_unconditional_branch(next_bb).unwrap();
+
+            self.builder.position_at_end(next_bb);
+        }
+    }
+
+    /// `left == right` (or `!=`) on two values of the enum `enum_name`.
+    pub fn generate_enum_compare(
+        &mut self,
+        op: &str,
+        name: &str,
+        left: &str,
+        right: &str,
+        enum_name: &str,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let left_ptr = self.resolve_value(left);
+        let right_ptr = self.resolve_value(right);
+        if !left_ptr.is_pointer_value() || !right_ptr.is_pointer_value() {
+            return None;
+        }
+        let eq_fn = self.get_or_create_enum_eq_fn(enum_name);
+        let is_equal = self
+            .builder
+            .build_call(eq_fn, &[left_ptr.into(), right_ptr.into()], "enum_eq")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let result = if op == "ne" {
+            self.builder.build_not(is_equal, "enum_ne").unwrap()
+        } else {
+            is_equal
+        };
+        let result = self.bool_value(result);
+
+        self.temp_values.insert(name.to_string(), result.into());
+        if let Some(sym) = self.symbols.get(name) {
+            self.builder.build_store(sym.ptr, result).unwrap();
+        }
+        Some(result.into())
+    }
+
+    /// Emits (once) `i1 __eq_<Name>(ptr a, ptr b)`: whether the two values
+    /// hold the same variant with equal payloads. Numbers compare by value,
+    /// strings by contents and enums through their own `__eq_`; arrays, maps
+    /// and structs are equal only when they are the same value.
+    fn get_or_create_enum_eq_fn(&self, enum_name: &str) -> FunctionValue<'ctx> {
+        let fn_name = format!("__eq_{}", enum_name);
+        if let Some(func) = self.module.get_function(&fn_name) {
+            return func;
+        }
+
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let bool_type = self.context.bool_type();
+        let function = self.module.add_function(
+            &fn_name,
+            bool_type.fn_type(&[ptr_type.into(), ptr_type.into()], false),
+            None,
+        );
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        let check_tags = self.context.append_basic_block(function, "check_tags");
+        let compare_payloads = self
+            .context
+            .append_basic_block(function, "compare_payloads");
+        let equal = self.context.append_basic_block(function, "equal");
+        let not_equal = self.context.append_basic_block(function, "not_equal");
+
+        // The same value is always equal to itself
+        self.builder.position_at_end(entry);
+        let left = function.get_nth_param(0).unwrap().into_pointer_value();
+        let right = function.get_nth_param(1).unwrap().into_pointer_value();
+        let same = self
+            .builder
+            .build_int_compare(IntPredicate::EQ, left, right, "same")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(same, equal, check_tags)
+            .unwrap();
+
+        self.builder.position_at_end(check_tags);
+        let tag = self.load_enum_tag(left);
+        let right_tag = self.load_enum_tag(right);
+        let same_tag = self
+            .builder
+            .build_int_compare(IntPredicate::EQ, tag, right_tag, "same_tag")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(same_tag, compare_payloads, not_equal)
+            .unwrap();
+
+        // One case per variant with a payload; unit variants are equal by tag
+        self.builder.position_at_end(compare_payloads);
+        let variants = self
+            .enum_layouts
+            .get(enum_name)
+            .cloned()
+            .unwrap_or_default();
+        let mut cases = Vec::new();
+        for (index, (variant, payload)) in variants.iter().enumerate() {
+            let Some(ty) = payload else {
+                continue;
+            };
+            let case = self
+                .context
+                .append_basic_block(function, &format!("compare_{}", variant));
+            cases.push((self.context.i32_type().const_int(index as u64, false), case));
+
+            self.builder.position_at_end(case);
+            let variant_type = self.enum_variant_type(enum_name, variant);
+            let field_type = self.struct_field_type(ty);
+            let mut payloads = [left, right].into_iter().map(|data_ptr| {
+                let payload_ptr = self
+                    .builder
+                    .build_struct_gep(variant_type, data_ptr, 1, "payload_ptr")
+                    .unwrap();
+                self.builder
+                    .build_load(field_type, payload_ptr, "payload")
+                    .unwrap()
+            });
+            let (a, b) = (payloads.next().unwrap(), payloads.next().unwrap());
+            let payload_equal = self.build_payload_eq(ty, a, b);
+            self.builder
+                .build_conditional_branch(payload_equal, equal, not_equal)
+                .unwrap();
+        }
+        self.builder.position_at_end(compare_payloads);
+        self.builder.build_switch(tag, equal, &cases).unwrap();
+
+        self.builder.position_at_end(equal);
+        self.builder
+            .build_return(Some(&bool_type.const_int(1, false)))
+            .unwrap();
+        self.builder.position_at_end(not_equal);
+        self.builder
+            .build_return(Some(&bool_type.const_zero()))
+            .unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// Whether two payloads of type `ty` are equal, for `__eq_<Name>`.
+    fn build_payload_eq(
+        &self,
+        ty: &MirType,
+        a: BasicValueEnum<'ctx>,
+        b: BasicValueEnum<'ctx>,
+    ) -> IntValue<'ctx> {
+        match (ty, a, b) {
+            (MirType::Str, BasicValueEnum::PointerValue(a), BasicValueEnum::PointerValue(b)) => {
+                let cmp = self
+                    .builder
+                    .build_call(
+                        self.get_or_declare_strcmp(),
+                        &[a.into(), b.into()],
+                        "strcmp",
+                    )
+                    .unwrap()
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap()
+                    .into_int_value();
+                self.builder
+                    .build_int_compare(
+                        IntPredicate::EQ,
+                        cmp,
+                        self.context.i32_type().const_zero(),
+                        "str_eq",
+                    )
+                    .unwrap()
+            }
+            (
+                MirType::Enum(name),
+                BasicValueEnum::PointerValue(a),
+                BasicValueEnum::PointerValue(b),
+            ) if self.enum_layouts.contains_key(name) => self
+                .builder
+                .build_call(
+                    self.get_or_create_enum_eq_fn(name),
+                    &[a.into(), b.into()],
+                    "enum_eq",
+                )
+                .unwrap()
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_int_value(),
+            (_, BasicValueEnum::IntValue(a), BasicValueEnum::IntValue(b)) => self
+                .builder
+                .build_int_compare(IntPredicate::EQ, a, b, "payload_eq")
+                .unwrap(),
+            (_, BasicValueEnum::FloatValue(a), BasicValueEnum::FloatValue(b)) => self
+                .builder
+                .build_float_compare(FloatPredicate::OEQ, a, b, "payload_eq")
+                .unwrap(),
+            (_, BasicValueEnum::PointerValue(a), BasicValueEnum::PointerValue(b)) => self
+                .builder
+                .build_int_compare(IntPredicate::EQ, a, b, "payload_eq")
+                .unwrap(),
+            // Optional numbers and flags, held by value, never compare equal
+            _ => self.context.bool_type().const_zero(),
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use crate::codegen::core::CodeGen;
+use crate::mir::MirType;
+use inkwell::values::{BasicValueEnum, FunctionValue, PointerValue};
+use inkwell::{AddressSpace, IntPredicate};
+
+/// `print` shows an array or map whose type the MIR gives it through
+/// `__print_<tag>(ptr)`, emitted once per type. The function reads the length
+/// from the value's header and prints each element by its type, calling the
+/// printer of nested arrays and maps in turn, so it works for any value of the
+/// type: built at run time, returned by a function, or grown in a loop.
+///
+/// The tag spells the type: `i` Int, `l` Int64, `f` Float, `b` Bool, `s` Str,
+/// `a<element>` arrays and `m<key><value>` maps, so `{Str: [Int]}` prints
+/// through `__print_msai`. Collections of other types have no tag and are
+/// printed from their metadata as before.
+impl<'ctx> CodeGen<'ctx> {
+    /// The printer tag of `ty`, or None when there is no printer for it.
+    pub fn print_tag(ty: &MirType) -> Option<String> {
+        match ty {
+            MirType::Int => Some("i".to_string()),
+            MirType::Int64 => Some("l".to_string()),
+            MirType::Float => Some("f".to_string()),
+            MirType::Bool => Some("b".to_string()),
+            MirType::Str => Some("s".to_string()),
+            MirType::Array(elem) => Some(format!("a{}", Self::print_tag(elem)?)),
+            MirType::Map(key, value) => Some(format!(
+                "m{}{}",
+                Self::print_tag(key)?,
+                Self::print_tag(value)?
+            )),
+            _ => None,
+        }
+    }
+
+    /// Prints the array or map `value` of type `ty` through its printer.
+    /// Returns false, printing nothing, when `ty` has no printer or `value`
+    /// is a global constant rather than a pointer to a heap value.
+    pub fn print_formatted(&mut self, value: &str, ty: &MirType) -> bool {
+        let Some(tag) = Self::print_tag(ty) else {
+            return false;
+        };
+        let Some(ptr) = self.collection_ptr(value) else {
+            return false;
+        };
+        let printer = self.get_or_create_printer(ty, &tag);
+        self.builder.build_call(printer, &[ptr.into()], "").unwrap();
+        true
+    }
+
+    /// Emits (once) `void __print_<tag>(ptr value)` for the array or map type `ty`.
+    fn get_or_create_printer(&self, ty: &MirType, tag: &str) -> FunctionValue<'ctx> {
+        let fn_name = format!("__print_{}", tag);
+        if let Some(func) = self.module.get_function(&fn_name) {
+            return func;
+        }
+
+        let i32_type = self.context.i32_type();
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let function = self.module.add_function(
+            &fn_name,
+            self.context.void_type().fn_type(&[ptr_type.into()], false),
+            None,
+        );
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        let cond = self.context.append_basic_block(function, "cond");
+        let body = self.context.append_basic_block(function, "body");
+        let exit = self.context.append_basic_block(function, "exit");
+
+        self.builder.position_at_end(entry);
+        let collection = function.get_nth_param(0).unwrap().into_pointer_value();
+        let (open, close) = match ty {
+            MirType::Map(..) => ("{", "}"),
+            _ => ("[", "]"),
+        };
+        self.print_text(open);
+        // An empty literal may be a null pointer, which reads as length 0
+        let len = self.load_runtime_map_length(collection);
+        let index_alloca = self.builder.build_alloca(i32_type, "index").unwrap();
+        self.builder
+            .build_store(index_alloca, i32_type.const_zero())
+            .unwrap();
+        self.builder.build_unconditional_branch(cond).unwrap();
+
+        // while index < len
+        self.builder.position_at_end(cond);
+        let index = self
+            .builder
+            .build_load(i32_type, index_alloca, "i")
+            .unwrap()
+            .into_int_value();
+        let in_bounds = self
+            .builder
+            .build_int_compare(IntPredicate::SLT, index, len, "in_bounds")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(in_bounds, body, exit)
+            .unwrap();
+
+        // Separator before every element but the first
+        self.builder.position_at_end(body);
+        let is_first = self
+            .builder
+            .build_int_compare(IntPredicate::EQ, index, i32_type.const_zero(), "first")
+            .unwrap();
+        let none = self
+            .builder
+            .build_global_string_ptr("", "sep_none")
+            .unwrap();
+        let comma = self.builder.build_global_string_ptr(", ", "sep").unwrap();
+        let sep = self
+            .builder
+            .build_select(
+                is_first,
+                none.as_pointer_value(),
+                comma.as_pointer_value(),
+                "sep_sel",
+            )
+            .unwrap()
+            .into_pointer_value();
+        self.print_string(sep);
+
+        match ty {
+            MirType::Map(key_ty, value_ty) => {
+                let key_type = self.struct_field_type(key_ty);
+                let value_type = self.struct_field_type(value_ty);
+                let pair_type = self.context.struct_type(&[key_type, value_type], false);
+                let pair_ptr = unsafe {
+                    self.builder
+                        .build_in_bounds_gep(pair_type, collection, &[index], "pair_ptr")
+                }
+                .unwrap();
+                let key_ptr = self
+                    .builder
+                    .build_struct_gep(pair_type, pair_ptr, 0, "key_ptr")
+                    .unwrap();
+                let key = self.builder.build_load(key_type, key_ptr, "key").unwrap();
+                self.print_element(key, key_ty);
+                self.print_text(": ");
+                let value_ptr = self
+                    .builder
+                    .build_struct_gep(pair_type, pair_ptr, 1, "value_ptr")
+                    .unwrap();
+                let value = self
+                    .builder
+                    .build_load(value_type, value_ptr, "value")
+                    .unwrap();
+                self.print_element(value, value_ty);
+            }
+            _ => {
+                let elem_ty = ty.element().expect("printers are for arrays and maps");
+                let elem_type = self.struct_field_type(elem_ty);
+                let elem_ptr = unsafe {
+                    self.builder
+                        .build_in_bounds_gep(elem_type, collection, &[index], "elem_ptr")
+                }
+                .unwrap();
+                let elem = self
+                    .builder
+                    .build_load(elem_type, elem_ptr, "elem")
+                    .unwrap();
+                self.print_element(elem, elem_ty);
+            }
+        }
+        let next = self
+            .builder
+            .build_int_add(index, i32_type.const_int(1, false), "next")
+            .unwrap();
+        self.builder.build_store(index_alloca, next).unwrap();
+        self.builder.build_unconditional_branch(cond).unwrap();
+
+        self.builder.position_at_end(exit);
+        self.print_text(close);
+        self.builder.build_return(None).unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// Prints one element of a collection: strings quoted, nested arrays and
+    /// maps through their own printer.
+    fn print_element(&self, value: BasicValueEnum<'ctx>, ty: &MirType) {
+        let format = match ty {
+            MirType::Int => "%d",
+            MirType::Int64 => "%lld",
+            MirType::Float => "%f",
+            MirType::Str => "\"%s\"",
+            MirType::Bool => {
+                let is_true = self.build_condition(value.into_int_value());
+                let true_str = self
+                    .builder
+                    .build_global_string_ptr("true", "true_str")
+                    .unwrap();
+                let false_str = self
+                    .builder
+                    .build_global_string_ptr("false", "false_str")
+                    .unwrap();
+                let text = self
+                    .builder
+                    .build_select(
+                        is_true,
+                        true_str.as_pointer_value(),
+                        false_str.as_pointer_value(),
+                        "bool_str",
+                    )
+                    .unwrap()
+                    .into_pointer_value();
+                self.print_string(text);
+                return;
+            }
+            _ => {
+                let tag = Self::print_tag(ty).expect("element types have a printer tag");
+                let printer = self.get_or_create_printer(ty, &tag);
+                self.builder
+                    .build_call(printer, &[value.into()], "")
+                    .unwrap();
+                return;
+            }
+        };
+        let format = self
+            .builder
+            .build_global_string_ptr(format, "elem_fmt")
+            .unwrap();
+        self.builder
+            .build_call(
+                self.get_or_declare_printf(),
+                &[format.as_pointer_value().into(), value.into()],
+                "",
+            )
+            .unwrap();
+    }
+
+    /// Prints `text` as it is.
+    fn print_text(&self, text: &str) {
+        let text = self.builder.build_global_string_ptr(text, "text").unwrap();
+        self.print_string(text.as_pointer_value());
+    }
+
+    /// Prints the C string at `text`.
+    fn print_string(&self, text: PointerValue<'ctx>) {
+        let format = self
+            .builder
+            .build_global_string_ptr("%s", "str_fmt")
+            .unwrap();
+        self.builder
+            .build_call(
+                self.get_or_declare_printf(),
+                &[format.as_pointer_value().into(), text.into()],
+                "",
+            )
+            .unwrap();
+    }
+}
@@ -0,0 +1,716 @@
+use crate::codegen::core::CodeGen;
+use crate::mir::MirType;
+use inkwell::types::{BasicType, BasicTypeEnum};
+use inkwell::values::{BasicValueEnum, FunctionValue, IntValue, PointerValue};
+use inkwell::AddressSpace;
+use inkwell::IntPredicate;
+
+/// Smallest number of elements an array's heap block has room for.
+const MIN_ARRAY_CAPACITY: usize = 4;
+
+/// Number of elements the heap block of an array of `len` elements has room
+/// for: the next power of two, at least `MIN_ARRAY_CAPACITY`.
+pub fn array_capacity(len: usize) -> usize {
+    len.next_power_of_two().max(MIN_ARRAY_CAPACITY)
+}
+
+/// Growable arrays: `push`, `pop`, `insert`, `remove` and `clear`.
+///
+/// Arrays keep their layout `[RC: 4 bytes][Length: 4 bytes][data]`. The
+/// capacity is not stored: the header in front of every RC'd value is freed
+/// through `data - 8`, so a third field would change strings, maps and
+/// structs too. Instead the capacity is derived from the length with
+/// `array_capacity`, and every array is allocated with at least that much
+/// room. Growing past it reallocates to the next power of two, so pushes are
+/// amortized O(1).
+///
+/// An array shared by several variables (RC > 1) is copied before it is
+/// changed, and the copy holds its own reference to each heap element.
+/// Elements added by these methods hold a reference the array never gives
+/// back, as arrays don't release their elements when freed.
+///
+/// The variable holding the array is updated with the (possibly moved) data
+/// pointer after each operation. Arrays changed this way are found before
+/// a function is generated (`growable_arrays`) and always read their length
+/// from the header, like the rows of nested arrays.
+impl<'ctx> CodeGen<'ctx> {
+    /// Element type of an array type (`Array<Int>` -> `Int`).
+    pub(crate) fn growable_elem_type(ty: &MirType) -> &MirType {
+        ty.element().unwrap_or(ty)
+    }
+
+    /// Marks `name` as an array whose length is only known at runtime, with
+    /// the element shape of the array type `ty`.
+    pub fn track_growable_array(&mut self, name: &str, ty: &MirType) {
+        self.array_metadata.remove(name);
+        self.temp_values.remove(name);
+        if let Some(shape) = Self::array_shape_from_type(ty) {
+            self.runtime_array_shapes.insert(name.to_string(), shape);
+        }
+    }
+
+    /// `__array_capacity(len)`: the capacity implied by a length (see `array_capacity`).
+    pub(crate) fn get_or_create_capacity_fn(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("__array_capacity") {
+            return func;
+        }
+        let i32_type = self.context.i32_type();
+        let function = self.module.add_function(
+            "__array_capacity",
+            i32_type.fn_type(&[i32_type.into()], false),
+            None,
+        );
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        let cond = self.context.append_basic_block(function, "cond");
+        let double = self.context.append_basic_block(function, "double");
+        let done = self.context.append_basic_block(function, "done");
+        let len = function.get_nth_param(0).unwrap().into_int_value();
+
+        self.builder.position_at_end(entry);
+        self.builder.build_unconditional_branch(cond).unwrap();
+
+        // cap = MIN; while cap < len: cap *= 2
+        self.builder.position_at_end(cond);
+        let cap = self.builder.build_phi(i32_type, "cap").unwrap();
+        let cap_val = cap.as_basic_value().into_int_value();
+        let fits = self
+            .builder
+            .build_int_compare(IntPredicate::SGE, cap_val, len, "fits")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(fits, done, double)
+            .unwrap();
+
+        self.builder.position_at_end(double);
+        let next = self
+            .builder
+            .build_int_mul(cap_val, i32_type.const_int(2, false), "next_cap")
+            .unwrap();
+        self.builder.build_unconditional_branch(cond).unwrap();
+        cap.add_incoming(&[
+            (&i32_type.const_int(MIN_ARRAY_CAPACITY as u64, false), entry),
+            (&next, double),
+        ]);
+
+        self.builder.position_at_end(done);
+        self.builder.build_return(Some(&cap_val)).unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// `__array_reserve(data, min_len, elem_size, rc_slots, extra_size)`:
+    /// returns the data pointer of an array equal to `data`, owned by the
+    /// caller alone and with room for `min_len` elements. Grows with `realloc`;
+    /// a shared array is copied instead, each heap pointer in its elements
+    /// gaining a reference. Bit `n` of `rc_slots` marks the pointer at byte
+    /// `8 * n` of every element as one (the map pairs use both). A null `data`
+    /// (an empty map literal) gets a fresh block. Blocks also have
+    /// `extra_size` bytes per element of capacity after the elements, which
+    /// maps keep their index in; they are neither copied nor kept up to date.
+    /// The length is left to the caller.
+    fn get_or_create_reserve_fn(&self) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function("__array_reserve") {
+            return func;
+        }
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let i32_type = self.context.i32_type();
+        let i64_type = self.context.i64_type();
+        let function = self.module.add_function(
+            "__array_reserve",
+            ptr_type.fn_type(
+                &[
+                    ptr_type.into(),
+                    i32_type.into(),
+                    i64_type.into(),
+                    i32_type.into(),
+                    i64_type.into(),
+                ],
+                false,
+            ),
+            None,
+        );
+        let capacity_fn = self.get_or_create_capacity_fn();
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        let fresh = self.context.append_basic_block(function, "fresh");
+        let owned_check = self.context.append_basic_block(function, "owned_check");
+        let check_room = self.context.append_basic_block(function, "check_room");
+        let keep = self.context.append_basic_block(function, "keep");
+        let grow = self.context.append_basic_block(function, "grow");
+        let copy = self.context.append_basic_block(function, "copy");
+        let incref_cond = self.context.append_basic_block(function, "incref_cond");
+        let incref_body = self.context.append_basic_block(function, "incref_body");
+        let incref_first = self.context.append_basic_block(function, "incref_first");
+        let check_second = self.context.append_basic_block(function, "check_second");
+        let incref_second = self.context.append_basic_block(function, "incref_second");
+        let incref_next = self.context.append_basic_block(function, "incref_next");
+        let copied = self.context.append_basic_block(function, "copied");
+
+        let data = function.get_nth_param(0).unwrap().into_pointer_value();
+        let min_len = function.get_nth_param(1).unwrap().into_int_value();
+        let elem_size = function.get_nth_param(2).unwrap().into_int_value();
+        let rc_slots = function.get_nth_param(3).unwrap().into_int_value();
+        let extra_size = function.get_nth_param(4).unwrap().into_int_value();
+
+        // Bytes taken by a header and a capacity of `count` elements
+        let block_size = |count: IntValue<'ctx>, name: &str| {
+            let count = self
+                .builder
+                .build_int_z_extend(count, i64_type, "count")
+                .unwrap();
+            let room = self
+                .builder
+                .build_int_add(elem_size, extra_size, "room")
+                .unwrap();
+            let bytes = self
+                .builder
+                .build_int_mul(count, room, "data_size")
+                .unwrap();
+            self.builder
+                .build_int_add(i64_type.const_int(8, false), bytes, name)
+                .unwrap()
+        };
+        let capacity = |len: IntValue<'ctx>| {
+            self.builder
+                .build_call(capacity_fn, &[len.into()], "capacity")
+                .unwrap()
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_int_value()
+        };
+
+        // Header and data pointer of a new block with room for `count` elements
+        let new_block = |count: IntValue<'ctx>, name: &str| {
+            let size = block_size(capacity(count), &format!("{}_size", name));
+            let block = self
+                .builder
+                .build_call(
+                    self.get_or_declare_malloc(),
+                    &[self.to_size_t(size).into()],
+                    name,
+                )
+                .unwrap()
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_pointer_value();
+            self.builder
+                .build_store(block, i32_type.const_int(1, false))
+                .unwrap();
+            let data = unsafe {
+                self.builder.build_in_bounds_gep(
+                    self.context.i8_type(),
+                    block,
+                    &[i32_type.const_int(8, false)],
+                    &format!("{}_data", name),
+                )
+            }
+            .unwrap();
+            (block, data)
+        };
+
+        self.builder.position_at_end(entry);
+        let is_null = self.builder.build_is_null(data, "is_null").unwrap();
+        self.builder
+            .build_conditional_branch(is_null, fresh, owned_check)
+            .unwrap();
+
+        self.builder.position_at_end(fresh);
+        let (fresh_block, fresh_data) = new_block(min_len, "fresh");
+        let fresh_len_ptr = unsafe {
+            self.builder.build_in_bounds_gep(
+                self.context.i8_type(),
+                fresh_block,
+                &[i32_type.const_int(4, false)],
+                "fresh_len_ptr",
+            )
+        }
+        .unwrap();
+        self.builder
+            .build_store(fresh_len_ptr, i32_type.const_zero())
+            .unwrap();
+        self.builder.build_return(Some(&fresh_data)).unwrap();
+
+        self.builder.position_at_end(owned_check);
+        let header = self.rc_header_ptr(data);
+        let rc = self
+            .builder
+            .build_load(i32_type, header, "rc")
+            .unwrap()
+            .into_int_value();
+        let len = self.load_runtime_array_length(data);
+        let shared = self
+            .builder
+            .build_int_compare(IntPredicate::NE, rc, i32_type.const_int(1, false), "shared")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(shared, copy, check_room)
+            .unwrap();
+
+        // Owned alone: grow in place when the capacity is exceeded
+        self.builder.position_at_end(check_room);
+        let has_room = self
+            .builder
+            .build_int_compare(IntPredicate::SLE, min_len, capacity(len), "has_room")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(has_room, keep, grow)
+            .unwrap();
+
+        self.builder.position_at_end(keep);
+        self.builder.build_return(Some(&data)).unwrap();
+
+        self.builder.position_at_end(grow);
+        let new_size = block_size(capacity(min_len), "new_size");
+        let grown = self
+            .builder
+            .build_call(
+                self.get_or_declare_realloc(),
+                &[header.into(), self.to_size_t(new_size).into()],
+                "grown",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value();
+        let grown_data = unsafe {
+            self.builder.build_in_bounds_gep(
+                self.context.i8_type(),
+                grown,
+                &[i32_type.const_int(8, false)],
+                "grown_data",
+            )
+        }
+        .unwrap();
+        self.builder.build_return(Some(&grown_data)).unwrap();
+
+        // Shared: copy into a block of our own and drop our reference to the old one
+        self.builder.position_at_end(copy);
+        let keeps_all = self
+            .builder
+            .build_int_compare(IntPredicate::SGE, min_len, len, "keeps_all")
+            .unwrap();
+        let wanted = self
+            .builder
+            .build_select(keeps_all, min_len, len, "wanted")
+            .unwrap()
+            .into_int_value();
+        let (block, copy_data) = new_block(wanted, "copy");
+        let copy_len_ptr = unsafe {
+            self.builder.build_in_bounds_gep(
+                self.context.i8_type(),
+                block,
+                &[i32_type.const_int(4, false)],
+                "copy_len_ptr",
+            )
+        }
+        .unwrap();
+        self.builder.build_store(copy_len_ptr, len).unwrap();
+        let used_size = self
+            .builder
+            .build_int_mul(
+                self.builder
+                    .build_int_z_extend(len, i64_type, "used")
+                    .unwrap(),
+                elem_size,
+                "used_size",
+            )
+            .unwrap();
+        self.builder
+            .build_memcpy(copy_data, 1, data, 1, used_size)
+            .unwrap();
+        self.decref_value(data);
+        let index_ptr = self.builder.build_alloca(i32_type, "index").unwrap();
+        self.builder
+            .build_store(index_ptr, i32_type.const_zero())
+            .unwrap();
+        let has_rc = self
+            .builder
+            .build_int_compare(IntPredicate::NE, rc_slots, i32_type.const_zero(), "has_rc")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(has_rc, incref_cond, copied)
+            .unwrap();
+
+        // Each heap pointer in the elements gains a reference held by the copy
+        self.builder.position_at_end(incref_cond);
+        let index = self
+            .builder
+            .build_load(i32_type, index_ptr, "i")
+            .unwrap()
+            .into_int_value();
+        let in_bounds = self
+            .builder
+            .build_int_compare(IntPredicate::SLT, index, len, "in_bounds")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(in_bounds, incref_body, copied)
+            .unwrap();
+
+        // Increfs the pointer stored at `slot`
+        let incref_slot = |slot: PointerValue<'ctx>| {
+            let elem = self
+                .builder
+                .build_load(ptr_type, slot, "elem")
+                .unwrap()
+                .into_pointer_value();
+            self.incref_value(elem);
+        };
+        let slot_set = |bit: u64, name: &str| {
+            let masked = self
+                .builder
+                .build_and(rc_slots, i32_type.const_int(bit, false), name)
+                .unwrap();
+            self.builder
+                .build_int_compare(IntPredicate::NE, masked, i32_type.const_zero(), name)
+                .unwrap()
+        };
+
+        self.builder.position_at_end(incref_body);
+        let offset = self
+            .builder
+            .build_int_mul(
+                self.builder
+                    .build_int_z_extend(index, i64_type, "index64")
+                    .unwrap(),
+                elem_size,
+                "offset",
+            )
+            .unwrap();
+        let elem_ptr = unsafe {
+            self.builder.build_in_bounds_gep(
+                self.context.i8_type(),
+                copy_data,
+                &[offset],
+                "elem_ptr",
+            )
+        }
+        .unwrap();
+        let first_set = slot_set(1, "first_rc");
+        self.builder
+            .build_conditional_branch(first_set, incref_first, check_second)
+            .unwrap();
+
+        self.builder.position_at_end(incref_first);
+        incref_slot(elem_ptr);
+        self.builder
+            .build_unconditional_branch(check_second)
+            .unwrap();
+
+        self.builder.position_at_end(check_second);
+        let second_set = slot_set(2, "second_rc");
+        self.builder
+            .build_conditional_branch(second_set, incref_second, incref_next)
+            .unwrap();
+
+        self.builder.position_at_end(incref_second);
+        let second_ptr = unsafe {
+            self.builder.build_in_bounds_gep(
+                self.context.i8_type(),
+                elem_ptr,
+                &[i64_type.const_int(8, false)],
+                "second_ptr",
+            )
+        }
+        .unwrap();
+        incref_slot(second_ptr);
+        self.builder
+            .build_unconditional_branch(incref_next)
+            .unwrap();
+
+        self.builder.position_at_end(incref_next);
+        let next = self
+            .builder
+            .build_int_add(index, i32_type.const_int(1, false), "next")
+            .unwrap();
+        self.builder.build_store(index_ptr, next).unwrap();
+        self.builder
+            .build_unconditional_branch(incref_cond)
+            .unwrap();
+
+        self.builder.position_at_end(copied);
+        self.builder.build_return(Some(&copy_data)).unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// Calls `__array_reserve` (see `get_or_create_reserve_fn`) on the block at `data`.
+    pub(crate) fn reserve_block(
+        &self,
+        data: PointerValue<'ctx>,
+        min_len: IntValue<'ctx>,
+        elem_size: IntValue<'ctx>,
+        rc_slots: u64,
+        extra_size: u64,
+    ) -> PointerValue<'ctx> {
+        let rc_slots = self.context.i32_type().const_int(rc_slots, false);
+        let extra_size = self.context.i64_type().const_int(extra_size, false);
+        self.builder
+            .build_call(
+                self.get_or_create_reserve_fn(),
+                &[
+                    data.into(),
+                    min_len.into(),
+                    elem_size.into(),
+                    rc_slots.into(),
+                    extra_size.into(),
+                ],
+                "reserved",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value()
+    }
+
+    /// Loads the data pointer of the array variable `array` and makes it
+    /// owned alone with room for `min_len(len)` elements. Returns the new data
+    /// pointer, the current length and the element type.
+    pub(crate) fn reserve_array(
+        &mut self,
+        array: &str,
+        ty: &MirType,
+        min_len: impl Fn(&Self, IntValue<'ctx>) -> IntValue<'ctx>,
+    ) -> (PointerValue<'ctx>, IntValue<'ctx>, BasicTypeEnum<'ctx>) {
+        let elem_ty = Self::growable_elem_type(ty);
+        let elem_type = self.struct_field_type(elem_ty);
+        let data = self.resolve_value(array).into_pointer_value();
+        let len = self.load_runtime_array_length(data);
+        let wanted = min_len(self, len);
+        let elem_size = elem_type.size_of().unwrap();
+        let rc_slots = Self::field_is_rc(elem_ty) as u64;
+        let data = self.reserve_block(data, wanted, elem_size, rc_slots, 0);
+        (data, len, elem_type)
+    }
+
+    /// Stores the new length and data pointer of the array variable `array`.
+    pub(crate) fn finish_array_update(
+        &mut self,
+        array: &str,
+        ty: &MirType,
+        data: PointerValue<'ctx>,
+        len: IntValue<'ctx>,
+    ) {
+        let len_ptr = unsafe {
+            self.builder.build_in_bounds_gep(
+                self.context.i8_type(),
+                data,
+                &[self.context.i32_type().const_int((-4_i32) as u64, true)],
+                "len_ptr",
+            )
+        }
+        .unwrap();
+        self.builder.build_store(len_ptr, len).unwrap();
+        if let Some(sym) = self.symbols.get(array) {
+            self.builder.build_store(sym.ptr, data).unwrap();
+        }
+        self.track_growable_array(array, ty);
+    }
+
+    /// Pointer to element `index` of the array `data`.
+    pub(crate) fn growable_elem_ptr(
+        &self,
+        elem_type: BasicTypeEnum<'ctx>,
+        data: PointerValue<'ctx>,
+        index: IntValue<'ctx>,
+    ) -> PointerValue<'ctx> {
+        unsafe {
+            self.builder
+                .build_in_bounds_gep(elem_type, data, &[index], "elem_ptr")
+        }
+        .unwrap()
+    }
+
+    /// Moves the `count` elements starting at `from` to start at `to`.
+    fn shift_elements(
+        &self,
+        elem_type: BasicTypeEnum<'ctx>,
+        data: PointerValue<'ctx>,
+        from: IntValue<'ctx>,
+        to: IntValue<'ctx>,
+        count: IntValue<'ctx>,
+    ) {
+        let count = self
+            .builder
+            .build_int_z_extend(count, self.context.i64_type(), "shift_count")
+            .unwrap();
+        let size = self
+            .builder
+            .build_int_mul(count, elem_type.size_of().unwrap(), "shift_size")
+            .unwrap();
+        let src = self.growable_elem_ptr(elem_type, data, from);
+        let dest = self.growable_elem_ptr(elem_type, data, to);
+        self.builder.build_memmove(dest, 1, src, 1, size).unwrap();
+    }
+
+    /// The value of an element taken out of an array. Heap elements gain a
+    /// reference: literal elements are still released with the array.
+    fn taken_element(&mut self, name: &str, val: BasicValueEnum<'ctx>) {
+        if val.is_pointer_value() {
+            self.incref_value(val.into_pointer_value());
+        }
+        if let Some(sym) = self.symbols.get(name) {
+            self.builder.build_store(sym.ptr, val).unwrap();
+        }
+        self.temp_values.insert(name.to_string(), val);
+    }
+
+    /// `arr.push(value)`: appends `value`, which the array holds a reference to.
+    pub fn generate_array_push(
+        &mut self,
+        array: &str,
+        value: &str,
+        ty: &MirType,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let elem_ty = Self::growable_elem_type(ty).clone();
+        let val = self.owned_field_value(&elem_ty, value);
+        let one = self.context.i32_type().const_int(1, false);
+        let (data, len, elem_type) = self.reserve_array(array, ty, |cg, len| {
+            cg.builder.build_int_add(len, one, "min_len").unwrap()
+        });
+        let elem_ptr = self.growable_elem_ptr(elem_type, data, len);
+        self.builder.build_store(elem_ptr, val).unwrap();
+        let new_len = self.builder.build_int_add(len, one, "new_len").unwrap();
+        self.finish_array_update(array, ty, data, new_len);
+        None
+    }
+
+    /// `arr.pop()`: takes out the last element, as an optional that is
+    /// absent when the array is empty.
+    pub fn generate_array_pop(
+        &mut self,
+        name: &str,
+        array: &str,
+        ty: &MirType,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let i32_type = self.context.i32_type();
+        let (data, len, elem_type) = self.reserve_array(array, ty, |_, len| len);
+        let is_some = self
+            .builder
+            .build_int_compare(IntPredicate::SGT, len, i32_type.const_zero(), "is_some")
+            .unwrap();
+        let last = self
+            .builder
+            .build_int_sub(len, i32_type.const_int(1, false), "last")
+            .unwrap();
+        let new_len = self
+            .builder
+            .build_select(is_some, last, i32_type.const_zero(), "new_len")
+            .unwrap()
+            .into_int_value();
+        // An empty array still has room for MIN_ARRAY_CAPACITY elements, so
+        // the load stays inside the block; its value is only used when present
+        let elem_ptr = self.growable_elem_ptr(elem_type, data, new_len);
+        let elem = self
+            .builder
+            .build_load(elem_type, elem_ptr, "popped")
+            .unwrap();
+        self.finish_array_update(array, ty, data, new_len);
+
+        let optional_ty = MirType::Optional(Box::new(Self::growable_elem_type(ty).clone()));
+        let val: BasicValueEnum<'ctx> = match self.scalar_optional_type(&optional_ty) {
+            Some(st) => {
+                let with_tag = self
+                    .builder
+                    .build_insert_value(st.get_undef(), is_some, 0, "opt_tag")
+                    .unwrap();
+                self.builder
+                    .build_insert_value(with_tag, elem, 1, "opt")
+                    .unwrap()
+                    .into_struct_value()
+                    .into()
+            }
+            None => {
+                let null = self.context.ptr_type(AddressSpace::default()).const_null();
+                self.builder
+                    .build_select(is_some, elem.into_pointer_value(), null, "opt")
+                    .unwrap()
+            }
+        };
+        self.taken_element(name, val);
+        Some(val)
+    }
+
+    /// `arr.insert(index, value)`: moves the elements from `index` on one
+    /// place up and stores `value` at `index`, panicking unless `index` is
+    /// within the array or at its end.
+    pub fn generate_array_insert(
+        &mut self,
+        array: &str,
+        index: &str,
+        value: &str,
+        ty: &MirType,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let index_val = self.resolve_value(index).into_int_value();
+        let length = self.load_runtime_array_length(self.resolve_value(array).into_pointer_value());
+        self.build_insert_bounds_check(index_val, length);
+        let elem_ty = Self::growable_elem_type(ty).clone();
+        let val = self.owned_field_value(&elem_ty, value);
+        let one = self.context.i32_type().const_int(1, false);
+        let (data, len, elem_type) = self.reserve_array(array, ty, |cg, len| {
+            cg.builder.build_int_add(len, one, "min_len").unwrap()
+        });
+        let after = self.builder.build_int_add(index_val, one, "after").unwrap();
+        let count = self.builder.build_int_sub(len, index_val, "moved").unwrap();
+        self.shift_elements(elem_type, data, index_val, after, count);
+        let elem_ptr = self.growable_elem_ptr(elem_type, data, index_val);
+        self.builder.build_store(elem_ptr, val).unwrap();
+        let new_len = self.builder.build_int_add(len, one, "new_len").unwrap();
+        self.finish_array_update(array, ty, data, new_len);
+        None
+    }
+
+    /// `arr.remove(index)`: takes out the element at `index` and moves the
+    /// ones after it one place down, panicking when `index` is outside the
+    /// array.
+    pub fn generate_array_remove(
+        &mut self,
+        name: &str,
+        array: &str,
+        index: &str,
+        ty: &MirType,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let index_val = self.resolve_value(index).into_int_value();
+        let length = self.load_runtime_array_length(self.resolve_value(array).into_pointer_value());
+        self.build_bounds_check(index_val, length);
+        let one = self.context.i32_type().const_int(1, false);
+        let (data, len, elem_type) = self.reserve_array(array, ty, |_, len| len);
+        let elem_ptr = self.growable_elem_ptr(elem_type, data, index_val);
+        let elem = self
+            .builder
+            .build_load(elem_type, elem_ptr, "removed")
+            .unwrap();
+        let after = self.builder.build_int_add(index_val, one, "after").unwrap();
+        let new_len = self.builder.build_int_sub(len, one, "new_len").unwrap();
+        let count = self
+            .builder
+            .build_int_sub(new_len, index_val, "moved")
+            .unwrap();
+        self.shift_elements(elem_type, data, after, index_val, count);
+        self.finish_array_update(array, ty, data, new_len);
+        self.taken_element(name, elem);
+        Some(elem)
+    }
+
+    /// `arr.clear()`: drops every element, keeping the allocation.
+    pub fn generate_array_clear(
+        &mut self,
+        array: &str,
+        ty: &MirType,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let (data, _, _) = self.reserve_array(array, ty, |_, len| len);
+        let zero = self.context.i32_type().const_zero();
+        self.finish_array_update(array, ty, data, zero);
+        None
+    }
+}
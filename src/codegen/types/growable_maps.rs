@@ -0,0 +1,1061 @@
+use crate::codegen::core::CodeGen;
+use crate::mir::MirType;
+use inkwell::types::{BasicType, BasicTypeEnum, StructType};
+use inkwell::values::{BasicValueEnum, FunctionValue, IntValue, PointerValue};
+use inkwell::AddressSpace;
+use inkwell::IntPredicate;
+
+/// Bytes of index a map block has per pair of capacity: two `i32` slots,
+/// so the index is never more than half full.
+pub(crate) const MAP_INDEX_SIZE: u64 = 8;
+
+/// Map lookups and methods: `m[k]`, `insert`, `remove`, `clear`,
+/// `containsKey`, `keys` and `values`.
+///
+/// Maps keep their layout `[RC: 4 bytes][Length: 4 bytes][pairs]`, the pairs
+/// dense and in insertion order, so iterating and printing them walks the
+/// pairs. After room for `array_capacity(len)` pairs comes the index: twice
+/// as many `i32` slots, each the number of a pair or -1 when empty. A key is
+/// found by open addressing: hashed (see `get_or_create_hash_fn`), then the
+/// slots are probed one after another from its hash until one holds its pair
+/// or is empty, so lookups take O(1) on average.
+///
+/// Maps grow like arrays (see growable_arrays): the capacity is implied by
+/// the length, a shared map is copied before it is changed, and the variable
+/// holding it is updated with the new data pointer. A block that moved, or
+/// whose capacity changed, is indexed again; otherwise `insert` only adds its
+/// new pair to the index. Maps changed in place (`growable_maps`) read their
+/// length from the header everywhere in the function.
+///
+/// Keys and values added by `insert` hold a reference the map never gives
+/// back, and values taken out by `m[k]` or `remove` gain one, as maps don't
+/// release their pairs when freed.
+impl<'ctx> CodeGen<'ctx> {
+    /// LLVM pair type of a map type, with its key and value types.
+    fn map_pair_layout<'a>(&self, ty: &'a MirType) -> (StructType<'ctx>, &'a MirType, &'a MirType) {
+        let (key_ty, value_ty) = ty.map_parts().unwrap_or((&MirType::Int, &MirType::Int));
+        let pair_type = self.context.struct_type(
+            &[
+                self.struct_field_type(key_ty),
+                self.struct_field_type(value_ty),
+            ],
+            false,
+        );
+        (pair_type, key_ty, value_ty)
+    }
+
+    /// Marks `name` as a map whose length is only known at runtime, with the
+    /// shape of the map type `ty`.
+    pub fn track_growable_map(&mut self, name: &str, ty: &MirType) {
+        self.map_metadata.remove(name);
+        self.temp_values.remove(name);
+        if let Some(shape) = Self::map_shape_from_type(ty) {
+            self.runtime_map_shapes.insert(name.to_string(), shape);
+        }
+    }
+
+    /// `__map_hash_int(key)` / `__map_hash_str(key)`: the hash a key's
+    /// probe starts from. Ints are multiplied by a large odd constant and
+    /// their high bits folded into the low ones, which pick the slot;
+    /// strings are hashed by content with 32-bit FNV-1a.
+    fn get_or_create_hash_fn(&self, key_is_string: bool) -> FunctionValue<'ctx> {
+        let fn_name = if key_is_string {
+            "__map_hash_str"
+        } else {
+            "__map_hash_int"
+        };
+        if let Some(func) = self.module.get_function(fn_name) {
+            return func;
+        }
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let i8_type = self.context.i8_type();
+        let i32_type = self.context.i32_type();
+        let key_type: BasicTypeEnum<'ctx> = if key_is_string {
+            ptr_type.into()
+        } else {
+            i32_type.into()
+        };
+        let function =
+            self.module
+                .add_function(fn_name, i32_type.fn_type(&[key_type.into()], false), None);
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        let key = function.get_nth_param(0).unwrap();
+
+        self.builder.position_at_end(entry);
+        if key_is_string {
+            let cond = self.context.append_basic_block(function, "cond");
+            let body = self.context.append_basic_block(function, "body");
+            let done = self.context.append_basic_block(function, "done");
+            self.builder.build_unconditional_branch(cond).unwrap();
+
+            // hash = offset basis; for each byte: hash = (hash ^ byte) * prime
+            self.builder.position_at_end(cond);
+            let hash = self.builder.build_phi(i32_type, "hash").unwrap();
+            let hash_val = hash.as_basic_value().into_int_value();
+            let byte_ptr = self.builder.build_phi(ptr_type, "byte_ptr").unwrap();
+            let byte_ptr_val = byte_ptr.as_basic_value().into_pointer_value();
+            let byte = self
+                .builder
+                .build_load(i8_type, byte_ptr_val, "byte")
+                .unwrap()
+                .into_int_value();
+            let at_end = self
+                .builder
+                .build_int_compare(IntPredicate::EQ, byte, i8_type.const_zero(), "at_end")
+                .unwrap();
+            self.builder
+                .build_conditional_branch(at_end, done, body)
+                .unwrap();
+
+            self.builder.position_at_end(body);
+            let byte32 = self
+                .builder
+                .build_int_z_extend(byte, i32_type, "byte32")
+                .unwrap();
+            let mixed = self.builder.build_xor(hash_val, byte32, "mixed").unwrap();
+            let next_hash = self
+                .builder
+                .build_int_mul(mixed, i32_type.const_int(16777619, false), "next_hash")
+                .unwrap();
+            let next_ptr = unsafe {
+                self.builder.build_in_bounds_gep(
+                    i8_type,
+                    byte_ptr_val,
+                    &[i32_type.const_int(1, false)],
+                    "next_ptr",
+                )
+            }
+            .unwrap();
+            self.builder.build_unconditional_branch(cond).unwrap();
+            hash.add_incoming(&[
+                (&i32_type.const_int(2166136261, false), entry),
+                (&next_hash, body),
+            ]);
+            byte_ptr.add_incoming(&[(&key.into_pointer_value(), entry), (&next_ptr, body)]);
+
+            self.builder.position_at_end(done);
+            self.builder.build_return(Some(&hash_val)).unwrap();
+        } else {
+            let product = self
+                .builder
+                .build_int_mul(
+                    key.into_int_value(),
+                    i32_type.const_int(0x9E3779B1, false),
+                    "product",
+                )
+                .unwrap();
+            let high = self
+                .builder
+                .build_right_shift(product, i32_type.const_int(16, false), false, "high")
+                .unwrap();
+            let hash = self.builder.build_xor(product, high, "hash").unwrap();
+            self.builder.build_return(Some(&hash)).unwrap();
+        }
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// The index of the map `data` holding `len` pairs of `pair_size` bytes:
+    /// its first slot, and the mask that wraps a slot number around it.
+    fn map_index_slots(
+        &self,
+        data: PointerValue<'ctx>,
+        len: IntValue<'ctx>,
+        pair_size: IntValue<'ctx>,
+    ) -> (PointerValue<'ctx>, IntValue<'ctx>) {
+        let i32_type = self.context.i32_type();
+        let capacity = self
+            .builder
+            .build_call(self.get_or_create_capacity_fn(), &[len.into()], "capacity")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let capacity64 = self
+            .builder
+            .build_int_z_extend(capacity, self.context.i64_type(), "capacity64")
+            .unwrap();
+        let pairs_size = self
+            .builder
+            .build_int_mul(capacity64, pair_size, "pairs_size")
+            .unwrap();
+        let slots = unsafe {
+            self.builder
+                .build_in_bounds_gep(self.context.i8_type(), data, &[pairs_size], "slots")
+        }
+        .unwrap();
+        // Capacities are powers of two, and there are twice as many slots
+        let slot_count = self
+            .builder
+            .build_int_mul(capacity, i32_type.const_int(2, false), "slot_count")
+            .unwrap();
+        let mask = self
+            .builder
+            .build_int_sub(slot_count, i32_type.const_int(1, false), "mask")
+            .unwrap();
+        (slots, mask)
+    }
+
+    /// Loads the key of pair `index` of the map `data`.
+    fn load_map_key(
+        &self,
+        data: PointerValue<'ctx>,
+        index: IntValue<'ctx>,
+        pair_size: IntValue<'ctx>,
+        key_type: BasicTypeEnum<'ctx>,
+    ) -> BasicValueEnum<'ctx> {
+        let offset = self
+            .builder
+            .build_int_mul(
+                self.builder
+                    .build_int_z_extend(index, self.context.i64_type(), "index64")
+                    .unwrap(),
+                pair_size,
+                "offset",
+            )
+            .unwrap();
+        let pair_ptr = unsafe {
+            self.builder
+                .build_in_bounds_gep(self.context.i8_type(), data, &[offset], "pair_ptr")
+        }
+        .unwrap();
+        self.builder
+            .build_load(key_type, pair_ptr, "pair_key")
+            .unwrap()
+    }
+
+    /// Pointer to slot `slot` of a map's index.
+    fn map_slot_ptr(&self, slots: PointerValue<'ctx>, slot: IntValue<'ctx>) -> PointerValue<'ctx> {
+        unsafe {
+            self.builder
+                .build_in_bounds_gep(self.context.i32_type(), slots, &[slot], "slot_ptr")
+        }
+        .unwrap()
+    }
+
+    /// The slot after `slot`, wrapping around to the first.
+    fn next_map_slot(&self, slot: IntValue<'ctx>, mask: IntValue<'ctx>) -> IntValue<'ctx> {
+        let next = self
+            .builder
+            .build_int_add(
+                slot,
+                self.context.i32_type().const_int(1, false),
+                "next_slot",
+            )
+            .unwrap();
+        self.builder.build_and(next, mask, "wrapped_slot").unwrap()
+    }
+
+    /// `__map_find_int(data, key, pair_size)` / `__map_find_str(...)`: index of
+    /// the pair whose key equals `key`, or -1. Strings are compared by content.
+    /// Probes the index from the key's hash up to the first empty slot. A null
+    /// `data` (an empty map literal) has no pairs.
+    fn get_or_create_find_fn(&self, key_is_string: bool) -> FunctionValue<'ctx> {
+        let fn_name = if key_is_string {
+            "__map_find_str"
+        } else {
+            "__map_find_int"
+        };
+        if let Some(func) = self.module.get_function(fn_name) {
+            return func;
+        }
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let i32_type = self.context.i32_type();
+        let i64_type = self.context.i64_type();
+        let key_type: BasicTypeEnum<'ctx> = if key_is_string {
+            ptr_type.into()
+        } else {
+            i32_type.into()
+        };
+        let function = self.module.add_function(
+            fn_name,
+            i32_type.fn_type(&[ptr_type.into(), key_type.into(), i64_type.into()], false),
+            None,
+        );
+        let hash_fn = self.get_or_create_hash_fn(key_is_string);
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        let start = self.context.append_basic_block(function, "start");
+        let probe = self.context.append_basic_block(function, "probe");
+        let check = self.context.append_basic_block(function, "check");
+        let found = self.context.append_basic_block(function, "found");
+        let next = self.context.append_basic_block(function, "next");
+        let missing = self.context.append_basic_block(function, "missing");
+
+        let data = function.get_nth_param(0).unwrap().into_pointer_value();
+        let key = function.get_nth_param(1).unwrap();
+        let pair_size = function.get_nth_param(2).unwrap().into_int_value();
+
+        self.builder.position_at_end(entry);
+        let is_null = self.builder.build_is_null(data, "is_null").unwrap();
+        self.builder
+            .build_conditional_branch(is_null, missing, start)
+            .unwrap();
+
+        self.builder.position_at_end(start);
+        let len = self.load_runtime_map_length(data);
+        let (slots, mask) = self.map_index_slots(data, len, pair_size);
+        let hash = self
+            .builder
+            .build_call(hash_fn, &[key.into()], "hash")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let first_slot = self.builder.build_and(hash, mask, "first_slot").unwrap();
+        self.builder.build_unconditional_branch(probe).unwrap();
+
+        // Until an empty slot: if the slot's pair has the key, return its index
+        self.builder.position_at_end(probe);
+        let slot = self.builder.build_phi(i32_type, "slot").unwrap();
+        let slot_val = slot.as_basic_value().into_int_value();
+        let index = self
+            .builder
+            .build_load(i32_type, self.map_slot_ptr(slots, slot_val), "index")
+            .unwrap()
+            .into_int_value();
+        let empty = self
+            .builder
+            .build_int_compare(IntPredicate::EQ, index, i32_type.const_all_ones(), "empty")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(empty, missing, check)
+            .unwrap();
+
+        self.builder.position_at_end(check);
+        let pair_key = self.load_map_key(data, index, pair_size, key_type);
+        let matches = if key_is_string {
+            let cmp = self
+                .builder
+                .build_call(
+                    self.get_or_declare_strcmp(),
+                    &[pair_key.into(), key.into()],
+                    "strcmp",
+                )
+                .unwrap()
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_int_value();
+            self.builder
+                .build_int_compare(IntPredicate::EQ, cmp, i32_type.const_zero(), "matches")
+                .unwrap()
+        } else {
+            self.builder
+                .build_int_compare(
+                    IntPredicate::EQ,
+                    pair_key.into_int_value(),
+                    key.into_int_value(),
+                    "matches",
+                )
+                .unwrap()
+        };
+        self.builder
+            .build_conditional_branch(matches, found, next)
+            .unwrap();
+
+        self.builder.position_at_end(found);
+        self.builder.build_return(Some(&index)).unwrap();
+
+        self.builder.position_at_end(next);
+        let next_slot = self.next_map_slot(slot_val, mask);
+        self.builder.build_unconditional_branch(probe).unwrap();
+        slot.add_incoming(&[(&first_slot, start), (&next_slot, next)]);
+
+        self.builder.position_at_end(missing);
+        self.builder
+            .build_return(Some(&i32_type.const_all_ones()))
+            .unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// `__map_index_int(data, from, pair_size)` / `__map_index_str(...)`: adds
+    /// the pairs of the map `data` from pair `from` to its length to its index,
+    /// each in the first empty slot from its key's hash. With `from` 0 the
+    /// index is emptied first, so it is rebuilt for the current length.
+    /// `data` is never null.
+    fn get_or_create_index_fn(&self, key_is_string: bool) -> FunctionValue<'ctx> {
+        let fn_name = if key_is_string {
+            "__map_index_str"
+        } else {
+            "__map_index_int"
+        };
+        if let Some(func) = self.module.get_function(fn_name) {
+            return func;
+        }
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let i32_type = self.context.i32_type();
+        let i64_type = self.context.i64_type();
+        let key_type: BasicTypeEnum<'ctx> = if key_is_string {
+            ptr_type.into()
+        } else {
+            i32_type.into()
+        };
+        let function = self.module.add_function(
+            fn_name,
+            self.context
+                .void_type()
+                .fn_type(&[ptr_type.into(), i32_type.into(), i64_type.into()], false),
+            None,
+        );
+        let hash_fn = self.get_or_create_hash_fn(key_is_string);
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        let clear = self.context.append_basic_block(function, "clear");
+        let start = self.context.append_basic_block(function, "start");
+        let cond = self.context.append_basic_block(function, "cond");
+        let body = self.context.append_basic_block(function, "body");
+        let probe = self.context.append_basic_block(function, "probe");
+        let taken = self.context.append_basic_block(function, "taken");
+        let placed = self.context.append_basic_block(function, "placed");
+        let done = self.context.append_basic_block(function, "done");
+
+        let data = function.get_nth_param(0).unwrap().into_pointer_value();
+        let from = function.get_nth_param(1).unwrap().into_int_value();
+        let pair_size = function.get_nth_param(2).unwrap().into_int_value();
+
+        self.builder.position_at_end(entry);
+        let len = self.load_runtime_map_length(data);
+        let (slots, mask) = self.map_index_slots(data, len, pair_size);
+        let rebuild = self
+            .builder
+            .build_int_compare(IntPredicate::EQ, from, i32_type.const_zero(), "rebuild")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(rebuild, clear, start)
+            .unwrap();
+
+        // Every slot empty: all bytes 0xff make each one -1
+        self.builder.position_at_end(clear);
+        let slot_count = self
+            .builder
+            .build_int_add(mask, i32_type.const_int(1, false), "slot_count")
+            .unwrap();
+        let slot_count = self
+            .builder
+            .build_int_z_extend(slot_count, i64_type, "slot_count64")
+            .unwrap();
+        let index_size = self
+            .builder
+            .build_int_mul(slot_count, i64_type.const_int(4, false), "index_size")
+            .unwrap();
+        self.builder
+            .build_memset(
+                slots,
+                4,
+                self.context.i8_type().const_all_ones(),
+                index_size,
+            )
+            .unwrap();
+        self.builder.build_unconditional_branch(start).unwrap();
+
+        self.builder.position_at_end(start);
+        self.builder.build_unconditional_branch(cond).unwrap();
+
+        // for i in from..len: put i in the first empty slot from its key's hash
+        self.builder.position_at_end(cond);
+        let index = self.builder.build_phi(i32_type, "i").unwrap();
+        let index_val = index.as_basic_value().into_int_value();
+        let in_bounds = self
+            .builder
+            .build_int_compare(IntPredicate::SLT, index_val, len, "in_bounds")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(in_bounds, body, done)
+            .unwrap();
+
+        self.builder.position_at_end(body);
+        let key = self.load_map_key(data, index_val, pair_size, key_type);
+        let hash = self
+            .builder
+            .build_call(hash_fn, &[key.into()], "hash")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let first_slot = self.builder.build_and(hash, mask, "first_slot").unwrap();
+        self.builder.build_unconditional_branch(probe).unwrap();
+
+        self.builder.position_at_end(probe);
+        let slot = self.builder.build_phi(i32_type, "slot").unwrap();
+        let slot_val = slot.as_basic_value().into_int_value();
+        let slot_ptr = self.map_slot_ptr(slots, slot_val);
+        let occupant = self
+            .builder
+            .build_load(i32_type, slot_ptr, "occupant")
+            .unwrap()
+            .into_int_value();
+        let empty = self
+            .builder
+            .build_int_compare(
+                IntPredicate::EQ,
+                occupant,
+                i32_type.const_all_ones(),
+                "empty",
+            )
+            .unwrap();
+        self.builder
+            .build_conditional_branch(empty, placed, taken)
+            .unwrap();
+
+        self.builder.position_at_end(taken);
+        let next_slot = self.next_map_slot(slot_val, mask);
+        self.builder.build_unconditional_branch(probe).unwrap();
+        slot.add_incoming(&[(&first_slot, body), (&next_slot, taken)]);
+
+        self.builder.position_at_end(placed);
+        self.builder.build_store(slot_ptr, index_val).unwrap();
+        let next_index = self
+            .builder
+            .build_int_add(index_val, i32_type.const_int(1, false), "next_i")
+            .unwrap();
+        self.builder.build_unconditional_branch(cond).unwrap();
+        index.add_incoming(&[(&from, start), (&next_index, placed)]);
+
+        self.builder.position_at_end(done);
+        self.builder.build_return(None).unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// Indexes the pairs of the map `data` from pair `from` on (see
+    /// `get_or_create_index_fn`), after its length was stored.
+    pub(crate) fn index_map(
+        &self,
+        data: PointerValue<'ctx>,
+        from: IntValue<'ctx>,
+        pair_type: StructType<'ctx>,
+        key_is_string: bool,
+    ) {
+        let index_fn = self.get_or_create_index_fn(key_is_string);
+        self.builder
+            .build_call(
+                index_fn,
+                &[
+                    data.into(),
+                    from.into(),
+                    pair_type.size_of().unwrap().into(),
+                ],
+                "",
+            )
+            .unwrap();
+    }
+
+    /// Index of the pair holding `key` in the map `data`, or -1.
+    fn find_map_key(
+        &self,
+        data: PointerValue<'ctx>,
+        key: BasicValueEnum<'ctx>,
+        pair_type: StructType<'ctx>,
+    ) -> IntValue<'ctx> {
+        let find_fn = self.get_or_create_find_fn(key.is_pointer_value());
+        self.builder
+            .build_call(
+                find_fn,
+                &[data.into(), key.into(), pair_type.size_of().unwrap().into()],
+                "key_index",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value()
+    }
+
+    /// Pointer to the key (`field` 0) or value (`field` 1) of pair `index`.
+    fn map_pair_field_ptr(
+        &self,
+        pair_type: StructType<'ctx>,
+        data: PointerValue<'ctx>,
+        index: IntValue<'ctx>,
+        field: u32,
+    ) -> PointerValue<'ctx> {
+        let pair_ptr = unsafe {
+            self.builder
+                .build_in_bounds_gep(pair_type, data, &[index], "pair_ptr")
+        }
+        .unwrap();
+        self.builder
+            .build_struct_gep(pair_type, pair_ptr, field, "pair_field_ptr")
+            .unwrap()
+    }
+
+    /// Loads the data pointer of the map variable `map` and makes it owned
+    /// alone with room for `min_len(len)` pairs. Returns the new data
+    /// pointer, the current length and the pair type. The index is left to
+    /// the caller: a block that was copied or grown has none yet.
+    fn reserve_map(
+        &mut self,
+        map: &str,
+        ty: &MirType,
+        min_len: impl Fn(&Self, IntValue<'ctx>) -> IntValue<'ctx>,
+    ) -> (PointerValue<'ctx>, IntValue<'ctx>, StructType<'ctx>) {
+        let (pair_type, key_ty, value_ty) = self.map_pair_layout(ty);
+        let data = self.resolve_value(map).into_pointer_value();
+        let len = self.load_runtime_map_length(data);
+        let wanted = min_len(self, len);
+        // Keys sit at byte 0 of a pair and values at byte 8
+        let rc_slots = Self::field_is_rc(key_ty) as u64 | (Self::field_is_rc(value_ty) as u64) << 1;
+        let data = self.reserve_block(
+            data,
+            wanted,
+            pair_type.size_of().unwrap(),
+            rc_slots,
+            MAP_INDEX_SIZE,
+        );
+        (data, len, pair_type)
+    }
+
+    /// Stores the new length and data pointer of the map variable `map`.
+    fn finish_map_update(
+        &mut self,
+        map: &str,
+        ty: &MirType,
+        data: PointerValue<'ctx>,
+        len: IntValue<'ctx>,
+    ) {
+        let len_ptr = unsafe {
+            self.builder.build_in_bounds_gep(
+                self.context.i8_type(),
+                data,
+                &[self.context.i32_type().const_int((-4_i32) as u64, true)],
+                "map_len_ptr",
+            )
+        }
+        .unwrap();
+        self.builder.build_store(len_ptr, len).unwrap();
+        if let Some(sym) = self.symbols.get(map) {
+            self.builder.build_store(sym.ptr, data).unwrap();
+        }
+        self.track_growable_map(map, ty);
+    }
+
+    /// An optional of type `value_ty?` holding `val` when `present`.
+    fn map_optional(
+        &self,
+        value_ty: &MirType,
+        present: IntValue<'ctx>,
+        val: BasicValueEnum<'ctx>,
+    ) -> BasicValueEnum<'ctx> {
+        match self.scalar_optional_type(&MirType::Optional(Box::new(value_ty.clone()))) {
+            Some(st) => {
+                let with_tag = self
+                    .builder
+                    .build_insert_value(st.get_undef(), present, 0, "opt_tag")
+                    .unwrap();
+                self.builder
+                    .build_insert_value(with_tag, val, 1, "opt")
+                    .unwrap()
+                    .into_struct_value()
+                    .into()
+            }
+            None => {
+                let null = self.context.ptr_type(AddressSpace::default()).const_null();
+                self.builder
+                    .build_select(present, val.into_pointer_value(), null, "opt")
+                    .unwrap()
+            }
+        }
+    }
+
+    /// Stores the result `val` of a map operation into `name`. A heap value
+    /// `taken` out of the map gains a reference (null ones are ignored).
+    fn map_result(&mut self, name: &str, val: BasicValueEnum<'ctx>, taken: bool) {
+        if taken && val.is_pointer_value() {
+            self.incref_value(val.into_pointer_value());
+        }
+        if let Some(sym) = self.symbols.get(name) {
+            self.builder.build_store(sym.ptr, val).unwrap();
+        }
+        self.temp_values.insert(name.to_string(), val);
+    }
+
+    /// `m[key]`: the value of `key`, as an optional that is absent when the
+    /// map has no such key.
+    pub fn generate_map_get(
+        &mut self,
+        name: &str,
+        map: &str,
+        key: &str,
+        ty: &MirType,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let (pair_type, _, value_ty) = self.map_pair_layout(ty);
+        let value_type = pair_type.get_field_type_at_index(1).unwrap();
+        let data = self.resolve_value(map).into_pointer_value();
+        let key_val = self.resolve_value(key);
+        let index = self.find_map_key(data, key_val, pair_type);
+        let present = self
+            .builder
+            .build_int_compare(
+                IntPredicate::SGE,
+                index,
+                self.context.i32_type().const_zero(),
+                "has_key",
+            )
+            .unwrap();
+
+        // The pairs may be a null pointer: only load when the key was found
+        let current_func = self
+            .builder
+            .get_insert_block()
+            .unwrap()
+            .get_parent()
+            .unwrap();
+        let lookup_block = self.builder.get_insert_block().unwrap();
+        let found_block = self
+            .context
+            .append_basic_block(current_func, "map.get.found");
+        let done_block = self
+            .context
+            .append_basic_block(current_func, "map.get.done");
+        self.builder
+            .build_conditional_branch(present, found_block, done_block)
+            .unwrap();
+
+        self.builder.position_at_end(found_block);
+        let value_ptr = self.map_pair_field_ptr(pair_type, data, index, 1);
+        let found_val = self
+            .builder
+            .build_load(value_type, value_ptr, "map_value")
+            .unwrap();
+        self.builder.build_unconditional_branch(done_block).unwrap();
+
+        self.builder.position_at_end(done_block);
+        let value = self.builder.build_phi(value_type, "map_value").unwrap();
+        value.add_incoming(&[
+            (&found_val, found_block),
+            (&value_type.const_zero(), lookup_block),
+        ]);
+        let val = self.map_optional(value_ty, present, value.as_basic_value());
+        self.map_result(name, val, true);
+        Some(val)
+    }
+
+    /// `m.insert(key, value)`: replaces the value of `key`, or adds the pair
+    /// at the end. The map holds a reference to both.
+    pub fn generate_map_insert(
+        &mut self,
+        map: &str,
+        key: &str,
+        value: &str,
+        ty: &MirType,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let (pair_type, key_ty, value_ty) = self.map_pair_layout(ty);
+        let (key_ty, value_ty) = (key_ty.clone(), value_ty.clone());
+        let key_val = self.owned_field_value(&key_ty, key);
+        let value_val = self.owned_field_value(&value_ty, value);
+        let i32_type = self.context.i32_type();
+        let one = i32_type.const_int(1, false);
+        // Looked up before reserving room, as a copied block has no index yet
+        let old_data = self.resolve_value(map).into_pointer_value();
+        let index = self.find_map_key(old_data, key_val, pair_type);
+        let present = self
+            .builder
+            .build_int_compare(IntPredicate::SGE, index, i32_type.const_zero(), "has_key")
+            .unwrap();
+        let (data, len, pair_type) = self.reserve_map(map, ty, |cg, len| {
+            let grown_len = cg.builder.build_int_add(len, one, "min_len").unwrap();
+            cg.builder
+                .build_select(present, len, grown_len, "min_len")
+                .unwrap()
+                .into_int_value()
+        });
+        let slot = self
+            .builder
+            .build_select(present, index, len, "slot")
+            .unwrap()
+            .into_int_value();
+        let grown_len = self.builder.build_int_add(len, one, "grown_len").unwrap();
+        let new_len = self
+            .builder
+            .build_select(present, len, grown_len, "new_len")
+            .unwrap()
+            .into_int_value();
+        let key_ptr = self.map_pair_field_ptr(pair_type, data, slot, 0);
+        self.builder.build_store(key_ptr, key_val).unwrap();
+        let value_ptr = self.map_pair_field_ptr(pair_type, data, slot, 1);
+        self.builder.build_store(value_ptr, value_val).unwrap();
+        self.finish_map_update(map, ty, data, new_len);
+
+        // A new or grown block is indexed from scratch; otherwise only a new pair is added
+        let moved = self
+            .builder
+            .build_int_compare(IntPredicate::NE, data, old_data, "moved")
+            .unwrap();
+        let capacity_fn = self.get_or_create_capacity_fn();
+        let capacity = |len: IntValue<'ctx>| {
+            self.builder
+                .build_call(capacity_fn, &[len.into()], "capacity")
+                .unwrap()
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_int_value()
+        };
+        let regrown = self
+            .builder
+            .build_int_compare(
+                IntPredicate::NE,
+                capacity(new_len),
+                capacity(len),
+                "regrown",
+            )
+            .unwrap();
+        let rebuild = self.builder.build_or(moved, regrown, "rebuild").unwrap();
+        let from = self
+            .builder
+            .build_select(rebuild, i32_type.const_zero(), len, "index_from")
+            .unwrap()
+            .into_int_value();
+        self.index_map(data, from, pair_type, key_val.is_pointer_value());
+        None
+    }
+
+    /// `m.remove(key)`: takes out the pair holding `key`, moving the ones
+    /// after it one place down, and indexes the map again. Returns its value
+    /// as an optional that is absent when the map has no such key.
+    pub fn generate_map_remove(
+        &mut self,
+        name: &str,
+        map: &str,
+        key: &str,
+        ty: &MirType,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let i32_type = self.context.i32_type();
+        let one = i32_type.const_int(1, false);
+        let (pair_type, _, value_ty) = self.map_pair_layout(ty);
+        let value_ty = value_ty.clone();
+        let key_val = self.resolve_value(key);
+        // Looked up before reserving room, as a copied block has no index yet
+        let old_data = self.resolve_value(map).into_pointer_value();
+        let index = self.find_map_key(old_data, key_val, pair_type);
+        let present = self
+            .builder
+            .build_int_compare(IntPredicate::SGE, index, i32_type.const_zero(), "has_key")
+            .unwrap();
+        let (data, len, pair_type) = self.reserve_map(map, ty, |_, len| len);
+        let value_type = pair_type.get_field_type_at_index(1).unwrap();
+        // A reserved block has room for at least one pair, so reading pair 0
+        // of an empty map stays inside it; the value is only used when present
+        let at = self
+            .builder
+            .build_select(present, index, i32_type.const_zero(), "at")
+            .unwrap()
+            .into_int_value();
+        let value_ptr = self.map_pair_field_ptr(pair_type, data, at, 1);
+        let removed = self
+            .builder
+            .build_load(value_type, value_ptr, "removed")
+            .unwrap();
+
+        let after = self.builder.build_int_add(at, one, "after").unwrap();
+        let trailing = self.builder.build_int_sub(len, after, "trailing").unwrap();
+        let moved = self
+            .builder
+            .build_select(present, trailing, i32_type.const_zero(), "moved")
+            .unwrap()
+            .into_int_value();
+        let moved = self
+            .builder
+            .build_int_z_extend(moved, self.context.i64_type(), "moved64")
+            .unwrap();
+        let size = self
+            .builder
+            .build_int_mul(moved, pair_type.size_of().unwrap(), "moved_size")
+            .unwrap();
+        let src = unsafe {
+            self.builder
+                .build_in_bounds_gep(pair_type, data, &[after], "src")
+        }
+        .unwrap();
+        let dest = unsafe {
+            self.builder
+                .build_in_bounds_gep(pair_type, data, &[at], "dest")
+        }
+        .unwrap();
+        self.builder.build_memmove(dest, 1, src, 1, size).unwrap();
+
+        let shrunk_len = self.builder.build_int_sub(len, one, "shrunk_len").unwrap();
+        let new_len = self
+            .builder
+            .build_select(present, shrunk_len, len, "new_len")
+            .unwrap()
+            .into_int_value();
+        self.finish_map_update(map, ty, data, new_len);
+        // The pairs after the removed one have new numbers, and a copied
+        // block no index; a map left as it was keeps its own
+        let copied = self
+            .builder
+            .build_int_compare(IntPredicate::NE, data, old_data, "copied")
+            .unwrap();
+        let rebuild = self.builder.build_or(present, copied, "rebuild").unwrap();
+        let from = self
+            .builder
+            .build_select(rebuild, i32_type.const_zero(), new_len, "index_from")
+            .unwrap()
+            .into_int_value();
+        self.index_map(data, from, pair_type, key_val.is_pointer_value());
+
+        let val = self.map_optional(&value_ty, present, removed);
+        self.map_result(name, val, true);
+        Some(val)
+    }
+
+    /// `m.clear()`: drops every pair and empties the index, keeping the
+    /// allocation.
+    pub fn generate_map_clear(&mut self, map: &str, ty: &MirType) -> Option<BasicValueEnum<'ctx>> {
+        let (data, _, pair_type) = self.reserve_map(map, ty, |_, len| len);
+        let zero = self.context.i32_type().const_zero();
+        self.finish_map_update(map, ty, data, zero);
+        let key_is_string = pair_type
+            .get_field_type_at_index(0)
+            .unwrap()
+            .is_pointer_type();
+        self.index_map(data, zero, pair_type, key_is_string);
+        None
+    }
+
+    /// `m.containsKey(key)`
+    pub fn generate_map_contains(
+        &mut self,
+        name: &str,
+        map: &str,
+        key: &str,
+        ty: &MirType,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let (pair_type, _, _) = self.map_pair_layout(ty);
+        let data = self.resolve_value(map).into_pointer_value();
+        let key_val = self.resolve_value(key);
+        let index = self.find_map_key(data, key_val, pair_type);
+        let i32_type = self.context.i32_type();
+        let present = self
+            .builder
+            .build_int_compare(IntPredicate::SGE, index, i32_type.const_zero(), "has_key")
+            .unwrap();
+        // Bools are i32 throughout codegen
+        let val = self
+            .builder
+            .build_int_z_extend(present, i32_type, "contains")
+            .unwrap()
+            .into();
+        self.map_result(name, val, false);
+        Some(val)
+    }
+
+    /// `m.keys()` (`field` 0) or `m.values()` (`field` 1): a new array of
+    /// the keys or values in insertion order, each heap one gaining a
+    /// reference held by the array.
+    pub fn generate_map_column(
+        &mut self,
+        name: &str,
+        map: &str,
+        ty: &MirType,
+        field: u32,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let (pair_type, key_ty, value_ty) = self.map_pair_layout(ty);
+        let elem_ty = if field == 0 { key_ty } else { value_ty }.clone();
+        let elem_type = pair_type.get_field_type_at_index(field).unwrap();
+        let i32_type = self.context.i32_type();
+        let data = self.resolve_value(map).into_pointer_value();
+        let len = self.load_runtime_map_length(data);
+        // A null block makes `__array_reserve` allocate an empty array with room for `len`
+        let null = self.context.ptr_type(AddressSpace::default()).const_null();
+        let array = self.reserve_block(null, len, elem_type.size_of().unwrap(), 0, 0);
+
+        let current_func = self
+            .builder
+            .get_insert_block()
+            .unwrap()
+            .get_parent()
+            .unwrap();
+        let cond_block = self
+            .context
+            .append_basic_block(current_func, "map.column.cond");
+        let body_block = self
+            .context
+            .append_basic_block(current_func, "map.column.body");
+        let exit_block = self
+            .context
+            .append_basic_block(current_func, "map.column.exit");
+        let index_alloca = self.builder.build_alloca(i32_type, "column_index").unwrap();
+        self.builder
+            .build_store(index_alloca, i32_type.const_zero())
+            .unwrap();
+        self.builder.build_unconditional_branch(cond_block).unwrap();
+
+        // while index < len: array[index] = pair[index].field
+        self.builder.position_at_end(cond_block);
+        let index = self
+            .builder
+            .build_load(i32_type, index_alloca, "column_i")
+            .unwrap()
+            .into_int_value();
+        let in_bounds = self
+            .builder
+            .build_int_compare(IntPredicate::SLT, index, len, "column_in_bounds")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(in_bounds, body_block, exit_block)
+            .unwrap();
+
+        self.builder.position_at_end(body_block);
+        let field_ptr = self.map_pair_field_ptr(pair_type, data, index, field);
+        let elem = self
+            .builder
+            .build_load(elem_type, field_ptr, "column_elem")
+            .unwrap();
+        if Self::field_is_rc(&elem_ty) {
+            self.incref_value(elem.into_pointer_value());
+        }
+        let elem_ptr = unsafe {
+            self.builder
+                .build_in_bounds_gep(elem_type, array, &[index], "column_elem_ptr")
+        }
+        .unwrap();
+        self.builder.build_store(elem_ptr, elem).unwrap();
+        let next = self
+            .builder
+            .build_int_add(index, i32_type.const_int(1, false), "column_next")
+            .unwrap();
+        self.builder.build_store(index_alloca, next).unwrap();
+        self.builder.build_unconditional_branch(cond_block).unwrap();
+
+        self.builder.position_at_end(exit_block);
+        let len_ptr = unsafe {
+            self.builder.build_in_bounds_gep(
+                self.context.i8_type(),
+                array,
+                &[i32_type.const_int((-4_i32) as u64, true)],
+                "column_len_ptr",
+            )
+        }
+        .unwrap();
+        self.builder.build_store(len_ptr, len).unwrap();
+
+        let val: BasicValueEnum<'ctx> = array.into();
+        self.heap_arrays.insert(name.to_string());
+        if let Some(shape) = Self::array_shape_from_type(&MirType::Array(Box::new(elem_ty.clone())))
+        {
+            self.runtime_array_shapes.insert(name.to_string(), shape);
+        }
+        if let Some(sym) = self.symbols.get(name) {
+            self.builder.build_store(sym.ptr, val).unwrap();
+        }
+        self.temp_values.insert(name.to_string(), val);
+        Some(val)
+    }
+}
@@ -1,5 +1,7 @@
 use crate::codegen::core::{CodeGen, MapMetadata};
-use inkwell::types::{BasicType, StructType};
+use crate::codegen::types::growable_arrays::array_capacity;
+use crate::codegen::types::growable_maps::MAP_INDEX_SIZE;
+use crate::mir::MirType;
 use inkwell::values::BasicValue;
 use inkwell::values::{BasicValueEnum, IntValue, PointerValue};
 use inkwell::AddressSpace;
@@ -21,10 +23,12 @@ impl<'ctx> CodeGen<'ctx> {
                 name.to_string(),
                 crate::codegen::MapMetadata {
                     length: 0,
-                    key_type: "Int".to_string(),
-                    value_type: "Int".to_string(),
+                    key_type: MirType::Int,
+                    value_type: MirType::Int,
                     key_is_string: false,
                     value_is_string: false,
+                    value_array: None,
+                    value_map: None,
                 },
             );
 
@@ -56,48 +60,91 @@ impl<'ctx> CodeGen<'ctx> {
         let key_type = first_key.get_type();
         let val_type = first_val.get_type();
 
-        let key_type_name = if key_type.is_int_type() {
-            "Int"
-        } else if key_type.is_pointer_type() {
-            "Str"
+        // Values that are collections themselves (`{"a": [1, 2]}` or `{"a": {"x": 1}}`)
+        let (value_array, value_map) = if val_type.is_pointer_type()
+            && entries
+                .iter()
+                .all(|(_, v)| self.array_shape(v).is_some() || self.map_shape(v).is_some())
+        {
+            let first = &entries[0].1;
+            (self.array_shape(first), self.map_shape(first).map(Box::new))
         } else {
-            "Unknown"
+            (None, None)
         };
 
-        let val_type_name = if val_type.is_int_type() {
-            "Int"
-        } else if val_type.is_pointer_type() {
-            "Str"
-        } else {
-            "Unknown"
+        // The map owns its collection values: temporaries hand over their reference
+        // (and whatever they own), variables are shared with an extra one
+        if value_array.is_some() || value_map.is_some() {
+            let mut owned_ptrs = Vec::new();
+            for (_, v) in entries {
+                let val = self.resolve_value(v);
+                // Empty map literals are a null pointer and own nothing
+                if val.into_pointer_value().is_null() {
+                    continue;
+                }
+                if !self.heap_arrays.contains(v) && !self.heap_maps.contains(v) {
+                    continue;
+                }
+                if self.symbols.contains_key(v) && !v.starts_with('%') {
+                    self.emit_incref(v);
+                } else {
+                    self.heap_arrays.remove(v);
+                    self.heap_maps.remove(v);
+                    if let Some(inner) = self.composite_string_ptrs.remove(v) {
+                        owned_ptrs.extend(inner);
+                    }
+                }
+                owned_ptrs.push(val);
+            }
+            if !owned_ptrs.is_empty() {
+                self.composite_string_ptrs
+                    .insert(name.to_string(), owned_ptrs);
+            }
+        }
+
+        let value_mir_type = match (&value_array, &value_map) {
+            (Some(values), _) => MirType::Array(Box::new(values.element_type.clone())),
+            (_, Some(values)) => MirType::Map(
+                Box::new(values.key_type.clone()),
+                Box::new(values.value_type.clone()),
+            ),
+            _ => Self::literal_element_type(val_type),
         };
 
         self.map_metadata.insert(
             name.to_string(),
             crate::codegen::MapMetadata {
                 length: entries.len(),
-                key_type: key_type_name.to_string(),
-                value_type: val_type_name.to_string(),
+                key_type: Self::literal_element_type(key_type),
+                value_type: value_mir_type,
                 key_is_string,
                 value_is_string,
+                value_array,
+                value_map,
             },
         );
 
         let pair_type = self.context.struct_type(&[key_type, val_type], false);
         let map_type = pair_type.array_type(entries.len() as u32);
 
-        // HEAP ALLOCATE with RC header
+        // HEAP ALLOCATE with RC header and length field
+        // Layout: [RC: 4 bytes][Length: 4 bytes][pairs...][index]
+        // with room for the pairs `insert` may add (see growable_maps)
         let malloc_fn = self.get_or_declare_malloc();
-        let map_size = map_type.size_of().unwrap();
-        let total_size = self.context.i64_type().const_int(8, false); // Use i64 for header size
+        let capacity = array_capacity(entries.len()) as u64;
+        let map_size = pair_type.array_type(capacity as u32).size_of().unwrap();
+        let header_and_index = self
+            .context
+            .i64_type()
+            .const_int(8 + capacity * MAP_INDEX_SIZE, false);
         let total_size = self
             .builder
-            .build_int_add(total_size, map_size, "total_size")
+            .build_int_add(header_and_index, map_size, "total_size")
             .unwrap();
 
         let heap_ptr = self
             .builder
-            .build_call(malloc_fn, &[total_size.into()], "heap_map")
+            .build_call(malloc_fn, &[self.to_size_t(total_size).into()], "heap_map")
             .unwrap()
             .try_as_basic_value()
             .left()
@@ -117,6 +164,26 @@ impl<'ctx> CodeGen<'ctx> {
             .build_store(rc_ptr, self.context.i32_type().const_int(1, false))
             .unwrap();
 
+        // Store the length after the RC so maps nested as values can be walked at runtime
+        let len_ptr = unsafe {
+            self.builder
+                .build_gep(
+                    self.context.i8_type(),
+                    heap_ptr,
+                    &[self.context.i32_type().const_int(4, false)],
+                    "len_ptr",
+                )
+                .unwrap()
+        };
+        self.builder
+            .build_store(
+                len_ptr,
+                self.context
+                    .i32_type()
+                    .const_int(entries.len() as u64, false),
+            )
+            .unwrap();
+
         // Get data pointer
         let data_ptr = unsafe {
             self.builder
@@ -179,48 +246,26 @@ impl<'ctx> CodeGen<'ctx> {
             }
         }
 
+        self.index_map(
+            data_ptr,
+            self.context.i32_type().const_zero(),
+            pair_type,
+            key_type.is_pointer_type(),
+        );
+
         self.temp_values.insert(name.to_string(), data_ptr.into());
         self.heap_maps.insert(name.to_string());
         Some(data_ptr.into())
     }
 
+    /// Length of the map `map_name`, read from its heap header. Global map
+    /// constants have no header: theirs comes from their metadata.
     pub fn get_map_length(&self, map_name: &str) -> inkwell::values::IntValue<'ctx> {
-        if let Some(metadata) = self.map_metadata.get(map_name) {
-            self.context
-                .i32_type()
-                .const_int(metadata.length as u64, false)
-        } else {
-            // Try one more time to find by pointer equality before giving up
-            if let Some(sym) = self.symbols.get(map_name) {
-                if let Ok(loaded) = self.builder.build_load(sym.ty, sym.ptr, "check_map_len") {
-                    if loaded.is_pointer_value() {
-                        let ptr_val = loaded.into_pointer_value();
-                        for (other_name, metadata) in &self.map_metadata {
-                            if let Some(other_val) = self.temp_values.get(other_name) {
-                                if other_val.is_pointer_value()
-                                    && other_val.into_pointer_value() == ptr_val
-                                {
-                                    return self
-                                        .context
-                                        .i32_type()
-                                        .const_int(metadata.length as u64, false);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-
-            eprintln!(
-                "Warning: No map metadata found for '{}' in get_map_length",
-                map_name
-            );
-            eprintln!(
-                "Available map metadata: {:?}",
-                self.map_metadata.keys().collect::<Vec<_>>()
-            );
-            self.context.i32_type().const_int(0, false)
+        if let Some(map_ptr) = self.collection_ptr(map_name) {
+            return self.load_runtime_map_length(map_ptr);
         }
+        let length = self.map_metadata.get(map_name).map_or(0, |m| m.length);
+        self.context.i32_type().const_int(length as u64, false)
     }
 
     pub fn get_map_types(
@@ -230,34 +275,11 @@ impl<'ctx> CodeGen<'ctx> {
         inkwell::types::BasicTypeEnum<'ctx>,
         inkwell::types::BasicTypeEnum<'ctx>,
     ) {
-        if let Some(metadata) = self.map_metadata.get(map_name) {
-            let key_type = match metadata.key_type.as_str() {
-                "Int" => self.context.i32_type().into(),
-                "Bool" => self.context.bool_type().into(),
-                "Str" => self.context.ptr_type(AddressSpace::default()).into(),
-                _ => {
-                    eprintln!(
-                        "WARNING: Unknown key type '{}' for map '{}', defaulting to i32",
-                        metadata.key_type, map_name
-                    );
-                    self.context.i32_type().into()
-                }
-            };
-
-            let val_type = match metadata.value_type.as_str() {
-                "Int" => self.context.i32_type().into(),
-                "Bool" => self.context.bool_type().into(),
-                "Str" => self.context.ptr_type(AddressSpace::default()).into(),
-                _ => {
-                    eprintln!(
-                        "WARNING: Unknown value type '{}' for map '{}', defaulting to i32",
-                        metadata.value_type, map_name
-                    );
-                    self.context.i32_type().into()
-                }
-            };
-
-            (key_type, val_type)
+        if let Some(metadata) = self.map_shape(map_name) {
+            (
+                self.struct_field_type(&metadata.key_type),
+                self.struct_field_type(&metadata.value_type),
+            )
         } else {
             eprintln!("\n╔════════════════════════════════════════════════════════════════════╗");
             eprintln!("║ ERROR: No metadata found for map '{}'", map_name);
@@ -299,13 +321,76 @@ impl<'ctx> CodeGen<'ctx> {
 
     /// Returns true if the map contains string keys or values.
     pub fn map_contains_strings(&self, map_name: &str) -> (bool, bool) {
-        if let Some(metadata) = self.map_metadata.get(map_name) {
+        if let Some(metadata) = self.map_shape(map_name) {
             (metadata.key_is_string, metadata.value_is_string)
         } else {
             (false, false)
         }
     }
 
+    /// Looks up what is known about a map: its own metadata, or the shape
+    /// it was given as a value of another map.
+    pub fn map_shape(&self, map_name: &str) -> Option<MapMetadata> {
+        self.map_metadata
+            .get(map_name)
+            .or_else(|| self.runtime_map_shapes.get(map_name))
+            .cloned()
+    }
+
+    /// Builds the shape of a map from its MIR type, e.g. `Map<String, Array<Int>>`.
+    /// Returns None for non-map types.
+    pub fn map_shape_from_type(ty: &MirType) -> Option<MapMetadata> {
+        let (key, value) = ty.map_parts()?;
+        Some(MapMetadata {
+            length: 0,
+            key_type: key.clone(),
+            value_type: value.clone(),
+            key_is_string: *key == MirType::Str,
+            value_is_string: *value == MirType::Str,
+            value_array: Self::array_shape_from_type(value),
+            value_map: Self::map_shape_from_type(value).map(Box::new),
+        })
+    }
+
+    /// Reads a map's length from its heap header (stored 4 bytes before the pairs).
+    /// Empty map literals are a null pointer and read as length 0.
+    pub fn load_runtime_map_length(&self, map_ptr: PointerValue<'ctx>) -> IntValue<'ctx> {
+        let i32_type = self.context.i32_type();
+        let zero_len = match self.module.get_global("empty_map_len") {
+            Some(global) => global,
+            None => {
+                let global = self.module.add_global(i32_type, None, "empty_map_len");
+                global.set_initializer(&i32_type.const_zero());
+                global.set_constant(true);
+                global
+            }
+        };
+        let header_len_ptr = unsafe {
+            self.builder.build_in_bounds_gep(
+                self.context.i8_type(),
+                map_ptr,
+                &[i32_type.const_int((-4_i32) as u64, true)],
+                "map_len_ptr",
+            )
+        }
+        .unwrap();
+        let is_null = self.builder.build_is_null(map_ptr, "map_is_null").unwrap();
+        let len_ptr = self
+            .builder
+            .build_select(
+                is_null,
+                zero_len.as_pointer_value(),
+                header_len_ptr,
+                "map_len_src",
+            )
+            .unwrap()
+            .into_pointer_value();
+        self.builder
+            .build_load(i32_type, len_ptr, "map_len")
+            .unwrap()
+            .into_int_value()
+    }
+
     /// Extract map key-value pair with RC handling
     pub fn load_map_pair_with_rc(
         &mut self,
@@ -356,37 +441,11 @@ impl<'ctx> CodeGen<'ctx> {
 
         // Handle RC for strings
         if key_is_string {
-            let str_ptr = key_val.into_pointer_value();
-            let rc_header = unsafe {
-                self.builder.build_in_bounds_gep(
-                    self.context.i8_type(),
-                    str_ptr,
-                    &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                    "rc_header",
-                )
-            }
-            .unwrap();
-            let incref = self.incref_fn.unwrap();
-            self.builder
-                .build_call(incref, &[rc_header.into()], "")
-                .unwrap();
+            self.incref_value(key_val.into_pointer_value());
         }
 
         if val_is_string {
-            let str_ptr = val_val.into_pointer_value();
-            let rc_header = unsafe {
-                self.builder.build_in_bounds_gep(
-                    self.context.i8_type(),
-                    str_ptr,
-                    &[self.context.i32_type().const_int((-8_i32) as u64, true)],
-                    "rc_header",
-                )
-            }
-            .unwrap();
-            let incref = self.incref_fn.unwrap();
-            self.builder
-                .build_call(incref, &[rc_header.into()], "")
-                .unwrap();
+            self.incref_value(val_val.into_pointer_value());
         }
 
         (key_val, val_val)
@@ -407,6 +466,22 @@ impl<'ctx> CodeGen<'ctx> {
             .build_call(printf_fn, &[open_brace.as_pointer_value().into()], "")
             .unwrap();
 
+        // Maps print as many pairs as their header says; only global
+        // constants, which have none, are unrolled from their metadata
+        if let Some(shape) = self.map_shape(map_name) {
+            if let Some(map_ptr) = self.collection_ptr(map_name) {
+                self.print_map_pairs_runtime(map_ptr, &shape);
+                let close_brace = self
+                    .builder
+                    .build_global_string_ptr("}", "close_brace")
+                    .unwrap();
+                self.builder
+                    .build_call(printf_fn, &[close_brace.as_pointer_value().into()], "")
+                    .unwrap();
+                return;
+            }
+        }
+
         // Get map metadata
         let metadata = self.map_metadata.get(map_name).cloned();
 
@@ -428,21 +503,8 @@ impl<'ctx> CodeGen<'ctx> {
                 self.resolve_value(map_name).into_pointer_value()
             };
 
-            let key_type = if metadata.key_type == "Str" {
-                self.context
-                    .ptr_type(AddressSpace::default())
-                    .as_basic_type_enum()
-            } else {
-                self.context.i32_type().as_basic_type_enum()
-            };
-
-            let val_type = if metadata.value_type == "Str" {
-                self.context
-                    .ptr_type(AddressSpace::default())
-                    .as_basic_type_enum()
-            } else {
-                self.context.i32_type().as_basic_type_enum()
-            };
+            let key_type = self.struct_field_type(&metadata.key_type);
+            let val_type = self.struct_field_type(&metadata.value_type);
 
             let pair_type = self.context.struct_type(&[key_type, val_type], false);
             let map_array_type = pair_type.array_type(metadata.length as u32);
@@ -484,7 +546,7 @@ impl<'ctx> CodeGen<'ctx> {
                 let val_val = self.builder.build_load(val_type, val_ptr, "val").unwrap();
 
                 // Print key
-                if metadata.key_type == "Str" {
+                if metadata.key_type == MirType::Str {
                     let key_fmt = self
                         .builder
                         .build_global_string_ptr("\"%s\": ", "key_fmt")
@@ -511,7 +573,18 @@ impl<'ctx> CodeGen<'ctx> {
                 }
 
                 // Print value
-                if metadata.value_type == "Str" {
+                if metadata.value_array.is_some() || metadata.value_map.is_some() {
+                    self.print_map_value(val_val, &metadata);
+                    if i < metadata.length - 1 {
+                        let sep = self
+                            .builder
+                            .build_global_string_ptr(", ", "val_sep")
+                            .unwrap();
+                        self.builder
+                            .build_call(printf_fn, &[sep.as_pointer_value().into()], "")
+                            .unwrap();
+                    }
+                } else if metadata.value_type == MirType::Str {
                     let val_fmt = if i < metadata.length - 1 {
                         "\"%s\", "
                     } else {
@@ -558,4 +631,192 @@ impl<'ctx> CodeGen<'ctx> {
             .build_call(printf_fn, &[close_brace.as_pointer_value().into()], "")
             .unwrap();
     }
+
+    /// Prints a single map value of the given map shape, without separator.
+    /// Collection values are walked through the lengths in their headers.
+    fn print_map_value(&mut self, val: BasicValueEnum<'ctx>, shape: &MapMetadata) {
+        let printf_fn = self.get_or_declare_printf();
+        if let Some(array_shape) = &shape.value_array {
+            let open = self
+                .builder
+                .build_global_string_ptr("[", "open_bracket")
+                .unwrap();
+            self.builder
+                .build_call(printf_fn, &[open.as_pointer_value().into()], "")
+                .unwrap();
+            self.print_array_elements_runtime(val.into_pointer_value(), array_shape);
+            let close = self
+                .builder
+                .build_global_string_ptr("]", "close_bracket")
+                .unwrap();
+            self.builder
+                .build_call(printf_fn, &[close.as_pointer_value().into()], "")
+                .unwrap();
+        } else if let Some(map_shape) = &shape.value_map {
+            let open = self
+                .builder
+                .build_global_string_ptr("{", "open_brace")
+                .unwrap();
+            self.builder
+                .build_call(printf_fn, &[open.as_pointer_value().into()], "")
+                .unwrap();
+            self.print_map_pairs_runtime(val.into_pointer_value(), map_shape);
+            let close = self
+                .builder
+                .build_global_string_ptr("}", "close_brace")
+                .unwrap();
+            self.builder
+                .build_call(printf_fn, &[close.as_pointer_value().into()], "")
+                .unwrap();
+        } else {
+            let fmt = if shape.value_type == MirType::Str {
+                "\"%s\""
+            } else {
+                "%d"
+            };
+            let fmt_global = self
+                .builder
+                .build_global_string_ptr(fmt, "val_fmt")
+                .unwrap();
+            self.builder
+                .build_call(
+                    printf_fn,
+                    &[fmt_global.as_pointer_value().into(), val.into()],
+                    "",
+                )
+                .unwrap();
+        }
+    }
+
+    /// Prints the pairs of a map whose length is only known at runtime
+    /// (a value of another map), comma separated and without braces.
+    /// Emits a loop over the length stored in the map's header.
+    fn print_map_pairs_runtime(&mut self, map_ptr: PointerValue<'ctx>, shape: &MapMetadata) {
+        let printf_fn = self.get_or_declare_printf();
+        let i32_type = self.context.i32_type();
+        let current_func = self
+            .builder
+            .get_insert_block()
+            .unwrap()
+            .get_parent()
+            .unwrap();
+        let cond_block = self
+            .context
+            .append_basic_block(current_func, "map.print.cond");
+        let body_block = self
+            .context
+            .append_basic_block(current_func, "map.print.body");
+        let exit_block = self
+            .context
+            .append_basic_block(current_func, "map.print.exit");
+
+        let len = self.load_runtime_map_length(map_ptr);
+        let index_alloca = self.builder.build_alloca(i32_type, "map_index").unwrap();
+        self.builder
+            .build_store(index_alloca, i32_type.const_zero())
+            .unwrap();
+        self.builder.build_unconditional_branch(cond_block).unwrap();
+
+        // while index < len
+        self.builder.position_at_end(cond_block);
+        let index = self
+            .builder
+            .build_load(i32_type, index_alloca, "map_i")
+            .unwrap()
+            .into_int_value();
+        let in_bounds = self
+            .builder
+            .build_int_compare(inkwell::IntPredicate::SLT, index, len, "map_in_bounds")
+            .unwrap();
+        self.builder
+            .build_conditional_branch(in_bounds, body_block, exit_block)
+            .unwrap();
+
+        // Separator before every pair but the first
+        self.builder.position_at_end(body_block);
+        let is_first = self
+            .builder
+            .build_int_compare(
+                inkwell::IntPredicate::EQ,
+                index,
+                i32_type.const_zero(),
+                "map_first",
+            )
+            .unwrap();
+        let none = self
+            .builder
+            .build_global_string_ptr("", "map_sep_none")
+            .unwrap();
+        let comma = self
+            .builder
+            .build_global_string_ptr(", ", "map_sep")
+            .unwrap();
+        let sep = self
+            .builder
+            .build_select(
+                is_first,
+                none.as_pointer_value(),
+                comma.as_pointer_value(),
+                "map_sep_sel",
+            )
+            .unwrap();
+        let sep_fmt = self
+            .builder
+            .build_global_string_ptr("%s", "map_sep_fmt")
+            .unwrap();
+        self.builder
+            .build_call(
+                printf_fn,
+                &[sep_fmt.as_pointer_value().into(), sep.into()],
+                "",
+            )
+            .unwrap();
+
+        let key_type = self.struct_field_type(&shape.key_type);
+        let val_type = self.struct_field_type(&shape.value_type);
+        let pair_type = self.context.struct_type(&[key_type, val_type], false);
+        let pair_ptr = unsafe {
+            self.builder
+                .build_in_bounds_gep(pair_type, map_ptr, &[index], "pair_ptr")
+        }
+        .unwrap();
+        let key_ptr = self
+            .builder
+            .build_struct_gep(pair_type, pair_ptr, 0, "key_ptr")
+            .unwrap();
+        let key_val = self.builder.build_load(key_type, key_ptr, "key").unwrap();
+        let val_ptr = self
+            .builder
+            .build_struct_gep(pair_type, pair_ptr, 1, "val_ptr")
+            .unwrap();
+        let val_val = self.builder.build_load(val_type, val_ptr, "val").unwrap();
+
+        let key_fmt = if shape.key_type == MirType::Str {
+            "\"%s\": "
+        } else {
+            "%d: "
+        };
+        let key_fmt = self
+            .builder
+            .build_global_string_ptr(key_fmt, "key_fmt")
+            .unwrap();
+        self.builder
+            .build_call(
+                printf_fn,
+                &[key_fmt.as_pointer_value().into(), key_val.into()],
+                "",
+            )
+            .unwrap();
+        // Leaves the builder in the exit block of any loop printing a nested value
+        self.print_map_value(val_val, shape);
+
+        let next = self
+            .builder
+            .build_int_add(index, i32_type.const_int(1, false), "map_next")
+            .unwrap();
+        self.builder.build_store(index_alloca, next).unwrap();
+        self.builder.build_unconditional_branch(cond_block).unwrap();
+
+        self.builder.position_at_end(exit_block);
+    }
 }
@@ -1,2 +1,9 @@
+pub mod array_builtins;
 pub mod arrays;
+pub mod closures;
+pub mod enums;
+pub mod formatter;
+pub mod growable_arrays;
+pub mod growable_maps;
 pub mod maps;
+pub mod structs;
@@ -0,0 +1,614 @@
+use crate::codegen::core::CodeGen;
+use crate::mir::types::CLOSURE_ENV;
+use crate::mir::MirType;
+use inkwell::types::{BasicTypeEnum, StructType};
+use inkwell::values::{BasicValueEnum, FunctionValue, PointerValue};
+use inkwell::AddressSpace;
+
+/// How a struct field takes part in reference counting.
+enum FieldRc<'a> {
+    String,
+    Collection,      // arrays and maps
+    Struct(&'a str), // a nested struct or enum value, released through its own release function
+}
+
+/// Struct values live on the heap like strings, arrays and maps:
+/// Layout: [RC: 4 bytes][weak count: 4 bytes][fields as an LLVM struct]
+/// A struct value is the pointer to its fields. Fields holding strings,
+/// arrays, maps or structs own one reference to their value; the struct's
+/// `__release_<Name>` function drops them when the last reference goes away.
+/// A `weak` field owns none: it counts in its target's weak count, which keeps
+/// the memory (not the fields) of a released struct until the field lets go.
+impl<'ctx> CodeGen<'ctx> {
+    /// The named LLVM struct holding the fields of struct `name`, in declaration order.
+    pub fn struct_llvm_type(&self, name: &str) -> StructType<'ctx> {
+        let llvm_name = format!("struct.{}", name);
+        if let Some(st) = self.module.get_struct_type(&llvm_name) {
+            return st;
+        }
+        let st = self.context.opaque_struct_type(&llvm_name);
+        let fields: Vec<BasicTypeEnum<'ctx>> = self
+            .struct_layouts
+            .get(name)
+            .map(|fields| {
+                fields
+                    .iter()
+                    .map(|(_, ty)| self.struct_field_type(ty))
+                    .collect()
+            })
+            .unwrap_or_default();
+        st.set_body(&fields, false);
+        st
+    }
+
+    /// LLVM type of one struct field: pointers for heap types (structs included),
+    /// f64/i64 for Float/Int64, i32 for Int and Bool, optionals as everywhere else.
+    pub fn struct_field_type(&self, ty: &MirType) -> BasicTypeEnum<'ctx> {
+        if matches!(ty, MirType::Optional(_)) {
+            self.optional_llvm_type(ty)
+        } else {
+            self.optional_payload_type(ty)
+        }
+    }
+
+    /// Index and type of `field` in struct `struct_name`.
+    fn struct_field(&self, struct_name: &str, field: &str) -> Option<(u32, MirType)> {
+        let fields = self.struct_layouts.get(struct_name)?;
+        let index = fields.iter().position(|(f, _)| f == field)?;
+        Some((index as u32, fields[index].1.clone()))
+    }
+
+    /// Whether `field` of struct `struct_name` is declared `weak`.
+    fn is_weak_field(&self, struct_name: &str, field: &str) -> bool {
+        self.weak_fields
+            .get(struct_name)
+            .is_some_and(|weak| weak.iter().any(|name| name == field))
+    }
+
+    /// Whether some `weak` field can point to struct `struct_name`.
+    fn is_weak_target(&self, struct_name: &str) -> bool {
+        self.weak_fields.iter().any(|(owner, weak)| {
+            weak.iter().any(|field| {
+                self.struct_field(owner, field)
+                    .is_some_and(|(_, ty)| ty.optional_payload().struct_name() == Some(struct_name))
+            })
+        })
+    }
+
+    /// Whether a field of type `ty` holds a reference to its value.
+    pub fn field_is_rc(ty: &MirType) -> bool {
+        Self::field_rc(ty).is_some()
+    }
+
+    /// Optional fields are stored as they are and never released.
+    fn field_rc(ty: &MirType) -> Option<FieldRc<'_>> {
+        match ty {
+            MirType::Str => Some(FieldRc::String),
+            MirType::Array(_) | MirType::Map(_, _) => Some(FieldRc::Collection),
+            _ => ty.struct_name().map(FieldRc::Struct),
+        }
+    }
+
+    /// Pointer to `field` of the struct value `instance`, with the field's index and type.
+    fn struct_field_ptr(
+        &self,
+        instance: &str,
+        struct_name: &str,
+        field: &str,
+    ) -> Option<(PointerValue<'ctx>, MirType)> {
+        let (index, ty) = self.struct_field(struct_name, field)?;
+        let data_ptr = self.resolve_value(instance);
+        if !data_ptr.is_pointer_value() {
+            return None;
+        }
+        let field_ptr = self
+            .builder
+            .build_struct_gep(
+                self.struct_llvm_type(struct_name),
+                data_ptr.into_pointer_value(),
+                index,
+                &format!("{}_field_ptr", field),
+            )
+            .unwrap();
+        Some((field_ptr, ty))
+    }
+
+    /// The value `value` as stored into a field of type `ty`, holding its own reference:
+    /// temporaries hand over theirs, variables and borrowed values are incref'd, and
+    /// string constants (which have no RC header) are copied to the heap.
+    pub fn owned_field_value(&mut self, ty: &MirType, value: &str) -> BasicValueEnum<'ctx> {
+        let val = self.resolve_value(value);
+        if !val.is_pointer_value() {
+            return val;
+        }
+
+        let is_temp = value.starts_with('%');
+        let takes_temp = match Self::field_rc(ty) {
+            None => return val,
+            Some(FieldRc::String) => {
+                if !self.heap_strings.contains(value) {
+                    let ptr_type = self.context.ptr_type(AddressSpace::default());
+                    let copy_fn =
+                        self.get_or_create_to_str_fn("__str_to_str", ptr_type.into(), "%s", false);
+                    return self
+                        .builder
+                        .build_call(copy_fn, &[val.into()], "field_str")
+                        .unwrap()
+                        .try_as_basic_value()
+                        .left()
+                        .unwrap();
+                }
+                is_temp && self.heap_strings.remove(value)
+            }
+            Some(FieldRc::Collection) => {
+                is_temp && (self.heap_arrays.remove(value) || self.heap_maps.remove(value))
+            }
+            Some(FieldRc::Struct(_)) => is_temp && self.heap_structs.remove(value).is_some(),
+        };
+        if !takes_temp {
+            self.incref_value(val.into_pointer_value());
+        }
+        val
+    }
+
+    /// Drops the reference a field of type `ty` holds to `val`.
+    pub fn release_field_value(&self, ty: &MirType, val: BasicValueEnum<'ctx>) {
+        if !val.is_pointer_value() {
+            return;
+        }
+        let ptr = val.into_pointer_value();
+        match Self::field_rc(ty) {
+            Some(FieldRc::String) | Some(FieldRc::Collection) => {
+                self.decref_value(ptr);
+            }
+            Some(FieldRc::Struct(name)) => self.release_struct_value(name, ptr),
+            None => {}
+        }
+    }
+
+    /// Drops one reference to the struct value `ptr`.
+    pub fn release_struct_value(&self, struct_name: &str, ptr: PointerValue<'ctx>) {
+        let release_fn = self.get_or_create_release_fn(struct_name);
+        self.builder
+            .build_call(release_fn, &[ptr.into()], "")
+            .unwrap();
+    }
+
+    /// Drops the reference the variable `var` holds to its struct value.
+    pub fn release_struct_var(&self, var: &str, struct_name: &str) {
+        if let Some(sym) = self.symbols.get(var) {
+            let val = self.builder.build_load(sym.ty, sym.ptr, "loaded").unwrap();
+            if val.is_pointer_value() {
+                self.release_struct_value(struct_name, val.into_pointer_value());
+            }
+        }
+    }
+
+    /// Emits (once) `void __release_<Name>(ptr)`: when the reference being dropped
+    /// is the last one, the heap fields (or the enum payload, or the closure's
+    /// captures) are released first;
+    /// then the RC goes down (freeing the value at zero). A struct that weak fields
+    /// still point to is left at RC 0 for `__weak_release` to free. Null pointers are ignored.
+    fn get_or_create_release_fn(&self, struct_name: &str) -> FunctionValue<'ctx> {
+        let fn_name = format!("__release_{}", struct_name);
+        if let Some(func) = self.module.get_function(&fn_name) {
+            return func;
+        }
+
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let i32_type = self.context.i32_type();
+        let function = self.module.add_function(
+            &fn_name,
+            self.context.void_type().fn_type(&[ptr_type.into()], false),
+            None,
+        );
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        let check_last = self.context.append_basic_block(function, "check_last");
+        let release_fields = self.context.append_basic_block(function, "release_fields");
+        let drop_ref = self.context.append_basic_block(function, "drop_ref");
+        let exit = self.context.append_basic_block(function, "exit");
+
+        self.builder.position_at_end(entry);
+        let data_ptr = function.get_nth_param(0).unwrap().into_pointer_value();
+        let is_null = self.builder.build_is_null(data_ptr, "is_null").unwrap();
+        self.builder
+            .build_conditional_branch(is_null, exit, check_last)
+            .unwrap();
+
+        self.builder.position_at_end(check_last);
+        let rc_header = self.rc_header_ptr(data_ptr);
+        let rc = self
+            .builder
+            .build_load(i32_type, rc_header, "rc")
+            .unwrap()
+            .into_int_value();
+        let is_last = self
+            .builder
+            .build_int_compare(
+                inkwell::IntPredicate::SLE,
+                rc,
+                i32_type.const_int(1, false),
+                "is_last",
+            )
+            .unwrap();
+        self.builder
+            .build_conditional_branch(is_last, release_fields, drop_ref)
+            .unwrap();
+
+        self.builder.position_at_end(release_fields);
+        if struct_name == CLOSURE_ENV {
+            self.release_closure_captures(data_ptr);
+        } else if self.enum_layouts.contains_key(struct_name) {
+            self.release_enum_payload(function, struct_name, data_ptr);
+        } else {
+            let struct_type = self.struct_llvm_type(struct_name);
+            let fields = self
+                .struct_layouts
+                .get(struct_name)
+                .cloned()
+                .unwrap_or_default();
+            for (index, (field, ty)) in fields.iter().enumerate() {
+                let weak = self.is_weak_field(struct_name, field);
+                if Self::field_rc(ty).is_none() && !weak {
+                    continue;
+                }
+                let field_ptr = self
+                    .builder
+                    .build_struct_gep(struct_type, data_ptr, index as u32, field)
+                    .unwrap();
+                let val = self
+                    .builder
+                    .build_load(self.struct_field_type(ty), field_ptr, "field_val")
+                    .unwrap();
+                if weak {
+                    self.release_weak_value(val);
+                } else {
+                    self.release_field_value(ty, val);
+                }
+            }
+        }
+        if self.is_weak_target(struct_name) {
+            // Weak fields still see this struct: mark it released and keep the memory
+            let keep = self.context.append_basic_block(function, "keep_for_weak");
+            let weak_count = self
+                .builder
+                .build_load(i32_type, self.weak_count_ptr(data_ptr), "weak_count")
+                .unwrap()
+                .into_int_value();
+            let no_weak = self
+                .builder
+                .build_int_compare(
+                    inkwell::IntPredicate::EQ,
+                    weak_count,
+                    i32_type.const_zero(),
+                    "no_weak",
+                )
+                .unwrap();
+            self.builder
+                .build_conditional_branch(no_weak, drop_ref, keep)
+                .unwrap();
+            self.builder.position_at_end(keep);
+            self.builder
+                .build_store(rc_header, i32_type.const_zero())
+                .unwrap();
+            self.builder.build_unconditional_branch(exit).unwrap();
+        } else {
+            self.builder.build_unconditional_branch(drop_ref).unwrap();
+        }
+
+        self.builder.position_at_end(drop_ref);
+        self.decref_value(data_ptr);
+        self.builder.build_unconditional_branch(exit).unwrap();
+
+        self.builder.position_at_end(exit);
+        self.builder.build_return(None).unwrap();
+
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// Allocates a struct or enum value laid out as `data_type` behind the RC
+    /// header, with RC = 1 and no weak references. Returns the pointer to the data.
+    pub fn alloc_rc_data(&self, data_type: StructType<'ctx>) -> PointerValue<'ctx> {
+        let i64_type = self.context.i64_type();
+        let total_size = self
+            .builder
+            .build_int_add(
+                i64_type.const_int(8, false),
+                data_type.size_of().unwrap(),
+                "total_size",
+            )
+            .unwrap();
+
+        let malloc_fn = self.get_or_declare_malloc();
+        let heap_ptr = self
+            .builder
+            .build_call(
+                malloc_fn,
+                &[self.to_size_t(total_size).into()],
+                "heap_struct",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value();
+        self.builder
+            .build_store(heap_ptr, self.context.i32_type().const_int(1, false))
+            .unwrap();
+        let data_ptr = unsafe {
+            self.builder.build_gep(
+                self.context.i8_type(),
+                heap_ptr,
+                &[self.context.i32_type().const_int(8, false)],
+                "struct_data",
+            )
+        }
+        .unwrap();
+        self.builder
+            .build_store(
+                self.weak_count_ptr(data_ptr),
+                self.context.i32_type().const_zero(),
+            )
+            .unwrap();
+        data_ptr
+    }
+
+    /// Pointer to the weak count of the struct value `data_ptr`, the header word after the RC.
+    fn weak_count_ptr(&self, data_ptr: PointerValue<'ctx>) -> PointerValue<'ctx> {
+        unsafe {
+            self.builder.build_in_bounds_gep(
+                self.context.i8_type(),
+                data_ptr,
+                &[self.context.i32_type().const_int((-4_i32) as u64, true)],
+                "weak_count_ptr",
+            )
+        }
+        .unwrap()
+    }
+
+    /// Adds the weak reference a `weak` field holds to the struct value `val`.
+    fn retain_weak_value(&self, val: BasicValueEnum<'ctx>) {
+        if val.is_pointer_value() {
+            let retain_fn = self.get_or_create_weak_fn("__weak_retain");
+            self.builder
+                .build_call(retain_fn, &[val.into()], "")
+                .unwrap();
+        }
+    }
+
+    /// Drops the weak reference a `weak` field holds to the struct value `val`.
+    fn release_weak_value(&self, val: BasicValueEnum<'ctx>) {
+        if val.is_pointer_value() {
+            let release_fn = self.get_or_create_weak_fn("__weak_release");
+            self.builder
+                .build_call(release_fn, &[val.into()], "")
+                .unwrap();
+        }
+    }
+
+    /// Emits (once) the weak reference helpers, all taking a struct value and ignoring null:
+    /// - `void __weak_retain(ptr)` adds one to the weak count
+    /// - `void __weak_release(ptr)` takes one off, and frees a released struct at zero
+    /// - `ptr __weak_get(ptr)` is the value itself, or null once it has been released
+    fn get_or_create_weak_fn(&self, fn_name: &str) -> FunctionValue<'ctx> {
+        if let Some(func) = self.module.get_function(fn_name) {
+            return func;
+        }
+
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let i32_type = self.context.i32_type();
+        let fn_type = if fn_name == "__weak_get" {
+            ptr_type.fn_type(&[ptr_type.into()], false)
+        } else {
+            self.context.void_type().fn_type(&[ptr_type.into()], false)
+        };
+        let function = self.module.add_function(fn_name, fn_type, None);
+        let saved_block = self.builder.get_insert_block();
+        let entry = self.context.append_basic_block(function, "entry");
+        let body = self.context.append_basic_block(function, "body");
+        let exit = self.context.append_basic_block(function, "exit");
+
+        self.builder.position_at_end(entry);
+        let data_ptr = function.get_nth_param(0).unwrap().into_pointer_value();
+        let is_null = self.builder.build_is_null(data_ptr, "is_null").unwrap();
+        self.builder
+            .build_conditional_branch(is_null, exit, body)
+            .unwrap();
+
+        self.builder.position_at_end(body);
+        let rc_header = self.rc_header_ptr(data_ptr);
+        let rc = self
+            .builder
+            .build_load(i32_type, rc_header, "rc")
+            .unwrap()
+            .into_int_value();
+        let released = self
+            .builder
+            .build_int_compare(
+                inkwell::IntPredicate::EQ,
+                rc,
+                i32_type.const_zero(),
+                "released",
+            )
+            .unwrap();
+        match fn_name {
+            "__weak_get" => {
+                self.builder.build_unconditional_branch(exit).unwrap();
+                self.builder.position_at_end(exit);
+                let result = self.builder.build_phi(ptr_type, "result").unwrap();
+                let live = self
+                    .builder
+                    .build_select(released, ptr_type.const_null(), data_ptr, "live")
+                    .unwrap();
+                result.add_incoming(&[(&ptr_type.const_null(), entry), (&live, body)]);
+                self.builder
+                    .build_return(Some(&result.as_basic_value()))
+                    .unwrap();
+            }
+            _ => {
+                let weak_ptr = self.weak_count_ptr(data_ptr);
+                let weak_count = self
+                    .builder
+                    .build_load(i32_type, weak_ptr, "weak_count")
+                    .unwrap()
+                    .into_int_value();
+                let delta = if fn_name == "__weak_retain" { 1 } else { -1 };
+                let new_count = self
+                    .builder
+                    .build_int_add(
+                        weak_count,
+                        i32_type.const_int(delta as u64, true),
+                        "new_count",
+                    )
+                    .unwrap();
+                self.builder.build_store(weak_ptr, new_count).unwrap();
+                if fn_name == "__weak_release" {
+                    let free_block = self.context.append_basic_block(function, "free");
+                    let last_weak = self
+                        .builder
+                        .build_int_compare(
+                            inkwell::IntPredicate::EQ,
+                            new_count,
+                            i32_type.const_zero(),
+                            "last_weak",
+                        )
+                        .unwrap();
+                    let can_free = self
+                        .builder
+                        .build_and(released, last_weak, "can_free")
+                        .unwrap();
+                    self.builder
+                        .build_conditional_branch(can_free, free_block, exit)
+                        .unwrap();
+                    self.builder.position_at_end(free_block);
+                    self.builder
+                        .build_call(self.get_or_declare_free(), &[rc_header.into()], "")
+                        .unwrap();
+                }
+                self.builder.build_unconditional_branch(exit).unwrap();
+                self.builder.position_at_end(exit);
+                self.builder.build_return(None).unwrap();
+            }
+        }
+        if let Some(block) = saved_block {
+            self.builder.position_at_end(block);
+        }
+        function
+    }
+
+    /// `User { name: n, age: 3 }`: allocates the struct with RC = 1 and stores its fields.
+    pub fn generate_struct_init(
+        &mut self,
+        name: &str,
+        struct_name: &str,
+        fields: &[(String, String)],
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let struct_type = self.struct_llvm_type(struct_name);
+        let data_ptr = self.alloc_rc_data(struct_type);
+
+        for (field, value) in fields {
+            let Some((index, ty)) = self.struct_field(struct_name, field) else {
+                continue;
+            };
+            let val = self.owned_field_value(&ty, value);
+            if self.is_weak_field(struct_name, field) {
+                self.retain_weak_value(val);
+            }
+            let field_ptr = self
+                .builder
+                .build_struct_gep(
+                    struct_type,
+                    data_ptr,
+                    index,
+                    &format!("{}_field_ptr", field),
+                )
+                .unwrap();
+            self.builder.build_store(field_ptr, val).unwrap();
+        }
+
+        if let Some(sym) = self.symbols.get(name) {
+            self.builder.build_store(sym.ptr, data_ptr).unwrap();
+        }
+        self.temp_values.insert(name.to_string(), data_ptr.into());
+        self.heap_structs
+            .insert(name.to_string(), struct_name.to_string());
+        Some(data_ptr.into())
+    }
+
+    /// `u.name`: loads the field. The result borrows the struct's reference.
+    /// A `weak` field reads as null once the struct it points to is released.
+    pub fn generate_struct_get(
+        &mut self,
+        name: &str,
+        instance: &str,
+        struct_name: &str,
+        field: &str,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let (field_ptr, ty) = self.struct_field_ptr(instance, struct_name, field)?;
+        let mut val = self
+            .builder
+            .build_load(self.struct_field_type(&ty), field_ptr, field)
+            .unwrap();
+        if self.is_weak_field(struct_name, field) {
+            let get_fn = self.get_or_create_weak_fn("__weak_get");
+            val = self
+                .builder
+                .build_call(get_fn, &[val.into()], "weak_target")
+                .unwrap()
+                .try_as_basic_value()
+                .left()
+                .unwrap();
+        }
+        if let Some(sym) = self.symbols.get(name) {
+            self.builder.build_store(sym.ptr, val).unwrap();
+        }
+        self.temp_values.insert(name.to_string(), val);
+        Some(val)
+    }
+
+    /// `u.name = value;`: stores the new value and drops the old one.
+    pub fn generate_struct_set(
+        &mut self,
+        instance: &str,
+        struct_name: &str,
+        field: &str,
+        value: &str,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let (field_ptr, ty) = self.struct_field_ptr(instance, struct_name, field)?;
+        let old = self
+            .builder
+            .build_load(self.struct_field_type(&ty), field_ptr, "old_field")
+            .unwrap();
+        let val = self.owned_field_value(&ty, value);
+        self.builder.build_store(field_ptr, val).unwrap();
+        if self.is_weak_field(struct_name, field) {
+            self.retain_weak_value(val);
+            self.release_weak_value(old);
+        } else {
+            self.release_field_value(&ty, old);
+        }
+        None
+    }
+
+    /// Ownership of struct values on `name = value`: the variable takes over a
+    /// temporary's reference or adds one when copying another variable, and
+    /// drops the reference to the struct it held before.
+    pub fn assign_struct(&mut self, name: &str, value: &str) {
+        let old = self.heap_structs.remove(name);
+        if let Some(struct_name) = self.heap_structs.get(value).cloned() {
+            if value.starts_with('%') {
+                self.heap_structs.remove(value);
+            } else {
+                self.emit_incref(value);
+            }
+            self.heap_structs.insert(name.to_string(), struct_name);
+        }
+        if let Some(old_struct) = old {
+            self.release_struct_var(name, &old_struct);
+        }
+    }
+}
@@ -1,28 +1,36 @@
 // Hybrid linking: Embedded LLD for Windows, Clang for Unix
 
-use crate::analyzer::types::SemanticError;
-use crate::analyzer::SemanticAnalyzer;
+use crate::analyzer::files::DiskFiles;
+use crate::analyzer::warnings::LintLevels;
 use crate::codegen::core::CodeGen;
-use crate::diagnostics::{print_grouped, DiagnosticRecord};
-use crate::lexar::lexer::lex;
-use crate::mir::builder::MirBuilder;
-use crate::parser::{ast::AstNode, ParseError, Parser};
+use crate::frontend::{front_end, FrontEndOptions, Lowered};
+use crate::mir::MirProgram;
+use crate::output::bold_yellow;
+use crate::project::{resolve_dependencies, Manifest, MANIFEST_FILE};
+use crate::testing;
+use inkwell::passes::PassBuilderOptions;
 use inkwell::targets::{
-    CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine,
+    CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetData, TargetMachine,
+    TargetTriple,
 };
 use inkwell::OptimizationLevel;
-use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::rc::Rc;
+
+pub use crate::frontend::{CompileResult, Emit};
 
 // Embed linker for Windows only
 #[cfg(target_os = "windows")]
 const EMBEDDED_LINKER: &[u8] = include_bytes!("../linkers/lld-link.exe");
 
+// The C library of wasm32-unknown-unknown modules, for their loader
+const WASM_GLUE: &str = include_str!("wasm_glue.js");
+
 #[cfg(target_os = "windows")]
 fn extract_embedded_linker() -> Result<PathBuf, String> {
     let temp_dir = env::temp_dir();
@@ -46,6 +54,204 @@ fn extract_embedded_linker() -> Result<PathBuf, String> {
     Ok(linker_path)
 }
 
+/// Output kept in memory instead of written to a file, for programs that
+/// embed the compiler (see `capi`). The compile stops once it is produced.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Artifact {
+    Mir,    // The optimized MIR as text
+    LlvmIr, // The optimized LLVM module as text
+    Object, // An object file for the target
+}
+
+impl Artifact {
+    /// The artifact named `mir`, `llvm-ir` or `object`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "mir" => Some(Artifact::Mir),
+            "llvm-ir" => Some(Artifact::LlvmIr),
+            "object" => Some(Artifact::Object),
+            _ => None,
+        }
+    }
+}
+
+/// What lowers the MIR to machine code, chosen with `--backend`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Backend {
+    #[default]
+    Llvm, // Optimizing, for every target
+    Cranelift, // Unoptimized and much faster, for the host; a subset of the language so far
+}
+
+impl Backend {
+    /// The backend named `llvm` or `cranelift`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "llvm" => Some(Backend::Llvm),
+            "cranelift" => Some(Backend::Cranelift),
+            _ => None,
+        }
+    }
+}
+
+/// A library to link, named with `-l [KIND=]NAME` as in `-l static=foo`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkLib {
+    pub name: String,
+    pub static_lib: bool, // `static=`: the archive (`libfoo.a`) even where a shared library is found
+}
+
+impl LinkLib {
+    /// `foo` or `dylib=foo` for whichever the linker finds first, usually the
+    /// shared library, and `static=foo` for the archive.
+    pub fn from_arg(arg: &str) -> Result<Self, String> {
+        let (static_lib, name) = match arg.split_once('=') {
+            Some(("static", name)) => (true, name),
+            Some(("dylib", name)) => (false, name),
+            Some((kind, _)) => {
+                return Err(format!(
+                    "Unknown library kind '{}' in -l {}, expected 'static' or 'dylib'",
+                    kind, arg
+                ))
+            }
+            None => (false, arg),
+        };
+        if name.is_empty() {
+            return Err(format!("Missing library name in -l {}", arg));
+        }
+        Ok(LinkLib {
+            name: name.to_string(),
+            static_lib,
+        })
+    }
+}
+
+/// What the linker gets besides the object file, so programs calling
+/// `extern "C"` functions can link the libraries that define them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LinkOptions {
+    pub libs: Vec<LinkLib>,         // `-l`, in order
+    pub search_paths: Vec<PathBuf>, // `-L`, searched for them first
+    pub args: Vec<String>,          // `--link-arg`, passed on as they are
+    pub static_link: bool,          // `--static`: a static executable, C library included
+}
+
+impl LinkOptions {
+    /// Arguments for a Unix C compiler driver (cc, clang, gcc), after the
+    /// object file so the libraries resolve its symbols. Static libraries
+    /// switch the linker to archives around them, which Apple's linker can't.
+    pub fn driver_args(&self, apple: bool) -> Result<Vec<String>, String> {
+        let mut args: Vec<String> = self
+            .search_paths
+            .iter()
+            .map(|path| format!("-L{}", path.display()))
+            .collect();
+        for lib in &self.libs {
+            if lib.static_lib && !self.static_link {
+                if apple {
+                    return Err(format!(
+                        "The macOS linker can't pick static libraries: pass the archive itself with --link-arg path/to/lib{}.a",
+                        lib.name
+                    ));
+                }
+                args.push("-Wl,-Bstatic".to_string());
+                args.push(format!("-l{}", lib.name));
+                args.push("-Wl,-Bdynamic".to_string());
+            } else {
+                args.push(format!("-l{}", lib.name));
+            }
+        }
+        if self.static_link {
+            args.push("-static".to_string());
+        }
+        args.extend(self.args.iter().cloned());
+        Ok(args)
+    }
+
+    /// Arguments for lld-link. Static and import libraries are both `.lib`
+    /// files, and the C runtime is linked statically already.
+    #[cfg(target_os = "windows")]
+    fn lld_link_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = self
+            .search_paths
+            .iter()
+            .map(|path| format!("/LIBPATH:{}", path.display()))
+            .collect();
+        args.extend(self.libs.iter().map(|lib| format!("{}.lib", lib.name)));
+        args.extend(self.args.iter().cloned());
+        args
+    }
+}
+
+/// How much LLVM optimizes the program, chosen with `-O`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OptLevel {
+    O0, // No optimizations, for debugging
+    O1,
+    O2,
+    O3,
+    Os, // O2 without the passes that grow the code
+}
+
+impl OptLevel {
+    /// The level named `0`, `1`, `2`, `3` or `s`, as in `-O2`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "0" => Some(OptLevel::O0),
+            "1" => Some(OptLevel::O1),
+            "2" => Some(OptLevel::O2),
+            "3" => Some(OptLevel::O3),
+            "s" => Some(OptLevel::Os),
+            _ => None,
+        }
+    }
+
+    /// The module pipeline of LLVM's pass builder for this level.
+    fn pipeline(self) -> &'static str {
+        match self {
+            OptLevel::O0 => "default<O0>",
+            OptLevel::O1 => "default<O1>",
+            OptLevel::O2 => "default<O2>",
+            OptLevel::O3 => "default<O3>",
+            OptLevel::Os => "default<Os>",
+        }
+    }
+
+    /// The pipeline for `--lto`: the ThinLTO pre-link half of this level, with
+    /// the rest left to the linker.
+    fn pre_link_pipeline(self) -> &'static str {
+        match self {
+            OptLevel::O0 => "thinlto-pre-link<O0>",
+            OptLevel::O1 => "thinlto-pre-link<O1>",
+            OptLevel::O2 => "thinlto-pre-link<O2>",
+            OptLevel::O3 => "thinlto-pre-link<O3>",
+            OptLevel::Os => "thinlto-pre-link<Os>",
+        }
+    }
+
+    /// The flag the linker driver takes for this level, which it passes on
+    /// to the LTO it runs.
+    fn driver_flag(self) -> &'static str {
+        match self {
+            OptLevel::O0 => "-O0",
+            OptLevel::O1 => "-O1",
+            OptLevel::O2 => "-O2",
+            OptLevel::O3 => "-O3",
+            OptLevel::Os => "-Os",
+        }
+    }
+
+    /// How hard instruction selection and register allocation work.
+    fn codegen_level(self) -> OptimizationLevel {
+        match self {
+            OptLevel::O0 => OptimizationLevel::None,
+            OptLevel::O1 => OptimizationLevel::Less,
+            OptLevel::O2 | OptLevel::Os => OptimizationLevel::Default,
+            OptLevel::O3 => OptimizationLevel::Aggressive,
+        }
+    }
+}
+
 pub struct CompileOptions {
     pub input_path: PathBuf,
     pub output_name: String,
@@ -53,8 +259,49 @@ pub struct CompileOptions {
     pub print_ast: bool,
     pub print_mir: bool,
     pub keep_ll: bool,
+    pub keep_mir: bool,
     pub keep_obj: bool,
     pub check_only: bool,
+    pub interpret: bool,  // Run the MIR with the interpreter instead of building
+    pub backend: Backend, // What builds the program: LLVM, or Cranelift for fast unoptimized builds
+    pub release: bool,    // Leave out `assert` checks
+    pub debug: bool,      // Emit DWARF debug info
+    pub opt_level: Option<OptLevel>, // `-O`; None for the manifest profile's, or else O2 (O0 with `debug`)
+    pub lto: bool, // Write bitcode and leave the last optimizations and code generation to the linker
+    pub target: Option<String>, // Target triple to build for instead of the host
+    pub overflow_checks: bool, // Panic on Int overflow instead of wrapping
+    pub atomic_rc: bool, // Change reference counts atomically
+    pub parallel_codegen: bool, // Generate the functions with LLVM on several threads (see codegen::parallel)
+    pub lint_levels: LintLevels, // Which analyzer lints are allowed, warn or are errors
+    pub emit: Vec<Emit>,
+    pub link: LinkOptions,          // Libraries and arguments for the linker
+    pub artifact: Option<Artifact>, // Keep this in `CompileResult::artifact` instead of linking
+    pub test: bool, // Build the `#[test]` functions and run them into `CompileResult::tests`
+    pub dependencies: Vec<(String, PathBuf)>, // Each dependency's name and root module file; compile_project fills it from doo.toml
+}
+
+impl CompileOptions {
+    /// The options the front end uses.
+    pub fn front_end(&self) -> FrontEndOptions {
+        FrontEndOptions {
+            check_only: self.check_only,
+            release: self.release,
+            lint_levels: self.lint_levels.clone(),
+            emit: self.emit.clone(),
+            test: self.test,
+            dependencies: self.dependencies.clone(),
+        }
+    }
+
+    /// The optimization level: `opt_level`, or else O2, or O0 for debug
+    /// builds so they step line by line.
+    pub fn opt_level(&self) -> OptLevel {
+        match self.opt_level {
+            Some(level) => level,
+            None if self.debug => OptLevel::O0,
+            None => OptLevel::O2,
+        }
+    }
 }
 
 impl Default for CompileOptions {
@@ -66,19 +313,55 @@ impl Default for CompileOptions {
             print_ast: false,
             print_mir: false,
             keep_ll: false,
+            keep_mir: false,
             keep_obj: false,
             check_only: false,
+            interpret: false,
+            backend: Backend::Llvm,
+            release: false,
+            debug: false,
+            opt_level: None,
+            lto: false,
+            target: None,
+            overflow_checks: false,
+            atomic_rc: false,
+            parallel_codegen: false,
+            lint_levels: LintLevels::default(),
+            emit: Vec::new(),
+            link: LinkOptions::default(),
+            artifact: None,
+            test: false,
+            dependencies: Vec::new(),
         }
     }
 }
 
-pub struct CompileResult {
-    pub success: bool,
-    pub error_count: usize,
-    pub exe_path: Option<PathBuf>,
-}
+/// Compiles the project at `opts.input_path`: a `.doo` file, a `.mir` file,
+/// or a directory. A directory with a `doo.toml` builds the entry point it
+/// names with the profile `release` picks, the options given winning over it
+/// (see `project::Profile`), and with the dependencies it declares, git ones
+/// fetched first; otherwise it builds its `main.doo` or `src/main.doo`.
+pub fn compile_project(mut opts: CompileOptions) -> Result<CompileResult, String> {
+    let manifest = if opts.input_path.is_dir() {
+        Manifest::load(&opts.input_path)?
+    } else {
+        None
+    };
+    if let Some(manifest) = &manifest {
+        let profile = manifest.profile(opts.release);
+        // `output` is the name when none is given
+        if opts.output_name == "output" {
+            opts.output_name = manifest.output_name().to_string();
+        }
+        if opts.opt_level.is_none() {
+            opts.opt_level = profile.opt_level.as_deref().and_then(OptLevel::from_name);
+        }
+        opts.debug |= profile.debug;
+        opts.overflow_checks |= profile.overflow_checks;
+        opts.lto |= profile.lto;
+        opts.dependencies = resolve_dependencies(&opts.input_path, manifest)?;
+    }
 
-pub fn compile_project(opts: CompileOptions) -> Result<CompileResult, String> {
     let output_name = env::var("DOO_OUTPUT_NAME").unwrap_or(opts.output_name);
     let check_only = env::var("DOO_CHECK_ONLY").is_ok() || opts.check_only;
 
@@ -88,8 +371,19 @@ pub fn compile_project(opts: CompileOptions) -> Result<CompileResult, String> {
         ..opts
     };
 
+    let entry = manifest.and_then(|manifest| manifest.package.entry);
     let input_path = if opts.input_path.is_file() {
         opts.input_path.clone()
+    } else if let Some(entry) = entry {
+        let entry = opts.input_path.join(entry);
+        if !entry.is_file() {
+            return Err(format!(
+                "Error: entry point {} named in {} not found",
+                entry.display(),
+                MANIFEST_FILE
+            ));
+        }
+        entry
     } else {
         // Try main.doo in the specified directory
         let main_file = opts.input_path.join("main.doo");
@@ -112,187 +406,222 @@ pub fn compile_project(opts: CompileOptions) -> Result<CompileResult, String> {
 
     let input = fs::read_to_string(&input_path)
         .map_err(|e| format!("Failed to read {}: {}", input_path.display(), e))?;
+    if input_path.extension().is_some_and(|ext| ext == "mir") {
+        return compile_mir_file(&input_path, &input, &opts);
+    }
+    compile_source(&input_path, &input, opts)
+}
 
-    let project_root = input_path
-        .parent()
-        .map(|p| p.to_path_buf())
-        .unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
-
-    let tokens = lex(&input);
-    let mut parser = Parser::new(&tokens);
-    let mut analyzer = SemanticAnalyzer::new(Some(project_root.clone()));
-
-    let mut diagnostics: Vec<DiagnosticRecord> = Vec::new();
-    let mut error_count = 0;
-    let mut sources = HashMap::new();
-
-    let mut statements = Vec::new();
-    while parser.current < parser.tokens.len() {
-        match parser.parse_statement() {
-            Ok(stmt) => statements.push(stmt),
-            Err(e) => {
-                let (line, col, msg) = match &e {
-                    ParseError::UnexpectedTokenAt { msg, line, col } => {
-                        (Some(*line), Some(*col), msg.clone())
-                    }
-                    _ => (None, None, e.to_string()),
-                };
-                diagnostics.push(DiagnosticRecord {
-                    filename: input_path.display().to_string(),
-                    message: msg,
-                    line,
-                    col,
-                    is_parse: true,
-                });
-                skip_to_next_statement(&mut parser);
-                error_count += 1;
-            }
+/// Compiles the program text `input` without reading it from disk, so an
+/// editor or an embedding program can compile text it holds. `input_path`
+/// names it in diagnostics, and the modules it imports and the files it
+/// includes are found relative to its directory.
+pub fn compile_source(
+    input_path: &Path,
+    input: &str,
+    opts: CompileOptions,
+) -> Result<CompileResult, String> {
+    let (result, lowered) = front_end(input_path, input, &opts.front_end(), Rc::new(DiskFiles));
+    let Some(Lowered {
+        program,
+        imported,
+        tests,
+    }) = lowered
+    else {
+        return Ok(result);
+    };
+
+    // A test build runs the tests instead of main, and is not built without any
+    if opts.test {
+        if tests.is_empty() {
+            return Ok(result);
         }
+    } else if !is_executable(&program) && !is_library(&program) {
+        // Check that main() function exists before code generation; a library
+        // exporting functions to C has none
+        return Err("Error: main() function not found. Every program must have a main() function as the entry point.".to_string());
     }
 
-    let mut analyzer = SemanticAnalyzer::new(Some(project_root.clone()));
+    generate(&program, &opts, input_path, imported, &tests, result)
+}
 
-    if let Err(e) = analyzer.analyze_program(&mut statements) {
-        match &e {
-            SemanticError::ParseErrorInModule { file, error } => {
-                let re = Regex::new(r"at (\d+):(\d+): (.+)").expect("Regex pattern is valid");
-                let (line, col, msg) = if let Some(caps) = re.captures(error) {
-                    (
-                        caps.get(1).and_then(|m| m.as_str().parse().ok()),
-                        caps.get(2).and_then(|m| m.as_str().parse().ok()),
-                        caps.get(3)
-                            .map(|m| m.as_str().to_string())
-                            .unwrap_or_else(|| error.clone()),
-                    )
-                } else {
-                    (None, None, error.clone())
-                };
-                diagnostics.push(DiagnosticRecord {
-                    filename: file.clone(),
-                    message: msg,
-                    line,
-                    col,
-                    is_parse: true,
-                });
-                if !sources.contains_key(file) {
-                    if let Ok(src) = std::fs::read_to_string(file) {
-                        sources.insert(file.clone(), src);
-                    }
-                }
-                error_count += 1;
-            }
-            _ => {
-                diagnostics.push(DiagnosticRecord {
-                    filename: input_path.display().to_string(),
-                    message: e.to_string(),
-                    line: None,
-                    col: None,
-                    is_parse: false,
-                });
-                error_count += 1;
-            }
-        }
+/// Builds a program from MIR text (see `MirProgram::parse`), skipping the
+/// front end. The MIR is verified first since it may have been edited by hand.
+fn compile_mir_file(
+    input_path: &Path,
+    input: &str,
+    opts: &CompileOptions,
+) -> Result<CompileResult, String> {
+    let program =
+        MirProgram::parse(input).map_err(|e| format!("Error: {}: {}", input_path.display(), e))?;
+    if let Err(errors) = program.verify() {
+        let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        return Err(format!(
+            "Error: invalid MIR in {}:\n{}",
+            input_path.display(),
+            errors.join("\n")
+        ));
+    }
+    if !is_executable(&program) && !is_library(&program) {
+        return Err("Error: main() function not found. Every program must have a main() function as the entry point.".to_string());
     }
+    let result = CompileResult {
+        success: true,
+        error_count: 0,
+        warning_count: 0,
+        diagnostics: Vec::new(),
+        sources: HashMap::new(),
+        exe_path: None,
+        ll_path: None,
+        mir_path: None,
+        obj_path: None,
+        asm_path: None,
+        header_path: None,
+        js_path: None,
+        exit_code: None,
+        ast_json: None,
+        artifact: None,
+        tests: Vec::new(),
+    };
+    if opts.check_only {
+        return Ok(result);
+    }
+    generate(&program, opts, input_path, HashSet::new(), &[], result)
+}
 
-    for error in &analyzer.collected_errors {
-        match error {
-            SemanticError::ParseErrorInModule {
-                file,
-                error: err_msg,
-            } => {
-                let re = Regex::new(r"at (\d+):(\d+): (.+)").expect("Regex pattern is valid");
-                let (line, col, msg) = if let Some(caps) = re.captures(err_msg) {
-                    (
-                        caps.get(1).and_then(|m| m.as_str().parse().ok()),
-                        caps.get(2).and_then(|m| m.as_str().parse().ok()),
-                        caps.get(3)
-                            .map(|m| m.as_str().to_string())
-                            .unwrap_or_else(|| err_msg.clone()),
-                    )
-                } else {
-                    (None, None, err_msg.clone())
-                };
-                diagnostics.push(DiagnosticRecord {
-                    filename: file.clone(),
-                    message: msg,
-                    line,
-                    col,
-                    is_parse: true,
-                });
-                if !sources.contains_key(file) {
-                    if let Ok(src) = std::fs::read_to_string(file) {
-                        sources.insert(file.clone(), src);
-                    }
-                }
-                error_count += 1;
-            }
-            _ => {
-                diagnostics.push(DiagnosticRecord {
-                    filename: input_path.display().to_string(),
-                    message: error.to_string(),
-                    line: None,
-                    col: None,
-                    is_parse: false,
-                });
-                error_count += 1;
-            }
-        }
+/// Whether `program` has a `main` to link an executable around.
+fn is_executable(program: &MirProgram) -> bool {
+    program.functions.iter().any(|f| f.name == "main")
+}
+
+/// Whether `program` is a library for C to link: it has no `main` but
+/// exports functions, and builds to an object file instead of an executable.
+fn is_library(program: &MirProgram) -> bool {
+    !is_executable(program) && program.functions.iter().any(|f| f.export)
+}
+
+/// Lowers finished MIR to LLVM and links the executable, writing the
+/// requested intermediate files along the way. With `interpret`, runs the
+/// MIR with the interpreter instead. `source` is the file debug info and
+/// panic messages describe, which the functions in `imported` don't come from.
+/// With `test`, the executable runs the functions named in `tests`, and
+/// each of them is run in it.
+fn generate(
+    program: &MirProgram,
+    opts: &CompileOptions,
+    source: &Path,
+    imported: HashSet<String>,
+    tests: &[String],
+    mut result: CompileResult,
+) -> Result<CompileResult, String> {
+    if opts.print_mir {
+        eprint!("{}", program);
     }
 
-    if !diagnostics.is_empty() {
-        sources.insert(input_path.display().to_string(), input.clone());
-        for diag in &diagnostics {
-            if !sources.contains_key(&diag.filename) {
-                if let Ok(src) = std::fs::read_to_string(&diag.filename) {
-                    sources.insert(diag.filename.clone(), src);
-                }
-            }
-        }
-        print_grouped(&diagnostics, &sources);
+    if opts.keep_mir {
+        let mir_file = format!("{}.mir", opts.output_name);
+        fs::write(&mir_file, program.to_string())
+            .map_err(|e| format!("Failed to write MIR: {}", e))?;
+        result.mir_path = Some(PathBuf::from(mir_file));
     }
 
-    if error_count > 0 {
-        if opts.dev_mode {}
-        return Ok(CompileResult {
-            success: false,
-            error_count,
-            exe_path: None,
-        });
+    if opts.emit.contains(&Emit::Header) {
+        let header_file = PathBuf::from(format!("{}.h", opts.output_name));
+        let name = header_file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        fs::write(&header_file, program.c_header(&name))
+            .map_err(|e| format!("Failed to write C header: {}", e))?;
+        result.header_path = Some(header_file);
     }
 
-    if opts.check_only {
-        return Ok(CompileResult {
-            success: error_count == 0,
-            error_count,
-            exe_path: None,
-        });
-    }
-
-    let mut all_nodes = analyzer.imported_functions.clone();
-    all_nodes.extend(statements);
-
-    if opts.print_ast {}
-
-    let mut mir_builder = MirBuilder::new();
-    mir_builder.set_is_main_entry(true); // Mark this as the main entry point
-    mir_builder.build_program(&all_nodes);
-    mir_builder.finalize();
-
-    // Check that main() function exists before code generation
-    let has_main = mir_builder
-        .program
-        .functions
-        .iter()
-        .any(|f| f.name == "main");
-    if !has_main {
-        return Err("Error: main() function not found. Every program must have a main() function as the entry point.".to_string());
+    if opts.artifact == Some(Artifact::Mir) {
+        result.artifact = Some(program.to_string().into_bytes());
+        return Ok(result);
     }
 
-    if opts.print_mir || opts.dev_mode {}
+    if opts.interpret && opts.test {
+        result.tests = testing::run_interpreted(program, tests, opts.overflow_checks);
+        return Ok(result);
+    }
+    if opts.interpret {
+        let code = program
+            .interpret(opts.overflow_checks)
+            .map_err(|e| format!("Error: {}", e))?;
+        result.exit_code = Some(code);
+        return Ok(result);
+    }
+
+    if opts.backend == Backend::Cranelift {
+        return generate_cranelift(program, opts, source, imported, result);
+    }
+
+    let triple = opts.target.as_deref().map(target_triple);
+    let target_machine = create_target_machine(triple.as_deref(), opts)?;
+
+    // The target and options every module is generated with. Target
+    // machines stay on this thread, so each module gets them by name.
+    let target_triple = target_machine
+        .get_triple()
+        .as_str()
+        .to_string_lossy()
+        .to_string();
+    let target_data = target_machine.get_target_data();
+    let data_layout = target_data
+        .get_data_layout()
+        .as_str()
+        .to_string_lossy()
+        .to_string();
+    let pointer_bits = target_data.get_pointer_byte_size(None) * 8;
+    let configure = |codegen: &mut CodeGen| {
+        codegen.overflow_checks = opts.overflow_checks;
+        codegen.atomic_rc = opts.atomic_rc;
+        codegen
+            .module
+            .set_triple(&TargetTriple::create(&target_triple));
+        codegen
+            .module
+            .set_data_layout(&TargetData::create(&data_layout).get_data_layout());
+        codegen.pointer_bits = pointer_bits;
+        codegen.source_file = Some(source.display().to_string());
+        codegen.imported_functions = imported.clone();
+        if opts.test {
+            codegen.tests = tests.to_vec();
+        }
+        if opts.debug {
+            codegen.enable_debug_info(source, imported.clone());
+        }
+    };
 
     let context = inkwell::context::Context::create();
     let mut codegen = CodeGen::new("main_module", &context);
-    codegen.generate_program(&mir_builder.program);
+    // With --parallel-codegen, functions are generated in parallel, each into
+    // a module of its own, unless there is only one or only one thread to
+    // generate them on. Debug info stays one module's, with one compile unit.
+    if opts.parallel_codegen
+        && !opts.debug
+        && program.functions.len() > 1
+        && rayon::current_num_threads() > 1
+    {
+        codegen.generate_parallel(program, &configure)?;
+    } else {
+        configure(&mut codegen);
+        codegen.generate_program(program);
+    }
+
+    codegen
+        .module
+        .run_passes(
+            if opts.lto {
+                opts.opt_level().pre_link_pipeline()
+            } else {
+                opts.opt_level().pipeline()
+            },
+            &target_machine,
+            PassBuilderOptions::create(),
+        )
+        .map_err(|e| format!("Failed to optimize: {}", e))?;
 
     if opts.dev_mode {
         codegen.dump();
@@ -303,83 +632,440 @@ pub fn compile_project(opts: CompileOptions) -> Result<CompileResult, String> {
         let ll_file = format!("{}.ll", opts.output_name);
         fs::write(&ll_file, llvm_ir.to_string())
             .map_err(|e| format!("Failed to write LLVM IR: {}", e))?;
+        result.ll_path = Some(PathBuf::from(ll_file));
+    }
+
+    if opts.emit.contains(&Emit::Asm) {
+        let asm_file = PathBuf::from(format!("{}.s", opts.output_name));
+        target_machine
+            .write_to_file(&codegen.module, FileType::Assembly, &asm_file)
+            .map_err(|e| format!("Failed to write assembly: {}", e))?;
+        result.asm_path = Some(asm_file);
+    }
+
+    match opts.artifact {
+        Some(Artifact::LlvmIr) => {
+            result.artifact = Some(codegen.module.print_to_string().to_bytes().to_vec());
+            return Ok(result);
+        }
+        Some(Artifact::Object) => {
+            let object = target_machine
+                .write_to_memory_buffer(&codegen.module, FileType::Object)
+                .map_err(|e| format!("Failed to write object file: {}", e))?;
+            result.artifact = Some(object.as_slice().to_vec());
+            return Ok(result);
+        }
+        _ => {}
+    }
+
+    // A library is left as an object file for a C program to link
+    if is_library(program) {
+        let extension = if opts.lto { "bc" } else { "o" };
+        let obj_file = PathBuf::from(format!("{}.{}", opts.output_name, extension));
+        write_object(&codegen, &target_machine, opts.lto, &obj_file)?;
+        result.obj_path = Some(obj_file);
+        return Ok(result);
     }
 
     let current_dir =
         env::current_dir().map_err(|e| format!("Failed to get current directory: {}", e))?;
 
-    let exe_name = if cfg!(windows) {
+    let triple = triple.as_deref();
+    let wasm = triple.is_some_and(|triple| triple.starts_with("wasm32"));
+    let windows = match triple {
+        Some(triple) => triple.contains("windows"),
+        None => cfg!(windows),
+    };
+    let exe_name = if wasm {
+        format!("{}.wasm", opts.output_name)
+    } else if windows {
         format!("{}.exe", opts.output_name)
     } else {
         opts.output_name.clone()
     };
     let exe_path = current_dir.join(&exe_name);
 
-    compile_to_native(&codegen, &opts, &exe_path)?;
+    result.obj_path = compile_to_native(&codegen, &target_machine, opts, triple, &exe_path)?;
 
-    if !exe_path.exists() {
-        return Ok(CompileResult {
-            success: false,
-            error_count: 0,
-            exe_path: None,
-        });
-    } else {
+    if wasm && exe_path.exists() {
+        let js_file = PathBuf::from(format!("{}.js", opts.output_name));
+        fs::write(&js_file, wasm_loader(&exe_name, triple.unwrap_or_default()))
+            .map_err(|e| format!("Failed to write JavaScript loader: {}", e))?;
+        result.js_path = Some(js_file);
     }
 
-    Ok(CompileResult {
-        success: true,
-        error_count: 0,
-        exe_path: Some(exe_path),
-    })
+    result.success = exe_path.exists();
+    if opts.test && result.success {
+        result.tests = testing::run_harness(&exe_path, tests);
+    }
+    result.exe_path = result.success.then_some(exe_path);
+    Ok(result)
 }
 
-fn compile_to_native(
-    codegen: &CodeGen,
+/// `generate` with the Cranelift backend: the object file it writes is
+/// linked for the host as LLVM's is. What only LLVM does (other targets,
+/// optimizations, debug info, LLVM IR) is an error rather than ignored.
+#[cfg(feature = "cranelift")]
+fn generate_cranelift(
+    program: &MirProgram,
     opts: &CompileOptions,
-    exe_path: &Path,
-) -> Result<(), String> {
-    Target::initialize_native(&InitializationConfig::default())
-        .map_err(|e| format!("Failed to initialize target: {}", e))?;
+    source: &Path,
+    imported: HashSet<String>,
+    mut result: CompileResult,
+) -> Result<CompileResult, String> {
+    let llvm_only = [
+        (opts.target.is_some(), "--target"),
+        (opts.lto, "--lto"),
+        (opts.debug, "--debug"),
+        (opts.keep_ll, "--keep-ll"),
+        (opts.emit.contains(&Emit::Asm), "--emit asm"),
+        (opts.test, "doo test"),
+        (opts.artifact == Some(Artifact::LlvmIr), "LLVM IR output"),
+    ];
+    if let Some((_, what)) = llvm_only.iter().find(|(used, _)| *used) {
+        return Err(format!(
+            "Error: {} needs the LLVM backend, not --backend cranelift",
+            what
+        ));
+    }
+
+    let mut codegen = crate::cranelift::CraneliftGen::new()?;
+    codegen.overflow_checks = opts.overflow_checks;
+    codegen.source_file = Some(source.display().to_string());
+    codegen.imported_functions = imported;
+    codegen.generate_program(program)?;
+    let object = codegen.finish()?;
+
+    if opts.artifact == Some(Artifact::Object) {
+        result.artifact = Some(object);
+        return Ok(result);
+    }
+    // A library is left as an object file for a C program to link
+    if is_library(program) {
+        let obj_file = PathBuf::from(format!("{}.o", opts.output_name));
+        fs::write(&obj_file, object).map_err(|e| format!("Failed to write object file: {}", e))?;
+        result.obj_path = Some(obj_file);
+        return Ok(result);
+    }
+
+    let current_dir =
+        env::current_dir().map_err(|e| format!("Failed to get current directory: {}", e))?;
+    let exe_name = if cfg!(windows) {
+        format!("{}.exe", opts.output_name)
+    } else {
+        opts.output_name.clone()
+    };
+    let exe_path = current_dir.join(&exe_name);
+    let obj_file = object_file(opts, "o");
+    fs::write(&obj_file, object).map_err(|e| format!("Failed to write object file: {}", e))?;
+    let obj_file_str = obj_file
+        .to_str()
+        .ok_or_else(|| "Could not convert object file path to string".to_string())?;
+    let exe_path_str = exe_path
+        .to_str()
+        .ok_or_else(|| "Could not convert executable path to string".to_string())?;
+    let linked = link_object_file(obj_file_str, exe_path_str, opts.dev_mode, None, &opts.link);
+    if opts.keep_obj {
+        result.obj_path = Some(obj_file);
+    } else if fs::remove_file(&obj_file).is_err() && opts.dev_mode {
+        eprintln!(
+            "{} failed to remove object file {}",
+            bold_yellow("Warning:"),
+            obj_file.display()
+        );
+    }
+    linked?;
+
+    result.success = exe_path.exists();
+    result.exe_path = result.success.then_some(exe_path);
+    Ok(result)
+}
+
+#[cfg(not(feature = "cranelift"))]
+fn generate_cranelift(
+    _program: &MirProgram,
+    _opts: &CompileOptions,
+    _source: &Path,
+    _imported: HashSet<String>,
+    _result: CompileResult,
+) -> Result<CompileResult, String> {
+    Err("Error: this doo was built without the Cranelift backend: build it with `--features cranelift`".to_string())
+}
+
+/// The triple `--target` names: `wasm32` is short for
+/// `wasm32-unknown-unknown`.
+fn target_triple(target: &str) -> String {
+    match target {
+        "wasm32" => "wasm32-unknown-unknown".to_string(),
+        _ => target.to_string(),
+    }
+}
+
+/// Whether `triple` has no C library, so the module imports the functions
+/// the runtime calls (printf, malloc, ...) from the JavaScript that loads it.
+fn is_bare_wasm(triple: &str) -> bool {
+    triple.starts_with("wasm32") && !triple.contains("wasi")
+}
+
+/// Node script that runs `<output>.wasm`, next to it. Without WASI, it is
+/// `wasm_glue.js`, which provides the C library the module imports; with it,
+/// Node's WASI implementation provides the standard output `print` writes to.
+fn wasm_loader(wasm_file: &str, triple: &str) -> String {
+    let wasm_name = Path::new(wasm_file)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if is_bare_wasm(triple) {
+        return format!(
+            "// Runs {wasm} with Node: node <this file>\n\
+             const WASM_FILE = \"{wasm}\";\n\
+             \n\
+             {glue}",
+            wasm = wasm_name,
+            glue = WASM_GLUE
+        );
+    }
+    format!(
+        "// Runs {wasm} with Node: node <this file>\n\
+         const {{ readFileSync }} = require(\"node:fs\");\n\
+         const {{ join }} = require(\"node:path\");\n\
+         const {{ WASI }} = require(\"node:wasi\");\n\
+         \n\
+         const wasi = new WASI({{ version: \"preview1\", args: process.argv.slice(1), env: process.env }});\n\
+         const bytes = readFileSync(join(__dirname, \"{wasm}\"));\n\
+         WebAssembly.instantiate(bytes, wasi.getImportObject()).then(({{ instance }}) => {{\n\
+         \x20\x20process.exitCode = wasi.start(instance);\n\
+         }});\n",
+        wasm = wasm_name
+    )
+}
 
-    let triple = TargetMachine::get_default_triple();
-    let cpu = TargetMachine::get_host_cpu_name().to_string();
-    let features = TargetMachine::get_host_cpu_features().to_string();
+/// The machine code is generated for: the host, or `triple` for generic
+/// CPUs of that triple.
+fn create_target_machine(
+    triple: Option<&str>,
+    opts: &CompileOptions,
+) -> Result<TargetMachine, String> {
+    let (triple, cpu, features) = match triple {
+        Some(triple) => {
+            Target::initialize_all(&InitializationConfig::default());
+            (
+                TargetTriple::create(triple),
+                "generic".to_string(),
+                String::new(),
+            )
+        }
+        None => {
+            Target::initialize_native(&InitializationConfig::default())
+                .map_err(|e| format!("Failed to initialize target: {}", e))?;
+            (
+                TargetMachine::get_default_triple(),
+                TargetMachine::get_host_cpu_name().to_string(),
+                TargetMachine::get_host_cpu_features().to_string(),
+            )
+        }
+    };
 
-    let target =
-        Target::from_triple(&triple).map_err(|e| format!("Failed to create target: {}", e))?;
+    let target = Target::from_triple(&triple).map_err(|e| {
+        format!(
+            "Failed to create target {}: {}",
+            triple.as_str().to_string_lossy(),
+            e
+        )
+    })?;
 
-    let target_machine = target
+    target
         .create_target_machine(
             &triple,
             &cpu,
             &features,
-            OptimizationLevel::Aggressive,
+            opts.opt_level().codegen_level(),
             RelocMode::PIC,
             CodeModel::Default,
         )
-        .ok_or("Failed to create target machine")?;
+        .ok_or_else(|| "Failed to create target machine".to_string())
+}
 
-    let obj_file = format!("{}.o", opts.output_name);
-    target_machine
-        .write_to_file(&codegen.module, FileType::Object, Path::new(&obj_file))
-        .map_err(|e| format!("Failed to write object file: {}", e))?;
+fn compile_to_native(
+    codegen: &CodeGen,
+    target_machine: &TargetMachine,
+    opts: &CompileOptions,
+    triple: Option<&str>,
+    exe_path: &Path,
+) -> Result<Option<PathBuf>, String> {
+    // With LTO the object file holds bitcode, which the linker compiles
+    let obj_file = object_file(opts, if opts.lto { "bc" } else { "o" });
+    write_object(codegen, target_machine, opts.lto, &obj_file)?;
 
+    let obj_file_str = obj_file
+        .to_str()
+        .ok_or_else(|| "Could not convert object file path to string".to_string())?;
     let exe_path_str = exe_path
         .to_str()
         .ok_or_else(|| "Could not convert executable path to string".to_string())?;
-    link_object_file(&obj_file, exe_path_str, opts.dev_mode)?;
+    let lto = opts.lto.then_some(opts.opt_level());
+    let linked = match triple {
+        Some(triple) => link_for_target(obj_file_str, exe_path_str, triple, lto, &opts.link),
+        None => link_object_file(obj_file_str, exe_path_str, opts.dev_mode, lto, &opts.link),
+    };
+
+    if opts.keep_obj {
+        return linked.map(|()| Some(obj_file));
+    }
+    if fs::remove_file(&obj_file).is_err() && opts.dev_mode {
+        eprintln!(
+            "{} failed to remove object file {}",
+            bold_yellow("Warning:"),
+            obj_file.display()
+        );
+    }
+    linked.map(|()| None)
+}
+
+/// Where the object file to link goes: `<output>.<extension>` when it is
+/// kept, or else a temporary file that only lives until it is linked.
+fn object_file(opts: &CompileOptions, extension: &str) -> PathBuf {
+    if opts.keep_obj {
+        return PathBuf::from(format!("{}.{}", opts.output_name, extension));
+    }
+    let stem = Path::new(&opts.output_name)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "output".to_string());
+    env::temp_dir().join(format!("doo_{}_{}.{}", stem, std::process::id(), extension))
+}
+
+/// Writes the module to `obj_file`: machine code, or bitcode with `lto`.
+fn write_object(
+    codegen: &CodeGen,
+    target_machine: &TargetMachine,
+    lto: bool,
+    obj_file: &Path,
+) -> Result<(), String> {
+    if lto {
+        if !codegen.module.write_bitcode_to_path(obj_file) {
+            return Err(format!("Failed to write bitcode to {}", obj_file.display()));
+        }
+        Ok(())
+    } else {
+        target_machine
+            .write_to_file(&codegen.module, FileType::Object, obj_file)
+            .map_err(|e| format!("Failed to write object file: {}", e))
+    }
+}
 
-    // Always remove .o file after linking unless keep_obj is true
-    if !opts.keep_obj {
-        if fs::remove_file(&obj_file).is_err() && opts.dev_mode {
-            eprintln!("Warning: failed to remove object file {}", obj_file);
+/// The program that links object files on Unix: `$DOO_LINKER`, `$CC`, or
+/// the first of `cc`, `clang` and `gcc` found. Any C compiler driver will do,
+/// since it only has to link the C library in.
+#[cfg(not(target_os = "windows"))]
+fn find_unix_linker() -> Result<String, String> {
+    for var in ["DOO_LINKER", "CC"] {
+        if let Ok(linker) = env::var(var) {
+            if !linker.is_empty() {
+                return Ok(linker);
+            }
+        }
+    }
+    ["cc", "clang", "gcc"]
+        .into_iter()
+        .find(|linker| Command::new(linker).arg("--version").output().is_ok())
+        .map(str::to_string)
+        .ok_or_else(|| {
+            "No linker found: install a C compiler (cc, clang or gcc), or set DOO_LINKER. For example:\n\
+            - Ubuntu/Debian: sudo apt install gcc\n\
+            - Fedora: sudo dnf install gcc\n\
+            - macOS: xcode-select --install"
+                .to_string()
+        })
+}
+
+/// Links for `--target`: with `$DOO_LINKER` as is, or with `clang --target`,
+/// which looks for the target's C library in its sysroot (`$WASI_SYSROOT`
+/// for wasm32-wasi, from wasi-sdk). musl targets are linked statically, as
+/// their binaries usually are. wasm32-unknown-unknown has no C library: the
+/// module is a library exporting `main`, whose C functions are left to import.
+fn link_for_target(
+    obj_file: &str,
+    output: &str,
+    triple: &str,
+    lto: Option<OptLevel>,
+    link: &LinkOptions,
+) -> Result<(), String> {
+    let mut cmd = match env::var("DOO_LINKER") {
+        Ok(linker) if !linker.is_empty() => Command::new(linker),
+        _ => {
+            let mut clang = Command::new("clang");
+            clang.arg(format!("--target={}", triple));
+            if let Ok(sysroot) = env::var("WASI_SYSROOT") {
+                if triple.starts_with("wasm32-wasi") {
+                    clang.arg(format!("--sysroot={}", sysroot));
+                }
+            }
+            clang
         }
+    };
+    if triple.contains("musl") && !link.static_link {
+        cmd.arg("-static");
+    }
+    if is_bare_wasm(triple) {
+        cmd.args(BARE_WASM_ARGS);
+    }
+    if let Some(level) = lto {
+        cmd.args(lto_args(level));
     }
+    let linker = cmd.get_program().to_string_lossy().to_string();
+    cmd.arg(obj_file)
+        .args(link.driver_args(triple.contains("apple"))?);
+    if !is_bare_wasm(triple) {
+        cmd.args(RUNTIME_LIBS);
+    }
+    match cmd.arg("-o").arg(output).output() {
+        Ok(r) if r.status.success() => Ok(()),
+        Ok(r) => Err(format!(
+            "Linking for {} with {} failed:\n{}",
+            triple,
+            linker,
+            String::from_utf8_lossy(&r.stderr)
+        )),
+        Err(e) => Err(format!(
+            "Linker error ({}): {}. Set DOO_LINKER to a linker for {}",
+            linker, e, triple
+        )),
+    }
+}
 
-    Ok(())
+/// Libraries every program links on Unix, after the user's: libm, for the
+/// `fmod` a Float `%` compiles to.
+const RUNTIME_LIBS: [&str; 1] = ["-lm"];
+
+/// What the linker driver needs to link a wasm32-unknown-unknown module:
+/// no C library or entry point, `main` and what `wasm_glue.js` reads (the
+/// heap's start, and the table `qsort` calls comparators through) exported,
+/// and the C functions imported.
+const BARE_WASM_ARGS: [&str; 6] = [
+    "-nostdlib",
+    "-Wl,--no-entry",
+    "-Wl,--export=main",
+    "-Wl,--export=__heap_base",
+    "-Wl,--export-table",
+    "-Wl,--allow-undefined",
+];
+
+/// What the linker driver needs to link bitcode: ThinLTO, run by lld (GNU ld
+/// can't read LLVM bitcode) at `level`.
+fn lto_args(level: OptLevel) -> [&'static str; 3] {
+    ["-flto=thin", "-fuse-ld=lld", level.driver_flag()]
 }
 
-fn link_object_file(obj_file: &str, output: &str, dev_mode: bool) -> Result<(), String> {
+/// Links for the host. lld-link on Windows reads bitcode as it is; on Unix,
+/// LTO links with clang unless `$DOO_LINKER` names another driver that can.
+fn link_object_file(
+    obj_file: &str,
+    output: &str,
+    dev_mode: bool,
+    lto: Option<OptLevel>,
+    link: &LinkOptions,
+) -> Result<(), String> {
     #[cfg(target_os = "windows")]
     {
         let linker = extract_embedded_linker()?;
@@ -406,6 +1092,7 @@ fn link_object_file(obj_file: &str, output: &str, dev_mode: bool) -> Result<(),
                 .arg("legacy_stdio_definitions.lib")
                 .arg("libcmt.lib");
         }
+        cmd.args(link.lld_link_args());
 
         let result = cmd.output();
         match result {
@@ -421,29 +1108,31 @@ fn link_object_file(obj_file: &str, output: &str, dev_mode: bool) -> Result<(),
 
     #[cfg(not(target_os = "windows"))]
     {
-        // Use clang on Unix - simple and reliable
-        let clang_check = Command::new("clang").arg("--version").output();
-        if clang_check.is_err() {
-            return Err("Clang not found. Install with:\n\
-                - Ubuntu/Debian: sudo apt install clang\n\
-                - Fedora: sudo dnf install clang\n\
-                - macOS: xcode-select --install"
-                .to_string());
-        }
-
-        let result = Command::new("clang")
-            .arg(obj_file)
-            .arg("-o")
-            .arg(output)
-            .output();
+        let mut cmd = match lto {
+            Some(level) => {
+                let mut cmd = match env::var("DOO_LINKER") {
+                    Ok(linker) if !linker.is_empty() => Command::new(linker),
+                    _ => Command::new("clang"),
+                };
+                cmd.args(lto_args(level));
+                cmd
+            }
+            None => Command::new(find_unix_linker()?),
+        };
+        let linker = cmd.get_program().to_string_lossy().to_string();
+        cmd.arg(obj_file)
+            .args(link.driver_args(cfg!(target_os = "macos"))?)
+            .args(RUNTIME_LIBS);
+        let result = cmd.arg("-o").arg(output).output();
 
         match result {
             Ok(r) if r.status.success() => Ok(()),
             Ok(r) => Err(format!(
-                "Linking failed:\n{}",
+                "Linking with {} failed:\n{}",
+                linker,
                 String::from_utf8_lossy(&r.stderr)
             )),
-            Err(e) => Err(format!("Linker error: {}", e)),
+            Err(e) => Err(format!("Linker error ({}): {}", linker, e)),
         }
     }
 }
@@ -511,15 +1200,3 @@ fn find_msvc_lib_path(base: &str) -> Option<String> {
     }
     None
 }
-
-fn skip_to_next_statement(parser: &mut Parser) {
-    while parser.current < parser.tokens.len() {
-        if let Some(tok) = parser.peek() {
-            if matches!(tok.kind, crate::lexar::token::TokenType::Semi) {
-                parser.advance();
-                break;
-            }
-        }
-        parser.advance();
-    }
-}
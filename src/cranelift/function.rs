@@ -0,0 +1,572 @@
+use crate::analyzer::builtins::BUILTIN_FUNCTIONS;
+use crate::cranelift::{unsupported, CraneliftGen, Declared};
+use crate::mir::{MirFunction, MirInstr, MirType};
+use crate::parser::ast::Span;
+use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
+use cranelift_codegen::ir::{types, Block, Function, InstBuilder, TrapCode, Type, Value};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_module::Module;
+use std::collections::HashMap;
+
+impl CraneliftGen {
+    /// Defines `func`, which is declared already. `prelude` runs before its
+    /// first block: for `main`, the global-scope statements.
+    pub(crate) fn define(
+        &mut self,
+        func: &MirFunction,
+        prelude: &[MirInstr],
+    ) -> Result<(), String> {
+        let declared = self.functions[&func.name].clone();
+        let types = self.value_types(func, prelude, &declared);
+        let sig = self
+            .module
+            .declarations()
+            .get_function_decl(declared.id)
+            .signature
+            .clone();
+        // Functions from other files get no panic location: their lines
+        // would point into this one
+        let spans = if self.imported_functions.contains(&func.name) {
+            HashMap::new()
+        } else {
+            func.spans
+                .iter()
+                .map(|(label, index, span)| ((label.as_str(), *index), *span))
+                .collect()
+        };
+
+        let mut ir = Function::with_name_signature(Default::default(), sig);
+        let mut func_ctx = FunctionBuilderContext::new();
+        let mut lowering = FunctionLowering {
+            b: FunctionBuilder::new(&mut ir, &mut func_ctx),
+            gen: self,
+            func,
+            return_type: declared.return_type,
+            types,
+            vars: HashMap::new(),
+            blocks: HashMap::new(),
+            spans,
+            line: 0,
+        };
+        lowering
+            .lower(prelude)
+            .map_err(|e| format!("{} (in {})", e, func.name))?;
+        lowering.b.seal_all_blocks();
+        let config = lowering.gen.module.target_config();
+        lowering.b.finalize(config);
+        self.define_function(declared.id, ir)
+            .map_err(|e| format!("{} (in {})", e, func.name))
+    }
+
+    /// The type of every value `func` defines: its parameters' declared
+    /// types, and the types MIR gives the values its instructions write.
+    fn value_types(
+        &self,
+        func: &MirFunction,
+        prelude: &[MirInstr],
+        declared: &Declared,
+    ) -> HashMap<String, MirType> {
+        let mut types: HashMap<String, MirType> = func
+            .params
+            .iter()
+            .cloned()
+            .zip(declared.param_types.iter().cloned())
+            .collect();
+        let instrs = prelude
+            .iter()
+            .chain(func.blocks.iter().flat_map(|block| &block.instrs));
+        for instr in instrs {
+            if let Some((name, ty)) = defined_type(instr) {
+                types.insert(name.clone(), ty.clone());
+            }
+        }
+        types
+    }
+}
+
+/// The value `instr` writes and its type, if MIR knows it.
+fn defined_type(instr: &MirInstr) -> Option<(&String, &MirType)> {
+    let (name, ty) = match instr {
+        MirInstr::ConstInt { name, ty, .. } => (name, ty),
+        MirInstr::ConstInt64 { name, .. } => (name, &MirType::Int64),
+        MirInstr::ConstFloat { name, .. } => (name, &MirType::Float),
+        MirInstr::ConstBool { name, .. } => (name, &MirType::Bool),
+        MirInstr::ConstString { name, .. } => (name, &MirType::Str),
+        MirInstr::Add(dest, _, _, ty)
+        | MirInstr::Sub(dest, _, _, ty)
+        | MirInstr::Mul(dest, _, _, ty)
+        | MirInstr::Div(dest, _, _, ty)
+        | MirInstr::BinaryOp(_, dest, _, _, ty) => (dest, ty),
+        MirInstr::Assign { name, ty, .. } => (name, ty),
+        MirInstr::Call { dest, ty, .. } => (dest.first()?, ty),
+        _ => return None,
+    };
+    (*ty != MirType::Void).then_some((name, ty))
+}
+
+/// The name of a MIR operation or builtin without its operand type: `add`
+/// of `add:int`.
+fn op_name(op: &str) -> &str {
+    op.split_once(':').map_or(op, |(name, _)| name)
+}
+
+/// Lowers one MIR function into a Cranelift function. Every MIR name is a
+/// Cranelift variable, which the builder turns into SSA values.
+struct FunctionLowering<'a, 'f> {
+    gen: &'a mut CraneliftGen,
+    b: FunctionBuilder<'f>,
+    func: &'a MirFunction,
+    return_type: MirType,
+    types: HashMap<String, MirType>,
+    vars: HashMap<String, Variable>,
+    blocks: HashMap<&'a str, Block>,
+    spans: HashMap<(&'a str, usize), Span>, // (block, instruction) -> statement starting there
+    line: u32,                              // Line of the statement being lowered, 0 if unknown
+}
+
+impl<'a> FunctionLowering<'a, '_> {
+    fn lower(&mut self, prelude: &[MirInstr]) -> Result<(), String> {
+        // Sorted, so the same program always builds the same object file
+        let mut names: Vec<(&String, &MirType)> = self.types.iter().collect();
+        names.sort_by_key(|(name, _)| *name);
+        let mut vars = HashMap::new();
+        for (name, ty) in names {
+            // Values of types without one are written by MIR this backend
+            // rejects when it gets to them
+            if let Ok(ty) = self.gen.value_type(ty) {
+                vars.insert(name.clone(), self.b.declare_var(ty));
+            }
+        }
+        self.vars = vars;
+
+        let entry = self.b.create_block();
+        self.b.append_block_params_for_function_params(entry);
+        let func = self.func;
+        for block in &func.blocks {
+            let clif_block = self.b.create_block();
+            self.blocks.insert(block.label.as_str(), clif_block);
+        }
+
+        self.b.switch_to_block(entry);
+        let params = self.b.block_params(entry).to_vec();
+        for (name, value) in func.params.iter().zip(params) {
+            self.def(name, value)?;
+        }
+        for instr in prelude {
+            self.lower_instr(instr)?;
+        }
+        let Some(first) = func.blocks.first() else {
+            return self.lower_return(&[]);
+        };
+        let first = self.block(&first.label)?;
+        self.b.ins().jump(first, &[]);
+
+        for block in &func.blocks {
+            let clif_block = self.block(&block.label)?;
+            self.b.switch_to_block(clif_block);
+            for (index, instr) in block.instrs.iter().enumerate() {
+                self.set_location(&block.label, index);
+                self.lower_instr(instr)?;
+            }
+            self.set_location(&block.label, block.instrs.len());
+            self.lower_terminator(block.terminator.as_ref())?;
+        }
+        Ok(())
+    }
+
+    /// Moves the line panics report to the statement starting at
+    /// instruction `index` of `block`, if one does.
+    fn set_location(&mut self, block: &str, index: usize) {
+        if let Some(span) = self.spans.get(&(block, index)) {
+            self.line = span.line as u32;
+        }
+    }
+
+    fn block(&self, label: &str) -> Result<Block, String> {
+        self.blocks
+            .get(label)
+            .copied()
+            .ok_or_else(|| format!("jump to unknown block '{}'", label))
+    }
+
+    fn ty(&self, name: &str) -> Result<&MirType, String> {
+        self.types
+            .get(name)
+            .ok_or_else(|| format!("'{}' is read before it is written", name))
+    }
+
+    fn var(&self, name: &str) -> Result<Variable, String> {
+        match self.vars.get(name) {
+            Some(&var) => Ok(var),
+            None => match self.gen.value_type(self.ty(name)?) {
+                Err(e) => Err(e),
+                Ok(_) => Err(format!("'{}' has no variable", name)),
+            },
+        }
+    }
+
+    fn use_value(&mut self, name: &str) -> Result<Value, String> {
+        let var = self.var(name)?;
+        Ok(self.b.use_var(var))
+    }
+
+    fn use_values(&mut self, names: &[String]) -> Result<Vec<Value>, String> {
+        names.iter().map(|name| self.use_value(name)).collect()
+    }
+
+    fn def(&mut self, name: &str, value: Value) -> Result<(), String> {
+        let var = self.var(name)?;
+        self.b.def_var(var, value);
+        Ok(())
+    }
+
+    /// An integer constant of type `ty`. Cranelift wants the bits above
+    /// the type's width clear.
+    fn int_const(&mut self, ty: Type, value: i64) -> Value {
+        let bits = ty.bits();
+        let value = if bits < 64 {
+            value & ((1 << bits) - 1)
+        } else {
+            value
+        };
+        self.b.ins().iconst(ty, value)
+    }
+
+    /// A comparison's flag as a Bool value.
+    fn bool_value(&mut self, flag: Value) -> Value {
+        self.b.ins().uextend(types::I32, flag)
+    }
+
+    fn lower_instr(&mut self, instr: &MirInstr) -> Result<(), String> {
+        match instr {
+            // The values this backend has are scalars and string constants,
+            // none of them reference counted
+            MirInstr::IncRef { .. } | MirInstr::DecRef { .. } => {}
+
+            MirInstr::ConstInt { name, value, .. } => {
+                let value = self.int_const(types::I32, i64::from(*value));
+                self.def(name, value)?;
+            }
+            MirInstr::ConstInt64 { name, value } => {
+                let value = self.b.ins().iconst(types::I64, *value);
+                self.def(name, value)?;
+            }
+            MirInstr::ConstFloat { name, value } => {
+                let value = self.b.ins().f64const(*value);
+                self.def(name, value)?;
+            }
+            MirInstr::ConstBool { name, value } => {
+                let value = self.int_const(types::I32, i64::from(*value));
+                self.def(name, value)?;
+            }
+            MirInstr::ConstString { name, value } => {
+                let value = self.gen.string_ptr(&mut self.b, value)?;
+                self.def(name, value)?;
+            }
+
+            MirInstr::Add(dest, lhs, rhs, _) => self.lower_binary("add", dest, lhs, rhs)?,
+            MirInstr::Sub(dest, lhs, rhs, _) => self.lower_binary("sub", dest, lhs, rhs)?,
+            MirInstr::Mul(dest, lhs, rhs, _) => self.lower_binary("mul", dest, lhs, rhs)?,
+            MirInstr::Div(dest, lhs, rhs, _) => self.lower_binary("div", dest, lhs, rhs)?,
+            MirInstr::BinaryOp(op, dest, lhs, rhs, _) => {
+                self.lower_binary(op_name(op), dest, lhs, rhs)?
+            }
+
+            MirInstr::Assign { name, value, .. } => {
+                let value = self.use_value(value)?;
+                self.def(name, value)?;
+            }
+
+            MirInstr::Call {
+                dest, func, args, ..
+            } => self.lower_call(dest, func, args)?,
+            MirInstr::Print { values, .. } => self.lower_print(values)?,
+
+            other => return Err(unsupported(format!("`{}`", other))),
+        }
+        Ok(())
+    }
+
+    fn lower_terminator(&mut self, terminator: Option<&MirInstr>) -> Result<(), String> {
+        match terminator {
+            // A block without a terminator returns, as in the LLVM backend
+            None => self.lower_return(&[])?,
+            Some(MirInstr::Return { values }) => self.lower_return(values)?,
+            Some(MirInstr::Panic { message }) => {
+                let message = self.use_value(message)?;
+                self.build_panic(message)?;
+            }
+            Some(MirInstr::Jump { target }) => {
+                let target = self.block(target)?;
+                self.b.ins().jump(target, &[]);
+            }
+            Some(MirInstr::CondJump {
+                cond,
+                then_block,
+                else_block,
+            }) => {
+                let cond = self.use_value(cond)?;
+                let (then_block, else_block) = (self.block(then_block)?, self.block(else_block)?);
+                self.b.ins().brif(cond, then_block, &[], else_block, &[]);
+            }
+            Some(other) => return Err(unsupported(format!("the terminator `{}`", other))),
+        }
+        Ok(())
+    }
+
+    /// Returns `values`: `main` returns 0 to C, whatever it returns, and a
+    /// function ending without a value returns its type's zero.
+    fn lower_return(&mut self, values: &[String]) -> Result<(), String> {
+        if self.func.name == "main" {
+            let zero = self.b.ins().iconst(types::I32, 0);
+            self.b.ins().return_(&[zero]);
+            return Ok(());
+        }
+        let Some(ty) = self.gen.result_type(&self.return_type)? else {
+            self.b.ins().return_(&[]);
+            return Ok(());
+        };
+        let value = match values {
+            [] if ty == types::F64 => self.b.ins().f64const(0.0),
+            [] => self.b.ins().iconst(ty, 0),
+            [value] => self.use_value(value)?,
+            _ => return Err(unsupported("returning several values")),
+        };
+        self.b.ins().return_(&[value]);
+        Ok(())
+    }
+
+    /// Ends the current block with a call to `doo_panic`, naming the
+    /// statement being lowered as where it happened.
+    fn build_panic(&mut self, message: Value) -> Result<(), String> {
+        let ptr = self.gen.pointer_type();
+        let file = match self.gen.source_file.clone() {
+            Some(file) if self.line > 0 => self.gen.string_ptr(&mut self.b, &file)?,
+            _ => self.b.ins().iconst(ptr, 0),
+        };
+        let line = self.int_const(types::I32, i64::from(self.line));
+        let panic_fn = self.gen.panic_fn()?;
+        self.gen.call(&mut self.b, panic_fn, &[message, file, line]);
+        self.b.ins().trap(TrapCode::unwrap_user(1));
+        Ok(())
+    }
+
+    /// Panics with `message` unless `ok` holds, then carries on in a new block.
+    fn build_panic_unless(&mut self, ok: Value, message: &str) -> Result<(), String> {
+        let panic_block = self.b.create_block();
+        let ok_block = self.b.create_block();
+        self.b.ins().brif(ok, ok_block, &[], panic_block, &[]);
+
+        self.b.switch_to_block(panic_block);
+        let message = self.gen.string_ptr(&mut self.b, message)?;
+        self.build_panic(message)?;
+
+        self.b.switch_to_block(ok_block);
+        Ok(())
+    }
+
+    /// `lhs op rhs`, by the type of `lhs`: the analyzer has checked both
+    /// sides have the same one.
+    fn lower_binary(&mut self, op: &str, dest: &str, lhs: &str, rhs: &str) -> Result<(), String> {
+        let ty = self.ty(lhs)?.clone();
+        let (lhs, rhs) = (self.use_value(lhs)?, self.use_value(rhs)?);
+        let value = match (&ty, op) {
+            (MirType::Int | MirType::Int64, _) => self.int_op(op, lhs, rhs)?,
+            (MirType::Float, _) => self.float_op(op, lhs, rhs)?,
+            (MirType::Bool, "and") => self.b.ins().band(lhs, rhs),
+            (MirType::Bool, "or") => self.b.ins().bor(lhs, rhs),
+            (MirType::Bool, "eq" | "ne") => {
+                let cc = int_cc(op).expect("eq and ne compare");
+                let flag = self.b.ins().icmp(cc, lhs, rhs);
+                self.bool_value(flag)
+            }
+            // Strings compare by their contents
+            (MirType::Str | MirType::CStr, _) if int_cc(op).is_some() => {
+                let ptr = self.gen.pointer_type();
+                let strcmp = self.gen.import("strcmp", &[ptr, ptr], &[types::I32])?;
+                let order = self.gen.call(&mut self.b, strcmp, &[lhs, rhs])[0];
+                let cc = int_cc(op).expect("checked above");
+                let flag = self.b.ins().icmp_imm_s(cc, order, 0);
+                self.bool_value(flag)
+            }
+            (ty, op) => return Err(unsupported(format!("`{}` on {}", op, ty))),
+        };
+        self.def(dest, value)
+    }
+
+    /// Int and Int64 arithmetic, which wraps unless `overflow_checks` is
+    /// set, and comparisons.
+    fn int_op(&mut self, op: &str, lhs: Value, rhs: Value) -> Result<Value, String> {
+        if let Some(cc) = int_cc(op) {
+            let flag = self.b.ins().icmp(cc, lhs, rhs);
+            return Ok(self.bool_value(flag));
+        }
+        let value = match op {
+            "add" | "sub" | "mul" if self.gen.overflow_checks => {
+                let (value, overflowed) = match op {
+                    "add" => self.b.ins().sadd_overflow(lhs, rhs),
+                    "sub" => self.b.ins().ssub_overflow(lhs, rhs),
+                    _ => self.b.ins().smul_overflow(lhs, rhs),
+                };
+                let ok = self.b.ins().icmp_imm_s(IntCC::Equal, overflowed, 0);
+                self.build_panic_unless(ok, "integer overflow")?;
+                value
+            }
+            "add" => self.b.ins().iadd(lhs, rhs),
+            "sub" => self.b.ins().isub(lhs, rhs),
+            "mul" => self.b.ins().imul(lhs, rhs),
+            "div" => {
+                let divisor = self.build_division_check(lhs, rhs)?;
+                self.b.ins().sdiv(lhs, divisor)
+            }
+            "mod" => {
+                let divisor = self.build_division_check(lhs, rhs)?;
+                self.b.ins().srem(lhs, divisor)
+            }
+            op => return Err(unsupported(format!("`{}` on integers", op))),
+        };
+        Ok(value)
+    }
+
+    /// Panics with "division by zero" when `divisor` is 0, and returns the
+    /// divisor to divide by. The minimum value divided by -1 overflows: with
+    /// overflow checks it panics with "integer overflow", and otherwise
+    /// divides by 1 instead, the same as wrapping.
+    fn build_division_check(&mut self, dividend: Value, divisor: Value) -> Result<Value, String> {
+        let ty = self.b.func.dfg.value_type(divisor);
+        let nonzero = self.b.ins().icmp_imm_s(IntCC::NotEqual, divisor, 0);
+        self.build_panic_unless(nonzero, "division by zero")?;
+
+        let min = self.int_const(ty, i64::MIN >> (64 - ty.bits()));
+        let minus_one = self.int_const(ty, -1);
+        let is_min = self.b.ins().icmp(IntCC::Equal, dividend, min);
+        let is_minus_one = self.b.ins().icmp(IntCC::Equal, divisor, minus_one);
+        let overflows = self.b.ins().band(is_min, is_minus_one);
+        if self.gen.overflow_checks {
+            let fits = self.b.ins().icmp_imm_s(IntCC::Equal, overflows, 0);
+            self.build_panic_unless(fits, "integer overflow")?;
+            return Ok(divisor);
+        }
+        let one = self.int_const(ty, 1);
+        Ok(self.b.ins().select(overflows, one, divisor))
+    }
+
+    fn float_op(&mut self, op: &str, lhs: Value, rhs: Value) -> Result<Value, String> {
+        let cc = match op {
+            "eq" => Some(FloatCC::Equal),
+            "ne" => Some(FloatCC::OrderedNotEqual),
+            "lt" => Some(FloatCC::LessThan),
+            "le" => Some(FloatCC::LessThanOrEqual),
+            "gt" => Some(FloatCC::GreaterThan),
+            "ge" => Some(FloatCC::GreaterThanOrEqual),
+            _ => None,
+        };
+        if let Some(cc) = cc {
+            let flag = self.b.ins().fcmp(cc, lhs, rhs);
+            return Ok(self.bool_value(flag));
+        }
+        let value = match op {
+            "add" => self.b.ins().fadd(lhs, rhs),
+            "sub" => self.b.ins().fsub(lhs, rhs),
+            "mul" => self.b.ins().fmul(lhs, rhs),
+            "div" => self.b.ins().fdiv(lhs, rhs),
+            "mod" => {
+                let fmod = self
+                    .gen
+                    .import("fmod", &[types::F64, types::F64], &[types::F64])?;
+                self.gen.call(&mut self.b, fmod, &[lhs, rhs])[0]
+            }
+            op => return Err(unsupported(format!("`{}` on floats", op))),
+        };
+        Ok(value)
+    }
+
+    /// A call to a function of the program, an `extern "C"` one or a
+    /// conversion builtin, tagged with its argument type (`toFloat:int`).
+    fn lower_call(&mut self, dest: &[String], func: &str, args: &[String]) -> Result<(), String> {
+        let args = self.use_values(args)?;
+        let results = if BUILTIN_FUNCTIONS.contains(&op_name(func)) {
+            vec![self.lower_conversion(func, &args)?]
+        } else {
+            let Some(declared) = self.gen.functions.get(func) else {
+                return Err(format!("call to unknown function '{}'", func));
+            };
+            let id = declared.id;
+            self.gen.call(&mut self.b, id, &args)
+        };
+        for (name, value) in dest.iter().zip(results) {
+            self.def(name, value)?;
+        }
+        Ok(())
+    }
+
+    fn lower_conversion(&mut self, func: &str, args: &[Value]) -> Result<Value, String> {
+        let Some(&arg) = args.first() else {
+            return Err(format!("{} needs an argument", func));
+        };
+        let value = match func.split_once(':').unwrap_or((func, "")) {
+            ("toFloat", "int" | "int64" | "bool") => self.b.ins().fcvt_from_sint(types::F64, arg),
+            ("toInt", "float") => self.b.ins().fcvt_to_sint_sat(types::I32, arg),
+            ("toInt64", "float") => self.b.ins().fcvt_to_sint_sat(types::I64, arg),
+            ("toInt64", "int") => self.b.ins().sextend(types::I64, arg),
+            // toInt(Int), toInt(Bool), toFloat(Float) and toInt64(Int64)
+            // are the value itself
+            ("toInt", "int" | "bool") | ("toFloat", "float") | ("toInt64", "int64") => arg,
+            _ => return Err(unsupported(format!("`{}`", func))),
+        };
+        Ok(value)
+    }
+
+    /// Prints `values` separated by spaces, then a newline, formatted as
+    /// the LLVM backend's printf calls do.
+    fn lower_print(&mut self, values: &[String]) -> Result<(), String> {
+        for (index, name) in values.iter().enumerate() {
+            let separator = if index + 1 < values.len() { " " } else { "" };
+            let ty = self.ty(name)?.clone();
+            let value = self.use_value(name)?;
+            match ty {
+                MirType::Int => {
+                    self.gen
+                        .printf(&mut self.b, &format!("%d{}", separator), &[value])?
+                }
+                MirType::Int64 => {
+                    self.gen
+                        .printf(&mut self.b, &format!("%lld{}", separator), &[value])?
+                }
+                MirType::Bool => {
+                    let true_text = self.gen.string_ptr(&mut self.b, "true")?;
+                    let false_text = self.gen.string_ptr(&mut self.b, "false")?;
+                    let text = self.b.ins().select(value, true_text, false_text);
+                    self.gen
+                        .printf(&mut self.b, &format!("%s{}", separator), &[text])?
+                }
+                MirType::Str | MirType::CStr => {
+                    self.gen
+                        .printf(&mut self.b, &format!("%s{}", separator), &[value])?
+                }
+                MirType::Float => {
+                    let print_float = self.gen.print_float_fn()?;
+                    self.gen.call(&mut self.b, print_float, &[value]);
+                    if !separator.is_empty() {
+                        self.gen.printf(&mut self.b, separator, &[])?;
+                    }
+                }
+                ty => return Err(unsupported(format!("printing {}", ty))),
+            }
+        }
+        self.gen.printf(&mut self.b, "\n", &[])
+    }
+}
+
+/// The signed integer comparison `op` names.
+fn int_cc(op: &str) -> Option<IntCC> {
+    match op {
+        "eq" => Some(IntCC::Equal),
+        "ne" => Some(IntCC::NotEqual),
+        "lt" => Some(IntCC::SignedLessThan),
+        "le" => Some(IntCC::SignedLessThanOrEqual),
+        "gt" => Some(IntCC::SignedGreaterThan),
+        "ge" => Some(IntCC::SignedGreaterThanOrEqual),
+        _ => None,
+    }
+}
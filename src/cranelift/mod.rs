@@ -0,0 +1,234 @@
+//! The Cranelift backend: lowers MIR to an object file through Cranelift
+//! instead of LLVM. It runs no optimizations, so it builds much faster, and
+//! its code follows the LLVM backend's ABI (symbol names, `doo_panic`, how
+//! values are passed), so the two link the same way.
+//!
+//! It covers the scalar subset of MIR so far: Int, Int64, Float and Bool
+//! values, string constants, arithmetic and comparisons, control flow,
+//! calls (to the program's functions, `extern "C"` ones and the numeric
+//! conversions), `print` and `panic`. A program using anything else fails to
+//! build with an error naming it, instead of being miscompiled.
+
+mod function;
+mod runtime;
+
+use crate::mir::{MirFunction, MirProgram, MirType};
+use cranelift_codegen::ir::{types, AbiParam, Signature, Type};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_module::{default_libcall_names, DataDescription, DataId, FuncId, Linkage, Module};
+use cranelift_object::{ObjectBuilder, ObjectModule};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+/// A declared function of the object file, with the MIR signature calls to
+/// it are typed by.
+#[derive(Debug, Clone)]
+pub(crate) struct Declared {
+    pub id: FuncId,
+    pub param_types: Vec<MirType>,
+    pub return_type: MirType,
+}
+
+/// Generates one object file from a `MirProgram`, like `CodeGen` does an
+/// LLVM module.
+pub struct CraneliftGen {
+    pub(crate) module: ObjectModule,
+    pub(crate) functions: HashMap<String, Declared>, // MIR name -> declaration
+    pub(crate) runtime: HashMap<&'static str, FuncId>, // C library and runtime functions used so far
+    strings: HashMap<String, DataId>,                  // Interned string constants
+    pub overflow_checks: bool,                         // Panic on Int overflow instead of wrapping
+    pub source_file: Option<String>,                   // The file panic messages point into
+    pub imported_functions: HashSet<String>, // Functions from other files, which get no panic location
+}
+
+impl CraneliftGen {
+    /// A generator for the host, producing position-independent code for
+    /// the C compiler driver to link.
+    pub fn new() -> Result<Self, String> {
+        if cfg!(all(target_os = "macos", target_arch = "aarch64")) {
+            // `print` calls printf as Cranelift calls any function, which is
+            // how variadic arguments are passed everywhere else
+            return Err(unsupported("Apple silicon hosts"));
+        }
+        let mut flags = settings::builder();
+        flags
+            .set("is_pic", "true")
+            .map_err(|e| format!("Failed to configure Cranelift: {}", e))?;
+        let isa = cranelift_native::builder()
+            .map_err(|e| format!("Cranelift does not support this host: {}", e))?
+            .finish(settings::Flags::new(flags))
+            .map_err(|e| format!("Failed to configure Cranelift: {}", e))?;
+        let builder = ObjectBuilder::new(isa, "main_module", default_libcall_names())
+            .map_err(|e| format!("Failed to create the object file: {}", e))?;
+        Ok(Self {
+            module: ObjectModule::new(builder),
+            functions: HashMap::new(),
+            runtime: HashMap::new(),
+            strings: HashMap::new(),
+            overflow_checks: false,
+            source_file: None,
+            imported_functions: HashSet::new(),
+        })
+    }
+
+    /// Declares every function first, so calls can come before definitions,
+    /// then defines them. `main` runs the global-scope statements first.
+    pub fn generate_program(&mut self, program: &MirProgram) -> Result<(), String> {
+        for func in &program.externs {
+            self.declare(func, Linkage::Import)?;
+        }
+        for func in &program.functions {
+            let linkage = if func.export || func.name == "main" {
+                Linkage::Export
+            } else {
+                Linkage::Local
+            };
+            self.declare(func, linkage)?;
+        }
+        for func in &program.functions {
+            let prelude: &[_] = if func.name == "main" {
+                &program.globals
+            } else {
+                &[]
+            };
+            self.define(func, prelude)?;
+        }
+        Ok(())
+    }
+
+    /// The object file's bytes.
+    pub fn finish(self) -> Result<Vec<u8>, String> {
+        self.module
+            .finish()
+            .emit()
+            .map_err(|e| format!("Failed to write object file: {}", e))
+    }
+
+    fn declare(&mut self, func: &MirFunction, linkage: Linkage) -> Result<(), String> {
+        let param_types: Vec<MirType> = func
+            .param_types
+            .iter()
+            .map(|ty| ty.clone().unwrap_or(MirType::Int))
+            .collect();
+        let return_type = func.return_type.clone().unwrap_or(MirType::Void);
+        let mut sig = self.module.make_signature();
+        for ty in &param_types {
+            sig.params.push(AbiParam::new(self.value_type(ty)?));
+        }
+        if func.name == "main" {
+            // A C `main`, whatever doo's returns
+            sig.returns.push(AbiParam::new(types::I32));
+        } else if let Some(ty) = self.result_type(&return_type)? {
+            sig.returns.push(AbiParam::new(ty));
+        }
+        let id = self
+            .module
+            .declare_function(&symbol_name(&func.name), linkage, &sig)
+            .map_err(|e| format!("Failed to declare {}: {}", func.name, e))?;
+        self.functions.insert(
+            func.name.clone(),
+            Declared {
+                id,
+                param_types,
+                return_type,
+            },
+        );
+        Ok(())
+    }
+
+    /// The Cranelift type holding a value of `ty`. Bools are `i32`s, as the
+    /// LLVM backend passes them; strings are pointers to their bytes.
+    pub(crate) fn value_type(&self, ty: &MirType) -> Result<Type, String> {
+        match ty {
+            MirType::Int | MirType::Bool => Ok(types::I32),
+            MirType::Int64 => Ok(types::I64),
+            MirType::Float => Ok(types::F64),
+            MirType::Str | MirType::CStr => Ok(self.pointer_type()),
+            ty => Err(unsupported(format!("values of type {}", ty))),
+        }
+    }
+
+    /// `value_type` of a function's result: none for `Void`.
+    pub(crate) fn result_type(&self, ty: &MirType) -> Result<Option<Type>, String> {
+        match ty {
+            MirType::Void | MirType::Never => Ok(None),
+            ty => self.value_type(ty).map(Some),
+        }
+    }
+
+    pub(crate) fn pointer_type(&self) -> Type {
+        self.module.target_config().pointer_type()
+    }
+
+    /// A read-only, NUL-terminated copy of `text` in the object file, shared
+    /// by every use of the same text.
+    pub(crate) fn intern_string(&mut self, text: &str) -> Result<DataId, String> {
+        if let Some(&id) = self.strings.get(text) {
+            return Ok(id);
+        }
+        let id = self
+            .module
+            .declare_data(
+                &format!("str.{}", self.strings.len()),
+                Linkage::Local,
+                false,
+                false,
+            )
+            .map_err(|e| format!("Failed to declare a string constant: {}", e))?;
+        let mut data = DataDescription::new();
+        let mut bytes = text.as_bytes().to_vec();
+        bytes.push(0);
+        data.define(bytes.into_boxed_slice());
+        self.module
+            .define_data(id, &data)
+            .map_err(|e| format!("Failed to define a string constant: {}", e))?;
+        self.strings.insert(text.to_string(), id);
+        Ok(id)
+    }
+
+    /// Declares (once) the C library function `name` with `params` and
+    /// `returns`, linked from outside the object file.
+    pub(crate) fn import(
+        &mut self,
+        name: &'static str,
+        params: &[Type],
+        returns: &[Type],
+    ) -> Result<FuncId, String> {
+        if let Some(&id) = self.runtime.get(name) {
+            return Ok(id);
+        }
+        let sig = self.signature(params, returns);
+        let id = self
+            .module
+            .declare_function(name, Linkage::Import, &sig)
+            .map_err(|e| format!("Failed to declare {}: {}", name, e))?;
+        self.runtime.insert(name, id);
+        Ok(id)
+    }
+
+    pub(crate) fn signature(&self, params: &[Type], returns: &[Type]) -> Signature {
+        let mut sig = self.module.make_signature();
+        sig.params
+            .extend(params.iter().map(|&ty| AbiParam::new(ty)));
+        sig.returns
+            .extend(returns.iter().map(|&ty| AbiParam::new(ty)));
+        sig
+    }
+}
+
+/// Symbol of a MIR function, as the LLVM backend names it: `User::greet` is
+/// emitted as `User_greet`.
+fn symbol_name(name: &str) -> String {
+    name.replace("::", "_")
+}
+
+/// The error for MIR this backend can't lower yet.
+pub(crate) fn unsupported(what: impl Display) -> String {
+    format!(
+        "The Cranelift backend does not support {} yet: build with the LLVM backend",
+        what
+    )
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,228 @@
+use crate::cranelift::CraneliftGen;
+use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
+use cranelift_codegen::ir::{
+    types, Function, InstBuilder, MemFlagsData, StackSlotData, StackSlotKind, TrapCode, Value,
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_module::{FuncId, Linkage, Module};
+
+impl CraneliftGen {
+    /// A pointer to `text`, NUL-terminated, in the function being built.
+    pub(crate) fn string_ptr(
+        &mut self,
+        b: &mut FunctionBuilder,
+        text: &str,
+    ) -> Result<Value, String> {
+        let id = self.intern_string(text)?;
+        let data = self.module.declare_data_in_func(id, b.func);
+        Ok(b.ins().symbol_value(self.pointer_type(), data))
+    }
+
+    /// Calls `id` with `args` and returns its results.
+    pub(crate) fn call(
+        &mut self,
+        b: &mut FunctionBuilder,
+        id: FuncId,
+        args: &[Value],
+    ) -> Vec<Value> {
+        let func = self.module.declare_func_in_func(id, b.func);
+        let call = b.ins().call(func, args);
+        b.inst_results(call).to_vec()
+    }
+
+    /// Calls the variadic C function `name` (`printf`, or `dprintf`) as one
+    /// taking exactly `args`. Only integers and pointers are passed to it:
+    /// on x86-64, floats would need the count of vector registers used in
+    /// `al`, which Cranelift doesn't set.
+    pub(crate) fn call_variadic(
+        &mut self,
+        b: &mut FunctionBuilder,
+        name: &'static str,
+        args: &[Value],
+    ) -> Result<(), String> {
+        let ptr = self.pointer_type();
+        let fixed: &[_] = if name == "dprintf" {
+            &[types::I32, ptr]
+        } else {
+            &[ptr]
+        };
+        let id = self.import(name, fixed, &[types::I32])?;
+        let func = self.module.declare_func_in_func(id, b.func);
+        let addr = b.ins().func_addr(ptr, func);
+        let arg_types: Vec<_> = args.iter().map(|&arg| b.func.dfg.value_type(arg)).collect();
+        let sig = b.import_signature(self.signature(&arg_types, &[types::I32]));
+        b.ins().call_indirect(sig, addr, args);
+        Ok(())
+    }
+
+    /// `printf(format, args...)`.
+    pub(crate) fn printf(
+        &mut self,
+        b: &mut FunctionBuilder,
+        format: &str,
+        args: &[Value],
+    ) -> Result<(), String> {
+        let format = self.string_ptr(b, format)?;
+        let args: Vec<Value> = std::iter::once(format)
+            .chain(args.iter().copied())
+            .collect();
+        self.call_variadic(b, "printf", &args)
+    }
+
+    /// Emits (once) `void doo_panic(ptr message, ptr file, i32 line)`, the
+    /// same function the LLVM backend emits: writes "panic: <message>" to
+    /// stderr, followed by "  at <file>:<line>" when `file` isn't null, and
+    /// exits with status 101.
+    pub(crate) fn panic_fn(&mut self) -> Result<FuncId, String> {
+        if let Some(&id) = self.runtime.get("doo_panic") {
+            return Ok(id);
+        }
+        let ptr = self.pointer_type();
+        let sig = self.signature(&[ptr, ptr, types::I32], &[]);
+        let id = self
+            .module
+            .declare_function("doo_panic", Linkage::Export, &sig)
+            .map_err(|e| format!("Failed to declare doo_panic: {}", e))?;
+        self.runtime.insert("doo_panic", id);
+
+        let mut func = Function::with_name_signature(Default::default(), sig);
+        let mut func_ctx = FunctionBuilderContext::new();
+        let mut b = FunctionBuilder::new(&mut func, &mut func_ctx);
+        let entry = b.create_block();
+        let located = b.create_block();
+        let exit_block = b.create_block();
+        b.append_block_params_for_function_params(entry);
+        b.switch_to_block(entry);
+        let (message, file, line) = match *b.block_params(entry) {
+            [message, file, line] => (message, file, line),
+            _ => unreachable!("doo_panic takes three parameters"),
+        };
+        let stderr = b.ins().iconst(types::I32, 2);
+        let format = self.string_ptr(&mut b, "panic: %s\n")?;
+        self.call_variadic(&mut b, "dprintf", &[stderr, format, message])?;
+        b.ins().brif(file, located, &[], exit_block, &[]);
+
+        b.switch_to_block(located);
+        let format = self.string_ptr(&mut b, "  at %s:%d\n")?;
+        self.call_variadic(&mut b, "dprintf", &[stderr, format, file, line])?;
+        b.ins().jump(exit_block, &[]);
+
+        b.switch_to_block(exit_block);
+        let exit = self.import("exit", &[types::I32], &[])?;
+        let status = b.ins().iconst(types::I32, 101);
+        self.call(&mut b, exit, &[status]);
+        b.ins().trap(TrapCode::unwrap_user(1));
+
+        b.seal_all_blocks();
+        b.finalize(self.module.target_config());
+        self.define_function(id, func)?;
+        Ok(id)
+    }
+
+    /// Emits (once) `void doo_print_float(double value)`: prints `value`
+    /// as printf's `%f` does, which it can't be handed (see `call_variadic`).
+    /// `fcvt` gives its digits and where the point goes among them.
+    pub(crate) fn print_float_fn(&mut self) -> Result<FuncId, String> {
+        if let Some(&id) = self.runtime.get("doo_print_float") {
+            return Ok(id);
+        }
+        let ptr = self.pointer_type();
+        let sig = self.signature(&[types::F64], &[]);
+        let id = self
+            .module
+            .declare_function("doo_print_float", Linkage::Local, &sig)
+            .map_err(|e| format!("Failed to declare doo_print_float: {}", e))?;
+        self.runtime.insert("doo_print_float", id);
+
+        let mut func = Function::with_name_signature(Default::default(), sig);
+        let mut func_ctx = FunctionBuilderContext::new();
+        let mut b = FunctionBuilder::new(&mut func, &mut func_ctx);
+        let entry = b.create_block();
+        let nan = b.create_block();
+        let check_inf = b.create_block();
+        let inf = b.create_block();
+        let finite = b.create_block();
+        b.append_block_params_for_function_params(entry);
+        b.switch_to_block(entry);
+        let value = b.block_params(entry)[0];
+        let is_nan = b.ins().fcmp(FloatCC::Unordered, value, value);
+        b.ins().brif(is_nan, nan, &[], check_inf, &[]);
+
+        b.switch_to_block(nan);
+        self.printf(&mut b, "nan", &[])?;
+        b.ins().return_(&[]);
+
+        b.switch_to_block(check_inf);
+        let magnitude = b.ins().fabs(value);
+        let infinity = b.ins().f64const(f64::INFINITY);
+        let is_inf = b.ins().fcmp(FloatCC::Equal, magnitude, infinity);
+        b.ins().brif(is_inf, inf, &[], finite, &[]);
+
+        b.switch_to_block(inf);
+        let zero = b.ins().f64const(0.0);
+        let negative = b.ins().fcmp(FloatCC::LessThan, value, zero);
+        let minus_inf = self.string_ptr(&mut b, "-inf")?;
+        let plus_inf = self.string_ptr(&mut b, "inf")?;
+        let text = b.ins().select(negative, minus_inf, plus_inf);
+        self.printf(&mut b, "%s", &[text])?;
+        b.ins().return_(&[]);
+
+        // char *fcvt(double value, int ndigit, int *decpt, int *sign)
+        b.switch_to_block(finite);
+        let fcvt = self.import("fcvt", &[types::F64, types::I32, ptr, ptr], &[ptr])?;
+        let slot = b.create_sized_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 8, 2));
+        let point_ptr = b.ins().stack_addr(ptr, slot, 0);
+        let sign_ptr = b.ins().stack_addr(ptr, slot, 4);
+        let six = b.ins().iconst(types::I32, 6);
+        let digits = self.call(&mut b, fcvt, &[value, six, point_ptr, sign_ptr])[0];
+        let point = b
+            .ins()
+            .load(types::I32, MemFlagsData::trusted(), point_ptr, 0);
+        let sign = b
+            .ins()
+            .load(types::I32, MemFlagsData::trusted(), sign_ptr, 0);
+
+        // "-" if negative; the digits before the point, or "0" if none
+        let minus = self.string_ptr(&mut b, "-")?;
+        let empty = self.string_ptr(&mut b, "")?;
+        let sign_text = b.ins().select(sign, minus, empty);
+        let zero = b.ins().iconst(types::I32, 0);
+        let whole_len = b.ins().smax(point, zero);
+        let has_whole = b.ins().icmp(IntCC::SignedGreaterThan, point, zero);
+        let zero_text = self.string_ptr(&mut b, "0")?;
+        let whole_zero = b.ins().select(has_whole, empty, zero_text);
+        // After the point, the zeros `fcvt` leaves out, then the rest
+        let negated = b.ins().ineg(point);
+        let leading = b.ins().smax(negated, zero);
+        let leading = b.ins().smin(leading, six);
+        let zeros = self.string_ptr(&mut b, "000000")?;
+        let offset = if ptr == types::I32 {
+            whole_len
+        } else {
+            b.ins().sextend(ptr, whole_len)
+        };
+        let fraction = b.ins().iadd(digits, offset);
+        self.printf(
+            &mut b,
+            "%s%.*s%s.%.*s%s",
+            &[
+                sign_text, whole_len, digits, whole_zero, leading, zeros, fraction,
+            ],
+        )?;
+        b.ins().return_(&[]);
+
+        b.seal_all_blocks();
+        b.finalize(self.module.target_config());
+        self.define_function(id, func)?;
+        Ok(id)
+    }
+
+    /// Compiles the finished `func` into the object file as `id`.
+    pub(crate) fn define_function(&mut self, id: FuncId, func: Function) -> Result<(), String> {
+        let mut ctx = self.module.make_context();
+        ctx.func = func;
+        self.module
+            .define_function(id, &mut ctx)
+            .map_err(|e| format!("Failed to compile a function: {:?}", e))
+    }
+}
@@ -0,0 +1,207 @@
+#[cfg(test)]
+mod cranelift_tests {
+    use crate::analyzer::files::MemoryFiles;
+    use crate::cranelift::CraneliftGen;
+    use crate::frontend::{front_end, FrontEndOptions};
+    use crate::mir::{Interpreter, MirProgram};
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+    use std::rc::Rc;
+
+    /// What a program did: its exit code, stdout and stderr.
+    struct Run {
+        code: i32,
+        stdout: String,
+        stderr: String,
+    }
+
+    fn lower(source: &str) -> MirProgram {
+        let (result, lowered) = front_end(
+            Path::new("main.doo"),
+            source,
+            &FrontEndOptions::default(),
+            Rc::new(MemoryFiles::new()),
+        );
+        assert_eq!(result.error_count, 0, "{:?}", result.diagnostics);
+        lowered.expect("the program lowers to MIR").program
+    }
+
+    /// The object file Cranelift builds for `source`.
+    fn build(source: &str, overflow_checks: bool) -> Result<Vec<u8>, String> {
+        let program = lower(source);
+        let mut gen = CraneliftGen::new()?;
+        gen.overflow_checks = overflow_checks;
+        gen.source_file = Some("main.doo".to_string());
+        gen.generate_program(&program)?;
+        gen.finish()
+    }
+
+    /// Builds `source` with Cranelift, links it with the C compiler and runs it.
+    fn run_with(name: &str, source: &str, overflow_checks: bool) -> Run {
+        let object = build(source, overflow_checks).unwrap();
+        let stem = format!("doo_cranelift_{}_{}", name, std::process::id());
+        let obj_file = std::env::temp_dir().join(format!("{}.o", stem));
+        let exe_file: PathBuf = std::env::temp_dir().join(stem);
+        std::fs::write(&obj_file, object).unwrap();
+        let linked = Command::new("cc")
+            .arg(&obj_file)
+            .arg("-lm")
+            .arg("-o")
+            .arg(&exe_file)
+            .output()
+            .expect("a C compiler links the object file");
+        assert!(
+            linked.status.success(),
+            "{}",
+            String::from_utf8_lossy(&linked.stderr)
+        );
+        let output = Command::new(&exe_file).output().unwrap();
+        let _ = std::fs::remove_file(&obj_file);
+        let _ = std::fs::remove_file(&exe_file);
+        Run {
+            code: output.status.code().unwrap_or(-1),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }
+    }
+
+    fn run(name: &str, source: &str) -> Run {
+        run_with(name, source, false)
+    }
+
+    /// What the MIR interpreter prints for `source`, which compiled code
+    /// must print too.
+    fn interpret(source: &str) -> String {
+        let program = lower(source);
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        Interpreter::new(&program, &mut out, &mut err)
+            .run()
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_runs_like_the_interpreter() {
+        let source = r#"
+fn fib(n: Int) -> Int {
+    if n < 2 { return n; }
+    return fib(n - 1) + fib(n - 2);
+}
+
+fn half(x: Float) -> Float { return x / 2.0; }
+
+fn main() {
+    let mut total = 0;
+    for i in 0..10 {
+        total = total + i;
+    }
+    let big: Int64 = toInt64(total) * toInt64(100000000);
+    let b = total == 45 && total < 99 || false;
+    print(fib(20), total, big, b, "done");
+    print(half(3.0), toFloat(total), 7 % 3, -total, 17 / 5, toInt(9.99));
+    if total >= 45 {
+        print("big enough");
+    } else {
+        print("too small");
+    }
+}
+"#;
+        let run = run("interp", source);
+        assert_eq!(run.code, 0, "{}", run.stderr);
+        assert_eq!(run.stdout, interpret(source));
+        assert_eq!(
+            run.stdout,
+            "6765 45 4500000000 true done\n1.500000 45.000000 1 -45 3 9\nbig enough\n"
+        );
+    }
+
+    #[test]
+    fn test_prints_floats_as_printf_does() {
+        let source = r#"
+fn main() {
+    let zero = 0.0;
+    print(zero, -2.5, 100000000000000000000.0, 0.00012, 0.0 - 0.0000001);
+    print(1.0 / zero, -1.0 / zero, 0.9999999, 123456.789, 7.5 % 2.0);
+}
+"#;
+        let run = run("floats", source);
+        assert_eq!(run.code, 0, "{}", run.stderr);
+        assert_eq!(
+            run.stdout,
+            "0.000000 -2.500000 100000000000000000000.000000 0.000120 -0.000000\n\
+             inf -inf 1.000000 123456.789000 1.500000\n"
+        );
+    }
+
+    #[test]
+    fn test_panic_exits_through_doo_panic() {
+        let source = r#"
+fn main() {
+    print("before");
+    let n = 3;
+    if n > 2 {
+        panic("too big");
+    }
+    print("after");
+}
+"#;
+        let run = run("panic", source);
+        assert_eq!(run.code, 101);
+        assert_eq!(run.stdout, "before\n");
+        // Located, as in the LLVM backend, at the function's statement it is in
+        assert_eq!(run.stderr, "panic: too big\n  at main.doo:5\n");
+    }
+
+    #[test]
+    fn test_integer_checks_panic() {
+        let divide = r#"
+fn divide(a: Int, b: Int) -> Int { return a / b; }
+
+fn main() {
+    print(divide(7, 2));
+    print(divide(1, 0));
+}
+"#;
+        let divided = run("divide", divide);
+        assert_eq!(divided.code, 101);
+        assert_eq!(divided.stdout, "3\n");
+        assert!(
+            divided.stderr.starts_with("panic: division by zero\n"),
+            "{}",
+            divided.stderr
+        );
+
+        let overflow = r#"
+fn grow(x: Int) -> Int { return x * 1000; }
+
+fn main() {
+    print(grow(2000000));
+}
+"#;
+        assert_eq!(run("wrap", overflow).stdout, "2000000000\n");
+        let overflowed = run_with("overflow", &overflow.replace("2000000", "3000000"), true);
+        assert_eq!(overflowed.code, 101);
+        assert!(
+            overflowed.stderr.starts_with("panic: integer overflow\n"),
+            "{}",
+            overflowed.stderr
+        );
+    }
+
+    #[test]
+    fn test_unsupported_mir_is_an_error() {
+        let source = r#"
+fn main() {
+    let items = [1, 2, 3];
+    print(items);
+}
+"#;
+        let error = build(source, false).unwrap_err();
+        assert!(
+            error.contains("The Cranelift backend does not support"),
+            "{}",
+            error
+        );
+        assert!(error.contains("build with the LLVM backend"), "{}", error);
+    }
+}
@@ -0,0 +1,808 @@
+/// One entry of the registry of error codes and their extended descriptions, shown by
+/// `doo explain <code>`. Codes are stable: a code keeps its meaning once
+/// released, and new errors get new codes. `E0xxx` codes come from the
+/// semantic analyzer (`SemanticError::code`), `E2xxx` codes from the parser
+/// (`ParseError::code`).
+pub struct ErrorCodeInfo {
+    pub code: &'static str,
+    /// One-line summary of the error
+    pub title: &'static str,
+    /// What causes the error and how to fix it, with an example
+    pub explanation: &'static str,
+}
+
+/// Looks up an error code, written as `E0002` or `e0002`.
+pub fn explain(code: &str) -> Option<&'static ErrorCodeInfo> {
+    ERROR_CODES
+        .iter()
+        .find(|info| info.code.eq_ignore_ascii_case(code))
+}
+
+/// Every error code, in order.
+pub const ERROR_CODES: &[ErrorCodeInfo] = &[
+    // Variable Declaration/Assignment Errors
+    ErrorCodeInfo {
+        code: "E0001",
+        title: "variable redeclared in the same scope",
+        explanation: "A `let` declares a name that is already declared in the same block.
+
+    let count = 1;
+    let count = 2; // error
+
+Declare the variable with `let mut` and assign the new value, or pick another name:
+
+    let mut count = 1;
+    count = 2;",
+    },
+    ErrorCodeInfo {
+        code: "E0002",
+        title: "use of an undeclared variable",
+        explanation: "A name is used that no `let`, parameter or loop variable in scope declares.
+
+    fn main() {
+        print(total); // error: no `total` here
+    }
+
+Declare the variable before the statement that uses it, and check the spelling.",
+    },
+    ErrorCodeInfo {
+        code: "E0003",
+        title: "value doesn't match the variable's type",
+        explanation: "The value given to a variable (in its `let` or a later assignment) has a
+different type than the variable.
+
+    let n: Int = \"five\"; // error: expected Int, found String
+
+Convert the value (`toInt`, `toStr`, `toFloat`) or change the annotation.",
+    },
+    ErrorCodeInfo {
+        code: "E0004",
+        title: "tuple destructuring with the wrong number of names",
+        explanation: "A `let (a, b) = ...` names a different number of variables than the tuple has
+values.
+
+    fn pair() -> (Int, Int) { return 1, 2; }
+    let (a, b, c) = pair(); // error: expected 2 elements, found 3
+
+Name exactly one variable per value.",
+    },
+    ErrorCodeInfo {
+        code: "E0005",
+        title: "invalid assignment target",
+        explanation: "A value is assigned to, or changed in place through, something that can't be
+changed: an immutable variable, a parameter, or an expression that isn't a variable.
+
+    let nums = [1, 2];
+    nums.push(3); // error: `nums` isn't `mut`
+
+Declare the variable with `let mut`. To change a parameter, copy it into a
+`let mut` variable first.",
+    },
+    ErrorCodeInfo {
+        code: "E0006",
+        title: "variable used outside its block",
+        explanation: "A variable declared inside a block (an `if`, loop or `{ ... }`) is used after
+the block ended.
+
+    if ready {
+        let msg = \"go\";
+    }
+    print(msg); // error
+
+Declare the variable before the block, with `let mut` if the block sets it.",
+    },
+    ErrorCodeInfo {
+        code: "E0007",
+        title: "map key of the wrong type",
+        explanation:
+            "A map is indexed or filled with a key whose type differs from the map's key type.
+
+    let ages: {Str: Int} = {\"ann\": 30};
+    let a = ages[1]; // error: expected String, found Int
+
+Use keys of the map's key type.",
+    },
+    ErrorCodeInfo {
+        code: "E0008",
+        title: "nested function uses a variable of its enclosing function",
+        explanation: "Functions declared inside another function don't capture that function's
+variables.
+
+    fn outer() -> Int {
+        let base = 10;
+        fn add(n: Int) -> Int { return n + base; } // error
+        return add(1);
+    }
+
+Pass the value as a parameter: `fn add(n: Int, base: Int) -> Int`.",
+    },
+    // Function Declaration/Call Errors
+    ErrorCodeInfo {
+        code: "E0101",
+        title: "function redeclared",
+        explanation: "Two functions have the same name and the same parameter types.
+
+    fn area(r: Float) -> Float { return r * r; }
+    fn area(r: Float) -> Float { return 3.14 * r * r; } // error
+
+Rename one of them. Functions may share a name when their parameter types differ.",
+    },
+    ErrorCodeInfo {
+        code: "E0102",
+        title: "duplicate parameter name",
+        explanation: "A function declares two parameters with the same name.
+
+    fn add(a: Int, a: Int) -> Int { return a + a; } // error
+
+Give every parameter its own name.",
+    },
+    ErrorCodeInfo {
+        code: "E0103",
+        title: "parameter without a type",
+        explanation: "Every function parameter needs a type annotation.
+
+    fn double(n) -> Int { return n * 2; } // error
+
+Write the type: `fn double(n: Int) -> Int`.",
+    },
+    ErrorCodeInfo {
+        code: "E0104",
+        title: "call of an undeclared function",
+        explanation: "A call names a function that isn't declared, imported or built in.
+
+    fn main() {
+        let n = square(3); // error: no `square`
+    }
+
+Declare the function, import it from its module, or check the spelling.",
+    },
+    ErrorCodeInfo {
+        code: "E0105",
+        title: "invalid call",
+        explanation: "Something that isn't a function is called, or a call is used where its
+result can't go, such as a call returning one value assigned to several variables.
+
+    let x = 5;
+    x(1); // error
+
+Call a function by its name.",
+    },
+    ErrorCodeInfo {
+        code: "E0106",
+        title: "wrong number of arguments",
+        explanation: "A call passes more or fewer arguments than the function has parameters.
+
+    fn add(a: Int, b: Int) -> Int { return a + b; }
+    let n = add(1); // error: expects 2 arguments, found 1
+
+Pass one argument per parameter.",
+    },
+    ErrorCodeInfo {
+        code: "E0107",
+        title: "argument of the wrong type",
+        explanation: "An argument's type differs from the type of its parameter.
+
+    fn greet(name: Str) { print(name); }
+    greet(42); // error: expected String, found Int
+
+Convert the argument, for example with `toStr(42)`.",
+    },
+    ErrorCodeInfo {
+        code: "E0108",
+        title: "function may end without returning a value",
+        explanation: "A function with a return type has a path through its body that reaches the
+end without a `return`.
+
+    fn sign(n: Int) -> Int {
+        if n > 0 {
+            return 1;
+        }
+    } // error: nothing returned when n <= 0
+
+End every path with `return`, or with `panic(..)` where the function can't go on.",
+    },
+    ErrorCodeInfo {
+        code: "E0109",
+        title: "value returned from a Void function",
+        explanation: "A function without a return type returns a value.
+
+    fn log(msg: Str) {
+        return 1; // error
+    }
+
+Declare the return type (`fn log(msg: Str) -> Int`) or use a bare `return;`.",
+    },
+    ErrorCodeInfo {
+        code: "E0110",
+        title: "returned value doesn't match the return type",
+        explanation: "A `return` gives a value of a different type than the function declares.
+
+    fn half(n: Int) -> Int {
+        return n / 2.0; // error: expected Int, found Float
+    }
+
+Convert the value or change the return type.",
+    },
+    ErrorCodeInfo {
+        code: "E0111",
+        title: "public function name not capitalized",
+        explanation: "Functions that other modules import must start with an uppercase letter;
+functions starting with a lowercase letter are private to their module.
+
+    // models/User.doo, imported elsewhere as models::User::createUser
+    fn createUser(name: Str) -> Str { return name; } // error
+
+Rename it `CreateUser`.",
+    },
+    ErrorCodeInfo {
+        code: "E0112",
+        title: "no overload matches the arguments",
+        explanation: "A function has several overloads and none takes arguments of these types.
+
+    fn add(a: Int, b: Int) -> Int { return a + b; }
+    fn add(a: Str, b: Str) -> Str { return a + b; }
+    let x = add(1, \"b\"); // error
+
+Pass arguments matching one of the overloads.",
+    },
+    ErrorCodeInfo {
+        code: "E0113",
+        title: "arena function returning a heap value",
+        explanation: "Everything an `#[arena]` function allocates is freed when it returns, so it
+can only return numbers, booleans or nothing.
+
+    #[arena]
+    fn label(n: Int) -> Str { return \"#\" + toStr(n); } // error
+
+Drop `#[arena]`, or return a number computed from the values it builds.",
+    },
+    ErrorCodeInfo {
+        code: "E0114",
+        title: "C function with a type C can't take",
+        explanation: "An `extern \"C\"` function's parameters can be Int, Int64, Float, Bool,
+CStr or Str, and it can return any of those but Str, or nothing. An
+`export \"C\"` function takes the same types but Str, since C strings have no
+header; it takes a CStr instead. Arrays, maps, structs and the other types
+have no C equivalent.
+
+    extern \"C\" fn sum(xs: [Int]) -> Int; // error
+    export \"C\" fn greet(name: Str) { print(name); } // error
+
+Pass the values one by one, convert strings with `toCStr` and `fromCStr`, or
+write the function in doo.",
+    },
+    // Type/Operator Errors
+    ErrorCodeInfo {
+        code: "E0201",
+        title: "operator applied to mismatched types",
+        explanation: "A binary operator's operands have types it doesn't combine.
+
+    let n = 1 + \"2\"; // error: expected Int, found String
+
+Convert one operand (`1 + toInt(\"2\")`, `toStr(1) + \"2\"`).",
+    },
+    ErrorCodeInfo {
+        code: "E0202",
+        title: "type of an empty collection can't be inferred",
+        explanation: "An empty array or map literal gives no element type.
+
+    let names = []; // error
+
+Annotate the variable: `let names: [Str] = [];`.",
+    },
+    ErrorCodeInfo {
+        code: "E0203",
+        title: "condition isn't a Bool",
+        explanation: "The condition of an `if` or loop must be a Bool; numbers aren't truthy.
+
+    let n = 3;
+    if n { print(n); } // error: expected Bool, found Int
+
+Compare explicitly: `if n != 0 { ... }`.",
+    },
+    ErrorCodeInfo {
+        code: "E0204",
+        title: "type of null can't be inferred",
+        explanation: "A bare `null` gives no type for the variable it is stored in.
+
+    let x = null; // error
+
+Annotate the optional type: `let x: Int? = null;`.",
+    },
+    ErrorCodeInfo {
+        code: "E0205",
+        title: "if and else branches have different types",
+        explanation: "Both branches of an `if` used as a value must have the same type.
+
+    let label = if ok { \"yes\" } else { 0 }; // error
+
+Make both branches produce the same type.",
+    },
+    // Print
+    ErrorCodeInfo {
+        code: "E0301",
+        title: "value can't be printed",
+        explanation:
+            "`print` takes numbers, strings, Bools and collections of them; this value's type
+has no printed form.
+
+    let v = Shape::Empty;
+    print(v); // error
+
+Print its fields, or match on it and print a description.",
+    },
+    ErrorCodeInfo {
+        code: "E0302",
+        title: "construct not allowed here",
+        explanation: "A statement or expression appears where the language doesn't allow it; the
+message says what was expected instead.
+
+    let r = Ok(1); // error: 'Ok(..)' where a Result type is expected
+
+Here, annotate the Result type: `let r: Result<Int, Str> = Ok(1);`.",
+    },
+    // For
+    ErrorCodeInfo {
+        code: "E0401",
+        title: "invalid for-loop iterable",
+        explanation: "A `for` loop iterates over a value it can't loop over.
+
+    for c in 42 { print(c); } // error
+
+Loop over a range (`0..42`), an array or a map.",
+    },
+    ErrorCodeInfo {
+        code: "E0402",
+        title: "array loop with a tuple pattern",
+        explanation: "Array elements are bound to a single name, optionally after an index.
+
+    for (a, b) in [1, 2, 3] { print(a); } // error
+
+Write `for n in nums` or `for i, n in nums`.",
+    },
+    ErrorCodeInfo {
+        code: "E0403",
+        title: "map loop without a (key, value) pattern",
+        explanation: "Looping over a map binds both the key and the value.
+
+    for name in scores { print(name); } // error
+
+Write `for (name, score) in scores`.",
+    },
+    ErrorCodeInfo {
+        code: "E0404",
+        title: "loop over a value that isn't iterable",
+        explanation: "A `for` loop iterates over a value whose type can't be looped over.
+
+    let ok = true;
+    for x in ok { print(x); } // error
+
+Loop over a range, an array or a map.",
+    },
+    ErrorCodeInfo {
+        code: "E0405",
+        title: "infinite loop with a pattern",
+        explanation: "An infinite `for { ... }` loop has nothing to bind, so it takes no pattern.
+
+    for x { break; } // error
+
+Write `for { ... }`, or loop over something with `for x in ...`.",
+    },
+    ErrorCodeInfo {
+        code: "E0406",
+        title: "range bounds of different types",
+        explanation: "Both ends of a range, and its step, must have the same numeric type.
+
+    for i in 0..10L { print(i); } // error: expected Int, found Int64
+
+Give both ends the same type.",
+    },
+    ErrorCodeInfo {
+        code: "E0407",
+        title: "break values of different types",
+        explanation: "Every `break` of a loop used as a value must carry a value of the same type.
+
+    let r = for {
+        if a { break 1; }
+        break \"no\"; // error
+    };
+
+Break with values of one type.",
+    },
+    ErrorCodeInfo {
+        code: "E0408",
+        title: "range step of zero",
+        explanation: "A step of 0 never reaches the end of the range.
+
+    for i in 0..10 step 0 { print(i); } // error
+
+Use a positive step to count up, or a negative one to count down.",
+    },
+    // Struct
+    ErrorCodeInfo {
+        code: "E0501",
+        title: "struct redeclared",
+        explanation: "Two structs (or a struct and another type) share a name.
+
+    struct Point { x: Int }
+    struct Point { x: Float } // error
+
+Rename one of them.",
+    },
+    ErrorCodeInfo {
+        code: "E0502",
+        title: "duplicate struct field",
+        explanation: "A struct declares two fields with the same name.
+
+    struct Point { x: Int, x: Int } // error
+
+Give every field its own name.",
+    },
+    ErrorCodeInfo {
+        code: "E0503",
+        title: "undeclared struct",
+        explanation: "A struct literal or type names a struct that isn't declared.
+
+    let p = Pointt { x: 1 }; // error
+
+Declare the struct, or check the spelling.",
+    },
+    ErrorCodeInfo {
+        code: "E0504",
+        title: "unknown struct field",
+        explanation: "A struct literal or field access names a field the struct doesn't have.
+
+    struct Point { x: Int }
+    let p = Point { x: 1, y: 2 }; // error: no field 'y'
+
+Use the fields the struct declares.",
+    },
+    ErrorCodeInfo {
+        code: "E0505",
+        title: "struct literal misses a field",
+        explanation: "Every field must be given when building a struct.
+
+    struct Point { x: Int, y: Int }
+    let p = Point { x: 1 }; // error: missing field 'y'
+
+Give a value for every field.",
+    },
+    ErrorCodeInfo {
+        code: "E0506",
+        title: "field access on a value without fields",
+        explanation: "`.field` is used on a value that isn't a struct.
+
+    let n = 5;
+    print(n.value); // error
+
+Only struct values have fields.",
+    },
+    ErrorCodeInfo {
+        code: "E0507",
+        title: "unknown method",
+        explanation: "A method is called that the value's type doesn't have: not a builtin method,
+not in an `impl` block, and not promised by a trait bound.
+
+    let s = \"abc\";
+    s.push(\"d\"); // error: no method 'push' on String
+
+Declare the method in an `impl` block, or use one the type has.",
+    },
+    ErrorCodeInfo {
+        code: "E0508",
+        title: "weak field of a type other than an optional struct",
+        explanation: "A `weak` field doesn't keep its value alive: it reads as null once the
+value is freed, so it must have the type of an optional struct.
+
+    struct Node { weak parent: Node } // error: not optional
+
+Write the type as optional: `weak parent: Node?`.",
+    },
+    // Enum
+    ErrorCodeInfo {
+        code: "E0601",
+        title: "enum redeclared",
+        explanation: "Two enums (or an enum and another type) share a name.
+
+    enum Color { Red }
+    enum Color { Blue } // error
+
+Rename one of them.",
+    },
+    ErrorCodeInfo {
+        code: "E0602",
+        title: "duplicate enum variant",
+        explanation: "An enum declares two variants with the same name.
+
+    enum Color { Red, Red } // error
+
+Give every variant its own name.",
+    },
+    ErrorCodeInfo {
+        code: "E0603",
+        title: "undeclared enum",
+        explanation: "An `Enum::Variant` value or pattern names an enum that isn't declared.
+
+    let c = Colour::Red; // error
+
+Declare the enum, or check the spelling.",
+    },
+    ErrorCodeInfo {
+        code: "E0604",
+        title: "unknown enum variant",
+        explanation: "An enum value or pattern names a variant the enum doesn't have.
+
+    enum Color { Red, Green }
+    let c = Color::Blue; // error
+
+Use a declared variant, or add it to the enum.",
+    },
+    ErrorCodeInfo {
+        code: "E0605",
+        title: "variant payload mismatch",
+        explanation:
+            "A payload is given to a variant without one, or missing for a variant with one.
+
+    enum Shape { Circle(Float), Empty }
+    let a = Shape::Circle;     // error: takes a payload of type Float
+    let b = Shape::Empty(1.0); // error: takes no payload
+
+Match the variant's declaration.",
+    },
+    ErrorCodeInfo {
+        code: "E0606",
+        title: "'?' on a value that isn't a Result",
+        explanation: "`value?` takes apart a `Result<T, E>`; other values have no error to return.
+
+    fn f() -> Result<Int, Str> {
+        let n = 5?; // error
+        return Ok(n);
+    }
+
+Apply `?` to a call returning a Result, or unwrap optionals with `if let` or `??`.",
+    },
+    ErrorCodeInfo {
+        code: "E0607",
+        title: "'?' in a function that can't return the error",
+        explanation:
+            "`value?` returns the value's `Err` from the enclosing function, so the function
+must return a Result with the same error type.
+
+    fn parse(s: Str) -> Result<Int, Str> { return Ok(1); }
+    fn main() {
+        let n = parse(\"1\")?; // error: main doesn't return a Result
+    }
+
+Return `Result<T, Str>` from the function, or `match` on the Result instead.",
+    },
+    // Module Import / Parse
+    ErrorCodeInfo {
+        code: "E0701",
+        title: "module not found",
+        explanation: "An `import` names a module file that doesn't exist. `import a::b::F;` reads
+`a/b.doo` relative to the project root.
+
+    import models::Usr::CreateUser; // error: no models/Usr.doo
+
+Check the path and the file name's capitalization.",
+    },
+    ErrorCodeInfo {
+        code: "E0702",
+        title: "no `main` function",
+        explanation: "A program starts running at its `main` function, and the file compiled has
+none. Modules it imports don't need one.
+
+    fn greet() { print(\"hi\"); } // error: the file has no main
+
+    fn main() { greet(); }           // add it
+
+Add `fn main()`, or compile the file that has it. A program that exports
+functions with `export \"C\"` is a library and needs no `main`.",
+    },
+    ErrorCodeInfo {
+        code: "E0703",
+        title: "parse error in an imported module",
+        explanation: "A module imported by the program has a syntax error, reported at its location
+in that module's file. Fix the syntax there.",
+    },
+    ErrorCodeInfo {
+        code: "E0704",
+        title: "circular import",
+        explanation: "Modules import each other in a cycle, such as `a` importing `b` and `b`
+importing `a`.
+
+Move what the modules share into a module that neither of them imports.",
+    },
+    ErrorCodeInfo {
+        code: "E0705",
+        title: "file to include can't be read",
+        explanation: "`includeStr` and `includeBytes` read their file when the program is compiled,
+relative to the directory of the source file they are in. The file doesn't
+exist there, can't be read, or, for `includeStr`, isn't UTF-8 text.
+
+    let page = includeStr(\"templates/page.html\"); // error: no such file
+
+Check the path, or include a file that isn't text with `includeBytes`.",
+    },
+    ErrorCodeInfo {
+        code: "E0706",
+        title: "import of a private function",
+        explanation: "Only a module's public functions, whose names start with an uppercase letter,
+can be imported. The others are private to the module: its own functions can
+call them, but nothing outside it can.
+
+    // utils.doo
+    fn helper(x: Int) -> Int { return x * 2; }
+
+    import utils::helper; // error
+
+Rename the function so it starts with an uppercase letter to make it public,
+or import a public function of the module that calls it.",
+    },
+    // Match
+    ErrorCodeInfo {
+        code: "E0801",
+        title: "match on a value that can't be matched",
+        explanation: "`match` takes Int, Int64, Str, Bool, enum and Result values.
+
+    match [1, 2] { _ => { print(0); } } // error
+
+Match on a value of one of those types.",
+    },
+    ErrorCodeInfo {
+        code: "E0802",
+        title: "pattern of the wrong type",
+        explanation: "A match arm's pattern has a different type than the matched value.
+
+    let day = 3;
+    match day {
+        \"monday\" => { print(1); } // error: expected Int, found String
+        _ => { print(0); }
+    }
+
+Write patterns of the value's type.",
+    },
+    ErrorCodeInfo {
+        code: "E0803",
+        title: "match doesn't cover every value",
+        explanation: "A match must handle every possible value: both Bools, every enum variant, or
+a final `_` arm for other types.
+
+    match day {
+        1 => { print(\"one\"); }
+    } // error: pattern _ not covered
+
+Add arms for the missing values, or a `_` arm.",
+    },
+    ErrorCodeInfo {
+        code: "E0804",
+        title: "unreachable match arm",
+        explanation: "Earlier arms already match every value this arm matches, so it never runs.
+
+    match day {
+        _ => { print(\"any\"); }
+        1 => { print(\"one\"); } // error: arm 2 is unreachable
+    }
+
+Remove the arm, or move it before the arm that covers it.",
+    },
+    ErrorCodeInfo {
+        code: "E0805",
+        title: "match arms have different types",
+        explanation: "Every arm of a match used as a value must produce the same type.
+
+    let label = match ok { true => \"yes\", false => 0 }; // error
+
+Make all arms produce the same type.",
+    },
+    // Trait
+    ErrorCodeInfo {
+        code: "E0901",
+        title: "trait redeclared",
+        explanation: "Two traits share a name.
+
+    trait Shape { fn area(self) -> Float; }
+    trait Shape { } // error
+
+Rename one of them.",
+    },
+    ErrorCodeInfo {
+        code: "E0902",
+        title: "undeclared trait",
+        explanation: "An `impl` or type parameter bound names a trait that isn't declared.
+
+    impl Drawable for Circle { } // error
+
+Declare the trait, or check the spelling.",
+    },
+    ErrorCodeInfo {
+        code: "E0903",
+        title: "duplicate trait method",
+        explanation: "A trait lists two methods with the same name.
+
+    trait Named { fn name(self) -> Str; fn name(self) -> Str; } // error
+
+List every method once.",
+    },
+    ErrorCodeInfo {
+        code: "E0904",
+        title: "impl misses a trait method",
+        explanation: "An `impl Trait for Struct` block must provide every method of the trait.
+
+    trait Shape { fn area(self) -> Float; }
+    impl Shape for Circle { } // error: missing method 'area'
+
+Add the missing methods.",
+    },
+    ErrorCodeInfo {
+        code: "E0905",
+        title: "impl method doesn't match the trait",
+        explanation:
+            "A method in an `impl Trait for Struct` block has a different signature than the
+trait's, or the trait doesn't list it.
+
+    trait Shape { fn area(self) -> Float; }
+    impl Shape for Circle {
+        fn area(self) -> Int { return 1; } // error: returns Int, not Float
+    }
+
+Copy the signature from the trait; put extra methods in a plain `impl Circle` block.",
+    },
+    ErrorCodeInfo {
+        code: "E0906",
+        title: "type doesn't implement the required trait",
+        explanation: "A generic function's type parameter is bound by a trait, and the argument's
+type has no `impl` of it.
+
+    fn describe<T: Shape>(s: T) -> Float { return s.area(); }
+    let a = describe(1); // error: Int doesn't implement Shape
+
+Pass a value whose type implements the trait, or add the `impl`.",
+    },
+    ErrorCodeInfo {
+        code: "E0907",
+        title: "type parameter can't be inferred",
+        explanation: "Type parameters are inferred from the call's arguments; one that no parameter
+type mentions has nothing to be inferred from.
+
+    fn make<T>() -> Int { return 1; }
+    let a = make(); // error
+
+Use the type parameter in a parameter's type, or drop it.",
+    },
+    // Parse
+    ErrorCodeInfo {
+        code: "E2001",
+        title: "unexpected token",
+        explanation: "The parser found a token that can't appear here; the message names what it
+expected.
+
+    let x 5; // error: expected '=', found Number
+
+Fix the syntax at the underlined token. Often the problem is a missing `;`, `)`
+or `}` just before it.",
+    },
+    ErrorCodeInfo {
+        code: "E2002",
+        title: "unexpected end of input",
+        explanation: "The file ended in the middle of a declaration or statement.
+
+    fn main() {
+        print(1);
+    // error: the `{` is never closed
+
+Close every `{`, `(` and `[`, and end statements with `;`.",
+    },
+    ErrorCodeInfo {
+        code: "E2003",
+        title: "invalid token",
+        explanation: "The source contains text that isn't part of the language: a character
+no token starts with, a name containing `_`, a string or `/*` comment that is
+never closed, an unknown escape in a string, or `0x`, `0b` or `0o` without digits.
+
+    let user_name = \"ann\"; // error: names are camelCase
+    let userName = \"ann\";  // ok
+
+Each one is reported where it starts; the file is parsed once they are fixed.",
+    },
+];
@@ -0,0 +1,428 @@
+/// Centralized diagnostics and error formatting for doo.
+/// Provides colorized output, error code extraction, and source snippet rendering.
+/// Used for both semantic and parse errors, as well as grouped reporting.
+use crate::analyzer::types::SemanticError;
+use crate::analyzer::warnings::Warning;
+use crate::output::{bold_cyan, bold_green, bold_red, bold_yellow, cyan, dim, gray};
+use crate::parser::parser::ParseError;
+use std::collections::HashMap;
+
+pub mod codes;
+
+/// Width of the token starting at 0-based character `idx` of `src_line`: a run
+/// of identifier or number characters, or a single other character.
+fn token_width(src_line: &str, idx: usize) -> usize {
+    let word = src_line
+        .chars()
+        .skip(idx)
+        .take_while(|ch| ch.is_alphanumeric() || *ch == '_')
+        .count();
+    word.max(1)
+}
+
+/// Width of the statement starting at 0-based character `idx` of `src_line`:
+/// the rest of the line without trailing whitespace.
+fn statement_width(src_line: &str, idx: usize) -> usize {
+    src_line
+        .trim_end()
+        .chars()
+        .count()
+        .saturating_sub(idx)
+        .max(1)
+}
+
+/// Width of the `len` bytes of source from 0-based character `idx` of
+/// `src_line`, cut at the end of the line: a span located on it.
+fn span_width(src_line: &str, idx: usize, len: usize) -> usize {
+    let rest: String = src_line.chars().skip(idx).collect();
+    rest.trim_end()
+        .char_indices()
+        .take_while(|(i, _)| *i < len)
+        .count()
+        .max(1)
+}
+
+/// Renders the source line `line` with a caret underline of `width` characters
+/// from column `col`, framed by a gutter as wide as the line number:
+///
+/// ```text
+///    |
+///  7 |     print(y);
+///    |     ^^^^^^^^^
+/// ```
+///
+/// Line and column are 1-based; returns None when the line isn't in `source`.
+fn render_snippet(source: &str, line: usize, col: usize, width: usize) -> Option<String> {
+    if line == 0 {
+        return None;
+    }
+    let src_line = source.lines().nth(line - 1)?;
+    let pad = " ".repeat(line.to_string().len());
+    let idx = col.saturating_sub(1);
+    // Tabs keep their width so the caret lines up under them
+    let lead: String = src_line
+        .chars()
+        .take(idx)
+        .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+        .collect();
+    Some(format!(
+        "{pad} {bar}\n{num} {bar} {src}\n{pad} {bar} {lead}{carets}",
+        pad = pad,
+        bar = gray("|"),
+        num = gray(&line.to_string()),
+        src = src_line,
+        lead = lead,
+        carets = bold_red(&"^".repeat(width)),
+    ))
+}
+
+/// Colorizes diagnostic messages for expected/found errors, unexpected tokens, etc.
+/// Used to improve readability of error output.
+fn colorize_message(msg: &str) -> String {
+    if let Some(exp_idx) = msg.find("expected ") {
+        if let Some(found_idx) = msg.find(", found ") {
+            let before = &msg[..exp_idx + 9];
+            let expected = &msg[exp_idx + 9..found_idx];
+            let found = &msg[found_idx + 8..];
+            return format!(
+                "{}{}, {}{}",
+                before,
+                bold_green(expected.trim()),
+                "found ",
+                bold_red(found.trim())
+            );
+        }
+    }
+    if msg.starts_with("Expected ") && msg.contains(", got ") {
+        let parts: Vec<&str> = msg.split(", got ").collect();
+        if parts.len() == 2 {
+            let expected = parts[0].trim_start_matches("Expected ");
+            let got = parts[1];
+            return format!(
+                "Expected {}, got {}",
+                bold_green(expected.trim()),
+                bold_red(got.trim())
+            );
+        }
+    }
+    if msg.starts_with("Unexpected token:") {
+        let tok = msg.trim_start_matches("Unexpected token:").trim();
+        return format!("Unexpected token: {}", bold_cyan(tok));
+    }
+    msg.to_string()
+}
+
+/// Colorizes quoted names (e.g., variable or function names in single quotes).
+/// Used for highlighting identifiers in error messages.
+fn colorize_quoted_names(input: &str) -> String {
+    let mut out = String::new();
+    let mut in_quote = false;
+    let mut buf = String::new();
+    for ch in input.chars() {
+        if ch == '\'' {
+            if in_quote {
+                // Close quote: append colored content then closing quote
+                out.push_str(&bold_cyan(&buf));
+                out.push('\'');
+                buf.clear();
+                in_quote = false;
+            } else {
+                in_quote = true;
+                out.push('\''); // Fix: print opening quote
+            }
+        } else if in_quote {
+            buf.push(ch);
+        } else {
+            out.push(ch);
+        }
+    }
+    if in_quote {
+        // Unbalanced quote: flush raw content and a single quote
+        out.push_str(&buf);
+        out.push('\'');
+    }
+    out
+}
+
+/// Extracts an error code from a diagnostic message (e.g., "error[E1234]: ...").
+/// Returns (code, rest of message) if found.
+fn extract_error_code(msg: &str) -> Option<(String, String)> {
+    // Try to extract error code from message like "error[E1234]:"
+    if let Some(start) = msg.find("error[") {
+        if let Some(end) = msg[start..].find("]:") {
+            let code = &msg[start..start + end + 1];
+            let rest = msg[start + end + 2..].trim_start();
+            return Some((code.to_string(), rest.to_string()));
+        }
+    }
+    None
+}
+
+/// Prints a semantic error (from the analyzer) with colorized formatting.
+pub fn print_semantic_error(err: &SemanticError) {
+    let msg = err.to_string();
+    if let Some((code, rest)) = extract_error_code(&msg) {
+        // Only color the error code part in red
+        eprintln!("{}: {}", bold_red(&code), colorize_message(&rest));
+    } else {
+        eprintln!("{}", colorize_message(&msg));
+    }
+}
+
+/// Prints a parse error (from the parser) with colorized formatting.
+pub fn print_parse_error(err: &ParseError) {
+    let code = format!("error[{}]", err.code());
+    eprintln!("{}: {}", bold_red(&code), err);
+}
+
+/// Prints a note (additional info) in yellow.
+pub fn print_note(note: &str) {
+    eprintln!("{}: {}", bold_yellow("note"), note);
+}
+
+/// Prints a parse error with source code snippet and caret.
+/// Used for errors with line/column info.
+pub fn print_parse_error_with_source(err: &ParseError, source: &str, filename: &str) {
+    let record = DiagnosticRecord::syntax(filename, err);
+    eprintln!("{}\n", record.render(Some(source)));
+}
+
+/// Escapes `s` as a JSON string literal, quotes included.
+pub fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Represents a single diagnostic (error or warning) record.
+/// Used for grouped reporting and source annotation.
+#[derive(Debug, Clone)]
+pub struct DiagnosticRecord {
+    pub filename: String,
+    /// Stable error code, explained by `doo explain`
+    pub code: &'static str,
+    pub message: String,
+    pub line: Option<usize>,
+    pub col: Option<usize>,
+    /// How many bytes from `line`:`col` the error covers, when known: the
+    /// name or operator of an expression, or a statement
+    pub len: Option<usize>,
+    pub is_parse: bool,
+    /// A lint the analyzer reported as a warning; denied lints are errors
+    pub is_warning: bool,
+    /// Why the error happened, printed as `= note: ...` under the snippet
+    pub note: Option<String>,
+    /// How to fix it, printed as `= help: ...` under the snippet
+    pub help: Option<String>,
+}
+
+impl DiagnosticRecord {
+    /// A parse error in `filename`, at the token starting at `line`:`col` if known.
+    pub fn parse(
+        filename: &str,
+        code: &'static str,
+        message: String,
+        line: Option<usize>,
+        col: Option<usize>,
+    ) -> Self {
+        DiagnosticRecord {
+            filename: filename.to_string(),
+            code,
+            message,
+            line,
+            col,
+            len: None,
+            is_parse: true,
+            is_warning: false,
+            note: None,
+            help: None,
+        }
+    }
+
+    /// The syntax error `err` in `filename`, at its token if it has one.
+    pub fn syntax(filename: &str, err: &ParseError) -> Self {
+        match err {
+            ParseError::UnexpectedTokenAt { msg, line, col } => {
+                Self::parse(filename, err.code(), msg.clone(), Some(*line), Some(*col))
+            }
+            _ => Self::parse(filename, err.code(), err.to_string(), None, None),
+        }
+    }
+
+    /// A semantic error in `filename`, at the expression or statement it
+    /// occurred in if known.
+    pub fn semantic(filename: &str, error: &SemanticError) -> Self {
+        DiagnosticRecord {
+            filename: filename.to_string(),
+            code: error.code(),
+            message: error.to_string(),
+            line: error.span().map(|span| span.line),
+            col: error.span().map(|span| span.col),
+            len: error
+                .span()
+                .map(|span| span.end - span.start)
+                .filter(|len| *len > 0),
+            is_parse: false,
+            is_warning: false,
+            note: error.note(),
+            help: error.help(),
+        }
+    }
+
+    /// A lint warning in `filename`, coded with the lint name. A lint denied
+    /// on the command line is reported as an error instead.
+    pub fn lint(filename: &str, warning: &Warning, denied: bool) -> Self {
+        let located = warning.span.line > 0;
+        DiagnosticRecord {
+            filename: filename.to_string(),
+            code: warning.lint,
+            message: warning.message.clone(),
+            line: located.then_some(warning.span.line),
+            col: located.then_some(warning.span.col),
+            len: None,
+            is_parse: false,
+            is_warning: !denied,
+            note: denied.then(|| format!("'{}' is denied on the command line", warning.lint)),
+            help: warning.help.clone(),
+        }
+    }
+
+    fn severity(&self) -> &'static str {
+        if self.is_warning {
+            "warning"
+        } else {
+            "error"
+        }
+    }
+
+    /// Renders the record: the colorized `error[E..]: message` (or
+    /// `warning[lint]: message`) header, a
+    /// `--> file:line:col` location, the source line with the token (parse
+    /// errors) or the expression or statement (semantic errors) underlined,
+    /// then note and help.
+    pub fn render(&self, source: Option<&str>) -> String {
+        let label = format!("{}[{}]", self.severity(), self.code);
+        let label = if self.is_warning {
+            bold_yellow(&label)
+        } else {
+            bold_red(&label)
+        };
+        let header = format!("{}: {}", label, colorize_message(&self.plain_message()));
+
+        let mut loc = self.filename.clone();
+        let mut snippet = None;
+        if let (Some(line), Some(col)) = (self.line, self.col) {
+            loc = format!("{}:{}:{}", self.filename, line, col);
+            snippet = source.and_then(|src| {
+                let src_line = src.lines().nth(line.checked_sub(1)?)?;
+                let idx = col.saturating_sub(1);
+                let width = match (self.is_parse, self.len) {
+                    (true, _) => token_width(src_line, idx),
+                    (false, Some(len)) => span_width(src_line, idx, len),
+                    (false, None) => statement_width(src_line, idx),
+                };
+                render_snippet(src, line, col, width)
+            });
+        }
+
+        let pad = " ".repeat(self.line.map_or(1, |line| line.to_string().len()));
+        let mut out = format!("{}\n{}{} {}", header, pad, cyan("-->"), dim(&loc));
+        if let Some(snippet) = snippet {
+            out.push('\n');
+            out.push_str(&snippet);
+        }
+        for (label, text) in [("note", &self.note), ("help", &self.help)] {
+            if let Some(text) = text {
+                out.push_str(&format!(
+                    "\n{} {} {}: {}",
+                    pad,
+                    gray("="),
+                    bold_yellow(label),
+                    colorize_quoted_names(text)
+                ));
+            }
+        }
+        out
+    }
+
+    /// The message without its `error[E..]:` prefix.
+    fn plain_message(&self) -> String {
+        match extract_error_code(&self.message) {
+            Some((_, rest)) => rest,
+            None => self.message.clone(),
+        }
+    }
+
+    /// The record as one line of JSON:
+    ///
+    /// ```text
+    /// {"code":"E0002","severity":"error","message":"...","file":"main.doo",
+    ///  "span":{"line":7,"col":5},"children":[{"severity":"help","message":"..."}]}
+    /// ```
+    ///
+    /// `span` is null when the location is unknown; notes and help are the children.
+    pub fn to_json(&self) -> String {
+        let span = match (self.line, self.col) {
+            (Some(line), Some(col)) => format!("{{\"line\":{},\"col\":{}}}", line, col),
+            _ => "null".to_string(),
+        };
+        let children: Vec<String> = [("note", &self.note), ("help", &self.help)]
+            .into_iter()
+            .filter_map(|(severity, text)| {
+                text.as_ref().map(|text| {
+                    format!(
+                        "{{\"severity\":\"{}\",\"message\":{}}}",
+                        severity,
+                        json_string(text)
+                    )
+                })
+            })
+            .collect();
+        format!(
+            "{{\"code\":\"{}\",\"severity\":\"{}\",\"message\":{},\"file\":{},\"span\":{},\"children\":[{}]}}",
+            self.code,
+            self.severity(),
+            json_string(&self.plain_message()),
+            json_string(&self.filename),
+            span,
+            children.join(",")
+        )
+    }
+}
+
+/// Prints each diagnostic as one line of JSON on stdout, for editors and CI.
+pub fn print_json(records: &[DiagnosticRecord]) {
+    for r in records {
+        println!("{}", r.to_json());
+    }
+}
+
+/// Prints grouped diagnostics by file, with colorized output and source snippets.
+/// Handles both parse and semantic errors, and annotates locations if available.
+pub fn print_grouped(records: &[DiagnosticRecord], sources: &HashMap<String, String>) {
+    let mut by_file: HashMap<&str, Vec<&DiagnosticRecord>> = HashMap::new();
+    for r in records {
+        by_file.entry(&r.filename).or_default().push(r);
+    }
+    for (file, recs) in by_file {
+        eprintln!("\n{} {}", cyan("In"), dim(file));
+        let source = sources.get(file).map(String::as_str);
+        for r in recs {
+            eprintln!("{}\n", r.render(source));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
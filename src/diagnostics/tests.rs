@@ -0,0 +1,242 @@
+#[cfg(test)]
+mod diagnostics_tests {
+    use crate::analyzer::types::{NamedError, SemanticError};
+    use crate::analyzer::warnings::{LintLevel, LintLevels, Warning};
+    use crate::diagnostics::codes::{explain, ERROR_CODES};
+    use crate::diagnostics::DiagnosticRecord;
+    use crate::output::{set_color, ColorChoice};
+    use crate::parser::ast::Span;
+    use crate::parser::ParseError;
+
+    /// The rendered text without its ANSI color codes.
+    fn plain(rendered: &str) -> String {
+        let mut out = String::new();
+        let mut chars = rendered.chars();
+        while let Some(ch) = chars.next() {
+            if ch == '\x1b' {
+                for ch in chars.by_ref() {
+                    if ch == 'm' {
+                        break;
+                    }
+                }
+            } else {
+                out.push(ch);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_semantic_error_snippet() {
+        let source = "fn main() {\n    let x = 1;\n    print(y);   \n}";
+        let error = SemanticError::UndeclaredVariable(NamedError {
+            name: "y".to_string(),
+        })
+        .at(Span {
+            line: 3,
+            col: 5,
+            ..Default::default()
+        });
+        let record = DiagnosticRecord::semantic("main.doo", &error);
+        assert_eq!(
+            plain(&record.render(Some(source))),
+            "error[E0002]: use of undeclared variable 'y'\n \
+             --> main.doo:3:5\n  \
+             |\n\
+             3 |     print(y);   \n  \
+             |     ^^^^^^^^^\n  \
+             = help: declare it first with 'let y = ...;'"
+        );
+    }
+
+    #[test]
+    fn test_semantic_error_underlines_operand() {
+        use crate::analyzer::files::MemoryFiles;
+        use crate::frontend::{front_end, FrontEndOptions};
+        use std::path::Path;
+        use std::rc::Rc;
+
+        let source = "fn main() {\n    let count = 1;\n    print(\"n\" + count);\n}";
+        let opts = FrontEndOptions {
+            check_only: true,
+            ..Default::default()
+        };
+        let (result, _) = front_end(
+            Path::new("main.doo"),
+            source,
+            &opts,
+            Rc::new(MemoryFiles::new()),
+        );
+        assert_eq!(
+            plain(&result.diagnostics[0].render(Some(source))),
+            "error[E0201]: operator type mismatch: expected String, found Int\n \
+             --> main.doo:3:17\n  \
+             |\n\
+             3 |     print(\"n\" + count);\n  \
+             |                 ^^^^^"
+        );
+    }
+
+    #[test]
+    fn test_parse_error_underlines_token() {
+        let source = "let x = 1;\n\n\n\n\n\n\n\n\nlet total 2;";
+        let record = DiagnosticRecord::parse(
+            "main.doo",
+            "E2001",
+            "Expected '=', got Number".to_string(),
+            Some(10),
+            Some(5),
+        );
+        let rendered = plain(&record.render(Some(source)));
+        assert_eq!(
+            rendered,
+            "error[E2001]: Expected '=', got Number\n  \
+             --> main.doo:10:5\n   \
+             |\n\
+             10 | let total 2;\n   \
+             |     ^^^^^"
+        );
+    }
+
+    #[test]
+    fn test_diagnostic_without_source() {
+        let error = SemanticError::UnreachableMatchArm { arm: 2 }.at(Span {
+            line: 4,
+            col: 9,
+            ..Default::default()
+        });
+        let record = DiagnosticRecord::semantic("lib.doo", &error);
+        assert_eq!(
+            plain(&record.render(None)),
+            "error[E0804]: match arm 2 is unreachable\n \
+             --> lib.doo:4:9\n  \
+             = note: earlier arms already match every value this arm matches"
+        );
+
+        // Errors without a location name only the file
+        let error = SemanticError::ZeroRangeStep;
+        let record = DiagnosticRecord::semantic("lib.doo", &error);
+        assert!(plain(&record.render(Some("fn main() { }"))).contains(" --> lib.doo\n"));
+    }
+
+    #[test]
+    fn test_error_code_registry() {
+        // Codes are unique and listed in order
+        for pair in ERROR_CODES.windows(2) {
+            assert!(pair[0].code < pair[1].code, "{}", pair[1].code);
+        }
+
+        let errors = [
+            SemanticError::UndeclaredVariable(NamedError {
+                name: "x".to_string(),
+            }),
+            SemanticError::ZeroRangeStep,
+            SemanticError::UnreachableMatchArm { arm: 1 },
+            SemanticError::UninferredTypeParam {
+                function: "f".to_string(),
+                type_param: "T".to_string(),
+            },
+            SemanticError::CircularImport { cycle: vec![] },
+        ];
+        for error in errors {
+            assert!(explain(error.code()).is_some(), "{}", error.code());
+        }
+        for error in [
+            ParseError::EndOfInput,
+            ParseError::UnexpectedToken(String::new()),
+        ] {
+            assert!(explain(error.code()).is_some(), "{}", error.code());
+        }
+
+        let info = explain("e0803").unwrap();
+        assert_eq!(info.code, "E0803");
+        assert!(info.explanation.contains("match day"));
+        assert!(explain("E9999").is_none());
+    }
+
+    #[test]
+    fn test_json_diagnostics() {
+        let error = SemanticError::UndeclaredVariable(NamedError {
+            name: "y".to_string(),
+        })
+        .at(Span {
+            line: 3,
+            col: 5,
+            ..Default::default()
+        });
+        let record = DiagnosticRecord::semantic("src/main.doo", &error);
+        assert_eq!(
+            record.to_json(),
+            "{\"code\":\"E0002\",\"severity\":\"error\",\
+             \"message\":\"use of undeclared variable 'y'\",\"file\":\"src/main.doo\",\
+             \"span\":{\"line\":3,\"col\":5},\
+             \"children\":[{\"severity\":\"help\",\"message\":\"declare it first with 'let y = ...;'\"}]}"
+        );
+
+        // Quotes and backslashes are escaped, and unknown locations are null
+        let record = DiagnosticRecord::parse(
+            "C:\\main.doo",
+            "E2002",
+            "expected \"}\"".to_string(),
+            None,
+            None,
+        );
+        assert_eq!(
+            record.to_json(),
+            "{\"code\":\"E2002\",\"severity\":\"error\",\"message\":\"expected \\\"}\\\"\",\
+             \"file\":\"C:\\\\main.doo\",\"span\":null,\"children\":[]}"
+        );
+    }
+
+    #[test]
+    fn test_lint_warnings() {
+        let warning = Warning {
+            lint: "unused",
+            message: "unused variable 'a'".to_string(),
+            help: None,
+            span: Span {
+                line: 2,
+                col: 5,
+                ..Default::default()
+            },
+        };
+        let record = DiagnosticRecord::lint("main.doo", &warning, false);
+        assert_eq!(
+            plain(&record.render(None)),
+            "warning[unused]: unused variable 'a'\n --> main.doo:2:5"
+        );
+        assert!(record.to_json().contains("\"severity\":\"warning\""));
+
+        // A denied lint is an error
+        let record = DiagnosticRecord::lint("main.doo", &warning, true);
+        assert!(plain(&record.render(None)).starts_with("error[unused]: "));
+
+        let levels = LintLevels {
+            allow: vec!["unused".to_string(), "unreachable".to_string()],
+            warn: vec!["unreachable".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(levels.level("unused"), LintLevel::Allow);
+        assert_eq!(levels.level("unreachable"), LintLevel::Warn);
+        let levels = LintLevels {
+            deny_warnings: true,
+            ..levels
+        };
+        assert_eq!(levels.level("unused"), LintLevel::Allow);
+        assert_eq!(levels.level("unreachable"), LintLevel::Deny);
+    }
+
+    #[test]
+    fn test_color_choice() {
+        let error = SemanticError::ZeroRangeStep;
+        let record = DiagnosticRecord::semantic("lib.doo", &error);
+
+        set_color(ColorChoice::Never);
+        assert!(!record.render(None).contains('\x1b'));
+        set_color(ColorChoice::Always);
+        assert!(record.render(None).contains("\x1b[1;31merror["));
+
+        assert_eq!(ColorChoice::from_name("never"), Some(ColorChoice::Never));
+        assert_eq!(ColorChoice::from_name("sometimes"), None);
+    }
+}
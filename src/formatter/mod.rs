@@ -0,0 +1,396 @@
+//! `doo fmt`: lays programs out in one canonical style. Only the whitespace
+//! between tokens changes, so a formatted program means exactly what it did:
+//! - indentation is four spaces per open brace, bracket or parenthesis, plus
+//!   one for a statement continued on the next line;
+//! - spacing within a line follows the tokens (`a + b`, `f(x)`, `-x`,
+//!   `xs[0]`, `x: Int`, `{"a": 1}`, `User { name: "A" }`);
+//! - line breaks are kept where they are, with at most one blank line in a
+//!   row and none at the start or end of a block; a block spanning lines
+//!   opens and closes on lines of its own, and `else` follows its `}`;
+//! - comments stay where they are, and trailing `//` comments on
+//!   consecutive lines line up.
+
+mod spacing;
+
+use crate::diagnostics::DiagnosticRecord;
+use crate::lexar::lexer::{lex, try_lex};
+use crate::lexar::token::TokenType;
+use crate::lexar::trivia::{lex_with_trivia, TriviaKind};
+use crate::parser::Parser;
+use spacing::{classify, is_binary_operator, space_between, Role, Tok};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const INDENT: &str = "    ";
+
+/// `input` in the canonical style. A program with syntax errors isn't
+/// formatted; its errors, in `filename`, are returned instead.
+pub fn format_source(input: &str, filename: &str) -> Result<String, Vec<DiagnosticRecord>> {
+    let tokens = try_lex(input).map_err(|errors| {
+        errors
+            .iter()
+            .map(|e| {
+                DiagnosticRecord::parse(filename, e.code(), e.message(), Some(e.line), Some(e.col))
+            })
+            .collect::<Vec<_>>()
+    })?;
+    let mut parser = Parser::new(&tokens);
+    parser.parse_program_with_recovery();
+    if !parser.errors.is_empty() {
+        return Err(parser
+            .errors
+            .iter()
+            .map(|e| DiagnosticRecord::syntax(filename, e))
+            .collect());
+    }
+
+    let output = layout(&items(input));
+    let same_tokens = lex(input)
+        .iter()
+        .map(|tok| (tok.kind, tok.value))
+        .eq(lex(&output).iter().map(|tok| (tok.kind, tok.value)));
+    assert!(same_tokens, "formatting changed the tokens of {}", filename);
+    Ok(output)
+}
+
+/// The `.doo` files `path` names: itself if it is a file, else every one in
+/// the directory and those under it, leaving out hidden ones, in order.
+pub fn source_files(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if path.is_dir() {
+        let mut entries = fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        entries.sort();
+        for entry in entries {
+            let hidden = entry
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with('.'));
+            if hidden {
+                continue;
+            }
+            if entry.is_dir() {
+                files.extend(source_files(&entry)?);
+            } else if entry.extension().is_some_and(|ext| ext == "doo") {
+                files.push(entry);
+            }
+        }
+    } else {
+        fs::metadata(path)?;
+        files.push(path.to_path_buf());
+    }
+    Ok(files)
+}
+
+/// A token or comment, with how it stands in the source.
+struct Item<'a> {
+    kind: ItemKind,
+    text: &'a str,
+    newlines: usize, // Line breaks between it and the item before
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ItemKind {
+    Token(Tok),
+    LineComment, // A shebang line too
+    BlockComment,
+}
+
+impl Item<'_> {
+    fn token(&self) -> Option<Tok> {
+        match self.kind {
+            ItemKind::Token(tok) => Some(tok),
+            _ => None,
+        }
+    }
+
+    fn is_kind(&self, kind: TokenType) -> bool {
+        self.token().is_some_and(|tok| tok.kind == kind)
+    }
+
+    fn is_closer(&self) -> bool {
+        self.token().is_some_and(|tok| {
+            matches!(
+                tok.kind,
+                TokenType::CloseParen | TokenType::CloseBracket | TokenType::CloseBrace
+            )
+        })
+    }
+}
+
+/// The tokens and comments of `input`, in order.
+fn items(input: &str) -> Vec<Item<'_>> {
+    let (tokens, trivia) = lex_with_trivia(input);
+    let roles = classify(&tokens);
+    let mut items = Vec::new();
+    let mut newlines = 0;
+    let mut trivia = trivia.iter().peekable();
+    for (tok, role) in tokens.iter().zip(roles) {
+        while let Some(piece) = trivia.next_if(|piece| piece.span.start < tok.span.start) {
+            let text = &input[piece.span.start..piece.span.end];
+            let kind = match piece.kind {
+                TriviaKind::Whitespace | TriviaKind::Skipped => {
+                    newlines += text.matches('\n').count();
+                    continue;
+                }
+                TriviaKind::LineComment | TriviaKind::Shebang => ItemKind::LineComment,
+                TriviaKind::BlockComment => ItemKind::BlockComment,
+            };
+            items.push(Item {
+                kind,
+                text: text.trim_end(),
+                newlines: std::mem::take(&mut newlines),
+            });
+        }
+        items.push(Item {
+            kind: ItemKind::Token(Tok {
+                kind: tok.kind,
+                role,
+            }),
+            text: &input[tok.span.start..tok.span.end],
+            newlines: std::mem::take(&mut newlines),
+        });
+    }
+    for piece in trivia {
+        let text = &input[piece.span.start..piece.span.end];
+        let kind = match piece.kind {
+            TriviaKind::LineComment | TriviaKind::Shebang => ItemKind::LineComment,
+            TriviaKind::BlockComment => ItemKind::BlockComment,
+            _ => {
+                newlines += text.matches('\n').count();
+                continue;
+            }
+        };
+        items.push(Item {
+            kind,
+            text: text.trim_end(),
+            newlines: std::mem::take(&mut newlines),
+        });
+    }
+    items
+}
+
+/// For each `{` and `}` of `items`, whether its braces span lines.
+fn broken_braces(items: &[Item]) -> Vec<bool> {
+    let mut broken = vec![false; items.len()];
+    // Items up to each index that start a line
+    let mut line_starts = vec![0; items.len() + 1];
+    for (i, item) in items.iter().enumerate() {
+        line_starts[i + 1] = line_starts[i] + (item.newlines > 0) as usize;
+    }
+    let mut open = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        if item.is_kind(TokenType::OpenBrace) {
+            open.push(i);
+        } else if item.is_kind(TokenType::CloseBrace) {
+            if let Some(start) = open.pop() {
+                let spans_lines = line_starts[i + 1] > line_starts[start + 1];
+                broken[start] = spans_lines;
+                broken[i] = spans_lines;
+            }
+        }
+    }
+    broken
+}
+
+/// A line of output.
+#[derive(Default)]
+struct Line {
+    indent: usize,
+    code: String,
+    comment: Option<String>, // A `//` comment after the code
+}
+
+/// An open brace, bracket or parenthesis.
+struct Open {
+    inner: usize, // The indent of the lines inside
+    close: usize, // The indent of a line starting with the closer
+    brace: bool,
+}
+
+/// Whether a statement that `last` ends a line of goes on to the next line.
+fn continues(last: Tok) -> bool {
+    !matches!(
+        last.kind,
+        TokenType::Semi
+            | TokenType::Comma
+            | TokenType::OpenBrace
+            | TokenType::CloseBrace
+            | TokenType::OpenParen
+            | TokenType::OpenBracket
+    ) && last.role != Role::AttrClose
+}
+
+/// Whether `tok` carries on the expression a block spanning lines ends, on
+/// the line of its `}`: `} else {`, `});`, `} + 1`.
+fn follows_block(tok: Tok) -> bool {
+    matches!(
+        tok.kind,
+        TokenType::Else
+            | TokenType::Comma
+            | TokenType::Semi
+            | TokenType::CloseParen
+            | TokenType::CloseBracket
+            | TokenType::CloseBrace
+            | TokenType::Dot
+            | TokenType::Question
+    ) || is_binary_operator(tok.kind)
+}
+
+/// Lays `items` out in lines.
+fn layout(items: &[Item]) -> String {
+    let broken = broken_braces(items);
+    let mut lines: Vec<Line> = Vec::new();
+    let mut line: Option<Line> = None;
+    let mut line_base = 0; // The current line's indent without continuation
+    let mut open: Vec<Open> = Vec::new();
+    let mut last: Option<Tok> = None; // The last token laid out
+    let mut previous: Option<ItemKind> = None; // The item before on the same line
+    let mut break_next = false; // After a `//` comment or a block spanning lines opens
+    let mut after_block = false; // After a block spanning lines closes
+
+    for (i, item) in items.iter().enumerate() {
+        let token = item.token();
+        let mut new_line = line.is_none() || item.newlines > 0;
+        // Only tokens move to another line: a comment after a `{` stays there
+        if let Some(tok) = token {
+            new_line |= break_next
+                || (tok.kind == TokenType::CloseBrace && broken[i])
+                || (after_block && !follows_block(tok));
+            // `} else {` and `if x {` share a line
+            let joins = match tok.kind {
+                TokenType::Else => last.is_some_and(|last| last.kind == TokenType::CloseBrace),
+                TokenType::OpenBrace => tok.role == Role::Plain && last.is_some_and(continues),
+                _ => false,
+            };
+            if joins && matches!(previous, Some(ItemKind::Token(_))) {
+                new_line = false;
+            }
+        }
+
+        if new_line {
+            lines.extend(line.take());
+            let blank = item.newlines > 1
+                && !lines.is_empty()
+                && !item.is_closer()
+                && !ends_with_opener(&lines);
+            if blank {
+                lines.push(Line::default());
+            }
+            let indent = if item.is_closer() {
+                line_base = open.last().map_or(0, |o| o.close);
+                line_base
+            } else {
+                line_base = open.last().map_or(0, |o| o.inner);
+                let in_block = open.last().is_none_or(|o| o.brace);
+                let continued = token.is_some_and(|tok| tok.kind != TokenType::OpenBrace)
+                    && in_block
+                    && last.is_some_and(continues);
+                line_base + continued as usize
+            };
+            line = Some(Line {
+                indent,
+                ..Line::default()
+            });
+            previous = None;
+        }
+        let current = line.as_mut().expect("a line is open");
+
+        // The space before the item
+        let space = match (previous, item.kind) {
+            (None, _) | (_, ItemKind::LineComment) => false,
+            (Some(ItemKind::Token(a)), ItemKind::Token(b)) => space_between(a, b),
+            (Some(ItemKind::Token(a)), ItemKind::BlockComment) => {
+                !matches!(a.kind, TokenType::OpenParen | TokenType::OpenBracket)
+            }
+            (Some(_), ItemKind::Token(b)) => !matches!(
+                b.kind,
+                TokenType::CloseParen
+                    | TokenType::CloseBracket
+                    | TokenType::Comma
+                    | TokenType::Semi
+            ),
+            (Some(_), _) => true,
+        };
+        if space {
+            current.code.push(' ');
+        }
+
+        match item.kind {
+            ItemKind::LineComment if previous.is_some() => {
+                current.comment = Some(item.text.to_string());
+            }
+            _ => current.code.push_str(item.text),
+        }
+        previous = Some(item.kind);
+
+        match token {
+            Some(tok) => {
+                match tok.kind {
+                    TokenType::OpenBrace => open.push(Open {
+                        inner: line_base + 1,
+                        close: line_base,
+                        brace: true,
+                    }),
+                    TokenType::OpenParen | TokenType::OpenBracket => open.push(Open {
+                        inner: current.indent + 1,
+                        close: current.indent,
+                        brace: false,
+                    }),
+                    TokenType::CloseParen | TokenType::CloseBracket | TokenType::CloseBrace => {
+                        open.pop();
+                    }
+                    _ => {}
+                }
+                last = Some(tok);
+                break_next = tok.kind == TokenType::OpenBrace && broken[i];
+                after_block = tok.kind == TokenType::CloseBrace && broken[i];
+            }
+            None if item.kind == ItemKind::LineComment => break_next = true,
+            None => {}
+        }
+    }
+    lines.extend(line);
+    render(&lines)
+}
+
+/// Whether the last of `lines` ends with an opening brace, bracket or
+/// parenthesis, so a blank line after it would start a block.
+fn ends_with_opener(lines: &[Line]) -> bool {
+    lines
+        .last()
+        .is_some_and(|line| line.code.ends_with(['{', '(', '[']))
+}
+
+/// `lines` as text. The `//` comments after code on consecutive lines of
+/// one indent line up, a space after the longest code.
+fn render(lines: &[Line]) -> String {
+    let width = |line: &Line| line.indent * INDENT.len() + line.code.chars().count();
+    let aligned =
+        |a: &Line, b: &Line| a.comment.is_some() && b.comment.is_some() && a.indent == b.indent;
+    let mut out = String::new();
+    let mut column = 0;
+    for (i, line) in lines.iter().enumerate() {
+        if i == 0 || !aligned(&lines[i - 1], line) {
+            let end = (i + 1..lines.len())
+                .find(|&j| !aligned(&lines[j - 1], &lines[j]))
+                .unwrap_or(lines.len());
+            column = lines[i..end].iter().map(width).max().unwrap_or(0);
+        }
+        if !line.code.is_empty() {
+            out.push_str(&INDENT.repeat(line.indent));
+            out.push_str(&line.code);
+        }
+        if let Some(comment) = &line.comment {
+            out.push_str(&" ".repeat(column + 1 - width(line)));
+            out.push_str(comment);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests;
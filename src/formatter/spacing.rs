@@ -0,0 +1,233 @@
+// Which tokens get a space between them. Most of it follows from the token
+// kinds; `classify` settles the tokens whose kind alone doesn't say, such as a
+// `-` that negates or subtracts.
+
+use crate::lexar::token::{Token, TokenType};
+
+/// What a token does where it stands, when its kind leaves the spacing open.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Role {
+    Plain,
+    Unary,        // `-x`, `!done`
+    ClosureOpen,  // The `|` starting a closure's parameters
+    ClosureClose, // The `|` ending them
+    GenericOpen,  // The `<` of `Result<Int, Str>` or `fn max<T: Ord>`
+    GenericClose, // Its `>`
+    PathSep,      // Either `:` of `::`
+    MapOpen,      // The `{` of a map literal or type: `{"a": 1}`, `{Str: Int}`
+    MapClose,     // Its `}`
+    AttrOpen,     // The `[` of `#[arena]`
+    AttrClose,    // Its `]`
+}
+
+/// A token as spacing sees it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Tok {
+    pub kind: TokenType,
+    pub role: Role,
+}
+
+/// The role of each of `tokens`, in order.
+pub(super) fn classify(tokens: &[Token]) -> Vec<Role> {
+    let mut roles = vec![Role::Plain; tokens.len()];
+    let mut generics = 0;
+    let mut in_closure_params = false;
+    let mut brackets = Vec::new(); // Whether each open `[` starts an attribute
+    let mut braces = Vec::new(); // Whether each open `{` is a map's
+    for i in 0..tokens.len() {
+        let after_operand = i > 0 && ends_operand(tokens, &roles, i - 1);
+        roles[i] = match tokens[i].kind {
+            TokenType::Bang => Role::Unary,
+            TokenType::Minus if !after_operand => Role::Unary,
+            TokenType::Or if in_closure_params => {
+                in_closure_params = false;
+                Role::ClosureClose
+            }
+            TokenType::Or if !after_operand => {
+                in_closure_params = true;
+                Role::ClosureOpen
+            }
+            TokenType::Lt if opens_generics(tokens, i) => {
+                generics += 1;
+                Role::GenericOpen
+            }
+            TokenType::Gt if generics > 0 => {
+                generics -= 1;
+                Role::GenericClose
+            }
+            TokenType::Colon if is_path_sep(tokens, i) => Role::PathSep,
+            TokenType::OpenBracket => {
+                let attribute = i > 0 && tokens[i - 1].kind == TokenType::Pound;
+                brackets.push(attribute);
+                if attribute {
+                    Role::AttrOpen
+                } else {
+                    Role::Plain
+                }
+            }
+            TokenType::CloseBracket if brackets.pop() == Some(true) => Role::AttrClose,
+            TokenType::OpenBrace => {
+                let map = opens_map(tokens, &roles, i);
+                braces.push(map);
+                if map {
+                    Role::MapOpen
+                } else {
+                    Role::Plain
+                }
+            }
+            TokenType::CloseBrace if braces.pop() == Some(true) => Role::MapClose,
+            _ => Role::Plain,
+        };
+    }
+    roles
+}
+
+/// Whether the token at `i` can end an operand, so that an operator after it
+/// is binary: `a - b` rather than `-b`.
+fn ends_operand(tokens: &[Token], roles: &[Role], i: usize) -> bool {
+    match tokens[i].kind {
+        // `step` after a range is a keyword: `for i in 10..0 step -2`
+        TokenType::Identifier if tokens[i].value == "step" => {
+            !(i > 0 && ends_operand(tokens, roles, i - 1))
+        }
+        TokenType::Identifier
+        | TokenType::Number
+        | TokenType::Float
+        | TokenType::String
+        | TokenType::Boolean
+        | TokenType::Null
+        | TokenType::Underscore
+        | TokenType::CloseParen
+        | TokenType::CloseBracket
+        | TokenType::Question => true,
+        _ => roles[i] == Role::GenericClose,
+    }
+}
+
+/// Whether the `<` at `i` opens type arguments: after `Result`, or after
+/// the name of a function being declared.
+fn opens_generics(tokens: &[Token], i: usize) -> bool {
+    i > 0
+        && tokens[i - 1].kind == TokenType::Identifier
+        && (tokens[i - 1].value == "Result" || (i > 1 && tokens[i - 2].kind == TokenType::Function))
+}
+
+/// Whether the `:` at `i` is half of a `::` path separator.
+fn is_path_sep(tokens: &[Token], i: usize) -> bool {
+    let touches = |a: &Token, b: &Token| {
+        a.kind == TokenType::Colon && b.kind == TokenType::Colon && a.span.end == b.span.start
+    };
+    (i > 0 && touches(&tokens[i - 1], &tokens[i]))
+        || tokens
+            .get(i + 1)
+            .is_some_and(|next| touches(&tokens[i], next))
+}
+
+/// Whether the `{` at `i` opens a map literal or type rather than a block or
+/// a struct: it stands where a value or type starts, and is empty or its
+/// first key is followed by a `:`.
+fn opens_map(tokens: &[Token], roles: &[Role], i: usize) -> bool {
+    let value_starts = i == 0
+        || roles[i - 1] == Role::GenericOpen
+        || matches!(
+            tokens[i - 1].kind,
+            TokenType::Eq
+                | TokenType::OpenParen
+                | TokenType::OpenBracket
+                | TokenType::OpenBrace
+                | TokenType::Comma
+                | TokenType::Arrow
+                | TokenType::FatArrow
+                | TokenType::Return
+                | TokenType::In
+                | TokenType::QuestionQuestion
+        )
+        || (tokens[i - 1].kind == TokenType::Colon && !is_path_sep(tokens, i - 1));
+    let keyed = match tokens.get(i + 1).map(|tok| tok.kind) {
+        Some(TokenType::CloseBrace) => true,
+        _ => {
+            tokens.get(i + 2).map(|tok| tok.kind) == Some(TokenType::Colon)
+                && !is_path_sep(tokens, i + 2)
+        }
+    };
+    value_starts && keyed
+}
+
+/// Whether `kind` is an operator written with a space on either side.
+pub(super) fn is_binary_operator(kind: TokenType) -> bool {
+    matches!(
+        kind,
+        TokenType::Plus
+            | TokenType::Minus
+            | TokenType::Star
+            | TokenType::Slash
+            | TokenType::Percent
+            | TokenType::Eq
+            | TokenType::PlusEq
+            | TokenType::MinusEq
+            | TokenType::StarEq
+            | TokenType::SlashEq
+            | TokenType::PercentEq
+            | TokenType::EqEq
+            | TokenType::EqEqEq
+            | TokenType::NotEq
+            | TokenType::NotEqEq
+            | TokenType::Gt
+            | TokenType::Lt
+            | TokenType::GtEq
+            | TokenType::LtEq
+            | TokenType::AndAnd
+            | TokenType::OrOr
+            | TokenType::Or
+            | TokenType::QuestionQuestion
+            | TokenType::Arrow
+            | TokenType::FatArrow
+    )
+}
+
+/// Whether `a` and `b`, next to each other on a line, have a space between.
+pub(super) fn space_between(a: Tok, b: Tok) -> bool {
+    use TokenType::*;
+
+    // Nothing after
+    if matches!(
+        a.role,
+        Role::Unary | Role::ClosureOpen | Role::GenericOpen | Role::PathSep | Role::MapOpen
+    ) || matches!(
+        a.kind,
+        OpenParen | OpenBracket | Dot | Pound | RangeExc | RangeInc
+    ) {
+        return false;
+    }
+    // Nothing before
+    if matches!(
+        b.role,
+        Role::ClosureClose
+            | Role::GenericOpen
+            | Role::GenericClose
+            | Role::PathSep
+            | Role::MapClose
+    ) || matches!(
+        b.kind,
+        CloseParen | CloseBracket | Comma | Semi | Dot | Question | Colon | RangeExc | RangeInc
+    ) {
+        return false;
+    }
+    match b.kind {
+        // A call, not a parenthesized expression: `f(x)`, `print(x)`, or a function type: `fn(Int)`
+        OpenParen => {
+            !(matches!(
+                a.kind,
+                Identifier | CloseParen | CloseBracket | Print | Function
+            ) || a.role == Role::GenericClose)
+        }
+        // An index, not an array literal: `xs[0]`, `#[arena]`
+        OpenBracket => !matches!(
+            a.kind,
+            Identifier | CloseParen | CloseBracket | String | Pound
+        ),
+        // An empty block: `{}`
+        CloseBrace => a.kind != OpenBrace,
+        _ => true,
+    }
+}
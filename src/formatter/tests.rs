@@ -0,0 +1,142 @@
+#[cfg(test)]
+mod formatter_tests {
+    use crate::formatter::{format_source, source_files};
+    use std::fs;
+    use std::path::Path;
+
+    fn format(input: &str) -> String {
+        format_source(input, "test.doo").expect("formats")
+    }
+
+    #[test]
+    fn test_spacing() {
+        assert_eq!(
+            format("fn add(a:Int,b:Int)->Int{return a+b;}"),
+            "fn add(a: Int, b: Int) -> Int { return a + b; }\n"
+        );
+        assert_eq!(
+            format("import   std :: os :: Read ;\nlet m:{Str:Int}={\"a\":1};"),
+            "import std::os::Read;\nlet m: {Str: Int} = {\"a\": 1};\n"
+        );
+        assert_eq!(
+            format("fn max<T:Shape>(a:T)->Result<Int,Str>?{return null;}"),
+            "fn max<T: Shape>(a: T) -> Result<Int, Str>? { return null; }\n"
+        );
+        // Unary and binary operators, closures and match alternatives
+        assert_eq!(
+            format("let p=Point{x:1,y:-2};let xs=[1,2].map( |x|x*-2 ).filter(|x|x>p.y);"),
+            "let p = Point { x: 1, y: -2 }; let xs = [1, 2].map(|x| x * -2).filter(|x| x > p.y);\n"
+        );
+        assert_eq!(
+            format("match n { 1|2 => { print(xs[0]); } _ => {} }"),
+            "match n { 1 | 2 => { print(xs[0]); } _ => {} }\n"
+        );
+        assert_eq!(
+            format("for i in 10 .. 0 step -2 { x=step-1; }"),
+            "for i in 10..0 step -2 { x = step - 1; }\n"
+        );
+        assert_eq!(
+            format("#[ arena ]fn f( ){ let s=Shape :: Circle(1.0); let v=parse(\"1\") ?; }"),
+            "#[arena] fn f() { let s = Shape::Circle(1.0); let v = parse(\"1\")?; }\n"
+        );
+        assert_eq!(
+            format("fn apply(f:fn (Int,Int)->Int){let g:fn ()=||run(1);}"),
+            "fn apply(f: fn(Int, Int) -> Int) { let g: fn() = || run(1); }\n"
+        );
+    }
+
+    #[test]
+    fn test_indentation() {
+        let input = "fn main() {
+  let total = 1 +
+  2;
+        if total > 2
+  {
+print(total); }
+else {
+      foo(1,
+ 2);
+  }
+}
+";
+        let expected = "fn main() {
+    let total = 1 +
+        2;
+    if total > 2 {
+        print(total);
+    } else {
+        foo(1,
+            2);
+    }
+}
+";
+        assert_eq!(format(input), expected);
+    }
+
+    #[test]
+    fn test_blank_lines() {
+        let input =
+            "\n\nfn main() {\n\n    let x = 1;\n\n\n\n    print(x);\n\n}\n\n\nfn f() {}\n\n";
+        assert_eq!(
+            format(input),
+            "fn main() {\n    let x = 1;\n\n    print(x);\n}\n\nfn f() {}\n"
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        let input = "#!/usr/bin/env doo run
+// Says hello
+fn main() { // starts here
+    let x = 42;   // Int
+    let name = \"doo\"; // Str
+    /* a block
+       comment */
+    print(x,/* inline */name);
+  // at the end
+}
+";
+        let expected = "#!/usr/bin/env doo run
+// Says hello
+fn main() { // starts here
+    let x = 42;       // Int
+    let name = \"doo\"; // Str
+    /* a block
+       comment */
+    print(x, /* inline */ name);
+    // at the end
+}
+";
+        assert_eq!(format(input), expected);
+    }
+
+    #[test]
+    fn test_syntax_errors_not_formatted() {
+        let errors = format_source("fn main() { let x = ; }", "bad.doo").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].filename, "bad.doo");
+        assert!(errors[0].is_parse);
+
+        let errors = format_source("let my_var = 1;", "bad.doo").unwrap_err();
+        assert_eq!(errors[0].code, "E2003");
+    }
+
+    #[test]
+    fn test_format_is_stable() {
+        // Every program that parses formats to a fixed point
+        let files = source_files(Path::new("tests/programs")).unwrap();
+        assert!(files.len() > 30);
+        assert!(files
+            .iter()
+            .all(|file| file.extension().is_some_and(|ext| ext == "doo")));
+        let mut formatted = 0;
+        for file in files {
+            let source = fs::read_to_string(&file).unwrap();
+            if let Ok(once) = format_source(&source, "once.doo") {
+                assert_eq!(format(&once), once, "{}", file.display());
+                formatted += 1;
+            }
+        }
+        assert!(formatted > 30);
+    }
+}
@@ -0,0 +1,321 @@
+use crate::analyzer::files::SourceFiles;
+use crate::analyzer::types::SemanticError;
+use crate::analyzer::warnings::{LintLevel, LintLevels};
+use crate::analyzer::SemanticAnalyzer;
+use crate::diagnostics::{print_grouped, print_json, DiagnosticRecord};
+use crate::lexar::lexer::try_lex;
+use crate::mir::builder::MirBuilder;
+use crate::mir::{MirProgram, PassManager};
+use crate::parser::{ast::AstNode, json::program_to_json, Parser};
+use crate::testing::TestOutcome;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Output a compile can produce besides the binary, chosen with `--emit`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Emit {
+    AstJson,      // The AST as parsed
+    TypedAstJson, // The AST once analyzed, with the types the analyzer fills in
+    Asm,          // Assembly for the target, written next to the binary as `<output>.s`
+    Header,       // A C header declaring the `export "C"` functions, as `<output>.h`
+}
+
+impl Emit {
+    /// The output named `ast-json`, `typed-ast-json`, `asm` or `header`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ast-json" => Some(Emit::AstJson),
+            "typed-ast-json" => Some(Emit::TypedAstJson),
+            "asm" => Some(Emit::Asm),
+            "header" => Some(Emit::Header),
+            _ => None,
+        }
+    }
+}
+
+/// What a compile produced: every diagnostic found, left for the caller to
+/// print (see `print_diagnostics`), and the paths of the files written.
+pub struct CompileResult {
+    pub success: bool,
+    pub error_count: usize,
+    pub warning_count: usize,
+    pub diagnostics: Vec<DiagnosticRecord>,
+    pub sources: HashMap<String, String>, // File name -> text, for the files the diagnostics are in
+    pub exe_path: Option<PathBuf>,
+    pub ll_path: Option<PathBuf>,     // With `keep_ll`
+    pub mir_path: Option<PathBuf>,    // With `keep_mir`
+    pub obj_path: Option<PathBuf>,    // With `keep_obj`
+    pub asm_path: Option<PathBuf>,    // With `Emit::Asm`
+    pub header_path: Option<PathBuf>, // With `Emit::Header`
+    pub js_path: Option<PathBuf>,     // For wasm32: the loader that runs the module in Node
+    pub exit_code: Option<i32>,       // With `interpret`: the exit code of the program
+    pub ast_json: Option<String>,     // With `Emit::AstJson`, or `Emit::TypedAstJson` once analyzed
+    pub artifact: Option<Vec<u8>>,    // With `CompileOptions::artifact`
+    pub tests: Vec<TestOutcome>,      // With `test`: how each `#[test]` function went, in order
+}
+
+impl CompileResult {
+    /// Prints the diagnostics grouped by file with source snippets on stderr,
+    /// or as JSON lines on stdout.
+    pub fn print_diagnostics(&self, json: bool) {
+        if self.diagnostics.is_empty() {
+            return;
+        }
+        if json {
+            print_json(&self.diagnostics);
+        } else {
+            print_grouped(&self.diagnostics, &self.sources);
+        }
+    }
+}
+
+/// What the front end takes from `CompileOptions`.
+#[derive(Debug, Clone, Default)]
+pub struct FrontEndOptions {
+    pub check_only: bool, // Stop once the program is analyzed, without lowering it
+    pub release: bool,    // Leave out `assert` checks
+    pub lint_levels: LintLevels,
+    pub emit: Vec<Emit>, // Only `Emit::AstJson` and `Emit::TypedAstJson` are the front end's
+    pub test: bool,      // Lower the `#[test]` functions without the program's `main`
+    pub dependencies: Vec<(String, PathBuf)>, // Each dependency's name and root module file
+}
+
+/// A program the front end lowered, for a backend to build.
+pub struct Lowered {
+    pub program: MirProgram,       // Optimized by the default passes
+    pub imported: HashSet<String>, // Functions parsed from other files than the one compiled
+    pub tests: Vec<String>,        // The `#[test]` functions of the file compiled, in order
+}
+
+/// Lexes, parses and analyzes the program text `input`, then lowers it to
+/// optimized MIR unless `check_only` is set or it has errors. `input_path`
+/// names it in diagnostics and is where imports and includes are found from,
+/// but every file is read from `files`: the front end never touches the disk
+/// itself, so it runs where there is none, such as in a browser.
+pub fn front_end(
+    input_path: &Path,
+    input: &str,
+    opts: &FrontEndOptions,
+    files: Rc<dyn SourceFiles>,
+) -> (CompileResult, Option<Lowered>) {
+    let project_root = input_path
+        .parent()
+        .map(|p| p.to_path_buf())
+        .unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
+
+    let mut diagnostics: Vec<DiagnosticRecord> = Vec::new();
+    let mut error_count = 0;
+    let mut sources = HashMap::new();
+
+    // Text that isn't made of tokens is reported on its own; the file is
+    // parsed once it lexes
+    let tokens = match try_lex(input) {
+        Ok(tokens) => tokens,
+        Err(errors) => {
+            for e in &errors {
+                diagnostics.push(DiagnosticRecord::parse(
+                    &input_path.display().to_string(),
+                    e.code(),
+                    e.message(),
+                    Some(e.line),
+                    Some(e.col),
+                ));
+            }
+            error_count += errors.len();
+            Vec::new()
+        }
+    };
+    let lexed = error_count == 0;
+    let mut parser = Parser::new(&tokens);
+
+    // Every syntax error is reported; the statements that parsed are kept
+    let AstNode::Program(mut statements) = parser.parse_program_with_recovery() else {
+        unreachable!("parse_program_with_recovery returns a Program");
+    };
+    for e in &parser.errors {
+        diagnostics.push(DiagnosticRecord::syntax(
+            &input_path.display().to_string(),
+            e,
+        ));
+        error_count += 1;
+    }
+
+    let mut ast_json = None;
+    if opts.emit.contains(&Emit::AstJson) {
+        ast_json = Some(program_to_json(&statements, &parser.statement_spans));
+    }
+
+    let mut analyzer = SemanticAnalyzer::new(Some(project_root.clone()));
+    analyzer.files = Rc::clone(&files);
+    analyzer.dependencies = opts.dependencies.iter().cloned().collect();
+    analyzer.statement_spans = std::mem::take(&mut parser.statement_spans);
+
+    // Statements missing from a program with syntax errors would only cause
+    // follow-on type errors, so it is checked once the syntax is fixed
+    if lexed && parser.errors.is_empty() {
+        if let Err(e) = analyzer.analyze_program(&mut statements) {
+            match &e {
+                SemanticError::ParseErrorInModule { file, error } => {
+                    let re = Regex::new(r"at (\d+):(\d+): (.+)").expect("Regex pattern is valid");
+                    let (line, col, msg) = if let Some(caps) = re.captures(error) {
+                        (
+                            caps.get(1).and_then(|m| m.as_str().parse().ok()),
+                            caps.get(2).and_then(|m| m.as_str().parse().ok()),
+                            caps.get(3)
+                                .map(|m| m.as_str().to_string())
+                                .unwrap_or_else(|| error.clone()),
+                        )
+                    } else {
+                        (None, None, error.clone())
+                    };
+                    diagnostics.push(DiagnosticRecord::parse(file, "E2001", msg, line, col));
+                    if !sources.contains_key(file) {
+                        if let Ok(src) = files.read_to_string(Path::new(file)) {
+                            sources.insert(file.clone(), src);
+                        }
+                    }
+                    error_count += 1;
+                }
+                _ => {
+                    diagnostics.push(DiagnosticRecord::semantic(
+                        &input_path.display().to_string(),
+                        &e,
+                    ));
+                    error_count += 1;
+                }
+            }
+        }
+
+        for error in &analyzer.collected_errors {
+            match error {
+                SemanticError::ParseErrorInModule {
+                    file,
+                    error: err_msg,
+                } => {
+                    let re = Regex::new(r"at (\d+):(\d+): (.+)").expect("Regex pattern is valid");
+                    let (line, col, msg) = if let Some(caps) = re.captures(err_msg) {
+                        (
+                            caps.get(1).and_then(|m| m.as_str().parse().ok()),
+                            caps.get(2).and_then(|m| m.as_str().parse().ok()),
+                            caps.get(3)
+                                .map(|m| m.as_str().to_string())
+                                .unwrap_or_else(|| err_msg.clone()),
+                        )
+                    } else {
+                        (None, None, err_msg.clone())
+                    };
+                    diagnostics.push(DiagnosticRecord::parse(file, "E2001", msg, line, col));
+                    if !sources.contains_key(file) {
+                        if let Ok(src) = files.read_to_string(Path::new(file)) {
+                            sources.insert(file.clone(), src);
+                        }
+                    }
+                    error_count += 1;
+                }
+                _ => {
+                    diagnostics.push(DiagnosticRecord::semantic(
+                        &input_path.display().to_string(),
+                        error,
+                    ));
+                    error_count += 1;
+                }
+            }
+        }
+
+        if error_count == 0 && opts.emit.contains(&Emit::TypedAstJson) {
+            ast_json = Some(program_to_json(&statements, &analyzer.statement_spans));
+        }
+
+        for warning in &analyzer.warnings {
+            let denied = match opts.lint_levels.level(warning.lint) {
+                LintLevel::Allow => continue,
+                LintLevel::Warn => false,
+                LintLevel::Deny => true,
+            };
+            diagnostics.push(DiagnosticRecord::lint(
+                &input_path.display().to_string(),
+                warning,
+                denied,
+            ));
+            if denied {
+                error_count += 1;
+            }
+        }
+    }
+
+    if !diagnostics.is_empty() {
+        sources.insert(input_path.display().to_string(), input.to_string());
+        for diag in &diagnostics {
+            if !sources.contains_key(&diag.filename) {
+                if let Ok(src) = files.read_to_string(Path::new(&diag.filename)) {
+                    sources.insert(diag.filename.clone(), src);
+                }
+            }
+        }
+    }
+
+    let result = CompileResult {
+        success: error_count == 0,
+        error_count,
+        warning_count: diagnostics.iter().filter(|d| d.is_warning).count(),
+        diagnostics,
+        sources,
+        exe_path: None,
+        ll_path: None,
+        mir_path: None,
+        obj_path: None,
+        asm_path: None,
+        header_path: None,
+        js_path: None,
+        exit_code: None,
+        ast_json,
+        artifact: None,
+        tests: Vec::new(),
+    };
+    if error_count > 0 || opts.check_only {
+        return (result, None);
+    }
+
+    // Imported functions were parsed from other files: debug info, which
+    // describes the main file, leaves them out
+    let imported: HashSet<String> = analyzer
+        .imported_functions
+        .iter()
+        .filter_map(|node| match node {
+            AstNode::FunctionDecl { name, .. } => Some(name.clone()),
+            _ => None,
+        })
+        .collect();
+    let tests: Vec<String> = statements
+        .iter()
+        .filter_map(|node| match node {
+            AstNode::FunctionDecl {
+                name, test: true, ..
+            } => Some(name.clone()),
+            _ => None,
+        })
+        .collect();
+    // The tests are run in place of the program
+    if opts.test {
+        statements
+            .retain(|node| !matches!(node, AstNode::FunctionDecl { name, .. } if name == "main"));
+    }
+    let mut all_nodes = analyzer.imported_functions.clone();
+    all_nodes.extend(statements);
+
+    let mut mir_builder = MirBuilder::new();
+    mir_builder.set_is_main_entry(true); // Mark this as the main entry point
+    mir_builder.set_release(opts.release);
+    mir_builder.build_program(&all_nodes);
+    mir_builder.finalize();
+    PassManager::with_default_passes().run(&mut mir_builder.program);
+
+    let lowered = Lowered {
+        program: mir_builder.program,
+        imported,
+        tests,
+    };
+    (result, Some(lowered))
+}
@@ -1,9 +1,112 @@
-use crate::lexar::token::{Token, TokenType};
+use crate::lexar::token::{Span, Token, TokenType};
 use std::collections::HashMap;
+use std::fmt;
 
+/// Text the lexer can't turn into a token, at the line and column it starts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub line: usize,
+    pub col: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexErrorKind {
+    UnknownCharacter(char),
+    InvalidEscape(String),          // The escape as written: `\q`
+    UnderscoreInIdentifier(String), // Identifiers are camelCase: `my_var`
+    MalformedNumber(String),        // A radix prefix without digits: `0x`
+    UnterminatedString,
+    UnterminatedComment,
+}
+
+impl LexError {
+    /// Stable code for this error, explained by `doo explain`.
+    pub fn code(&self) -> &'static str {
+        "E2003"
+    }
+
+    /// The error without its location.
+    pub fn message(&self) -> String {
+        match &self.kind {
+            LexErrorKind::UnknownCharacter(ch) => format!("Unknown character '{}'", ch),
+            LexErrorKind::InvalidEscape(escape) => {
+                format!("Invalid escape sequence '{}' in string literal", escape)
+            }
+            LexErrorKind::UnderscoreInIdentifier(name) => format!(
+                "Identifier '{}' contains '_'; names are camelCase, like '{}'",
+                name,
+                camel_case(name)
+            ),
+            LexErrorKind::MalformedNumber(text) => format!("Number '{}' has no digits", text),
+            LexErrorKind::UnterminatedString => {
+                "Unterminated string: '\"' is never closed".to_string()
+            }
+            LexErrorKind::UnterminatedComment => {
+                "Unterminated block comment: '/*' is never closed with '*/'".to_string()
+            }
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "lex error at {}:{}: {}",
+            self.line,
+            self.col,
+            self.message()
+        )
+    }
+}
+
+/// `my_var` as `myVar`.
+fn camel_case(name: &str) -> String {
+    let mut out = String::new();
+    let mut upper = false;
+    for ch in name.chars() {
+        if ch == '_' {
+            upper = !out.is_empty();
+        } else if upper {
+            out.extend(ch.to_uppercase());
+            upper = false;
+        } else {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+/// Splits `input` into tokens. Text that can't be lexed becomes an Unknown
+/// token for the parser to report; `try_lex` reports it as a `LexError` instead.
 pub fn lex(input: &str) -> Vec<Token<'_>> {
+    lex_with_errors(input).0
+}
+
+/// Splits `input` into tokens, or returns every error in it, in order.
+pub fn try_lex(input: &str) -> Result<Vec<Token<'_>>, Vec<LexError>> {
+    let (tokens, errors) = lex_with_errors(input);
+    if errors.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(errors)
+    }
+}
+
+fn lex_with_errors(input: &str) -> (Vec<Token<'_>>, Vec<LexError>) {
     let chars: Vec<char> = input.chars().collect();
+    // Byte offset of each char, and of the end of input
+    let offsets: Vec<usize> = input
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain(std::iter::once(input.len()))
+        .collect();
     let mut tokens: Vec<Token> = Vec::new();
+    let mut errors: Vec<LexError> = Vec::new();
+    let mut error = |kind: LexErrorKind, line: usize, col: usize| {
+        errors.push(LexError { kind, line, col });
+    };
 
     // --- Keyword Maps ---
     let mut keywords: HashMap<&str, TokenType> = HashMap::new();
@@ -16,12 +119,17 @@ pub fn lex(input: &str) -> Vec<Token<'_>> {
     keywords.insert("import", TokenType::Import);
     keywords.insert("struct", TokenType::Struct);
     keywords.insert("enum", TokenType::Enum);
+    keywords.insert("impl", TokenType::Impl);
+    keywords.insert("trait", TokenType::Trait);
+    keywords.insert("extern", TokenType::Extern);
+    keywords.insert("export", TokenType::Export);
 
     // Control flow statements
     keywords.insert("if", TokenType::If);
     keywords.insert("else", TokenType::Else);
     keywords.insert("for", TokenType::For);
     keywords.insert("in", TokenType::In);
+    keywords.insert("match", TokenType::Match);
 
     // Statement keywords
     keywords.insert("return", TokenType::Return);
@@ -32,6 +140,7 @@ pub fn lex(input: &str) -> Vec<Token<'_>> {
     // Special values and types
     keywords.insert("true", TokenType::Boolean);
     keywords.insert("false", TokenType::Boolean);
+    keywords.insert("null", TokenType::Null);
 
     // --- Operator and Punctuation Map ---
     let mut operators: HashMap<&str, TokenType> = HashMap::new();
@@ -91,6 +200,7 @@ pub fn lex(input: &str) -> Vec<Token<'_>> {
     operators.insert("#", TokenType::Pound);
     operators.insert("~", TokenType::Tilde);
     operators.insert("?", TokenType::Question);
+    operators.insert("??", TokenType::QuestionQuestion);
     operators.insert("$", TokenType::Dollar);
 
     // Special identifier
@@ -99,7 +209,21 @@ pub fn lex(input: &str) -> Vec<Token<'_>> {
     let mut i = 0;
     let mut line: usize = 1;
     let mut col: usize = 1;
+    // A shebang line (`#!/usr/bin/env doo run`) makes the file a script; skip it
+    if input.starts_with("#!") {
+        while i < chars.len() && chars[i] != '\n' {
+            i += 1;
+            col += 1;
+        }
+    }
+    // Tokens pushed since `token_start` get the span of the text lexed since
+    // then, unless they set their own
+    let mut token_start = 0;
+    let mut spanned = 0;
     while i < chars.len() {
+        fill_spans(&mut tokens[spanned..], offsets[token_start], offsets[i]);
+        spanned = tokens.len();
+        token_start = i;
         let c = chars[i];
 
         // Skip whitespace
@@ -125,23 +249,48 @@ pub fn lex(input: &str) -> Vec<Token<'_>> {
             continue;
         }
 
-        // Skip C-style multiline comments /* ... */
+        // Skip block comments /* ... */, which nest: /* a /* b */ still a */
         if c == '/' && i + 1 < chars.len() && chars[i + 1] == '*' {
+            let start = i;
+            let token_line = line;
+            let token_col = col;
             i += 2;
             col += 2;
-            // Find closing */
-            while i + 1 < chars.len() && !(chars[i] == '*' && chars[i + 1] == '/') {
-                if chars[i] == '\n' {
-                    line += 1;
-                    col = 1;
+            let mut depth = 1;
+            while i < chars.len() && depth > 0 {
+                if chars[i] == '/' && i + 1 < chars.len() && chars[i + 1] == '*' {
+                    depth += 1;
+                    i += 2;
+                    col += 2;
+                } else if chars[i] == '*' && i + 1 < chars.len() && chars[i + 1] == '/' {
+                    depth -= 1;
+                    i += 2;
+                    col += 2;
                 } else {
-                    col += 1;
+                    if chars[i] == '\n' {
+                        line += 1;
+                        col = 1;
+                    } else {
+                        col += 1;
+                    }
+                    i += 1;
                 }
-                i += 1;
             }
-            if i + 1 < chars.len() && chars[i] == '*' && chars[i + 1] == '/' {
-                i += 2;
-                col += 2;
+            // Unterminated: an Unknown `/*` token at the opening lets the parser point there
+            if depth > 0 {
+                error(LexErrorKind::UnterminatedComment, token_line, token_col);
+                tokens.push(Token {
+                    kind: TokenType::Unknown,
+                    value: "/*",
+                    line: token_line,
+                    col: token_col,
+                    span: Span {
+                        start: offsets[start],
+                        end: offsets[start + 2],
+                        line: token_line,
+                        col: token_col,
+                    },
+                });
             }
             continue;
         }
@@ -156,6 +305,7 @@ pub fn lex(input: &str) -> Vec<Token<'_>> {
                     value: Box::leak(op.into_boxed_str()),
                     line,
                     col,
+                    span: Span::default(),
                 });
                 i += 3;
                 col += 3;
@@ -170,6 +320,7 @@ pub fn lex(input: &str) -> Vec<Token<'_>> {
                     value: Box::leak(op.into_boxed_str()),
                     line,
                     col,
+                    span: Span::default(),
                 });
                 i += 2;
                 col += 2;
@@ -178,30 +329,120 @@ pub fn lex(input: &str) -> Vec<Token<'_>> {
         }
 
         // For value inside string literal
-        // Ex: "hello world"
+        // Ex: "hello world", "tab\tseparated\n"
+        // Escapes are decoded here, so the token value holds the final bytes.
         if c == '"' {
             let token_line = line;
             let token_col = col;
-            let start = i + 1; // skip opening "
-            i += 1;
+            i += 1; // skip opening "
             col += 1;
+            let mut value = String::new();
+            // First invalid escape: (raw text, line, col, char index)
+            let mut bad_escape: Option<(String, usize, usize, usize)> = None;
             while i < chars.len() && chars[i] != '"' {
-                i += 1;
-                col += 1;
+                if chars[i] != '\\' {
+                    value.push(chars[i]);
+                    i += 1;
+                    col += 1;
+                    continue;
+                }
+                let esc_col = col;
+                let (decoded, len) = decode_escape(&chars[i..]);
+                match decoded {
+                    Some(ch) => value.push(ch),
+                    None => {
+                        if bad_escape.is_none() {
+                            let raw: String = chars[i..i + len].iter().collect();
+                            bad_escape = Some((raw, line, esc_col, i));
+                        }
+                    }
+                }
+                i += len;
+                col += len;
             }
             // Only emit String token if closing quote is found
             if i < chars.len() && chars[i] == '"' {
-                let value: String = chars[start..i].iter().collect();
-                tokens.push(Token {
-                    kind: TokenType::String,
-                    value: Box::leak(value.into_boxed_str()),
-                    line: token_line,
-                    col: token_col,
-                });
+                match bad_escape {
+                    // Invalid escape: emit an Unknown token carrying the bad sequence
+                    // so the parser can point at it
+                    Some((raw, esc_line, esc_col, esc_start)) => {
+                        error(LexErrorKind::InvalidEscape(raw.clone()), esc_line, esc_col);
+                        let span = Span {
+                            start: offsets[esc_start],
+                            end: offsets[esc_start + raw.chars().count()],
+                            line: esc_line,
+                            col: esc_col,
+                        };
+                        tokens.push(Token {
+                            kind: TokenType::Unknown,
+                            value: Box::leak(raw.into_boxed_str()),
+                            line: esc_line,
+                            col: esc_col,
+                            span,
+                        })
+                    }
+                    None => tokens.push(Token {
+                        kind: TokenType::String,
+                        value: Box::leak(value.into_boxed_str()),
+                        line: token_line,
+                        col: token_col,
+                        span: Span::default(),
+                    }),
+                }
                 i += 1; // skip closing "
                 col += 1;
             }
             // If no closing quote, skip emitting String token
+            else {
+                error(LexErrorKind::UnterminatedString, token_line, token_col);
+            }
+            continue;
+        }
+
+        // Hex, binary and octal integers: 0xFF, 0b1010, 0o777 (with `_` separators)
+        if c == '0'
+            && i + 1 < chars.len()
+            && matches!(chars[i + 1], 'x' | 'X' | 'b' | 'B' | 'o' | 'O')
+        {
+            let token_line = line;
+            let token_col = col;
+            let start = i;
+            let radix = match chars[i + 1] {
+                'x' | 'X' => 16,
+                'b' | 'B' => 2,
+                _ => 8,
+            };
+            i += 2;
+            col += 2;
+            while i < chars.len()
+                && (chars[i].is_digit(radix)
+                    || (chars[i] == '_' && i + 1 < chars.len() && chars[i + 1].is_digit(radix)))
+            {
+                i += 1;
+                col += 1;
+            }
+            if i < chars.len()
+                && chars[i] == 'L'
+                && !(i + 1 < chars.len() && (chars[i + 1].is_alphanumeric() || chars[i + 1] == '_'))
+            {
+                i += 1;
+                col += 1;
+            }
+            let value: String = chars[start..i].iter().collect();
+            if !value[2..].chars().any(|ch| ch.is_digit(radix)) {
+                error(
+                    LexErrorKind::MalformedNumber(value.clone()),
+                    token_line,
+                    token_col,
+                );
+            }
+            tokens.push(Token {
+                kind: TokenType::Number,
+                value: Box::leak(value.into_boxed_str()),
+                line: token_line,
+                col: token_col,
+                span: Span::default(),
+            });
             continue;
         }
 
@@ -213,8 +454,11 @@ pub fn lex(input: &str) -> Vec<Token<'_>> {
             let mut has_dot = false;
             let mut has_exp = false;
             let mut exp_idx = 0;
-            // Integer part
-            while i < chars.len() && chars[i].is_digit(10) {
+            // Integer part (`_` is allowed between digits: 1_000_000)
+            while i < chars.len()
+                && (chars[i].is_digit(10)
+                    || (chars[i] == '_' && i + 1 < chars.len() && chars[i + 1].is_ascii_digit()))
+            {
                 i += 1;
                 col += 1;
             }
@@ -252,11 +496,21 @@ pub fn lex(input: &str) -> Vec<Token<'_>> {
                 }
                 // If exponent is not followed by digits, treat as integer/float up to 'e'
                 if exp_start == i {
-                    i = exp_idx; // rewind to before 'e'
                     col -= i - exp_idx;
+                    i = exp_idx; // rewind to before 'e'
                     has_exp = false;
                 }
             }
+            // `L` suffix marks a 64-bit integer literal (e.g. 5000000000L)
+            if !has_dot
+                && !has_exp
+                && i < chars.len()
+                && chars[i] == 'L'
+                && !(i + 1 < chars.len() && (chars[i + 1].is_alphanumeric() || chars[i + 1] == '_'))
+            {
+                i += 1;
+                col += 1;
+            }
             let value: String = chars[start..i].iter().collect();
             tokens.push(Token {
                 kind: if has_dot || has_exp {
@@ -267,6 +521,7 @@ pub fn lex(input: &str) -> Vec<Token<'_>> {
                 value: Box::leak(value.into_boxed_str()),
                 line: token_line,
                 col: token_col,
+                span: Span::default(),
             });
             continue;
         }
@@ -286,21 +541,36 @@ pub fn lex(input: &str) -> Vec<Token<'_>> {
                 .get(word.as_str())
                 .unwrap_or(&TokenType::Identifier);
 
-            // Disallow identifiers starting with underscore
-            if word.contains('_') {
+            if word == "_" {
+                // A lone `_` is the wildcard pattern
+                tokens.push(Token {
+                    kind: TokenType::Underscore,
+                    value: "_",
+                    line: token_line,
+                    col: token_col,
+                    span: Span::default(),
+                });
+            } else if word.contains('_') {
+                // Disallow identifiers containing underscores
+                error(
+                    LexErrorKind::UnderscoreInIdentifier(word.clone()),
+                    token_line,
+                    token_col,
+                );
                 tokens.push(Token {
                     kind: TokenType::Unknown,
                     value: Box::leak(word.clone().into_boxed_str()),
                     line: token_line,
                     col: token_col,
+                    span: Span::default(),
                 });
-                // Optionally: emit error here or let parser/analyzer handle centralized error
             } else {
                 tokens.push(Token {
                     kind: *kind,
                     value: Box::leak(word.into_boxed_str()),
                     line: token_line,
                     col: token_col,
+                    span: Span::default(),
                 });
             }
             continue;
@@ -321,6 +591,7 @@ pub fn lex(input: &str) -> Vec<Token<'_>> {
                         value: Box::leak(op.into_boxed_str()),
                         line: token_line,
                         col: token_col,
+                        span: Span::default(),
                     });
                     i += len;
                     col += len;
@@ -334,16 +605,69 @@ pub fn lex(input: &str) -> Vec<Token<'_>> {
         }
 
         // Unknown character: emit Unknown token
+        error(LexErrorKind::UnknownCharacter(c), line, col);
         let value: String = chars[i..i + 1].iter().collect();
         tokens.push(Token {
             kind: TokenType::Unknown,
             value: Box::leak(value.into_boxed_str()),
             line,
             col,
+            span: Span::default(),
         });
         i += 1;
         col += 1;
     }
+    let end = offsets[i.min(chars.len())];
+    fill_spans(&mut tokens[spanned..], offsets[token_start], end);
+
+    (tokens, errors)
+}
+
+/// Gives the tokens without a span yet the byte range `start..end`.
+fn fill_spans(tokens: &mut [Token], start: usize, end: usize) {
+    for tok in tokens.iter_mut().filter(|tok| tok.span.line == 0) {
+        tok.span = Span {
+            start,
+            end,
+            line: tok.line,
+            col: tok.col,
+        };
+    }
+}
 
-    return tokens;
+/// Decodes one escape sequence starting at the backslash in `chars`.
+/// Returns the decoded character (None if the escape is invalid) and the number
+/// of chars consumed. Supports \n \t \r \0 \" \\ and \u{XXXX}.
+fn decode_escape(chars: &[char]) -> (Option<char>, usize) {
+    match chars.get(1) {
+        Some('n') => (Some('\n'), 2),
+        Some('t') => (Some('\t'), 2),
+        Some('r') => (Some('\r'), 2),
+        Some('0') => (Some('\0'), 2),
+        Some('"') => (Some('"'), 2),
+        Some('\\') => (Some('\\'), 2),
+        Some('u') => {
+            if chars.get(2) != Some(&'{') {
+                return (None, 2);
+            }
+            // \u{...}: 1-6 hex digits naming a valid Unicode scalar value
+            let mut end = 3;
+            while end < chars.len() && chars[end] != '}' && chars[end] != '"' {
+                end += 1;
+            }
+            if chars.get(end) != Some(&'}') {
+                return (None, end);
+            }
+            let hex: String = chars[3..end].iter().collect();
+            let decoded = if (1..=6).contains(&hex.len()) {
+                u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32)
+            } else {
+                None
+            };
+            (decoded, end + 1)
+        }
+        // Unknown escape (or a lone backslash at end of input)
+        Some(_) => (None, 2),
+        None => (None, 1),
+    }
 }
@@ -2,5 +2,7 @@ pub mod token; // declares token.rs as a submodule
 
 pub mod lexer; // declares lexer.rs as a submodule
 
+pub mod trivia; // declares trivia.rs as a submodule
+
 #[cfg(test)]
 mod tests;
@@ -1,8 +1,9 @@
 // --- VALID TESTS ---
 #[cfg(test)]
 mod lexer_tests {
-    use crate::lexar::lexer::lex;
+    use crate::lexar::lexer::{lex, try_lex, LexErrorKind};
     use crate::lexar::token::TokenType;
+    use crate::lexar::trivia::{lex_with_trivia, TriviaKind};
 
     // =====================
     // Valid Token Tests
@@ -91,6 +92,81 @@ mod lexer_tests {
         assert_eq!(tokens[0].value, "3.14");
     }
 
+    #[test]
+    fn test_radix_literals_and_separators() {
+        let tokens = lex("0xFF 0b1010 0o777 1_000_000 0xFFFF_FFFFL");
+        let values: Vec<&str> = tokens.iter().map(|t| t.value).collect();
+        assert_eq!(
+            values,
+            vec!["0xFF", "0b1010", "0o777", "1_000_000", "0xFFFF_FFFFL"]
+        );
+        assert!(tokens.iter().all(|t| t.kind == TokenType::Number));
+
+        // A trailing separator is not part of the number
+        let tokens = lex("1_");
+        assert_eq!(tokens[0].value, "1");
+    }
+
+    #[test]
+    fn test_optional_tokens() {
+        let tokens = lex("let x: Int? = null ?? 1;");
+        let kinds: Vec<TokenType> = tokens.iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenType::Let,
+                TokenType::Identifier,
+                TokenType::Colon,
+                TokenType::Identifier,
+                TokenType::Question,
+                TokenType::Eq,
+                TokenType::Null,
+                TokenType::QuestionQuestion,
+                TokenType::Number,
+                TokenType::Semi,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_string_escape_sequences() {
+        let tokens = lex(r#""a\tb\n" "say \"hi\"" "back\\slash" "\u{48}\u{1F600}""#);
+        let values: Vec<&str> = tokens.iter().map(|t| t.value).collect();
+        assert_eq!(
+            values,
+            vec!["a\tb\n", "say \"hi\"", "back\\slash", "H\u{1F600}"]
+        );
+        assert!(tokens.iter().all(|t| t.kind == TokenType::String));
+    }
+
+    #[test]
+    fn test_invalid_string_escape() {
+        let tokens = lex(r#"let s = "ab\qc";"#);
+        assert_eq!(tokens[3].kind, TokenType::Unknown);
+        assert_eq!(tokens[3].value, "\\q");
+        assert_eq!(tokens[3].col, 12);
+        // The literal ends at its real closing quote
+        assert_eq!(tokens[4].kind, TokenType::Semi);
+
+        for input in [r#""\u{110000}""#, r#""\u{}""#, r#""\u48""#] {
+            let tokens = lex(input);
+            assert_eq!(tokens[0].kind, TokenType::Unknown, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_int64_suffix() {
+        let tokens = lex("5000000000L");
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].kind, TokenType::Number);
+        assert_eq!(tokens[0].value, "5000000000L");
+
+        // `L` followed by more identifier characters is not a suffix
+        let tokens = lex("5Lx");
+        assert_eq!(tokens[0].value, "5");
+        assert_eq!(tokens[1].kind, TokenType::Identifier);
+    }
+
     #[test]
     fn test_very_long_string() {
         let input = format!(r#"let s = "{}";"#, "a".repeat(10000));
@@ -221,6 +297,17 @@ mod lexer_tests {
         assert_eq!(tokens.len(), 14);
     }
 
+    #[test]
+    fn test_match_keyword_and_wildcard() {
+        let input = "match x { 1 | 2 => 3, _ => 4 }";
+        let tokens = lex(input);
+        assert_eq!(tokens[0].kind, TokenType::Match);
+        assert!(tokens.iter().any(|t| t.kind == TokenType::FatArrow));
+        assert!(tokens.iter().any(|t| t.kind == TokenType::Or));
+        let wildcard = tokens.iter().find(|t| t.value == "_").unwrap();
+        assert_eq!(wildcard.kind, TokenType::Underscore);
+    }
+
     #[test]
     fn test_range_operators() {
         let input = "0..10 0..=10";
@@ -668,14 +755,20 @@ mod lexer_tests {
     fn test_invalid_string_escaped_unicode() {
         let input = "let s = \"hello\\u1234world\";";
         let tokens = lex(input);
-        assert!(tokens.iter().any(|t| t.kind == TokenType::String));
+        assert!(!tokens.iter().any(|t| t.kind == TokenType::String));
+        assert!(tokens
+            .iter()
+            .any(|t| t.kind == TokenType::Unknown && t.value == "\\u"));
     }
 
     #[test]
     fn test_invalid_string_escaped_hex() {
         let input = "let s = \"hello\\x41world\";";
         let tokens = lex(input);
-        assert!(tokens.iter().any(|t| t.kind == TokenType::String));
+        assert!(!tokens.iter().any(|t| t.kind == TokenType::String));
+        assert!(tokens
+            .iter()
+            .any(|t| t.kind == TokenType::Unknown && t.value == "\\x"));
     }
 
     #[test]
@@ -689,7 +782,10 @@ mod lexer_tests {
     fn test_invalid_string_escaped_bell() {
         let input = "let s = \"hello\\aworld\";";
         let tokens = lex(input);
-        assert!(tokens.iter().any(|t| t.kind == TokenType::String));
+        assert!(!tokens.iter().any(|t| t.kind == TokenType::String));
+        assert!(tokens
+            .iter()
+            .any(|t| t.kind == TokenType::Unknown && t.value == "\\a"));
     }
 
     #[test]
@@ -749,7 +845,8 @@ mod lexer_tests {
 
     #[test]
     fn test_keywords() {
-        let input = "let mut fn if else for in return break continue struct enum import print";
+        let input =
+            "let mut fn if else for in return break continue struct enum import print impl trait";
         let tokens = lex(input);
         assert_eq!(tokens[0].kind, TokenType::Let);
         assert_eq!(tokens[1].kind, TokenType::Mut);
@@ -765,6 +862,8 @@ mod lexer_tests {
         assert_eq!(tokens[11].kind, TokenType::Enum);
         assert_eq!(tokens[12].kind, TokenType::Import);
         assert_eq!(tokens[13].kind, TokenType::Print);
+        assert_eq!(tokens[14].kind, TokenType::Impl);
+        assert_eq!(tokens[15].kind, TokenType::Trait);
     }
 
     #[test]
@@ -909,4 +1008,148 @@ mod lexer_tests {
             "Lexer should not produce String token for unterminated string"
         );
     }
+
+    #[test]
+    fn test_nested_block_comments() {
+        let input = "let /* outer /* inner */ still a comment */ x = 1;\n/* a\n * b */ let y = 2;";
+        let tokens = lex(input);
+        let kinds: Vec<TokenType> = tokens.iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds[..5],
+            [
+                TokenType::Let,
+                TokenType::Identifier,
+                TokenType::Eq,
+                TokenType::Number,
+                TokenType::Semi
+            ]
+        );
+        assert_eq!(tokens[1].value, "x");
+        // Lines and columns after a comment are unchanged by it
+        assert_eq!((tokens[5].line, tokens[5].col), (3, 9));
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let input = "let x = 1;\n  /* one /* two */ never closed";
+        let tokens = lex(input);
+        let last = tokens.last().unwrap();
+        assert_eq!(last.kind, TokenType::Unknown);
+        assert_eq!(last.value, "/*");
+        assert_eq!((last.line, last.col), (2, 3));
+    }
+
+    #[test]
+    fn test_try_lex_errors() {
+        assert!(try_lex("let x = 0xFF + 1; // fine").is_ok());
+
+        let input = "let my_var = 1 @ 2;\nlet s = \"a\\qb\";\nlet h = 0x;\nlet t = \"open";
+        let errors = try_lex(input).unwrap_err();
+        let found: Vec<(LexErrorKind, usize, usize)> = errors
+            .iter()
+            .map(|e| (e.kind.clone(), e.line, e.col))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    LexErrorKind::UnderscoreInIdentifier("my_var".to_string()),
+                    1,
+                    5
+                ),
+                (LexErrorKind::UnknownCharacter('@'), 1, 16),
+                (LexErrorKind::InvalidEscape("\\q".to_string()), 2, 11),
+                (LexErrorKind::MalformedNumber("0x".to_string()), 3, 9),
+                (LexErrorKind::UnterminatedString, 4, 9),
+            ]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "lex error at 1:5: Identifier 'my_var' contains '_'; names are camelCase, like 'myVar'"
+        );
+
+        // `lex` still returns the tokens, with Unknown ones for the parser to report
+        assert!(lex(input).iter().any(|t| t.kind == TokenType::Unknown));
+    }
+
+    #[test]
+    fn test_token_spans() {
+        // Spans are byte offsets, so text after a multi-byte char still lines up
+        let input = "let café = \"ü\" ..= 10.5; /* ok */ x";
+        let tokens = lex(input);
+        let texts: Vec<&str> = tokens
+            .iter()
+            .map(|t| &input[t.span.start..t.span.end])
+            .collect();
+        assert_eq!(
+            texts,
+            vec!["let", "café", "=", "\"ü\"", "..=", "10.5", ";", "x"]
+        );
+        assert!(tokens
+            .iter()
+            .all(|t| (t.span.line, t.span.col) == (t.line, t.col)));
+
+        // Unknown tokens made for errors span the bad text itself
+        let input = "let s = \"a\\qb\";\n/* open";
+        let tokens = lex(input);
+        let texts: Vec<&str> = tokens
+            .iter()
+            .filter(|t| t.kind == TokenType::Unknown)
+            .map(|t| &input[t.span.start..t.span.end])
+            .collect();
+        assert_eq!(texts, vec!["\\q", "/*"]);
+    }
+
+    #[test]
+    fn test_shebang() {
+        let tokens = lex("#!/usr/bin/env doo run\nlet x = 1;");
+        assert_eq!(tokens[0].kind, TokenType::Let);
+        assert_eq!((tokens[0].line, tokens[0].col), (2, 1));
+        assert_eq!(tokens[0].span.start, 23);
+
+        // Only on the first line; elsewhere `#` is still a token
+        let tokens = lex("let x = 1;\n#!");
+        assert_eq!(tokens[5].kind, TokenType::Pound);
+    }
+
+    #[test]
+    fn test_trivia_round_trip() {
+        let input = "#!/usr/bin/env doo run\n// add\nfn add(a: Int) -> Int {\n\t/* a /* b */ */ return a + 1; // done\n}\n";
+        let (tokens, trivia) = lex_with_trivia(input);
+
+        // Tokens and trivia in offset order give back the source exactly
+        let mut pieces: Vec<(usize, usize)> = tokens
+            .iter()
+            .map(|t| (t.span.start, t.span.end))
+            .chain(trivia.iter().map(|t| (t.span.start, t.span.end)))
+            .collect();
+        pieces.sort();
+        let rebuilt: String = pieces
+            .iter()
+            .map(|&(start, end)| &input[start..end])
+            .collect();
+        assert_eq!(rebuilt, input);
+
+        let comments: Vec<(TriviaKind, &str, usize, usize)> = trivia
+            .iter()
+            .filter(|t| t.kind != TriviaKind::Whitespace)
+            .map(|t| {
+                (
+                    t.kind,
+                    &input[t.span.start..t.span.end],
+                    t.span.line,
+                    t.span.col,
+                )
+            })
+            .collect();
+        assert_eq!(
+            comments,
+            vec![
+                (TriviaKind::Shebang, "#!/usr/bin/env doo run", 1, 1),
+                (TriviaKind::LineComment, "// add", 2, 1),
+                (TriviaKind::BlockComment, "/* a /* b */ */", 4, 2),
+                (TriviaKind::LineComment, "// done", 4, 32),
+            ]
+        );
+    }
 }
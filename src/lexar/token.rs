@@ -9,6 +9,10 @@ pub enum TokenType {
     Import,   // import
     Struct,   // struct
     Enum,     // enum
+    Impl,     // impl
+    Trait,    // trait
+    Extern,   // extern
+    Export,   // export
     If,       // if
     Else,     // else
     For,      // for
@@ -16,7 +20,9 @@ pub enum TokenType {
     Return,   // return
     Break,    // break
     Continue, // continue
+    Match,    // match
     Print,    // print
+    Null,     // null
 
     // --- Literals ---
     Number,
@@ -65,23 +71,47 @@ pub enum TokenType {
     FatArrow, // =>
 
     // --- Delimiters & Punctuation ---
-    OpenParen,    // (
-    CloseParen,   // )
-    OpenBrace,    // {
-    CloseBrace,   // }
-    OpenBracket,  // [
-    CloseBracket, // ]
-    Comma,        // ,
-    Semi,         // ;
-    Dot,          // .
-    RangeInc,     // ..=
-    RangeExc,     // ..
-    Colon,        // :
-    Pound,        // #
-    Tilde,        // ~
-    Question,     // ?
-    Dollar,       // $
-    Underscore,   // _
+    OpenParen,        // (
+    CloseParen,       // )
+    OpenBrace,        // {
+    CloseBrace,       // }
+    OpenBracket,      // [
+    CloseBracket,     // ]
+    Comma,            // ,
+    Semi,             // ;
+    Dot,              // .
+    RangeInc,         // ..=
+    RangeExc,         // ..
+    Colon,            // :
+    Pound,            // #
+    Tilde,            // ~
+    Question,         // ?
+    QuestionQuestion, // ??
+    Dollar,           // $
+    Underscore,       // _
+}
+
+/// A range of source text: byte offsets `start..end` (end exclusive), and the
+/// 1-based line and column `start` is at. Tokens carry one, the parser keeps
+/// them for statements and for the names and operators of expressions, and
+/// MIR for statements. The default span (line 0) marks code the compiler
+/// made up.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Span {
+    /// The span from the start of this one to the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end: other.end.max(self.end),
+            ..self
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -90,4 +120,5 @@ pub struct Token<'a> {
     pub value: &'a str,
     pub line: usize,
     pub col: usize,
+    pub span: Span,
 }
@@ -0,0 +1,129 @@
+// Lossless lexing: the text `lex` skips between tokens (whitespace, comments,
+// a shebang line), kept in a side table so a formatter or refactoring tool can
+// put the source back together exactly.
+
+use crate::lexar::lexer::lex;
+use crate::lexar::token::{Span, Token};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriviaKind {
+    Whitespace,
+    LineComment,  // `// ...`, without the newline ending it
+    BlockComment, // `/* ... */`, nested ones included
+    Shebang,      // `#!...` on the first line
+    Skipped,      // Text that is neither a token nor trivia, such as an unterminated string
+}
+
+/// A piece of source between tokens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub span: Span,
+}
+
+/// Lexes `input` like `lex`, also returning the trivia between the tokens in
+/// source order. Together their spans cover the whole input without overlap.
+pub fn lex_with_trivia(input: &str) -> (Vec<Token<'_>>, Vec<Trivia>) {
+    let tokens = lex(input);
+    let mut trivia = Vec::new();
+    let mut cursor = Cursor {
+        offset: 0,
+        line: 1,
+        col: 1,
+    };
+    let token_spans = tokens.iter().map(|tok| tok.span);
+    for span in token_spans.chain(std::iter::once(Span {
+        start: input.len(),
+        end: input.len(),
+        ..Default::default()
+    })) {
+        if span.start < cursor.offset {
+            continue;
+        }
+        split_trivia(input, span.start, &mut cursor, &mut trivia);
+        cursor.advance(input, span.end);
+    }
+    (tokens, trivia)
+}
+
+/// Where the scan is: a byte offset and the 1-based line and column there.
+struct Cursor {
+    offset: usize,
+    line: usize,
+    col: usize,
+}
+
+impl Cursor {
+    /// Moves to byte offset `end`, counting lines and columns like the lexer.
+    fn advance(&mut self, input: &str, end: usize) {
+        for ch in input[self.offset..end].chars() {
+            if ch == '\n' {
+                self.line += 1;
+                self.col = 1;
+            } else {
+                self.col += 1;
+            }
+        }
+        self.offset = end;
+    }
+}
+
+/// Splits the text from the cursor up to `end` into trivia.
+fn split_trivia(input: &str, end: usize, cursor: &mut Cursor, trivia: &mut Vec<Trivia>) {
+    while cursor.offset < end {
+        let rest = &input[cursor.offset..end];
+        let (kind, len) = if rest.starts_with("#!") && cursor.offset == 0 {
+            (TriviaKind::Shebang, rest.find('\n').unwrap_or(rest.len()))
+        } else if rest.starts_with("//") {
+            (
+                TriviaKind::LineComment,
+                rest.find('\n').unwrap_or(rest.len()),
+            )
+        } else if rest.starts_with("/*") {
+            (TriviaKind::BlockComment, block_comment_len(rest))
+        } else if rest.starts_with(char::is_whitespace) {
+            let len = rest
+                .find(|ch: char| !ch.is_whitespace())
+                .unwrap_or(rest.len());
+            (TriviaKind::Whitespace, len)
+        } else {
+            let len = rest.chars().next().map_or(1, char::len_utf8);
+            (TriviaKind::Skipped, len)
+        };
+        let (line, col) = (cursor.line, cursor.col);
+        let start = cursor.offset;
+        cursor.advance(input, start + len);
+        trivia.push(Trivia {
+            kind,
+            span: Span {
+                start,
+                end: start + len,
+                line,
+                col,
+            },
+        });
+    }
+}
+
+/// Length in bytes of the block comment `text` starts with, up to the end of
+/// `text` if it is never closed.
+fn block_comment_len(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i..].starts_with(b"/*") {
+            depth += 1;
+            i += 2;
+        } else if bytes[i..].starts_with(b"*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    bytes.len()
+}
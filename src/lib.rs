@@ -2,18 +2,34 @@
 // Exports all compiler modules for testing and external use
 
 pub mod analyzer;
+#[cfg(feature = "llvm")]
+pub mod capi;
+#[cfg(feature = "llvm")]
 pub mod codegen;
+#[cfg(feature = "llvm")]
 pub mod compiler;
+#[cfg(feature = "cranelift")]
+pub mod cranelift;
 pub mod diagnostics;
+pub mod formatter;
+pub mod frontend;
 pub mod lexar;
 pub mod mir;
+pub mod output;
 pub mod parser;
+#[cfg(feature = "playground")]
+pub mod playground;
+pub mod project;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod testing;
 
 // Re-export commonly used types
 pub use analyzer::SemanticAnalyzer;
+#[cfg(feature = "llvm")]
 pub use codegen::core::CodeGen;
 pub use lexar::lexer::lex;
-pub use lexar::token::{Token, TokenType};
+pub use lexar::token::{Span, Token, TokenType};
 pub use mir::builder::MirBuilder;
 pub use parser::ast::AstNode;
 pub use parser::Parser;
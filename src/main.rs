@@ -4,12 +4,14 @@ use clap::Parser;
 use cli::{run_cli, Cli};
 
 use doo::compiler::{compile_project, CompileOptions};
+use doo::output::{bold_red, set_color, ColorChoice};
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
 
 fn main() {
     // If no subcommand is provided, default to dev-mode compilation and run (for cargo run)
     let cli = Cli::parse();
+    set_color(ColorChoice::from_name(&cli.color).unwrap_or(ColorChoice::Auto));
 
     if cli.command.is_none() {
         // Dev mode: compile and run the project as in the old workflow
@@ -20,12 +22,30 @@ fn main() {
             print_ast: true,
             print_mir: true,
             keep_ll: true,
+            keep_mir: false,
             keep_obj: false,
             check_only: false,
+            interpret: false,
+            backend: Default::default(),
+            release: false,
+            debug: false,
+            opt_level: None,
+            lto: false,
+            target: None,
+            overflow_checks: false,
+            atomic_rc: false,
+            parallel_codegen: false,
+            lint_levels: Default::default(),
+            emit: Vec::new(),
+            link: Default::default(),
+            artifact: None,
+            test: false,
+            dependencies: Vec::new(),
         };
 
         match compile_project(opts) {
             Ok(result) => {
+                result.print_diagnostics(false);
                 if result.success {
                     if let Some(exe_path) = result.exe_path {
                         let status = Command::new(&exe_path)
@@ -38,7 +58,11 @@ fn main() {
                     }
                     std::process::exit(0);
                 } else {
-                    eprintln!("✗ Compilation failed with {} error(s)", result.error_count);
+                    eprintln!(
+                        "{} with {} error(s)",
+                        bold_red("✗ Compilation failed"),
+                        result.error_count
+                    );
                     std::process::exit(1);
                 }
             }
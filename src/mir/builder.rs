@@ -1,9 +1,9 @@
 use crate::mir::declarations::{build_function_decl, build_let_decl, build_nested_collection};
 use crate::mir::{
     expresssions::build_expression, statements::build_statement, MirBlock, MirFunction, MirInstr,
-    MirProgram,
+    MirProgram, MirType,
 };
-use crate::parser::ast::{AstNode, Pattern};
+use crate::parser::ast::{AstNode, Pattern, TypeNode};
 use std::collections::HashSet;
 use std::mem::discriminant;
 
@@ -17,6 +17,14 @@ pub struct MirBuilder {
     pub loop_stack: Vec<LoopContext>, // Stack for nested loop break/continue targets
     pub rc_tracked_vars: Vec<Vec<String>>, // Stack of scopes with reference-counted variables
     pub mir_symbol_table: std::collections::HashMap<String, crate::parser::ast::TypeNode>, // Track variable types for MIR
+    pub struct_layouts: std::collections::HashMap<String, Vec<(String, TypeNode)>>, // Struct name -> declared fields in order
+    pub enum_layouts: std::collections::HashMap<String, Vec<(String, Option<TypeNode>)>>, // Enum name -> declared variants in order
+    pub call_returns: std::collections::HashMap<String, TypeNode>, // Function name -> type it returns
+    pub intrinsics: std::collections::HashMap<String, String>, // Extern function name -> LLVM intrinsic its calls go to
+    pub release: bool,          // Release build: `assert` calls are left out
+    pub closure_scope: String,  // Function whose closures are being numbered
+    pub closure_counter: usize, // Closures of that function so far
+    pub pending_closures: Vec<AstNode>, // Closure bodies to build as functions of their own
 }
 
 /// Context for tracking loop break/continue targets
@@ -25,6 +33,7 @@ pub struct MirBuilder {
 pub struct LoopContext {
     pub break_target: String,    // Where break jumps to
     pub continue_target: String, // Where continue jumps to
+    pub result: Option<String>,  // Variable receiving break values, for loops used as values
 }
 
 impl MirBuilder {
@@ -33,14 +42,26 @@ impl MirBuilder {
         Self {
             program: MirProgram {
                 functions: vec![],
+                externs: vec![],
                 globals: vec![],
                 is_main_entry: true, // Default to true; can be set to false for imported modules
+                structs: std::collections::HashMap::new(),
+                enums: std::collections::HashMap::new(),
+                weak_fields: std::collections::HashMap::new(),
             },
             tmp_counter: 1,
             block_counter: 0,
             loop_stack: vec![],
             rc_tracked_vars: vec![vec![]],
             mir_symbol_table: std::collections::HashMap::new(),
+            struct_layouts: std::collections::HashMap::new(),
+            enum_layouts: std::collections::HashMap::new(),
+            call_returns: std::collections::HashMap::new(),
+            intrinsics: std::collections::HashMap::new(),
+            release: false,
+            closure_scope: String::new(),
+            closure_counter: 0,
+            pending_closures: vec![],
         }
     }
 
@@ -50,6 +71,11 @@ impl MirBuilder {
         self.program.is_main_entry = is_main;
     }
 
+    /// Builds for release: `assert` calls are dropped without evaluating them.
+    pub fn set_release(&mut self, release: bool) {
+        self.release = release;
+    }
+
     pub fn next_tmp(&mut self) -> String {
         let tmp = format!("%{}", self.tmp_counter);
         self.tmp_counter += 1;
@@ -69,6 +95,21 @@ impl MirBuilder {
         self.loop_stack.push(LoopContext {
             break_target,
             continue_target,
+            result: None,
+        });
+    }
+
+    /// Enter a loop used as a value; `break value` assigns to `result` before jumping.
+    pub fn enter_value_loop(
+        &mut self,
+        break_target: String,
+        continue_target: String,
+        result: String,
+    ) {
+        self.loop_stack.push(LoopContext {
+            break_target,
+            continue_target,
+            result: Some(result),
         });
     }
 
@@ -115,10 +156,170 @@ impl MirBuilder {
             .any(|scope| scope.contains(&var.to_string()))
     }
 
+    /// Records the field layout of a struct declaration. Fields keep their
+    /// declaration order, which is also the order of the LLVM struct fields.
+    pub fn register_struct(
+        &mut self,
+        name: &str,
+        fields: &[(String, TypeNode)],
+        weak_fields: &[String],
+    ) {
+        self.struct_layouts
+            .insert(name.to_string(), fields.to_vec());
+        let field_types = fields
+            .iter()
+            .map(|(field, ty)| (field.clone(), self.mir_type(ty)))
+            .collect();
+        self.program.structs.insert(name.to_string(), field_types);
+        if !weak_fields.is_empty() {
+            self.program
+                .weak_fields
+                .insert(name.to_string(), weak_fields.to_vec());
+        }
+    }
+
+    /// The value type of struct `name`, as the analyzer builds it.
+    pub fn struct_type(&self, name: &str) -> Option<TypeNode> {
+        let fields = self.struct_layouts.get(name)?;
+        Some(TypeNode::Struct(
+            name.to_string(),
+            fields.iter().cloned().collect(),
+        ))
+    }
+
+    /// Records the variants of an enum declaration. A variant's position is
+    /// its discriminant in codegen.
+    pub fn register_enum(&mut self, name: &str, variants: &[(String, Option<TypeNode>)]) {
+        self.enum_layouts
+            .insert(name.to_string(), variants.to_vec());
+        let variant_types = variants
+            .iter()
+            .map(|(variant, payload)| {
+                let payload = payload.as_ref().map(|ty| self.mir_type(ty));
+                (variant.clone(), payload)
+            })
+            .collect();
+        self.program.enums.insert(name.to_string(), variant_types);
+    }
+
+    /// The value type of enum `name`, as the analyzer builds it.
+    pub fn enum_type(&self, name: &str) -> Option<TypeNode> {
+        let variants = self.enum_layouts.get(name)?;
+        Some(TypeNode::Enum(
+            name.to_string(),
+            variants.iter().cloned().collect(),
+        ))
+    }
+
+    /// Payload type of `enum_name::variant`, with struct and enum names resolved.
+    pub fn variant_payload_type(&self, enum_name: &str, variant: &str) -> Option<TypeNode> {
+        let (_, payload) = self
+            .enum_layouts
+            .get(enum_name)?
+            .iter()
+            .find(|(v, _)| v == variant)?;
+        payload.as_ref().map(|ty| self.resolve_struct_ref(ty))
+    }
+
+    /// Records what the functions in `nodes` (and the functions nested in them,
+    /// methods and C functions) return, so their call results can be used as such.
+    fn register_call_returns(&mut self, nodes: &[AstNode]) {
+        for node in nodes {
+            match node {
+                AstNode::FunctionDecl {
+                    name,
+                    return_type,
+                    body,
+                    ..
+                } => {
+                    if let Some(ty) = return_type {
+                        self.call_returns.insert(name.clone(), ty.clone());
+                    }
+                    self.register_call_returns(body);
+                }
+                AstNode::ExternFunctionDecl {
+                    name,
+                    return_type: Some(ty),
+                    ..
+                } => {
+                    self.call_returns.insert(name.clone(), ty.clone());
+                }
+                AstNode::ImplBlock { methods, .. } => self.register_call_returns(methods),
+                _ => {}
+            }
+        }
+    }
+
+    /// Resolves a struct field or enum payload declared with a struct or enum name to that type.
+    pub fn resolve_struct_ref(&self, ty: &TypeNode) -> TypeNode {
+        match ty {
+            TypeNode::TypeRef(name) => self
+                .struct_type(name)
+                .or_else(|| self.enum_type(name))
+                .unwrap_or_else(|| ty.clone()),
+            _ => ty.clone(),
+        }
+    }
+
+    /// The MIR type of `ty`, a struct or enum named by a bare type reference included.
+    pub fn mir_type(&self, ty: &TypeNode) -> MirType {
+        MirType::from(&self.resolve_struct_ref(ty))
+    }
+
+    /// The MIR type of the value or variable `name`, Void when it isn't known.
+    pub fn value_type(&self, name: &str) -> MirType {
+        self.mir_symbol_table
+            .get(name)
+            .map_or(MirType::Void, |ty| self.mir_type(ty))
+    }
+
+    /// The type `print` is told about for `value`: arrays and maps are
+    /// formatted at run time by walking their elements, so codegen needs their
+    /// element types. None for everything else, and for values of unknown type.
+    pub fn print_type(&self, value: &str) -> Option<MirType> {
+        let ty = self.mir_type(self.mir_symbol_table.get(value)?);
+        matches!(ty, MirType::Array(_) | MirType::Map(..)).then_some(ty)
+    }
+
     /// Build the MIR program from a list of AST nodes.
     /// This is the main entry point for converting parsed code into MIR.
     /// Handles functions, globals, structs, enums, assignments, prints, loops, conditionals, and expressions.
     pub fn build_program(&mut self, nodes: &[AstNode]) {
+        // Struct and enum layouts first: functions declared earlier may use them.
+        // Enum names are known before struct fields are resolved, and enums are
+        // registered once all the structs their payloads may name are known.
+        for node in nodes {
+            if let AstNode::EnumDecl { name, variants } = node {
+                self.enum_layouts.insert(name.clone(), variants.clone());
+            }
+        }
+        for node in nodes {
+            if let AstNode::StructDecl {
+                name,
+                fields,
+                weak_fields,
+            } = node
+            {
+                self.register_struct(name, fields, weak_fields);
+            }
+        }
+        for node in nodes {
+            if let AstNode::EnumDecl { name, variants } = node {
+                self.register_enum(name, variants);
+            }
+        }
+        self.register_call_returns(nodes);
+        for node in nodes {
+            if let AstNode::ExternFunctionDecl {
+                name,
+                intrinsic: Some(intrinsic),
+                ..
+            } = node
+            {
+                self.intrinsics.insert(name.clone(), intrinsic.clone());
+            }
+        }
+
         for node in nodes {
             match node {
                 // Declarations
@@ -126,9 +327,18 @@ impl MirBuilder {
                     let instrs = build_let_decl(self, node);
                     self.program.globals.extend(instrs);
                 }
+                // Generic functions are templates: the analyzer appended
+                // an ordinary function for each instance
+                AstNode::FunctionDecl { type_params, .. } if !type_params.is_empty() => {}
                 AstNode::FunctionDecl { .. } => {
                     build_function_decl(self, node);
                 }
+                // The analyzer already named methods `User::greet`
+                AstNode::ImplBlock { methods, .. } => {
+                    for method in methods {
+                        build_function_decl(self, method);
+                    }
+                }
 
                 // Import statement - skip in MIR (already handled by analyzer)
                 // The analyzer has already loaded imported functions into the function table
@@ -138,24 +348,9 @@ impl MirBuilder {
                     continue;
                 }
 
-                // Handle struct declarations (type definitions, not instances).
-                AstNode::StructDecl { name, fields } => {
-                    // For demonstration, create a placeholder instance showing the structure.
-                    let tmp = self.next_tmp();
-                    let field_vals: Vec<(String, String)> = fields
-                        .iter()
-                        .map(|(fname, _typ)| {
-                            let val_tmp = self.next_tmp();
-                            (fname.clone(), val_tmp)
-                        })
-                        .collect();
-
-                    self.program.globals.push(MirInstr::StructInit {
-                        name: tmp,
-                        struct_name: name.clone(),
-                        fields: field_vals,
-                    });
-                }
+                // Struct declarations are type definitions: their layouts were
+                // recorded above, instances come from struct literals.
+                AstNode::StructDecl { .. } => {}
 
                 // Statements
                 AstNode::StringLiteral(value) => {
@@ -172,31 +367,44 @@ impl MirBuilder {
                         name: format!("global_str_{}", self.tmp_counter - 1),
                         value: tmp,
                         mutable: false,
+                        ty: MirType::Str,
                     });
                 }
 
-                AstNode::EnumDecl { name, variants } => {
-                    for (variant_name, opt_type) in variants {
-                        let tmp = self.next_tmp();
-                        let value_tmp = if opt_type.is_some() {
-                            Some(self.next_tmp())
-                        } else {
-                            None
-                        };
-
-                        self.program.globals.push(MirInstr::EnumInit {
-                            name: tmp.clone(),
-                            enum_name: name.clone(),
-                            variant: variant_name.clone(),
-                            value: value_tmp,
-                        });
-
-                        self.program.globals.push(MirInstr::Assign {
-                            name: format!("global_enum_{}_{}", name, variant_name),
-                            value: tmp,
-                            mutable: false,
-                        });
+                // Enum declarations are type definitions: their variants were
+                // recorded above, instances come from `Enum::Variant` values.
+                AstNode::EnumDecl { .. } => {}
+
+                // Traits only exist for the analyzer's checks; their methods
+                // are compiled from the impls
+                AstNode::TraitDecl { .. } => {}
+
+                // C functions are declared for codegen; they have no body.
+                // An intrinsic is declared, and called, by its LLVM name. A
+                // program and a module it imports may declare the same one.
+                AstNode::ExternFunctionDecl {
+                    name,
+                    params,
+                    return_type,
+                    intrinsic,
+                } => {
+                    let name = intrinsic.clone().unwrap_or_else(|| name.clone());
+                    if self.program.externs.iter().any(|f| f.name == name) {
+                        continue;
                     }
+                    self.program.externs.push(MirFunction {
+                        name,
+                        params: params.iter().map(|(n, _)| n.clone()).collect(),
+                        param_types: params
+                            .iter()
+                            .map(|(_, t)| t.as_ref().map(MirType::from))
+                            .collect(),
+                        return_type: return_type.as_ref().map(MirType::from),
+                        blocks: vec![],
+                        spans: vec![],
+                        arena: false,
+                        export: false,
+                    });
                 }
 
                 // Handle global assignments (outside functions).
@@ -211,10 +419,12 @@ impl MirBuilder {
                     self.program.globals.extend(temp_block.instrs);
                     // Only handle simple identifier patterns for globals.
                     if let Pattern::Identifier(name) = pattern {
+                        let ty = self.value_type(&value_tmp);
                         self.program.globals.push(MirInstr::Assign {
                             name: name.clone(),
                             value: value_tmp,
                             mutable: true,
+                            ty,
                         });
                     }
                 }
@@ -233,13 +443,15 @@ impl MirBuilder {
                     }
 
                     self.program.globals.extend(temp_block.instrs);
-                    self.program
-                        .globals
-                        .push(MirInstr::Print { values: print_vals });
+                    let types = print_vals.iter().map(|val| self.print_type(val)).collect();
+                    self.program.globals.push(MirInstr::Print {
+                        values: print_vals,
+                        types,
+                    });
                 }
 
-                AstNode::ConditionalStmt { .. } => {
-                    // Wrap the if statement in a temporary function for isolation.
+                AstNode::ConditionalStmt { .. } | AstNode::Match { .. } => {
+                    // Wrap the if/match statement in a temporary function for isolation.
                     let if_func_name = self.create_temp_function("if");
                     let mut temp_func = MirFunction {
                         name: if_func_name.clone(),
@@ -247,6 +459,9 @@ impl MirBuilder {
                         param_types: vec![],
                         return_type: None,
                         blocks: vec![],
+                        spans: vec![],
+                        arena: false,
+                        export: false,
                     };
 
                     let block_label = self.next_block();
@@ -264,6 +479,7 @@ impl MirBuilder {
                         dest: vec![call_tmp],
                         func: if_func_name,
                         args: vec![],
+                        ty: MirType::Void,
                     });
                 }
 
@@ -277,6 +493,9 @@ impl MirBuilder {
                         param_types: vec![],
                         return_type: None,
                         blocks: vec![],
+                        spans: vec![],
+                        arena: false,
+                        export: false,
                     };
 
                     let block_label = self.next_block();
@@ -296,6 +515,7 @@ impl MirBuilder {
                         dest: vec![call_tmp],
                         func: loop_func_name,
                         args: vec![],
+                        ty: MirType::Void,
                     });
                 }
 
@@ -309,10 +529,12 @@ impl MirBuilder {
                     let expr_tmp = build_expression(self, node, &mut temp_block);
                     self.program.globals.extend(temp_block.instrs);
                     let global_name = format!("__global_expr_{}", self.tmp_counter - 1);
+                    let ty = self.value_type(&expr_tmp);
                     self.program.globals.push(MirInstr::Assign {
                         name: global_name,
                         value: expr_tmp,
                         mutable: false,
+                        ty,
                     });
                 }
 
@@ -328,10 +550,12 @@ impl MirBuilder {
                     let collection_tmp = build_nested_collection(self, node, &mut temp_block);
                     self.program.globals.extend(temp_block.instrs);
                     let global_name = format!("__global_collection_{}", self.tmp_counter - 1);
+                    let ty = self.value_type(&collection_tmp);
                     self.program.globals.push(MirInstr::Assign {
                         name: global_name,
                         value: collection_tmp,
                         mutable: false,
+                        ty,
                     });
                 }
 
@@ -437,5 +661,13 @@ impl MirBuilder {
                 true
             }
         });
+
+        // 4. Debug builds check the invariants codegen relies on
+        if cfg!(debug_assertions) {
+            if let Err(errors) = self.program.verify() {
+                let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                panic!("invalid MIR:\n{}", messages.join("\n"));
+            }
+        }
     }
 }
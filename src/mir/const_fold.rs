@@ -0,0 +1,173 @@
+use crate::mir::passes::MirPass;
+use crate::mir::{MirFunction, MirInstr, MirProgram, MirType};
+use std::collections::{HashMap, HashSet};
+
+/// Folds Int and Int64 arithmetic whose operands are known constants into a
+/// constant, following values through `Assign`, then drops the constant
+/// temps nothing reads anymore.
+///
+/// A value is known when its name is written exactly once in the function,
+/// by a constant or by an assignment from a known value. Parameters and names
+/// the global initializers write are never known. Arithmetic that would
+/// overflow or divide by zero is left for codegen to report. Comparisons are
+/// left alone: codegen gives them a different width than `ConstBool`.
+pub struct ConstFold;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Const {
+    Int(i32),
+    Int64(i64),
+}
+
+impl MirPass for ConstFold {
+    fn name(&self) -> &'static str {
+        "const-fold"
+    }
+
+    fn run(&mut self, program: &mut MirProgram) -> bool {
+        // Functions number their temps on their own: only global variables
+        // are shared with them
+        let globals: HashSet<String> = program
+            .globals
+            .iter()
+            .flat_map(|instr| instr.defs())
+            .filter(|name| !name.starts_with('%'))
+            .cloned()
+            .collect();
+        let mut changed = false;
+        for func in &mut program.functions {
+            changed |= fold_function(func, &globals);
+        }
+        changed
+    }
+}
+
+fn fold_function(func: &mut MirFunction, globals: &HashSet<String>) -> bool {
+    let mut def_counts: HashMap<String, usize> = HashMap::new();
+    for instr in func
+        .blocks
+        .iter()
+        .flat_map(|b| b.instrs.iter().chain(&b.terminator))
+    {
+        for name in instr.defs() {
+            *def_counts.entry(name.clone()).or_default() += 1;
+        }
+    }
+    let written_once = |name: &String| {
+        def_counts.get(name) == Some(&1) && !globals.contains(name) && !func.params.contains(name)
+    };
+
+    // Blocks aren't in execution order, so facts are gathered to a fixed point
+    let mut known: HashMap<String, Const> = HashMap::new();
+    let mut folded = false;
+    loop {
+        let mut learned = false;
+        for block in &mut func.blocks {
+            for instr in &mut block.instrs {
+                let fact = match instr {
+                    MirInstr::ConstInt { name, value, .. } => Some((name, Const::Int(*value))),
+                    MirInstr::ConstInt64 { name, value } => Some((name, Const::Int64(*value))),
+                    MirInstr::Assign { name, value, .. } => {
+                        known.get(value).map(|value| (name, *value))
+                    }
+                    MirInstr::BinaryOp(op, dest, lhs, rhs, _) => {
+                        match (known.get(lhs), known.get(rhs)) {
+                            (Some(&lhs), Some(&rhs)) => {
+                                fold(op, lhs, rhs).map(|value| (dest, value))
+                            }
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                let Some((name, value)) = fact else { continue };
+                if !written_once(name) || known.contains_key(name.as_str()) {
+                    continue;
+                }
+                known.insert(name.clone(), value);
+                learned = true;
+                if let MirInstr::BinaryOp(_, dest, ..) = instr {
+                    let name = std::mem::take(dest);
+                    *instr = match value {
+                        Const::Int(value) => MirInstr::ConstInt {
+                            name,
+                            value,
+                            ty: MirType::Int,
+                        },
+                        Const::Int64(value) => MirInstr::ConstInt64 { name, value },
+                    };
+                    folded = true;
+                }
+            }
+        }
+        if !learned {
+            break;
+        }
+    }
+
+    if folded {
+        remove_dead_constants(func);
+    }
+    folded
+}
+
+/// `lhs op rhs` for an Int or Int64 arithmetic `op` such as "add:int", or
+/// `None` when it can't be computed here.
+fn fold(op: &str, lhs: Const, rhs: Const) -> Option<Const> {
+    let (name, ty) = op.split_once(':')?;
+    match (ty, lhs, rhs) {
+        ("int", Const::Int(lhs), Const::Int(rhs)) => match name {
+            "add" => lhs.checked_add(rhs),
+            "sub" => lhs.checked_sub(rhs),
+            "mul" => lhs.checked_mul(rhs),
+            "div" => lhs.checked_div(rhs),
+            "mod" => lhs.checked_rem(rhs),
+            _ => None,
+        }
+        .map(Const::Int),
+        ("int64", Const::Int64(lhs), Const::Int64(rhs)) => match name {
+            "add" => lhs.checked_add(rhs),
+            "sub" => lhs.checked_sub(rhs),
+            "mul" => lhs.checked_mul(rhs),
+            "div" => lhs.checked_div(rhs),
+            "mod" => lhs.checked_rem(rhs),
+            _ => None,
+        }
+        .map(Const::Int64),
+        _ => None,
+    }
+}
+
+/// Removes integer constants written to temps that nothing reads, keeping
+/// the statement spans pointing at the same instructions.
+fn remove_dead_constants(func: &mut MirFunction) {
+    let used: HashSet<String> = func
+        .blocks
+        .iter()
+        .flat_map(|b| b.instrs.iter().chain(&b.terminator))
+        .flat_map(|instr| instr.uses())
+        .cloned()
+        .collect();
+    for block in &mut func.blocks {
+        let dead: Vec<usize> = block
+            .instrs
+            .iter()
+            .enumerate()
+            .filter(|(_, instr)| match instr {
+                MirInstr::ConstInt { name, .. } | MirInstr::ConstInt64 { name, .. } => {
+                    name.starts_with('%') && !used.contains(name)
+                }
+                _ => false,
+            })
+            .map(|(i, _)| i)
+            .collect();
+        for &i in dead.iter().rev() {
+            block.instrs.remove(i);
+        }
+        for (label, index, _) in &mut func.spans {
+            if *label == block.label {
+                *index -= dead.iter().filter(|&&i| i < *index).count();
+            }
+        }
+    }
+}
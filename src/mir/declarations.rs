@@ -1,7 +1,7 @@
 use crate::mir::builder::MirBuilder;
 use crate::mir::expresssions::build_expression;
 use crate::mir::statements::build_statement;
-use crate::mir::{MirBlock, MirFunction, MirInstr};
+use crate::mir::{MirBlock, MirFunction, MirInstr, MirType};
 use crate::parser::ast::TypeNode;
 use crate::parser::ast::{AstNode, Pattern};
 
@@ -53,10 +53,15 @@ pub fn build_let_decl(builder: &mut MirBuilder, node: &AstNode) -> Vec<MirInstr>
         // Handle different binding patterns for the left-hand side.
         match pattern {
             Pattern::Identifier(name) => {
+                // An annotated `Int64` may hold an int literal, so the annotation wins
+                let ty = type_annotation
+                    .as_ref()
+                    .map_or_else(|| builder.value_type(&value_tmp), |ty| builder.mir_type(ty));
                 instrs.push(MirInstr::Assign {
                     name: name.clone(),
                     value: value_tmp.clone(),
                     mutable: *mutable,
+                    ty,
                 });
 
                 // Track variable type in mir_symbol_table
@@ -83,6 +88,10 @@ pub fn build_let_decl(builder: &mut MirBuilder, node: &AstNode) -> Vec<MirInstr>
                 }
             }
             Pattern::Tuple(patterns) => {
+                let tuple_type = match type_annotation {
+                    Some(ty) => builder.mir_type(ty),
+                    None => builder.value_type(&value_tmp),
+                };
                 for (i, pattern) in patterns.iter().enumerate() {
                     if let Pattern::Identifier(name) = pattern {
                         // Extract each tuple element into a temporary.
@@ -96,6 +105,11 @@ pub fn build_let_decl(builder: &mut MirBuilder, node: &AstNode) -> Vec<MirInstr>
                             name: name.clone(),
                             value: extract_tmp,
                             mutable: *mutable,
+                            ty: tuple_type
+                                .tuple_elements()
+                                .and_then(|elements| elements.get(i))
+                                .cloned()
+                                .unwrap_or(MirType::Void),
                         });
 
                         // Reference counting for tuple elements if needed.
@@ -127,12 +141,17 @@ pub fn build_let_decl(builder: &mut MirBuilder, node: &AstNode) -> Vec<MirInstr>
 /// - Adds DecRef cleanup to the final reachable block only (no duplicates).
 /// - Parameters are NOT tracked for RC cleanup since caller owns them.
 /// - Adds an implicit return if none is present and the function has no return type.
+/// - Hoists nested function declarations into MIR functions of their own.
+/// - Lifts the bodies of its closure values into functions taking their captures first.
 pub fn build_function_decl(builder: &mut MirBuilder, node: &AstNode) {
     if let AstNode::FunctionDecl {
         name,
         params,
         return_type,
         body,
+        body_spans,
+        arena,
+        export,
         ..
     } = node
     {
@@ -141,12 +160,33 @@ pub fn build_function_decl(builder: &mut MirBuilder, node: &AstNode) {
             params: params.iter().map(|(n, _)| n.clone()).collect(),
             param_types: params
                 .iter()
-                .map(|(_, t)| t.as_ref().map(|ty| format!("{:?}", ty)))
+                .map(|(_, t)| t.as_ref().map(MirType::from))
                 .collect(),
-            return_type: return_type.as_ref().map(|t| format!("{:?}", t)),
+            return_type: return_type.as_ref().map(MirType::from),
             blocks: vec![],
+            spans: vec![],
+            arena: *arena,
+            export: *export,
         };
 
+        // Temps and blocks are numbered from the start of each function, so
+        // changing one function renames nothing in the others. The types of
+        // the surrounding code's temps are set aside until the function is done.
+        let outer_counters = (builder.tmp_counter, builder.block_counter);
+        let outer_symbols = builder.mir_symbol_table.clone();
+        builder.tmp_counter = 1;
+        builder.block_counter = 0;
+        builder
+            .mir_symbol_table
+            .retain(|name, _| !name.starts_with('%'));
+        // Closures are named after the LLVM symbol of their function, which
+        // spells methods `User_greet`, so codegen knows them by either name
+        let outer_closures = (
+            std::mem::replace(&mut builder.closure_scope, name.replace("::", "_")),
+            std::mem::take(&mut builder.closure_counter),
+        );
+        let pending_closures = builder.pending_closures.len();
+
         // Add function to program BEFORE processing body
         // This ensures that when build_statement adds blocks for loops,
         // it adds them to THIS function (via last_mut())
@@ -193,7 +233,19 @@ pub fn build_function_decl(builder: &mut MirBuilder, node: &AstNode) {
         }
 
         // Build MIR for each statement in the function body.
-        for stmt in body {
+        for (index, stmt) in body.iter().enumerate() {
+            // Nested functions are hoisted: built after this one as their own functions
+            if matches!(stmt, AstNode::FunctionDecl { .. }) {
+                continue;
+            }
+            // The statement's instructions start at the end of the current block
+            if let Some(span) = body_spans.get(index) {
+                if let Some(current_func) = builder.program.functions.last_mut() {
+                    current_func
+                        .spans
+                        .push((block.label.clone(), block.instrs.len(), *span));
+                }
+            }
             let old_label = block.label.clone();
             build_statement(builder, stmt, &mut block);
 
@@ -294,6 +346,35 @@ pub fn build_function_decl(builder: &mut MirBuilder, node: &AstNode) {
                 }
             }
         }
+
+        // Ifs and loops add the blocks they finish before the block they
+        // started in, so the body's first block may not come first anymore.
+        // Codegen enters the function at the first block: move it back.
+        if let Some(func) = builder.program.functions.last_mut() {
+            if let Some(pos) = func
+                .blocks
+                .iter()
+                .position(|b| b.label == first_block_label)
+            {
+                let entry = func.blocks.remove(pos);
+                func.blocks.insert(0, entry);
+            }
+        }
+
+        (builder.tmp_counter, builder.block_counter) = outer_counters;
+        builder.mir_symbol_table = outer_symbols;
+        (builder.closure_scope, builder.closure_counter) = outer_closures;
+
+        // The analyzer already named nested functions `outer.inner`
+        for stmt in body {
+            if matches!(stmt, AstNode::FunctionDecl { .. }) {
+                build_function_decl(builder, stmt);
+            }
+        }
+        // And the closure values made here were lifted into `outer.closure.1`, ...
+        for closure in builder.pending_closures.split_off(pending_closures) {
+            build_function_decl(builder, &closure);
+        }
     } else {
         debug_assert!(
             false,
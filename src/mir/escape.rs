@@ -0,0 +1,105 @@
+use crate::mir::{MirFunction, MirInstr};
+use std::collections::{HashMap, HashSet};
+
+/// Escape analysis: the array literals of `func` that never leave it, and
+/// can live in its stack frame instead of on the heap.
+///
+/// An array escapes when anything but reading it sees it: returning it,
+/// passing it to a function, storing it in a struct or collection, writing
+/// or resizing it (which may move it), or assigning it to a name that is
+/// written more than once, is a parameter, or is a global. Assigning it to a
+/// name written only there makes that name one more way to read it, like the
+/// copy a `for` loop takes. Only arrays of constant numbers and booleans are
+/// considered: their elements own nothing, and their size is known when the
+/// frame is laid out. Strings built at run time have no size until then, so
+/// they always go to the heap.
+///
+/// The result names the temps the literals are built in; codegen allocates
+/// those on the stack and leaves every name they reach out of RC tracking.
+pub fn stack_arrays(func: &MirFunction, globals: &[MirInstr]) -> HashSet<String> {
+    let mut writes: HashMap<&String, usize> = HashMap::new();
+    for instr in func.blocks.iter().flat_map(|b| &b.instrs) {
+        for name in instr.defs() {
+            *writes.entry(name).or_default() += 1;
+        }
+    }
+    let written_once = |name: &String| {
+        writes.get(name) == Some(&1)
+            && !func.params.contains(name)
+            && (name.starts_with('%') || !globals.iter().any(|g| g.defs().contains(&name)))
+    };
+    let constants: HashSet<&String> = func
+        .blocks
+        .iter()
+        .flat_map(|b| &b.instrs)
+        .filter_map(|instr| match instr {
+            MirInstr::ConstInt { name, .. }
+            | MirInstr::ConstInt64 { name, .. }
+            | MirInstr::ConstFloat { name, .. }
+            | MirInstr::ConstBool { name, .. } => Some(name),
+            _ => None,
+        })
+        .filter(|name| written_once(name))
+        .collect();
+
+    let mut stack = HashSet::new();
+    for instr in func.blocks.iter().flat_map(|b| &b.instrs) {
+        let MirInstr::Array { name, elements } = instr else {
+            continue;
+        };
+        if elements.is_empty()
+            || !written_once(name)
+            || !elements.iter().all(|el| constants.contains(el))
+        {
+            continue;
+        }
+        if !escapes(func, name, &written_once) {
+            stack.insert(name.clone());
+        }
+    }
+    stack
+}
+
+/// Whether the array built in `array` is used other than by reading it.
+fn escapes(func: &MirFunction, array: &String, written_once: &dyn Fn(&String) -> bool) -> bool {
+    // Every name the array is assigned to reads it just like the temp
+    let mut names = vec![array];
+    let mut i = 0;
+    while i < names.len() {
+        for instr in func.blocks.iter().flat_map(|b| &b.instrs) {
+            if let MirInstr::Assign { name, value, .. } = instr {
+                if value == names[i] && !names.contains(&name) {
+                    if !written_once(name) {
+                        return true;
+                    }
+                    names.push(name);
+                }
+            }
+        }
+        i += 1;
+    }
+
+    let instrs = func
+        .blocks
+        .iter()
+        .flat_map(|b| b.instrs.iter().chain(b.terminator.as_ref()));
+    for instr in instrs {
+        if !instr.uses().iter().any(|used| names.contains(used)) {
+            continue;
+        }
+        let reads = match instr {
+            MirInstr::Assign { value, .. } => names.contains(&value),
+            MirInstr::ArrayLen { .. }
+            | MirInstr::ArrayGet { .. }
+            | MirInstr::ArraySum { .. }
+            | MirInstr::ArrayMin { .. }
+            | MirInstr::ArrayMax { .. }
+            | MirInstr::Print { .. } => true,
+            _ => false,
+        };
+        if !reads {
+            return true;
+        }
+    }
+    false
+}
@@ -1,6 +1,13 @@
 use crate::{
     lexar::token::TokenType,
-    mir::{builder::MirBuilder, MirBlock, MirInstr},
+    mir::{
+        builder::MirBuilder,
+        statements::{
+            build_assert, build_closure_loop, build_if_expr, build_loop_expr, build_match,
+            build_statement, build_try,
+        },
+        MirBlock, MirInstr, MirType,
+    },
     parser::ast::{AstNode, TypeNode},
 };
 
@@ -9,17 +16,46 @@ fn get_operand_type(builder: &MirBuilder, operand: &str) -> Option<TypeNode> {
     builder.mir_symbol_table.get(operand).cloned()
 }
 
+/// The type of `lhs <op> rhs` for the operator `op` on operands of `op_type`
+/// (see `determine_op_type`): comparisons and logical operators give a Bool,
+/// arithmetic the operand type.
+pub fn binary_result_type(op: &str, op_type: &str) -> TypeNode {
+    match (op, op_type) {
+        ("eq" | "ne" | "lt" | "le" | "gt" | "ge" | "and" | "or", _) => TypeNode::Bool,
+        (_, "float") => TypeNode::Float,
+        (_, "int64") => TypeNode::Int64,
+        (_, "string") => TypeNode::String,
+        (_, "bool") => TypeNode::Bool,
+        _ => TypeNode::Int,
+    }
+}
+
 /// Helper function to determine the operation type for binary operations
 /// Returns "float" if either operand is float, "int" if both are int, or None for incompatible types
 pub fn determine_op_type(builder: &MirBuilder, lhs: &str, rhs: &str) -> Result<String, String> {
     let lhs_type = get_operand_type(builder, lhs);
     let rhs_type = get_operand_type(builder, rhs);
 
+    // Enum values compare by variant and payload, which codegen looks up by the enum's name
+    let enum_name = |ty: &Option<TypeNode>| match ty {
+        Some(TypeNode::Enum(name, _)) => Some(name.clone()),
+        Some(TypeNode::TypeRef(name)) if builder.enum_layouts.contains_key(name) => {
+            Some(name.clone())
+        }
+        _ => None,
+    };
+    if let (Some(lhs_enum), Some(rhs_enum)) = (enum_name(&lhs_type), enum_name(&rhs_type)) {
+        if lhs_enum == rhs_enum {
+            return Ok(format!("enum:{}", lhs_enum));
+        }
+    }
+
     match (lhs_type, rhs_type) {
         (Some(TypeNode::Float), Some(TypeNode::Float)) => Ok("float".to_string()),
         (Some(TypeNode::Float), Some(TypeNode::Int)) => Ok("float".to_string()),
         (Some(TypeNode::Int), Some(TypeNode::Float)) => Ok("float".to_string()),
         (Some(TypeNode::Int), Some(TypeNode::Int)) => Ok("int".to_string()),
+        (Some(TypeNode::Int64), Some(TypeNode::Int64)) => Ok("int64".to_string()),
         (Some(TypeNode::Bool), Some(TypeNode::Bool)) => Ok("bool".to_string()),
         (Some(TypeNode::String), Some(TypeNode::String)) => Ok("string".to_string()),
         (Some(TypeNode::String), _) | (_, Some(TypeNode::String)) => {
@@ -64,11 +100,25 @@ pub fn build_expression(builder: &mut MirBuilder, expr: &AstNode, block: &mut Mi
             block.instrs.push(MirInstr::ConstInt {
                 name: tmp.clone(),
                 value: *n,
+                ty: MirType::Int,
             });
             // Track type in symbol table
             builder.mir_symbol_table.insert(tmp.clone(), TypeNode::Int);
             tmp
         }
+        AstNode::Int64Literal(n) => {
+            let tmp = builder.next_tmp();
+            block.instrs.push(MirInstr::ConstInt64 {
+                name: tmp.clone(),
+                value: *n,
+            });
+            // Track type in symbol table
+            builder
+                .mir_symbol_table
+                .insert(tmp.clone(), TypeNode::Int64);
+            tmp
+        }
+
         AstNode::FloatLiteral(f) => {
             let tmp = builder.next_tmp();
             block.instrs.push(MirInstr::ConstFloat {
@@ -106,38 +156,92 @@ pub fn build_expression(builder: &mut MirBuilder, expr: &AstNode, block: &mut Mi
             tmp
         }
 
-        AstNode::Identifier(name) => name.clone(),
+        AstNode::Identifier(name, _) => name.clone(),
+
+        // A null the analyzer couldn't attach to a typed slot
+        AstNode::NullLiteral => {
+            let tmp = builder.next_tmp();
+            let ty = TypeNode::Optional(Box::new(TypeNode::Void));
+            block.instrs.push(MirInstr::OptionalWrap {
+                name: tmp.clone(),
+                value: None,
+                ty: MirType::from(&ty),
+            });
+            builder.mir_symbol_table.insert(tmp.clone(), ty);
+            tmp
+        }
+
+        AstNode::OptionalWrap { value, ty } => {
+            let value_tmp = value.as_ref().map(|v| build_expression(builder, v, block));
+            let tmp = builder.next_tmp();
+            block.instrs.push(MirInstr::OptionalWrap {
+                name: tmp.clone(),
+                value: value_tmp,
+                ty: ty.into(),
+            });
+            builder.mir_symbol_table.insert(tmp.clone(), ty.clone());
+            tmp
+        }
 
-        AstNode::UnaryExpr { op, expr } => {
+        AstNode::UnaryExpr { op, expr, .. } => {
             let expr_tmp = build_expression(builder, expr, block);
             let tmp = builder.next_tmp();
 
             match op {
                 TokenType::Minus => {
                     // Negation: negate the operand
-                    // Create a negate operation (0 - expr)
+                    // Create a negate operation (0 - expr), with a zero of the operand's type
+                    let is_float = matches!(
+                        builder.mir_symbol_table.get(&expr_tmp),
+                        Some(TypeNode::Float)
+                    );
+                    let is_int64 = matches!(
+                        builder.mir_symbol_table.get(&expr_tmp),
+                        Some(TypeNode::Int64)
+                    );
                     let zero_tmp = builder.next_tmp();
-                    block.instrs.push(MirInstr::ConstInt {
-                        name: zero_tmp.clone(),
-                        value: 0,
-                    });
-                    builder
-                        .mir_symbol_table
-                        .insert(zero_tmp.clone(), TypeNode::Int);
+                    if is_int64 {
+                        block.instrs.push(MirInstr::ConstInt64 {
+                            name: zero_tmp.clone(),
+                            value: 0,
+                        });
+                        builder
+                            .mir_symbol_table
+                            .insert(zero_tmp.clone(), TypeNode::Int64);
+                    } else if is_float {
+                        block.instrs.push(MirInstr::ConstFloat {
+                            name: zero_tmp.clone(),
+                            value: 0.0,
+                        });
+                        builder
+                            .mir_symbol_table
+                            .insert(zero_tmp.clone(), TypeNode::Float);
+                    } else {
+                        block.instrs.push(MirInstr::ConstInt {
+                            name: zero_tmp.clone(),
+                            value: 0,
+                            ty: MirType::Int,
+                        });
+                        builder
+                            .mir_symbol_table
+                            .insert(zero_tmp.clone(), TypeNode::Int);
+                    }
 
                     // Determine operation type based on operand
-                    let op_type =
-                        if let Some(TypeNode::Float) = builder.mir_symbol_table.get(&expr_tmp) {
-                            "float".to_string()
-                        } else {
-                            "int".to_string()
-                        };
+                    let op_type = if is_float {
+                        "float".to_string()
+                    } else if is_int64 {
+                        "int64".to_string()
+                    } else {
+                        "int".to_string()
+                    };
 
                     block.instrs.push(MirInstr::BinaryOp(
                         format!("sub:{}", op_type),
                         tmp.clone(),
                         zero_tmp,
                         expr_tmp.clone(),
+                        MirType::from(&binary_result_type("sub", &op_type)),
                     ));
 
                     // Track result type
@@ -168,6 +272,7 @@ pub fn build_expression(builder: &mut MirBuilder, expr: &AstNode, block: &mut Mi
                         tmp.clone(),
                         expr_tmp,
                         true_tmp,
+                        MirType::Bool,
                     ));
                     builder.mir_symbol_table.insert(tmp.clone(), TypeNode::Bool);
                     tmp
@@ -183,7 +288,9 @@ pub fn build_expression(builder: &mut MirBuilder, expr: &AstNode, block: &mut Mi
             }
         }
 
-        AstNode::BinaryExpr { left, op, right } => {
+        AstNode::BinaryExpr {
+            left, op, right, ..
+        } => {
             // Special handling for range expressions (.., ..=) used in for loops.
             match op {
                 TokenType::RangeExc | TokenType::RangeInc => {
@@ -201,6 +308,27 @@ pub fn build_expression(builder: &mut MirBuilder, expr: &AstNode, block: &mut Mi
                     range_tmp
                 }
 
+                // `opt ?? fallback`: both sides are evaluated, then the payload or fallback is picked
+                TokenType::QuestionQuestion => {
+                    let opt_tmp = build_expression(builder, left, block);
+                    let default_tmp = build_expression(builder, right, block);
+                    let tmp = builder.next_tmp();
+                    let opt_type = get_operand_type(builder, &opt_tmp);
+                    block.instrs.push(MirInstr::OptionalUnwrapOr {
+                        name: tmp.clone(),
+                        optional: opt_tmp,
+                        default: default_tmp,
+                        ty: opt_type
+                            .as_ref()
+                            .map(MirType::from)
+                            .unwrap_or(MirType::Void),
+                    });
+                    if let Some(TypeNode::Optional(inner)) = opt_type {
+                        builder.mir_symbol_table.insert(tmp.clone(), *inner);
+                    }
+                    tmp
+                }
+
                 _ => {
                     // Regular binary operations (add, sub, mul, div, etc.).
                     let lhs_tmp = build_expression(builder, left, block);
@@ -212,7 +340,17 @@ pub fn build_expression(builder: &mut MirBuilder, expr: &AstNode, block: &mut Mi
                         let lhs_type = get_operand_type(builder, &lhs_tmp);
                         let rhs_type = get_operand_type(builder, &rhs_tmp);
 
-                        if matches!(lhs_type, Some(TypeNode::String))
+                        if let Some(array_type @ TypeNode::Array(_)) = lhs_type {
+                            block.instrs.push(MirInstr::ArrayConcat {
+                                name: dest_tmp.clone(),
+                                left: lhs_tmp,
+                                right: rhs_tmp,
+                                ty: MirType::from(&array_type),
+                            });
+                            builder
+                                .mir_symbol_table
+                                .insert(dest_tmp.clone(), array_type);
+                        } else if matches!(lhs_type, Some(TypeNode::String))
                             || matches!(rhs_type, Some(TypeNode::String))
                         {
                             block.instrs.push(MirInstr::StringConcat {
@@ -237,22 +375,17 @@ pub fn build_expression(builder: &mut MirBuilder, expr: &AstNode, block: &mut Mi
                                         .insert(dest_tmp.clone(), TypeNode::String);
                                 }
                                 Ok(op_type) => {
+                                    let result_type = binary_result_type("add", &op_type);
                                     block.instrs.push(MirInstr::BinaryOp(
                                         format!("add:{}", op_type),
                                         dest_tmp.clone(),
                                         lhs_tmp,
                                         rhs_tmp,
+                                        MirType::from(&result_type),
                                     ));
-                                    // Track result type
-                                    if op_type == "float" {
-                                        builder
-                                            .mir_symbol_table
-                                            .insert(dest_tmp.clone(), TypeNode::Float);
-                                    } else {
-                                        builder
-                                            .mir_symbol_table
-                                            .insert(dest_tmp.clone(), TypeNode::Int);
-                                    }
+                                    builder
+                                        .mir_symbol_table
+                                        .insert(dest_tmp.clone(), result_type);
                                 }
                                 Err(err) => {
                                     debug_assert!(
@@ -266,6 +399,7 @@ pub fn build_expression(builder: &mut MirBuilder, expr: &AstNode, block: &mut Mi
                                         dest_tmp.clone(),
                                         lhs_tmp,
                                         rhs_tmp,
+                                        MirType::Int,
                                     ));
                                     builder
                                         .mir_symbol_table
@@ -294,6 +428,27 @@ pub fn build_expression(builder: &mut MirBuilder, expr: &AstNode, block: &mut Mi
 
                         // Determine operation type based on operands
                         match determine_op_type(builder, &lhs_tmp, &rhs_tmp) {
+                            Ok(op_type)
+                                if op_type == "string"
+                                    && matches!(
+                                        op_str.as_str(),
+                                        "eq" | "ne" | "lt" | "le" | "gt" | "ge"
+                                    ) =>
+                            {
+                                // String comparisons compare contents: equality is lowered
+                                // by codegen into a length and first-byte check before a full
+                                // compare, ordering into a strcmp
+                                block.instrs.push(MirInstr::BinaryOp(
+                                    format!("{}:string", op_str),
+                                    dest_tmp.clone(),
+                                    lhs_tmp,
+                                    rhs_tmp,
+                                    MirType::Bool,
+                                ));
+                                builder
+                                    .mir_symbol_table
+                                    .insert(dest_tmp.clone(), TypeNode::Bool);
+                            }
                             Ok(op_type) if op_type == "string" => {
                                 debug_assert!(false, "Cannot perform '{}' operation on string types - should be caught by analyzer", op_str);
                                 // Fallback: generate placeholder instruction
@@ -302,35 +457,25 @@ pub fn build_expression(builder: &mut MirBuilder, expr: &AstNode, block: &mut Mi
                                     dest_tmp.clone(),
                                     lhs_tmp,
                                     rhs_tmp,
+                                    MirType::Int,
                                 ));
                                 builder
                                     .mir_symbol_table
                                     .insert(dest_tmp.clone(), TypeNode::Int);
                             }
                             Ok(op_type) => {
+                                // Comparisons and logical ops return bool, others the operand type
+                                let result_type = binary_result_type(&op_str, &op_type);
                                 block.instrs.push(MirInstr::BinaryOp(
                                     format!("{}:{}", op_str, op_type),
                                     dest_tmp.clone(),
                                     lhs_tmp,
                                     rhs_tmp,
+                                    MirType::from(&result_type),
                                 ));
-                                // Track result type - comparisons and logical ops return bool, others return the operand type
-                                if matches!(
-                                    op_str.as_str(),
-                                    "eq" | "ne" | "lt" | "le" | "gt" | "ge" | "and" | "or"
-                                ) {
-                                    builder
-                                        .mir_symbol_table
-                                        .insert(dest_tmp.clone(), TypeNode::Bool);
-                                } else if op_type == "float" {
-                                    builder
-                                        .mir_symbol_table
-                                        .insert(dest_tmp.clone(), TypeNode::Float);
-                                } else {
-                                    builder
-                                        .mir_symbol_table
-                                        .insert(dest_tmp.clone(), TypeNode::Int);
-                                }
+                                builder
+                                    .mir_symbol_table
+                                    .insert(dest_tmp.clone(), result_type);
                             }
                             Err(err) => {
                                 debug_assert!(false, "Type error in '{}' operation: {} - should be caught by analyzer", op_str, err);
@@ -340,6 +485,7 @@ pub fn build_expression(builder: &mut MirBuilder, expr: &AstNode, block: &mut Mi
                                     dest_tmp.clone(),
                                     lhs_tmp,
                                     rhs_tmp,
+                                    MirType::Int,
                                 ));
                                 builder
                                     .mir_symbol_table
@@ -353,6 +499,23 @@ pub fn build_expression(builder: &mut MirBuilder, expr: &AstNode, block: &mut Mi
             }
         }
 
+        // `panic(message)` ends the block; code after it is unreachable
+        AstNode::FunctionCall { func, args } if matches!(&**func, AstNode::Identifier(name, _) if name == "panic") =>
+        {
+            let message = build_expression(builder, &args[0], block);
+            block.terminator = Some(MirInstr::Panic {
+                message: message.clone(),
+            });
+            message
+        }
+
+        // `assert(cond, message)` branches to a panic when the condition is false
+        AstNode::FunctionCall { func, args } if matches!(&**func, AstNode::Identifier(name, _) if name == "assert") =>
+        {
+            build_assert(builder, &args[0], &args[1], block);
+            String::new()
+        }
+
         AstNode::FunctionCall { func, args } => {
             let mut arg_tmps = vec![];
             for arg in args {
@@ -361,23 +524,133 @@ pub fn build_expression(builder: &mut MirBuilder, expr: &AstNode, block: &mut Mi
             }
 
             let dest_tmp = builder.next_tmp();
+            // A call through a variable holding a closure value
+            if let AstNode::Identifier(name, _) = &**func {
+                if let Some(ty @ TypeNode::Function(_, ret)) = builder.mir_symbol_table.get(name) {
+                    let (ty, ret) = (MirType::from(ty), (**ret).clone());
+                    builder.mir_symbol_table.insert(dest_tmp.clone(), ret);
+                    block.instrs.push(MirInstr::CallClosure {
+                        dest: vec![dest_tmp.clone()],
+                        closure: name.clone(),
+                        args: arg_tmps,
+                        ty,
+                    });
+                    return dest_tmp;
+                }
+            }
             let func_name = match &**func {
-                AstNode::Identifier(name) => name.clone(),
+                AstNode::Identifier(name, _) => name.clone(),
                 _ => {
                     // If func is an expression, evaluate it and use its result as the function name.
                     build_expression(builder, func, block)
                 }
             };
 
+            // Builtins are tagged with their first argument's type ("toStr:float")
+            // so codegen can pick the matching runtime helper
+            let func_name =
+                if crate::analyzer::builtins::BUILTIN_FUNCTIONS.contains(&func_name.as_str()) {
+                    let result_type = match func_name.as_str() {
+                        "toStr" | "format" => TypeNode::String,
+                        "toFloat" => TypeNode::Float,
+                        "toInt64" => TypeNode::Int64,
+                        "parseInt" => TypeNode::Optional(Box::new(TypeNode::Int)),
+                        "parseFloat" => TypeNode::Optional(Box::new(TypeNode::Float)),
+                        "cStr" | "toCStr" => TypeNode::CStr,
+                        "fromCStr" | "takeCStr" => TypeNode::Optional(Box::new(TypeNode::String)),
+                        _ => TypeNode::Int,
+                    };
+                    builder
+                        .mir_symbol_table
+                        .insert(dest_tmp.clone(), result_type);
+                    let arg_type = arg_tmps.first().and_then(|a| get_operand_type(builder, a));
+                    match arg_type {
+                        Some(TypeNode::Int) => format!("{}:int", func_name),
+                        Some(TypeNode::Int64) => format!("{}:int64", func_name),
+                        Some(TypeNode::Float) => format!("{}:float", func_name),
+                        Some(TypeNode::Bool) => format!("{}:bool", func_name),
+                        Some(TypeNode::String) => format!("{}:string", func_name),
+                        // Unknown at this point: codegen falls back to the LLVM value type
+                        _ => func_name,
+                    }
+                } else {
+                    if let Some(return_type) = builder.call_returns.get(&func_name).cloned() {
+                        builder
+                            .mir_symbol_table
+                            .insert(dest_tmp.clone(), return_type);
+                    }
+                    builder
+                        .intrinsics
+                        .get(&func_name)
+                        .cloned()
+                        .unwrap_or(func_name)
+                };
+
             block.instrs.push(MirInstr::Call {
                 dest: vec![dest_tmp.clone()],
                 func: func_name,
                 args: arg_tmps,
+                ty: builder.value_type(&dest_tmp),
             });
 
             dest_tmp
         }
 
+        // A closure value: its body becomes a function taking the captured
+        // values ahead of the parameters, built once the enclosing one is
+        AstNode::Closure {
+            params,
+            body,
+            value_type,
+            captures,
+        } => {
+            builder.closure_counter += 1;
+            let func = format!(
+                "{}.closure.{}",
+                builder.closure_scope, builder.closure_counter
+            );
+            let value_type = value_type.clone().unwrap_or(TypeNode::Void);
+            let (return_type, body) = match value_type {
+                TypeNode::Void => (None, (**body).clone()),
+                ref ty => (
+                    Some(ty.clone()),
+                    AstNode::Return {
+                        values: vec![(**body).clone()],
+                    },
+                ),
+            };
+            builder.pending_closures.push(AstNode::FunctionDecl {
+                name: func.clone(),
+                visibility: "Private".to_string(),
+                params: captures
+                    .iter()
+                    .map(|(name, ty)| (name.clone(), Some(ty.clone())))
+                    .chain(params.iter().cloned())
+                    .collect(),
+                return_type,
+                body: vec![body],
+                body_spans: vec![],
+                type_params: vec![],
+                allow: vec![],
+                arena: false,
+                export: false,
+                test: false,
+            });
+
+            let tmp = builder.next_tmp();
+            block.instrs.push(MirInstr::MakeClosure {
+                name: tmp.clone(),
+                func,
+                captures: captures.iter().map(|(name, _)| name.clone()).collect(),
+            });
+            let param_types = params.iter().filter_map(|(_, ty)| ty.clone()).collect();
+            builder.mir_symbol_table.insert(
+                tmp.clone(),
+                TypeNode::Function(param_types, Box::new(value_type)),
+            );
+            tmp
+        }
+
         AstNode::ArrayLiteral(elements) => {
             let mut tmp_elements = vec![];
             let mut element_type = TypeNode::Int; // Default element type
@@ -405,6 +678,23 @@ pub fn build_expression(builder: &mut MirBuilder, expr: &AstNode, block: &mut Mi
             tmp
         }
 
+        // Arguments for a variadic parameter are passed as one array
+        AstNode::VariadicArgs { values, elem_type } => {
+            let tmp_elements = values
+                .iter()
+                .map(|value| build_expression(builder, value, block))
+                .collect();
+            let tmp = builder.next_tmp();
+            block.instrs.push(MirInstr::Array {
+                name: tmp.clone(),
+                elements: tmp_elements,
+            });
+            builder
+                .mir_symbol_table
+                .insert(tmp.clone(), TypeNode::Array(Box::new(elem_type.clone())));
+            tmp
+        }
+
         AstNode::MapLiteral(entries) => {
             let mut map_entries = vec![];
             let mut key_type = TypeNode::String; // Default key type
@@ -436,8 +726,171 @@ pub fn build_expression(builder: &mut MirBuilder, expr: &AstNode, block: &mut Mi
             tmp
         }
 
+        // Match used as a value: each arm assigns to a hidden variable that
+        // is read after the match (`_` cannot appear in user identifiers)
+        AstNode::Match { value, arms } => {
+            let result = format!("__match_{}", &builder.next_tmp()[1..]);
+            build_match(builder, value, arms, Some(&result), block);
+            result
+        }
+
+        // Infinite loop used as a value: `break value` fills a hidden result variable
+        AstNode::LoopExpr { body, .. } => {
+            let result = format!("__loop_{}", &builder.next_tmp()[1..]);
+            build_loop_expr(builder, body, &result, block);
+            result
+        }
+
+        // `if` used as a value, lowered like a two-arm match
+        AstNode::IfExpr {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            let result = format!("__if_{}", &builder.next_tmp()[1..]);
+            build_if_expr(builder, condition, then_branch, else_branch, &result, block);
+            result
+        }
+
+        // `value?`: the Ok payload, or an early return of the Err
+        AstNode::Try {
+            expr,
+            expr_type: Some(TypeNode::Enum(result_name, _)),
+            return_type: Some(TypeNode::Enum(return_name, _)),
+        } => build_try(builder, expr, result_name, return_name, block),
+
+        // Block used as a value: its statements run first, then the final expression
+        AstNode::BlockExpr { body, value, .. } => {
+            for stmt in body {
+                build_statement(builder, stmt, block);
+            }
+            build_expression(builder, value, block)
+        }
+
+        // Struct literal: fields are evaluated in source order
+        AstNode::StructLiteral { name, fields } => {
+            let field_vals = fields
+                .iter()
+                .map(|(field, value)| (field.clone(), build_expression(builder, value, block)))
+                .collect();
+            let tmp = builder.next_tmp();
+            block.instrs.push(MirInstr::StructInit {
+                name: tmp.clone(),
+                struct_name: name.clone(),
+                fields: field_vals,
+            });
+            if let Some(struct_type) = builder.struct_type(name) {
+                builder.mir_symbol_table.insert(tmp.clone(), struct_type);
+            }
+            tmp
+        }
+
+        // Field access: `u.name`
+        AstNode::FieldAccess { object, field, .. } => {
+            let object_tmp = build_expression(builder, object, block);
+            let tmp = builder.next_tmp();
+            let Some(TypeNode::Struct(struct_name, _)) = get_operand_type(builder, &object_tmp)
+            else {
+                return tmp;
+            };
+            let field_type = builder
+                .struct_layouts
+                .get(&struct_name)
+                .and_then(|fields| fields.iter().find(|(f, _)| f == field))
+                .map(|(_, ty)| builder.resolve_struct_ref(ty));
+            block.instrs.push(MirInstr::StructGet {
+                name: tmp.clone(),
+                struct_instance: object_tmp,
+                struct_name,
+                field: field.clone(),
+            });
+            if let Some(field_type) = field_type {
+                builder.mir_symbol_table.insert(tmp.clone(), field_type);
+            }
+            tmp
+        }
+
+        // Enum value: `Color::Red` or `Shape::Circle(2.0)`
+        AstNode::EnumValue {
+            enum_name,
+            variant,
+            payload,
+        } => {
+            let value = payload
+                .as_ref()
+                .map(|payload| build_expression(builder, payload, block));
+            let tmp = builder.next_tmp();
+            block.instrs.push(MirInstr::EnumInit {
+                name: tmp.clone(),
+                enum_name: enum_name.clone(),
+                variant: variant.clone(),
+                value,
+            });
+            if let Some(enum_type) = builder.enum_type(enum_name) {
+                builder.mir_symbol_table.insert(tmp.clone(), enum_type);
+            }
+            tmp
+        }
+
+        // Method call: `u.greet(x)` calls `User::greet(u, x)`, passing the
+        // object as `self`
+        AstNode::MethodCall {
+            object,
+            method,
+            args,
+            ..
+        } => {
+            let mut arg_tmps = vec![build_expression(builder, object, block)];
+            // An array's `map`, `filter` and `reduce` inline their closure into a
+            // loop; other closure arguments are closure values
+            let is_array = matches!(
+                get_operand_type(builder, &arg_tmps[0]),
+                Some(TypeNode::Array(_))
+            );
+            if let (
+                true,
+                "map" | "filter" | "reduce",
+                Some((closure @ AstNode::Closure { .. }, rest)),
+            ) = (is_array, method.as_str(), args.split_last())
+            {
+                let init = rest
+                    .first()
+                    .map(|arg| build_expression(builder, arg, block));
+                let array = arg_tmps.remove(0);
+                return build_closure_loop(builder, method, array, init, closure, block);
+            }
+            for arg in args {
+                arg_tmps.push(build_expression(builder, arg, block));
+            }
+            match get_operand_type(builder, &arg_tmps[0]) {
+                Some(array_type @ TypeNode::Array(_)) => {
+                    return build_array_method(builder, method, &arg_tmps, &array_type, block);
+                }
+                Some(map_type @ TypeNode::Map(..)) => {
+                    return build_map_method(builder, method, &arg_tmps, &map_type, block);
+                }
+                Some(TypeNode::String) => {
+                    return build_string_method(builder, method, &arg_tmps, block);
+                }
+                _ => {}
+            }
+            let dest_tmp = builder.next_tmp();
+            if let Some(return_type) = builder.call_returns.get(method).cloned() {
+                builder
+                    .mir_symbol_table
+                    .insert(dest_tmp.clone(), return_type);
+            }
+            block.instrs.push(MirInstr::Call {
+                dest: vec![dest_tmp.clone()],
+                func: method.clone(),
+                args: arg_tmps,
+                ty: builder.value_type(&dest_tmp),
+            });
+            dest_tmp
+        }
+
         // Element access: arr[index] or map[key]
-        AstNode::ElementAccess { array, index } => {
+        AstNode::ElementAccess { array, index, .. } => {
             let array_tmp = build_expression(builder, array, block);
             let index_tmp = build_expression(builder, index, block);
 
@@ -446,27 +899,34 @@ pub fn build_expression(builder: &mut MirBuilder, expr: &AstNode, block: &mut Mi
 
             match array_type {
                 // Array element access
-                Some(TypeNode::Array(_)) => {
+                Some(TypeNode::Array(elem_type)) => {
                     let result_tmp = builder.next_tmp();
                     block.instrs.push(MirInstr::ArrayGet {
                         name: result_tmp.clone(),
                         array: array_tmp,
                         index: index_tmp,
                     });
+                    builder
+                        .mir_symbol_table
+                        .insert(result_tmp.clone(), *elem_type);
                     result_tmp
                 }
-                // Map element access
-                Some(TypeNode::Map(_, value_type)) => {
+                // Map element access, absent for a missing key
+                Some(TypeNode::Map(key_type, value_type)) => {
+                    let ty = MirType::Map(
+                        Box::new(key_type.as_ref().into()),
+                        Box::new(value_type.as_ref().into()),
+                    );
                     let result_tmp = builder.next_tmp();
+                    builder
+                        .mir_symbol_table
+                        .insert(result_tmp.clone(), TypeNode::Optional(value_type));
                     block.instrs.push(MirInstr::MapGet {
                         name: result_tmp.clone(),
                         map: array_tmp,
                         key: index_tmp,
+                        ty,
                     });
-                    // Track the value type
-                    builder
-                        .mir_symbol_table
-                        .insert(result_tmp.clone(), *value_type);
                     result_tmp
                 }
                 // Fallback: treat as array access
@@ -489,3 +949,224 @@ pub fn build_expression(builder: &mut MirBuilder, expr: &AstNode, block: &mut Mi
         }
     }
 }
+
+/// Lowers a method call on an array (`arr.push(4)`, `arr.pop()`, `arr.sum()`,
+/// ...) to its instruction. `operands` holds the array, then the arguments.
+/// Returns the temporary holding the result, which is unused for methods
+/// returning nothing.
+fn build_array_method(
+    builder: &mut MirBuilder,
+    method: &str,
+    operands: &[String],
+    array_type: &TypeNode,
+    block: &mut MirBlock,
+) -> String {
+    let TypeNode::Array(elem_type) = array_type else {
+        unreachable!("build_array_method is only called for arrays");
+    };
+    let array = operands[0].clone();
+    let ty = MirType::from(array_type);
+    let dest_tmp = builder.next_tmp();
+    match (method, &operands[1..]) {
+        ("push", [value]) => block.instrs.push(MirInstr::ArrayPush {
+            array,
+            value: value.clone(),
+            ty,
+        }),
+        ("pop", []) => {
+            block.instrs.push(MirInstr::ArrayPop {
+                name: dest_tmp.clone(),
+                array,
+                ty,
+            });
+            builder
+                .mir_symbol_table
+                .insert(dest_tmp.clone(), TypeNode::Optional(elem_type.clone()));
+        }
+        ("insert", [index, value]) => block.instrs.push(MirInstr::ArrayInsert {
+            array,
+            index: index.clone(),
+            value: value.clone(),
+            ty,
+        }),
+        ("remove", [index]) => {
+            block.instrs.push(MirInstr::ArrayRemove {
+                name: dest_tmp.clone(),
+                array,
+                index: index.clone(),
+                ty,
+            });
+            builder
+                .mir_symbol_table
+                .insert(dest_tmp.clone(), (**elem_type).clone());
+        }
+        ("clear", []) => block.instrs.push(MirInstr::ArrayClear { array, ty }),
+        ("sort", []) => block.instrs.push(MirInstr::ArraySort { array, ty }),
+        ("reverse", []) => block.instrs.push(MirInstr::ArrayReverse { array, ty }),
+        ("sum", []) => {
+            block.instrs.push(MirInstr::ArraySum {
+                name: dest_tmp.clone(),
+                array,
+                ty,
+            });
+            builder
+                .mir_symbol_table
+                .insert(dest_tmp.clone(), (**elem_type).clone());
+        }
+        ("min" | "max", []) => {
+            let name = dest_tmp.clone();
+            block.instrs.push(if method == "min" {
+                MirInstr::ArrayMin { name, array, ty }
+            } else {
+                MirInstr::ArrayMax { name, array, ty }
+            });
+            builder
+                .mir_symbol_table
+                .insert(dest_tmp.clone(), TypeNode::Optional(elem_type.clone()));
+        }
+        // Rejected by the analyzer
+        _ => {}
+    }
+    dest_tmp
+}
+
+/// Lowers a method call on a map (`m.insert(k, v)`, `m.keys()`, ...) to its
+/// instruction. `operands` holds the map, then the arguments. Returns the
+/// temporary holding the result, which is unused for methods returning nothing.
+fn build_map_method(
+    builder: &mut MirBuilder,
+    method: &str,
+    operands: &[String],
+    map_type: &TypeNode,
+    block: &mut MirBlock,
+) -> String {
+    let TypeNode::Map(key_type, value_type) = map_type else {
+        unreachable!("build_map_method is only called for maps");
+    };
+    let map = operands[0].clone();
+    let ty = MirType::from(map_type);
+    let dest_tmp = builder.next_tmp();
+    let (instr, result_type) = match (method, &operands[1..]) {
+        ("insert", [key, value]) => (
+            MirInstr::MapInsert {
+                map,
+                key: key.clone(),
+                value: value.clone(),
+                ty,
+            },
+            None,
+        ),
+        ("remove", [key]) => (
+            MirInstr::MapRemove {
+                name: dest_tmp.clone(),
+                map,
+                key: key.clone(),
+                ty,
+            },
+            Some(TypeNode::Optional(value_type.clone())),
+        ),
+        ("clear", []) => (MirInstr::MapClear { map, ty }, None),
+        ("containsKey", [key]) => (
+            MirInstr::MapContains {
+                name: dest_tmp.clone(),
+                map,
+                key: key.clone(),
+                ty,
+            },
+            Some(TypeNode::Bool),
+        ),
+        ("keys", []) => (
+            MirInstr::MapKeys {
+                name: dest_tmp.clone(),
+                map,
+                ty,
+            },
+            Some(TypeNode::Array(key_type.clone())),
+        ),
+        ("values", []) => (
+            MirInstr::MapValues {
+                name: dest_tmp.clone(),
+                map,
+                ty,
+            },
+            Some(TypeNode::Array(value_type.clone())),
+        ),
+        // Rejected by the analyzer
+        _ => return dest_tmp,
+    };
+    block.instrs.push(instr);
+    if let Some(result_type) = result_type {
+        builder
+            .mir_symbol_table
+            .insert(dest_tmp.clone(), result_type);
+    }
+    dest_tmp
+}
+
+/// Lowers a method call on a string (`s.len()`, `s.split(",")`, ...) to its
+/// instruction. `operands` holds the string, then the arguments. Returns the
+/// temporary holding the result.
+fn build_string_method(
+    builder: &mut MirBuilder,
+    method: &str,
+    operands: &[String],
+    block: &mut MirBlock,
+) -> String {
+    let string = operands[0].clone();
+    let name = builder.next_tmp();
+    let (instr, result_type) = match (method, &operands[1..]) {
+        ("len", []) => (
+            MirInstr::StrLen {
+                name: name.clone(),
+                string,
+            },
+            TypeNode::Int,
+        ),
+        ("contains", [part]) => (
+            MirInstr::StrContains {
+                name: name.clone(),
+                string,
+                part: part.clone(),
+            },
+            TypeNode::Bool,
+        ),
+        ("split", [sep]) => (
+            MirInstr::StrSplit {
+                name: name.clone(),
+                string,
+                sep: sep.clone(),
+            },
+            TypeNode::Array(Box::new(TypeNode::String)),
+        ),
+        ("trim", []) => (
+            MirInstr::StrTrim {
+                name: name.clone(),
+                string,
+            },
+            TypeNode::String,
+        ),
+        ("replace", [from, to]) => (
+            MirInstr::StrReplace {
+                name: name.clone(),
+                string,
+                from: from.clone(),
+                to: to.clone(),
+            },
+            TypeNode::String,
+        ),
+        ("substring", [start, end]) => (
+            MirInstr::StrSubstring {
+                name: name.clone(),
+                string,
+                start: start.clone(),
+                end: end.clone(),
+            },
+            TypeNode::String,
+        ),
+        // Rejected by the analyzer
+        _ => return name,
+    };
+    block.instrs.push(instr);
+    builder.mir_symbol_table.insert(name.clone(), result_type);
+    name
+}
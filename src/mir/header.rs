@@ -0,0 +1,80 @@
+use crate::mir::{MirFunction, MirProgram, MirType};
+
+impl MirProgram {
+    /// C header declaring the program's `export "C"` functions, for
+    /// `--emit=header`. `name` is the output name, which the include guard
+    /// is made from.
+    pub fn c_header(&self, name: &str) -> String {
+        let guard: String = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let mut header = format!(
+            "/* Functions exported by {name}, generated by doo build --emit=header */\n\
+             #ifndef DOO_{guard}_H\n\
+             #define DOO_{guard}_H\n\
+             \n\
+             #include <stdint.h>\n\
+             \n\
+             #ifdef __cplusplus\n\
+             extern \"C\" {{\n\
+             #endif\n\
+             \n"
+        );
+        for func in self.functions.iter().filter(|func| func.export) {
+            header.push_str(&c_prototype(func));
+            header.push('\n');
+        }
+        header.push_str(&format!(
+            "\n#ifdef __cplusplus\n}}\n#endif\n\n#endif /* DOO_{guard}_H */\n"
+        ));
+        header
+    }
+}
+
+/// `int32_t add(int32_t a, int32_t b);`
+fn c_prototype(func: &MirFunction) -> String {
+    let params: Vec<String> = func
+        .params
+        .iter()
+        .zip(&func.param_types)
+        .map(|(param, ty)| c_declaration(c_type(ty.as_ref()), param))
+        .collect();
+    let params = if params.is_empty() {
+        "void".to_string()
+    } else {
+        params.join(", ")
+    };
+    let name = format!("{}({})", func.name, params);
+    format!(
+        "{};",
+        c_declaration(c_type(func.return_type.as_ref()), &name)
+    )
+}
+
+/// `int32_t a`, or `char *s` with the `*` against the name.
+fn c_declaration(ty: &str, name: &str) -> String {
+    if ty.ends_with('*') {
+        format!("{}{}", ty, name)
+    } else {
+        format!("{} {}", ty, name)
+    }
+}
+
+/// The C type a value of `ty` is passed as. The analyzer only lets numbers,
+/// Bools, which are `i32` like Int, and CStrs into exported signatures.
+fn c_type(ty: Option<&MirType>) -> &'static str {
+    match ty {
+        None | Some(MirType::Void) => "void",
+        Some(MirType::Int64) => "int64_t",
+        Some(MirType::Float) => "double",
+        Some(MirType::CStr) => "char *",
+        Some(_) => "int32_t",
+    }
+}
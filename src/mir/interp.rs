@@ -0,0 +1,1282 @@
+use crate::analyzer::builtins::BUILTIN_FUNCTIONS;
+use crate::mir::{MirFunction, MirInstr, MirProgram, MirType};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::rc::{Rc, Weak};
+
+/// A runtime value of the interpreter.
+///
+/// Arrays and maps are shared until written, like the copy-on-write buffers
+/// of compiled code, so assigning one is cheap and `==` on them compares
+/// identity. Structs are shared and mutated in place; strings are immutable.
+/// A `weak` field holds a `Weak` and reads as null once its struct is gone.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Void,
+    Int(i32),
+    Int64(i64),
+    Float(f64),
+    Bool(bool),
+    Str(Rc<str>),
+    Array(Rc<Vec<Value>>),
+    Map(Rc<Vec<(Value, Value)>>), // In insertion order
+    Tuple(Rc<Vec<Value>>),
+    Range(Box<Value>, Box<Value>, bool), // (start, end, inclusive)
+    Struct(Rc<RefCell<StructValue>>),
+    Enum(Rc<EnumValue>),
+    Optional(Option<Box<Value>>),
+    Weak(Weak<RefCell<StructValue>>), // Only ever stored in a `weak` field
+    Closure(Rc<ClosureValue>),
+}
+
+#[derive(Debug)]
+pub struct StructValue {
+    pub name: String,
+    pub fields: Vec<(String, Value)>,
+}
+
+/// A closure value: the function its body was lifted into and the values it
+/// captured, passed ahead of the arguments of each call.
+#[derive(Debug)]
+pub struct ClosureValue {
+    pub func: String,
+    pub captures: Vec<Value>,
+}
+
+#[derive(Debug)]
+pub struct EnumValue {
+    pub enum_name: String,
+    pub variant: String,
+    pub payload: Option<Value>,
+}
+
+/// Why the interpreter stopped before the program finished: MIR it can't
+/// run, or an operation compiled code leaves undefined.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterpError {
+    pub function: String,
+    pub block: String,
+    pub message: String,
+}
+
+impl fmt::Display for InterpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "in {} at {}: {}",
+            self.function, self.block, self.message
+        )
+    }
+}
+
+/// What ends a call early: a `panic` of the program, or an error located
+/// once it reaches the block it happened in.
+enum Stop {
+    Panic(String),
+    Error(String),
+    Located(InterpError),
+}
+
+type Exec<T> = Result<T, Stop>;
+
+fn error<T>(message: impl Into<String>) -> Exec<T> {
+    Err(Stop::Error(message.into()))
+}
+
+/// Runs a `MirProgram` directly, without LLVM: the globals first, then
+/// `main`. `print` writes to `out` with the formatting of compiled code, and
+/// a panic writes `panic: <message>` to `err` and exits with 1.
+///
+/// Reference counting instructions do nothing here. The loop instructions
+/// the builder no longer emits (`ForRange`, the loop markers, ...) are not
+/// supported.
+pub struct Interpreter<'a> {
+    program: &'a MirProgram,
+    functions: HashMap<&'a str, &'a MirFunction>,
+    labels: HashMap<&'a str, HashMap<&'a str, usize>>, // Function -> block label -> index
+    globals: HashMap<String, Value>,
+    out: &'a mut dyn Write,
+    err: &'a mut dyn Write,
+    pub overflow_checks: bool, // Panic on Int overflow instead of wrapping
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(program: &'a MirProgram, out: &'a mut dyn Write, err: &'a mut dyn Write) -> Self {
+        let functions = program
+            .functions
+            .iter()
+            .map(|func| (func.name.as_str(), func))
+            .collect();
+        let labels = program
+            .functions
+            .iter()
+            .map(|func| {
+                let labels = func
+                    .blocks
+                    .iter()
+                    .enumerate()
+                    .map(|(i, block)| (block.label.as_str(), i))
+                    .collect();
+                (func.name.as_str(), labels)
+            })
+            .collect();
+        Self {
+            program,
+            functions,
+            labels,
+            globals: HashMap::new(),
+            out,
+            err,
+            overflow_checks: false,
+        }
+    }
+
+    /// Runs the program and returns its exit code.
+    pub fn run(&mut self) -> Result<i32, InterpError> {
+        self.run_from("main")
+    }
+
+    /// Runs the program as if `entry`, a function taking no arguments, were
+    /// its `main`: how `doo test` runs each test.
+    pub fn run_from(&mut self, entry: &str) -> Result<i32, InterpError> {
+        let outcome = self
+            .run_globals()
+            .and_then(|()| self.call(entry, Vec::new()));
+        match outcome {
+            Ok(_) => Ok(0),
+            Err(Stop::Panic(message)) => {
+                let _ = writeln!(self.err, "panic: {}", message);
+                Ok(101)
+            }
+            Err(Stop::Located(error)) => Err(error),
+            Err(Stop::Error(message)) => Err(InterpError {
+                function: entry.to_string(),
+                block: String::new(),
+                message,
+            }),
+        }
+    }
+
+    /// Calls the function `name` with `args` and returns what it returned.
+    pub fn call_function(
+        &mut self,
+        name: &str,
+        args: Vec<Value>,
+    ) -> Result<Vec<Value>, InterpError> {
+        self.call(name, args).map_err(|stop| match stop {
+            Stop::Panic(message) => InterpError {
+                function: name.to_string(),
+                block: String::new(),
+                message: format!("panic: {}", message),
+            },
+            Stop::Located(error) => error,
+            Stop::Error(message) => InterpError {
+                function: name.to_string(),
+                block: String::new(),
+                message,
+            },
+        })
+    }
+
+    fn run_globals(&mut self) -> Exec<()> {
+        let mut frame = std::mem::take(&mut self.globals);
+        let result = self.program.globals.iter().try_for_each(|instr| {
+            self.exec(instr, &mut frame).map_err(|stop| match stop {
+                Stop::Error(message) => Stop::Located(InterpError {
+                    function: "<globals>".to_string(),
+                    block: String::new(),
+                    message,
+                }),
+                stop => stop,
+            })
+        });
+        self.globals = frame;
+        result
+    }
+
+    fn call(&mut self, name: &str, args: Vec<Value>) -> Exec<Vec<Value>> {
+        let Some(&func) = self.functions.get(name) else {
+            return error(format!("call to unknown function '{}'", name));
+        };
+        if args.len() != func.params.len() {
+            return error(format!(
+                "'{}' takes {} arguments, got {}",
+                name,
+                func.params.len(),
+                args.len()
+            ));
+        }
+        let mut frame: HashMap<String, Value> = func.params.iter().cloned().zip(args).collect();
+
+        let mut current = 0;
+        loop {
+            let Some(block) = func.blocks.get(current) else {
+                return Ok(Vec::new());
+            };
+            let locate = |stop| match stop {
+                Stop::Error(message) => Stop::Located(InterpError {
+                    function: func.name.clone(),
+                    block: block.label.clone(),
+                    message,
+                }),
+                stop => stop,
+            };
+            for instr in &block.instrs {
+                self.exec(instr, &mut frame).map_err(locate)?;
+            }
+            let target = match &block.terminator {
+                // Codegen ends a block without a terminator by returning
+                None => return Ok(Vec::new()),
+                Some(MirInstr::Return { values }) => {
+                    return values
+                        .iter()
+                        .map(|value| self.read(&frame, value))
+                        .collect::<Exec<Vec<Value>>>()
+                        .map_err(locate);
+                }
+                Some(MirInstr::Panic { message }) => {
+                    let message = self.read(&frame, message).map_err(locate)?;
+                    return Err(Stop::Panic(display(&message)));
+                }
+                Some(MirInstr::Jump { target }) => target,
+                Some(MirInstr::CondJump {
+                    cond,
+                    then_block,
+                    else_block,
+                }) => {
+                    if truthy(&self.read(&frame, cond).map_err(locate)?).map_err(locate)? {
+                        then_block
+                    } else {
+                        else_block
+                    }
+                }
+                Some(other) => {
+                    return Err(locate(Stop::Error(format!(
+                        "unsupported terminator {}",
+                        other
+                    ))))
+                }
+            };
+            current = match self.labels[func.name.as_str()].get(target.as_str()) {
+                Some(&index) => index,
+                None => {
+                    return Err(locate(Stop::Error(format!(
+                        "jump to unknown block '{}'",
+                        target
+                    ))))
+                }
+            };
+        }
+    }
+
+    /// The value of `name`: a local, or else a global.
+    fn read(&self, frame: &HashMap<String, Value>, name: &str) -> Exec<Value> {
+        match frame.get(name).or_else(|| self.globals.get(name)) {
+            Some(value) => Ok(value.clone()),
+            None => error(format!("'{}' is read before it is written", name)),
+        }
+    }
+
+    /// `value` as field `field` of struct `struct_name` holds it: a `weak`
+    /// field keeps a `Weak` to the struct instead of the optional.
+    fn store(&self, struct_name: &str, field: &str, value: Value) -> Value {
+        let weak = self
+            .program
+            .weak_fields
+            .get(struct_name)
+            .is_some_and(|weak| weak.iter().any(|name| name == field));
+        match value {
+            Value::Optional(Some(target)) if weak => match *target {
+                Value::Struct(target) => Value::Weak(Rc::downgrade(&target)),
+                target => Value::Optional(Some(Box::new(target))),
+            },
+            Value::Optional(None) if weak => Value::Weak(Weak::new()),
+            value => value,
+        }
+    }
+
+    /// The variable `name` itself, for the operations that change it in place.
+    fn slot<'f>(
+        &'f mut self,
+        frame: &'f mut HashMap<String, Value>,
+        name: &str,
+    ) -> Exec<&'f mut Value> {
+        if frame.contains_key(name) {
+            return Ok(frame.get_mut(name).unwrap());
+        }
+        match self.globals.get_mut(name) {
+            Some(value) => Ok(value),
+            None => error(format!("'{}' is changed before it is written", name)),
+        }
+    }
+
+    fn array_slot<'f>(
+        &'f mut self,
+        frame: &'f mut HashMap<String, Value>,
+        name: &str,
+    ) -> Exec<&'f mut Vec<Value>> {
+        match self.slot(frame, name)? {
+            Value::Array(items) => Ok(Rc::make_mut(items)),
+            other => error(format!("'{}' is not an array: {}", name, display(other))),
+        }
+    }
+
+    fn map_slot<'f>(
+        &'f mut self,
+        frame: &'f mut HashMap<String, Value>,
+        name: &str,
+    ) -> Exec<&'f mut Vec<(Value, Value)>> {
+        match self.slot(frame, name)? {
+            Value::Map(entries) => Ok(Rc::make_mut(entries)),
+            other => error(format!("'{}' is not a map: {}", name, display(other))),
+        }
+    }
+
+    fn exec(&mut self, instr: &MirInstr, frame: &mut HashMap<String, Value>) -> Exec<()> {
+        let (name, value) = match instr {
+            MirInstr::IncRef { .. } | MirInstr::DecRef { .. } => return Ok(()),
+
+            MirInstr::ConstInt { name, value, .. } => (name, Value::Int(*value)),
+            MirInstr::ConstInt64 { name, value } => (name, Value::Int64(*value)),
+            MirInstr::ConstFloat { name, value } => (name, Value::Float(*value)),
+            MirInstr::ConstBool { name, value } => (name, Value::Bool(*value)),
+            MirInstr::ConstString { name, value } => (name, Value::Str(value.as_str().into())),
+
+            MirInstr::Array { name, elements } => {
+                let items = self.read_all(frame, elements)?;
+                (name, Value::Array(Rc::new(items)))
+            }
+            MirInstr::Map { name, entries } => {
+                let mut map = Vec::new();
+                for (key, value) in entries {
+                    map_set(&mut map, self.read(frame, key)?, self.read(frame, value)?);
+                }
+                (name, Value::Map(Rc::new(map)))
+            }
+            MirInstr::RangeCreate {
+                name,
+                start,
+                end,
+                inclusive,
+            } => {
+                let start = Box::new(self.read(frame, start)?);
+                let end = Box::new(self.read(frame, end)?);
+                (name, Value::Range(start, end, *inclusive))
+            }
+
+            MirInstr::ArrayLen { name, array } => {
+                let len = match self.read(frame, array)? {
+                    Value::Array(items) => items.len(),
+                    Value::Map(entries) => entries.len(),
+                    Value::Range(start, end, inclusive) => {
+                        let (start, end) = (as_i64(&start)?, as_i64(&end)?);
+                        (end - start + i64::from(inclusive)).max(0) as usize
+                    }
+                    other => return error(format!("length of a non-array: {}", display(&other))),
+                };
+                (name, Value::Int(len as i32))
+            }
+            MirInstr::ArrayGet { name, array, index } => {
+                let index = as_i64(&self.read(frame, index)?)?;
+                let value = match self.read(frame, array)? {
+                    Value::Array(items) => element(&items, index)?.clone(),
+                    Value::Map(entries) => {
+                        let (key, value) = element(&entries, index)?;
+                        Value::Tuple(Rc::new(vec![key.clone(), value.clone()]))
+                    }
+                    Value::Range(start, ..) => int_like(&start, as_i64(&start)? + index),
+                    other => return error(format!("index into a non-array: {}", display(&other))),
+                };
+                (name, value)
+            }
+            MirInstr::ArraySet {
+                array,
+                index,
+                value,
+            } => {
+                let index = as_i64(&self.read(frame, index)?)?;
+                let value = self.read(frame, value)?;
+                let items = self.array_slot(frame, array)?;
+                let len = items.len();
+                match usize::try_from(index).ok().and_then(|i| items.get_mut(i)) {
+                    Some(slot) => *slot = value,
+                    None => return Err(Stop::Panic(out_of_bounds(index, len))),
+                }
+                return Ok(());
+            }
+            MirInstr::ArrayConcat {
+                name, left, right, ..
+            } => {
+                let mut items = as_array(&self.read(frame, left)?)?.to_vec();
+                items.extend(as_array(&self.read(frame, right)?)?.iter().cloned());
+                (name, Value::Array(Rc::new(items)))
+            }
+            MirInstr::ArrayPush { array, value, .. } => {
+                let value = self.read(frame, value)?;
+                self.array_slot(frame, array)?.push(value);
+                return Ok(());
+            }
+            MirInstr::ArrayPop { name, array, .. } => {
+                let popped = self.array_slot(frame, array)?.pop();
+                (name, Value::Optional(popped.map(Box::new)))
+            }
+            MirInstr::ArrayInsert {
+                array,
+                index,
+                value,
+                ..
+            } => {
+                let index = as_i64(&self.read(frame, index)?)?;
+                let value = self.read(frame, value)?;
+                let items = self.array_slot(frame, array)?;
+                match usize::try_from(index) {
+                    Ok(i) if i <= items.len() => items.insert(i, value),
+                    _ => return Err(Stop::Panic(out_of_bounds(index, items.len()))),
+                }
+                return Ok(());
+            }
+            MirInstr::ArrayRemove {
+                name, array, index, ..
+            } => {
+                let index = as_i64(&self.read(frame, index)?)?;
+                let items = self.array_slot(frame, array)?;
+                let removed = match usize::try_from(index) {
+                    Ok(i) if i < items.len() => items.remove(i),
+                    _ => return Err(Stop::Panic(out_of_bounds(index, items.len()))),
+                };
+                (name, removed)
+            }
+            MirInstr::ArrayClear { array, .. } => {
+                self.array_slot(frame, array)?.clear();
+                return Ok(());
+            }
+            MirInstr::ArraySort { array, .. } => {
+                let items = self.array_slot(frame, array)?;
+                items.sort_by(|a, b| compare(a, b).unwrap_or(std::cmp::Ordering::Equal));
+                return Ok(());
+            }
+            MirInstr::ArrayReverse { array, .. } => {
+                self.array_slot(frame, array)?.reverse();
+                return Ok(());
+            }
+            MirInstr::ArraySum { name, array, ty } => {
+                let items = self.read(frame, array)?;
+                let zero = match ty.element() {
+                    Some(MirType::Float) => Value::Float(0.0),
+                    Some(MirType::Int64) => Value::Int64(0),
+                    _ => Value::Int(0),
+                };
+                let mut sum = zero;
+                for item in as_array(&items)?.iter() {
+                    sum = self.arithmetic("add", &sum, item)?;
+                }
+                (name, sum)
+            }
+            MirInstr::ArrayMin { name, array, .. } | MirInstr::ArrayMax { name, array, .. } => {
+                let want = if matches!(instr, MirInstr::ArrayMin { .. }) {
+                    std::cmp::Ordering::Less
+                } else {
+                    std::cmp::Ordering::Greater
+                };
+                let items = self.read(frame, array)?;
+                let mut best: Option<&Value> = None;
+                for item in as_array(&items)?.iter() {
+                    if best.is_none_or(|best| compare(item, best) == Some(want)) {
+                        best = Some(item);
+                    }
+                }
+                (name, Value::Optional(best.cloned().map(Box::new)))
+            }
+
+            MirInstr::MapLen { name, map } => {
+                let len = as_map(&self.read(frame, map)?)?.len();
+                (name, Value::Int(len as i32))
+            }
+            MirInstr::MapGet { name, map, key, .. } => {
+                let key = self.read(frame, key)?;
+                let map = self.read(frame, map)?;
+                let found = as_map(&map)?
+                    .iter()
+                    .find(|(k, _)| same_key(k, &key))
+                    .map(|(_, v)| Box::new(v.clone()));
+                (name, Value::Optional(found))
+            }
+            MirInstr::MapGetPair { name, map, index } => {
+                let index = as_i64(&self.read(frame, index)?)?;
+                let map = self.read(frame, map)?;
+                let (key, value) = element(as_map(&map)?, index)?;
+                (
+                    name,
+                    Value::Tuple(Rc::new(vec![key.clone(), value.clone()])),
+                )
+            }
+            MirInstr::MapSet { map, key, value }
+            | MirInstr::MapInsert {
+                map, key, value, ..
+            } => {
+                let key = self.read(frame, key)?;
+                let value = self.read(frame, value)?;
+                map_set(self.map_slot(frame, map)?, key, value);
+                return Ok(());
+            }
+            MirInstr::MapRemove { name, map, key, .. } => {
+                let key = self.read(frame, key)?;
+                let entries = self.map_slot(frame, map)?;
+                let removed = entries
+                    .iter()
+                    .position(|(k, _)| same_key(k, &key))
+                    .map(|i| Box::new(entries.remove(i).1));
+                (name, Value::Optional(removed))
+            }
+            MirInstr::MapClear { map, .. } => {
+                self.map_slot(frame, map)?.clear();
+                return Ok(());
+            }
+            MirInstr::MapContains { name, map, key, .. } => {
+                let key = self.read(frame, key)?;
+                let map = self.read(frame, map)?;
+                let found = as_map(&map)?.iter().any(|(k, _)| same_key(k, &key));
+                (name, Value::Bool(found))
+            }
+            MirInstr::MapKeys { name, map, .. } | MirInstr::MapValues { name, map, .. } => {
+                let keys = matches!(instr, MirInstr::MapKeys { .. });
+                let map = self.read(frame, map)?;
+                let items = as_map(&map)?
+                    .iter()
+                    .map(|(k, v)| if keys { k.clone() } else { v.clone() })
+                    .collect();
+                (name, Value::Array(Rc::new(items)))
+            }
+
+            MirInstr::StrLen { name, string } => {
+                let len = as_str(&self.read(frame, string)?)?.len();
+                (name, Value::Int(len as i32))
+            }
+            MirInstr::StrContains { name, string, part } => {
+                let string = self.read(frame, string)?;
+                let part = self.read(frame, part)?;
+                (name, Value::Bool(as_str(&string)?.contains(as_str(&part)?)))
+            }
+            MirInstr::StrSplit { name, string, sep } => {
+                let string = self.read(frame, string)?;
+                let sep = self.read(frame, sep)?;
+                let (string, sep) = (as_str(&string)?, as_str(&sep)?);
+                let pieces = if sep.is_empty() {
+                    vec![Value::Str(string.into())]
+                } else {
+                    string
+                        .split(sep)
+                        .map(|piece| Value::Str(piece.into()))
+                        .collect()
+                };
+                (name, Value::Array(Rc::new(pieces)))
+            }
+            MirInstr::StrTrim { name, string } => {
+                let string = self.read(frame, string)?;
+                let trimmed = as_str(&string)?.trim_matches(|c| " \t\n\x0b\x0c\r".contains(c));
+                (name, Value::Str(trimmed.into()))
+            }
+            MirInstr::StrReplace {
+                name,
+                string,
+                from,
+                to,
+            } => {
+                let string = self.read(frame, string)?;
+                let from = self.read(frame, from)?;
+                let to = self.read(frame, to)?;
+                let (string, from, to) = (as_str(&string)?, as_str(&from)?, as_str(&to)?);
+                let replaced = if from.is_empty() {
+                    string.to_string()
+                } else {
+                    string.replace(from, to)
+                };
+                (name, Value::Str(replaced.into()))
+            }
+            MirInstr::StrSubstring {
+                name,
+                string,
+                start,
+                end,
+            } => {
+                let string = self.read(frame, string)?;
+                let bytes = as_str(&string)?.as_bytes();
+                let clamp = |i: i64| i.clamp(0, bytes.len() as i64) as usize;
+                let start = clamp(as_i64(&self.read(frame, start)?)?);
+                let end = clamp(as_i64(&self.read(frame, end)?)?).max(start);
+                let part = String::from_utf8_lossy(&bytes[start..end]);
+                (name, Value::Str(part.as_ref().into()))
+            }
+
+            MirInstr::Add(dest, lhs, rhs, _)
+            | MirInstr::Sub(dest, lhs, rhs, _)
+            | MirInstr::Mul(dest, lhs, rhs, _)
+            | MirInstr::Div(dest, lhs, rhs, _) => {
+                let op = match instr {
+                    MirInstr::Add(..) => "add",
+                    MirInstr::Sub(..) => "sub",
+                    MirInstr::Mul(..) => "mul",
+                    _ => "div",
+                };
+                let (lhs, rhs) = (self.read(frame, lhs)?, self.read(frame, rhs)?);
+                (dest, self.binary_op(op, &lhs, &rhs)?)
+            }
+            MirInstr::BinaryOp(op, dest, lhs, rhs, _) => {
+                let (lhs, rhs) = (self.read(frame, lhs)?, self.read(frame, rhs)?);
+                (dest, self.binary_op(op, &lhs, &rhs)?)
+            }
+            MirInstr::StringConcat { name, left, right } => {
+                let left = self.read(frame, left)?;
+                let right = self.read(frame, right)?;
+                let joined = format!("{}{}", as_str(&left)?, as_str(&right)?);
+                (name, Value::Str(joined.into()))
+            }
+
+            MirInstr::Assign { name, value, .. } => (name, self.read(frame, value)?),
+
+            MirInstr::TupleCreate { name, elements } => {
+                let items = self.read_all(frame, elements)?;
+                (name, Value::Tuple(Rc::new(items)))
+            }
+            MirInstr::TupleExtract {
+                name,
+                source: tuple,
+                index,
+            }
+            | MirInstr::TupleGet { name, tuple, index } => match self.read(frame, tuple)? {
+                Value::Tuple(items) => match items.get(*index) {
+                    Some(value) => (name, value.clone()),
+                    None => return error(out_of_bounds(*index as i64, items.len())),
+                },
+                other => return error(format!("not a tuple: {}", display(&other))),
+            },
+
+            MirInstr::Call {
+                dest, func, args, ..
+            } => {
+                let args = self.read_all(frame, args)?;
+                let mut values = self.call_any(func, args)?;
+                if dest.len() == 1 && values.len() > 1 {
+                    values = vec![Value::Tuple(Rc::new(values))];
+                }
+                for (i, name) in dest.iter().enumerate() {
+                    let value = values.get(i).cloned().unwrap_or(Value::Void);
+                    frame.insert(name.clone(), value);
+                }
+                return Ok(());
+            }
+
+            MirInstr::MakeClosure {
+                name,
+                func,
+                captures,
+            } => (
+                name,
+                Value::Closure(Rc::new(ClosureValue {
+                    func: func.clone(),
+                    captures: self.read_all(frame, captures)?,
+                })),
+            ),
+
+            MirInstr::CallClosure {
+                dest,
+                closure,
+                args,
+                ..
+            } => {
+                let Value::Closure(closure) = self.read(frame, closure)? else {
+                    return error(format!("not a closure: {}", closure));
+                };
+                let mut values = closure.captures.clone();
+                values.extend(self.read_all(frame, args)?);
+                let values = self.call(&closure.func, values)?;
+                for (i, name) in dest.iter().enumerate() {
+                    let value = values.get(i).cloned().unwrap_or(Value::Void);
+                    frame.insert(name.clone(), value);
+                }
+                return Ok(());
+            }
+
+            MirInstr::Print { values, .. } => {
+                let line: Vec<String> = values
+                    .iter()
+                    .map(|value| self.read(frame, value).map(|value| display(&value)))
+                    .collect::<Exec<_>>()?;
+                if writeln!(self.out, "{}", line.join(" ")).is_err() {
+                    return error("failed to write the output");
+                }
+                return Ok(());
+            }
+
+            MirInstr::StructInit {
+                name,
+                struct_name,
+                fields,
+            } => {
+                let mut values = Vec::new();
+                for (field, value) in fields {
+                    let value = self.read(frame, value)?;
+                    values.push((field.clone(), self.store(struct_name, field, value)));
+                }
+                // Fields are kept in declaration order
+                if let Some(layout) = self.program.structs.get(struct_name) {
+                    values.sort_by_key(|(field, _)| {
+                        layout.iter().position(|(name, _)| name == field)
+                    });
+                }
+                let value = StructValue {
+                    name: struct_name.clone(),
+                    fields: values,
+                };
+                (name, Value::Struct(Rc::new(RefCell::new(value))))
+            }
+            MirInstr::StructGet {
+                name,
+                struct_instance,
+                field,
+                ..
+            } => {
+                let instance = self.read(frame, struct_instance)?;
+                let instance = as_struct(&instance)?.borrow();
+                match instance.fields.iter().find(|(name, _)| name == field) {
+                    Some((_, Value::Weak(target))) => {
+                        let target = target.upgrade().map(|t| Box::new(Value::Struct(t)));
+                        (name, Value::Optional(target))
+                    }
+                    Some((_, value)) => (name, value.clone()),
+                    None => {
+                        return error(format!("{} has no field '{}'", instance.name, field));
+                    }
+                }
+            }
+            MirInstr::StructSet {
+                struct_instance,
+                struct_name,
+                field,
+                value,
+            } => {
+                let value = self.read(frame, value)?;
+                let value = self.store(struct_name, field, value);
+                let instance = self.read(frame, struct_instance)?;
+                let mut instance = as_struct(&instance)?.borrow_mut();
+                match instance.fields.iter().position(|(name, _)| name == field) {
+                    Some(i) => instance.fields[i].1 = value,
+                    None => instance.fields.push((field.clone(), value)),
+                }
+                return Ok(());
+            }
+
+            MirInstr::OptionalWrap { name, value, .. } => {
+                let value = match value {
+                    Some(value) => Some(Box::new(self.read(frame, value)?)),
+                    None => None,
+                };
+                (name, Value::Optional(value))
+            }
+            MirInstr::OptionalIsSome { name, optional } => {
+                let present = !matches!(self.read(frame, optional)?, Value::Optional(None));
+                (name, Value::Bool(present))
+            }
+            MirInstr::OptionalUnwrap { name, optional, .. } => match self.read(frame, optional)? {
+                Value::Optional(Some(value)) => (name, *value),
+                Value::Optional(None) => return error("unwrapped an absent optional"),
+                value => (name, value),
+            },
+            MirInstr::OptionalUnwrapOr {
+                name,
+                optional,
+                default,
+                ..
+            } => match self.read(frame, optional)? {
+                Value::Optional(Some(value)) => (name, *value),
+                Value::Optional(None) => (name, self.read(frame, default)?),
+                value => (name, value),
+            },
+
+            MirInstr::EnumInit {
+                name,
+                enum_name,
+                variant,
+                value,
+            } => {
+                let payload = match value {
+                    Some(value) => Some(self.read(frame, value)?),
+                    None => None,
+                };
+                let value = EnumValue {
+                    enum_name: enum_name.clone(),
+                    variant: variant.clone(),
+                    payload,
+                };
+                (name, Value::Enum(Rc::new(value)))
+            }
+            MirInstr::EnumMatch {
+                name,
+                enum_instance,
+                variant,
+                ..
+            } => match self.read(frame, enum_instance)? {
+                Value::Enum(value) => (name, Value::Bool(value.variant == *variant)),
+                other => return error(format!("not an enum value: {}", display(&other))),
+            },
+            MirInstr::EnumPayload {
+                name,
+                enum_instance,
+                variant,
+                ..
+            } => match self.read(frame, enum_instance)? {
+                Value::Enum(value) if value.variant == *variant => match &value.payload {
+                    Some(payload) => (name, payload.clone()),
+                    None => return error(format!("{} has no payload", variant)),
+                },
+                other => {
+                    return error(format!(
+                        "payload of {} read from {}",
+                        variant,
+                        display(&other)
+                    ))
+                }
+            },
+
+            other => return error(format!("unsupported instruction {}", other)),
+        };
+        frame.insert(name.clone(), value);
+        Ok(())
+    }
+
+    fn read_all(&self, frame: &HashMap<String, Value>, names: &[String]) -> Exec<Vec<Value>> {
+        names.iter().map(|name| self.read(frame, name)).collect()
+    }
+
+    /// Calls a builtin, tagged with its argument type ("toStr:float") or
+    /// not, or else a function of the program.
+    fn call_any(&mut self, func: &str, args: Vec<Value>) -> Exec<Vec<Value>> {
+        let name = func.split_once(':').map_or(func, |(name, _)| name);
+        if BUILTIN_FUNCTIONS.contains(&name) {
+            return builtin(name, &args).map(|value| vec![value]);
+        }
+        if self.program.externs.iter().any(|f| f.name == func) {
+            return error(format!(
+                "extern function '{}' can only be called from a compiled program",
+                func
+            ));
+        }
+        self.call(func, args)
+    }
+
+    fn binary_op(&self, op: &str, lhs: &Value, rhs: &Value) -> Exec<Value> {
+        let name = op.split_once(':').map_or(op, |(name, _)| name);
+        match name {
+            "eq" | "ne" => Ok(Value::Bool(equal(lhs, rhs) == (name == "eq"))),
+            "lt" | "le" | "gt" | "ge" => {
+                let Some(order) = compare(lhs, rhs) else {
+                    return error(format!(
+                        "cannot compare {} and {}",
+                        display(lhs),
+                        display(rhs)
+                    ));
+                };
+                let result = match name {
+                    "lt" => order.is_lt(),
+                    "le" => order.is_le(),
+                    "gt" => order.is_gt(),
+                    _ => order.is_ge(),
+                };
+                Ok(Value::Bool(result))
+            }
+            "and" | "or" => {
+                let (lhs, rhs) = (truthy(lhs)?, truthy(rhs)?);
+                Ok(Value::Bool(if name == "and" {
+                    lhs && rhs
+                } else {
+                    lhs || rhs
+                }))
+            }
+            "add" => match (lhs, rhs) {
+                (Value::Str(a), Value::Str(b)) => Ok(Value::Str(format!("{}{}", a, b).into())),
+                _ => self.arithmetic(name, lhs, rhs),
+            },
+            _ => self.arithmetic(name, lhs, rhs),
+        }
+    }
+
+    /// `add`, `sub`, `mul`, `div` or `mod` on two numbers. Int and Int64
+    /// wrap on overflow unless `overflow_checks` is set, and panic on
+    /// division by zero, like compiled code.
+    fn arithmetic(&self, op: &str, lhs: &Value, rhs: &Value) -> Exec<Value> {
+        macro_rules! int_op {
+            ($a:expr, $b:expr, $wrap:path) => {{
+                let (a, b) = ($a, $b);
+                if matches!(op, "div" | "mod") && b == 0 {
+                    return Err(Stop::Panic("division by zero".to_string()));
+                }
+                let checked = match op {
+                    "add" => a.checked_add(b),
+                    "sub" => a.checked_sub(b),
+                    "mul" => a.checked_mul(b),
+                    "div" => a.checked_div(b),
+                    "mod" => a.checked_rem(b),
+                    _ => return error(format!("unknown operation '{}'", op)),
+                };
+                match checked {
+                    Some(value) => $wrap(value),
+                    None if self.overflow_checks => {
+                        return Err(Stop::Panic("integer overflow".to_string()));
+                    }
+                    None => $wrap(match op {
+                        "add" => a.wrapping_add(b),
+                        "sub" => a.wrapping_sub(b),
+                        "mul" => a.wrapping_mul(b),
+                        "div" => a.wrapping_div(b),
+                        _ => a.wrapping_rem(b),
+                    }),
+                }
+            }};
+        }
+        let value = match (lhs, rhs) {
+            (Value::Int(a), Value::Int(b)) => int_op!(*a, *b, Value::Int),
+            (Value::Int64(a), Value::Int64(b)) => int_op!(*a, *b, Value::Int64),
+            (Value::Float(_) | Value::Int(_), Value::Float(_) | Value::Int(_)) => {
+                let (a, b) = (as_f64(lhs)?, as_f64(rhs)?);
+                Value::Float(match op {
+                    "add" => a + b,
+                    "sub" => a - b,
+                    "mul" => a * b,
+                    "div" => a / b,
+                    "mod" => a % b,
+                    _ => return error(format!("unknown operation '{}'", op)),
+                })
+            }
+            _ => {
+                return error(format!(
+                    "cannot {} {} and {}",
+                    op,
+                    display(lhs),
+                    display(rhs)
+                ))
+            }
+        };
+        Ok(value)
+    }
+}
+
+/// The conversion builtins, with the behavior of their runtime helpers.
+fn builtin(name: &str, args: &[Value]) -> Exec<Value> {
+    let Some(arg) = args.first() else {
+        return error(format!("{} needs an argument", name));
+    };
+    let value = match (name, arg) {
+        ("toStr", value) => Value::Str(display(value).into()),
+        ("format", value) => {
+            let digits = match args.get(1) {
+                Some(digits) => as_i64(digits)?.max(0) as usize,
+                None => return error("format needs a number of digits"),
+            };
+            Value::Str(format_float(as_f64(value)?, digits).into())
+        }
+        ("toInt", Value::Str(text)) => Value::Int(atoi(text)),
+        ("toInt", Value::Float(value)) => Value::Int(*value as i32),
+        ("toInt", Value::Bool(value)) => Value::Int(i32::from(*value)),
+        ("toFloat", Value::Str(text)) => Value::Float(float_prefix(text).map_or(0.0, |(v, _)| v)),
+        ("toFloat", Value::Int(_) | Value::Bool(_)) => Value::Float(as_f64(arg)?),
+        ("toInt64", Value::Int(value)) => Value::Int64(i64::from(*value)),
+        ("toInt64", Value::Float(value)) => Value::Int64(*value as i64),
+        ("parseInt", Value::Str(text)) => {
+            let trimmed = text.trim_start_matches(|c| " \t\n\x0b\x0c\r".contains(c));
+            let parsed = trimmed.parse::<i32>().ok();
+            Value::Optional(parsed.map(|value| Box::new(Value::Int(value))))
+        }
+        ("parseFloat", Value::Str(text)) => {
+            let parsed = float_prefix(text).filter(|(_, end)| *end == text.len());
+            Value::Optional(parsed.map(|(value, _)| Box::new(Value::Float(value))))
+        }
+        ("panic", message) => return Err(Stop::Panic(display(message))),
+        ("assert", cond) => {
+            if !truthy(cond)? {
+                let message = args.get(1).map(display).unwrap_or_default();
+                return Err(Stop::Panic(message));
+            }
+            Value::Void
+        }
+        // With no C code to hand it to, a C string is the text itself
+        ("cStr" | "toCStr", Value::Str(text)) => Value::Str(text.clone()),
+        ("fromCStr" | "takeCStr", Value::Str(text)) => {
+            Value::Optional(Some(Box::new(Value::Str(text.clone()))))
+        }
+        // toInt(Int), toFloat(Float) and toInt64(Int64) are the value itself
+        ("toInt" | "toFloat" | "toInt64", value) => value.clone(),
+        (name, value) => {
+            return error(format!("{} of {}", name, display(value)));
+        }
+    };
+    Ok(value)
+}
+
+/// C's `atoi`: leading whitespace, a sign and digits, 0 when there are none.
+fn atoi(text: &str) -> i32 {
+    let text = text.trim_start_matches(|c| " \t\n\x0b\x0c\r".contains(c));
+    let (negative, digits) = match text.as_bytes().first() {
+        Some(b'-') => (true, &text[1..]),
+        Some(b'+') => (false, &text[1..]),
+        _ => (false, text),
+    };
+    let mut value: i32 = 0;
+    for digit in digits.bytes().take_while(u8::is_ascii_digit) {
+        value = value.wrapping_mul(10).wrapping_add(i32::from(digit - b'0'));
+    }
+    if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    }
+}
+
+/// The number at the start of `text` as `strtod` reads it, with the byte
+/// offset where it ends, or `None` when there is none.
+fn float_prefix(text: &str) -> Option<(f64, usize)> {
+    let start = text.len()
+        - text
+            .trim_start_matches(|c| " \t\n\x0b\x0c\r".contains(c))
+            .len();
+    let bytes = text.as_bytes();
+    let mut end = start;
+    if matches!(bytes.get(end), Some(b'+' | b'-')) {
+        end += 1;
+    }
+    let digits_from = end;
+    while bytes.get(end).is_some_and(u8::is_ascii_digit) {
+        end += 1;
+    }
+    if bytes.get(end) == Some(&b'.') {
+        end += 1;
+        while bytes.get(end).is_some_and(u8::is_ascii_digit) {
+            end += 1;
+        }
+    }
+    if !text[digits_from..end].bytes().any(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let mut exp = end + 1;
+        if matches!(bytes.get(exp), Some(b'+' | b'-')) {
+            exp += 1;
+        }
+        if bytes.get(exp).is_some_and(u8::is_ascii_digit) {
+            while bytes.get(exp).is_some_and(u8::is_ascii_digit) {
+                exp += 1;
+            }
+            end = exp;
+        }
+    }
+    text[start..end].parse().ok().map(|value| (value, end))
+}
+
+/// `%.*f`, with C's spelling of the special values.
+fn format_float(value: f64, digits: usize) -> String {
+    if value.is_nan() {
+        "nan".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        format!("{:.*}", digits, value)
+    }
+}
+
+/// How `print` shows a value on its own.
+pub fn display(value: &Value) -> String {
+    match value {
+        Value::Str(text) => text.to_string(),
+        value => display_nested(value),
+    }
+}
+
+/// How `print` shows a value inside a collection: strings are quoted.
+fn display_nested(value: &Value) -> String {
+    let list = |items: Vec<String>| items.join(", ");
+    match value {
+        Value::Void => String::new(),
+        Value::Int(value) => value.to_string(),
+        Value::Int64(value) => value.to_string(),
+        Value::Float(value) => format_float(*value, 6),
+        Value::Bool(value) => value.to_string(),
+        Value::Str(text) => format!("\"{}\"", text),
+        Value::Array(items) => format!("[{}]", list(items.iter().map(display_nested).collect())),
+        Value::Map(entries) => format!(
+            "{{{}}}",
+            list(
+                entries
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", display_nested(k), display_nested(v)))
+                    .collect()
+            )
+        ),
+        Value::Tuple(items) => format!("({})", list(items.iter().map(display_nested).collect())),
+        Value::Range(start, end, inclusive) => format!(
+            "{}{}{}",
+            display_nested(start),
+            if *inclusive { "..=" } else { ".." },
+            display_nested(end)
+        ),
+        Value::Struct(instance) => {
+            let instance = instance.borrow();
+            let fields: Vec<String> = instance
+                .fields
+                .iter()
+                .map(|(name, value)| format!("{}: {}", name, display_nested(value)))
+                .collect();
+            format!("{} {{ {} }}", instance.name, fields.join(", "))
+        }
+        Value::Enum(value) => match &value.payload {
+            Some(payload) => format!(
+                "{}::{}({})",
+                value.enum_name,
+                value.variant,
+                display_nested(payload)
+            ),
+            None => format!("{}::{}", value.enum_name, value.variant),
+        },
+        Value::Optional(Some(value)) => display_nested(value),
+        Value::Optional(None) => "null".to_string(),
+        // Not followed: a weak field usually points back up a cycle
+        Value::Weak(target) => match target.upgrade() {
+            Some(target) => format!("{} {{ .. }}", target.borrow().name),
+            None => "null".to_string(),
+        },
+        Value::Closure(_) => "<closure>".to_string(),
+    }
+}
+
+/// The order of two numbers, strings or booleans; `None` for anything else.
+/// `lhs == rhs`: arrays and maps are equal when they are the same value, enum
+/// values when they hold the same variant with equal payloads.
+fn equal(lhs: &Value, rhs: &Value) -> bool {
+    match (lhs, rhs) {
+        (Value::Array(a), Value::Array(b)) => Rc::ptr_eq(a, b),
+        (Value::Map(a), Value::Map(b)) => Rc::ptr_eq(a, b),
+        (Value::Enum(a), Value::Enum(b)) => {
+            a.variant == b.variant
+                && match (&a.payload, &b.payload) {
+                    (Some(a), Some(b)) => equal(a, b),
+                    (a, b) => a.is_none() && b.is_none(),
+                }
+        }
+        _ => compare(lhs, rhs) == Some(std::cmp::Ordering::Equal),
+    }
+}
+
+fn compare(lhs: &Value, rhs: &Value) -> Option<std::cmp::Ordering> {
+    match (lhs, rhs) {
+        (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+        (Value::Int64(a), Value::Int64(b)) => Some(a.cmp(b)),
+        (Value::Float(_) | Value::Int(_), Value::Float(_) | Value::Int(_)) => {
+            as_f64(lhs).ok()?.partial_cmp(&as_f64(rhs).ok()?)
+        }
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (Value::Str(a), Value::Str(b)) => Some(a.as_bytes().cmp(b.as_bytes())),
+        (Value::Optional(None), Value::Optional(None)) => Some(std::cmp::Ordering::Equal),
+        (Value::Optional(Some(a)), Value::Optional(Some(b))) => compare(a, b),
+        (Value::Enum(a), Value::Enum(b)) if a.payload.is_none() && b.payload.is_none() => {
+            (a.variant == b.variant).then_some(std::cmp::Ordering::Equal)
+        }
+        (Value::Struct(a), Value::Struct(b)) if Rc::ptr_eq(a, b) => Some(std::cmp::Ordering::Equal),
+        _ => None,
+    }
+}
+
+fn same_key(lhs: &Value, rhs: &Value) -> bool {
+    compare(lhs, rhs) == Some(std::cmp::Ordering::Equal)
+}
+
+/// Sets `key` to `value`, keeping the place of a key already present.
+fn map_set(entries: &mut Vec<(Value, Value)>, key: Value, value: Value) {
+    match entries.iter_mut().find(|(k, _)| same_key(k, &key)) {
+        Some(entry) => entry.1 = value,
+        None => entries.push((key, value)),
+    }
+}
+
+fn truthy(value: &Value) -> Exec<bool> {
+    match value {
+        Value::Bool(value) => Ok(*value),
+        Value::Int(value) => Ok(*value != 0),
+        other => error(format!("not a Bool: {}", display(other))),
+    }
+}
+
+fn as_i64(value: &Value) -> Exec<i64> {
+    match value {
+        Value::Int(value) => Ok(i64::from(*value)),
+        Value::Int64(value) => Ok(*value),
+        other => error(format!("not an integer: {}", display(other))),
+    }
+}
+
+fn as_f64(value: &Value) -> Exec<f64> {
+    match value {
+        Value::Float(value) => Ok(*value),
+        Value::Int(value) => Ok(f64::from(*value)),
+        Value::Int64(value) => Ok(*value as f64),
+        Value::Bool(value) => Ok(f64::from(u8::from(*value))),
+        other => error(format!("not a number: {}", display(other))),
+    }
+}
+
+/// An integer of the same width as `like`.
+fn int_like(like: &Value, value: i64) -> Value {
+    match like {
+        Value::Int64(_) => Value::Int64(value),
+        _ => Value::Int(value as i32),
+    }
+}
+
+fn as_str(value: &Value) -> Exec<&str> {
+    match value {
+        Value::Str(text) => Ok(text),
+        other => error(format!("not a string: {}", display(other))),
+    }
+}
+
+fn as_array(value: &Value) -> Exec<&[Value]> {
+    match value {
+        Value::Array(items) => Ok(items),
+        other => error(format!("not an array: {}", display(other))),
+    }
+}
+
+fn as_map(value: &Value) -> Exec<&[(Value, Value)]> {
+    match value {
+        Value::Map(entries) => Ok(entries),
+        other => error(format!("not a map: {}", display(other))),
+    }
+}
+
+fn as_struct(value: &Value) -> Exec<&Rc<RefCell<StructValue>>> {
+    match value {
+        Value::Struct(instance) => Ok(instance),
+        other => error(format!("not a struct: {}", display(other))),
+    }
+}
+
+fn element<T>(items: &[T], index: i64) -> Exec<&T> {
+    match usize::try_from(index).ok().and_then(|i| items.get(i)) {
+        Some(item) => Ok(item),
+        None => Err(Stop::Panic(out_of_bounds(index, items.len()))),
+    }
+}
+
+fn out_of_bounds(index: i64, len: usize) -> String {
+    format!("index {} is out of bounds for length {}", index, len)
+}
+
+impl MirProgram {
+    /// Runs the program with the interpreter, printing to stdout and stderr,
+    /// and returns its exit code.
+    pub fn interpret(&self, overflow_checks: bool) -> Result<i32, InterpError> {
+        let (mut out, mut err) = (std::io::stdout(), std::io::stderr());
+        let mut interpreter = Interpreter::new(self, &mut out, &mut err);
+        interpreter.overflow_checks = overflow_checks;
+        interpreter.run()
+    }
+}
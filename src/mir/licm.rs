@@ -0,0 +1,245 @@
+use crate::mir::passes::MirPass;
+use crate::mir::{MirFunction, MirInstr, MirProgram};
+use std::collections::{HashMap, HashSet};
+
+/// Moves loop-invariant instructions out of loop headers into the block that
+/// enters the loop, so `ArrayLen`, `MapLen` and the like run once instead of
+/// once per iteration.
+///
+/// Loops are found from the back edges of the control flow graph. Only the
+/// header is looked at: it runs whenever the loop is entered, so moving an
+/// instruction out of it never runs something that wouldn't have run. An
+/// instruction moves when it has no side effects, writes a temp nothing else
+/// writes, and reads nothing the loop writes. Lengths also stay put when the
+/// loop grows or shrinks a collection, or calls a function that could change
+/// a global one.
+pub struct LoopInvariantMotion;
+
+impl MirPass for LoopInvariantMotion {
+    fn name(&self) -> &'static str {
+        "licm"
+    }
+
+    fn run(&mut self, program: &mut MirProgram) -> bool {
+        let mut changed = false;
+        for func in &mut program.functions {
+            changed |= hoist_function(func);
+        }
+        changed
+    }
+}
+
+/// Instructions that only compute a scalar from their operands.
+fn is_pure(instr: &MirInstr) -> bool {
+    match instr {
+        MirInstr::ConstInt { .. }
+        | MirInstr::ConstInt64 { .. }
+        | MirInstr::ConstFloat { .. }
+        | MirInstr::ConstBool { .. }
+        | MirInstr::ArrayLen { .. }
+        | MirInstr::MapLen { .. }
+        | MirInstr::StrLen { .. } => true,
+        // An untyped `add` of two strings is a concatenation
+        MirInstr::BinaryOp(op, ..) => op.contains(':'),
+        _ => false,
+    }
+}
+
+/// Instructions that change how many elements a collection holds.
+fn resizes(instr: &MirInstr) -> bool {
+    matches!(
+        instr,
+        MirInstr::ArrayPush { .. }
+            | MirInstr::ArrayPop { .. }
+            | MirInstr::ArrayInsert { .. }
+            | MirInstr::ArrayRemove { .. }
+            | MirInstr::ArrayClear { .. }
+            | MirInstr::MapSet { .. }
+            | MirInstr::MapInsert { .. }
+            | MirInstr::MapRemove { .. }
+            | MirInstr::MapClear { .. }
+    )
+}
+
+fn hoist_function(func: &mut MirFunction) -> bool {
+    // The old loop instructions carry their own control flow
+    let has_loop_instrs = func.blocks.iter().flat_map(|b| &b.instrs).any(|instr| {
+        matches!(
+            instr,
+            MirInstr::ForRange { .. }
+                | MirInstr::ForArray { .. }
+                | MirInstr::ForMap { .. }
+                | MirInstr::ForInfinite { .. }
+                | MirInstr::LoopBodyMarker { .. }
+                | MirInstr::ArrayLoopMarker { .. }
+                | MirInstr::MapLoopMarker { .. }
+        )
+    });
+    if has_loop_instrs || func.blocks.is_empty() {
+        return false;
+    }
+
+    let index: HashMap<&String, usize> = func
+        .blocks
+        .iter()
+        .enumerate()
+        .map(|(i, block)| (&block.label, i))
+        .collect();
+    let successors: Vec<Vec<usize>> = func
+        .blocks
+        .iter()
+        .map(|block| {
+            block
+                .terminator
+                .iter()
+                .flat_map(|instr| instr.targets())
+                .filter_map(|target| index.get(target).copied())
+                .collect()
+        })
+        .collect();
+    let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); func.blocks.len()];
+    for (i, succs) in successors.iter().enumerate() {
+        for &j in succs {
+            predecessors[j].push(i);
+        }
+    }
+
+    let mut reachable = HashSet::new();
+    let mut stack = vec![0];
+    while let Some(block) = stack.pop() {
+        if reachable.insert(block) {
+            stack.extend(&successors[block]);
+        }
+    }
+
+    let dominators = dominators(&successors, &predecessors);
+    let mut loops: HashMap<usize, HashSet<usize>> = HashMap::new();
+    for (tail, succs) in successors.iter().enumerate() {
+        if !reachable.contains(&tail) {
+            continue;
+        }
+        for &header in succs {
+            if dominators[tail].contains(&header) {
+                let body = loops
+                    .entry(header)
+                    .or_insert_with(|| HashSet::from([header]));
+                let mut stack = vec![tail];
+                while let Some(block) = stack.pop() {
+                    if body.insert(block) {
+                        stack.extend(&predecessors[block]);
+                    }
+                }
+            }
+        }
+    }
+    let mut loops: Vec<(usize, HashSet<usize>)> = loops.into_iter().collect();
+    loops.sort_by_key(|(header, body)| (body.len(), *header));
+
+    let mut def_counts: HashMap<String, usize> = HashMap::new();
+    for instr in func
+        .blocks
+        .iter()
+        .flat_map(|b| b.instrs.iter().chain(&b.terminator))
+    {
+        for name in instr.defs() {
+            *def_counts.entry(name.clone()).or_default() += 1;
+        }
+    }
+    let locals: HashSet<&String> = def_counts.keys().chain(&func.params).collect();
+
+    let mut moves: Vec<(usize, usize, Vec<usize>)> = Vec::new(); // (preheader, header, header positions)
+    for (header, body) in &loops {
+        // The loop must be entered from a single block that only jumps to it,
+        // and which codegen reaches first
+        let outside: Vec<usize> = predecessors[*header]
+            .iter()
+            .copied()
+            .filter(|pred| !body.contains(pred))
+            .collect();
+        let [preheader] = outside[..] else { continue };
+        if successors[preheader] != [*header] || preheader > *header {
+            continue;
+        }
+
+        let instrs = || body.iter().flat_map(|&b| func.blocks[b].instrs.iter());
+        let mut written: HashSet<&String> = instrs()
+            .chain(body.iter().flat_map(|&b| &func.blocks[b].terminator))
+            .flat_map(|instr| instr.defs())
+            .collect();
+        let loop_resizes = instrs().any(resizes);
+        let loop_calls = instrs()
+            .any(|instr| matches!(instr, MirInstr::Call { .. } | MirInstr::CallClosure { .. }));
+
+        let mut hoisted = Vec::new();
+        for (pos, instr) in func.blocks[*header].instrs.iter().enumerate() {
+            let [dest] = instr.defs()[..] else { continue };
+            let invariant = is_pure(instr)
+                && dest.starts_with('%')
+                && def_counts.get(dest) == Some(&1)
+                && instr.uses().iter().all(|used| !written.contains(used));
+            let length_safe = match instr {
+                MirInstr::ArrayLen { array: c, .. } | MirInstr::MapLen { map: c, .. } => {
+                    !loop_resizes && (!loop_calls || locals.contains(c))
+                }
+                _ => true,
+            };
+            if invariant && length_safe {
+                written.remove(dest);
+                hoisted.push(pos);
+            }
+        }
+        if !hoisted.is_empty() {
+            moves.push((preheader, *header, hoisted));
+        }
+    }
+
+    let changed = !moves.is_empty();
+    for (preheader, header, positions) in moves {
+        let mut moved = Vec::new();
+        for &pos in positions.iter().rev() {
+            moved.push(func.blocks[header].instrs.remove(pos));
+        }
+        moved.reverse();
+        let count = moved.len();
+        let end = func.blocks[preheader].instrs.len();
+        func.blocks[preheader].instrs.extend(moved);
+
+        let (header_label, preheader_label) = (
+            func.blocks[header].label.clone(),
+            func.blocks[preheader].label.clone(),
+        );
+        for (label, index, _) in &mut func.spans {
+            if *label == header_label {
+                *index -= positions.iter().filter(|&&pos| pos < *index).count();
+            } else if *label == preheader_label && *index >= end {
+                *index += count;
+            }
+        }
+    }
+    changed
+}
+
+/// The blocks dominating each block, itself included, with block 0 as the
+/// entry. Only meaningful for blocks the entry reaches.
+fn dominators(successors: &[Vec<usize>], predecessors: &[Vec<usize>]) -> Vec<HashSet<usize>> {
+    let all: HashSet<usize> = (0..successors.len()).collect();
+    let mut dominators = vec![all; successors.len()];
+    dominators[0] = HashSet::from([0]);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in 1..successors.len() {
+            let mut doms = predecessors[block]
+                .iter()
+                .map(|&pred| dominators[pred].clone())
+                .reduce(|acc, doms| acc.intersection(&doms).copied().collect())
+                .unwrap_or_default();
+            doms.insert(block);
+            if doms != dominators[block] {
+                dominators[block] = doms;
+                changed = true;
+            }
+        }
+    }
+    dominators
+}
@@ -1,4 +1,5 @@
-use crate::parser::ast::AstNode;
+use crate::mir::types::MirType;
+use crate::parser::ast::Span;
 
 /// Mid-level Intermediate Representation for the language
 /// Contains the core data structures used after AST parsing
@@ -8,8 +9,12 @@ use crate::parser::ast::AstNode;
 #[derive(Debug, Clone)]
 pub struct MirProgram {
     pub functions: Vec<MirFunction>, // All function definitions
+    pub externs: Vec<MirFunction>,   // `extern "C"` functions: signatures without blocks
     pub globals: Vec<MirInstr>,      // Global variable initializations
     pub is_main_entry: bool,         // Whether this is the main entry point file (requires main())
+    pub structs: std::collections::HashMap<String, Vec<(String, MirType)>>, // Struct name -> (field, type) in declaration order
+    pub enums: std::collections::HashMap<String, Vec<(String, Option<MirType>)>>, // Enum name -> (variant, payload type) in declaration order
+    pub weak_fields: std::collections::HashMap<String, Vec<String>>, // Struct name -> fields declared `weak`
 }
 
 /// A single function in MIR form
@@ -17,9 +22,12 @@ pub struct MirProgram {
 pub struct MirFunction {
     pub name: String, // Function identifier
     pub params: Vec<String>,
-    pub param_types: Vec<Option<String>>, // Parameter types (e.g., "Int", "Str", "Array", "Map")
-    pub return_type: Option<String>,
+    pub param_types: Vec<Option<MirType>>,
+    pub return_type: Option<MirType>,
     pub blocks: Vec<MirBlock>,
+    pub spans: Vec<(String, usize, Span)>, // (block label, instruction index, span): where each statement's instructions begin
+    pub arena: bool, // `#[arena]`: what it allocates is freed at once when it returns
+    pub export: bool, // `export "C"`: declared in the header `--emit=header` writes
 }
 
 /// A basic block - sequence of instructions with single entry/exit
@@ -51,6 +59,11 @@ pub enum MirInstr {
     ConstInt {
         name: String,
         value: i32,
+        ty: MirType,
+    },
+    ConstInt64 {
+        name: String,
+        value: i64,
     },
     ConstFloat {
         name: String,
@@ -99,14 +112,76 @@ pub enum MirInstr {
         index: String,
         value: String,
     },
+    // `left + right` on arrays: a new array. `ty` is the arrays' type.
+    ArrayConcat {
+        name: String,
+        left: String,
+        right: String,
+        ty: MirType,
+    },
+    // Growable arrays: the operations change `array`, a variable, in place.
+    // `ty` is the array's type (e.g. `Array<Int>`).
+    ArrayPush {
+        array: String,
+        value: String,
+        ty: MirType,
+    },
+    ArrayPop {
+        name: String, // an optional: absent when the array is empty
+        array: String,
+        ty: MirType,
+    },
+    ArrayInsert {
+        array: String,
+        index: String,
+        value: String,
+        ty: MirType,
+    },
+    ArrayRemove {
+        name: String,
+        array: String,
+        index: String,
+        ty: MirType,
+    },
+    ArrayClear {
+        array: String,
+        ty: MirType,
+    },
+    ArraySort {
+        array: String,
+        ty: MirType,
+    },
+    ArrayReverse {
+        array: String,
+        ty: MirType,
+    },
+    // Arrays of numbers; these only read `array`, which can be any array value
+    ArraySum {
+        name: String,
+        array: String,
+        ty: MirType,
+    },
+    ArrayMin {
+        name: String, // an optional: absent when the array is empty
+        array: String,
+        ty: MirType,
+    },
+    ArrayMax {
+        name: String,
+        array: String,
+        ty: MirType,
+    },
     MapLen {
         name: String,
         map: String,
     },
+    // `map[key]`: an optional, absent when the key is missing.
+    // `ty` is the map's type (e.g. `Map<String, Int>`).
     MapGet {
         name: String,
         map: String,
         key: String,
+        ty: MirType,
     },
     MapGetPair {
         name: String,
@@ -118,15 +193,79 @@ pub enum MirInstr {
         key: String,
         value: String,
     },
+    // Map methods; insert, remove and clear change `map`, a variable, in place
+    MapInsert {
+        map: String,
+        key: String,
+        value: String,
+        ty: MirType,
+    },
+    MapRemove {
+        name: String, // an optional: absent when the key is missing
+        map: String,
+        key: String,
+        ty: MirType,
+    },
+    MapClear {
+        map: String,
+        ty: MirType,
+    },
+    MapContains {
+        name: String,
+        map: String,
+        key: String,
+        ty: MirType,
+    },
+    MapKeys {
+        name: String, // a new array
+        map: String,
+        ty: MirType,
+    },
+    MapValues {
+        name: String,
+        map: String,
+        ty: MirType,
+    },
+    // String methods; these only read `string`, each result is a new value
+    StrLen {
+        name: String,
+        string: String,
+    },
+    StrContains {
+        name: String,
+        string: String,
+        part: String,
+    },
+    StrSplit {
+        name: String, // a new array of new strings
+        string: String,
+        sep: String,
+    },
+    StrTrim {
+        name: String,
+        string: String,
+    },
+    StrReplace {
+        name: String,
+        string: String,
+        from: String,
+        to: String,
+    },
+    StrSubstring {
+        name: String,
+        string: String,
+        start: String,
+        end: String,
+    },
 
     // Arithmetic operations
-    Add(String, String, String), // (dest, lhs, rhs)
-    Sub(String, String, String),
-    Mul(String, String, String),
-    Div(String, String, String),
+    Add(String, String, String, MirType), // (dest, lhs, rhs, type of dest)
+    Sub(String, String, String, MirType),
+    Mul(String, String, String, MirType),
+    Div(String, String, String, MirType),
 
     // Generic binary operations (covers arithmetic and comparisons)
-    BinaryOp(String, String, String, String), // (op, dest, lhs, rhs)
+    BinaryOp(String, String, String, String, MirType), // (op, dest, lhs, rhs, type of dest)
     StringConcat {
         name: String,
         left: String,
@@ -138,6 +277,7 @@ pub enum MirInstr {
         name: String,
         value: String,
         mutable: bool,
+        ty: MirType,
     },
 
     // Tuple operations
@@ -164,10 +304,29 @@ pub enum MirInstr {
         dest: Vec<String>, // multiple temps for tuple destructuring
         func: String,      // function name
         args: Vec<String>, // arguments (as temp names)
+        ty: MirType,       // what `func` returns, a tuple for several `dest`s
+    },
+    /// A closure value: `func` is the function its body was lifted into, taking
+    /// the captured values ahead of the closure's own parameters
+    MakeClosure {
+        name: String,
+        func: String,
+        captures: Vec<String>,
+    },
+    /// Calls the closure value `closure` of type `ty`
+    CallClosure {
+        dest: Vec<String>,
+        closure: String,
+        args: Vec<String>,
+        ty: MirType,
     },
     Return {
         values: Vec<String>,
     },
+    /// `panic(message)`: prints the message and exits the program
+    Panic {
+        message: String,
+    },
 
     // Control flow
     Jump {
@@ -182,6 +341,7 @@ pub enum MirInstr {
     // I/O operations
     Print {
         values: Vec<String>,
+        types: Vec<Option<MirType>>, // Per value: its type if it is an array or map, which codegen formats at run time
     },
 
     // Struct and enum operations
@@ -193,23 +353,56 @@ pub enum MirInstr {
     StructGet {
         name: String,
         struct_instance: String,
+        struct_name: String,
         field: String,
     },
     StructSet {
         struct_instance: String,
+        struct_name: String,
         field: String,
         value: String,
     },
 
+    // Optional values. `ty` is the optional's type (e.g. `Int?`).
+    // Scalars are lowered to a tagged { i1, T } pair, heap types to a nullable pointer.
+    OptionalWrap {
+        name: String,
+        value: Option<String>, // None for null
+        ty: MirType,
+    },
+    OptionalIsSome {
+        name: String,
+        optional: String,
+    },
+    OptionalUnwrap {
+        name: String,
+        optional: String,
+        ty: MirType,
+    },
+    OptionalUnwrapOr {
+        name: String,
+        optional: String,
+        default: String,
+        ty: MirType,
+    },
+
+    // Enum values. The variant's position in the declaration is its discriminant.
     EnumInit {
         name: String,
         enum_name: String,
         variant: String,
-        value: Option<String>,
+        value: Option<String>, // the payload, if the variant has one
     },
     EnumMatch {
-        name: String,
+        name: String, // Bool: does `enum_instance` hold `variant`?
         enum_instance: String,
+        enum_name: String,
+        variant: String,
+    },
+    EnumPayload {
+        name: String, // borrows the payload of `enum_instance`, which must hold `variant`
+        enum_instance: String,
+        enum_name: String,
         variant: String,
     },
 
@@ -304,6 +497,11 @@ pub enum MirTerminator {
         values: Vec<String>, // return values
     },
 
+    /// Stop the program with an error message
+    Panic {
+        message: String, // string temp/variable
+    },
+
     /// Unconditional jump to another block
     Jump {
         target: String, // block label
@@ -324,149 +522,209 @@ impl MirInstr {
             _ => None,
         }
     }
-}
 
-// Implement Display trait for MirProgram as human readable format
-// No production usecase
-// impl std::fmt::Display for MirProgram {
-//     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-//         // Print global variables
-//         if !self.globals.is_empty() {
-//             writeln!(f, "Globals:")?;
-//             for instr in &self.globals {
-//                 writeln!(f, "  {}", instr)?;
-//             }
-//             writeln!(f)?;
-//         }
-
-//         // Print functions
-//         for func in &self.functions {
-//             writeln!(
-//                 f,
-//                 "Function {}({}) -> {}",
-//                 func.name,
-//                 func.params.join(", "),
-//                 func.return_type.clone().unwrap_or("Void".to_string())
-//             )?;
-//             for block in &func.blocks {
-//                 writeln!(f, "  {}:", block.label)?;
-//                 for instr in &block.instrs {
-//                     writeln!(f, "    {}", instr)?;
-//                 }
-//                 if let Some(term) = &block.terminator {
-//                     writeln!(f, "    {}", term)?;
-//                 }
-//             }
-//             writeln!(f)?;
-//         }
-//         Ok(())
-//     }
-// }
-
-// impl std::fmt::Display for MirInstr {
-//     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-//         match self {
-//             MirInstr::ConstInt { name, value } => write!(f, "Let {} = {}", name, value),
-//             MirInstr::ConstBool { name, value } => write!(f, "Let {} = {}", name, value),
-//             MirInstr::ConstString { name, value } => write!(f, "Let {} = \"{}\"", name, value),
-//             MirInstr::Array { name, elements } => {
-//                 write!(f, "Let {} = [{}]", name, elements.join(", "))
-//             }
-//             MirInstr::Map { name, entries } => {
-//                 let entries_str: Vec<String> = entries
-//                     .iter()
-//                     .map(|(k, v)| format!("\"{}\": {}", k, v))
-//                     .collect();
-//                 write!(f, "Let {} = {{ {} }}", name, entries_str.join(", "))
-//             }
-//             MirInstr::Assign {
-//                 name,
-//                 value,
-//                 mutable,
-//             } => {
-//                 let mut_str = if *mutable { "mut " } else { "" };
-//                 write!(f, "{}{} = {}", mut_str, name, value)
-//             }
-//             MirInstr::Arg { name } => write!(f, "Arg {}", name),
-//             MirInstr::Return { values } => write!(f, "ret ({})", values.join(", ")),
-//             MirInstr::Call { dest, func, args } => {
-//                 if dest.len() == 1 {
-//                     write!(f, "Let {} = {}({})", dest[0], func, args.join(", "))
-//                 } else {
-//                     write!(f, "Let {} = {}({})", dest.join(", "), func, args.join(", "))
-//                 }
-//             }
-//             MirInstr::Add(dest, lhs, rhs) => write!(f, "Let {} = add {}, {}", dest, lhs, rhs),
-//             MirInstr::Sub(dest, lhs, rhs) => write!(f, "Let {} = sub {}, {}", dest, lhs, rhs),
-//             MirInstr::Mul(dest, lhs, rhs) => write!(f, "Let {} = mul {}, {}", dest, lhs, rhs),
-//             MirInstr::Div(dest, lhs, rhs) => write!(f, "Let {} = div {}, {}", dest, lhs, rhs),
-
-//             MirInstr::BinaryOp(op, dest, lhs, rhs) => match op.as_str() {
-//                 "gt" => write!(f, "Let {} = gt {}, {}", dest, lhs, rhs),
-//                 "lt" => write!(f, "Let {} = lt {}, {}", dest, lhs, rhs),
-//                 "%" => write!(f, "Let {} = rem {}, {}", dest, lhs, rhs), // <-- add this
-//                 _ => write!(f, "Let {} = {} {}, {}", dest, op, lhs, rhs),
-//             },
-
-//             MirInstr::Jump { target } => write!(f, "jump {}", target),
-//             MirInstr::CondJump {
-//                 cond,
-//                 then_block,
-//                 else_block,
-//             } => {
-//                 write!(f, "if {} then {} else {}", cond, then_block, else_block)
-//             }
-//             MirInstr::Print { values } => write!(f, "print({})", values.join(", ")),
-
-//             MirInstr::StructInit {
-//                 name,
-//                 struct_name,
-//                 fields,
-//             } => {
-//                 let f_str: Vec<String> = fields
-//                     .iter()
-//                     .map(|(k, v)| format!("{}: {}", k, v))
-//                     .collect();
-//                 write!(f, "{} = {} {{ {} }}", name, struct_name, f_str.join(", "))
-//             }
-//             MirInstr::EnumInit {
-//                 name,
-//                 enum_name,
-//                 variant,
-//                 value,
-//             } => {
-//                 if let Some(v) = value {
-//                     write!(f, "{} = {}::{}({})", name, enum_name, variant, v)
-//                 } else {
-//                     write!(f, "{} = {}::{}", name, enum_name, variant)
-//                 }
-//             }
-
-//             MirInstr::TupleExtract {
-//                 name,
-//                 source,
-//                 index,
-//             } => {
-//                 write!(f, "Let {} = extract({}, {})", name, source, index)
-//             }
-//             MirInstr::ArrayLen { name, array } => {
-//                 write!(f, "Let {} = len({})", name, array)
-//             }
-//             MirInstr::ArrayGet { name, array, index } => {
-//                 write!(f, "Let {} = {}[{}]", name, array, index)
-//             }
-//             MirInstr::RangeCreate {
-//                 name,
-//                 start,
-//                 end,
-//                 inclusive,
-//             } => {
-//                 let op = if *inclusive { "..=" } else { ".." };
-//                 write!(f, "Let {} = {}{}{}", name, start, op, end)
-//             }
-
-//             // Catch-all for any future variants
-//             _ => write!(f, "<unimplemented MIR instruction>"),
-//         }
-//     }
-// }
+    /// The values this instruction writes.
+    pub fn defs(&self) -> Vec<&String> {
+        match self {
+            MirInstr::ConstInt { name, .. }
+            | MirInstr::ConstInt64 { name, .. }
+            | MirInstr::ConstFloat { name, .. }
+            | MirInstr::ConstBool { name, .. }
+            | MirInstr::ConstString { name, .. }
+            | MirInstr::Array { name, .. }
+            | MirInstr::Map { name, .. }
+            | MirInstr::RangeCreate { name, .. }
+            | MirInstr::ArrayLen { name, .. }
+            | MirInstr::ArrayGet { name, .. }
+            | MirInstr::ArrayConcat { name, .. }
+            | MirInstr::ArrayPop { name, .. }
+            | MirInstr::ArrayRemove { name, .. }
+            | MirInstr::ArraySum { name, .. }
+            | MirInstr::ArrayMin { name, .. }
+            | MirInstr::ArrayMax { name, .. }
+            | MirInstr::MapLen { name, .. }
+            | MirInstr::MapGet { name, .. }
+            | MirInstr::MapGetPair { name, .. }
+            | MirInstr::MapRemove { name, .. }
+            | MirInstr::MapContains { name, .. }
+            | MirInstr::MapKeys { name, .. }
+            | MirInstr::MapValues { name, .. }
+            | MirInstr::StrLen { name, .. }
+            | MirInstr::StrContains { name, .. }
+            | MirInstr::StrSplit { name, .. }
+            | MirInstr::StrTrim { name, .. }
+            | MirInstr::StrReplace { name, .. }
+            | MirInstr::StrSubstring { name, .. }
+            | MirInstr::StringConcat { name, .. }
+            | MirInstr::Assign { name, .. }
+            | MirInstr::TupleCreate { name, .. }
+            | MirInstr::TupleExtract { name, .. }
+            | MirInstr::TupleGet { name, .. }
+            | MirInstr::Arg { name }
+            | MirInstr::StructInit { name, .. }
+            | MirInstr::StructGet { name, .. }
+            | MirInstr::OptionalWrap { name, .. }
+            | MirInstr::OptionalIsSome { name, .. }
+            | MirInstr::OptionalUnwrap { name, .. }
+            | MirInstr::OptionalUnwrapOr { name, .. }
+            | MirInstr::EnumInit { name, .. }
+            | MirInstr::EnumMatch { name, .. }
+            | MirInstr::EnumPayload { name, .. }
+            | MirInstr::MakeClosure { name, .. } => vec![name],
+            MirInstr::Add(dest, ..)
+            | MirInstr::Sub(dest, ..)
+            | MirInstr::Mul(dest, ..)
+            | MirInstr::Div(dest, ..)
+            | MirInstr::BinaryOp(_, dest, ..) => vec![dest],
+            MirInstr::Call { dest, .. } | MirInstr::CallClosure { dest, .. } => {
+                dest.iter().collect()
+            }
+            MirInstr::ForRange { var, .. } | MirInstr::ForArray { var, .. } => vec![var],
+            MirInstr::ForMap {
+                key_var, value_var, ..
+            } => vec![key_var, value_var],
+            MirInstr::LoadArrayElement { dest, .. } => vec![dest],
+            MirInstr::LoadMapPair {
+                key_dest, val_dest, ..
+            } => vec![key_dest, val_dest],
+            _ => vec![],
+        }
+    }
+
+    /// The values this instruction reads.
+    pub fn uses(&self) -> Vec<&String> {
+        match self {
+            MirInstr::IncRef { value } | MirInstr::DecRef { value } => vec![value],
+            MirInstr::Array { elements, .. } | MirInstr::TupleCreate { elements, .. } => {
+                elements.iter().collect()
+            }
+            MirInstr::Map { entries, .. } => entries.iter().flat_map(|(k, v)| [k, v]).collect(),
+            MirInstr::RangeCreate { start, end, .. } => vec![start, end],
+            MirInstr::ArrayLen { array, .. }
+            | MirInstr::ArrayPop { array, .. }
+            | MirInstr::ArrayClear { array, .. }
+            | MirInstr::ArraySort { array, .. }
+            | MirInstr::ArrayReverse { array, .. }
+            | MirInstr::ArraySum { array, .. }
+            | MirInstr::ArrayMin { array, .. }
+            | MirInstr::ArrayMax { array, .. } => vec![array],
+            MirInstr::ArrayGet { array, index, .. }
+            | MirInstr::ArrayRemove { array, index, .. } => {
+                vec![array, index]
+            }
+            MirInstr::ArraySet {
+                array,
+                index,
+                value,
+            }
+            | MirInstr::ArrayInsert {
+                array,
+                index,
+                value,
+                ..
+            } => vec![array, index, value],
+            MirInstr::ArrayPush { array, value, .. } => vec![array, value],
+            MirInstr::ArrayConcat { left, right, .. }
+            | MirInstr::StringConcat { left, right, .. } => {
+                vec![left, right]
+            }
+            MirInstr::MapLen { map, .. }
+            | MirInstr::MapClear { map, .. }
+            | MirInstr::MapKeys { map, .. }
+            | MirInstr::MapValues { map, .. } => vec![map],
+            MirInstr::MapGet { map, key, .. }
+            | MirInstr::MapRemove { map, key, .. }
+            | MirInstr::MapContains { map, key, .. } => vec![map, key],
+            MirInstr::MapGetPair { map, index, .. } => vec![map, index],
+            MirInstr::MapSet { map, key, value }
+            | MirInstr::MapInsert {
+                map, key, value, ..
+            } => {
+                vec![map, key, value]
+            }
+            MirInstr::StrLen { string, .. } | MirInstr::StrTrim { string, .. } => vec![string],
+            MirInstr::StrContains { string, part, .. } => vec![string, part],
+            MirInstr::StrSplit { string, sep, .. } => vec![string, sep],
+            MirInstr::StrReplace {
+                string, from, to, ..
+            } => vec![string, from, to],
+            MirInstr::StrSubstring {
+                string, start, end, ..
+            } => vec![string, start, end],
+            MirInstr::Add(_, lhs, rhs, _)
+            | MirInstr::Sub(_, lhs, rhs, _)
+            | MirInstr::Mul(_, lhs, rhs, _)
+            | MirInstr::Div(_, lhs, rhs, _)
+            | MirInstr::BinaryOp(_, _, lhs, rhs, _) => vec![lhs, rhs],
+            MirInstr::Assign { value, .. } => vec![value],
+            MirInstr::TupleExtract { source, .. } => vec![source],
+            MirInstr::TupleGet { tuple, .. } => vec![tuple],
+            MirInstr::Call { args, .. } => args.iter().collect(),
+            MirInstr::MakeClosure { captures, .. } => captures.iter().collect(),
+            MirInstr::CallClosure { closure, args, .. } => {
+                std::iter::once(closure).chain(args).collect()
+            }
+            MirInstr::Return { values } | MirInstr::Print { values, .. } => values.iter().collect(),
+            MirInstr::Panic { message } => vec![message],
+            MirInstr::CondJump { cond, .. } => vec![cond],
+            MirInstr::StructInit { fields, .. } => fields.iter().map(|(_, v)| v).collect(),
+            MirInstr::StructGet {
+                struct_instance, ..
+            } => vec![struct_instance],
+            MirInstr::StructSet {
+                struct_instance,
+                value,
+                ..
+            } => vec![struct_instance, value],
+            MirInstr::OptionalWrap { value, .. } | MirInstr::EnumInit { value, .. } => {
+                value.iter().collect()
+            }
+            MirInstr::OptionalIsSome { optional, .. }
+            | MirInstr::OptionalUnwrap { optional, .. } => {
+                vec![optional]
+            }
+            MirInstr::OptionalUnwrapOr {
+                optional, default, ..
+            } => vec![optional, default],
+            MirInstr::EnumMatch { enum_instance, .. }
+            | MirInstr::EnumPayload { enum_instance, .. } => vec![enum_instance],
+            MirInstr::ForRange { start, end, .. } => vec![start, end],
+            MirInstr::ForArray { array, .. } => vec![array],
+            MirInstr::ForMap { map, .. } => vec![map],
+            MirInstr::LoadArrayElement { array, index, .. } => vec![array, index],
+            MirInstr::LoadMapPair { map, index, .. } => vec![map, index],
+            _ => vec![],
+        }
+    }
+
+    /// Labels of the blocks this instruction can transfer control to.
+    pub fn targets(&self) -> Vec<&String> {
+        match self {
+            MirInstr::Jump { target }
+            | MirInstr::Break { target }
+            | MirInstr::Continue { target } => vec![target],
+            MirInstr::CondJump {
+                then_block,
+                else_block,
+                ..
+            } => vec![then_block, else_block],
+            MirInstr::ForRange {
+                body_block,
+                exit_block,
+                ..
+            }
+            | MirInstr::ForArray {
+                body_block,
+                exit_block,
+                ..
+            }
+            | MirInstr::ForMap {
+                body_block,
+                exit_block,
+                ..
+            } => vec![body_block, exit_block],
+            MirInstr::ForInfinite { body_block } => vec![body_block],
+            _ => vec![],
+        }
+    }
+}
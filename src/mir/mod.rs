@@ -1,10 +1,24 @@
 pub mod builder;
+pub mod const_fold;
 pub mod declarations;
+pub mod escape;
 pub mod expresssions;
+pub mod header;
+pub mod interp;
+pub mod licm;
 pub mod mir;
+pub mod passes;
 pub mod statements;
+pub mod text;
+pub mod types;
+pub mod verify;
 
+pub use interp::{InterpError, Interpreter};
 pub use mir::{MirBlock, MirFunction, MirInstr, MirProgram};
+pub use passes::{MirPass, PassManager};
+pub use text::MirTextError;
+pub use types::MirType;
+pub use verify::{VerifyError, VerifyErrorKind};
 
 #[cfg(test)]
 mod tests;
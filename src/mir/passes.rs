@@ -0,0 +1,64 @@
+use crate::mir::const_fold::ConstFold;
+use crate::mir::licm::LoopInvariantMotion;
+use crate::mir::MirProgram;
+
+/// A rewrite of a whole MIR program that keeps its meaning.
+pub trait MirPass {
+    /// Names the pass in the error when it breaks the MIR.
+    fn name(&self) -> &'static str;
+
+    /// Rewrites `program`, returning whether anything changed.
+    fn run(&mut self, program: &mut MirProgram) -> bool;
+}
+
+/// Runs passes over a finished program, in the order they were added.
+pub struct PassManager {
+    passes: Vec<Box<dyn MirPass>>,
+}
+
+impl PassManager {
+    pub fn new() -> Self {
+        Self { passes: Vec::new() }
+    }
+
+    /// The passes the compiler runs on every program before codegen.
+    pub fn with_default_passes() -> Self {
+        let mut manager = Self::new();
+        manager.add(ConstFold);
+        manager.add(LoopInvariantMotion);
+        manager
+    }
+
+    pub fn add(&mut self, pass: impl MirPass + 'static) {
+        self.passes.push(Box::new(pass));
+    }
+
+    /// Runs every pass once, returning whether any of them changed the
+    /// program. Debug builds verify the MIR after each pass that did.
+    pub fn run(&mut self, program: &mut MirProgram) -> bool {
+        let mut changed = false;
+        for pass in &mut self.passes {
+            if !pass.run(program) {
+                continue;
+            }
+            changed = true;
+            if cfg!(debug_assertions) {
+                if let Err(errors) = program.verify() {
+                    let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                    panic!(
+                        "invalid MIR after {}:\n{}",
+                        pass.name(),
+                        messages.join("\n")
+                    );
+                }
+            }
+        }
+        changed
+    }
+}
+
+impl Default for PassManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
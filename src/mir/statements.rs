@@ -1,8 +1,8 @@
 use crate::lexar::token::TokenType;
 use crate::mir::builder::MirBuilder;
-use crate::mir::expresssions::build_expression;
-use crate::mir::{MirBlock, MirInstr};
-use crate::parser::ast::{AstNode, Pattern};
+use crate::mir::expresssions::{binary_result_type, build_expression, determine_op_type};
+use crate::mir::{MirBlock, MirInstr, MirType};
+use crate::parser::ast::{AstNode, MatchArm, MatchPattern, Pattern, TypeNode};
 
 pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut MirBlock) {
     match stmt {
@@ -13,7 +13,7 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
             value,
             mutable,
             is_ref_counted,
-            ..
+            type_annotation,
         } => {
             // Build MIR for the right-hand side expression.
             let value_tmp = build_expression(builder, value, block);
@@ -21,16 +21,31 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
             match pattern {
                 // Simple variable assignment.
                 Pattern::Identifier(name) => {
+                    // The variable's type: the value's, or the type the analyzer inferred
+                    // (which also gives `[]` and `{}` their element types)
+                    let var_type = match type_annotation {
+                        Some(ty @ (TypeNode::Array(_) | TypeNode::Map(..))) => Some(ty.clone()),
+                        _ => builder
+                            .mir_symbol_table
+                            .get(&value_tmp)
+                            .cloned()
+                            .or_else(|| match type_annotation {
+                                Some(ty @ (TypeNode::Struct(..) | TypeNode::Enum(..))) => {
+                                    Some(ty.clone())
+                                }
+                                _ => None,
+                            }),
+                    };
                     block.instrs.push(MirInstr::Assign {
                         name: name.clone(),
                         value: value_tmp.clone(),
                         mutable: *mutable,
+                        ty: var_type
+                            .as_ref()
+                            .map_or(MirType::Void, |ty| builder.mir_type(ty)),
                     });
-
-                    // Track variable type in mir_symbol_table
-                    // Copy type from value_tmp if available
-                    if let Some(value_type) = builder.mir_symbol_table.get(&value_tmp).cloned() {
-                        builder.mir_symbol_table.insert(name.clone(), value_type);
+                    if let Some(var_type) = var_type {
+                        builder.mir_symbol_table.insert(name.clone(), var_type);
                     }
                 }
                 // Tuple destructuring: let (a, b) = expr;
@@ -38,16 +53,24 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                     for (i, pattern) in patterns.iter().enumerate() {
                         if let Pattern::Identifier(name) = pattern {
                             // Extract each tuple element into a temporary variable.
+                            let extract_tmp = builder.next_tmp();
                             block.instrs.push(MirInstr::TupleExtract {
-                                name: builder.next_tmp(),
+                                name: extract_tmp.clone(),
                                 source: value_tmp.clone(),
                                 index: i,
                             });
+                            let elem_type = tuple_element_type(builder, &value_tmp, i);
                             block.instrs.push(MirInstr::Assign {
                                 name: name.clone(),
-                                value: builder.next_tmp(),
+                                value: extract_tmp,
                                 mutable: *mutable,
+                                ty: elem_type
+                                    .as_ref()
+                                    .map_or(MirType::Void, |ty| builder.mir_type(ty)),
                             });
+                            if let Some(elem_type) = elem_type {
+                                builder.mir_symbol_table.insert(name.clone(), elem_type);
+                            }
                         }
                     }
                 }
@@ -63,33 +86,41 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
             match pattern {
                 // Simple variable assignment.
                 Pattern::Identifier(name) => {
-                    block.instrs.push(MirInstr::Assign {
-                        name: name.clone(),
-                        value: value_tmp.clone(),
-                        mutable: true,
-                    });
-
                     // Track variable type in mir_symbol_table for re-assignments
                     // Copy type from value_tmp if available
                     if let Some(value_type) = builder.mir_symbol_table.get(&value_tmp).cloned() {
                         builder.mir_symbol_table.insert(name.clone(), value_type);
                     }
+                    block.instrs.push(MirInstr::Assign {
+                        name: name.clone(),
+                        value: value_tmp.clone(),
+                        mutable: true,
+                        ty: builder.value_type(name),
+                    });
                 }
                 // Tuple destructuring assignment.
                 Pattern::Tuple(patterns) => {
                     for (i, pattern) in patterns.iter().enumerate() {
                         if let Pattern::Identifier(name) = pattern {
                             // Extract each tuple element into a temporary variable.
+                            let extract_tmp = builder.next_tmp();
                             block.instrs.push(MirInstr::TupleExtract {
-                                name: builder.next_tmp(),
+                                name: extract_tmp.clone(),
                                 source: value_tmp.clone(),
                                 index: i,
                             });
+                            let elem_type = tuple_element_type(builder, &value_tmp, i);
                             block.instrs.push(MirInstr::Assign {
                                 name: name.clone(),
-                                value: builder.next_tmp(),
+                                value: extract_tmp,
                                 mutable: true,
+                                ty: elem_type
+                                    .as_ref()
+                                    .map_or(MirType::Void, |ty| builder.mir_type(ty)),
                             });
+                            if let Some(elem_type) = elem_type {
+                                builder.mir_symbol_table.insert(name.clone(), elem_type);
+                            }
                         }
                     }
                 }
@@ -126,60 +157,55 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                 let result_tmp = builder.next_tmp();
 
                 // Generate: result_tmp = name <op> rhs_tmp
+                let ty = builder.mir_type(&binary_result_type(op_str, &op_type));
                 block.instrs.push(MirInstr::BinaryOp(
                     format!("{}:{}", op_str, op_type),
                     result_tmp.clone(),
                     name.clone(),
                     rhs_tmp,
+                    ty.clone(),
                 ));
 
                 // Generate: name = result_tmp
                 block.instrs.push(MirInstr::Assign {
                     name: name.clone(),
-                    value: result_tmp.clone(),
+                    value: result_tmp,
                     mutable: true,
+                    ty,
                 });
-
-                // Track variable type in mir_symbol_table
-                if let Some(value_type) = builder.mir_symbol_table.get(&result_tmp).cloned() {
-                    builder.mir_symbol_table.insert(name.clone(), value_type);
-                }
             }
         }
 
-        // Handle struct declarations (type definitions, not instances).
-        AstNode::StructDecl { name, fields } => {
-            // Create a placeholder instance showing the structure.
-            let tmp = builder.next_tmp();
-            let field_vals: Vec<(String, String)> = fields
-                .iter()
-                .map(|(fname, _typ)| {
-                    let val_tmp = builder.next_tmp();
-                    (fname.clone(), val_tmp)
-                })
-                .collect();
-
-            block.instrs.push(MirInstr::StructInit {
-                name: tmp,
-                struct_name: name.clone(),
-                fields: field_vals,
-            });
-        }
-
-        // Handle enum declarations (type definitions, not instances).
-        AstNode::EnumDecl { name, variants } => {
-            for (variant_name, opt_type) in variants {
-                let tmp = builder.next_tmp();
-                let value_tmp = opt_type.as_ref().map(|_| builder.next_tmp());
-                block.instrs.push(MirInstr::EnumInit {
-                    name: tmp,
-                    enum_name: name.clone(),
-                    variant: variant_name.clone(),
+        // Struct declarations are type definitions: only their layout is recorded.
+        AstNode::StructDecl {
+            name,
+            fields,
+            weak_fields,
+        } => builder.register_struct(name, fields, weak_fields),
+
+        // Field assignment: `u.age = 4;`
+        AstNode::FieldAssignment {
+            object,
+            field,
+            value,
+        } => {
+            let object_tmp = build_expression(builder, object, block);
+            let value_tmp = build_expression(builder, value, block);
+            if let Some(TypeNode::Struct(struct_name, _)) =
+                builder.mir_symbol_table.get(&object_tmp)
+            {
+                block.instrs.push(MirInstr::StructSet {
+                    struct_instance: object_tmp,
+                    struct_name: struct_name.clone(),
+                    field: field.clone(),
                     value: value_tmp,
                 });
             }
         }
 
+        // Enum declarations are type definitions: only their variants are recorded.
+        AstNode::EnumDecl { name, variants } => builder.register_enum(name, variants),
+
         // Handle conditional statements (if/else).
         AstNode::ConditionalStmt {
             condition,
@@ -188,103 +214,58 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
         } => {
             // Build MIR for the condition expression.
             let cond_tmp = build_expression(builder, condition, block);
+            build_branches(builder, cond_tmp, vec![], then_block, else_branch, block);
+        }
 
-            // Generate labels for then, else, and exit blocks.
-            let then_label = builder.next_block();
-            let else_label = builder.next_block();
-            let end_label = builder.next_block();
+        // Handle `match value { ... }` used as a statement; arm values are discarded.
+        AstNode::Match { value, arms } => build_match(builder, value, arms, None, block),
 
-            block.terminator = Some(MirInstr::CondJump {
-                cond: cond_tmp,
-                then_block: then_label.clone(),
-                else_block: if else_branch.is_some() {
-                    else_label.clone()
-                } else {
-                    end_label.clone()
-                },
+        // Handle `if let name = optional { ... } else { ... }`.
+        // Branches on the optional's presence and binds the payload at the top of the then block.
+        AstNode::IfLet {
+            name,
+            value,
+            then_block,
+            else_branch,
+        } => {
+            let opt_tmp = build_expression(builder, value, block);
+            let opt_type = builder.mir_symbol_table.get(&opt_tmp).cloned();
+
+            let cond_tmp = builder.next_tmp();
+            block.instrs.push(MirInstr::OptionalIsSome {
+                name: cond_tmp.clone(),
+                optional: opt_tmp.clone(),
             });
 
-            // Then block with scope tracking for reference counting.
-            builder.enter_scope();
-            let mut then_mir_block = MirBlock {
-                label: then_label,
-                instrs: vec![],
-                terminator: None,
+            // Optional struct fields are declared by name, like `parent: Node?`
+            let payload_type = match &opt_type {
+                Some(TypeNode::Optional(inner)) => Some(builder.resolve_struct_ref(inner)),
+                _ => None,
             };
-
-            for stmt in then_block {
-                build_statement(builder, stmt, &mut then_mir_block);
-            }
-
-            builder.exit_scope(&mut then_mir_block); // DecRefs inserted here
-
-            // Add jump to end if then block doesn't have a terminator
-            if then_mir_block.terminator.is_none() {
-                then_mir_block.terminator = Some(MirInstr::Jump {
-                    target: end_label.clone(),
-                });
-            }
-
-            if let Some(else_stmt) = else_branch {
-                builder.enter_scope();
-                let mut else_mir_block = MirBlock {
-                    label: else_label,
-                    instrs: vec![],
-                    terminator: None, // Don't preset terminator - let statements set it
-                };
-
-                // Handle else branch - it might be a Block or a single statement
-                match else_stmt.as_ref() {
-                    AstNode::Block(statements) => {
-                        // If it's a block, iterate through all statements
-                        for stmt in statements {
-                            build_statement(builder, stmt, &mut else_mir_block);
-                        }
-                    }
-                    _ => {
-                        // Single statement (like another if)
-                        build_statement(builder, else_stmt, &mut else_mir_block);
-                    }
-                }
-
-                builder.exit_scope(&mut else_mir_block);
-
-                // Only add jump to end if block doesn't already have a terminator (like Return)
-                if else_mir_block.terminator.is_none() {
-                    else_mir_block.terminator = Some(MirInstr::Jump {
-                        target: end_label.clone(),
-                    });
-                }
-
-                if let Some(current_func) = builder.program.functions.last_mut() {
-                    // Save the original block (with CondJump) before modifying it
-                    let original_block = MirBlock {
-                        label: block.label.clone(),
-                        instrs: block.instrs.clone(),
-                        terminator: block.terminator.clone(),
-                    };
-                    current_func.blocks.push(original_block);
-                    current_func.blocks.push(then_mir_block);
-                    current_func.blocks.push(else_mir_block);
-                }
-            } else {
-                if let Some(current_func) = builder.program.functions.last_mut() {
-                    // Save the original block (with CondJump) before modifying it
-                    let original_block = MirBlock {
-                        label: block.label.clone(),
-                        instrs: block.instrs.clone(),
-                        terminator: block.terminator.clone(),
-                    };
-                    current_func.blocks.push(original_block);
-                    current_func.blocks.push(then_mir_block);
-                }
+            let payload_tmp = builder.next_tmp();
+            let bind = vec![
+                MirInstr::OptionalUnwrap {
+                    name: payload_tmp.clone(),
+                    optional: opt_tmp,
+                    ty: opt_type
+                        .as_ref()
+                        .map(MirType::from)
+                        .unwrap_or(MirType::Void),
+                },
+                MirInstr::Assign {
+                    name: name.clone(),
+                    value: payload_tmp,
+                    mutable: false,
+                    ty: payload_type
+                        .as_ref()
+                        .map_or(MirType::Void, |ty| builder.mir_type(ty)),
+                },
+            ];
+            if let Some(payload_type) = payload_type {
+                builder.mir_symbol_table.insert(name.clone(), payload_type);
             }
 
-            // Replace current block with the end_label continuation
-            // This ensures subsequent statements in the same scope go into the continuation block
-            block.label = end_label.clone();
-            block.instrs.clear();
-            block.terminator = None;
+            build_branches(builder, cond_tmp, bind, then_block, else_branch, block);
         }
 
         // Handle return statements.
@@ -299,7 +280,10 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
         }
 
         // Handle standalone expressions (like function calls for their side effects).
-        AstNode::BinaryExpr { .. } | AstNode::FunctionCall { .. } => {
+        AstNode::BinaryExpr { .. }
+        | AstNode::FunctionCall { .. }
+        | AstNode::MethodCall { .. }
+        | AstNode::Try { .. } => {
             // Evaluate the expression but don't necessarily store the result.
             build_expression(builder, stmt, block);
         }
@@ -312,14 +296,22 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                 let val_tmp = build_expression(builder, expr, block);
                 vals.push(val_tmp);
             }
-            block.instrs.push(MirInstr::Print { values: vals });
+            let types = vals.iter().map(|val| builder.print_type(val)).collect();
+            block.instrs.push(MirInstr::Print {
+                values: vals,
+                types,
+            });
         }
 
         // Handle break statement in loops.
-        AstNode::Break => {
-            if let Some(loop_ctx) = builder.current_loop() {
+        AstNode::Break { value } => {
+            if let Some(loop_ctx) = builder.current_loop().cloned() {
+                // A loop used as a value receives the break value in its result slot
+                if let (Some(value), Some(result)) = (value, &loop_ctx.result) {
+                    build_result_value(builder, value, result, block);
+                }
                 block.terminator = Some(MirInstr::Jump {
-                    target: loop_ctx.break_target.clone(),
+                    target: loop_ctx.break_target,
                 });
             } else {
                 debug_assert!(
@@ -347,6 +339,7 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
         AstNode::ForLoopStmt {
             pattern,
             iterable,
+            step,
             body,
         } => {
             // Infinite loop: for { ... }
@@ -462,9 +455,9 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
             if let Some(iter_expr) = iterable {
                 match iter_expr.as_ref() {
                     // Range-based loops: for i in 0..10
-                    AstNode::BinaryExpr { left, op, right }
-                        if matches!(op, TokenType::RangeExc | TokenType::RangeInc) =>
-                    {
+                    AstNode::BinaryExpr {
+                        left, op, right, ..
+                    } if matches!(op, TokenType::RangeExc | TokenType::RangeInc) => {
                         let loop_var = loop_var.expect("Loop variable required");
 
                         // Initialize loop variable
@@ -473,6 +466,7 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                             name: loop_var.clone(),
                             value: start_tmp,
                             mutable: true,
+                            ty: MirType::Int,
                         });
 
                         // Store end value in a variable so it's accessible in header block
@@ -482,6 +476,20 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                             name: end_var.clone(),
                             value: end_tmp,
                             mutable: false,
+                            ty: MirType::Int,
+                        });
+
+                        // Store the step too; without one the loop counts up by 1
+                        let step_var = step.as_ref().map(|step| {
+                            let step_tmp = build_expression(builder, step, block);
+                            let step_var = format!("{}_step", loop_var);
+                            block.instrs.push(MirInstr::Assign {
+                                name: step_var.clone(),
+                                value: step_tmp,
+                                mutable: false,
+                                ty: MirType::Int,
+                            });
+                            step_var
                         });
 
                         // Set terminator to jump to this loop's header
@@ -513,19 +521,59 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                             terminator: None,
                         };
 
-                        let cmp_tmp = builder.next_tmp();
-                        let op_str = match op {
-                            TokenType::RangeInc => "le",
-                            TokenType::RangeExc => "lt",
-                            _ => unreachable!(),
+                        let inclusive = matches!(op, TokenType::RangeInc);
+                        let (asc_op, desc_op) = if inclusive {
+                            ("le", "ge")
+                        } else {
+                            ("lt", "gt")
+                        };
+                        // Literal steps fix the direction; no step counts up
+                        let descending = match step.as_deref() {
+                            Some(step) => literal_step_descending(step),
+                            None => Some(false),
+                        };
+                        let cmp_tmp = match descending {
+                            Some(descending) => {
+                                let cmp_tmp = builder.next_tmp();
+                                header_block.instrs.push(MirInstr::BinaryOp(
+                                    if descending { desc_op } else { asc_op }.to_string(),
+                                    cmp_tmp.clone(),
+                                    loop_var.clone(),
+                                    end_var,
+                                    MirType::Bool,
+                                ));
+                                cmp_tmp
+                            }
+                            // Step only known at runtime: its sign picks the comparison,
+                            // and a zero step doesn't run the body at all
+                            None => {
+                                let step_var = step_var.clone().expect("Step variable required");
+                                let zero_tmp = builder.next_tmp();
+                                header_block.instrs.push(MirInstr::ConstInt {
+                                    name: zero_tmp.clone(),
+                                    value: 0,
+                                    ty: MirType::Int,
+                                });
+                                let mut compare = |op: &str, lhs: &str, rhs: &str| {
+                                    let tmp = builder.next_tmp();
+                                    header_block.instrs.push(MirInstr::BinaryOp(
+                                        op.to_string(),
+                                        tmp.clone(),
+                                        lhs.to_string(),
+                                        rhs.to_string(),
+                                        MirType::Bool,
+                                    ));
+                                    tmp
+                                };
+                                let up = compare("gt", &step_var, &zero_tmp);
+                                let down = compare("lt", &step_var, &zero_tmp);
+                                let asc = compare(asc_op, &loop_var, &end_var);
+                                let desc = compare(desc_op, &loop_var, &end_var);
+                                let up_asc = compare("and", &up, &asc);
+                                let down_desc = compare("and", &down, &desc);
+                                compare("or", &up_asc, &down_desc)
+                            }
                         };
-
-                        header_block.instrs.push(MirInstr::BinaryOp(
-                            op_str.to_string(),
-                            cmp_tmp.clone(),
-                            loop_var.clone(),
-                            end_var,
-                        ));
 
                         header_block.terminator = Some(MirInstr::CondJump {
                             cond: cmp_tmp,
@@ -556,31 +604,40 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
 
                         blocks_to_add.push(body_block);
 
-                        // Increment block: i = i + 1, then jump to header
+                        // Increment block: i = i + step (1 by default), then jump to header
                         let mut increment_block = MirBlock {
                             label: loop_increment,
                             instrs: vec![],
                             terminator: None,
                         };
 
-                        let one_tmp = builder.next_tmp();
-                        increment_block.instrs.push(MirInstr::ConstInt {
-                            name: one_tmp.clone(),
-                            value: 1,
-                        });
+                        let step_tmp = match step_var {
+                            Some(step_var) => step_var,
+                            None => {
+                                let one_tmp = builder.next_tmp();
+                                increment_block.instrs.push(MirInstr::ConstInt {
+                                    name: one_tmp.clone(),
+                                    value: 1,
+                                    ty: MirType::Int,
+                                });
+                                one_tmp
+                            }
+                        };
 
                         let new_val_tmp = builder.next_tmp();
                         increment_block.instrs.push(MirInstr::BinaryOp(
                             "add".to_string(),
                             new_val_tmp.clone(),
                             loop_var.clone(),
-                            one_tmp,
+                            step_tmp,
+                            MirType::Int,
                         ));
 
                         increment_block.instrs.push(MirInstr::Assign {
                             name: loop_var,
                             value: new_val_tmp,
                             mutable: true,
+                            ty: MirType::Int,
                         });
 
                         increment_block.terminator = Some(MirInstr::Jump {
@@ -618,10 +675,12 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
 
                                 // Store map directly without creating an array wrapper
                                 let map_var = format!("{}_{}_map", key_var, value_var);
+                                let iter_type = builder.value_type(&iter_tmp);
                                 block.instrs.push(MirInstr::Assign {
                                     name: map_var.clone(),
                                     value: iter_tmp,
                                     mutable: false,
+                                    ty: iter_type,
                                 });
 
                                 let index_var = format!("{}_{}__index", key_var, value_var);
@@ -631,11 +690,13 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                                 block.instrs.push(MirInstr::ConstInt {
                                     name: zero_tmp.clone(),
                                     value: 0,
+                                    ty: MirType::Int,
                                 });
                                 block.instrs.push(MirInstr::Assign {
                                     name: index_var.clone(),
                                     value: zero_tmp,
                                     mutable: true,
+                                    ty: MirType::Int,
                                 });
 
                                 if block.terminator.is_none() {
@@ -677,6 +738,7 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                                     cmp_tmp.clone(),
                                     index_var.clone(),
                                     len_tmp,
+                                    MirType::Bool,
                                 ));
 
                                 header_block.terminator = Some(MirInstr::CondJump {
@@ -739,6 +801,7 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                                 increment_block.instrs.push(MirInstr::ConstInt {
                                     name: one_tmp.clone(),
                                     value: 1,
+                                    ty: MirType::Int,
                                 });
 
                                 let new_index_tmp = builder.next_tmp();
@@ -747,12 +810,14 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                                     new_index_tmp.clone(),
                                     index_var.clone(),
                                     one_tmp,
+                                    MirType::Int,
                                 ));
 
                                 increment_block.instrs.push(MirInstr::Assign {
                                     name: index_var,
                                     value: new_index_tmp,
                                     mutable: true,
+                                    ty: MirType::Int,
                                 });
 
                                 increment_block.terminator = Some(MirInstr::Jump {
@@ -797,10 +862,13 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
 
                             // Store array in a variable so it's accessible in header block
                             let array_var = format!("{}_array", loop_var);
+                            let iter_type = builder.value_type(&iter_tmp);
+                            let elem_type = iter_type.element().cloned().unwrap_or(MirType::Void);
                             block.instrs.push(MirInstr::Assign {
                                 name: array_var.clone(),
                                 value: iter_tmp,
                                 mutable: false,
+                                ty: iter_type,
                             });
 
                             let index_var = format!("{}__index", loop_var);
@@ -810,11 +878,13 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                             block.instrs.push(MirInstr::ConstInt {
                                 name: zero_tmp.clone(),
                                 value: 0,
+                                ty: MirType::Int,
                             });
                             block.instrs.push(MirInstr::Assign {
                                 name: index_var.clone(),
                                 value: zero_tmp,
                                 mutable: true,
+                                ty: MirType::Int,
                             });
 
                             // Only set terminator if block doesn't already have one
@@ -855,6 +925,7 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                                 cmp_tmp.clone(),
                                 index_var.clone(),
                                 len_tmp,
+                                MirType::Bool,
                             ));
 
                             header_block.terminator = Some(MirInstr::CondJump {
@@ -879,12 +950,27 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                                 index: index_var.clone(),
                             });
 
-                            // Assign element to loop variable
-                            body_block.instrs.push(MirInstr::Assign {
-                                name: loop_var.clone(),
-                                value: elem_tmp,
-                                mutable: false,
-                            });
+                            if let (true, Some(index_name), Some(item_name)) =
+                                (is_tuple_pattern, &key_var, &value_var)
+                            {
+                                // `for i, item in [..]`: bind the index and the element
+                                bind_index_and_item(
+                                    &mut body_block,
+                                    index_name,
+                                    item_name,
+                                    &index_var,
+                                    elem_tmp,
+                                    elem_type,
+                                );
+                            } else {
+                                // Assign element to loop variable
+                                body_block.instrs.push(MirInstr::Assign {
+                                    name: loop_var.clone(),
+                                    value: elem_tmp,
+                                    mutable: false,
+                                    ty: elem_type,
+                                });
+                            }
 
                             // Build body statements
                             for stmt in body {
@@ -910,6 +996,7 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                             increment_block.instrs.push(MirInstr::ConstInt {
                                 name: one_tmp.clone(),
                                 value: 1,
+                                ty: MirType::Int,
                             });
 
                             let new_index_tmp = builder.next_tmp();
@@ -918,12 +1005,14 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                                 new_index_tmp.clone(),
                                 index_var.clone(),
                                 one_tmp,
+                                MirType::Int,
                             ));
 
                             increment_block.instrs.push(MirInstr::Assign {
                                 name: index_var,
                                 value: new_index_tmp,
                                 mutable: true,
+                                ty: MirType::Int,
                             });
 
                             increment_block.terminator = Some(MirInstr::Jump {
@@ -944,16 +1033,21 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                     }
 
                     // Array iteration with break/continue support
-                    AstNode::Identifier(_) => {
+                    AstNode::Identifier(name, _) => {
+                        let iterates_array =
+                            matches!(builder.mir_symbol_table.get(name), Some(TypeNode::Array(_)));
                         if let Some(loop_var) = &loop_var {
                             let iter_tmp = build_expression(builder, iter_expr, block);
 
                             // Store array in a variable so it's accessible in header block
                             let array_var = format!("{}_array", loop_var);
+                            let iter_type = builder.value_type(&iter_tmp);
+                            let elem_type = iter_type.element().cloned().unwrap_or(MirType::Void);
                             block.instrs.push(MirInstr::Assign {
                                 name: array_var.clone(),
                                 value: iter_tmp,
                                 mutable: false,
+                                ty: iter_type,
                             });
 
                             let index_var = format!("{}__index", loop_var);
@@ -963,11 +1057,13 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                             block.instrs.push(MirInstr::ConstInt {
                                 name: zero_tmp.clone(),
                                 value: 0,
+                                ty: MirType::Int,
                             });
                             block.instrs.push(MirInstr::Assign {
                                 name: index_var.clone(),
                                 value: zero_tmp,
                                 mutable: true,
+                                ty: MirType::Int,
                             });
 
                             // Only set terminator if block doesn't already have one
@@ -1008,6 +1104,7 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                                 cmp_tmp.clone(),
                                 index_var.clone(),
                                 len_tmp,
+                                MirType::Bool,
                             ));
 
                             header_block.terminator = Some(MirInstr::CondJump {
@@ -1032,8 +1129,20 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                                 index: index_var.clone(),
                             });
 
-                            // If this is a tuple pattern (for map iteration), extract key and value
-                            if is_tuple_pattern && key_var.is_some() && value_var.is_some() {
+                            if let (true, Some(index_name), Some(item_name)) =
+                                (is_tuple_pattern && iterates_array, &key_var, &value_var)
+                            {
+                                // `for i, item in arr`: bind the index and the element
+                                bind_index_and_item(
+                                    &mut body_block,
+                                    index_name,
+                                    item_name,
+                                    &index_var,
+                                    elem_tmp,
+                                    elem_type,
+                                );
+                            } else if is_tuple_pattern && key_var.is_some() && value_var.is_some() {
+                                // Tuple pattern over a map: extract key and value
                                 let key = key_var.as_ref().unwrap();
                                 let val = value_var.as_ref().unwrap();
 
@@ -1056,6 +1165,7 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                                     name: loop_var.clone(),
                                     value: elem_tmp,
                                     mutable: false,
+                                    ty: elem_type,
                                 });
                             }
 
@@ -1083,6 +1193,7 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                             increment_block.instrs.push(MirInstr::ConstInt {
                                 name: one_tmp.clone(),
                                 value: 1,
+                                ty: MirType::Int,
                             });
 
                             let new_index_tmp = builder.next_tmp();
@@ -1091,12 +1202,14 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
                                 new_index_tmp.clone(),
                                 index_var.clone(),
                                 one_tmp,
+                                MirType::Int,
                             ));
 
                             increment_block.instrs.push(MirInstr::Assign {
                                 name: index_var,
                                 value: new_index_tmp,
                                 mutable: true,
+                                ty: MirType::Int,
                             });
 
                             increment_block.terminator = Some(MirInstr::Jump {
@@ -1189,3 +1302,811 @@ pub fn build_statement(builder: &mut MirBuilder, stmt: &AstNode, block: &mut Mir
         _ => {}
     }
 }
+
+/// Lowers a match into a chain of equality tests on the value, evaluated once.
+/// Each pattern gets its own test block that jumps to the arm on a hit and to the
+/// next test otherwise; Bool patterns branch on the value directly, enum variants
+/// test its discriminant, and a `_` arm, or the last pattern of a match without
+/// one, is reached by a plain jump.
+/// With `result` set (match used as a value), every arm assigns its value to that
+/// variable. Afterwards `block` is the continuation block after the match.
+pub fn build_match(
+    builder: &mut MirBuilder,
+    value: &AstNode,
+    arms: &[MatchArm],
+    result: Option<&str>,
+    block: &mut MirBlock,
+) {
+    let value_tmp = build_expression(builder, value, block);
+    let arm_labels: Vec<String> = arms.iter().map(|_| builder.next_block()).collect();
+    let end_label = builder.next_block();
+
+    // Test blocks are pushed first so the block holding the value stays ahead
+    // of the arm bodies (it may be the function's first block).
+    for (arm_index, (arm, arm_label)) in arms.iter().zip(&arm_labels).enumerate() {
+        if arm
+            .patterns
+            .iter()
+            .any(|p| matches!(p, MatchPattern::Wildcard))
+        {
+            block.terminator = Some(MirInstr::Jump {
+                target: arm_label.clone(),
+            });
+            break;
+        }
+        for (pattern_index, pattern) in arm.patterns.iter().enumerate() {
+            // Without a `_` arm the analyzer has made sure the match is
+            // exhaustive, so the last pattern left matches without a test
+            if arm_index + 1 == arms.len() && pattern_index + 1 == arm.patterns.len() {
+                block.terminator = Some(MirInstr::Jump {
+                    target: arm_label.clone(),
+                });
+                break;
+            }
+            // Enum variants test the value's discriminant
+            if let MatchPattern::Variant {
+                enum_name, variant, ..
+            } = pattern
+            {
+                let next_label = builder.next_block();
+                let cond_tmp = builder.next_tmp();
+                block.instrs.push(MirInstr::EnumMatch {
+                    name: cond_tmp.clone(),
+                    enum_instance: value_tmp.clone(),
+                    enum_name: enum_name.clone(),
+                    variant: variant.clone(),
+                });
+                builder
+                    .mir_symbol_table
+                    .insert(cond_tmp.clone(), TypeNode::Bool);
+                block.terminator = Some(MirInstr::CondJump {
+                    cond: cond_tmp,
+                    then_block: arm_label.clone(),
+                    else_block: next_label.clone(),
+                });
+                push_and_continue(builder, block, next_label);
+                continue;
+            }
+            let MatchPattern::Literal(literal) = pattern else {
+                continue;
+            };
+            let next_label = builder.next_block();
+            block.terminator = Some(match literal {
+                // A Bool value is its own condition (it may be i1 or i32 in codegen)
+                AstNode::BoolLiteral(true) => MirInstr::CondJump {
+                    cond: value_tmp.clone(),
+                    then_block: arm_label.clone(),
+                    else_block: next_label.clone(),
+                },
+                AstNode::BoolLiteral(false) => MirInstr::CondJump {
+                    cond: value_tmp.clone(),
+                    then_block: next_label.clone(),
+                    else_block: arm_label.clone(),
+                },
+                _ => {
+                    let literal_tmp = build_expression(builder, literal, block);
+                    let op_type = determine_op_type(builder, &value_tmp, &literal_tmp)
+                        .unwrap_or_else(|_| "int".to_string());
+                    let cond_tmp = builder.next_tmp();
+                    block.instrs.push(MirInstr::BinaryOp(
+                        format!("eq:{}", op_type),
+                        cond_tmp.clone(),
+                        value_tmp.clone(),
+                        literal_tmp,
+                        MirType::Bool,
+                    ));
+                    builder
+                        .mir_symbol_table
+                        .insert(cond_tmp.clone(), TypeNode::Bool);
+                    MirInstr::CondJump {
+                        cond: cond_tmp,
+                        then_block: arm_label.clone(),
+                        else_block: next_label.clone(),
+                    }
+                }
+            });
+            push_and_continue(builder, block, next_label);
+        }
+    }
+    // Only a match without arms is left without a jump
+    if block.terminator.is_none() {
+        block.terminator = Some(MirInstr::Jump {
+            target: end_label.clone(),
+        });
+    }
+    push_and_continue(builder, block, end_label.clone());
+
+    for (arm, arm_label) in arms.iter().zip(arm_labels) {
+        builder.enter_scope();
+        let mut arm_block = MirBlock {
+            label: arm_label,
+            instrs: vec![],
+            terminator: None,
+        };
+        // `Shape::Circle(r) => ...` binds the payload, borrowed from the value
+        if let [MatchPattern::Variant {
+            enum_name,
+            variant,
+            binding: Some(binding),
+        }] = arm.patterns.as_slice()
+        {
+            let payload_tmp = builder.next_tmp();
+            arm_block.instrs.push(MirInstr::EnumPayload {
+                name: payload_tmp.clone(),
+                enum_instance: value_tmp.clone(),
+                enum_name: enum_name.clone(),
+                variant: variant.clone(),
+            });
+            if let Some(payload_type) = builder.variant_payload_type(enum_name, variant) {
+                builder
+                    .mir_symbol_table
+                    .insert(payload_tmp.clone(), payload_type.clone());
+                builder
+                    .mir_symbol_table
+                    .insert(binding.clone(), payload_type);
+            }
+            arm_block.instrs.push(MirInstr::Assign {
+                name: binding.clone(),
+                value: payload_tmp,
+                mutable: false,
+                ty: builder.value_type(binding),
+            });
+        }
+        match (arm.body.as_ref(), result) {
+            (AstNode::Block(statements), _) => {
+                for stmt in statements {
+                    build_statement(builder, stmt, &mut arm_block);
+                }
+            }
+            (expr, Some(result)) => build_result_value(builder, expr, result, &mut arm_block),
+            (expr, None) => build_statement(builder, expr, &mut arm_block),
+        }
+        builder.exit_scope(&mut arm_block);
+
+        if arm_block.terminator.is_none() {
+            arm_block.terminator = Some(MirInstr::Jump {
+                target: end_label.clone(),
+            });
+        }
+        if let Some(current_func) = builder.program.functions.last_mut() {
+            current_func.blocks.push(arm_block);
+        }
+    }
+}
+
+/// Lowers `if cond { a } else { b }` used as a value: each branch assigns its
+/// value to `result`, which is read after the branches.
+/// Afterwards `block` is the continuation block after the conditional.
+pub fn build_if_expr(
+    builder: &mut MirBuilder,
+    condition: &AstNode,
+    then_branch: &AstNode,
+    else_branch: &AstNode,
+    result: &str,
+    block: &mut MirBlock,
+) {
+    let cond_tmp = build_expression(builder, condition, block);
+    let then_label = builder.next_block();
+    let else_label = builder.next_block();
+    let end_label = builder.next_block();
+
+    block.terminator = Some(MirInstr::CondJump {
+        cond: cond_tmp,
+        then_block: then_label.clone(),
+        else_block: else_label.clone(),
+    });
+    push_and_continue(builder, block, end_label.clone());
+
+    for (label, branch) in [(then_label, then_branch), (else_label, else_branch)] {
+        builder.enter_scope();
+        let mut branch_block = MirBlock {
+            label,
+            instrs: vec![],
+            terminator: None,
+        };
+        build_result_value(builder, branch, result, &mut branch_block);
+        builder.exit_scope(&mut branch_block);
+        if branch_block.terminator.is_none() {
+            branch_block.terminator = Some(MirInstr::Jump {
+                target: end_label.clone(),
+            });
+        }
+        if let Some(current_func) = builder.program.functions.last_mut() {
+            current_func.blocks.push(branch_block);
+        }
+    }
+}
+
+/// Lowers `value?` on a value of Result enum `result_name` in a function
+/// returning Result enum `return_name`. An Err payload is re-wrapped in the
+/// function's Result and returned; otherwise `block` continues with the Ok
+/// payload, which borrows the value's reference like a match binding. Returns
+/// the payload (the value itself for a unit `Ok`).
+pub fn build_try(
+    builder: &mut MirBuilder,
+    value: &AstNode,
+    result_name: &str,
+    return_name: &str,
+    block: &mut MirBlock,
+) -> String {
+    let value_tmp = build_expression(builder, value, block);
+    let err_label = builder.next_block();
+    let ok_label = builder.next_block();
+
+    let is_err = builder.next_tmp();
+    block.instrs.push(MirInstr::EnumMatch {
+        name: is_err.clone(),
+        enum_instance: value_tmp.clone(),
+        enum_name: result_name.to_string(),
+        variant: "Err".to_string(),
+    });
+    builder
+        .mir_symbol_table
+        .insert(is_err.clone(), TypeNode::Bool);
+    block.terminator = Some(MirInstr::CondJump {
+        cond: is_err,
+        then_block: err_label.clone(),
+        else_block: ok_label.clone(),
+    });
+    push_and_continue(builder, block, err_label);
+
+    let err_tmp = builder.next_tmp();
+    block.instrs.push(MirInstr::EnumPayload {
+        name: err_tmp.clone(),
+        enum_instance: value_tmp.clone(),
+        enum_name: result_name.to_string(),
+        variant: "Err".to_string(),
+    });
+    if let Some(err_type) = builder.variant_payload_type(result_name, "Err") {
+        builder.mir_symbol_table.insert(err_tmp.clone(), err_type);
+    }
+    let returned = builder.next_tmp();
+    block.instrs.push(MirInstr::EnumInit {
+        name: returned.clone(),
+        enum_name: return_name.to_string(),
+        variant: "Err".to_string(),
+        value: Some(err_tmp),
+    });
+    if let Some(return_type) = builder.enum_type(return_name) {
+        builder
+            .mir_symbol_table
+            .insert(returned.clone(), return_type);
+    }
+    block.terminator = Some(MirInstr::Return {
+        values: vec![returned],
+    });
+    push_and_continue(builder, block, ok_label);
+
+    let Some(ok_type) = builder.variant_payload_type(result_name, "Ok") else {
+        return value_tmp;
+    };
+    let ok_tmp = builder.next_tmp();
+    block.instrs.push(MirInstr::EnumPayload {
+        name: ok_tmp.clone(),
+        enum_instance: value_tmp,
+        enum_name: result_name.to_string(),
+        variant: "Ok".to_string(),
+    });
+    builder.mir_symbol_table.insert(ok_tmp.clone(), ok_type);
+    ok_tmp
+}
+
+/// Lowers `assert(cond, message)`: when `cond` is false the message, prefixed
+/// with "assertion failed: ", is evaluated and the program panics; otherwise
+/// `block` continues after the call. Release builds drop the call entirely.
+pub fn build_assert(
+    builder: &mut MirBuilder,
+    cond: &AstNode,
+    message: &AstNode,
+    block: &mut MirBlock,
+) {
+    if builder.release {
+        return;
+    }
+    let cond_tmp = build_expression(builder, cond, block);
+    let fail_label = builder.next_block();
+    let ok_label = builder.next_block();
+    block.terminator = Some(MirInstr::CondJump {
+        cond: cond_tmp,
+        then_block: ok_label.clone(),
+        else_block: fail_label.clone(),
+    });
+    push_and_continue(builder, block, fail_label);
+
+    let prefix = builder.next_tmp();
+    block.instrs.push(MirInstr::ConstString {
+        name: prefix.clone(),
+        value: "assertion failed: ".to_string(),
+    });
+    builder
+        .mir_symbol_table
+        .insert(prefix.clone(), TypeNode::String);
+    let message_tmp = build_expression(builder, message, block);
+    let full_message = builder.next_tmp();
+    block.instrs.push(MirInstr::StringConcat {
+        name: full_message.clone(),
+        left: prefix,
+        right: message_tmp,
+    });
+    builder
+        .mir_symbol_table
+        .insert(full_message.clone(), TypeNode::String);
+    block.terminator = Some(MirInstr::Panic {
+        message: full_message,
+    });
+    push_and_continue(builder, block, ok_label);
+}
+
+/// Evaluates the value of one branch of a conditional or match used as a value,
+/// or of a `break` in a loop used as a value, and assigns it to `result`.
+fn build_result_value(
+    builder: &mut MirBuilder,
+    expr: &AstNode,
+    result: &str,
+    block: &mut MirBlock,
+) {
+    let value_tmp = build_expression(builder, expr, block);
+    // A branch that panics has no value
+    if block.terminator.is_some() {
+        return;
+    }
+    if let Some(value_type) = builder.mir_symbol_table.get(&value_tmp).cloned() {
+        builder
+            .mir_symbol_table
+            .insert(result.to_string(), value_type);
+    }
+    block.instrs.push(MirInstr::Assign {
+        name: result.to_string(),
+        value: value_tmp,
+        mutable: true,
+        ty: builder.value_type(result),
+    });
+}
+
+/// Lowers `for { ... }` used as a value. The body loops back to its start;
+/// each `break value` assigns `result` and jumps to the exit, which becomes `block`.
+pub fn build_loop_expr(
+    builder: &mut MirBuilder,
+    body: &[AstNode],
+    result: &str,
+    block: &mut MirBlock,
+) {
+    let loop_body = builder.next_block();
+    let loop_end = builder.next_block();
+    builder.enter_value_loop(loop_end.clone(), loop_body.clone(), result.to_string());
+
+    block.terminator = Some(MirInstr::Jump {
+        target: loop_body.clone(),
+    });
+    push_and_continue(builder, block, loop_end);
+
+    let mut body_block = MirBlock {
+        label: loop_body.clone(),
+        instrs: vec![],
+        terminator: None,
+    };
+    for stmt in body {
+        build_statement(builder, stmt, &mut body_block);
+    }
+    if body_block.terminator.is_none() {
+        body_block.terminator = Some(MirInstr::Jump { target: loop_body });
+    }
+    if let Some(current_func) = builder.program.functions.last_mut() {
+        current_func.blocks.push(body_block);
+    }
+    builder.exit_loop();
+}
+
+/// Lowers `arr.map(f)`, `arr.filter(f)` and `arr.reduce(init, f)` by inlining
+/// the closure `f` into a loop over `array` like `for x in arr`: each element is
+/// bound to the closure's last parameter before its body runs. `map` pushes the
+/// body's values onto a new array and `filter` the elements it holds true for;
+/// `reduce` starts the accumulator, the first parameter, at `init` and assigns
+/// it each value. Returns the hidden variable holding the result; afterwards
+/// `block` is the block after the loop.
+pub fn build_closure_loop(
+    builder: &mut MirBuilder,
+    method: &str,
+    array: String,
+    init: Option<String>,
+    closure: &AstNode,
+    block: &mut MirBlock,
+) -> String {
+    let AstNode::Closure {
+        params,
+        body,
+        value_type,
+        ..
+    } = closure
+    else {
+        unreachable!("build_closure_loop is only called with closures");
+    };
+    let (item, item_type) = params.last().cloned().expect("Closure parameter required");
+    let result = format!("__{}_{}", method, &builder.next_tmp()[1..]);
+
+    // The result array starts empty; the accumulator starts at `init`
+    let result_type = match (method, value_type) {
+        ("map", Some(ty)) => Some(TypeNode::Array(Box::new(ty.clone()))),
+        ("filter", _) => item_type.clone().map(|ty| TypeNode::Array(Box::new(ty))),
+        _ => None,
+    };
+    let acc = match (&init, params.first()) {
+        (Some(init), Some((acc, acc_type))) => {
+            block.instrs.push(MirInstr::Assign {
+                name: acc.clone(),
+                value: init.clone(),
+                mutable: true,
+                ty: acc_type
+                    .as_ref()
+                    .map_or_else(|| builder.value_type(init), |ty| builder.mir_type(ty)),
+            });
+            if let Some(ty) = acc_type {
+                builder.mir_symbol_table.insert(acc.clone(), ty.clone());
+            }
+            Some(acc.clone())
+        }
+        _ => {
+            let empty_tmp = builder.next_tmp();
+            block.instrs.push(MirInstr::Array {
+                name: empty_tmp.clone(),
+                elements: vec![],
+            });
+            block.instrs.push(MirInstr::Assign {
+                name: result.clone(),
+                value: empty_tmp,
+                mutable: true,
+                ty: result_type
+                    .as_ref()
+                    .map_or(MirType::Void, |ty| builder.mir_type(ty)),
+            });
+            None
+        }
+    };
+
+    let array_var = format!("{}_array", item);
+    let array_type = builder.value_type(&array);
+    block.instrs.push(MirInstr::Assign {
+        name: array_var.clone(),
+        value: array,
+        mutable: false,
+        ty: array_type,
+    });
+    let index_var = format!("{}__index", item);
+    let zero_tmp = builder.next_tmp();
+    block.instrs.push(MirInstr::ConstInt {
+        name: zero_tmp.clone(),
+        value: 0,
+        ty: MirType::Int,
+    });
+    block.instrs.push(MirInstr::Assign {
+        name: index_var.clone(),
+        value: zero_tmp,
+        mutable: true,
+        ty: MirType::Int,
+    });
+
+    let loop_header = builder.next_block();
+    let loop_body = builder.next_block();
+    let loop_increment = builder.next_block();
+    let loop_end = builder.next_block();
+    block.terminator = Some(MirInstr::Jump {
+        target: loop_header.clone(),
+    });
+    push_and_continue(builder, block, loop_end.clone());
+
+    // Header: bounds check
+    let len_tmp = builder.next_tmp();
+    let cmp_tmp = builder.next_tmp();
+    let header_block = MirBlock {
+        label: loop_header.clone(),
+        instrs: vec![
+            MirInstr::ArrayLen {
+                name: len_tmp.clone(),
+                array: array_var.clone(),
+            },
+            MirInstr::BinaryOp(
+                "lt".to_string(),
+                cmp_tmp.clone(),
+                index_var.clone(),
+                len_tmp,
+                MirType::Bool,
+            ),
+        ],
+        terminator: Some(MirInstr::CondJump {
+            cond: cmp_tmp,
+            then_block: loop_body.clone(),
+            else_block: loop_end,
+        }),
+    };
+
+    // Body: bind the element, then use the closure's value
+    let mut body_block = MirBlock {
+        label: loop_body,
+        instrs: vec![],
+        terminator: None,
+    };
+    let elem_tmp = builder.next_tmp();
+    body_block.instrs.push(MirInstr::ArrayGet {
+        name: elem_tmp.clone(),
+        array: array_var,
+        index: index_var.clone(),
+    });
+    body_block.instrs.push(MirInstr::Assign {
+        name: item.clone(),
+        value: elem_tmp,
+        mutable: false,
+        ty: item_type
+            .as_ref()
+            .map_or(MirType::Void, |ty| builder.mir_type(ty)),
+    });
+    if let Some(ty) = &item_type {
+        builder.mir_symbol_table.insert(item.clone(), ty.clone());
+    }
+    let value_tmp = build_expression(builder, body, &mut body_block);
+
+    let mut keep_block = None;
+    match (&acc, &result_type) {
+        (Some(acc), _) => body_block.instrs.push(MirInstr::Assign {
+            name: acc.clone(),
+            value: value_tmp,
+            mutable: true,
+            ty: builder.value_type(acc),
+        }),
+        (None, Some(ty)) if method == "filter" => {
+            let keep_label = builder.next_block();
+            body_block.terminator = Some(MirInstr::CondJump {
+                cond: value_tmp,
+                then_block: keep_label.clone(),
+                else_block: loop_increment.clone(),
+            });
+            keep_block = Some(MirBlock {
+                label: keep_label,
+                instrs: vec![MirInstr::ArrayPush {
+                    array: result.clone(),
+                    value: item,
+                    ty: MirType::from(ty),
+                }],
+                terminator: Some(MirInstr::Jump {
+                    target: loop_increment.clone(),
+                }),
+            });
+        }
+        (None, Some(ty)) => body_block.instrs.push(MirInstr::ArrayPush {
+            array: result.clone(),
+            value: value_tmp,
+            ty: MirType::from(ty),
+        }),
+        // Left untyped by the analyzer
+        (None, None) => {}
+    }
+    if body_block.terminator.is_none() {
+        body_block.terminator = Some(MirInstr::Jump {
+            target: loop_increment.clone(),
+        });
+    }
+
+    // Increment: index++
+    let one_tmp = builder.next_tmp();
+    let new_index_tmp = builder.next_tmp();
+    let increment_block = MirBlock {
+        label: loop_increment,
+        instrs: vec![
+            MirInstr::ConstInt {
+                name: one_tmp.clone(),
+                value: 1,
+                ty: MirType::Int,
+            },
+            MirInstr::BinaryOp(
+                "add".to_string(),
+                new_index_tmp.clone(),
+                index_var.clone(),
+                one_tmp,
+                MirType::Int,
+            ),
+            MirInstr::Assign {
+                name: index_var,
+                value: new_index_tmp,
+                mutable: true,
+                ty: MirType::Int,
+            },
+        ],
+        terminator: Some(MirInstr::Jump {
+            target: loop_header.clone(),
+        }),
+    };
+
+    if let Some(current_func) = builder.program.functions.last_mut() {
+        current_func.blocks.push(header_block);
+        current_func.blocks.push(body_block);
+        current_func.blocks.extend(keep_block);
+        current_func.blocks.push(increment_block);
+    }
+
+    // The accumulator is copied out, so a later reduce reusing its name
+    // doesn't change this result
+    if let Some(acc) = acc {
+        if let Some(ty) = builder.mir_symbol_table.get(&acc).cloned() {
+            builder.mir_symbol_table.insert(result.clone(), ty);
+        }
+        block.instrs.push(MirInstr::Assign {
+            name: result.clone(),
+            ty: builder.value_type(&acc),
+            value: acc,
+            mutable: false,
+        });
+    } else if let Some(ty) = result_type {
+        builder.mir_symbol_table.insert(result.clone(), ty);
+    }
+    result
+}
+
+/// Binds `for index, item in array` loop variables at the start of the body:
+/// the loop's index counter and the element `elem_tmp` read at that index.
+fn bind_index_and_item(
+    body_block: &mut MirBlock,
+    index_name: &str,
+    item_name: &str,
+    index_var: &str,
+    elem_tmp: String,
+    elem_type: MirType,
+) {
+    body_block.instrs.push(MirInstr::Assign {
+        name: index_name.to_string(),
+        value: index_var.to_string(),
+        mutable: false,
+        ty: MirType::Int,
+    });
+    body_block.instrs.push(MirInstr::Assign {
+        name: item_name.to_string(),
+        value: elem_tmp,
+        mutable: false,
+        ty: elem_type,
+    });
+}
+
+/// Direction of a range `step` written as a literal (`2`, `-2`):
+/// Some(true) when it counts down, None when only known at runtime.
+/// Type of element `index` of the tuple `value`, if known.
+fn tuple_element_type(builder: &MirBuilder, value: &str, index: usize) -> Option<TypeNode> {
+    match builder.mir_symbol_table.get(value) {
+        Some(TypeNode::Tuple(elements)) => elements.get(index).cloned(),
+        _ => None,
+    }
+}
+
+fn literal_step_descending(step: &AstNode) -> Option<bool> {
+    match step {
+        AstNode::NumberLiteral(n) => Some(*n < 0),
+        AstNode::UnaryExpr { op, expr, .. } => match (op, expr.as_ref()) {
+            (TokenType::Minus, AstNode::NumberLiteral(_)) => Some(true),
+            (TokenType::Plus, AstNode::NumberLiteral(_)) => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Pushes the terminated `block` to the current function and turns `block`
+/// into a fresh, empty block labelled `next_label`.
+fn push_and_continue(builder: &mut MirBuilder, block: &mut MirBlock, next_label: String) {
+    let finished = std::mem::replace(
+        block,
+        MirBlock {
+            label: next_label,
+            instrs: vec![],
+            terminator: None,
+        },
+    );
+    if let Some(current_func) = builder.program.functions.last_mut() {
+        current_func.blocks.push(finished);
+    }
+}
+
+/// Lowers the then/else branches of a conditional that jumps on `cond_tmp`.
+/// `then_prefix` is emitted at the start of the then block (e.g. an `if let` binding).
+/// Afterwards `block` is the continuation block where subsequent statements go.
+fn build_branches(
+    builder: &mut MirBuilder,
+    cond_tmp: String,
+    then_prefix: Vec<MirInstr>,
+    then_block: &[AstNode],
+    else_branch: &Option<Box<AstNode>>,
+    block: &mut MirBlock,
+) {
+    // Generate labels for then, else, and exit blocks.
+    let then_label = builder.next_block();
+    let else_label = builder.next_block();
+    let end_label = builder.next_block();
+
+    block.terminator = Some(MirInstr::CondJump {
+        cond: cond_tmp,
+        then_block: then_label.clone(),
+        else_block: if else_branch.is_some() {
+            else_label.clone()
+        } else {
+            end_label.clone()
+        },
+    });
+
+    // Then block with scope tracking for reference counting.
+    builder.enter_scope();
+    let mut then_mir_block = MirBlock {
+        label: then_label,
+        instrs: then_prefix,
+        terminator: None,
+    };
+
+    for stmt in then_block {
+        build_statement(builder, stmt, &mut then_mir_block);
+    }
+
+    builder.exit_scope(&mut then_mir_block); // DecRefs inserted here
+
+    // Add jump to end if then block doesn't have a terminator
+    if then_mir_block.terminator.is_none() {
+        then_mir_block.terminator = Some(MirInstr::Jump {
+            target: end_label.clone(),
+        });
+    }
+
+    if let Some(else_stmt) = else_branch {
+        builder.enter_scope();
+        let mut else_mir_block = MirBlock {
+            label: else_label,
+            instrs: vec![],
+            terminator: None, // Don't preset terminator - let statements set it
+        };
+
+        // Handle else branch - it might be a Block or a single statement
+        match else_stmt.as_ref() {
+            AstNode::Block(statements) => {
+                // If it's a block, iterate through all statements
+                for stmt in statements {
+                    build_statement(builder, stmt, &mut else_mir_block);
+                }
+            }
+            _ => {
+                // Single statement (like another if)
+                build_statement(builder, else_stmt, &mut else_mir_block);
+            }
+        }
+
+        builder.exit_scope(&mut else_mir_block);
+
+        // Only add jump to end if block doesn't already have a terminator (like Return)
+        if else_mir_block.terminator.is_none() {
+            else_mir_block.terminator = Some(MirInstr::Jump {
+                target: end_label.clone(),
+            });
+        }
+
+        if let Some(current_func) = builder.program.functions.last_mut() {
+            // Save the original block (with CondJump) before modifying it
+            let original_block = MirBlock {
+                label: block.label.clone(),
+                instrs: block.instrs.clone(),
+                terminator: block.terminator.clone(),
+            };
+            current_func.blocks.push(original_block);
+            current_func.blocks.push(then_mir_block);
+            current_func.blocks.push(else_mir_block);
+        }
+    } else {
+        if let Some(current_func) = builder.program.functions.last_mut() {
+            // Save the original block (with CondJump) before modifying it
+            let original_block = MirBlock {
+                label: block.label.clone(),
+                instrs: block.instrs.clone(),
+                terminator: block.terminator.clone(),
+            };
+            current_func.blocks.push(original_block);
+            current_func.blocks.push(then_mir_block);
+        }
+    }
+
+    // Replace current block with the end_label continuation
+    // This ensures subsequent statements in the same scope go into the continuation block
+    block.label = end_label.clone();
+    block.instrs.clear();
+    block.terminator = None;
+}
@@ -3,9 +3,14 @@ mod mir_tests {
     use crate::analyzer::SemanticAnalyzer;
     use crate::lexar::lexer::lex;
     use crate::mir::builder::MirBuilder;
+    use crate::mir::{MirType, VerifyErrorKind};
     use crate::parser::Parser;
 
     fn build_mir(input: &str) -> Result<MirBuilder, String> {
+        build_mir_with(input, false)
+    }
+
+    fn build_mir_with(input: &str, release: bool) -> Result<MirBuilder, String> {
         let tokens = lex(input);
         let mut parser = Parser::new(&tokens);
         let result = parser.parse_program();
@@ -19,6 +24,7 @@ mod mir_tests {
                         .map_err(|e| format!("{:?}", e))?;
 
                     let mut mir_builder = MirBuilder::new();
+                    mir_builder.set_release(release);
                     mir_builder.build_program(nodes);
                     mir_builder.finalize();
                     Ok(mir_builder)
@@ -78,6 +84,810 @@ mod mir_tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_mir_for_variadic_call() {
+        let input = r#"
+            fn sum(args: Int...) -> Int { return 0; }
+            fn main() {
+                let s = sum(1, 2, 3);
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let main_fn = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        let instrs: Vec<_> = main_fn
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .collect();
+        // The three arguments are packed into one array passed to `sum`
+        let packed = instrs
+            .iter()
+            .find_map(|i| match i {
+                crate::mir::MirInstr::Array { name, elements } if elements.len() == 3 => {
+                    Some(name.clone())
+                }
+                _ => None,
+            })
+            .expect("packed arguments");
+        assert!(instrs.iter().any(|i| {
+            matches!(i, crate::mir::MirInstr::Call { func, args, .. } if func == "sum" && *args == vec![packed.clone()])
+        }));
+    }
+
+    #[test]
+    fn test_mir_for_multiple_return_values() {
+        let input = r#"
+            fn divmod(a: Int, b: Int) -> (Int, Int) { return a / b, a % b; }
+            fn main() {
+                let (q, r) = divmod(7, 2);
+                print(q, r);
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let divmod = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "divmod")
+            .unwrap();
+        assert!(divmod
+            .blocks
+            .iter()
+            .any(|b| matches!(&b.terminator, Some(crate::mir::MirInstr::Return { values }) if values.len() == 2)));
+        let main_fn = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        let instrs: Vec<_> = main_fn
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .collect();
+        // Each element is extracted from the call result and assigned from that same temp
+        for (index, var) in ["q", "r"].iter().enumerate() {
+            let extracted = instrs
+                .iter()
+                .find_map(|i| match i {
+                    crate::mir::MirInstr::TupleExtract {
+                        name, index: idx, ..
+                    } if *idx == index => Some(name.clone()),
+                    _ => None,
+                })
+                .expect("tuple element extracted");
+            assert!(instrs.iter().any(|i| {
+                matches!(i, crate::mir::MirInstr::Assign { name, value, .. } if name == var && *value == extracted)
+            }));
+        }
+    }
+
+    #[test]
+    fn test_mir_for_nested_functions() {
+        let input = r#"
+            fn outer(x: Int) -> Int {
+                fn square(n: Int) -> Int { return n * n; }
+                return square(x);
+            }
+            fn main() { print(outer(3)); }
+        "#;
+        let mir = build_mir(input).unwrap();
+        // The nested function is hoisted to a function of its own
+        let square = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "outer.square")
+            .expect("hoisted nested function");
+        assert_eq!(square.params, vec!["n".to_string()]);
+        let outer = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "outer")
+            .unwrap();
+        assert!(outer.blocks.iter().flat_map(|b| b.instrs.iter()).any(
+            |i| matches!(i, crate::mir::MirInstr::Call { func, .. } if func == "outer.square")
+        ));
+    }
+
+    #[test]
+    fn test_mir_for_struct_values() {
+        let input = r#"
+            struct User { name: Str, age: Int }
+            fn main() {
+                let mut u = User { name: "A", age: 3 };
+                u.age = 4;
+                print(u.name);
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        // The declaration only records the layout
+        let fields: Vec<&str> = mir.program.structs["User"]
+            .iter()
+            .map(|(f, _)| f.as_str())
+            .collect();
+        assert_eq!(fields, vec!["name", "age"]);
+        assert!(mir.program.globals.is_empty());
+
+        let main = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        let instrs: Vec<&crate::mir::MirInstr> =
+            main.blocks.iter().flat_map(|b| b.instrs.iter()).collect();
+        assert!(instrs.iter().any(|i| matches!(
+            i,
+            crate::mir::MirInstr::StructInit { struct_name, fields, .. }
+                if struct_name == "User" && fields.len() == 2
+        )));
+        assert!(instrs.iter().any(|i| matches!(
+            i,
+            crate::mir::MirInstr::StructSet { struct_instance, struct_name, field, .. }
+                if struct_instance == "u" && struct_name == "User" && field == "age"
+        )));
+        assert!(instrs.iter().any(|i| matches!(
+            i,
+            crate::mir::MirInstr::StructGet { struct_instance, field, .. }
+                if struct_instance == "u" && field == "name"
+        )));
+    }
+
+    #[test]
+    fn test_mir_for_struct_methods() {
+        let input = r#"
+            struct User { name: Str }
+            impl User {
+                fn greet(self, greeting: Str) -> Str { return greeting + self.name; }
+            }
+            fn main() {
+                let u = User { name: "A" };
+                print(u.greet("Hi "));
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let method = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "User::greet")
+            .expect("methods become functions named after their struct");
+        assert_eq!(method.params.len(), 2);
+        assert_eq!(method.params[0], "self");
+
+        // The object is passed as the first argument
+        let main = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        let call = main
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .find_map(|i| match i {
+                crate::mir::MirInstr::Call { func, args, .. } if func == "User::greet" => {
+                    Some(args.clone())
+                }
+                _ => None,
+            })
+            .expect("method call lowers to a call");
+        assert_eq!(call.len(), 2);
+        assert_eq!(call[0], "u");
+    }
+
+    #[test]
+    fn test_mir_for_generic_instances() {
+        let input = r#"
+            trait Shape { fn area(self) -> Float; }
+            struct Circle { r: Float }
+            struct Square { side: Float }
+            impl Shape for Circle { fn area(self) -> Float { return self.r * 3.14; } }
+            impl Shape for Square { fn area(self) -> Float { return self.side; } }
+            fn describe<T: Shape>(s: T) -> Float { return s.area(); }
+            fn main() {
+                let a = describe(Circle { r: 1.0 });
+                let b = describe(Square { side: 2.0 });
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let names: Vec<&str> = mir
+            .program
+            .functions
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect();
+        // The template itself is never built, only its instances
+        assert!(!names.contains(&"describe"));
+        assert!(names.contains(&"describe__StructCircle"));
+        assert!(names.contains(&"describe__StructSquare"));
+
+        // Each instance calls its struct's method directly
+        let circle = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "describe__StructCircle")
+            .unwrap();
+        assert!(circle
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .any(|i| matches!(
+                i,
+                crate::mir::MirInstr::Call { func, .. } if func == "Circle::area"
+            )));
+    }
+
+    #[test]
+    fn test_mir_for_array_methods() {
+        let input = r#"
+            fn main() {
+                let mut names: [Str] = [];
+                names.push("a");
+                names.insert(0, "b");
+                let last = names.pop();
+                let first = names.remove(0);
+                names.clear();
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let instrs: Vec<&crate::mir::MirInstr> = mir.program.functions[0]
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .collect();
+        // Every operation names the variable and the array's type, from the annotation
+        let ops: Vec<(&str, String)> = instrs
+            .iter()
+            .filter_map(|i| match i {
+                crate::mir::MirInstr::ArrayPush { array, ty, .. }
+                | crate::mir::MirInstr::ArrayInsert { array, ty, .. }
+                | crate::mir::MirInstr::ArrayPop { array, ty, .. }
+                | crate::mir::MirInstr::ArrayRemove { array, ty, .. }
+                | crate::mir::MirInstr::ArrayClear { array, ty } => {
+                    Some((array.as_str(), ty.to_string()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ops.len(), 5);
+        assert!(ops
+            .iter()
+            .all(|op| *op == ("names", "Array<String>".to_string())));
+        assert!(!instrs
+            .iter()
+            .any(|i| matches!(i, crate::mir::MirInstr::Call { .. })));
+    }
+
+    #[test]
+    fn test_mir_for_array_concat() {
+        let input = r#"
+            fn main() {
+                let a = ["x"];
+                let b = a + ["y"];
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let concat = mir.program.functions[0]
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .find_map(|i| match i {
+                crate::mir::MirInstr::ArrayConcat { left, ty, .. } => Some((left, ty)),
+                _ => None,
+            });
+        let (left, ty) = concat.expect("ArrayConcat should be emitted for array +");
+        assert_eq!(left, "a");
+        assert_eq!(ty.to_string(), "Array<String>");
+    }
+
+    #[test]
+    fn test_mir_for_numeric_array_builtins() {
+        let input = r#"
+            fn main() {
+                let mut nums = [3, 1, 2];
+                nums.sort();
+                nums.reverse();
+                let total = nums.sum();
+                let low = nums.min();
+                let high = [1.5].max();
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let instrs: Vec<&crate::mir::MirInstr> = mir.program.functions[0]
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .collect();
+        let ops: Vec<(&str, String)> = instrs
+            .iter()
+            .filter_map(|i| match i {
+                crate::mir::MirInstr::ArraySort { array, ty }
+                | crate::mir::MirInstr::ArrayReverse { array, ty }
+                | crate::mir::MirInstr::ArraySum { array, ty, .. }
+                | crate::mir::MirInstr::ArrayMin { array, ty, .. } => {
+                    Some((array.as_str(), ty.to_string()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ops.len(), 4);
+        assert!(ops
+            .iter()
+            .all(|op| *op == ("nums", "Array<Int>".to_string())));
+        // `max` reads any array value, here a literal
+        assert!(instrs.iter().any(|i| matches!(
+            i,
+            crate::mir::MirInstr::ArrayMax { array, ty, .. }
+                if array.starts_with('%') && ty.to_string() == "Array<Float>"
+        )));
+    }
+
+    #[test]
+    fn test_mir_for_closure_array_methods() {
+        let input = r#"
+            fn main() {
+                let a = [1, 2, 3];
+                let b = a.map(|x| x * 2);
+                let c = a.filter(|x| x > 1);
+                let total = a.reduce(0, |acc, x| acc + x);
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let instrs: Vec<&crate::mir::MirInstr> = mir.program.functions[0]
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .collect();
+        // The closures are inlined: no calls, one loop per method
+        assert!(!instrs
+            .iter()
+            .any(|i| matches!(i, crate::mir::MirInstr::Call { .. })));
+        let pushes: Vec<(&str, &str, String)> = instrs
+            .iter()
+            .filter_map(|i| match i {
+                crate::mir::MirInstr::ArrayPush { array, value, ty } => {
+                    Some((array.as_str(), value.as_str(), ty.to_string()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(pushes.len(), 2);
+        assert!(pushes[0].0.starts_with("__map_") && pushes[0].2 == "Array<Int>");
+        // filter keeps the element itself
+        assert!(pushes[1].0.starts_with("__filter_") && pushes[1].1 == "x");
+        assert!(instrs.iter().any(|i| matches!(
+            i,
+            crate::mir::MirInstr::Assign { name, value, .. }
+                if name.starts_with("__reduce_") && value == "acc"
+        )));
+        assert_eq!(
+            instrs
+                .iter()
+                .filter(|i| matches!(i, crate::mir::MirInstr::ArrayLen { array, .. } if array == "x_array"))
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_mir_for_string_methods() {
+        let input = r#"
+            fn main() {
+                let s = " a b ";
+                let n = s.len();
+                let parts = s.trim().split(" ");
+                let t = s.replace("a", "c").substring(0, 2);
+                let has = t.contains("c");
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let instrs: Vec<&crate::mir::MirInstr> = mir.program.functions[0]
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .collect();
+        assert!(instrs.iter().any(|i| matches!(
+            i,
+            crate::mir::MirInstr::StrLen { string, .. } if string == "s"
+        )));
+        assert!(instrs.iter().any(|i| matches!(
+            i,
+            crate::mir::MirInstr::StrTrim { string, .. } if string == "s"
+        )));
+        // Methods chain on the temporaries of the previous call
+        assert!(instrs.iter().any(|i| matches!(
+            i,
+            crate::mir::MirInstr::StrSplit { string, .. } if string.starts_with('%')
+        )));
+        assert!(instrs.iter().any(|i| matches!(
+            i,
+            crate::mir::MirInstr::StrSubstring { string, .. } if string.starts_with('%')
+        )));
+        assert!(instrs
+            .iter()
+            .any(|i| matches!(i, crate::mir::MirInstr::StrReplace { .. })));
+        assert!(instrs.iter().any(|i| matches!(
+            i,
+            crate::mir::MirInstr::StrContains { string, .. } if string == "t"
+        )));
+    }
+
+    #[test]
+    fn test_mir_for_map_methods() {
+        let input = r#"
+            fn main() {
+                let mut ages: {Str: Int} = {};
+                ages.insert("ann", 30);
+                let a = ages["ann"];
+                let gone = ages.remove("bob");
+                let known = ages.containsKey("ann");
+                let names = ages.keys();
+                ages.clear();
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let instrs: Vec<&crate::mir::MirInstr> = mir.program.functions[0]
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .collect();
+        // Every operation names the variable and the map's type, from the annotation
+        let ops: Vec<(&str, String)> = instrs
+            .iter()
+            .filter_map(|i| match i {
+                crate::mir::MirInstr::MapInsert { map, ty, .. }
+                | crate::mir::MirInstr::MapGet { map, ty, .. }
+                | crate::mir::MirInstr::MapRemove { map, ty, .. }
+                | crate::mir::MirInstr::MapContains { map, ty, .. }
+                | crate::mir::MirInstr::MapKeys { map, ty, .. }
+                | crate::mir::MirInstr::MapClear { map, ty } => {
+                    Some((map.as_str(), ty.to_string()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ops.len(), 6);
+        assert!(ops
+            .iter()
+            .all(|op| *op == ("ages", "Map<String, Int>".to_string())));
+        assert!(!instrs
+            .iter()
+            .any(|i| matches!(i, crate::mir::MirInstr::Call { .. })));
+    }
+
+    #[test]
+    fn test_mir_for_enum_values() {
+        let input = r#"
+            enum Shape { Circle(Float), Empty }
+            fn main() {
+                let s = Shape::Circle(2.0);
+                match s {
+                    Shape::Circle(r) => { print(r); }
+                    Shape::Empty => { print("empty"); }
+                }
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        // The declaration only records the variants
+        let variants = &mir.program.enums["Shape"];
+        assert_eq!(variants[0], ("Circle".to_string(), Some(MirType::Float)));
+        assert_eq!(variants[1], ("Empty".to_string(), None));
+        assert!(mir.program.globals.is_empty());
+
+        let main = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        let instrs: Vec<&crate::mir::MirInstr> =
+            main.blocks.iter().flat_map(|b| b.instrs.iter()).collect();
+        assert!(instrs.iter().any(|i| matches!(
+            i,
+            crate::mir::MirInstr::EnumInit { enum_name, variant, value: Some(_), .. }
+                if enum_name == "Shape" && variant == "Circle"
+        )));
+        // Every variant pattern tests the discriminant but the last, which is
+        // all that's left when the others fail
+        let tested: Vec<&str> = instrs
+            .iter()
+            .filter_map(|i| match i {
+                crate::mir::MirInstr::EnumMatch {
+                    enum_instance,
+                    variant,
+                    ..
+                } if enum_instance == "s" => Some(variant.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(tested, vec!["Circle"]);
+        // The binding is assigned from the payload
+        assert!(instrs.iter().any(|i| matches!(
+            i,
+            crate::mir::MirInstr::EnumPayload { enum_instance, variant, .. }
+                if enum_instance == "s" && variant == "Circle"
+        )));
+        assert!(instrs.iter().any(|i| matches!(
+            i,
+            crate::mir::MirInstr::Assign { name, .. } if name == "r"
+        )));
+    }
+
+    #[test]
+    fn test_mir_enum_comparison() {
+        let input = r#"
+            enum Shape { Circle(Float), Label(Str), Empty }
+            fn same(a: Shape, b: Shape) -> Bool {
+                return a == b;
+            }
+            fn main() {
+                let name = "dot";
+                print(same(Shape::Circle(2.0), Shape::Circle(2.0)));
+                print(same(Shape::Circle(2.0), Shape::Circle(3.0)));
+                print(same(Shape::Label("dot"), Shape::Label(name)));
+                print(Shape::Empty != Shape::Empty, Shape::Empty == Shape::Circle(1.0));
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let same = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "same")
+            .unwrap();
+        // Comparisons name the enum, whose variants codegen compares
+        assert!(same.blocks.iter().flat_map(|b| &b.instrs).any(|i| matches!(
+            i,
+            crate::mir::MirInstr::BinaryOp(op, ..) if op == "eq:enum:Shape"
+        )));
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = crate::mir::Interpreter::new(&mir.program, &mut out, &mut err).run();
+        assert_eq!(code, Ok(0));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "true\nfalse\ntrue\nfalse false\n"
+        );
+    }
+
+    #[test]
+    fn test_mir_for_try_operator() {
+        let input = r#"
+            fn parse(s: Str) -> Result<Int, Str> {
+                if s == "" {
+                    return Err("empty");
+                }
+                return Ok(s.len());
+            }
+            fn check(s: Str) -> Result<Void, Str> {
+                let n = parse(s)?;
+                print(n);
+                return Ok();
+            }
+            fn main() {
+                match check("abc") {
+                    Ok => { print("ok"); }
+                    Err(e) => { print(e); }
+                }
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        assert_eq!(
+            mir.program.enums["Result<Int, Str>"][0],
+            ("Ok".to_string(), Some(MirType::Int))
+        );
+        assert_eq!(
+            mir.program.enums["Result<Void, Str>"][0],
+            ("Ok".to_string(), None)
+        );
+
+        let check = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "check")
+            .unwrap();
+        // The call's block tests for Err and branches
+        let call_block = &check.blocks[0];
+        assert!(matches!(
+            call_block.instrs.last(),
+            Some(crate::mir::MirInstr::EnumMatch { enum_name, variant, .. })
+                if enum_name == "Result<Int, Str>" && variant == "Err"
+        ));
+        let Some(crate::mir::MirInstr::CondJump {
+            then_block,
+            else_block,
+            ..
+        }) = &call_block.terminator
+        else {
+            panic!("Expected CondJump, got {:?}", call_block.terminator);
+        };
+        // The Err payload is returned in the function's own Result
+        let err_block = check
+            .blocks
+            .iter()
+            .find(|b| &b.label == then_block)
+            .unwrap();
+        assert!(err_block.instrs.iter().any(|i| matches!(
+            i,
+            crate::mir::MirInstr::EnumInit { enum_name, variant, value: Some(_), .. }
+                if enum_name == "Result<Void, Str>" && variant == "Err"
+        )));
+        assert!(matches!(
+            err_block.terminator,
+            Some(crate::mir::MirInstr::Return { .. })
+        ));
+        // The Ok payload becomes the value of `parse(s)?`
+        let ok_block = check
+            .blocks
+            .iter()
+            .find(|b| &b.label == else_block)
+            .unwrap();
+        assert!(matches!(
+            ok_block.instrs.first(),
+            Some(crate::mir::MirInstr::EnumPayload { variant, .. }) if variant == "Ok"
+        ));
+        assert!(ok_block.instrs.iter().any(|i| matches!(
+            i,
+            crate::mir::MirInstr::Assign { name, .. } if name == "n"
+        )));
+    }
+
+    #[test]
+    fn test_mir_for_assert() {
+        let input = r#"
+            fn half(n: Int) -> Int {
+                assert(n % 2 == 0, "odd");
+                return n / 2;
+            }
+            fn main() {
+                print(half(4));
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let half = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "half")
+            .unwrap();
+        // A false condition branches to a block that panics with the message
+        let Some(crate::mir::MirInstr::CondJump {
+            then_block,
+            else_block,
+            ..
+        }) = &half.blocks[0].terminator
+        else {
+            panic!("Expected CondJump, got {:?}", half.blocks[0].terminator);
+        };
+        let fail_block = half.blocks.iter().find(|b| &b.label == else_block).unwrap();
+        assert!(matches!(
+            fail_block.terminator,
+            Some(crate::mir::MirInstr::Panic { .. })
+        ));
+        assert!(fail_block.instrs.iter().any(|i| matches!(
+            i,
+            crate::mir::MirInstr::ConstString { value, .. } if value == "assertion failed: "
+        )));
+        // The function continues in the other block
+        let ok_block = half.blocks.iter().find(|b| &b.label == then_block).unwrap();
+        assert!(matches!(
+            ok_block.terminator,
+            Some(crate::mir::MirInstr::Return { .. })
+        ));
+
+        // Release builds leave the check out
+        let mir = build_mir_with(input, true).unwrap();
+        let half = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "half")
+            .unwrap();
+        assert_eq!(half.blocks.len(), 1);
+        assert!(!half.blocks[0]
+            .instrs
+            .iter()
+            .any(|i| matches!(i, crate::mir::MirInstr::ConstString { .. })));
+    }
+
+    #[test]
+    fn test_mir_for_panic() {
+        let input = r#"
+            fn half(n: Int) -> Int {
+                if n < 0 {
+                    panic("negative");
+                }
+                return if n % 2 == 0 { n / 2 } else { panic("odd") };
+            }
+            fn main() {
+                print(half(4));
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let half = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "half")
+            .unwrap();
+        // Each panic ends its block, which then has no successor
+        let panics: Vec<&String> = half
+            .blocks
+            .iter()
+            .filter_map(|b| match &b.terminator {
+                Some(crate::mir::MirInstr::Panic { message }) => Some(message),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(panics.len(), 2);
+        // The panicking branch assigns no value to the if's result
+        let odd_block = half
+            .blocks
+            .iter()
+            .find(|b| matches!(&b.terminator, Some(crate::mir::MirInstr::Panic { message }) if message == panics[1]))
+            .unwrap();
+        assert!(!odd_block
+            .instrs
+            .iter()
+            .any(|i| matches!(i, crate::mir::MirInstr::Assign { .. })));
+        // and no call to a `panic` function is made
+        assert!(
+            !half.blocks.iter().flat_map(|b| &b.instrs).any(|i| matches!(
+                i,
+                crate::mir::MirInstr::Call { func, .. } if func.starts_with("panic")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_mir_for_overloaded_functions() {
+        let input = r#"
+            fn show(x: Int) { print(x); }
+            fn show(x: Str) { print(x); }
+            fn main() {
+                show(1);
+                show("one");
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        // Each overload becomes its own function, called by its mangled name
+        let names: Vec<&str> = mir
+            .program
+            .functions
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect();
+        assert!(names.contains(&"show__Int"));
+        assert!(names.contains(&"show__String"));
+        let main_fn = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        let called: Vec<&str> = main_fn
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .filter_map(|i| match i {
+                crate::mir::MirInstr::Call { func, .. } => Some(func.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(called, vec!["show__Int", "show__String"]);
+    }
+
     #[test]
     fn test_mir_for_recursive_function() {
         let input = r#"
@@ -93,6 +903,633 @@ mod mir_tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_mir_statement_spans() {
+        let input = "fn main() {\n    let x = 1;\n    let y = x + 2;\n    print(y);\n}";
+        let builder = build_mir(input).unwrap();
+        let main = builder
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        // Each statement maps to the instruction it starts at, and back to its source
+        assert_eq!(main.spans.len(), 3);
+        let texts: Vec<&str> = main
+            .spans
+            .iter()
+            .map(|(_, _, span)| &input[span.start..span.end])
+            .collect();
+        assert_eq!(texts, vec!["let x = 1;", "let y = x + 2;", "print(y);"]);
+        let block = &main.blocks[0];
+        assert!(main.spans.iter().all(|(label, _, _)| *label == block.label));
+        let starts: Vec<usize> = main.spans.iter().map(|(_, index, _)| *index).collect();
+        assert_eq!(starts[0], 0);
+        assert!(starts.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(starts[2] < block.instrs.len());
+    }
+
+    #[test]
+    fn test_mir_types() {
+        let input = r#"
+            struct User { name: Str, tags: [Str], best: Int? }
+            fn find(users: [User], scores: {Str: Float}) -> User? {
+                return null;
+            }
+            fn main() { }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let fields = &mir.program.structs["User"];
+        assert_eq!(fields[0].1, MirType::Str);
+        assert_eq!(fields[1].1, MirType::Array(Box::new(MirType::Str)));
+        assert!(fields[2].1.is_scalar_optional());
+
+        let find = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "find")
+            .unwrap();
+        let params: Vec<String> = find
+            .param_types
+            .iter()
+            .map(|ty| ty.as_ref().unwrap().to_string())
+            .collect();
+        assert_eq!(params, vec!["Array<User>", "Map<String, Float>"]);
+        let ret = find.return_type.as_ref().unwrap();
+        assert_eq!(ret.optional_payload().struct_name(), Some("User"));
+        assert!(!ret.is_scalar_optional());
+        assert!(matches!(&find.param_types[1], Some(ty) if ty.map_parts().is_some()));
+    }
+
+    #[test]
+    fn test_mir_verify() {
+        let input = r#"
+            fn main() {
+                let mut total = 0;
+                for i in 0..10 {
+                    if i == 3 { continue; }
+                    if total > 20 { break; }
+                    total = total + i;
+                }
+                print(total);
+            }
+        "#;
+        let mut mir = build_mir(input).unwrap();
+        assert_eq!(mir.program.verify(), Ok(()));
+
+        let main = mir
+            .program
+            .functions
+            .iter_mut()
+            .find(|f| f.name == "main")
+            .unwrap();
+        let last = main.blocks.len() - 1;
+        main.blocks[last].terminator = None;
+        main.blocks[0].instrs.insert(
+            0,
+            crate::mir::MirInstr::Print {
+                values: vec!["%999".to_string()],
+                types: vec![None],
+            },
+        );
+        main.blocks[0]
+            .instrs
+            .push(crate::mir::MirInstr::LoopBodyMarker {
+                var: "i".to_string(),
+                cond_block: "Nowhere".to_string(),
+                increment_block: String::new(),
+            });
+        let kinds: Vec<VerifyErrorKind> = mir
+            .program
+            .verify()
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.kind)
+            .collect();
+        assert!(kinds.contains(&VerifyErrorKind::MissingTerminator));
+        assert!(kinds.contains(&VerifyErrorKind::UnknownBlock("Nowhere".to_string())));
+        assert!(kinds.contains(&VerifyErrorKind::MisplacedLoopMarker));
+        assert!(kinds.contains(&VerifyErrorKind::UndefinedTemp("%999".to_string())));
+    }
+
+    #[test]
+    fn test_mir_naming_is_per_function() {
+        let main = r#"
+            fn main() {
+                let xs = [1, 2, 3];
+                for x in xs {
+                    if x > 1 { print(x); }
+                }
+            }
+        "#;
+        let text_of_main = |input: &str| {
+            let mir = build_mir(input).unwrap();
+            let main = mir.program.functions.iter().find(|f| f.name == "main");
+            main.unwrap().to_string()
+        };
+        // Adding a function before main renames nothing in main
+        let alone = text_of_main(main);
+        let after_helper = text_of_main(&format!(
+            "fn helper(a: Int, b: Int) -> Int {{ let c = a * b; return c + a; }}\n{}",
+            main
+        ));
+        assert_eq!(alone, after_helper);
+        assert!(alone.contains("Block0:"));
+        assert!(alone.contains("%1 = "));
+    }
+
+    #[test]
+    fn test_mir_text_round_trip() {
+        let input = r#"
+            struct User { name: Str, tags: [Str], best: Int? }
+            enum Shape { Circle(Float), Empty }
+            fn pair(x: Int) -> (Int, Float) {
+                return x, 1.5;
+            }
+            fn main() {
+                let user = User { name: "ann", tags: ["a", "b"], best: null };
+                let s = Shape::Circle(2.0);
+                match s {
+                    Shape::Circle(r) => { print(r); }
+                    Shape::Empty => { print("empty"); }
+                }
+                let mut scores = {"a": 1, "b": 2};
+                let mut total = 0;
+                for i in 0..=10 {
+                    if i == 3 { continue; }
+                    total = total + i;
+                }
+                let (a, b) = pair(total);
+                let greeting = "hi \"there\"\n";
+                print(user.name, scores, a, b, greeting);
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let text = mir.program.to_string();
+        let parsed = crate::mir::MirProgram::parse(&text).unwrap();
+        assert_eq!(parsed.to_string(), text);
+        assert_eq!(parsed.structs["User"], mir.program.structs["User"]);
+        assert_eq!(parsed.verify(), Ok(()));
+        assert!(text.contains("const_string \"hi \\\"there\\\"\\n\""));
+        assert!(text.contains("fn pair(x: Int) -> (Int, Float) {"));
+        // Printed maps and arrays carry their type for codegen's printer
+        assert!(text.contains(", scores : Map<String, Int>, "));
+
+        let err =
+            crate::mir::MirProgram::parse("fn main() {\nBlock0:\n    frob %1\n}\n").unwrap_err();
+        assert_eq!(err.line, 3);
+        assert!(crate::mir::MirProgram::parse("fn main() {\nBlock0:\n").is_err());
+    }
+
+    #[test]
+    fn test_mir_const_fold() {
+        let input = r#"
+            fn main() {
+                let a = 6;
+                let b = a * 7;
+                let mut c = b + 1;
+                c = c + 1;
+                let max = 2147483647;
+                let over = max + 1;
+                let zero = 0;
+                let d = a / zero;
+                print(b, c, over, d);
+            }
+        "#;
+        let mut mir = build_mir(input).unwrap();
+        assert!(crate::mir::PassManager::with_default_passes().run(&mut mir.program));
+        assert_eq!(mir.program.verify(), Ok(()));
+
+        let main = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        let instrs: Vec<&crate::mir::MirInstr> =
+            main.blocks.iter().flat_map(|b| b.instrs.iter()).collect();
+        let b = instrs
+            .iter()
+            .find_map(|i| match i {
+                crate::mir::MirInstr::Assign { name, value, .. } if name == "b" => Some(value),
+                _ => None,
+            })
+            .unwrap();
+        assert!(instrs.iter().any(|i| matches!(
+            i,
+            crate::mir::MirInstr::ConstInt { name, value: 42, .. } if name == b
+        )));
+        // The 7 was only read by the folded multiply
+        assert!(!instrs
+            .iter()
+            .any(|i| matches!(i, crate::mir::MirInstr::ConstInt { value: 7, .. })));
+        // `c` is written twice, `max + 1` overflows and `a / zero` divides by zero
+        let ops: Vec<&str> = instrs
+            .iter()
+            .filter_map(|i| match i {
+                crate::mir::MirInstr::BinaryOp(op, ..) => Some(op.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ops, vec!["add:int", "add:int", "div:int"]);
+    }
+
+    #[test]
+    fn test_mir_loop_invariant_motion() {
+        let input = r#"
+            fn main() {
+                let nums = [1, 2, 3];
+                let mut total = 0;
+                for n in nums {
+                    total = total + n;
+                }
+                let mut grown = [1];
+                for n in nums {
+                    grown.push(n);
+                }
+                print(total, grown);
+            }
+        "#;
+        let mut mir = build_mir(input).unwrap();
+        // Where the lengths are computed: in the entry block or in a loop header
+        let lens = |mir: &MirBuilder| -> (usize, usize) {
+            let main = mir
+                .program
+                .functions
+                .iter()
+                .find(|f| f.name == "main")
+                .unwrap();
+            let count = |b: &crate::mir::MirBlock| {
+                b.instrs
+                    .iter()
+                    .filter(|i| matches!(i, crate::mir::MirInstr::ArrayLen { .. }))
+                    .count()
+            };
+            let in_headers = main
+                .blocks
+                .iter()
+                .filter(|b| matches!(b.terminator, Some(crate::mir::MirInstr::CondJump { .. })))
+                .map(count)
+                .sum();
+            (count(&main.blocks[0]), in_headers)
+        };
+        assert_eq!(lens(&mir), (0, 2));
+        assert!(crate::mir::PassManager::with_default_passes().run(&mut mir.program));
+        assert_eq!(mir.program.verify(), Ok(()));
+        // The second loop pushes, so its length is checked every iteration
+        assert_eq!(lens(&mir), (1, 1));
+    }
+
+    #[test]
+    fn test_mir_interpreter() {
+        let input = r#"
+            struct Counter { name: Str, count: Int }
+            impl Counter {
+                fn bump(self, by: Int) { self.count = self.count + by; }
+            }
+            enum Shape { Circle(Float), Empty }
+            fn area(s: Shape) -> Float {
+                match s {
+                    Shape::Circle(r) => { return r * r * 3.0; }
+                    Shape::Empty => { return 0.0; }
+                }
+                return 0.0;
+            }
+            fn divmod(a: Int, b: Int) -> (Int, Int) {
+                return a / b, a % b;
+            }
+            fn main() {
+                let mut words = "b a  c".split(" ");
+                words.push("d");
+                let copy = words;
+                words.push("e");
+                print(copy, words);
+                let mut ages = {"ann": 31, "bob": 27};
+                ages.insert("cy", 40);
+                let mut total = 0;
+                for (name, age) in ages {
+                    if age > 30 { total = total + age; }
+                }
+                print(ages, total, ages["zed"] ?? -1);
+                let c = Counter { name: "hits", count: 0 };
+                for i in 0..3 { c.bump(i); }
+                print(c.name, c.count);
+                print(area(Shape::Circle(2.0)), area(Shape::Empty));
+                let (q, r) = divmod(17, 5);
+                print(q, r, toStr(2.5), format(3.14159, 2), parseInt("42") ?? 0, parseInt("4x") ?? 0, toInt64(q) * 2000000000L);
+                print(" padded ".trim().replace("d", "D").substring(1, 4), "abc".contains("bc"));
+                assert(total == 71, "total is off");
+                panic("done: " + toStr(total));
+            }
+        "#;
+        let mut mir = build_mir(input).unwrap();
+        crate::mir::PassManager::with_default_passes().run(&mut mir.program);
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = crate::mir::Interpreter::new(&mir.program, &mut out, &mut err).run();
+        assert_eq!(code, Ok(101));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                "[\"b\", \"a\", \"\", \"c\", \"d\"] [\"b\", \"a\", \"\", \"c\", \"d\", \"e\"]\n",
+                "{\"ann\": 31, \"bob\": 27, \"cy\": 40} 71 -1\n",
+                "hits 3\n",
+                "12.000000 0.000000\n",
+                "3 2 2.500000 3.14 42 0 6000000000\n",
+                "aDD true\n",
+            )
+        );
+        assert_eq!(String::from_utf8(err).unwrap(), "panic: done: 71\n");
+
+        // Overflow wraps unless checked; dividing by zero panics
+        let input = r#"
+            fn main() {
+                let big = 2147483647;
+                print(big + 1);
+                let zero = big - big;
+                print(big / zero);
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = crate::mir::Interpreter::new(&mir.program, &mut out, &mut err).run();
+        assert_eq!(code, Ok(101));
+        assert_eq!(String::from_utf8(out).unwrap(), "-2147483648\n");
+        assert_eq!(String::from_utf8(err).unwrap(), "panic: division by zero\n");
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let mut interpreter = crate::mir::Interpreter::new(&mir.program, &mut out, &mut err);
+        interpreter.overflow_checks = true;
+        assert_eq!(interpreter.run(), Ok(101));
+        assert_eq!(String::from_utf8(err).unwrap(), "panic: integer overflow\n");
+
+        // The minimum divided by -1 overflows too, for `/` and `%`
+        let input = r#"
+            fn main() {
+                let min = toInt("-2147483648");
+                let m = toInt("-1");
+                print(min / m, min % m);
+                let long = toInt64(min) * 4294967296L;
+                print(long % toInt64(m));
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = crate::mir::Interpreter::new(&mir.program, &mut out, &mut err).run();
+        assert_eq!(code, Ok(0));
+        assert_eq!(String::from_utf8(out).unwrap(), "-2147483648 0\n0\n");
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let mut interpreter = crate::mir::Interpreter::new(&mir.program, &mut out, &mut err);
+        interpreter.overflow_checks = true;
+        assert_eq!(interpreter.run(), Ok(101));
+        assert_eq!(String::from_utf8(err).unwrap(), "panic: integer overflow\n");
+
+        // So does indexing past the end
+        let input = r#"
+            fn main() {
+                let xs = [1, 2, 3];
+                print(xs[1]);
+                print(xs[3]);
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = crate::mir::Interpreter::new(&mir.program, &mut out, &mut err).run();
+        assert_eq!(code, Ok(101));
+        assert_eq!(String::from_utf8(out).unwrap(), "2\n");
+        assert_eq!(
+            String::from_utf8(err).unwrap(),
+            "panic: index 3 is out of bounds for length 3\n"
+        );
+    }
+
+    #[test]
+    fn test_mir_array_insert_remove_bounds() {
+        // Inserting at the end is appending; past it panics like indexing
+        let (code, out, err) = run_program(
+            r#"
+            fn main() {
+                let mut xs = [1, 2, 3];
+                xs.insert(3, 4);
+                print(xs);
+                xs.insert(5, 6);
+                print(xs);
+            }
+        "#,
+        );
+        assert_eq!(code, Ok(101));
+        assert_eq!(out, "[1, 2, 3, 4]\n");
+        assert_eq!(err, "panic: index 5 is out of bounds for length 4\n");
+
+        let (code, out, err) = run_program(
+            r#"
+            fn main() {
+                let mut xs = [1, 2, 3];
+                print(xs.remove(0));
+                let i = 0 - 1;
+                print(xs.remove(i));
+            }
+        "#,
+        );
+        assert_eq!(code, Ok(101));
+        assert_eq!(out, "1\n");
+        assert_eq!(err, "panic: index -1 is out of bounds for length 2\n");
+    }
+
+    #[test]
+    fn test_mir_escape_analysis() {
+        let input = r#"
+            fn first(xs: [Int]) -> Int { return xs[0]; }
+            fn make() -> [Int] { return [7, 8]; }
+            fn main() {
+                let local = [1, 2, 3];
+                let mut t = 0;
+                for x in local { t = t + x; }
+                print(local[1], local.sum(), t);
+                let passed = [4, 5];
+                print(first(passed));
+                let mut grown = [6];
+                grown.push(7);
+                let words = ["a", "b"];
+                print(make(), words, grown);
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let function = |name: &str| {
+            let func = mir.program.functions.iter().find(|f| f.name == name);
+            func.unwrap()
+        };
+        // Only `local` is just read: the others are passed on, resized,
+        // returned, or hold strings
+        let main = crate::mir::escape::stack_arrays(function("main"), &mir.program.globals);
+        assert_eq!(main.len(), 1);
+        let local = function("main")
+            .blocks
+            .iter()
+            .flat_map(|b| &b.instrs)
+            .find_map(|instr| match instr {
+                crate::mir::MirInstr::Assign { name, value, .. } if name == "local" => Some(value),
+                _ => None,
+            });
+        assert!(main.contains(local.unwrap()));
+        let make = crate::mir::escape::stack_arrays(function("make"), &mir.program.globals);
+        assert!(make.is_empty());
+    }
+
+    #[test]
+    fn test_mir_weak_fields() {
+        let input = r#"
+            struct Node { name: Str, weak parent: Node? }
+            fn orphan() -> Node {
+                let root = Node { name: "root", parent: null };
+                let child = Node { name: "leaf", parent: root };
+                if let p = child.parent { print(p.name); }
+                return child;
+            }
+            fn main() {
+                let child = orphan();
+                if let p = child.parent { print(p.name); } else { print("gone"); }
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let text = mir.program.to_string();
+        assert!(text.contains("    weak parent: Node?\n"));
+        let parsed = crate::mir::MirProgram::parse(&text).unwrap();
+        assert_eq!(parsed.weak_fields["Node"], vec!["parent".to_string()]);
+
+        // The parent is gone once the function that held it returns
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = crate::mir::Interpreter::new(&mir.program, &mut out, &mut err).run();
+        assert_eq!(code, Ok(0));
+        assert_eq!(String::from_utf8(out).unwrap(), "root\ngone\n");
+    }
+
+    #[test]
+    fn test_mir_arena_functions() {
+        let input = r#"
+            #[arena]
+            fn total(n: Int) -> Int {
+                let mut parts = [0];
+                for i in 0..n { parts.push(i); }
+                return parts.sum();
+            }
+            fn main() { print(total(3)); }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let text = mir.program.to_string();
+        assert!(text.contains("arena fn total(n: Int) -> Int {"));
+        assert!(text.contains("\nfn main() -> Void {"));
+        let parsed = crate::mir::MirProgram::parse(&text).unwrap();
+        let arena: Vec<bool> = parsed.functions.iter().map(|f| f.arena).collect();
+        assert_eq!(arena, vec![true, false]);
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = crate::mir::Interpreter::new(&mir.program, &mut out, &mut err).run();
+        assert_eq!(code, Ok(0));
+        assert_eq!(String::from_utf8(out).unwrap(), "3\n");
+    }
+
+    #[test]
+    fn test_mir_extern_functions() {
+        let input = r#"
+            extern "C" fn puts(s: Str) -> Int;
+            fn main() { puts("hi"); }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let text = mir.program.to_string();
+        assert!(
+            text.starts_with("extern fn puts(s: String) -> Int\n\n"),
+            "{}",
+            text
+        );
+        let parsed = crate::mir::MirProgram::parse(&text).unwrap();
+        assert_eq!(parsed.externs.len(), 1);
+        assert!(parsed.externs[0].blocks.is_empty());
+        assert_eq!(parsed.to_string(), text);
+
+        // The interpreter has no C functions to call
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let result = crate::mir::Interpreter::new(&mir.program, &mut out, &mut err).run();
+        let error = result.unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("only be called from a compiled program"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_mir_intrinsic_calls() {
+        let input = r#"
+            fn main() { print(popcount(255)); }
+            #[intrinsic("llvm.ctpop.i32")] extern fn popcount(x: Int) -> Int;
+        "#;
+        let mir = build_mir(input).unwrap();
+        let text = mir.program.to_string();
+        // Declared and called by the intrinsic's name, even before the declaration
+        assert!(text.starts_with("extern fn llvm.ctpop.i32(x: Int) -> Int\n"));
+        assert!(text.contains("= call llvm.ctpop.i32("), "{}", text);
+        assert!(!text.contains("popcount"));
+        assert_eq!(
+            crate::mir::MirProgram::parse(&text).unwrap().to_string(),
+            text
+        );
+    }
+
+    #[test]
+    fn test_mir_c_strings() {
+        let input = r#"
+            export "C" fn greet(name: CStr) -> CStr { return name; }
+            fn main() {
+                let copy = fromCStr(greet(toCStr("doo")));
+                print(copy ?? "none", takeCStr(cStr("c")) ?? "none");
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let text = mir.program.to_string();
+        assert!(text.contains("export fn greet(name: CStr) -> CStr {"));
+        assert_eq!(
+            crate::mir::MirProgram::parse(&text).unwrap().to_string(),
+            text
+        );
+        assert!(mir
+            .program
+            .c_header("greet")
+            .contains("\nchar *greet(char *name);\n"));
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = crate::mir::Interpreter::new(&mir.program, &mut out, &mut err).run();
+        assert_eq!(code, Ok(0));
+        assert_eq!(String::from_utf8(out).unwrap(), "doo c\n");
+    }
+
+    #[test]
+    fn test_mir_export_header() {
+        let input = r#"
+            export "C" fn add(a: Int, b: Int) -> Int { return a + b; }
+            export "C" fn scale(x: Float, n: Int64) -> Float { return x; }
+            export "C" fn reset() { }
+            fn helper() -> Int { return 1; }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let text = mir.program.to_string();
+        assert!(text.contains("export fn add(a: Int, b: Int) -> Int {"));
+        assert!(text.contains("\nfn helper() -> Int {"));
+        let parsed = crate::mir::MirProgram::parse(&text).unwrap();
+        let export: Vec<bool> = parsed.functions.iter().map(|f| f.export).collect();
+        assert_eq!(export, vec![true, true, true, false]);
+
+        let header = mir.program.c_header("my-lib");
+        assert!(header.contains("#ifndef DOO_MY_LIB_H"));
+        assert!(header.contains("extern \"C\" {"));
+        assert!(header.contains("\nint32_t add(int32_t a, int32_t b);\n"));
+        assert!(header.contains("\ndouble scale(double x, int64_t n);\n"));
+        assert!(header.contains("\nvoid reset(void);\n"));
+        assert!(!header.contains("helper"));
+    }
+
     // =====================
     // Stress Test: Many Function Definitions (doolang syntax generated by Rust)
     // This test checks that the compiler can handle a large number of function definitions.
@@ -178,16 +1615,81 @@ mod mir_tests {
     }
 
     #[test]
-    fn test_mir_for_for_loop() {
+    fn test_mir_for_for_loop() {
+        let input = r#"
+            fn main() {
+                for i in 0..10 {
+                    let x = i;
+                }
+            }
+        "#;
+        let result = build_mir(input);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_mir_for_range_with_step() {
+        let input = r#"
+            fn main() {
+                for i in 10..0 step -2 {
+                    print(i);
+                }
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let main_fn = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        let instrs: Vec<_> = main_fn
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .collect();
+        // A negative literal step counts down: compare with `gt` and add the step
+        assert!(instrs.iter().any(|i| {
+            matches!(i, crate::mir::MirInstr::BinaryOp(op, _, lhs, rhs, _) if op == "gt" && lhs == "i" && rhs == "i_end")
+        }));
+        assert!(instrs.iter().any(|i| {
+            matches!(i, crate::mir::MirInstr::BinaryOp(op, _, lhs, rhs, _) if op == "add" && lhs == "i" && rhs == "i_step")
+        }));
+    }
+
+    #[test]
+    fn test_mir_for_index_and_item() {
         let input = r#"
             fn main() {
-                for i in 0..10 {
-                    let x = i;
+                let arr = [10, 20, 30];
+                for i, item in arr {
+                    print(i, item);
                 }
             }
         "#;
-        let result = build_mir(input);
-        assert!(result.is_ok());
+        let mir = build_mir(input).unwrap();
+        let main_fn = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        // The index comes from the loop's own counter, the item from ArrayGet
+        let body = main_fn
+            .blocks
+            .iter()
+            .find(|b| {
+                b.instrs
+                    .iter()
+                    .any(|i| matches!(i, crate::mir::MirInstr::ArrayGet { .. }))
+            })
+            .expect("loop body");
+        assert!(body.instrs.iter().any(|i| {
+            matches!(i, crate::mir::MirInstr::Assign { name, value, .. } if name == "i" && value.ends_with("__index"))
+        }));
+        assert!(body.instrs.iter().any(|i| {
+            matches!(i, crate::mir::MirInstr::Assign { name, value, .. } if name == "item" && value.starts_with('%'))
+        }));
     }
 
     #[test]
@@ -377,6 +1879,418 @@ mod mir_tests {
         assert!(found_array_get, "MIR should contain ArrayGet for arr[i]");
     }
 
+    #[test]
+    fn test_mir_nested_array_access() {
+        let input = r#"
+            fn main() {
+                let grid = [[1, 2], [3]];
+                let x = grid[1][0];
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let main_fn = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        let instrs: Vec<&crate::mir::MirInstr> = main_fn
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .collect();
+        // Two row literals plus the outer array
+        let arrays = instrs
+            .iter()
+            .filter(|i| matches!(i, crate::mir::MirInstr::Array { .. }))
+            .count();
+        assert_eq!(arrays, 3);
+        // grid[1][0] indexes the row returned by grid[1]
+        let gets: Vec<(&String, &String)> = instrs
+            .iter()
+            .filter_map(|i| match i {
+                crate::mir::MirInstr::ArrayGet { name, array, .. } => Some((name, array)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(gets.len(), 2);
+        assert_eq!(gets[1].1, gets[0].0);
+    }
+
+    #[test]
+    fn test_mir_map_collection_values() {
+        let input = r#"
+            fn main() {
+                let scores = {"ann": [90, 85], "bob": [70]};
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let main_fn = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        let instrs: Vec<&crate::mir::MirInstr> = main_fn
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .collect();
+        let arrays: Vec<&String> = instrs
+            .iter()
+            .filter_map(|i| match i {
+                crate::mir::MirInstr::Array { name, .. } => Some(name),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(arrays.len(), 2);
+        // Each value of the map is one of the array literals
+        let entries = instrs
+            .iter()
+            .find_map(|i| match i {
+                crate::mir::MirInstr::Map { entries, .. } => Some(entries),
+                _ => None,
+            })
+            .unwrap();
+        let values: Vec<&String> = entries.iter().map(|(_, v)| v).collect();
+        assert_eq!(values, arrays);
+    }
+
+    #[test]
+    fn test_mir_conversion_builtins() {
+        let input = r#"
+            fn main() {
+                let s = toStr(1.5);
+                let n = toInt(s);
+                let joined = s + toStr(n);
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let main_fn = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        let calls: Vec<&String> = main_fn
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .filter_map(|i| match i {
+                crate::mir::MirInstr::Call { func, .. } => Some(func),
+                _ => None,
+            })
+            .collect();
+        // Each call is tagged with its argument type
+        assert_eq!(calls, vec!["toStr:float", "toInt:string", "toStr:int"]);
+        // The result of toStr is a string, so `+` concatenates
+        let has_concat = main_fn
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .any(|i| matches!(i, crate::mir::MirInstr::StringConcat { .. }));
+        assert!(has_concat);
+    }
+
+    #[test]
+    fn test_mir_format_and_parse_builtins() {
+        let input = r#"
+            fn main() {
+                let s = format(2.5, 1);
+                let n = parseInt(s);
+                let x = parseFloat(s) ?? 0.0;
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let main_fn = &mir.program.functions[0];
+        let calls: Vec<(&String, usize)> = main_fn
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .filter_map(|i| match i {
+                crate::mir::MirInstr::Call { func, args, .. } => Some((func, args.len())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            calls,
+            vec![
+                (&"format:float".to_string(), 2),
+                (&"parseInt:string".to_string(), 1),
+                (&"parseFloat:string".to_string(), 1),
+            ]
+        );
+        // parseFloat's result is an optional, so `??` unwraps it
+        assert!(main_fn
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .any(|i| matches!(i, crate::mir::MirInstr::OptionalUnwrapOr { .. })));
+    }
+
+    #[test]
+    fn test_mir_match_lowering() {
+        let input = r#"
+            fn main() {
+                let n = 2;
+                let name = match n { 1 | 2 => "low", 3 => "mid", _ => "high" };
+                print(name);
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let main_fn = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        // One equality test per literal pattern, each ending in a CondJump
+        let tests = main_fn
+            .blocks
+            .iter()
+            .filter(|b| {
+                matches!(b.terminator, Some(crate::mir::MirInstr::CondJump { .. }))
+                    && b.instrs.iter().any(
+                        |i| matches!(i, crate::mir::MirInstr::BinaryOp(op, ..) if op == "eq:int"),
+                    )
+            })
+            .count();
+        assert_eq!(tests, 3);
+        // Every arm assigns its value to the same hidden result variable
+        let result_assigns = main_fn
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .filter(|i| {
+                matches!(i, crate::mir::MirInstr::Assign { name, .. } if name.starts_with("__match_"))
+            })
+            .count();
+        assert_eq!(result_assigns, 3);
+    }
+
+    /// Runs `input` through the interpreter, returning its exit code, output
+    /// and error output.
+    fn run_program(input: &str) -> (Result<i32, crate::mir::InterpError>, String, String) {
+        let mir = build_mir(input).unwrap();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = crate::mir::Interpreter::new(&mir.program, &mut out, &mut err).run();
+        (
+            code,
+            String::from_utf8(out).unwrap(),
+            String::from_utf8(err).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_mir_match_arms_all_return() {
+        // Without a `_` arm no test falls through to the end of the match,
+        // which is empty and dropped when every arm returns
+        let (code, out, _) = run_program(
+            r#"
+            fn f(b: Bool) -> Int { match b { true => { return 1; }, false => { return 0; } } }
+            fn main() { print(f(true), f(false)); }
+        "#,
+        );
+        assert_eq!(code, Ok(0));
+        assert_eq!(out, "1 0\n");
+
+        let (code, out, _) = run_program(
+            r#"
+            enum Shape { Circle(Int), Square(Int), Empty }
+            fn area(s: Shape) -> Int {
+                match s {
+                    Shape::Circle(r) => { return 3 * r * r; },
+                    Shape::Square(w) => { return w * w; },
+                    Shape::Empty => { return 0; }
+                }
+            }
+            fn main() { print(area(Shape::Circle(2)), area(Shape::Square(3)), area(Shape::Empty)); }
+        "#,
+        );
+        assert_eq!(code, Ok(0));
+        assert_eq!(out, "12 9 0\n");
+    }
+
+    #[test]
+    fn test_mir_closure_values() {
+        let input = r#"
+            fn apply(f: fn(Int) -> Int, v: Int) -> Int {
+                return f(v);
+            }
+            fn adder(n: Int) -> fn(Int) -> Int {
+                return |x: Int| x + n;
+            }
+            fn main() {
+                let base = 10;
+                let name = "doo";
+                let add = |x: Int| x + base;
+                let greet = |greeting: Str| greeting + " " + name;
+                let add3 = adder(3);
+                let twice = |x: Int| apply(add3, apply(add3, x));
+                print(add(5), apply(add, 1), greet("hi"), twice(1));
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let text = mir.program.to_string();
+        // Closure bodies become functions taking the captured values first
+        assert!(text.contains("fn main.closure.1(base: Int, x: Int) -> Int {"));
+        assert!(text.contains("fn adder.closure.1(n: Int, x: Int) -> Int {"));
+        assert!(text.contains("= make_closure main.closure.2(name)"));
+        assert!(text.contains("= call_closure add(%"));
+        assert!(text.contains(") : fn(Int) -> Int"));
+        let parsed = crate::mir::MirProgram::parse(&text).unwrap();
+        assert_eq!(parsed.to_string(), text);
+        assert_eq!(parsed.verify(), Ok(()));
+
+        let (code, out, _) = run_program(input);
+        assert_eq!(code, Ok(0));
+        assert_eq!(out, "15 11 hi doo 7\n");
+    }
+
+    #[test]
+    fn test_mir_closure_method_arguments() {
+        // Only an array's map, filter and reduce inline their closure; other
+        // methods get a closure value
+        let input = r#"
+            struct Counter { n: Int }
+            impl Counter {
+                fn apply(self, f: fn(Int) -> Int) -> Int {
+                    return f(self.n);
+                }
+            }
+            fn main() {
+                let k = 3;
+                let mut fs: [fn(Int) -> Int] = [];
+                fs.push(|x: Int| x * k);
+                let b = Counter { n: 4 };
+                let f = fs[0];
+                print(f(5), b.apply(|x: Int| x + 1));
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let text = mir.program.to_string();
+        assert!(text.contains("= make_closure main.closure.1(k)"));
+        assert!(text.contains("array_push fs, %"));
+        assert!(text.contains("= make_closure main.closure.2()"));
+        assert!(text.contains("= call Counter::apply(b, %"));
+        assert!(!text.contains("__push_"));
+        assert!(!text.contains("__apply_"));
+        assert_eq!(mir.program.verify(), Ok(()));
+
+        let (code, out, _) = run_program(input);
+        assert_eq!(code, Ok(0));
+        assert_eq!(out, "15 5\n");
+    }
+
+    #[test]
+    fn test_mir_if_expression() {
+        let input = r#"
+            fn main() {
+                let big = 3 > 2;
+                let x = if big { 10 } else { 20 };
+                print(x);
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let main_fn = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        // Both branches assign the hidden result variable, which `x` then copies
+        let result_assigns: Vec<&String> = main_fn
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .filter_map(|i| match i {
+                crate::mir::MirInstr::Assign { name, .. } if name.starts_with("__if_") => {
+                    Some(name)
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(result_assigns.len(), 2);
+        assert_eq!(result_assigns[0], result_assigns[1]);
+        let reads_result = main_fn.blocks.iter().flat_map(|b| b.instrs.iter()).any(|i| {
+            matches!(i, crate::mir::MirInstr::Assign { name, value, .. } if name == "x" && value == result_assigns[0])
+        });
+        assert!(reads_result);
+    }
+
+    #[test]
+    fn test_mir_block_expression() {
+        let input = r#"
+            fn main() {
+                let a = 4;
+                let y = { let t = a * a; t + 1 };
+                print(y);
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let main_fn = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        let instrs: Vec<_> = main_fn
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .collect();
+        // The block's statement runs before `y` takes the value of `t + 1`
+        let t_pos = instrs
+            .iter()
+            .position(|i| matches!(i, crate::mir::MirInstr::Assign { name, .. } if name == "t"))
+            .expect("assignment to t");
+        let y_pos = instrs
+            .iter()
+            .position(|i| matches!(i, crate::mir::MirInstr::Assign { name, .. } if name == "y"))
+            .expect("assignment to y");
+        assert!(t_pos < y_pos);
+    }
+
+    #[test]
+    fn test_mir_loop_break_value() {
+        let input = r#"
+            fn main() {
+                let mut i = 0;
+                let v = for {
+                    i += 1;
+                    if i > 3 { break i * 2; }
+                };
+                print(v);
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let main_fn = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        // `break i * 2` stores into the loop's result slot and jumps to the exit
+        let break_block = main_fn
+            .blocks
+            .iter()
+            .find(|b| {
+                b.instrs.iter().any(|i| {
+                    matches!(i, crate::mir::MirInstr::Assign { name, .. } if name.starts_with("__loop_"))
+                })
+            })
+            .expect("break value assignment");
+        let exit = match &break_block.terminator {
+            Some(crate::mir::MirInstr::Jump { target }) => target.clone(),
+            other => panic!("Expected jump to the loop exit, got {:?}", other),
+        };
+        // `v` reads the result slot in the exit block
+        let exit_block = main_fn.blocks.iter().find(|b| b.label == exit).unwrap();
+        assert!(exit_block.instrs.iter().any(|i| {
+            matches!(i, crate::mir::MirInstr::Assign { name, value, .. } if name == "v" && value.starts_with("__loop_"))
+        }));
+    }
+
     // Invalid array element access
     #[test]
     fn test_mir_array_access_invalid_empty_index() {
@@ -566,6 +2480,214 @@ mod mir_tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_mir_string_equality_uses_string_op() {
+        let input = r#"
+            fn main() {
+                let cmd = "run";
+                let isRun = cmd == "run";
+                let notBuild = cmd != "build";
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let main_fn = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        let ops: Vec<&String> = main_fn
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .filter_map(|i| match i {
+                crate::mir::MirInstr::BinaryOp(op, ..) => Some(op),
+                _ => None,
+            })
+            .collect();
+        assert!(ops.iter().any(|op| op.as_str() == "eq:string"));
+        assert!(ops.iter().any(|op| op.as_str() == "ne:string"));
+    }
+
+    #[test]
+    fn test_mir_string_ordering_uses_string_op() {
+        let input = r#"
+            fn main() {
+                let a = "apple";
+                let b = "banana";
+                let before = a < b;
+                let after = a >= b;
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let ops: Vec<&String> = mir.program.functions[0]
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .filter_map(|i| match i {
+                crate::mir::MirInstr::BinaryOp(op, ..) => Some(op),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ops, vec!["lt:string", "ge:string"]);
+    }
+
+    #[test]
+    fn test_mir_float_arithmetic() {
+        let input = r#"
+            fn main() {
+                let x: Float = 3.14;
+                let y = -x * 2.0;
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let main_fn = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        let instrs: Vec<&crate::mir::MirInstr> = main_fn
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .collect();
+        assert!(instrs
+            .iter()
+            .any(|i| matches!(i, crate::mir::MirInstr::BinaryOp(op, ..) if op == "mul:float")));
+        // Negation of a float subtracts from a float zero, not an int zero
+        assert!(instrs
+            .iter()
+            .any(|i| matches!(i, crate::mir::MirInstr::BinaryOp(op, ..) if op == "sub:float")));
+        assert!(!instrs
+            .iter()
+            .any(|i| matches!(i, crate::mir::MirInstr::ConstInt { .. })));
+    }
+
+    #[test]
+    fn test_mir_int64_arithmetic() {
+        let input = r#"
+            fn main() {
+                let x: Int64 = 1;
+                let y = x + 3000000000L;
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let main_fn = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        let instrs: Vec<&crate::mir::MirInstr> = main_fn
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .collect();
+        assert!(instrs.iter().any(|i| matches!(
+            i,
+            crate::mir::MirInstr::ConstInt64 {
+                value: 3000000000,
+                ..
+            }
+        )));
+        assert!(instrs
+            .iter()
+            .any(|i| matches!(i, crate::mir::MirInstr::BinaryOp(op, ..) if op == "add:int64")));
+    }
+
+    #[test]
+    fn test_mir_value_types() {
+        use crate::mir::{MirInstr, MirType};
+        let input = r#"
+            fn half(x: Float) -> Float {
+                return x / 2.0;
+            }
+            fn main() {
+                let n: Int64 = 4;
+                let h = half(1.5);
+                let big = h > 0.5;
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let main_fn = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        let instrs: Vec<&MirInstr> = main_fn
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .collect();
+        let assigned = |var: &str| {
+            instrs.iter().find_map(|i| match i {
+                MirInstr::Assign { name, ty, .. } if name == var => Some(ty.clone()),
+                _ => None,
+            })
+        };
+        // The annotation wins over the int literal's type
+        assert_eq!(assigned("n"), Some(MirType::Int64));
+        assert_eq!(assigned("h"), Some(MirType::Float));
+        assert_eq!(assigned("big"), Some(MirType::Bool));
+        assert!(instrs.iter().any(|i| matches!(
+            i,
+            MirInstr::Call { func, ty: MirType::Float, .. } if func == "half"
+        )));
+        assert!(instrs.iter().any(|i| matches!(
+            i,
+            MirInstr::BinaryOp(op, _, _, _, MirType::Bool) if op == "gt:float"
+        )));
+    }
+
+    #[test]
+    fn test_mir_optionals() {
+        let input = r#"
+            fn main() {
+                let mut x: Int? = null;
+                x = 4;
+                let y = x ?? 0;
+                if let n = x {
+                    print(n);
+                }
+            }
+        "#;
+        let mir = build_mir(input).unwrap();
+        let main_fn = mir
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        let instrs: Vec<&crate::mir::MirInstr> = main_fn
+            .blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter())
+            .collect();
+        // null and the plain 4 both become OptionalWrap of the declared type
+        let wraps: Vec<_> = instrs
+            .iter()
+            .filter_map(|i| match i {
+                crate::mir::MirInstr::OptionalWrap { value, ty, .. } => Some((value.is_some(), ty)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(wraps.len(), 2);
+        assert!(wraps
+            .iter()
+            .all(|(_, ty)| **ty == MirType::Optional(Box::new(MirType::Int))));
+        assert!(instrs
+            .iter()
+            .any(|i| matches!(i, crate::mir::MirInstr::OptionalUnwrapOr { .. })));
+        assert!(instrs
+            .iter()
+            .any(|i| matches!(i, crate::mir::MirInstr::OptionalIsSome { .. })));
+        assert!(instrs
+            .iter()
+            .any(|i| matches!(i, crate::mir::MirInstr::OptionalUnwrap { .. })));
+    }
+
     // =====================
     // Miscellaneous/Invalid/Edge Cases
     // =====================
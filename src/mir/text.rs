@@ -0,0 +1,1891 @@
+// MIR as text: `MirProgram` implements `Display`, and `MirProgram::parse`
+// reads that text back, so MIR can be dumped, edited by hand and handed to
+// codegen. One instruction per line, results first:
+//
+//     fn add(x: Int, y: Int) -> Int {
+//     Block0:
+//         %1 = add x, y : Int
+//         return %1
+//     }
+//
+// Names that aren't plain words are written as quoted strings, and types the
+// way the source writes them. Statement spans are not written.
+
+use crate::diagnostics::json_string;
+use crate::mir::{MirBlock, MirFunction, MirInstr, MirProgram, MirType};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Why MIR text could not be read, and on which line (1-based).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MirTextError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for MirTextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || matches!(ch, '_' | '%' | '.' | '$' | '@' | '#')
+}
+
+/// A value, block or type name: as is when it reads back as one word, quoted otherwise.
+struct Name<'a>(&'a str);
+
+impl fmt::Display for Name<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plain = !self.0.is_empty()
+            && self
+                .0
+                .split("::")
+                .all(|part| part.chars().all(is_word_char))
+            && !self.0.starts_with("::")
+            && !self.0.ends_with("::");
+        if plain {
+            write!(f, "{}", self.0)
+        } else {
+            write!(f, "{}", json_string(self.0))
+        }
+    }
+}
+
+fn names(values: &[String]) -> String {
+    values
+        .iter()
+        .map(|v| Name(v).to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl fmt::Display for MirInstr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MirInstr::IncRef { value } => write!(f, "incref {}", Name(value)),
+            MirInstr::DecRef { value } => write!(f, "decref {}", Name(value)),
+            MirInstr::ConstInt { name, value, ty } => {
+                write!(f, "{} = const_int {} : {}", Name(name), value, ty)
+            }
+            MirInstr::ConstInt64 { name, value } => {
+                write!(f, "{} = const_int64 {}", Name(name), value)
+            }
+            MirInstr::ConstFloat { name, value } => {
+                write!(f, "{} = const_float {:?}", Name(name), value)
+            }
+            MirInstr::ConstBool { name, value } => {
+                write!(f, "{} = const_bool {}", Name(name), value)
+            }
+            MirInstr::ConstString { name, value } => {
+                write!(f, "{} = const_string {}", Name(name), json_string(value))
+            }
+            MirInstr::Array { name, elements } => {
+                write!(f, "{} = array [{}]", Name(name), names(elements))
+            }
+            MirInstr::Map { name, entries } => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", Name(k), Name(v)))
+                    .collect();
+                write!(f, "{} = map {{{}}}", Name(name), entries.join(", "))
+            }
+            MirInstr::RangeCreate {
+                name,
+                start,
+                end,
+                inclusive,
+            } => {
+                let op = if *inclusive {
+                    "range_inclusive"
+                } else {
+                    "range"
+                };
+                write!(f, "{} = {} {}, {}", Name(name), op, Name(start), Name(end))
+            }
+            MirInstr::ArrayLen { name, array } => {
+                write!(f, "{} = array_len {}", Name(name), Name(array))
+            }
+            MirInstr::ArrayGet { name, array, index } => {
+                write!(
+                    f,
+                    "{} = array_get {}, {}",
+                    Name(name),
+                    Name(array),
+                    Name(index)
+                )
+            }
+            MirInstr::ArraySet {
+                array,
+                index,
+                value,
+            } => write!(
+                f,
+                "array_set {}, {}, {}",
+                Name(array),
+                Name(index),
+                Name(value)
+            ),
+            MirInstr::ArrayConcat {
+                name,
+                left,
+                right,
+                ty,
+            } => write!(
+                f,
+                "{} = array_concat {}, {} : {}",
+                Name(name),
+                Name(left),
+                Name(right),
+                ty
+            ),
+            MirInstr::ArrayPush { array, value, ty } => {
+                write!(f, "array_push {}, {} : {}", Name(array), Name(value), ty)
+            }
+            MirInstr::ArrayPop { name, array, ty } => {
+                write!(f, "{} = array_pop {} : {}", Name(name), Name(array), ty)
+            }
+            MirInstr::ArrayInsert {
+                array,
+                index,
+                value,
+                ty,
+            } => write!(
+                f,
+                "array_insert {}, {}, {} : {}",
+                Name(array),
+                Name(index),
+                Name(value),
+                ty
+            ),
+            MirInstr::ArrayRemove {
+                name,
+                array,
+                index,
+                ty,
+            } => write!(
+                f,
+                "{} = array_remove {}, {} : {}",
+                Name(name),
+                Name(array),
+                Name(index),
+                ty
+            ),
+            MirInstr::ArrayClear { array, ty } => write!(f, "array_clear {} : {}", Name(array), ty),
+            MirInstr::ArraySort { array, ty } => write!(f, "array_sort {} : {}", Name(array), ty),
+            MirInstr::ArrayReverse { array, ty } => {
+                write!(f, "array_reverse {} : {}", Name(array), ty)
+            }
+            MirInstr::ArraySum { name, array, ty } => {
+                write!(f, "{} = array_sum {} : {}", Name(name), Name(array), ty)
+            }
+            MirInstr::ArrayMin { name, array, ty } => {
+                write!(f, "{} = array_min {} : {}", Name(name), Name(array), ty)
+            }
+            MirInstr::ArrayMax { name, array, ty } => {
+                write!(f, "{} = array_max {} : {}", Name(name), Name(array), ty)
+            }
+            MirInstr::MapLen { name, map } => write!(f, "{} = map_len {}", Name(name), Name(map)),
+            MirInstr::MapGet { name, map, key, ty } => write!(
+                f,
+                "{} = map_get {}, {} : {}",
+                Name(name),
+                Name(map),
+                Name(key),
+                ty
+            ),
+            MirInstr::MapGetPair { name, map, index } => write!(
+                f,
+                "{} = map_get_pair {}, {}",
+                Name(name),
+                Name(map),
+                Name(index)
+            ),
+            MirInstr::MapSet { map, key, value } => {
+                write!(f, "map_set {}, {}, {}", Name(map), Name(key), Name(value))
+            }
+            MirInstr::MapInsert {
+                map,
+                key,
+                value,
+                ty,
+            } => write!(
+                f,
+                "map_insert {}, {}, {} : {}",
+                Name(map),
+                Name(key),
+                Name(value),
+                ty
+            ),
+            MirInstr::MapRemove { name, map, key, ty } => write!(
+                f,
+                "{} = map_remove {}, {} : {}",
+                Name(name),
+                Name(map),
+                Name(key),
+                ty
+            ),
+            MirInstr::MapClear { map, ty } => write!(f, "map_clear {} : {}", Name(map), ty),
+            MirInstr::MapContains { name, map, key, ty } => write!(
+                f,
+                "{} = map_contains {}, {} : {}",
+                Name(name),
+                Name(map),
+                Name(key),
+                ty
+            ),
+            MirInstr::MapKeys { name, map, ty } => {
+                write!(f, "{} = map_keys {} : {}", Name(name), Name(map), ty)
+            }
+            MirInstr::MapValues { name, map, ty } => {
+                write!(f, "{} = map_values {} : {}", Name(name), Name(map), ty)
+            }
+            MirInstr::StrLen { name, string } => {
+                write!(f, "{} = str_len {}", Name(name), Name(string))
+            }
+            MirInstr::StrContains { name, string, part } => write!(
+                f,
+                "{} = str_contains {}, {}",
+                Name(name),
+                Name(string),
+                Name(part)
+            ),
+            MirInstr::StrSplit { name, string, sep } => write!(
+                f,
+                "{} = str_split {}, {}",
+                Name(name),
+                Name(string),
+                Name(sep)
+            ),
+            MirInstr::StrTrim { name, string } => {
+                write!(f, "{} = str_trim {}", Name(name), Name(string))
+            }
+            MirInstr::StrReplace {
+                name,
+                string,
+                from,
+                to,
+            } => write!(
+                f,
+                "{} = str_replace {}, {}, {}",
+                Name(name),
+                Name(string),
+                Name(from),
+                Name(to)
+            ),
+            MirInstr::StrSubstring {
+                name,
+                string,
+                start,
+                end,
+            } => write!(
+                f,
+                "{} = str_substring {}, {}, {}",
+                Name(name),
+                Name(string),
+                Name(start),
+                Name(end)
+            ),
+            MirInstr::Add(dest, lhs, rhs, ty)
+            | MirInstr::Sub(dest, lhs, rhs, ty)
+            | MirInstr::Mul(dest, lhs, rhs, ty)
+            | MirInstr::Div(dest, lhs, rhs, ty) => {
+                let op = match self {
+                    MirInstr::Add(..) => "add",
+                    MirInstr::Sub(..) => "sub",
+                    MirInstr::Mul(..) => "mul",
+                    _ => "div",
+                };
+                write!(
+                    f,
+                    "{} = {} {}, {} : {}",
+                    Name(dest),
+                    op,
+                    Name(lhs),
+                    Name(rhs),
+                    ty
+                )
+            }
+            // The operator (`add`, `eq:int`, ...) is one word up to the next space
+            MirInstr::BinaryOp(op, dest, lhs, rhs, ty) => write!(
+                f,
+                "{} = binop {} {}, {} : {}",
+                Name(dest),
+                op,
+                Name(lhs),
+                Name(rhs),
+                ty
+            ),
+            MirInstr::StringConcat { name, left, right } => write!(
+                f,
+                "{} = string_concat {}, {}",
+                Name(name),
+                Name(left),
+                Name(right)
+            ),
+            MirInstr::Assign {
+                name,
+                value,
+                mutable,
+                ty,
+            } => {
+                let op = if *mutable { "assign_mut" } else { "assign" };
+                write!(f, "{} = {} {} : {}", Name(name), op, Name(value), ty)
+            }
+            MirInstr::TupleCreate { name, elements } => {
+                write!(f, "{} = tuple [{}]", Name(name), names(elements))
+            }
+            MirInstr::TupleExtract {
+                name,
+                source,
+                index,
+            } => write!(
+                f,
+                "{} = tuple_extract {}, {}",
+                Name(name),
+                Name(source),
+                index
+            ),
+            MirInstr::TupleGet { name, tuple, index } => {
+                write!(f, "{} = tuple_get {}, {}", Name(name), Name(tuple), index)
+            }
+            MirInstr::Arg { name } => write!(f, "{} = arg", Name(name)),
+            MirInstr::Call {
+                dest,
+                func,
+                args,
+                ty,
+            } => {
+                if !dest.is_empty() {
+                    write!(f, "{} = ", names(dest))?;
+                }
+                write!(f, "call {}({}) : {}", Name(func), names(args), ty)
+            }
+            MirInstr::MakeClosure {
+                name,
+                func,
+                captures,
+            } => write!(
+                f,
+                "{} = make_closure {}({})",
+                Name(name),
+                Name(func),
+                names(captures)
+            ),
+            MirInstr::CallClosure {
+                dest,
+                closure,
+                args,
+                ty,
+            } => {
+                if !dest.is_empty() {
+                    write!(f, "{} = ", names(dest))?;
+                }
+                write!(
+                    f,
+                    "call_closure {}({}) : {}",
+                    Name(closure),
+                    names(args),
+                    ty
+                )
+            }
+            MirInstr::Return { values } => {
+                if values.is_empty() {
+                    write!(f, "return")
+                } else {
+                    write!(f, "return {}", names(values))
+                }
+            }
+            MirInstr::Panic { message } => write!(f, "panic {}", Name(message)),
+            MirInstr::Jump { target } => write!(f, "jump {}", Name(target)),
+            MirInstr::CondJump {
+                cond,
+                then_block,
+                else_block,
+            } => write!(
+                f,
+                "cond_jump {}, {}, {}",
+                Name(cond),
+                Name(then_block),
+                Name(else_block)
+            ),
+            MirInstr::Print { values, types } => {
+                // Arrays and maps carry their type: `print %1 : [Int], %2`
+                let values: Vec<String> = values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| match types.get(i) {
+                        Some(Some(ty)) => format!("{} : {}", Name(value), ty),
+                        _ => Name(value).to_string(),
+                    })
+                    .collect();
+                if values.is_empty() {
+                    write!(f, "print")
+                } else {
+                    write!(f, "print {}", values.join(", "))
+                }
+            }
+            MirInstr::StructInit {
+                name,
+                struct_name,
+                fields,
+            } => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", Name(k), Name(v)))
+                    .collect();
+                write!(
+                    f,
+                    "{} = struct_init {} {{{}}}",
+                    Name(name),
+                    Name(struct_name),
+                    fields.join(", ")
+                )
+            }
+            MirInstr::StructGet {
+                name,
+                struct_instance,
+                struct_name,
+                field,
+            } => write!(
+                f,
+                "{} = struct_get {}, {}, {}",
+                Name(name),
+                Name(struct_instance),
+                Name(struct_name),
+                Name(field)
+            ),
+            MirInstr::StructSet {
+                struct_instance,
+                struct_name,
+                field,
+                value,
+            } => write!(
+                f,
+                "struct_set {}, {}, {}, {}",
+                Name(struct_instance),
+                Name(struct_name),
+                Name(field),
+                Name(value)
+            ),
+            MirInstr::OptionalWrap { name, value, ty } => match value {
+                Some(value) => write!(f, "{} = optional_wrap {} : {}", Name(name), Name(value), ty),
+                None => write!(f, "{} = optional_none : {}", Name(name), ty),
+            },
+            MirInstr::OptionalIsSome { name, optional } => {
+                write!(f, "{} = optional_is_some {}", Name(name), Name(optional))
+            }
+            MirInstr::OptionalUnwrap { name, optional, ty } => write!(
+                f,
+                "{} = optional_unwrap {} : {}",
+                Name(name),
+                Name(optional),
+                ty
+            ),
+            MirInstr::OptionalUnwrapOr {
+                name,
+                optional,
+                default,
+                ty,
+            } => write!(
+                f,
+                "{} = optional_unwrap_or {}, {} : {}",
+                Name(name),
+                Name(optional),
+                Name(default),
+                ty
+            ),
+            MirInstr::EnumInit {
+                name,
+                enum_name,
+                variant,
+                value,
+            } => {
+                write!(
+                    f,
+                    "{} = enum_init {}, {}",
+                    Name(name),
+                    Name(enum_name),
+                    Name(variant)
+                )?;
+                match value {
+                    Some(value) => write!(f, ", {}", Name(value)),
+                    None => Ok(()),
+                }
+            }
+            MirInstr::EnumMatch {
+                name,
+                enum_instance,
+                enum_name,
+                variant,
+            } => write!(
+                f,
+                "{} = enum_match {}, {}, {}",
+                Name(name),
+                Name(enum_instance),
+                Name(enum_name),
+                Name(variant)
+            ),
+            MirInstr::EnumPayload {
+                name,
+                enum_instance,
+                enum_name,
+                variant,
+            } => write!(
+                f,
+                "{} = enum_payload {}, {}, {}",
+                Name(name),
+                Name(enum_instance),
+                Name(enum_name),
+                Name(variant)
+            ),
+            MirInstr::ForRange {
+                var,
+                start,
+                end,
+                inclusive,
+                body_block,
+                exit_block,
+            } => write!(
+                f,
+                "for_range {}, {}, {}, {}, {}, {}",
+                Name(var),
+                Name(start),
+                Name(end),
+                inclusive,
+                Name(body_block),
+                Name(exit_block)
+            ),
+            MirInstr::ForArray {
+                var,
+                array,
+                index_var,
+                body_block,
+                exit_block,
+            } => write!(
+                f,
+                "for_array {}, {}, {}, {}, {}",
+                Name(var),
+                Name(array),
+                Name(index_var),
+                Name(body_block),
+                Name(exit_block)
+            ),
+            MirInstr::ForMap {
+                key_var,
+                value_var,
+                map,
+                index_var,
+                body_block,
+                exit_block,
+            } => write!(
+                f,
+                "for_map {}, {}, {}, {}, {}, {}",
+                Name(key_var),
+                Name(value_var),
+                Name(map),
+                Name(index_var),
+                Name(body_block),
+                Name(exit_block)
+            ),
+            MirInstr::ForInfinite { body_block } => write!(f, "for_infinite {}", Name(body_block)),
+            MirInstr::Break { target } => write!(f, "break {}", Name(target)),
+            MirInstr::Continue { target } => write!(f, "continue {}", Name(target)),
+            MirInstr::LoopBodyMarker {
+                var,
+                cond_block,
+                increment_block,
+            } => write!(
+                f,
+                "loop_body_marker {}, {}, {}",
+                Name(var),
+                Name(cond_block),
+                Name(increment_block)
+            ),
+            MirInstr::LoadArrayElement { dest, array, index } => write!(
+                f,
+                "{} = load_array_element {}, {}",
+                Name(dest),
+                Name(array),
+                Name(index)
+            ),
+            MirInstr::LoadMapPair {
+                key_dest,
+                val_dest,
+                map,
+                index,
+            } => write!(
+                f,
+                "{}, {} = load_map_pair {}, {}",
+                Name(key_dest),
+                Name(val_dest),
+                Name(map),
+                Name(index)
+            ),
+            MirInstr::ArrayLoopMarker {
+                array,
+                index,
+                item,
+                cond_block,
+            } => write!(
+                f,
+                "array_loop_marker {}, {}, {}, {}",
+                Name(array),
+                Name(index),
+                Name(item),
+                Name(cond_block)
+            ),
+            MirInstr::MapLoopMarker {
+                map,
+                index,
+                key,
+                value,
+                cond_block,
+            } => write!(
+                f,
+                "map_loop_marker {}, {}, {}, {}, {}",
+                Name(map),
+                Name(index),
+                Name(key),
+                Name(value),
+                Name(cond_block)
+            ),
+        }
+    }
+}
+
+/// Instructions that end a block; the last line of a block is its terminator if it is one.
+fn is_terminator(instr: &MirInstr) -> bool {
+    matches!(
+        instr,
+        MirInstr::Jump { .. }
+            | MirInstr::CondJump { .. }
+            | MirInstr::Return { .. }
+            | MirInstr::Panic { .. }
+    )
+}
+
+/// `fn name(params) -> type`, the part of a function before its body.
+fn write_signature(f: &mut fmt::Formatter<'_>, func: &MirFunction) -> fmt::Result {
+    let params: Vec<String> = func
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, param)| match func.param_types.get(i) {
+            Some(Some(ty)) => format!("{}: {}", Name(param), ty),
+            _ => Name(param).to_string(),
+        })
+        .collect();
+    write!(f, "fn {}({})", Name(&func.name), params.join(", "))?;
+    if let Some(ty) = &func.return_type {
+        write!(f, " -> {}", ty)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for MirFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.arena {
+            write!(f, "arena ")?;
+        }
+        if self.export {
+            write!(f, "export ")?;
+        }
+        write_signature(f, self)?;
+        writeln!(f, " {{")?;
+        for block in &self.blocks {
+            writeln!(f, "{}:", Name(&block.label))?;
+            for instr in block.instrs.iter().chain(&block.terminator) {
+                writeln!(f, "    {}", instr)?;
+            }
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for MirProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.is_main_entry {
+            writeln!(f, "module\n")?;
+        }
+        let mut structs: Vec<_> = self.structs.iter().collect();
+        structs.sort_by_key(|(name, _)| name.as_str());
+        for (name, fields) in structs {
+            writeln!(f, "struct {} {{", Name(name))?;
+            let weak = self.weak_fields.get(name);
+            for (field, ty) in fields {
+                let prefix = if weak.is_some_and(|weak| weak.contains(field)) {
+                    "weak "
+                } else {
+                    ""
+                };
+                writeln!(f, "    {}{}: {}", prefix, Name(field), ty)?;
+            }
+            writeln!(f, "}}\n")?;
+        }
+        let mut enums: Vec<_> = self.enums.iter().collect();
+        enums.sort_by_key(|(name, _)| name.as_str());
+        for (name, variants) in enums {
+            writeln!(f, "enum {} {{", Name(name))?;
+            for (variant, payload) in variants {
+                match payload {
+                    Some(ty) => writeln!(f, "    {}({})", Name(variant), ty)?,
+                    None => writeln!(f, "    {}", Name(variant))?,
+                }
+            }
+            writeln!(f, "}}\n")?;
+        }
+        for func in &self.externs {
+            write!(f, "extern ")?;
+            write_signature(f, func)?;
+            writeln!(f)?;
+        }
+        if !self.externs.is_empty() {
+            writeln!(f)?;
+        }
+        if !self.globals.is_empty() {
+            writeln!(f, "globals {{")?;
+            for instr in &self.globals {
+                writeln!(f, "    {}", instr)?;
+            }
+            writeln!(f, "}}\n")?;
+        }
+        for (i, func) in self.functions.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", func)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads one line of MIR text.
+struct Cursor<'a> {
+    text: &'a str,
+    pos: usize,
+    structs: &'a HashSet<String>,
+    enums: &'a HashSet<String>,
+}
+
+impl<'a> Cursor<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn skip_ws(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_ws();
+        self.rest().is_empty()
+    }
+
+    /// Consumes `token` if the line continues with it.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_ws();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(format!("expected '{}' at '{}'", token, self.rest()))
+        }
+    }
+
+    /// Everything up to the next space.
+    fn word(&mut self) -> Result<&'a str, String> {
+        self.skip_ws();
+        let rest = self.rest();
+        let len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if len == 0 {
+            return Err("unexpected end of line".to_string());
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    /// A name as `Name` writes it: a quoted string or a plain word.
+    fn name(&mut self) -> Result<String, String> {
+        self.skip_ws();
+        if self.rest().starts_with('"') {
+            return self.string();
+        }
+        let rest = self.rest();
+        let mut len = 0;
+        loop {
+            let tail = &rest[len..];
+            if tail.starts_with("::") {
+                len += 2;
+            } else if let Some(ch) = tail.chars().next().filter(|ch| is_word_char(*ch)) {
+                len += ch.len_utf8();
+            } else {
+                break;
+            }
+        }
+        if len == 0 {
+            return Err(format!("expected a name at '{}'", rest));
+        }
+        self.pos += len;
+        Ok(rest[..len].to_string())
+    }
+
+    /// Names separated by commas, up to `close` (which is consumed).
+    fn names_until(&mut self, close: &str) -> Result<Vec<String>, String> {
+        let mut names = Vec::new();
+        while !self.eat(close) {
+            if !names.is_empty() {
+                self.expect(",")?;
+            }
+            names.push(self.name()?);
+        }
+        Ok(names)
+    }
+
+    /// `n` names separated by commas.
+    fn operands(&mut self, n: usize) -> Result<Vec<String>, String> {
+        let mut names = Vec::new();
+        for i in 0..n {
+            if i > 0 {
+                self.expect(",")?;
+            }
+            names.push(self.name()?);
+        }
+        Ok(names)
+    }
+
+    /// `name: value` pairs in braces.
+    fn pairs(&mut self) -> Result<Vec<(String, String)>, String> {
+        self.expect("{")?;
+        let mut pairs = Vec::new();
+        while !self.eat("}") {
+            if !pairs.is_empty() {
+                self.expect(",")?;
+            }
+            let key = self.name()?;
+            self.expect(":")?;
+            pairs.push((key, self.name()?));
+        }
+        Ok(pairs)
+    }
+
+    /// A string literal with JSON escapes.
+    fn string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut out = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, ch)) = chars.next() {
+            match ch {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(out);
+                }
+                '\\' => match chars.next().map(|(_, ch)| ch) {
+                    Some('n') => out.push('\n'),
+                    Some('r') => out.push('\r'),
+                    Some('t') => out.push('\t'),
+                    Some('u') => {
+                        let hex: String = chars.by_ref().take(4).map(|(_, ch)| ch).collect();
+                        let code = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("bad escape '\\u{}'", hex))?;
+                        out.push(code);
+                    }
+                    Some(ch) => out.push(ch),
+                    None => break,
+                },
+                ch => out.push(ch),
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> Result<T, String> {
+        self.skip_ws();
+        let rest = self.rest();
+        let len = rest
+            .find(|ch: char| ch.is_whitespace() || ch == ',')
+            .unwrap_or(rest.len());
+        let value = rest[..len]
+            .parse()
+            .map_err(|_| format!("expected a number at '{}'", rest))?;
+        self.pos += len;
+        Ok(value)
+    }
+
+    fn bool(&mut self) -> Result<bool, String> {
+        if self.eat("true") {
+            Ok(true)
+        } else if self.eat("false") {
+            Ok(false)
+        } else {
+            Err(format!("expected true or false at '{}'", self.rest()))
+        }
+    }
+
+    /// A type as `MirType` displays it. Names are structs or enums when the
+    /// program declares them.
+    fn ty(&mut self) -> Result<MirType, String> {
+        self.skip_ws();
+        let mut ty = if self.eat("(") {
+            let mut elements = Vec::new();
+            while !self.eat(")") {
+                if !elements.is_empty() {
+                    self.expect(",")?;
+                }
+                elements.push(self.ty()?);
+            }
+            MirType::Tuple(elements)
+        } else if self.rest().starts_with('"') {
+            let name = self.string()?;
+            self.named_type(name)
+        } else {
+            let rest = self.rest();
+            let mut len = 0;
+            loop {
+                let tail = &rest[len..];
+                if tail.starts_with("::") {
+                    len += 2;
+                } else if let Some(ch) = tail
+                    .chars()
+                    .next()
+                    .filter(|ch| ch.is_alphanumeric() || *ch == '_')
+                {
+                    len += ch.len_utf8();
+                } else {
+                    break;
+                }
+            }
+            if len == 0 {
+                return Err(format!("expected a type at '{}'", rest));
+            }
+            let base = &rest[..len];
+            self.pos += len;
+            match base {
+                "Array" | "Range" => {
+                    self.expect("<")?;
+                    let elem = Box::new(self.ty()?);
+                    self.expect(">")?;
+                    if base == "Array" {
+                        MirType::Array(elem)
+                    } else {
+                        MirType::Range(elem)
+                    }
+                }
+                "Map" => {
+                    self.expect("<")?;
+                    let key = self.ty()?;
+                    self.expect(",")?;
+                    let value = self.ty()?;
+                    self.expect(">")?;
+                    MirType::Map(Box::new(key), Box::new(value))
+                }
+                "Int" => MirType::Int,
+                "Int64" => MirType::Int64,
+                "Float" => MirType::Float,
+                "Bool" => MirType::Bool,
+                "String" => MirType::Str,
+                "CStr" => MirType::CStr,
+                "Void" => MirType::Void,
+                "Never" => MirType::Never,
+                "fn" => {
+                    self.expect("(")?;
+                    let mut params = Vec::new();
+                    while !self.eat(")") {
+                        if !params.is_empty() {
+                            self.expect(",")?;
+                        }
+                        params.push(self.ty()?);
+                    }
+                    let ret = if self.eat("->") {
+                        self.ty()?
+                    } else {
+                        MirType::Void
+                    };
+                    MirType::Function(params, Box::new(ret))
+                }
+                _ if self.rest().starts_with('<') => {
+                    // A generic instance such as `Result<Int, Str>` is named by its whole text
+                    let mut depth = 0;
+                    let mut end = 0;
+                    for (i, ch) in self.rest().char_indices() {
+                        match ch {
+                            '<' => depth += 1,
+                            '>' => depth -= 1,
+                            _ => {}
+                        }
+                        if depth == 0 {
+                            end = i + 1;
+                            break;
+                        }
+                    }
+                    if end == 0 {
+                        return Err(format!("unclosed '<' in type at '{}'", rest));
+                    }
+                    let name = format!("{}{}", base, &self.rest()[..end]);
+                    self.pos += end;
+                    self.named_type(name)
+                }
+                _ => self.named_type(base.to_string()),
+            }
+        };
+        while self.rest().starts_with('?') {
+            self.pos += 1;
+            ty = MirType::Optional(Box::new(ty));
+        }
+        Ok(ty)
+    }
+
+    fn named_type(&self, name: String) -> MirType {
+        if self.structs.contains(&name) {
+            MirType::Struct(name)
+        } else if self.enums.contains(&name) {
+            MirType::Enum(name)
+        } else {
+            MirType::Named(name)
+        }
+    }
+
+    /// ` : type` at the end of an instruction.
+    fn annotation(&mut self) -> Result<MirType, String> {
+        self.expect(":")?;
+        self.ty()
+    }
+
+    /// One instruction line.
+    fn instr(&mut self) -> Result<MirInstr, String> {
+        // Results come first, before ` = `
+        let start = self.pos;
+        let mut dests = Vec::new();
+        if let Ok(first) = self.name() {
+            dests.push(first);
+            while self.eat(",") {
+                dests.push(self.name()?);
+            }
+            if !self.eat("= ") {
+                self.pos = start;
+                dests.clear();
+            }
+        }
+        let mnemonic = self.word()?;
+        let one = |dests: &mut Vec<String>| -> Result<String, String> {
+            match dests.len() {
+                1 => Ok(dests.remove(0)),
+                _ => Err(format!("'{}' takes one result", mnemonic)),
+            }
+        };
+        let instr = match mnemonic {
+            "incref" => MirInstr::IncRef {
+                value: self.name()?,
+            },
+            "decref" => MirInstr::DecRef {
+                value: self.name()?,
+            },
+            "const_int" => MirInstr::ConstInt {
+                name: one(&mut dests)?,
+                value: self.number()?,
+                ty: self.annotation()?,
+            },
+            "const_int64" => MirInstr::ConstInt64 {
+                name: one(&mut dests)?,
+                value: self.number()?,
+            },
+            "const_float" => MirInstr::ConstFloat {
+                name: one(&mut dests)?,
+                value: self.number()?,
+            },
+            "const_bool" => MirInstr::ConstBool {
+                name: one(&mut dests)?,
+                value: self.bool()?,
+            },
+            "const_string" => MirInstr::ConstString {
+                name: one(&mut dests)?,
+                value: self.string()?,
+            },
+            "array" => {
+                self.expect("[")?;
+                MirInstr::Array {
+                    name: one(&mut dests)?,
+                    elements: self.names_until("]")?,
+                }
+            }
+            "map" => MirInstr::Map {
+                name: one(&mut dests)?,
+                entries: self.pairs()?,
+            },
+            "range" | "range_inclusive" => {
+                let [start, end] = take(self.operands(2)?);
+                MirInstr::RangeCreate {
+                    name: one(&mut dests)?,
+                    start,
+                    end,
+                    inclusive: mnemonic == "range_inclusive",
+                }
+            }
+            "array_len" => MirInstr::ArrayLen {
+                name: one(&mut dests)?,
+                array: self.name()?,
+            },
+            "array_get" => {
+                let [array, index] = take(self.operands(2)?);
+                MirInstr::ArrayGet {
+                    name: one(&mut dests)?,
+                    array,
+                    index,
+                }
+            }
+            "array_set" => {
+                let [array, index, value] = take(self.operands(3)?);
+                MirInstr::ArraySet {
+                    array,
+                    index,
+                    value,
+                }
+            }
+            "array_concat" => {
+                let [left, right] = take(self.operands(2)?);
+                MirInstr::ArrayConcat {
+                    name: one(&mut dests)?,
+                    left,
+                    right,
+                    ty: self.annotation()?,
+                }
+            }
+            "array_push" => {
+                let [array, value] = take(self.operands(2)?);
+                MirInstr::ArrayPush {
+                    array,
+                    value,
+                    ty: self.annotation()?,
+                }
+            }
+            "array_pop" => MirInstr::ArrayPop {
+                name: one(&mut dests)?,
+                array: self.name()?,
+                ty: self.annotation()?,
+            },
+            "array_insert" => {
+                let [array, index, value] = take(self.operands(3)?);
+                MirInstr::ArrayInsert {
+                    array,
+                    index,
+                    value,
+                    ty: self.annotation()?,
+                }
+            }
+            "array_remove" => {
+                let [array, index] = take(self.operands(2)?);
+                MirInstr::ArrayRemove {
+                    name: one(&mut dests)?,
+                    array,
+                    index,
+                    ty: self.annotation()?,
+                }
+            }
+            "array_clear" => MirInstr::ArrayClear {
+                array: self.name()?,
+                ty: self.annotation()?,
+            },
+            "array_sort" => MirInstr::ArraySort {
+                array: self.name()?,
+                ty: self.annotation()?,
+            },
+            "array_reverse" => MirInstr::ArrayReverse {
+                array: self.name()?,
+                ty: self.annotation()?,
+            },
+            "array_sum" => MirInstr::ArraySum {
+                name: one(&mut dests)?,
+                array: self.name()?,
+                ty: self.annotation()?,
+            },
+            "array_min" => MirInstr::ArrayMin {
+                name: one(&mut dests)?,
+                array: self.name()?,
+                ty: self.annotation()?,
+            },
+            "array_max" => MirInstr::ArrayMax {
+                name: one(&mut dests)?,
+                array: self.name()?,
+                ty: self.annotation()?,
+            },
+            "map_len" => MirInstr::MapLen {
+                name: one(&mut dests)?,
+                map: self.name()?,
+            },
+            "map_get" => {
+                let [map, key] = take(self.operands(2)?);
+                MirInstr::MapGet {
+                    name: one(&mut dests)?,
+                    map,
+                    key,
+                    ty: self.annotation()?,
+                }
+            }
+            "map_get_pair" => {
+                let [map, index] = take(self.operands(2)?);
+                MirInstr::MapGetPair {
+                    name: one(&mut dests)?,
+                    map,
+                    index,
+                }
+            }
+            "map_set" => {
+                let [map, key, value] = take(self.operands(3)?);
+                MirInstr::MapSet { map, key, value }
+            }
+            "map_insert" => {
+                let [map, key, value] = take(self.operands(3)?);
+                MirInstr::MapInsert {
+                    map,
+                    key,
+                    value,
+                    ty: self.annotation()?,
+                }
+            }
+            "map_remove" => {
+                let [map, key] = take(self.operands(2)?);
+                MirInstr::MapRemove {
+                    name: one(&mut dests)?,
+                    map,
+                    key,
+                    ty: self.annotation()?,
+                }
+            }
+            "map_clear" => MirInstr::MapClear {
+                map: self.name()?,
+                ty: self.annotation()?,
+            },
+            "map_contains" => {
+                let [map, key] = take(self.operands(2)?);
+                MirInstr::MapContains {
+                    name: one(&mut dests)?,
+                    map,
+                    key,
+                    ty: self.annotation()?,
+                }
+            }
+            "map_keys" => MirInstr::MapKeys {
+                name: one(&mut dests)?,
+                map: self.name()?,
+                ty: self.annotation()?,
+            },
+            "map_values" => MirInstr::MapValues {
+                name: one(&mut dests)?,
+                map: self.name()?,
+                ty: self.annotation()?,
+            },
+            "str_len" => MirInstr::StrLen {
+                name: one(&mut dests)?,
+                string: self.name()?,
+            },
+            "str_contains" => {
+                let [string, part] = take(self.operands(2)?);
+                MirInstr::StrContains {
+                    name: one(&mut dests)?,
+                    string,
+                    part,
+                }
+            }
+            "str_split" => {
+                let [string, sep] = take(self.operands(2)?);
+                MirInstr::StrSplit {
+                    name: one(&mut dests)?,
+                    string,
+                    sep,
+                }
+            }
+            "str_trim" => MirInstr::StrTrim {
+                name: one(&mut dests)?,
+                string: self.name()?,
+            },
+            "str_replace" => {
+                let [string, from, to] = take(self.operands(3)?);
+                MirInstr::StrReplace {
+                    name: one(&mut dests)?,
+                    string,
+                    from,
+                    to,
+                }
+            }
+            "str_substring" => {
+                let [string, start, end] = take(self.operands(3)?);
+                MirInstr::StrSubstring {
+                    name: one(&mut dests)?,
+                    string,
+                    start,
+                    end,
+                }
+            }
+            "add" | "sub" | "mul" | "div" => {
+                let dest = one(&mut dests)?;
+                let [lhs, rhs] = take(self.operands(2)?);
+                let ty = self.annotation()?;
+                match mnemonic {
+                    "add" => MirInstr::Add(dest, lhs, rhs, ty),
+                    "sub" => MirInstr::Sub(dest, lhs, rhs, ty),
+                    "mul" => MirInstr::Mul(dest, lhs, rhs, ty),
+                    _ => MirInstr::Div(dest, lhs, rhs, ty),
+                }
+            }
+            "binop" => {
+                let op = self.word()?.to_string();
+                let [lhs, rhs] = take(self.operands(2)?);
+                MirInstr::BinaryOp(op, one(&mut dests)?, lhs, rhs, self.annotation()?)
+            }
+            "string_concat" => {
+                let [left, right] = take(self.operands(2)?);
+                MirInstr::StringConcat {
+                    name: one(&mut dests)?,
+                    left,
+                    right,
+                }
+            }
+            "assign" | "assign_mut" => MirInstr::Assign {
+                name: one(&mut dests)?,
+                value: self.name()?,
+                mutable: mnemonic == "assign_mut",
+                ty: self.annotation()?,
+            },
+            "tuple" => {
+                self.expect("[")?;
+                MirInstr::TupleCreate {
+                    name: one(&mut dests)?,
+                    elements: self.names_until("]")?,
+                }
+            }
+            "tuple_extract" => {
+                let source = self.name()?;
+                self.expect(",")?;
+                MirInstr::TupleExtract {
+                    name: one(&mut dests)?,
+                    source,
+                    index: self.number()?,
+                }
+            }
+            "tuple_get" => {
+                let tuple = self.name()?;
+                self.expect(",")?;
+                MirInstr::TupleGet {
+                    name: one(&mut dests)?,
+                    tuple,
+                    index: self.number()?,
+                }
+            }
+            "arg" => MirInstr::Arg {
+                name: one(&mut dests)?,
+            },
+            "call" => {
+                let func = self.name()?;
+                self.expect("(")?;
+                MirInstr::Call {
+                    dest: std::mem::take(&mut dests),
+                    func,
+                    args: self.names_until(")")?,
+                    ty: self.annotation()?,
+                }
+            }
+            "make_closure" => {
+                let func = self.name()?;
+                self.expect("(")?;
+                MirInstr::MakeClosure {
+                    name: one(&mut dests)?,
+                    func,
+                    captures: self.names_until(")")?,
+                }
+            }
+            "call_closure" => {
+                let closure = self.name()?;
+                self.expect("(")?;
+                MirInstr::CallClosure {
+                    dest: std::mem::take(&mut dests),
+                    closure,
+                    args: self.names_until(")")?,
+                    ty: self.annotation()?,
+                }
+            }
+            "return" => {
+                let mut values = Vec::new();
+                while !self.at_end() {
+                    if !values.is_empty() {
+                        self.expect(",")?;
+                    }
+                    values.push(self.name()?);
+                }
+                MirInstr::Return { values }
+            }
+            "print" => {
+                let mut values = Vec::new();
+                let mut types = Vec::new();
+                while !self.at_end() {
+                    if !values.is_empty() {
+                        self.expect(",")?;
+                    }
+                    values.push(self.name()?);
+                    types.push(if self.eat(":") {
+                        Some(self.ty()?)
+                    } else {
+                        None
+                    });
+                }
+                MirInstr::Print { values, types }
+            }
+            "panic" => MirInstr::Panic {
+                message: self.name()?,
+            },
+            "jump" => MirInstr::Jump {
+                target: self.name()?,
+            },
+            "cond_jump" => {
+                let [cond, then_block, else_block] = take(self.operands(3)?);
+                MirInstr::CondJump {
+                    cond,
+                    then_block,
+                    else_block,
+                }
+            }
+            "struct_init" => MirInstr::StructInit {
+                name: one(&mut dests)?,
+                struct_name: self.name()?,
+                fields: self.pairs()?,
+            },
+            "struct_get" => {
+                let [struct_instance, struct_name, field] = take(self.operands(3)?);
+                MirInstr::StructGet {
+                    name: one(&mut dests)?,
+                    struct_instance,
+                    struct_name,
+                    field,
+                }
+            }
+            "struct_set" => {
+                let [struct_instance, struct_name, field, value] = take(self.operands(4)?);
+                MirInstr::StructSet {
+                    struct_instance,
+                    struct_name,
+                    field,
+                    value,
+                }
+            }
+            "optional_wrap" => MirInstr::OptionalWrap {
+                name: one(&mut dests)?,
+                value: Some(self.name()?),
+                ty: self.annotation()?,
+            },
+            "optional_none" => MirInstr::OptionalWrap {
+                name: one(&mut dests)?,
+                value: None,
+                ty: self.annotation()?,
+            },
+            "optional_is_some" => MirInstr::OptionalIsSome {
+                name: one(&mut dests)?,
+                optional: self.name()?,
+            },
+            "optional_unwrap" => MirInstr::OptionalUnwrap {
+                name: one(&mut dests)?,
+                optional: self.name()?,
+                ty: self.annotation()?,
+            },
+            "optional_unwrap_or" => {
+                let [optional, default] = take(self.operands(2)?);
+                MirInstr::OptionalUnwrapOr {
+                    name: one(&mut dests)?,
+                    optional,
+                    default,
+                    ty: self.annotation()?,
+                }
+            }
+            "enum_init" => {
+                let [enum_name, variant] = take(self.operands(2)?);
+                let value = if self.eat(",") {
+                    Some(self.name()?)
+                } else {
+                    None
+                };
+                MirInstr::EnumInit {
+                    name: one(&mut dests)?,
+                    enum_name,
+                    variant,
+                    value,
+                }
+            }
+            "enum_match" => {
+                let [enum_instance, enum_name, variant] = take(self.operands(3)?);
+                MirInstr::EnumMatch {
+                    name: one(&mut dests)?,
+                    enum_instance,
+                    enum_name,
+                    variant,
+                }
+            }
+            "enum_payload" => {
+                let [enum_instance, enum_name, variant] = take(self.operands(3)?);
+                MirInstr::EnumPayload {
+                    name: one(&mut dests)?,
+                    enum_instance,
+                    enum_name,
+                    variant,
+                }
+            }
+            "for_range" => {
+                let [var, start, end] = take(self.operands(3)?);
+                self.expect(",")?;
+                let inclusive = self.bool()?;
+                self.expect(",")?;
+                let [body_block, exit_block] = take(self.operands(2)?);
+                MirInstr::ForRange {
+                    var,
+                    start,
+                    end,
+                    inclusive,
+                    body_block,
+                    exit_block,
+                }
+            }
+            "for_array" => {
+                let [var, array, index_var, body_block, exit_block] = take(self.operands(5)?);
+                MirInstr::ForArray {
+                    var,
+                    array,
+                    index_var,
+                    body_block,
+                    exit_block,
+                }
+            }
+            "for_map" => {
+                let [key_var, value_var, map, index_var, body_block, exit_block] =
+                    take(self.operands(6)?);
+                MirInstr::ForMap {
+                    key_var,
+                    value_var,
+                    map,
+                    index_var,
+                    body_block,
+                    exit_block,
+                }
+            }
+            "for_infinite" => MirInstr::ForInfinite {
+                body_block: self.name()?,
+            },
+            "break" => MirInstr::Break {
+                target: self.name()?,
+            },
+            "continue" => MirInstr::Continue {
+                target: self.name()?,
+            },
+            "loop_body_marker" => {
+                let [var, cond_block, increment_block] = take(self.operands(3)?);
+                MirInstr::LoopBodyMarker {
+                    var,
+                    cond_block,
+                    increment_block,
+                }
+            }
+            "load_array_element" => {
+                let [array, index] = take(self.operands(2)?);
+                MirInstr::LoadArrayElement {
+                    dest: one(&mut dests)?,
+                    array,
+                    index,
+                }
+            }
+            "load_map_pair" => {
+                let [map, index] = take(self.operands(2)?);
+                let [key_dest, val_dest] = take(std::mem::take(&mut dests));
+                MirInstr::LoadMapPair {
+                    key_dest,
+                    val_dest,
+                    map,
+                    index,
+                }
+            }
+            "array_loop_marker" => {
+                let [array, index, item, cond_block] = take(self.operands(4)?);
+                MirInstr::ArrayLoopMarker {
+                    array,
+                    index,
+                    item,
+                    cond_block,
+                }
+            }
+            "map_loop_marker" => {
+                let [map, index, key, value, cond_block] = take(self.operands(5)?);
+                MirInstr::MapLoopMarker {
+                    map,
+                    index,
+                    key,
+                    value,
+                    cond_block,
+                }
+            }
+            _ => return Err(format!("unknown instruction '{}'", mnemonic)),
+        };
+        if !dests.is_empty() {
+            return Err(format!("'{}' has no result", mnemonic));
+        }
+        if !self.at_end() {
+            return Err(format!("unexpected '{}'", self.rest()));
+        }
+        Ok(instr)
+    }
+}
+
+/// The `N` names of `values`; callers only pass vectors of that length.
+fn take<const N: usize>(values: Vec<String>) -> [String; N] {
+    values
+        .try_into()
+        .unwrap_or_else(|_| unreachable!("operand count"))
+}
+
+impl MirProgram {
+    /// Reads MIR in the text form `Display` writes. Blank lines and lines
+    /// starting with `//` are skipped.
+    pub fn parse(text: &str) -> Result<MirProgram, MirTextError> {
+        let lines: Vec<(usize, &str)> = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with("//"))
+            .collect();
+
+        // Types name structs and enums declared anywhere in the file
+        let mut structs = HashSet::new();
+        let mut enums = HashSet::new();
+        let no_names = HashSet::new();
+        for &(number, line) in &lines {
+            for (keyword, names) in [("struct ", &mut structs), ("enum ", &mut enums)] {
+                if let Some(rest) = line.strip_prefix(keyword) {
+                    let mut cursor = Cursor {
+                        text: rest,
+                        pos: 0,
+                        structs: &no_names,
+                        enums: &no_names,
+                    };
+                    let name = cursor.name().map_err(|message| MirTextError {
+                        line: number,
+                        message,
+                    })?;
+                    names.insert(name);
+                }
+            }
+        }
+
+        let mut program = MirProgram {
+            functions: Vec::new(),
+            externs: Vec::new(),
+            globals: Vec::new(),
+            is_main_entry: true,
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            weak_fields: HashMap::new(),
+        };
+        let mut lines = lines.into_iter();
+        while let Some((number, line)) = lines.next() {
+            let error = |message: String| MirTextError {
+                line: number,
+                message,
+            };
+            let mut cursor = Cursor {
+                text: line,
+                pos: 0,
+                structs: &structs,
+                enums: &enums,
+            };
+            if line == "module" {
+                program.is_main_entry = false;
+            } else if cursor.eat("struct ") {
+                let name = cursor.name().map_err(error)?;
+                let mut fields = Vec::new();
+                let mut weak_fields = Vec::new();
+                for (number, line) in body(&mut lines, number)? {
+                    let mut cursor = Cursor {
+                        text: line,
+                        pos: 0,
+                        structs: &structs,
+                        enums: &enums,
+                    };
+                    let weak = cursor.eat("weak ");
+                    let field = (|| {
+                        let field = cursor.name()?;
+                        cursor.expect(":")?;
+                        Ok((field, cursor.ty()?))
+                    })()
+                    .map_err(|message| MirTextError {
+                        line: number,
+                        message,
+                    })?;
+                    if weak {
+                        weak_fields.push(field.0.clone());
+                    }
+                    fields.push(field);
+                }
+                if !weak_fields.is_empty() {
+                    program.weak_fields.insert(name.clone(), weak_fields);
+                }
+                program.structs.insert(name, fields);
+            } else if cursor.eat("enum ") {
+                let name = cursor.name().map_err(error)?;
+                let mut variants = Vec::new();
+                for (number, line) in body(&mut lines, number)? {
+                    let mut cursor = Cursor {
+                        text: line,
+                        pos: 0,
+                        structs: &structs,
+                        enums: &enums,
+                    };
+                    let variant = (|| {
+                        let variant = cursor.name()?;
+                        let payload = if cursor.eat("(") {
+                            let ty = cursor.ty()?;
+                            cursor.expect(")")?;
+                            Some(ty)
+                        } else {
+                            None
+                        };
+                        Ok((variant, payload))
+                    })()
+                    .map_err(|message| MirTextError {
+                        line: number,
+                        message,
+                    })?;
+                    variants.push(variant);
+                }
+                program.enums.insert(name, variants);
+            } else if line == "globals {" {
+                for (number, line) in body(&mut lines, number)? {
+                    let mut cursor = Cursor {
+                        text: line,
+                        pos: 0,
+                        structs: &structs,
+                        enums: &enums,
+                    };
+                    let instr = cursor.instr().map_err(|message| MirTextError {
+                        line: number,
+                        message,
+                    })?;
+                    program.globals.push(instr);
+                }
+            } else if cursor.eat("extern fn ") {
+                let func = function_header(&mut cursor).map_err(error)?;
+                if !cursor.at_end() {
+                    return Err(error(format!("unexpected '{}'", cursor.rest())));
+                }
+                program.externs.push(func);
+            } else if line.starts_with("fn ")
+                || line.starts_with("arena ")
+                || line.starts_with("export ")
+            {
+                let arena = cursor.eat("arena ");
+                let export = cursor.eat("export ");
+                cursor.expect("fn ").map_err(error)?;
+                let mut func = function_header(&mut cursor).map_err(error)?;
+                cursor.expect("{").map_err(error)?;
+                func.arena = arena;
+                func.export = export;
+                for (number, line) in body(&mut lines, number)? {
+                    let error = |message: String| MirTextError {
+                        line: number,
+                        message,
+                    };
+                    if let Some(label) = line.strip_suffix(':') {
+                        let mut cursor = Cursor {
+                            text: label,
+                            pos: 0,
+                            structs: &structs,
+                            enums: &enums,
+                        };
+                        func.blocks.push(MirBlock {
+                            label: cursor.name().map_err(error)?,
+                            instrs: Vec::new(),
+                            terminator: None,
+                        });
+                        continue;
+                    }
+                    let mut cursor = Cursor {
+                        text: line,
+                        pos: 0,
+                        structs: &structs,
+                        enums: &enums,
+                    };
+                    let instr = cursor.instr().map_err(error)?;
+                    let Some(block) = func.blocks.last_mut() else {
+                        return Err(error("instruction outside a block".to_string()));
+                    };
+                    block.instrs.push(instr);
+                }
+                for block in &mut func.blocks {
+                    if block.instrs.last().is_some_and(is_terminator) {
+                        block.terminator = block.instrs.pop();
+                    }
+                }
+                program.functions.push(func);
+            } else {
+                return Err(error(format!("unexpected '{}'", line)));
+            }
+        }
+        Ok(program)
+    }
+}
+
+/// The lines of a `{ ... }` section up to its closing brace.
+fn body<'a>(
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+    start: usize,
+) -> Result<Vec<(usize, &'a str)>, MirTextError> {
+    let mut body = Vec::new();
+    for (number, line) in lines.by_ref() {
+        if line == "}" {
+            return Ok(body);
+        }
+        body.push((number, line));
+    }
+    Err(MirTextError {
+        line: start,
+        message: "missing '}'".to_string(),
+    })
+}
+
+/// `name(params) -> type` after `fn `.
+fn function_header(cursor: &mut Cursor) -> Result<MirFunction, String> {
+    let name = cursor.name()?;
+    cursor.expect("(")?;
+    let mut params = Vec::new();
+    let mut param_types = Vec::new();
+    while !cursor.eat(")") {
+        if !params.is_empty() {
+            cursor.expect(",")?;
+        }
+        params.push(cursor.name()?);
+        param_types.push(if cursor.eat(":") {
+            Some(cursor.ty()?)
+        } else {
+            None
+        });
+    }
+    let return_type = if cursor.eat("->") {
+        Some(cursor.ty()?)
+    } else {
+        None
+    };
+    Ok(MirFunction {
+        name,
+        params,
+        param_types,
+        return_type,
+        blocks: Vec::new(),
+        spans: Vec::new(),
+        arena: false,
+        export: false,
+    })
+}
@@ -0,0 +1,160 @@
+use crate::parser::ast::TypeNode;
+use std::fmt;
+
+/// The type of a MIR value: what codegen needs to choose its LLVM type and
+/// to reference count it. Built from the analyzer's `TypeNode`; structs and
+/// enums are known by name, their layouts live in `MirProgram`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MirType {
+    Int,
+    Int64,
+    Float,
+    Bool,
+    Str,
+    CStr,
+    Void,
+    Never,
+    Array(Box<MirType>),
+    Map(Box<MirType>, Box<MirType>),
+    Tuple(Vec<MirType>),
+    Optional(Box<MirType>), // `Optional(Void)` is a bare `null`
+    Range(Box<MirType>),
+    Struct(String),
+    Enum(String),
+    Named(String), // A type name that is neither a struct nor an enum known to MIR
+    Function(Vec<MirType>, Box<MirType>), // A closure value: its environment is on the heap
+}
+
+/// The name closure environments are released by, as if they were a struct
+/// of that name; no struct can have it since `fn` is a keyword.
+pub const CLOSURE_ENV: &str = "fn";
+
+impl MirType {
+    /// Values of heap types are pointers to reference-counted memory.
+    pub fn is_heap(&self) -> bool {
+        matches!(
+            self,
+            MirType::Str
+                | MirType::Array(_)
+                | MirType::Map(_, _)
+                | MirType::Struct(_)
+                | MirType::Enum(_)
+                | MirType::Function(_, _)
+        )
+    }
+
+    /// Name of a struct or enum type; enum values and closures are owned like
+    /// struct values.
+    pub fn struct_name(&self) -> Option<&str> {
+        match self {
+            MirType::Struct(name) | MirType::Enum(name) => Some(name),
+            MirType::Function(_, _) => Some(CLOSURE_ENV),
+            _ => None,
+        }
+    }
+
+    /// The payload of an optional type, or the type itself.
+    pub fn optional_payload(&self) -> &MirType {
+        match self {
+            MirType::Optional(payload) => payload,
+            ty => ty,
+        }
+    }
+
+    /// Optionals of scalars are a `{ i1, T }` pair; those of heap types a nullable pointer.
+    pub fn is_scalar_optional(&self) -> bool {
+        matches!(self, MirType::Optional(payload) if !payload.is_heap())
+    }
+
+    /// Element type of an array type.
+    pub fn element(&self) -> Option<&MirType> {
+        match self {
+            MirType::Array(elem) => Some(elem),
+            _ => None,
+        }
+    }
+
+    /// Key and value types of a map type.
+    pub fn map_parts(&self) -> Option<(&MirType, &MirType)> {
+        match self {
+            MirType::Map(key, value) => Some((key, value)),
+            _ => None,
+        }
+    }
+
+    /// Element types of a tuple type.
+    pub fn tuple_elements(&self) -> Option<&[MirType]> {
+        match self {
+            MirType::Tuple(elements) => Some(elements),
+            _ => None,
+        }
+    }
+}
+
+impl From<&TypeNode> for MirType {
+    fn from(ty: &TypeNode) -> Self {
+        match ty {
+            TypeNode::Int => MirType::Int,
+            TypeNode::Int64 => MirType::Int64,
+            TypeNode::Float => MirType::Float,
+            TypeNode::Bool => MirType::Bool,
+            TypeNode::String => MirType::Str,
+            TypeNode::CStr => MirType::CStr,
+            TypeNode::Void => MirType::Void,
+            TypeNode::Never => MirType::Never,
+            TypeNode::Array(elem) => MirType::Array(Box::new(elem.as_ref().into())),
+            // The arguments of a variadic parameter are passed as one array
+            TypeNode::Variadic(elem) => MirType::Array(Box::new(elem.as_ref().into())),
+            TypeNode::Map(key, value) => MirType::Map(
+                Box::new(key.as_ref().into()),
+                Box::new(value.as_ref().into()),
+            ),
+            TypeNode::Tuple(elements) => MirType::Tuple(elements.iter().map(Into::into).collect()),
+            TypeNode::Optional(payload) => MirType::Optional(Box::new(payload.as_ref().into())),
+            TypeNode::Range(start, _, _) => MirType::Range(Box::new(start.as_ref().into())),
+            TypeNode::Struct(name, _) => MirType::Struct(name.clone()),
+            TypeNode::Enum(name, _) => MirType::Enum(name.clone()),
+            TypeNode::TypeRef(name) => MirType::Named(name.clone()),
+            TypeNode::Function(params, ret) => MirType::Function(
+                params.iter().map(Into::into).collect(),
+                Box::new(ret.as_ref().into()),
+            ),
+        }
+    }
+}
+
+/// Types are written as in the source: `Array<Int>`, `Map<String, Int>`, `Int?`,
+/// `fn(Int) -> Int`.
+impl fmt::Display for MirType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MirType::Int => write!(f, "Int"),
+            MirType::Int64 => write!(f, "Int64"),
+            MirType::Float => write!(f, "Float"),
+            MirType::Bool => write!(f, "Bool"),
+            MirType::Str => write!(f, "String"),
+            MirType::CStr => write!(f, "CStr"),
+            MirType::Void => write!(f, "Void"),
+            MirType::Never => write!(f, "Never"),
+            MirType::Array(elem) => write!(f, "Array<{}>", elem),
+            MirType::Map(key, value) => write!(f, "Map<{}, {}>", key, value),
+            MirType::Tuple(elements) => {
+                let parts: Vec<String> = elements.iter().map(|t| t.to_string()).collect();
+                write!(f, "({})", parts.join(", "))
+            }
+            MirType::Optional(payload) => write!(f, "{}?", payload),
+            MirType::Range(elem) => write!(f, "Range<{}>", elem),
+            MirType::Struct(name) | MirType::Enum(name) | MirType::Named(name) => {
+                write!(f, "{}", name)
+            }
+            MirType::Function(params, ret) => {
+                let parts: Vec<String> = params.iter().map(|t| t.to_string()).collect();
+                write!(f, "fn({})", parts.join(", "))?;
+                match **ret {
+                    MirType::Void => Ok(()),
+                    _ => write!(f, " -> {}", ret),
+                }
+            }
+        }
+    }
+}
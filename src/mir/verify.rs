@@ -0,0 +1,195 @@
+use crate::mir::{MirBlock, MirFunction, MirInstr, MirProgram};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// A MIR invariant that a function breaks, found by `MirProgram::verify`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyError {
+    pub function: String,
+    pub block: String,
+    pub kind: VerifyErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyErrorKind {
+    MissingTerminator,
+    NotATerminator,        // The terminator slot holds an ordinary instruction
+    UnknownBlock(String),  // A jump or loop target that names no block
+    DuplicateBlock,        // Two blocks share this label
+    UndefinedTemp(String), // A temp read on a path where it was never written
+    MisplacedLoopMarker,   // A loop marker that isn't the first instruction of its block
+    MultipleLoopMarkers,   // More than one loop marker in a block
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "in {} at {}: ", self.function, self.block)?;
+        match &self.kind {
+            VerifyErrorKind::MissingTerminator => write!(f, "block has no terminator"),
+            VerifyErrorKind::NotATerminator => write!(f, "terminator is not a jump or return"),
+            VerifyErrorKind::UnknownBlock(label) => write!(f, "jump to unknown block '{}'", label),
+            VerifyErrorKind::DuplicateBlock => write!(f, "block label is used twice"),
+            VerifyErrorKind::UndefinedTemp(temp) => {
+                write!(f, "'{}' is used before it is defined", temp)
+            }
+            VerifyErrorKind::MisplacedLoopMarker => {
+                write!(f, "loop marker is not the first instruction")
+            }
+            VerifyErrorKind::MultipleLoopMarkers => {
+                write!(f, "block has more than one loop marker")
+            }
+        }
+    }
+}
+
+fn is_temp(value: &str) -> bool {
+    value.starts_with('%')
+}
+
+fn is_loop_marker(instr: &MirInstr) -> bool {
+    matches!(
+        instr,
+        MirInstr::LoopBodyMarker { .. }
+            | MirInstr::ArrayLoopMarker { .. }
+            | MirInstr::MapLoopMarker { .. }
+    )
+}
+
+/// Blocks the marker refers back to; an empty `increment_block` means there is none.
+fn marker_targets(instr: &MirInstr) -> Vec<&String> {
+    match instr {
+        MirInstr::LoopBodyMarker {
+            cond_block,
+            increment_block,
+            ..
+        } => {
+            let mut targets = vec![cond_block];
+            if !increment_block.is_empty() {
+                targets.push(increment_block);
+            }
+            targets
+        }
+        MirInstr::ArrayLoopMarker { cond_block, .. }
+        | MirInstr::MapLoopMarker { cond_block, .. } => {
+            vec![cond_block]
+        }
+        _ => vec![],
+    }
+}
+
+impl MirProgram {
+    /// Checks the invariants codegen relies on: every block ends with a
+    /// jump, return or panic, jumps and loop markers name blocks of their
+    /// function, each temp (`%N`) is written on some path before it is read,
+    /// and a block has at most one loop marker, as its first instruction.
+    pub fn verify(&self) -> Result<(), Vec<VerifyError>> {
+        let mut errors = Vec::new();
+        // Temps written by the global initializers are visible in every function
+        let globals: HashSet<&String> = self
+            .globals
+            .iter()
+            .flat_map(|instr| instr.defs())
+            .filter(|name| is_temp(name))
+            .collect();
+        for func in &self.functions {
+            verify_function(func, &globals, &mut errors);
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn verify_function(func: &MirFunction, globals: &HashSet<&String>, errors: &mut Vec<VerifyError>) {
+    let mut error = |block: &MirBlock, kind| {
+        errors.push(VerifyError {
+            function: func.name.clone(),
+            block: block.label.clone(),
+            kind,
+        })
+    };
+
+    let mut index: HashMap<&String, usize> = HashMap::new();
+    for (i, block) in func.blocks.iter().enumerate() {
+        if index.insert(&block.label, i).is_some() {
+            error(block, VerifyErrorKind::DuplicateBlock);
+        }
+    }
+
+    // Structure: terminators, targets and loop markers
+    let mut successors: Vec<Vec<usize>> = vec![Vec::new(); func.blocks.len()];
+    for (i, block) in func.blocks.iter().enumerate() {
+        match &block.terminator {
+            None => error(block, VerifyErrorKind::MissingTerminator),
+            Some(MirInstr::Jump { .. })
+            | Some(MirInstr::CondJump { .. })
+            | Some(MirInstr::Return { .. })
+            | Some(MirInstr::Panic { .. }) => {}
+            Some(_) => error(block, VerifyErrorKind::NotATerminator),
+        }
+        let targets = block
+            .instrs
+            .iter()
+            .chain(&block.terminator)
+            .flat_map(|instr| instr.targets().into_iter().chain(marker_targets(instr)));
+        for target in targets {
+            match index.get(target) {
+                Some(&j) => successors[i].push(j),
+                None => error(block, VerifyErrorKind::UnknownBlock(target.clone())),
+            }
+        }
+        let markers: Vec<usize> = block
+            .instrs
+            .iter()
+            .enumerate()
+            .filter(|(_, instr)| is_loop_marker(instr))
+            .map(|(pos, _)| pos)
+            .collect();
+        if markers.len() > 1 {
+            error(block, VerifyErrorKind::MultipleLoopMarkers);
+        } else if markers.first().is_some_and(|&pos| pos != 0) {
+            error(block, VerifyErrorKind::MisplacedLoopMarker);
+        }
+    }
+
+    // Temps written on some path into each block, propagated to a fixed point
+    let block_defs: Vec<HashSet<&String>> = func
+        .blocks
+        .iter()
+        .map(|block| {
+            block
+                .instrs
+                .iter()
+                .flat_map(|instr| instr.defs())
+                .filter(|name| is_temp(name))
+                .collect()
+        })
+        .collect();
+    let mut defined_in: Vec<HashSet<&String>> = vec![HashSet::new(); func.blocks.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in 0..func.blocks.len() {
+            let out: Vec<&String> = defined_in[i].union(&block_defs[i]).copied().collect();
+            for &j in &successors[i] {
+                for name in &out {
+                    changed |= defined_in[j].insert(name);
+                }
+            }
+        }
+    }
+
+    for (i, block) in func.blocks.iter().enumerate() {
+        let mut defined = defined_in[i].clone();
+        for instr in block.instrs.iter().chain(&block.terminator) {
+            for used in instr.uses() {
+                if is_temp(used) && !defined.contains(used) && !globals.contains(used) {
+                    error(block, VerifyErrorKind::UndefinedTemp(used.clone()));
+                }
+            }
+            defined.extend(instr.defs().into_iter().filter(|name| is_temp(name)));
+        }
+    }
+}
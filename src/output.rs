@@ -0,0 +1,90 @@
+// Terminal output shared by the compiler, the CLI and the diagnostics renderer:
+// whether to color it, and the ANSI color helpers that honor that choice.
+
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// When to color output, as chosen with `--color`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    Always,
+    Auto, // When stderr is a terminal and NO_COLOR isn't set
+    Never,
+}
+
+impl ColorChoice {
+    /// The choice named `always`, `auto` or `never`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "always" => Some(ColorChoice::Always),
+            "auto" => Some(ColorChoice::Auto),
+            "never" => Some(ColorChoice::Never),
+            _ => None,
+        }
+    }
+}
+
+// 0 until `set_color` is called, then 1 to color output and 2 not to
+static COLOR: AtomicU8 = AtomicU8::new(0);
+
+/// Sets whether output is colored from here on.
+pub fn set_color(choice: ColorChoice) {
+    let enabled = match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Auto => auto_color(),
+        ColorChoice::Never => false,
+    };
+    COLOR.store(if enabled { 1 } else { 2 }, Ordering::Relaxed);
+}
+
+/// Whether output is colored; decided as for `auto` until `set_color` is called.
+pub fn color_enabled() -> bool {
+    match COLOR.load(Ordering::Relaxed) {
+        0 => auto_color(),
+        setting => setting == 1,
+    }
+}
+
+/// Diagnostics go to stderr, so that is the stream checked. A non-empty
+/// NO_COLOR turns color off (https://no-color.org).
+fn auto_color() -> bool {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    !no_color && std::io::stderr().is_terminal()
+}
+
+/// `s` wrapped in the ANSI escape `code`, or `s` itself when color is off.
+fn paint(code: &str, s: &str) -> String {
+    if color_enabled() {
+        format!("\x1b[{}m{}\x1b[0m", code, s)
+    } else {
+        s.to_string()
+    }
+}
+
+pub fn red(s: &str) -> String {
+    paint("31", s)
+}
+pub fn bold_red(s: &str) -> String {
+    paint("1;31", s)
+}
+pub fn bold_green(s: &str) -> String {
+    paint("1;32", s)
+}
+pub fn yellow(s: &str) -> String {
+    paint("33", s)
+}
+pub fn bold_yellow(s: &str) -> String {
+    paint("1;33", s)
+}
+pub fn cyan(s: &str) -> String {
+    paint("36", s)
+}
+pub fn bold_cyan(s: &str) -> String {
+    paint("1;36", s)
+}
+pub fn dim(s: &str) -> String {
+    paint("2", s)
+}
+pub fn gray(s: &str) -> String {
+    paint("90", s)
+}
@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 
+pub use crate::lexar::token::Span;
 use crate::lexar::token::TokenType;
 use std::collections::HashMap;
 
@@ -7,7 +8,9 @@ use std::collections::HashMap;
 pub enum TypeNode {
     Float,
     Int,
+    Int64,
     String,
+    CStr, // A C `char *`, only passed to and from C functions
     Bool,
     Array(Box<TypeNode>),              // Array<Int>, Array<String>
     Map(Box<TypeNode>, Box<TypeNode>), // Map<String, Int>
@@ -17,6 +20,10 @@ pub enum TypeNode {
     Enum(String, HashMap<String, Option<TypeNode>>),
     Range(Box<TypeNode>, Box<TypeNode>, bool),
     TypeRef(String),
+    Optional(Box<TypeNode>),                // Int?, Str?
+    Variadic(Box<TypeNode>),                // `Int...`, only as the last function parameter
+    Never,                                  // `panic(..)`: never produces a value
+    Function(Vec<TypeNode>, Box<TypeNode>), // fn(Int, Str) -> Bool; the type of closure values
 }
 
 #[derive(Debug, Clone)]
@@ -26,19 +33,50 @@ pub enum Pattern {
     Wildcard,
 }
 
+/// One arm of a `match`: `1 | 2 => body`
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub patterns: Vec<MatchPattern>, // alternatives separated by `|`
+    pub body: Box<AstNode>,          // a Block, or a single expression
+}
+
+// A method a trait requires: `fn area(self) -> Float;`
+#[derive(Debug, Clone)]
+pub struct TraitMethod {
+    pub name: String,
+    pub params: Vec<(String, Option<TypeNode>)>, // starts with `self`
+    pub return_type: Option<TypeNode>,
+}
+
+#[derive(Debug, Clone)]
+pub enum MatchPattern {
+    Literal(AstNode), // NumberLiteral, Int64Literal, StringLiteral or BoolLiteral
+    Wildcard,         // _
+    // `Color::Red`, or `Shape::Circle(r)` binding the payload to `r`
+    // (`Shape::Circle` and `Shape::Circle(_)` ignore it)
+    Variant {
+        enum_name: String,
+        variant: String,
+        binding: Option<String>,
+    },
+}
+
 #[derive(Debug, Clone)]
 pub enum AstNode {
     Program(Vec<AstNode>),
     NumberLiteral(i32),
+    Int64Literal(i64), // Integer literal with an `L` suffix, e.g. 5000000000L
     FloatLiteral(f64),
-    Identifier(String),
+    Identifier(String, Span), // with where the name is
     StringLiteral(String),
     BoolLiteral(bool),
+    NullLiteral,
     ArrayLiteral(Vec<AstNode>),
     MapLiteral(Vec<(AstNode, AstNode)>),
     UnaryExpr {
         op: TokenType,
         expr: Box<AstNode>,
+        span: Span, // the operator
     },
 
     // 1+2 || a+2
@@ -46,6 +84,7 @@ pub enum AstNode {
         left: Box<AstNode>,
         op: TokenType,
         right: Box<AstNode>,
+        span: Span, // the operator
     },
 
     LetDecl {
@@ -59,6 +98,29 @@ pub enum AstNode {
     StructDecl {
         name: String,
         fields: Vec<(String, TypeNode)>,
+        weak_fields: Vec<String>, // `weak parent: Node?`: fields that don't keep their value alive
+    },
+
+    // impl User { fn greet(self) -> Str { ... } }; every method is a FunctionDecl
+    // whose first parameter is `self: User`. `impl Shape for User { ... }` sets `trait_name`.
+    ImplBlock {
+        struct_name: String,
+        trait_name: Option<String>,
+        methods: Vec<AstNode>,
+    },
+
+    // extern "C" fn puts(s: Str) -> Int; a C function, defined elsewhere
+    ExternFunctionDecl {
+        name: String,
+        params: Vec<(String, Option<TypeNode>)>,
+        return_type: Option<TypeNode>,
+        intrinsic: Option<String>, // `#[intrinsic("llvm.ctpop.i32")]`: the LLVM intrinsic calls go to instead
+    },
+
+    // trait Shape { fn area(self) -> Float; }
+    TraitDecl {
+        name: String,
+        methods: Vec<TraitMethod>,
     },
 
     EnumDecl {
@@ -71,6 +133,39 @@ pub enum AstNode {
         then_block: Vec<AstNode>,
         else_branch: Option<Box<AstNode>>,
     },
+    // let x = if cond { 10 } else { 20 }; each branch is a single expression,
+    // and `else_branch` may be another IfExpr for `else if`
+    IfExpr {
+        condition: Box<AstNode>,
+        then_branch: Box<AstNode>,
+        else_branch: Box<AstNode>,
+    },
+    // if let value = optional { ... } else { ... }
+    IfLet {
+        name: String,
+        value: Box<AstNode>,
+        then_block: Vec<AstNode>,
+        else_branch: Option<Box<AstNode>>,
+    },
+
+    // match value { 1 | 2 => ..., _ => ... }
+    Match {
+        value: Box<AstNode>,
+        arms: Vec<MatchArm>,
+    },
+
+    // A value (or null, when `value` is None) stored into a slot of optional type `ty`.
+    // Inserted by the analyzer, never produced by the parser.
+    OptionalWrap {
+        value: Option<Box<AstNode>>,
+        ty: TypeNode,
+    },
+    // The arguments a call passes to a variadic `T...` parameter, packed into
+    // one array of `elem_type`. Inserted by the analyzer, never produced by the parser.
+    VariadicArgs {
+        values: Vec<AstNode>,
+        elem_type: TypeNode,
+    },
     Block(Vec<AstNode>),
     Return {
         values: Vec<AstNode>, // multiple expressions can be returned
@@ -78,9 +173,37 @@ pub enum AstNode {
     Print {
         exprs: Vec<AstNode>,
     },
-    Break,
+    Break {
+        value: Option<Box<AstNode>>, // `break 42;` inside a loop used as a value
+    },
     Continue,
 
+    // let y = { let t = a * a; t + 1 }; statements followed by the value expression.
+    // `value_type` is filled in by the analyzer.
+    BlockExpr {
+        body: Vec<AstNode>,
+        value: Box<AstNode>,
+        value_type: Option<TypeNode>,
+    },
+
+    // let v = for { ... break 42; }; an infinite loop whose breaks carry its value.
+    // `value_type` is filled in by the analyzer.
+    LoopExpr {
+        body: Vec<AstNode>,
+        value_type: Option<TypeNode>,
+    },
+
+    // |x| x * 2; the argument of `map`, `filter` and `reduce`, or a value of
+    // its own. Parameters without a type, `value_type`, the body's type, and
+    // `captures`, the variables of the enclosing scope the body reads, are
+    // filled in by the analyzer.
+    Closure {
+        params: Vec<(String, Option<TypeNode>)>,
+        body: Box<AstNode>,
+        value_type: Option<TypeNode>,
+        captures: Vec<(String, TypeNode)>,
+    },
+
     Assignment {
         pattern: Pattern,
         value: Box<AstNode>,
@@ -98,6 +221,13 @@ pub enum AstNode {
         params: Vec<(String, Option<TypeNode>)>,
         return_type: Option<TypeNode>,
         body: Vec<AstNode>,
+        body_spans: Vec<Span>, // where each statement of `body` is, if known
+        // fn describe<T: Shape>(s: T); each type parameter with its optional trait bound
+        type_params: Vec<(String, Option<String>)>,
+        allow: Vec<String>, // lints named by `#[allow(..)]` before the function, not reported in it
+        arena: bool, // `#[arena]`: what the function allocates is freed at once when it returns
+        export: bool, // `export "C"`: callable from C under its own name
+        test: bool,  // `#[test]`: run by `doo test`, left out of other builds
     },
     FunctionCall {
         func: Box<AstNode>, // usually an Identifier node
@@ -107,7 +237,8 @@ pub enum AstNode {
     ForLoopStmt {
         pattern: Pattern,
         iterable: Option<Box<AstNode>>,
-        body: Vec<AstNode>, // keep Vec (block already returns Vec)
+        step: Option<Box<AstNode>>, // `for i in 10..0 step -2`, ranges only
+        body: Vec<AstNode>,         // keep Vec (block already returns Vec)
     },
 
     TupleLiteral(Vec<AstNode>),
@@ -122,6 +253,44 @@ pub enum AstNode {
     ElementAccess {
         array: Box<AstNode>,
         index: Box<AstNode>,
+        span: Span, // the `[`
+    },
+
+    // --- Struct Values ---
+    StructLiteral {
+        name: String,                   // e.g. "User"
+        fields: Vec<(String, AstNode)>, // in source order, e.g. `name: "A"`
+    },
+    FieldAccess {
+        object: Box<AstNode>, // e.g. `u` in `u.name`
+        field: String,
+        span: Span, // the field name
+    },
+    FieldAssignment {
+        object: Box<AstNode>, // e.g. `u` in `u.age = 4;`
+        field: String,
+        value: Box<AstNode>,
+    },
+    // `Color::Red`, `Shape::Circle(2.0)`
+    EnumValue {
+        enum_name: String,
+        variant: String,
+        payload: Option<Box<AstNode>>,
+    },
+    // `parse(s)?`: the Ok payload of a Result, or an early return of its Err.
+    // The analyzer records the Result types of the value and of the enclosing function.
+    Try {
+        expr: Box<AstNode>,
+        expr_type: Option<TypeNode>,
+        return_type: Option<TypeNode>,
+    },
+    // `u.greet(args)`; the analyzer replaces `method` with the name of the
+    // function implementing it (`User::greet`)
+    MethodCall {
+        object: Box<AstNode>,
+        method: String,
+        args: Vec<AstNode>,
+        span: Span, // the method name
     },
 
     // --- Module Import ---
@@ -130,3 +299,23 @@ pub enum AstNode {
         symbol: Option<String>, // e.g. Some("User") or None for wildcard
     },
 }
+
+impl AstNode {
+    /// Where an expression is: the name of a variable, call, method or field,
+    /// the operator of an operation, or the `[` of an element access. None
+    /// for literals and other nodes without a span of their own, and for
+    /// nodes the compiler made up.
+    pub fn span(&self) -> Option<Span> {
+        let span = match self {
+            AstNode::Identifier(_, span)
+            | AstNode::UnaryExpr { span, .. }
+            | AstNode::BinaryExpr { span, .. }
+            | AstNode::ElementAccess { span, .. }
+            | AstNode::FieldAccess { span, .. }
+            | AstNode::MethodCall { span, .. } => *span,
+            AstNode::FunctionCall { func, .. } => return func.span(),
+            _ => return None,
+        };
+        (span != Span::default()).then_some(span)
+    }
+}
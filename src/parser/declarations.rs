@@ -1,7 +1,11 @@
 use crate::lexar::token::TokenType;
-use crate::parser::ast::{AstNode, Pattern, TypeNode};
+use crate::parser::ast::{AstNode, Pattern, TraitMethod, TypeNode};
 use crate::parser::{ParseError, ParseResult, Parser};
 
+/// A function's parameters, each with its type when it has one, and its
+/// return type.
+type Signature = (Vec<(String, Option<TypeNode>)>, Option<TypeNode>);
+
 impl<'a> Parser<'a> {
     /// Let decl handles optional 'mut', pattern, optional type annotation, assignment, and semicolon.
     /// Example: `let mut x: Int = 42;`
@@ -57,7 +61,16 @@ impl<'a> Parser<'a> {
     /// Function decl handles function name, parameters (with mandatory types),
     /// optional return type, and body block.
     /// Example: `fn foo(a: Int, b: Str) -> Str { ... }`
+    /// The last parameter may be variadic: `fn sum(args: Int...) -> Int { ... }`
     pub fn parse_functional_decl(&mut self) -> ParseResult<AstNode> {
+        self.parse_function(None)
+    }
+
+    /// Shared by functions and methods. A method of `receiver` starts its
+    /// parameter list with a bare `self`, which gets the receiver's type.
+    /// Generic functions list their type parameters after the name:
+    /// `fn describe<T: Shape>(s: T) -> Str { ... }`
+    fn parse_function(&mut self, receiver: Option<&str>) -> ParseResult<AstNode> {
         self.expect(TokenType::Function)?; // consume 'fn'
 
         // Parse function name (identifier)
@@ -70,10 +83,167 @@ impl<'a> Parser<'a> {
             "Private".to_string()
         };
 
+        // Optional type parameters, each with an optional trait bound
+        let mut type_params = Vec::new();
+        if receiver.is_none() && self.consume_if(TokenType::Lt) {
+            type_params = self.parse_comma_separated(
+                |p| {
+                    let param = p.expect_ident()?;
+                    let bound = if p.consume_if(TokenType::Colon) {
+                        Some(p.expect_ident()?)
+                    } else {
+                        None
+                    };
+                    Ok((param, bound))
+                },
+                TokenType::Gt,
+            )?;
+            self.expect(TokenType::Gt)?;
+        }
+
+        let (params, return_type) = self.parse_function_signature(&func_name, receiver)?;
+
+        // Parse function body block
+        let (body_block, body_spans) = self.parse_braced_block_with_spans()?;
+
+        Ok(AstNode::FunctionDecl {
+            name: func_name,
+            visibility,
+            params,
+            return_type,
+            body: body_block,
+            body_spans,
+            type_params,
+            allow: Vec::new(),
+            arena: false,
+            export: false,
+            test: false,
+        })
+    }
+
+    /// Parses a function with attributes in front of it. `allow` names the
+    /// lints not to report for the function, and `arena` frees what it
+    /// allocates all at once when it returns:
+    /// `#[allow(unused)] #[arena] fn f() { ... }`
+    /// `test` marks a function `doo test` runs: `#[test] fn addsUp() { ... }`
+    /// `intrinsic` makes an extern function stand for an LLVM intrinsic:
+    /// `#[intrinsic("llvm.ctpop.i32")] extern fn popcount(x: Int) -> Int;`
+    pub fn parse_attributed_decl(&mut self) -> ParseResult<AstNode> {
+        let mut allow = Vec::new();
+        let mut arena = false;
+        let mut test = false;
+        let mut intrinsic = None;
+        while self.consume_if(TokenType::Pound) {
+            self.expect(TokenType::OpenBracket)?;
+            let attr = self.expect(TokenType::Identifier)?;
+            match attr.value {
+                "allow" => {
+                    self.expect(TokenType::OpenParen)?;
+                    allow.extend(
+                        self.parse_comma_separated(|p| p.expect_ident(), TokenType::CloseParen)?,
+                    );
+                    self.expect(TokenType::CloseParen)?;
+                }
+                "arena" => arena = true,
+                "test" => test = true,
+                "intrinsic" => {
+                    let (line, col) = (attr.line, attr.col);
+                    self.expect(TokenType::OpenParen)?;
+                    let name = self.expect(TokenType::String)?.value.to_string();
+                    intrinsic = Some((name, line, col));
+                    self.expect(TokenType::CloseParen)?;
+                }
+                _ => {
+                    return Err(ParseError::UnexpectedTokenAt {
+                        msg: format!(
+                        "Unknown attribute '{}', expected 'allow', 'arena', 'intrinsic' or 'test'",
+                        attr.value
+                    ),
+                        line: attr.line,
+                        col: attr.col,
+                    })
+                }
+            }
+            self.expect(TokenType::CloseBracket)?;
+        }
+
+        let mut decl = match self.peek() {
+            Some(tok) if tok.kind == TokenType::Function => self.parse_functional_decl()?,
+            Some(tok) if tok.kind == TokenType::Export => self.parse_export_decl()?,
+            Some(tok) if tok.kind == TokenType::Extern && !arena && !test => {
+                self.parse_extern_decl()?
+            }
+            Some(tok) => {
+                return Err(ParseError::UnexpectedTokenAt {
+                    msg: format!("Expected 'fn' after attribute, got {:?}", tok.kind),
+                    line: tok.line,
+                    col: tok.col,
+                })
+            }
+            None => return Err(ParseError::EndOfInput),
+        };
+        match &mut decl {
+            AstNode::FunctionDecl {
+                allow: lints,
+                arena: in_arena,
+                test: is_test,
+                ..
+            } => {
+                *lints = allow;
+                *in_arena = arena;
+                *is_test = test;
+                if let Some((_, line, col)) = intrinsic {
+                    return Err(ParseError::UnexpectedTokenAt {
+                        msg: "#[intrinsic] only applies to an extern fn".to_string(),
+                        line,
+                        col,
+                    });
+                }
+            }
+            AstNode::ExternFunctionDecl {
+                intrinsic: target, ..
+            } => *target = intrinsic.map(|(name, _, _)| name),
+            _ => {}
+        }
+        Ok(decl)
+    }
+
+    /// Parameters and optional return type of a function, from `(` to just
+    /// before its body.
+    fn parse_function_signature(
+        &mut self,
+        func_name: &str,
+        receiver: Option<&str>,
+    ) -> ParseResult<Signature> {
         self.expect(TokenType::OpenParen)?; // consume '('
 
+        let receiver_param = match receiver {
+            Some(struct_name) => {
+                let tok = self.peek().ok_or(ParseError::EndOfInput)?;
+                if tok.kind != TokenType::Identifier || tok.value != "self" {
+                    return Err(ParseError::UnexpectedTokenAt {
+                        msg: format!(
+                            "Method '{}' must take 'self' as its first parameter",
+                            func_name
+                        ),
+                        line: tok.line,
+                        col: tok.col,
+                    });
+                }
+                self.advance(); // consume 'self'
+                if !self.peek_is(TokenType::CloseParen) {
+                    self.expect(TokenType::Comma)?;
+                }
+                Some((
+                    "self".to_string(),
+                    Some(TypeNode::TypeRef(struct_name.to_string())),
+                ))
+            }
+            None => None,
+        };
+
         // Parse function parameters until ')' is found
-        let params = self.parse_comma_separated(
+        let mut params = self.parse_comma_separated(
             |p| {
                 let param_name = p.expect_ident()?;
                 // Enforce mandatory type annotation for each parameter
@@ -86,13 +256,38 @@ impl<'a> Parser<'a> {
                     });
                 }
                 p.advance(); // consume ':'
-                let param_type = Some(p.parse_type_annotation()?);
-                Ok((param_name, param_type))
+                let mut param_type = p.parse_type_annotation()?;
+                // `...` lexes as `..` followed by `.`
+                if p.peek_is(TokenType::RangeExc)
+                    && p.tokens.get(p.current + 1).map(|t| t.kind) == Some(TokenType::Dot)
+                {
+                    p.advance(); // consume '..'
+                    p.advance(); // consume '.'
+                    param_type = TypeNode::Variadic(Box::new(param_type));
+                }
+                Ok((param_name, Some(param_type)))
             },
             TokenType::CloseParen,
         )?;
+        if let Some(self_param) = receiver_param {
+            params.insert(0, self_param);
+        }
 
-        self.expect(TokenType::CloseParen)?; // consume ')'
+        // Only the last parameter can collect the extra arguments
+        let close_tok = self.expect(TokenType::CloseParen)?; // consume ')'
+        let (line, col) = (close_tok.line, close_tok.col);
+        let variadic_before_last = params
+            .iter()
+            .rev()
+            .skip(1)
+            .any(|(_, t)| matches!(t, Some(TypeNode::Variadic(_))));
+        if variadic_before_last {
+            return Err(ParseError::UnexpectedTokenAt {
+                msg: "Only the last function parameter can be variadic".to_string(),
+                line,
+                col,
+            });
+        }
 
         // Parse optional return type (e.g., '-> Type')
         let mut return_type = None;
@@ -105,16 +300,7 @@ impl<'a> Parser<'a> {
             }
         }
 
-        // Parse function body block
-        let body_block = self.parse_braced_block()?; // parse function body
-
-        Ok(AstNode::FunctionDecl {
-            name: func_name,
-            visibility,
-            params,
-            return_type,
-            body: body_block,
-        })
+        Ok((params, return_type))
     }
 
     /// Struct decl Handles struct name, fields (name and type), and braces.
@@ -126,10 +312,16 @@ impl<'a> Parser<'a> {
 
         self.expect(TokenType::OpenBrace)?; // `{`
 
-        // Parse fields until closing brace
+        // Parse fields until closing brace. `weak` before a field name marks
+        // it weak; a field can still be called `weak` (`weak: Int`).
+        let mut weak_fields = Vec::new();
         let fields = self.parse_comma_separated(
             |p| {
-                let field_name = p.expect_ident()?;
+                let mut field_name = p.expect_ident()?;
+                if field_name == "weak" && !p.peek_is(TokenType::Colon) {
+                    field_name = p.expect_ident()?;
+                    weak_fields.push(field_name.clone());
+                }
                 p.expect(TokenType::Colon)?;
                 let field_type = p.parse_type_annotation()?;
                 Ok((field_name, field_type))
@@ -142,6 +334,131 @@ impl<'a> Parser<'a> {
         Ok(AstNode::StructDecl {
             name: struct_name,
             fields,
+            weak_fields,
+        })
+    }
+
+    /// Impl block handles the struct name and the methods between braces.
+    /// Example: `impl User { fn greet(self) -> Str { ... } }`
+    /// A trait impl names the trait first: `impl Shape for Circle { ... }`
+    pub fn parse_impl_block(&mut self) -> ParseResult<AstNode> {
+        self.expect(TokenType::Impl)?; // consume 'impl'
+
+        let mut struct_name = self.expect_ident()?;
+        let mut trait_name = None;
+        if self.consume_if(TokenType::For) {
+            trait_name = Some(struct_name);
+            struct_name = self.expect_ident()?;
+        }
+
+        self.expect(TokenType::OpenBrace)?;
+
+        // Only methods are allowed inside
+        let mut methods = Vec::new();
+        while !self.peek_is(TokenType::CloseBrace) {
+            let tok = self.peek().ok_or(ParseError::EndOfInput)?;
+            if tok.kind != TokenType::Function {
+                return Err(ParseError::UnexpectedTokenAt {
+                    msg: format!("Expected 'fn' inside impl block, found {:?}", tok.kind),
+                    line: tok.line,
+                    col: tok.col,
+                });
+            }
+            methods.push(self.parse_function(Some(&struct_name))?);
+        }
+
+        self.expect(TokenType::CloseBrace)?;
+
+        Ok(AstNode::ImplBlock {
+            struct_name,
+            trait_name,
+            methods,
+        })
+    }
+
+    /// A C function's signature, ending with a semicolon instead of a body.
+    /// Example: `extern "C" fn puts(s: Str) -> Int;`
+    pub fn parse_extern_decl(&mut self) -> ParseResult<AstNode> {
+        self.expect(TokenType::Extern)?; // consume 'extern'
+        self.parse_abi()?;
+        self.expect(TokenType::Function)?; // consume 'fn'
+        let name = self.expect_ident()?;
+        let (params, return_type) = self.parse_function_signature(&name, None)?;
+        self.expect(TokenType::Semi)?;
+        Ok(AstNode::ExternFunctionDecl {
+            name,
+            params,
+            return_type,
+            intrinsic: None,
+        })
+    }
+
+    /// A function C code can call by its name.
+    /// Example: `export "C" fn add(a: Int, b: Int) -> Int { return a + b; }`
+    pub fn parse_export_decl(&mut self) -> ParseResult<AstNode> {
+        self.expect(TokenType::Export)?; // consume 'export'
+        self.parse_abi()?;
+        let mut decl = self.parse_functional_decl()?;
+        if let AstNode::FunctionDecl { export, .. } = &mut decl {
+            *export = true;
+        }
+        Ok(decl)
+    }
+
+    /// The ABI after `extern` or `export`. `"C"` is the only one, and may be left out.
+    fn parse_abi(&mut self) -> ParseResult<()> {
+        if let Some(tok) = self.peek() {
+            if tok.kind == TokenType::String {
+                if tok.value != "C" {
+                    return Err(ParseError::UnexpectedTokenAt {
+                        msg: format!("Unknown ABI \"{}\", expected \"C\"", tok.value),
+                        line: tok.line,
+                        col: tok.col,
+                    });
+                }
+                self.advance(); // consume "C"
+            }
+        }
+        Ok(())
+    }
+
+    /// Trait decl handles the trait name and the method signatures it requires,
+    /// each taking `self` and ending with a semicolon.
+    /// Example: `trait Shape { fn area(self) -> Float; }`
+    pub fn parse_trait_decl(&mut self) -> ParseResult<AstNode> {
+        self.expect(TokenType::Trait)?; // consume 'trait'
+
+        let trait_name = self.expect_ident()?;
+
+        self.expect(TokenType::OpenBrace)?;
+
+        let mut methods = Vec::new();
+        while !self.peek_is(TokenType::CloseBrace) {
+            let tok = self.peek().ok_or(ParseError::EndOfInput)?;
+            if tok.kind != TokenType::Function {
+                return Err(ParseError::UnexpectedTokenAt {
+                    msg: format!("Expected 'fn' inside trait, found {:?}", tok.kind),
+                    line: tok.line,
+                    col: tok.col,
+                });
+            }
+            self.advance(); // consume 'fn'
+            let name = self.expect_ident()?;
+            // `self` stands for whichever struct implements the trait
+            let (params, return_type) = self.parse_function_signature(&name, Some("Self"))?;
+            self.expect(TokenType::Semi)?;
+            methods.push(TraitMethod {
+                name,
+                params,
+                return_type,
+            });
+        }
+
+        self.expect(TokenType::CloseBrace)?;
+
+        Ok(AstNode::TraitDecl {
+            name: trait_name,
+            methods,
         })
     }
 
@@ -166,6 +483,7 @@ impl<'a> Parser<'a> {
                         p.advance();
                         let types = p.parse_type_annotation()?;
                         variant_data = Some(types);
+                        p.consume_if(TokenType::Comma);
                         p.expect(TokenType::CloseParen)?;
                     }
                 }
@@ -235,10 +553,10 @@ impl<'a> Parser<'a> {
         }
     }
 
-    /// Supports arrays, maps, primitive types
-    /// Examples: `Int`, `[Int]`, `{Str: Int}`, `Bool`
+    /// Supports arrays, maps, function types, primitive types
+    /// Examples: `Int`, `[Int]`, `{Str: Int}`, `fn(Int) -> Bool`, `Bool`
     /// Note: User defined types are not supported yet.
-    fn parse_type_annotation(&mut self) -> ParseResult<TypeNode> {
+    pub(crate) fn parse_type_annotation(&mut self) -> ParseResult<TypeNode> {
         self.depth += 1;
         if self.depth > super::parser::MAX_DEPTH {
             self.depth -= 1;
@@ -249,7 +567,7 @@ impl<'a> Parser<'a> {
             });
         }
 
-        let result = if self.peek_is(TokenType::OpenBracket) {
+        let base = if self.peek_is(TokenType::OpenBracket) {
             // Array type: [Type]
             self.advance(); // consume '['
             let inner = self.parse_type_annotation()?;
@@ -263,14 +581,32 @@ impl<'a> Parser<'a> {
             let value = self.parse_type_annotation()?;
             self.expect(TokenType::CloseBrace)?;
             Ok(TypeNode::Map(Box::new(key), Box::new(value)))
+        } else if self.consume_if(TokenType::Function) {
+            // Function type: fn(ParamType, ...) -> ReturnType, returning Void without `->`
+            self.expect(TokenType::OpenParen)?;
+            let params = self.parse_comma_separated(
+                |parser| parser.parse_type_annotation(),
+                TokenType::CloseParen,
+            )?;
+            self.expect(TokenType::CloseParen)?;
+            let ret = if self.consume_if(TokenType::Arrow) {
+                self.parse_type_annotation()?
+            } else {
+                TypeNode::Void
+            };
+            Ok(TypeNode::Function(params, Box::new(ret)))
         } else if self.peek_is(TokenType::Identifier) {
             // Primitive type
-            let tok = self.advance().unwrap();
-            match tok.value {
+            let name = self.advance().unwrap().value;
+            match name {
                 "Int" => Ok(TypeNode::Int),
+                "Int64" | "Long" => Ok(TypeNode::Int64),
+                "Float" => Ok(TypeNode::Float),
                 "Str" => Ok(TypeNode::String),
+                "CStr" => Ok(TypeNode::CStr),
                 "Bool" => Ok(TypeNode::Bool),
                 "Void" => Ok(TypeNode::Void),
+                "Result" if self.peek_is(TokenType::Lt) => self.parse_result_type(),
                 other => {
                     // Accept any previously declared struct as type
                     Ok(TypeNode::TypeRef(other.to_string()))
@@ -282,13 +618,71 @@ impl<'a> Parser<'a> {
             ))
         };
 
+        // Optional type: Type?
+        let result = match base {
+            Ok(ty) if self.consume_if(TokenType::Question) => Ok(TypeNode::Optional(Box::new(ty))),
+            other => other,
+        };
+
         self.depth -= 1;
         result
     }
 
+    /// `Result<T, E>`, after the `Result` name: the type of an enum with the
+    /// variants `Ok(T)` and `Err(E)` (a unit `Ok` for `Result<Void, E>`). The
+    /// enum is named after the type as written (`Result<Int, Str>`) and declared
+    /// once per program, see `parse_program`.
+    fn parse_result_type(&mut self) -> ParseResult<TypeNode> {
+        self.expect(TokenType::Lt)?;
+        let ok = self.parse_type_annotation()?;
+        self.expect(TokenType::Comma)?;
+        let err = self.parse_type_annotation()?;
+        self.consume_if(TokenType::Comma);
+        self.expect(TokenType::Gt)?;
+
+        let name = format!("Result<{}, {}>", type_source(&ok), type_source(&err));
+        let declared = self
+            .result_decls
+            .iter()
+            .any(|decl| matches!(decl, AstNode::EnumDecl { name: n, .. } if *n == name));
+        if !declared {
+            let ok = (ok != TypeNode::Void).then_some(ok);
+            self.result_decls.push(AstNode::EnumDecl {
+                name: name.clone(),
+                variants: vec![("Ok".to_string(), ok), ("Err".to_string(), Some(err))],
+            });
+        }
+        Ok(TypeNode::TypeRef(name))
+    }
+
     /// Expects and parses an identifier token, returning its string value.
-    fn expect_ident(&mut self) -> ParseResult<String> {
+    pub(crate) fn expect_ident(&mut self) -> ParseResult<String> {
         let tok = self.expect(TokenType::Identifier)?;
         Ok(tok.value.to_string())
     }
 }
+
+/// A type annotation as it is written in source: `Int`, `[Str]`, `{Str: Int}`, `Int?`, `fn(Int)`.
+fn type_source(ty: &TypeNode) -> String {
+    match ty {
+        TypeNode::Int => "Int".to_string(),
+        TypeNode::Int64 => "Int64".to_string(),
+        TypeNode::Float => "Float".to_string(),
+        TypeNode::String => "Str".to_string(),
+        TypeNode::CStr => "CStr".to_string(),
+        TypeNode::Bool => "Bool".to_string(),
+        TypeNode::Void => "Void".to_string(),
+        TypeNode::Array(elem) => format!("[{}]", type_source(elem)),
+        TypeNode::Map(key, value) => format!("{{{}: {}}}", type_source(key), type_source(value)),
+        TypeNode::Optional(inner) => format!("{}?", type_source(inner)),
+        TypeNode::TypeRef(name) => name.clone(),
+        TypeNode::Function(params, ret) => {
+            let params: Vec<String> = params.iter().map(type_source).collect();
+            match &**ret {
+                TypeNode::Void => format!("fn({})", params.join(", ")),
+                ret => format!("fn({}) -> {}", params.join(", "), type_source(ret)),
+            }
+        }
+        other => format!("{:?}", other),
+    }
+}
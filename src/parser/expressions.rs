@@ -1,7 +1,89 @@
-use crate::lexar::token::TokenType;
+use crate::lexar::token::{Token, TokenType};
 use crate::parser::ast::AstNode;
 use crate::parser::{ParseError, ParseResult, Parser};
 
+/// Builds the error for an invalid escape sequence found by the lexer.
+/// The token holds the offending escape (e.g. `\q`) and points at its position.
+pub(crate) fn invalid_escape_error(tok: &Token) -> ParseError {
+    ParseError::UnexpectedTokenAt {
+        msg: format!("Invalid escape sequence '{}' in string literal", tok.value),
+        line: tok.line,
+        col: tok.col,
+    }
+}
+
+/// The error for the Unknown `/*` token the lexer emits at the start of a
+/// block comment that is never closed.
+pub(crate) fn unterminated_comment_error(tok: &Token) -> ParseError {
+    ParseError::UnexpectedTokenAt {
+        msg: "Unterminated block comment: '/*' is never closed with '*/'".to_string(),
+        line: tok.line,
+        col: tok.col,
+    }
+}
+
+/// Converts the text of a Number token into a literal node, negated when it
+/// follows a `-`.
+/// Handles `0x`/`0b`/`0o` prefixes, `_` digit separators and the `L` (Int64) suffix,
+/// and rejects values that don't fit in the literal's type. The negative range
+/// reaches one further, so `-2147483648` and `-9223372036854775808L` are the minimums.
+pub(crate) fn int_literal_from_token(tok: &Token, negative: bool) -> ParseResult<AstNode> {
+    let error = |msg: String| ParseError::UnexpectedTokenAt {
+        msg,
+        line: tok.line,
+        col: tok.col,
+    };
+    let sign = if negative { "-" } else { "" };
+
+    let (text, is_int64) = match tok.value.strip_suffix('L') {
+        Some(rest) => (rest, true),
+        None => (tok.value, false),
+    };
+    let (digits, radix) = match text.get(..2) {
+        Some("0x") | Some("0X") => (&text[2..], 16),
+        Some("0b") | Some("0B") => (&text[2..], 2),
+        Some("0o") | Some("0O") => (&text[2..], 8),
+        _ => (text, 10),
+    };
+    let digits: String = digits.chars().filter(|c| *c != '_').collect();
+    if digits.is_empty() {
+        return Err(error(format!(
+            "Invalid integer literal: {}{}",
+            sign, tok.value
+        )));
+    }
+
+    let value = u64::from_str_radix(&digits, radix).map_err(|e| {
+        error(format!(
+            "Invalid integer literal '{}{}': {}",
+            sign, tok.value, e
+        ))
+    })?;
+    let value = if negative {
+        -i128::from(value)
+    } else {
+        i128::from(value)
+    };
+
+    if is_int64 {
+        match i64::try_from(value) {
+            Ok(value) => Ok(AstNode::Int64Literal(value)),
+            Err(_) => Err(error(format!(
+                "Integer literal {}{} is out of range for Int64",
+                sign, tok.value
+            ))),
+        }
+    } else {
+        match i32::try_from(value) {
+            Ok(value) => Ok(AstNode::NumberLiteral(value)),
+            Err(_) => Err(error(format!(
+                "Integer literal {}{} is out of range for Int (use the L suffix for Int64)",
+                sign, tok.value
+            ))),
+        }
+    }
+}
+
 impl<'a> Parser<'a> {
     /// Entry point for parsing any expression.
     /// Delegates to precedence-based parser.
@@ -36,14 +118,22 @@ impl<'a> Parser<'a> {
                         col: tok.col,
                     });
                 }
+                // A minus right before an integer is part of the literal, so
+                // the minimum of its type can be written
+                TokenType::Minus if self.negative_int_literal_ahead() => {
+                    self.advance(); // consume '-'
+                    let tok = self.advance().unwrap();
+                    int_literal_from_token(tok, true)?
+                }
                 // Allow unary minus and plus if desired
                 TokenType::Minus | TokenType::Plus => {
-                    let op = tok.kind;
+                    let (op, span) = (tok.kind, tok.span);
                     self.advance(); // consume operator
-                    let expr = self.parse_expression_prec(7)?; // unary has high precedence
+                    let expr = self.parse_expression_prec(8)?; // unary has high precedence
                     AstNode::UnaryExpr {
                         op,
                         expr: Box::new(expr),
+                        span,
                     }
                 }
                 // Primary expressions:
@@ -72,7 +162,7 @@ impl<'a> Parser<'a> {
                 break;
             }
 
-            let op = tok.kind;
+            let (op, span) = (tok.kind, tok.span);
             self.advance();
 
             // Recursively parse the right-hand side of the expression,
@@ -84,28 +174,83 @@ impl<'a> Parser<'a> {
                 left: Box::new(left),
                 op,
                 right: Box::new(right),
+                span,
             };
         }
 
         Ok(left)
     }
 
+    /// Whether a `-` is followed by an integer literal that nothing after it
+    /// binds to more tightly than the minus does: `-5`, but not `-5..0`.
+    fn negative_int_literal_ahead(&self) -> bool {
+        let kind = |offset: usize| self.tokens.get(self.current + offset).map(|t| t.kind);
+        kind(1) == Some(TokenType::Number)
+            && !matches!(
+                kind(2),
+                Some(
+                    TokenType::Dot
+                        | TokenType::OpenBracket
+                        | TokenType::Question
+                        | TokenType::RangeExc
+                        | TokenType::RangeInc
+                )
+            )
+    }
+
     /// Parses postfix operations on an expression.
-    /// Handles array/map element access: arr[0], map["key"], nested[i][j]
-    /// Can be chained: arr[0][1][2]
+    /// Handles array/map element access: arr[0], map["key"], nested[i][j],
+    /// struct field access: user.name, and error propagation: parse(s)?
+    /// Can be chained: arr[0][1][2], users[0].name, load(path)?.name
     fn parse_postfix(&mut self, mut expr: AstNode) -> ParseResult<AstNode> {
-        while self.peek_is(TokenType::OpenBracket) {
+        while self.peek_is(TokenType::OpenBracket)
+            || self.peek_is(TokenType::Dot)
+            || self.peek_is(TokenType::Question)
+        {
             if self.depth >= super::parser::MAX_DEPTH {
                 return Err(ParseError::UnexpectedToken(
                     "Expression too deeply nested".to_string(),
                 ));
             }
+            if self.consume_if(TokenType::Question) {
+                expr = AstNode::Try {
+                    expr: Box::new(expr),
+                    expr_type: None,
+                    return_type: None,
+                };
+                continue;
+            }
+            if self.consume_if(TokenType::Dot) {
+                let span = self.current_span();
+                let field = self.expect_ident()?;
+                // `u.greet(..)` calls a method, anything else reads a field
+                if self.consume_if(TokenType::OpenParen) {
+                    let args = self
+                        .parse_comma_separated(|p| p.parse_expression(), TokenType::CloseParen)?;
+                    self.expect(TokenType::CloseParen)?;
+                    expr = AstNode::MethodCall {
+                        object: Box::new(expr),
+                        method: field,
+                        args,
+                        span,
+                    };
+                    continue;
+                }
+                expr = AstNode::FieldAccess {
+                    object: Box::new(expr),
+                    field,
+                    span,
+                };
+                continue;
+            }
+            let span = self.current_span();
             self.advance(); // consume '['
             let index = self.parse_expression()?;
             self.expect(TokenType::CloseBracket)?;
             expr = AstNode::ElementAccess {
                 array: Box::new(expr),
                 index: Box::new(index),
+                span,
             };
         }
         Ok(expr)
@@ -118,18 +263,11 @@ impl<'a> Parser<'a> {
             match tok.kind {
                 TokenType::Number => {
                     let tok = self.advance().unwrap();
-                    match tok.value.parse::<i32>() {
-                        Ok(num) => Ok(AstNode::NumberLiteral(num)),
-                        Err(e) => Err(ParseError::UnexpectedTokenAt {
-                            msg: format!("Invalid integer literal: {}", e),
-                            line: tok.line,
-                            col: tok.col,
-                        }),
-                    }
+                    int_literal_from_token(tok, false)
                 }
                 TokenType::Float => {
                     let tok = self.advance().unwrap();
-                    match tok.value.parse::<f64>() {
+                    match tok.value.replace('_', "").parse::<f64>() {
                         Ok(num) => Ok(AstNode::FloatLiteral(num)),
                         Err(e) => Err(ParseError::UnexpectedTokenAt {
                             msg: format!("Invalid float literal: {}", e),
@@ -140,7 +278,7 @@ impl<'a> Parser<'a> {
                 }
                 TokenType::Identifier => {
                     let tok = self.advance().unwrap();
-                    let name = tok.value.to_string();
+                    let (name, span) = (tok.value.to_string(), tok.span);
 
                     // If followed by '(', parse as function call
                     if self.peek_is(TokenType::OpenParen) {
@@ -151,24 +289,76 @@ impl<'a> Parser<'a> {
                         )?;
                         self.expect(TokenType::CloseParen)?;
                         return Ok(AstNode::FunctionCall {
-                            func: Box::new(AstNode::Identifier(name)),
+                            func: Box::new(AstNode::Identifier(name, span)),
                             args,
                         });
                     }
 
-                    Ok(AstNode::Identifier(name))
+                    if self.is_struct_literal_start(&name) {
+                        return self.parse_struct_literal(name);
+                    }
+
+                    // `Color::Red` or `Shape::Circle(2.0)`; with no argument or
+                    // several it can only be a call into a module, `math::Add(1, 2)`
+                    if self.peek_is_path_sep() {
+                        self.advance(); // consume ':'
+                        self.advance(); // consume ':'
+                        let variant = self.expect_ident()?;
+                        let mut payload = None;
+                        if self.consume_if(TokenType::OpenParen) {
+                            let mut args = self.parse_comma_separated(
+                                |p| p.parse_expression(),
+                                TokenType::CloseParen,
+                            )?;
+                            self.expect(TokenType::CloseParen)?;
+                            if args.len() != 1 {
+                                return Ok(AstNode::FunctionCall {
+                                    func: Box::new(AstNode::Identifier(
+                                        format!("{}::{}", name, variant),
+                                        span,
+                                    )),
+                                    args,
+                                });
+                            }
+                            payload = args.pop().map(Box::new);
+                        }
+                        return Ok(AstNode::EnumValue {
+                            enum_name: name,
+                            variant,
+                            payload,
+                        });
+                    }
+
+                    Ok(AstNode::Identifier(name, span))
                 }
                 TokenType::String => {
                     let tok = self.advance().unwrap();
                     Ok(AstNode::StringLiteral(tok.value.to_string()))
                 }
+                // The lexer reports a bad escape as an Unknown token holding the escape text
+                TokenType::Unknown if tok.value.starts_with('\\') => Err(invalid_escape_error(tok)),
+                TokenType::Unknown if tok.value == "/*" => Err(unterminated_comment_error(tok)),
                 TokenType::Boolean => {
                     let tok = self.advance().unwrap();
                     let value = tok.value == "true";
                     Ok(AstNode::BoolLiteral(value))
                 }
+                TokenType::Null => {
+                    self.advance();
+                    Ok(AstNode::NullLiteral)
+                }
                 TokenType::OpenBracket => self.parse_array_literal(),
-                TokenType::OpenBrace => self.parse_map_literal(),
+                TokenType::OpenBrace => {
+                    if self.is_map_literal_start() {
+                        self.parse_map_literal()
+                    } else {
+                        self.parse_block_expr()
+                    }
+                }
+                TokenType::Match => self.parse_match(),
+                TokenType::If => self.parse_if_expr(),
+                TokenType::For => self.parse_loop_expr(),
+                TokenType::Or | TokenType::OrOr => self.parse_closure(),
                 TokenType::OpenParen => Err(ParseError::UnexpectedTokenAt {
                     msg: "Parentheses are not allowed in expressions in mtlang".to_string(),
                     line: tok.line,
@@ -196,6 +386,79 @@ impl<'a> Parser<'a> {
         Ok(AstNode::ArrayLiteral(elements))
     }
 
+    /// Example: `|x| x * 2`, `|acc, x: Int| acc + x`
+    /// Parameter types are optional; the analyzer takes them from the call.
+    fn parse_closure(&mut self) -> ParseResult<AstNode> {
+        // `||` lexes as a single token
+        let params = if self.consume_if(TokenType::OrOr) {
+            Vec::new()
+        } else {
+            self.expect(TokenType::Or)?;
+            let params = self.parse_comma_separated(
+                |p| {
+                    let name = p.expect_ident()?;
+                    let ty = if p.consume_if(TokenType::Colon) {
+                        Some(p.parse_type_annotation()?)
+                    } else {
+                        None
+                    };
+                    Ok((name, ty))
+                },
+                TokenType::Or,
+            )?;
+            self.expect(TokenType::Or)?;
+            params
+        };
+        let body = self.parse_expression()?;
+        Ok(AstNode::Closure {
+            params,
+            body: Box::new(body),
+            value_type: None,
+            captures: Vec::new(),
+        })
+    }
+
+    /// Looks past the `{` at the current token: braces that are empty or whose
+    /// first expression is followed by ':' open a map literal, anything else a block.
+    fn is_map_literal_start(&mut self) -> bool {
+        let start = self.current;
+        self.advance(); // consume '{'
+        self.speculative += 1;
+        let is_map = self.peek_is(TokenType::CloseBrace)
+            || (self.parse_expression().is_ok() && self.peek_is(TokenType::Colon));
+        self.speculative -= 1;
+        self.current = start;
+        is_map
+    }
+
+    /// A capitalized name followed by `{ field:` or `{}` starts a struct literal.
+    /// Struct names are capitalized, so `if ready { ... }` still opens a block.
+    fn is_struct_literal_start(&self, name: &str) -> bool {
+        let kind_at = |offset: usize| self.tokens.get(self.current + offset).map(|t| t.kind);
+        name.starts_with(|c: char| c.is_uppercase())
+            && kind_at(0) == Some(TokenType::OpenBrace)
+            && (kind_at(1) == Some(TokenType::CloseBrace)
+                || (kind_at(1) == Some(TokenType::Identifier)
+                    && kind_at(2) == Some(TokenType::Colon)))
+    }
+
+    /// Parses the fields of a struct literal after its name.
+    /// Example: `User { name: "A", age: 3 }`
+    fn parse_struct_literal(&mut self, name: String) -> ParseResult<AstNode> {
+        self.expect(TokenType::OpenBrace)?;
+        let fields = self.parse_comma_separated(
+            |p| {
+                let field = p.expect_ident()?;
+                p.expect(TokenType::Colon)?;
+                let value = p.parse_expression()?;
+                Ok((field, value))
+            },
+            TokenType::CloseBrace,
+        )?;
+        self.expect(TokenType::CloseBrace)?;
+        Ok(AstNode::StructLiteral { name, fields })
+    }
+
     /// Parses a map/dictionary literal.
     /// Example: `{ "a": 1, "b": 2 }`
     /// Each entry is a key-value pair separated by ':' and entries separated by ','.
@@ -220,13 +483,14 @@ impl<'a> Parser<'a> {
     /// Used in precedence climbing for binary expressions.
     fn get_precedence(op: TokenType) -> u8 {
         match op {
-            TokenType::OrOr => 1,
-            TokenType::AndAnd => 2,
-            TokenType::EqEq | TokenType::NotEq => 3,
-            TokenType::Lt | TokenType::Gt | TokenType::LtEq | TokenType::GtEq => 4,
-            TokenType::Plus | TokenType::Minus => 5,
-            TokenType::Star | TokenType::Slash | TokenType::Percent => 6,
-            TokenType::RangeExc | TokenType::RangeInc => 7, // Add range operators with lowest precedence
+            TokenType::QuestionQuestion => 1, // `a ?? b` binds loosest
+            TokenType::OrOr => 2,
+            TokenType::AndAnd => 3,
+            TokenType::EqEq | TokenType::NotEq => 4,
+            TokenType::Lt | TokenType::Gt | TokenType::LtEq | TokenType::GtEq => 5,
+            TokenType::Plus | TokenType::Minus => 6,
+            TokenType::Star | TokenType::Slash | TokenType::Percent => 7,
+            TokenType::RangeExc | TokenType::RangeInc => 8, // Add range operators with lowest precedence
             _ => 0,
         }
     }
@@ -0,0 +1,539 @@
+// The AST as JSON, for tools that don't link the compiler (`doo check --emit=ast-json`).
+// Every node is an object whose "kind" names its AstNode variant, with one
+// member per field. Types are written the way error messages show them.
+
+use crate::diagnostics::json_string;
+use crate::parser::ast::{AstNode, MatchPattern, Pattern, Span, TypeNode};
+
+/// A whole program: its statements, and where each one is in the source
+/// (`spans` may be shorter, or empty, when not known).
+pub fn program_to_json(statements: &[AstNode], spans: &[Span]) -> String {
+    object(
+        "Program",
+        vec![
+            ("statements", nodes(statements)),
+            ("spans", list(spans.iter().map(|span| span_json(*span)))),
+        ],
+    )
+}
+
+/// One node and everything below it.
+pub fn node_to_json(node: &AstNode) -> String {
+    match node {
+        AstNode::Program(statements) => program_to_json(statements, &[]),
+        AstNode::NumberLiteral(n) => object("NumberLiteral", vec![("value", n.to_string())]),
+        AstNode::Int64Literal(n) => object("Int64Literal", vec![("value", n.to_string())]),
+        AstNode::FloatLiteral(n) => object("FloatLiteral", vec![("value", float(*n))]),
+        AstNode::Identifier(name, span) => object(
+            "Identifier",
+            vec![("name", json_string(name)), ("span", span_json(*span))],
+        ),
+        AstNode::StringLiteral(s) => object("StringLiteral", vec![("value", json_string(s))]),
+        AstNode::BoolLiteral(b) => object("BoolLiteral", vec![("value", b.to_string())]),
+        AstNode::NullLiteral => object("NullLiteral", vec![]),
+        AstNode::ArrayLiteral(elements) => {
+            object("ArrayLiteral", vec![("elements", nodes(elements))])
+        }
+        AstNode::MapLiteral(entries) => object(
+            "MapLiteral",
+            vec![(
+                "entries",
+                list(entries.iter().map(|(key, value)| {
+                    format!("[{},{}]", node_to_json(key), node_to_json(value))
+                })),
+            )],
+        ),
+        AstNode::UnaryExpr { op, expr, span } => object(
+            "UnaryExpr",
+            vec![
+                ("op", json_string(&format!("{:?}", op))),
+                ("expr", node_to_json(expr)),
+                ("span", span_json(*span)),
+            ],
+        ),
+        AstNode::BinaryExpr {
+            left,
+            op,
+            right,
+            span,
+        } => object(
+            "BinaryExpr",
+            vec![
+                ("left", node_to_json(left)),
+                ("op", json_string(&format!("{:?}", op))),
+                ("right", node_to_json(right)),
+                ("span", span_json(*span)),
+            ],
+        ),
+        AstNode::LetDecl {
+            mutable,
+            type_annotation,
+            pattern,
+            value,
+            ..
+        } => object(
+            "LetDecl",
+            vec![
+                ("mutable", mutable.to_string()),
+                ("type_annotation", optional_type(type_annotation)),
+                ("pattern", pattern_json(pattern)),
+                ("value", node_to_json(value)),
+            ],
+        ),
+        AstNode::StructDecl {
+            name,
+            fields,
+            weak_fields,
+        } => object(
+            "StructDecl",
+            vec![
+                ("name", json_string(name)),
+                (
+                    "fields",
+                    typed_names(fields.iter().map(|(n, t)| (n, Some(t)))),
+                ),
+                (
+                    "weak_fields",
+                    list(weak_fields.iter().map(|n| json_string(n))),
+                ),
+            ],
+        ),
+        AstNode::ImplBlock {
+            struct_name,
+            trait_name,
+            methods,
+        } => object(
+            "ImplBlock",
+            vec![
+                ("struct_name", json_string(struct_name)),
+                ("trait_name", optional_string(trait_name)),
+                ("methods", nodes(methods)),
+            ],
+        ),
+        AstNode::ExternFunctionDecl {
+            name,
+            params: function_params,
+            return_type,
+            intrinsic,
+        } => object(
+            "ExternFunctionDecl",
+            vec![
+                ("name", json_string(name)),
+                ("params", params(function_params)),
+                ("return_type", optional_type(return_type)),
+                ("intrinsic", optional_string(intrinsic)),
+            ],
+        ),
+        AstNode::TraitDecl { name, methods } => object(
+            "TraitDecl",
+            vec![
+                ("name", json_string(name)),
+                (
+                    "methods",
+                    list(methods.iter().map(|method| {
+                        object(
+                            "TraitMethod",
+                            vec![
+                                ("name", json_string(&method.name)),
+                                ("params", params(&method.params)),
+                                ("return_type", optional_type(&method.return_type)),
+                            ],
+                        )
+                    })),
+                ),
+            ],
+        ),
+        AstNode::EnumDecl { name, variants } => object(
+            "EnumDecl",
+            vec![
+                ("name", json_string(name)),
+                (
+                    "variants",
+                    typed_names(variants.iter().map(|(n, t)| (n, t.as_ref()))),
+                ),
+            ],
+        ),
+        AstNode::ConditionalStmt {
+            condition,
+            then_block,
+            else_branch,
+        } => object(
+            "ConditionalStmt",
+            vec![
+                ("condition", node_to_json(condition)),
+                ("then_block", nodes(then_block)),
+                ("else_branch", optional_node(else_branch)),
+            ],
+        ),
+        AstNode::IfExpr {
+            condition,
+            then_branch,
+            else_branch,
+        } => object(
+            "IfExpr",
+            vec![
+                ("condition", node_to_json(condition)),
+                ("then_branch", node_to_json(then_branch)),
+                ("else_branch", node_to_json(else_branch)),
+            ],
+        ),
+        AstNode::IfLet {
+            name,
+            value,
+            then_block,
+            else_branch,
+        } => object(
+            "IfLet",
+            vec![
+                ("name", json_string(name)),
+                ("value", node_to_json(value)),
+                ("then_block", nodes(then_block)),
+                ("else_branch", optional_node(else_branch)),
+            ],
+        ),
+        AstNode::Match { value, arms } => object(
+            "Match",
+            vec![
+                ("value", node_to_json(value)),
+                (
+                    "arms",
+                    list(arms.iter().map(|arm| {
+                        object(
+                            "MatchArm",
+                            vec![
+                                (
+                                    "patterns",
+                                    list(arm.patterns.iter().map(match_pattern_json)),
+                                ),
+                                ("body", node_to_json(&arm.body)),
+                            ],
+                        )
+                    })),
+                ),
+            ],
+        ),
+        AstNode::OptionalWrap { value, ty } => object(
+            "OptionalWrap",
+            vec![("value", optional_node(value)), ("ty", type_json(ty))],
+        ),
+        AstNode::VariadicArgs { values, elem_type } => object(
+            "VariadicArgs",
+            vec![
+                ("values", nodes(values)),
+                ("elem_type", type_json(elem_type)),
+            ],
+        ),
+        AstNode::Block(statements) => object("Block", vec![("statements", nodes(statements))]),
+        AstNode::Return { values } => object("Return", vec![("values", nodes(values))]),
+        AstNode::Print { exprs } => object("Print", vec![("exprs", nodes(exprs))]),
+        AstNode::Break { value } => object("Break", vec![("value", optional_node(value))]),
+        AstNode::Continue => object("Continue", vec![]),
+        AstNode::BlockExpr {
+            body,
+            value,
+            value_type,
+        } => object(
+            "BlockExpr",
+            vec![
+                ("body", nodes(body)),
+                ("value", node_to_json(value)),
+                ("value_type", optional_type(value_type)),
+            ],
+        ),
+        AstNode::LoopExpr { body, value_type } => object(
+            "LoopExpr",
+            vec![
+                ("body", nodes(body)),
+                ("value_type", optional_type(value_type)),
+            ],
+        ),
+        AstNode::Closure {
+            params: closure_params,
+            body,
+            value_type,
+            captures,
+        } => object(
+            "Closure",
+            vec![
+                ("params", params(closure_params)),
+                ("body", node_to_json(body)),
+                ("value_type", optional_type(value_type)),
+                (
+                    "captures",
+                    typed_names(captures.iter().map(|(name, ty)| (name, Some(ty)))),
+                ),
+            ],
+        ),
+        AstNode::Assignment { pattern, value } => object(
+            "Assignment",
+            vec![
+                ("pattern", pattern_json(pattern)),
+                ("value", node_to_json(value)),
+            ],
+        ),
+        AstNode::CompoundAssignment { pattern, op, value } => object(
+            "CompoundAssignment",
+            vec![
+                ("pattern", pattern_json(pattern)),
+                ("op", json_string(&format!("{:?}", op))),
+                ("value", node_to_json(value)),
+            ],
+        ),
+        AstNode::FunctionDecl {
+            name,
+            visibility,
+            params: function_params,
+            return_type,
+            body,
+            body_spans,
+            type_params,
+            allow,
+            arena,
+            export,
+            test,
+        } => object(
+            "FunctionDecl",
+            vec![
+                ("name", json_string(name)),
+                ("visibility", json_string(visibility)),
+                ("params", params(function_params)),
+                ("return_type", optional_type(return_type)),
+                ("body", nodes(body)),
+                (
+                    "body_spans",
+                    list(body_spans.iter().map(|span| span_json(*span))),
+                ),
+                (
+                    "type_params",
+                    list(type_params.iter().map(|(param, bound)| {
+                        format!("[{},{}]", json_string(param), optional_string(bound))
+                    })),
+                ),
+                ("allow", list(allow.iter().map(|lint| json_string(lint)))),
+                ("arena", arena.to_string()),
+                ("export", export.to_string()),
+                ("test", test.to_string()),
+            ],
+        ),
+        AstNode::FunctionCall { func, args } => object(
+            "FunctionCall",
+            vec![("func", node_to_json(func)), ("args", nodes(args))],
+        ),
+        AstNode::ForLoopStmt {
+            pattern,
+            iterable,
+            step,
+            body,
+        } => object(
+            "ForLoopStmt",
+            vec![
+                ("pattern", pattern_json(pattern)),
+                ("iterable", optional_node(iterable)),
+                ("step", optional_node(step)),
+                ("body", nodes(body)),
+            ],
+        ),
+        AstNode::TupleLiteral(elements) => {
+            object("TupleLiteral", vec![("elements", nodes(elements))])
+        }
+        AstNode::Range {
+            start,
+            end,
+            inclusive,
+        } => object(
+            "Range",
+            vec![
+                ("start", node_to_json(start)),
+                ("end", node_to_json(end)),
+                ("inclusive", inclusive.to_string()),
+            ],
+        ),
+        AstNode::ElementAccess { array, index, span } => object(
+            "ElementAccess",
+            vec![
+                ("array", node_to_json(array)),
+                ("index", node_to_json(index)),
+                ("span", span_json(*span)),
+            ],
+        ),
+        AstNode::StructLiteral { name, fields } => object(
+            "StructLiteral",
+            vec![
+                ("name", json_string(name)),
+                (
+                    "fields",
+                    list(fields.iter().map(|(field, value)| {
+                        format!("[{},{}]", json_string(field), node_to_json(value))
+                    })),
+                ),
+            ],
+        ),
+        AstNode::FieldAccess {
+            object: target,
+            field,
+            span,
+        } => object(
+            "FieldAccess",
+            vec![
+                ("object", node_to_json(target)),
+                ("field", json_string(field)),
+                ("span", span_json(*span)),
+            ],
+        ),
+        AstNode::FieldAssignment {
+            object: target,
+            field,
+            value,
+        } => object(
+            "FieldAssignment",
+            vec![
+                ("object", node_to_json(target)),
+                ("field", json_string(field)),
+                ("value", node_to_json(value)),
+            ],
+        ),
+        AstNode::EnumValue {
+            enum_name,
+            variant,
+            payload,
+        } => object(
+            "EnumValue",
+            vec![
+                ("enum_name", json_string(enum_name)),
+                ("variant", json_string(variant)),
+                ("payload", optional_node(payload)),
+            ],
+        ),
+        AstNode::Try {
+            expr,
+            expr_type,
+            return_type,
+        } => object(
+            "Try",
+            vec![
+                ("expr", node_to_json(expr)),
+                ("expr_type", optional_type(expr_type)),
+                ("return_type", optional_type(return_type)),
+            ],
+        ),
+        AstNode::MethodCall {
+            object: target,
+            method,
+            args,
+            span,
+        } => object(
+            "MethodCall",
+            vec![
+                ("object", node_to_json(target)),
+                ("method", json_string(method)),
+                ("args", nodes(args)),
+                ("span", span_json(*span)),
+            ],
+        ),
+        AstNode::Import { path, symbol } => object(
+            "Import",
+            vec![
+                ("path", list(path.iter().map(|part| json_string(part)))),
+                ("symbol", optional_string(symbol)),
+            ],
+        ),
+    }
+}
+
+/// `{"kind":kind,...fields}`; each field value is JSON already.
+fn object(kind: &str, fields: Vec<(&str, String)>) -> String {
+    let mut out = format!("{{\"kind\":{}", json_string(kind));
+    for (name, value) in fields {
+        out.push_str(&format!(",{}:{}", json_string(name), value));
+    }
+    out.push('}');
+    out
+}
+
+fn list(items: impl Iterator<Item = String>) -> String {
+    format!("[{}]", items.collect::<Vec<_>>().join(","))
+}
+
+fn nodes(nodes: &[AstNode]) -> String {
+    list(nodes.iter().map(node_to_json))
+}
+
+fn optional_node(node: &Option<Box<AstNode>>) -> String {
+    node.as_ref()
+        .map(|node| node_to_json(node))
+        .unwrap_or_else(|| "null".to_string())
+}
+
+fn optional_string(s: &Option<String>) -> String {
+    s.as_ref()
+        .map(|s| json_string(s))
+        .unwrap_or_else(|| "null".to_string())
+}
+
+fn type_json(ty: &TypeNode) -> String {
+    json_string(&ty.to_string())
+}
+
+fn optional_type(ty: &Option<TypeNode>) -> String {
+    ty.as_ref()
+        .map(type_json)
+        .unwrap_or_else(|| "null".to_string())
+}
+
+/// `[[name, type or null], ...]`
+fn typed_names<'n>(names: impl Iterator<Item = (&'n String, Option<&'n TypeNode>)>) -> String {
+    list(names.map(|(name, ty)| {
+        let ty = ty.map(type_json).unwrap_or_else(|| "null".to_string());
+        format!("[{},{}]", json_string(name), ty)
+    }))
+}
+
+fn params(params: &[(String, Option<TypeNode>)]) -> String {
+    typed_names(params.iter().map(|(name, ty)| (name, ty.as_ref())))
+}
+
+/// JSON has no infinity or NaN; a float literal too large to be finite is null.
+fn float(n: f64) -> String {
+    if n.is_finite() {
+        format!("{:?}", n)
+    } else {
+        "null".to_string()
+    }
+}
+
+fn span_json(span: Span) -> String {
+    format!(
+        "{{\"start\":{},\"end\":{},\"line\":{},\"col\":{}}}",
+        span.start, span.end, span.line, span.col
+    )
+}
+
+fn pattern_json(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Identifier(name) => object("Identifier", vec![("name", json_string(name))]),
+        Pattern::Tuple(patterns) => object(
+            "Tuple",
+            vec![("patterns", list(patterns.iter().map(pattern_json)))],
+        ),
+        Pattern::Wildcard => object("Wildcard", vec![]),
+    }
+}
+
+fn match_pattern_json(pattern: &MatchPattern) -> String {
+    match pattern {
+        MatchPattern::Literal(value) => object("Literal", vec![("value", node_to_json(value))]),
+        MatchPattern::Wildcard => object("Wildcard", vec![]),
+        MatchPattern::Variant {
+            enum_name,
+            variant,
+            binding,
+        } => object(
+            "Variant",
+            vec![
+                ("enum_name", json_string(enum_name)),
+                ("variant", json_string(variant)),
+                ("binding", optional_string(binding)),
+            ],
+        ),
+    }
+}
@@ -1,6 +1,7 @@
 pub mod ast;
 pub mod declarations;
 pub mod expressions;
+pub mod json;
 pub mod parser;
 pub mod statements;
 
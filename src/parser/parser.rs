@@ -1,5 +1,5 @@
 use crate::lexar::token::{Token, TokenType};
-use crate::parser::ast::AstNode;
+use crate::parser::ast::{AstNode, Span};
 use std::fmt;
 
 /// Maximum recursion depth for parsing to prevent stack overflow
@@ -8,7 +8,7 @@ pub const MAX_DEPTH: usize = 64;
 /// Error type for parser.
 /// Used to signal parsing failures, such as unexpected tokens or premature end of input.
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ParseError {
     UnexpectedToken(String), // Legacy: without position
     UnexpectedTokenAt {
@@ -23,6 +23,16 @@ pub enum ParseError {
 /// Wraps either a successful parse result or a ParseError.
 pub type ParseResult<T> = Result<T, ParseError>;
 
+impl ParseError {
+    /// Stable code of the error, explained by `doo explain`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::UnexpectedToken(_) | ParseError::UnexpectedTokenAt { .. } => "E2001",
+            ParseError::EndOfInput => "E2002",
+        }
+    }
+}
+
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -42,6 +52,10 @@ pub struct Parser<'a> {
     pub tokens: &'a [Token<'a>], // Reference to a slice of tokens from lexar.
     pub current: usize,          // Current index; tracks progress through tokens.
     pub depth: usize,            // Current recursion depth to prevent stack overflow.
+    pub result_decls: Vec<AstNode>, // Enums behind the `Result<T, E>` types named so far.
+    pub statement_spans: Vec<Span>, // Where each statement parse_program returns is.
+    pub errors: Vec<ParseError>, // Syntax errors recovered from so far, in order.
+    pub(crate) speculative: usize, // Nesting of trial parses, which must fail rather than recover.
 }
 
 impl<'a> Parser<'a> {
@@ -51,6 +65,10 @@ impl<'a> Parser<'a> {
             tokens,
             current: 0,
             depth: 0,
+            result_decls: Vec::new(),
+            statement_spans: Vec::new(),
+            errors: Vec::new(),
+            speculative: 0,
         }
     }
 
@@ -60,11 +78,31 @@ impl<'a> Parser<'a> {
         self.tokens.get(self.current)
     }
 
+    /// The current token's span, the default span at the end of input.
+    pub fn current_span(&self) -> Span {
+        self.peek().map(|tok| tok.span).unwrap_or_default()
+    }
+
+    /// The span of the last token consumed, the default span before any.
+    pub fn previous_span(&self) -> Span {
+        self.current
+            .checked_sub(1)
+            .and_then(|index| self.tokens.get(index))
+            .map(|tok| tok.span)
+            .unwrap_or_default()
+    }
+
     /// Checks if the current token matches a given kind.
     pub(crate) fn peek_is(&self, kind: TokenType) -> bool {
         self.peek().map(|tok| tok.kind == kind).unwrap_or(false)
     }
 
+    /// Checks if the next two tokens are `::`, which lexes as two colons.
+    pub(crate) fn peek_is_path_sep(&self) -> bool {
+        self.peek_is(TokenType::Colon)
+            && self.tokens.get(self.current + 1).map(|t| t.kind) == Some(TokenType::Colon)
+    }
+
     /// Advance to the next token and return the previous one.
     pub fn advance(&mut self) -> Option<&Token<'a>> {
         let tok = self.tokens.get(self.current);
@@ -91,6 +129,9 @@ impl<'a> Parser<'a> {
     pub(crate) fn expect(&mut self, kind: TokenType) -> ParseResult<&Token<'a>> {
         match self.advance() {
             Some(tok) if tok.kind == kind => Ok(tok),
+            Some(tok) if tok.kind == TokenType::Unknown && tok.value == "/*" => {
+                Err(super::expressions::unterminated_comment_error(tok))
+            }
             Some(tok) => Err(ParseError::UnexpectedTokenAt {
                 msg: format!("Expected {:?}, got {:?} ({:?})", kind, tok.kind, tok.value),
                 line: tok.line,
@@ -109,8 +150,13 @@ impl<'a> Parser<'a> {
                 // Declarations
                 TokenType::Let => self.parse_let_decl(),
                 TokenType::Function => self.parse_functional_decl(),
+                TokenType::Pound => self.parse_attributed_decl(),
                 TokenType::Struct => self.parse_struct_decl(),
                 TokenType::Enum => self.parse_enum_decl(),
+                TokenType::Impl => self.parse_impl_block(),
+                TokenType::Trait => self.parse_trait_decl(),
+                TokenType::Extern => self.parse_extern_decl(),
+                TokenType::Export => self.parse_export_decl(),
 
                 // Import statement
                 TokenType::Import => self.parse_import(),
@@ -118,6 +164,11 @@ impl<'a> Parser<'a> {
                 // Statements
                 TokenType::If => self.parse_conditional_stmt(),
                 TokenType::For => self.parse_for_stmt(),
+                TokenType::Match => {
+                    let node = self.parse_match()?;
+                    self.consume_if(TokenType::Semi);
+                    Ok(node)
+                }
                 TokenType::Return => self.parse_return(),
                 TokenType::Break => self.parse_break(),
                 TokenType::Continue => self.parse_continue(),
@@ -138,11 +189,17 @@ impl<'a> Parser<'a> {
                                 self.expect(TokenType::Semi)?;
 
                                 // Extract identifier from expr for assignment
-                                if let AstNode::Identifier(name) = expr {
+                                if let AstNode::Identifier(name, _) = expr {
                                     return Ok(AstNode::Assignment {
                                         pattern: crate::parser::ast::Pattern::Identifier(name),
                                         value: Box::new(value),
                                     });
+                                } else if let AstNode::FieldAccess { object, field, .. } = expr {
+                                    return Ok(AstNode::FieldAssignment {
+                                        object,
+                                        field,
+                                        value: Box::new(value),
+                                    });
                                 } else {
                                     return Err(ParseError::UnexpectedToken(
                                         "Only single-variable assignment is allowed without 'let'"
@@ -161,7 +218,7 @@ impl<'a> Parser<'a> {
                                 self.expect(TokenType::Semi)?;
 
                                 // Extract identifier from expr for compound assignment
-                                if let AstNode::Identifier(name) = expr {
+                                if let AstNode::Identifier(name, _) = expr {
                                     return Ok(AstNode::CompoundAssignment {
                                         pattern: crate::parser::ast::Pattern::Identifier(name),
                                         op,
@@ -197,6 +254,10 @@ impl<'a> Parser<'a> {
                     });
                 }
 
+                TokenType::Unknown if tok.value == "/*" => {
+                    Err(super::expressions::unterminated_comment_error(tok))
+                }
+
                 // If the token doesn't match any known statement start, check for Unknown token and handle error.
                 _ => Err(ParseError::UnexpectedTokenAt {
                     msg: format!("Unexpected token: {:?}", tok.kind),
@@ -210,13 +271,90 @@ impl<'a> Parser<'a> {
 
     /// Parses an entire program (sequence of statements).
     /// Keeps parsing statements until all tokens are consumed.
+    /// The enums behind the `Result<T, E>` types used in the program are
+    /// declared ahead of its statements.
+    /// Fails with the first syntax error; `errors` lists all of them.
     pub fn parse_program(&mut self) -> ParseResult<AstNode> {
+        let program = self.parse_program_with_recovery();
+        match self.errors.first() {
+            Some(error) => Err(error.clone()),
+            None => Ok(program),
+        }
+    }
+
+    /// Parses the whole token stream like `parse_program`, recording each
+    /// syntax error in `errors` and skipping to the next statement instead
+    /// of stopping. The program holds the statements that parsed.
+    pub fn parse_program_with_recovery(&mut self) -> AstNode {
         let mut statements = Vec::new();
+        let mut spans = Vec::new();
         while self.current < self.tokens.len() {
-            let stmt = self.parse_statement()?;
-            statements.push(stmt);
+            let start = self.current;
+            let span = self.current_span();
+            match self.parse_statement() {
+                Ok(stmt) => {
+                    statements.push(stmt);
+                    spans.push(span.to(self.previous_span()));
+                }
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize(start);
+                }
+            }
+        }
+        let result_decls = std::mem::take(&mut self.result_decls);
+        spans.splice(0..0, vec![Span::default(); result_decls.len()]);
+        statements.splice(0..0, result_decls);
+        self.statement_spans = spans;
+        AstNode::Program(statements)
+    }
+
+    /// Skips the statement that failed to parse from token `start`, stopping
+    /// after its `;` or its closing `}` (unless an `else` follows), or before a
+    /// declaration keyword or the `}` of the enclosing block. Brackets the
+    /// statement opens are skipped whole. Always moves past at least one token.
+    pub(crate) fn synchronize(&mut self, start: usize) {
+        self.current = start;
+        let mut depth = 0usize;
+        while let Some(tok) = self.peek() {
+            match tok.kind {
+                TokenType::OpenBrace | TokenType::OpenParen | TokenType::OpenBracket => depth += 1,
+                TokenType::CloseBrace if depth == 0 => break,
+                TokenType::CloseBrace | TokenType::CloseParen | TokenType::CloseBracket
+                    if depth > 0 =>
+                {
+                    depth -= 1;
+                    if depth == 0
+                        && tok.kind == TokenType::CloseBrace
+                        && !self.tokens.get(self.current + 1).is_some_and(|next| {
+                            matches!(next.kind, TokenType::Else | TokenType::Semi)
+                        })
+                    {
+                        self.advance();
+                        break;
+                    }
+                }
+                TokenType::Semi if depth == 0 => {
+                    self.advance();
+                    break;
+                }
+                TokenType::Function
+                | TokenType::Struct
+                | TokenType::Enum
+                | TokenType::Impl
+                | TokenType::Trait
+                | TokenType::Import
+                    if depth == 0 && self.current > start =>
+                {
+                    break
+                }
+                _ => {}
+            }
+            self.advance();
+        }
+        if self.current == start {
+            self.advance();
         }
-        Ok(AstNode::Program(statements))
     }
 
     /// Parses an import statement.
@@ -292,9 +430,7 @@ impl<'a> Parser<'a> {
                 match tok.kind {
                     TokenType::Number => {
                         let tok = self.advance().unwrap();
-                        let value_str = tok.value;
-                        let value_line = tok.line;
-                        let value_col = tok.col;
+                        let literal = super::expressions::int_literal_from_token(tok, false);
                         // Mutable borrow ends here, now peek is allowed
                         if let Some(next) = self.peek() {
                             if next.kind == TokenType::Dot
@@ -311,14 +447,7 @@ impl<'a> Parser<'a> {
                                 });
                             }
                         }
-                        let value = value_str.parse::<i32>().map_err(|_| {
-                            ParseError::UnexpectedTokenAt {
-                                msg: format!("Invalid integer literal: {}", value_str),
-                                line: value_line,
-                                col: value_col,
-                            }
-                        })?;
-                        Ok(AstNode::NumberLiteral(value))
+                        literal
                     }
                     TokenType::Float => {
                         let tok = self.advance().unwrap();
@@ -341,7 +470,7 @@ impl<'a> Parser<'a> {
                                 });
                             }
                         }
-                        let value = value_str.parse::<f64>().map_err(|_| {
+                        let value = value_str.replace('_', "").parse::<f64>().map_err(|_| {
                             ParseError::UnexpectedTokenAt {
                                 msg: format!("Invalid float literal: {}", value_str),
                                 line: value_line,
@@ -354,6 +483,12 @@ impl<'a> Parser<'a> {
                         let tok = self.advance().unwrap();
                         Ok(AstNode::StringLiteral(tok.value.to_string()))
                     }
+                    TokenType::Unknown if tok.value.starts_with('\\') => {
+                        Err(super::expressions::invalid_escape_error(tok))
+                    }
+                    TokenType::Unknown if tok.value == "/*" => {
+                        Err(super::expressions::unterminated_comment_error(tok))
+                    }
                     // ... handle other expression types as before ...
                     _ => {
                         // Fallback to existing logic or error
@@ -1,5 +1,6 @@
 use crate::lexar::token::TokenType;
-use crate::parser::ast::{AstNode, Pattern};
+use crate::parser::ast::{AstNode, MatchArm, MatchPattern, Pattern, Span};
+use crate::parser::expressions::int_literal_from_token;
 use crate::parser::{ParseError, ParseResult, Parser};
 
 impl<'a> Parser<'a> {
@@ -7,10 +8,21 @@ impl<'a> Parser<'a> {
     ///   - `if condition { ... }`
     ///   - `if condition { ... } else { ... }`
     ///   - `if condition { ... } else if ...`
+    ///   - `if let name = optional { ... } else { ... }`
     /// Supports nested else-if branches recursively.
     pub fn parse_conditional_stmt(&mut self) -> ParseResult<AstNode> {
         self.expect(TokenType::If)?;
 
+        // if let: bind the payload of an optional when it is present
+        let binding = if self.consume_if(TokenType::Let) {
+            let tok = self.expect(TokenType::Identifier)?;
+            let name = tok.value.to_string();
+            self.expect(TokenType::Eq)?;
+            Some(name)
+        } else {
+            None
+        };
+
         // Parse condition expression
         let condition = self.parse_expression()?;
 
@@ -37,6 +49,15 @@ impl<'a> Parser<'a> {
             }
         }
 
+        if let Some(name) = binding {
+            return Ok(AstNode::IfLet {
+                name,
+                value: Box::new(condition),
+                then_block,
+                else_branch,
+            });
+        }
+
         Ok(AstNode::ConditionalStmt {
             condition: Box::new(condition),
             then_block,
@@ -44,9 +65,146 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Parses `if` in expression position.
+    /// Syntax: `if condition { expr } else { expr }`, with `else if` chains.
+    /// Each branch holds a single expression and the else branch is required.
+    pub fn parse_if_expr(&mut self) -> ParseResult<AstNode> {
+        self.expect(TokenType::If)?;
+        if let Some(tok) = self.peek().filter(|tok| tok.kind == TokenType::Let) {
+            return Err(ParseError::UnexpectedTokenAt {
+                msg: "'if let' cannot be used as a value".to_string(),
+                line: tok.line,
+                col: tok.col,
+            });
+        }
+        let condition = self.parse_expression()?;
+        let then_branch = self.parse_braced_value()?;
+
+        self.expect(TokenType::Else)?;
+        let else_branch = if self.peek_is(TokenType::If) {
+            self.parse_if_expr()?
+        } else {
+            self.parse_braced_value()?
+        };
+
+        Ok(AstNode::IfExpr {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch: Box::new(else_branch),
+        })
+    }
+
+    /// Parses `{ expr }`, the value of one branch of an if expression.
+    fn parse_braced_value(&mut self) -> ParseResult<AstNode> {
+        self.expect(TokenType::OpenBrace)?;
+        let value = self.parse_expression()?;
+        self.expect(TokenType::CloseBrace)?;
+        Ok(value)
+    }
+
+    /// Syntax: `match value { 1 | 2 => expr, "a" => { ... }, _ => expr }`
+    /// Arms are separated by commas; the comma after a block arm is optional.
+    /// Used both as a statement and as an expression (`let x = match ...;`).
+    pub fn parse_match(&mut self) -> ParseResult<AstNode> {
+        self.expect(TokenType::Match)?;
+        let value = self.parse_expression()?;
+        self.expect(TokenType::OpenBrace)?;
+
+        let mut arms = Vec::new();
+        while !self.peek_is(TokenType::CloseBrace) {
+            let mut patterns = vec![self.parse_match_pattern()?];
+            while self.consume_if(TokenType::Or) {
+                patterns.push(self.parse_match_pattern()?);
+            }
+            self.expect(TokenType::FatArrow)?;
+
+            let is_block = self.peek_is(TokenType::OpenBrace);
+            let body = if is_block {
+                AstNode::Block(self.parse_braced_block()?)
+            } else {
+                self.parse_expression()?
+            };
+            arms.push(MatchArm {
+                patterns,
+                body: Box::new(body),
+            });
+
+            if !self.consume_if(TokenType::Comma) && !is_block {
+                // Without a comma this must be the last arm
+                if !self.peek_is(TokenType::CloseBrace) {
+                    return match self.peek() {
+                        Some(tok) => Err(ParseError::UnexpectedTokenAt {
+                            msg: format!("Expected ',' between match arms, got {:?}", tok.kind),
+                            line: tok.line,
+                            col: tok.col,
+                        }),
+                        None => Err(ParseError::EndOfInput),
+                    };
+                }
+            }
+        }
+        self.expect(TokenType::CloseBrace)?;
+
+        Ok(AstNode::Match {
+            value: Box::new(value),
+            arms,
+        })
+    }
+
+    /// Parses a single match pattern: `_`, an int, string or bool literal, or an
+    /// enum variant with an optional payload binding (`Shape::Circle(r)`).
+    /// `Ok(v)` and `Err(e)` are the variants of a `Result`; the analyzer names
+    /// the Result enum from the matched value.
+    /// Integer patterns may be negative: `-1 => ...`
+    fn parse_match_pattern(&mut self) -> ParseResult<MatchPattern> {
+        let negative = self.consume_if(TokenType::Minus);
+        if !negative && self.peek_is(TokenType::Identifier) {
+            let name = self.expect_ident()?;
+            let (enum_name, variant) =
+                if matches!(name.as_str(), "Ok" | "Err") && !self.peek_is_path_sep() {
+                    ("Result".to_string(), name)
+                } else {
+                    self.expect(TokenType::Colon)?; // `::` lexes as two colons
+                    self.expect(TokenType::Colon)?;
+                    (name, self.expect_ident()?)
+                };
+            let mut binding = None;
+            if self.consume_if(TokenType::OpenParen) {
+                if !self.consume_if(TokenType::Underscore) {
+                    binding = Some(self.expect_ident()?);
+                }
+                self.expect(TokenType::CloseParen)?;
+            }
+            return Ok(MatchPattern::Variant {
+                enum_name,
+                variant,
+                binding,
+            });
+        }
+        let tok = match self.advance() {
+            Some(tok) => tok,
+            None => return Err(ParseError::EndOfInput),
+        };
+        let literal = match tok.kind {
+            TokenType::Underscore if !negative => return Ok(MatchPattern::Wildcard),
+            TokenType::Number => int_literal_from_token(tok, negative)?,
+            TokenType::String if !negative => AstNode::StringLiteral(tok.value.to_string()),
+            TokenType::Boolean if !negative => AstNode::BoolLiteral(tok.value == "true"),
+            _ => {
+                return Err(ParseError::UnexpectedTokenAt {
+                    msg: format!("Expected match pattern, got {:?}", tok.kind),
+                    line: tok.line,
+                    col: tok.col,
+                })
+            }
+        };
+        Ok(MatchPattern::Literal(literal))
+    }
+
     /// Supports tuple patterns and optional iterable expressions.
     /// Syntax:
     ///   - `for a, b or (a, b) in iterable { ... }`
+    ///   - `for i in 10..0 step -2 { ... }` (`step` is only a keyword here)
     ///   - `for { ... }` (infinite loop)
     /// Returns a ForLoopStmt AST node.
     pub fn parse_for_stmt(&mut self) -> ParseResult<AstNode> {
@@ -79,12 +237,26 @@ impl<'a> Parser<'a> {
             None
         };
 
+        // Parse optional `step expr` after the iterable
+        let step = match self.peek() {
+            Some(tok)
+                if iterable.is_some()
+                    && tok.kind == TokenType::Identifier
+                    && tok.value == "step" =>
+            {
+                self.advance(); // consume 'step'
+                Some(Box::new(self.parse_expression()?))
+            }
+            _ => None,
+        };
+
         // Parse loop body block
         let body = self.parse_braced_block()?;
 
         Ok(AstNode::ForLoopStmt {
             pattern,
             iterable,
+            step,
             body,
         })
     }
@@ -113,12 +285,71 @@ impl<'a> Parser<'a> {
         Ok(AstNode::Return { values })
     }
 
-    /// Syntax: `break;`
+    /// Syntax: `break;` or `break value;`
     /// Returns a Break AST node.
     pub fn parse_break(&mut self) -> ParseResult<AstNode> {
         self.expect(TokenType::Break)?;
+        let value = if self.peek_is(TokenType::Semi) {
+            None
+        } else {
+            Some(Box::new(self.parse_expression()?))
+        };
         self.expect(TokenType::Semi)?;
-        Ok(AstNode::Break)
+        Ok(AstNode::Break { value })
+    }
+
+    /// Syntax: `{ stmt; ... value }` in expression position. The trailing
+    /// expression without a `;` is the value of the block.
+    pub fn parse_block_expr(&mut self) -> ParseResult<AstNode> {
+        self.expect(TokenType::OpenBrace)?;
+        let mut body = Vec::new();
+        loop {
+            if let Some(tok) = self.peek().filter(|t| t.kind == TokenType::CloseBrace) {
+                return Err(ParseError::UnexpectedTokenAt {
+                    msg: "Block expression must end with a value".to_string(),
+                    line: tok.line,
+                    col: tok.col,
+                });
+            }
+
+            // Try the rest as the final value; otherwise it is another statement
+            let start = self.current;
+            self.speculative += 1;
+            let value = self.parse_expression();
+            self.speculative -= 1;
+            if let Ok(value) = value {
+                if self.consume_if(TokenType::CloseBrace) {
+                    return Ok(AstNode::BlockExpr {
+                        body,
+                        value: Box::new(value),
+                        value_type: None,
+                    });
+                }
+            }
+            self.current = start;
+            body.push(self.parse_statement()?);
+        }
+    }
+
+    /// Parses `for { ... }` in expression position, a loop that yields the
+    /// value of the `break` that ends it. Loops over an iterable can't be values.
+    pub fn parse_loop_expr(&mut self) -> ParseResult<AstNode> {
+        let (line, col) = self.peek().map(|t| (t.line, t.col)).unwrap_or((0, 0));
+        match self.parse_for_stmt()? {
+            AstNode::ForLoopStmt {
+                iterable: None,
+                body,
+                ..
+            } => Ok(AstNode::LoopExpr {
+                body,
+                value_type: None,
+            }),
+            _ => Err(ParseError::UnexpectedTokenAt {
+                msg: "Only an infinite 'for { ... }' loop can be used as a value".to_string(),
+                line,
+                col,
+            }),
+        }
     }
 
     /// Syntax: `continue;`
@@ -241,15 +472,35 @@ impl<'a> Parser<'a> {
     /// Parses a block of statements enclosed in braces `{ ... }`.
     /// Returns a vector of AST nodes for each statement in the block.
     fn parse_block(&mut self) -> ParseResult<Vec<AstNode>> {
+        self.parse_block_with_spans().map(|(stmts, _)| stmts)
+    }
+
+    /// Like `parse_block`, also returning where each statement is.
+    fn parse_block_with_spans(&mut self) -> ParseResult<(Vec<AstNode>, Vec<Span>)> {
         let mut stmts = Vec::new();
+        let mut spans = Vec::new();
         while let Some(tok) = self.peek() {
             if tok.kind == TokenType::CloseBrace {
                 break;
             }
-            stmts.push(self.parse_statement()?);
+            let start = self.current;
+            let span = self.current_span();
+            match self.parse_statement() {
+                Ok(stmt) => {
+                    stmts.push(stmt);
+                    spans.push(span.to(self.previous_span()));
+                }
+                // Trial parses fail as a whole; otherwise the error is
+                // recorded and the rest of the block still parsed
+                Err(error) if self.speculative > 0 => return Err(error),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize(start);
+                }
+            }
         }
         self.expect(TokenType::CloseBrace)?; // consume '}'
-        Ok(stmts)
+        Ok((stmts, spans))
     }
 
     /// Parses a block of statements, expecting an opening brace first.
@@ -259,4 +510,11 @@ impl<'a> Parser<'a> {
         self.expect(TokenType::OpenBrace)?;
         self.parse_block()
     }
+
+    /// Parses a braced block along with where each of its statements starts.
+    /// Used for function bodies.
+    pub fn parse_braced_block_with_spans(&mut self) -> ParseResult<(Vec<AstNode>, Vec<Span>)> {
+        self.expect(TokenType::OpenBrace)?;
+        self.parse_block_with_spans()
+    }
 }
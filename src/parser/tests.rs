@@ -1,8 +1,9 @@
 #[cfg(test)]
 mod parser_tests {
     use crate::lexar::lexer::lex;
-    use crate::parser::ast::AstNode;
-    use crate::parser::Parser;
+    use crate::parser::ast::{AstNode, MatchPattern, Pattern, Span, TypeNode};
+    use crate::parser::json::{node_to_json, program_to_json};
+    use crate::parser::{ParseError, Parser};
 
     // =====================
     // Declarations
@@ -34,6 +35,333 @@ mod parser_tests {
         }
     }
 
+    #[test]
+    fn test_int64_literal_and_annotation() {
+        let input = "let big: Int64 = 5000000000L;";
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement().unwrap() {
+            AstNode::LetDecl {
+                type_annotation,
+                value,
+                ..
+            } => {
+                assert_eq!(type_annotation, Some(crate::parser::ast::TypeNode::Int64));
+                assert!(matches!(*value, AstNode::Int64Literal(5000000000)));
+            }
+            _ => panic!("Expected LetDecl"),
+        }
+    }
+
+    #[test]
+    fn test_radix_integer_literals() {
+        let cases = [
+            ("0xFF", 255),
+            ("0b1010", 10),
+            ("0o777", 511),
+            ("1_000_000", 1_000_000),
+        ];
+        for (text, expected) in cases {
+            let input = format!("let x = {};", text);
+            let tokens = lex(&input);
+            let mut parser = Parser::new(&tokens);
+            match parser.parse_statement().unwrap() {
+                AstNode::LetDecl { value, .. } => match *value {
+                    AstNode::NumberLiteral(n) => assert_eq!(n, expected, "{}", text),
+                    other => panic!("Expected NumberLiteral for {}, got {:?}", text, other),
+                },
+                _ => panic!("Expected LetDecl"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_integer_literal_out_of_range() {
+        for input in ["let x = 0xFFFFFFFF;", "let x = 3000000000;"] {
+            let tokens = lex(input);
+            let mut parser = Parser::new(&tokens);
+            assert!(parser.parse_statement().is_err(), "{}", input);
+        }
+        // The same value fits once it's marked as Int64
+        let tokens = lex("let x = 0xFFFFFFFFL;");
+        let mut parser = Parser::new(&tokens);
+        assert!(parser.parse_statement().is_ok());
+    }
+
+    #[test]
+    fn test_minimum_integer_literals() {
+        // The minus is part of the literal, so the minimums can be written
+        let cases = [
+            ("-2147483648", AstNode::NumberLiteral(i32::MIN)),
+            ("-9223372036854775808L", AstNode::Int64Literal(i64::MIN)),
+            ("-0x80000000", AstNode::NumberLiteral(i32::MIN)),
+            ("- 5", AstNode::NumberLiteral(-5)),
+        ];
+        for (text, expected) in cases {
+            let input = format!("let x = {};", text);
+            let tokens = lex(&input);
+            let mut parser = Parser::new(&tokens);
+            match parser.parse_statement().unwrap() {
+                AstNode::LetDecl { value, .. } => {
+                    assert_eq!(
+                        format!("{:?}", value),
+                        format!("{:?}", expected),
+                        "{}",
+                        text
+                    )
+                }
+                _ => panic!("Expected LetDecl"),
+            }
+        }
+        // One further is still out of range
+        for input in ["let x = -2147483649;", "let x = -9223372036854775809L;"] {
+            let tokens = lex(input);
+            let mut parser = Parser::new(&tokens);
+            assert!(parser.parse_statement().is_err(), "{}", input);
+        }
+        // A postfix binds to the number before the minus does
+        let tokens = lex("let x = -5..0;");
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement().unwrap() {
+            AstNode::LetDecl { value, .. } => {
+                assert!(matches!(*value, AstNode::UnaryExpr { .. }), "{:?}", value)
+            }
+            _ => panic!("Expected LetDecl"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_escape_reports_position() {
+        let tokens = lex(r#"print("bad \x escape");"#);
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement() {
+            Err(ParseError::UnexpectedTokenAt { msg, line, col }) => {
+                assert!(msg.contains("Invalid escape sequence '\\x'"), "{}", msg);
+                assert_eq!((line, col), (1, 12));
+            }
+            other => panic!("expected escape error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_float_type_annotation() {
+        let input = "let x: Float = 3.14;";
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        let result = parser.parse_statement();
+        match result.unwrap() {
+            AstNode::LetDecl {
+                type_annotation, ..
+            } => assert_eq!(type_annotation, Some(crate::parser::ast::TypeNode::Float)),
+            _ => panic!("Expected LetDecl"),
+        }
+    }
+
+    #[test]
+    fn test_optional_type_and_null() {
+        use crate::parser::ast::TypeNode;
+        let tokens = lex("let x: Str? = null;");
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement().unwrap() {
+            AstNode::LetDecl {
+                type_annotation,
+                value,
+                ..
+            } => {
+                assert_eq!(
+                    type_annotation,
+                    Some(TypeNode::Optional(Box::new(TypeNode::String)))
+                );
+                assert!(matches!(*value, AstNode::NullLiteral));
+            }
+            _ => panic!("Expected LetDecl"),
+        }
+    }
+
+    #[test]
+    fn test_null_coalescing_binds_loosest() {
+        let tokens = lex("let y = x ?? a + 1;");
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement().unwrap() {
+            AstNode::LetDecl { value, .. } => match *value {
+                AstNode::BinaryExpr { op, right, .. } => {
+                    assert_eq!(op, crate::lexar::token::TokenType::QuestionQuestion);
+                    assert!(matches!(*right, AstNode::BinaryExpr { .. }));
+                }
+                other => panic!("Expected BinaryExpr, got {:?}", other),
+            },
+            _ => panic!("Expected LetDecl"),
+        }
+    }
+
+    #[test]
+    fn test_if_let_statement() {
+        let tokens = lex("if let n = find(3) { print(n); } else { print(0); }");
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement().unwrap() {
+            AstNode::IfLet {
+                name,
+                then_block,
+                else_branch,
+                ..
+            } => {
+                assert_eq!(name, "n");
+                assert_eq!(then_block.len(), 1);
+                assert!(else_branch.is_some());
+            }
+            other => panic!("Expected IfLet, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_match_statement_and_expression() {
+        let tokens = lex("match n { 1 | -2 => { print(n); } \"a\" => 3, _ => 4 }");
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement().unwrap() {
+            AstNode::Match { arms, .. } => {
+                assert_eq!(arms.len(), 3);
+                assert!(matches!(
+                    arms[0].patterns.as_slice(),
+                    [
+                        MatchPattern::Literal(AstNode::NumberLiteral(1)),
+                        MatchPattern::Literal(AstNode::NumberLiteral(-2))
+                    ]
+                ));
+                assert!(matches!(*arms[0].body, AstNode::Block(_)));
+                assert!(matches!(*arms[1].body, AstNode::NumberLiteral(3)));
+                assert!(matches!(
+                    arms[2].patterns.as_slice(),
+                    [MatchPattern::Wildcard]
+                ));
+            }
+            other => panic!("Expected Match, got {:?}", other),
+        }
+
+        let tokens = lex("let s = match flag { true => \"yes\", false => \"no\", };");
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement().unwrap() {
+            AstNode::LetDecl { value, .. } => {
+                assert!(matches!(*value, AstNode::Match { ref arms, .. } if arms.len() == 2))
+            }
+            other => panic!("Expected LetDecl, got {:?}", other),
+        }
+
+        // Expression arms need a separating comma
+        let tokens = lex("match n { 1 => 2 _ => 3 }");
+        assert!(Parser::new(&tokens).parse_statement().is_err());
+    }
+
+    #[test]
+    fn test_if_expression() {
+        let tokens = lex("let x = if a > 1 { 10 } else if a < 0 { -1 } else { 20 };");
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement().unwrap() {
+            AstNode::LetDecl { value, .. } => match *value {
+                AstNode::IfExpr {
+                    then_branch,
+                    else_branch,
+                    ..
+                } => {
+                    assert!(matches!(*then_branch, AstNode::NumberLiteral(10)));
+                    assert!(matches!(*else_branch, AstNode::IfExpr { .. }));
+                }
+                other => panic!("Expected IfExpr, got {:?}", other),
+            },
+            other => panic!("Expected LetDecl, got {:?}", other),
+        }
+
+        // A value needs both branches
+        let tokens = lex("let x = if a { 10 };");
+        assert!(Parser::new(&tokens).parse_statement().is_err());
+    }
+
+    #[test]
+    fn test_loop_expression_with_break_value() {
+        let tokens = lex("let v = for { i += 1; if i > 3 { break i * 2; } };");
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement().unwrap() {
+            AstNode::LetDecl { value, .. } => match *value {
+                AstNode::LoopExpr { body, value_type } => {
+                    assert_eq!(body.len(), 2);
+                    assert!(value_type.is_none());
+                }
+                other => panic!("Expected LoopExpr, got {:?}", other),
+            },
+            other => panic!("Expected LetDecl, got {:?}", other),
+        }
+
+        // Only infinite loops produce values
+        let tokens = lex("let v = for i in 0..3 { break i; };");
+        assert!(Parser::new(&tokens).parse_statement().is_err());
+    }
+
+    #[test]
+    fn test_block_expression_value() {
+        let tokens = lex("let y = { let t = a * a; t + 1 };");
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement().unwrap() {
+            AstNode::LetDecl { value, .. } => match *value {
+                AstNode::BlockExpr { body, value, .. } => {
+                    assert_eq!(body.len(), 1);
+                    assert!(matches!(*value, AstNode::BinaryExpr { .. }));
+                }
+                other => panic!("Expected BlockExpr, got {:?}", other),
+            },
+            other => panic!("Expected LetDecl, got {:?}", other),
+        }
+
+        // Braces holding `key: value` entries are still a map
+        let tokens = lex("let m = {k: 1};");
+        match Parser::new(&tokens).parse_statement().unwrap() {
+            AstNode::LetDecl { value, .. } => assert!(matches!(*value, AstNode::MapLiteral(_))),
+            other => panic!("Expected LetDecl, got {:?}", other),
+        }
+
+        // A block needs a final value
+        let tokens = lex("let y = { let t = 1; };");
+        assert!(Parser::new(&tokens).parse_statement().is_err());
+    }
+
+    #[test]
+    fn test_closure_arguments() {
+        let tokens = lex("let t = nums.reduce(0, |acc, x: Int| acc + x);");
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement().unwrap() {
+            AstNode::LetDecl { value, .. } => match *value {
+                AstNode::MethodCall { method, args, .. } => {
+                    assert_eq!(method, "reduce");
+                    assert_eq!(args.len(), 2);
+                    match &args[1] {
+                        AstNode::Closure {
+                            params,
+                            body,
+                            value_type,
+                            captures,
+                        } => {
+                            assert_eq!(params[0], ("acc".to_string(), None));
+                            assert_eq!(params[1], ("x".to_string(), Some(TypeNode::Int)));
+                            assert!(matches!(**body, AstNode::BinaryExpr { .. }));
+                            assert!(value_type.is_none());
+                            assert!(captures.is_empty());
+                        }
+                        other => panic!("Expected Closure, got {:?}", other),
+                    }
+                }
+                other => panic!("Expected MethodCall, got {:?}", other),
+            },
+            other => panic!("Expected LetDecl, got {:?}", other),
+        }
+
+        // `||` opens a closure without parameters
+        let tokens = lex("let f = || 1;");
+        match Parser::new(&tokens).parse_statement().unwrap() {
+            AstNode::LetDecl { value, .. } => {
+                assert!(matches!(*value, AstNode::Closure { ref params, .. } if params.is_empty()))
+            }
+            other => panic!("Expected LetDecl, got {:?}", other),
+        }
+    }
+
     // =====================
     // Functions
     // =====================
@@ -90,6 +418,25 @@ mod parser_tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_variadic_function_param() {
+        let tokens = lex("fn sum(label: Str, args: Int...) -> Int { return 0; }");
+        match Parser::new(&tokens).parse_statement().unwrap() {
+            AstNode::FunctionDecl { params, .. } => {
+                assert_eq!(params[0].1, Some(TypeNode::String));
+                assert_eq!(
+                    params[1].1,
+                    Some(TypeNode::Variadic(Box::new(TypeNode::Int)))
+                );
+            }
+            other => panic!("Expected FunctionDecl, got {:?}", other),
+        }
+
+        // Only the last parameter can be variadic
+        let tokens = lex("fn bad(args: Int..., last: Int) {}");
+        assert!(Parser::new(&tokens).parse_statement().is_err());
+    }
+
     #[test]
     fn test_recursive_function() {
         let input = r#"
@@ -383,6 +730,32 @@ mod parser_tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_for_loop_range_with_step() {
+        let input = "for i in 10..0 step -2 { print(i); }";
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement().unwrap() {
+            AstNode::ForLoopStmt { step, .. } => {
+                assert!(matches!(step.as_deref(), Some(AstNode::NumberLiteral(-2))))
+            }
+            other => panic!("Expected ForLoopStmt, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_for_loop_index_and_item() {
+        let input = "for i, item in arr { print(i, item); }";
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement().unwrap() {
+            AstNode::ForLoopStmt { pattern, .. } => {
+                assert!(matches!(pattern, Pattern::Tuple(ref p) if p.len() == 2))
+            }
+            other => panic!("Expected ForLoopStmt, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_for_loop_over_map_destructuring() {
         let input = r#"for (key, val) in map { print(key, val); }"#;
@@ -615,6 +988,479 @@ mod parser_tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_struct_literal_and_field_access() {
+        let input = r#"let u = User { name: "A", age: 3 }; print(u.name);"#;
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement().unwrap() {
+            AstNode::LetDecl { value, .. } => match *value {
+                AstNode::StructLiteral { name, fields } => {
+                    assert_eq!(name, "User");
+                    let names: Vec<&str> = fields.iter().map(|(f, _)| f.as_str()).collect();
+                    assert_eq!(names, vec!["name", "age"]);
+                }
+                other => panic!("Expected StructLiteral, got {:?}", other),
+            },
+            _ => panic!("Expected LetDecl"),
+        }
+        match parser.parse_statement().unwrap() {
+            AstNode::Print { exprs } => assert!(matches!(
+                &exprs[0],
+                AstNode::FieldAccess { field, .. } if field == "name"
+            )),
+            _ => panic!("Expected Print"),
+        }
+    }
+
+    #[test]
+    fn test_weak_struct_fields() {
+        // `weak` is only a modifier in front of a field name
+        let input = "struct Node { weak: Int, weak parent: Node?, name: Str }";
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement().unwrap() {
+            AstNode::StructDecl {
+                fields,
+                weak_fields,
+                ..
+            } => {
+                let names: Vec<&str> = fields.iter().map(|(f, _)| f.as_str()).collect();
+                assert_eq!(names, vec!["weak", "parent", "name"]);
+                assert_eq!(weak_fields, vec!["parent".to_string()]);
+            }
+            other => panic!("Expected StructDecl, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_field_assignment() {
+        let input = "u.address.city = \"Oslo\";";
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement().unwrap() {
+            AstNode::FieldAssignment { object, field, .. } => {
+                assert_eq!(field, "city");
+                assert!(matches!(*object, AstNode::FieldAccess { .. }));
+            }
+            other => panic!("Expected FieldAssignment, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_lowercase_name_before_brace_is_not_struct_literal() {
+        // `if ready { ... }` must keep parsing `{` as the block
+        let input = "if ready { print(1); }";
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        assert!(matches!(
+            parser.parse_statement().unwrap(),
+            AstNode::ConditionalStmt { .. }
+        ));
+    }
+
+    #[test]
+    fn test_impl_block_and_method_call() {
+        let input = r#"
+            impl User {
+                fn rename(self, name: Str) { self.name = name; }
+                fn greet(self) -> Str { return self.name; }
+            }
+            print(u.rename("B").greet());
+        "#;
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement().unwrap() {
+            AstNode::ImplBlock {
+                struct_name,
+                trait_name: None,
+                methods,
+            } => {
+                assert_eq!(struct_name, "User");
+                assert_eq!(methods.len(), 2);
+                match &methods[0] {
+                    AstNode::FunctionDecl { name, params, .. } => {
+                        assert_eq!(name, "rename");
+                        assert_eq!(params.len(), 2);
+                        assert_eq!(params[0].0, "self");
+                        assert_eq!(params[0].1, Some(TypeNode::TypeRef("User".to_string())));
+                    }
+                    other => panic!("Expected FunctionDecl, got {:?}", other),
+                }
+            }
+            other => panic!("Expected ImplBlock, got {:?}", other),
+        }
+        match parser.parse_statement().unwrap() {
+            AstNode::Print { exprs } => match &exprs[0] {
+                AstNode::MethodCall {
+                    object,
+                    method,
+                    args,
+                    span,
+                } => {
+                    assert_eq!(method, "greet");
+                    assert!(args.is_empty());
+                    assert_eq!((span.line, span.col), (6, 33));
+                    assert!(matches!(
+                        &**object,
+                        AstNode::MethodCall { method, args, .. } if method == "rename" && args.len() == 1
+                    ));
+                }
+                other => panic!("Expected MethodCall, got {:?}", other),
+            },
+            _ => panic!("Expected Print"),
+        }
+    }
+
+    #[test]
+    fn test_method_without_self_is_rejected() {
+        let input = "impl User { fn make(name: Str) -> Str { return name; } }";
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        assert!(parser.parse_statement().is_err());
+    }
+
+    #[test]
+    fn test_trait_impl_and_generic_function() {
+        let input = r#"
+            trait Shape { fn area(self) -> Float; fn scale(self, by: Float); }
+            impl Shape for Circle { fn area(self) -> Float { return 1.0; } }
+            fn describe<T: Shape, U>(s: T, label: U) -> Float { return s.area(); }
+        "#;
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement().unwrap() {
+            AstNode::TraitDecl { name, methods } => {
+                assert_eq!(name, "Shape");
+                assert_eq!(methods.len(), 2);
+                assert_eq!(methods[0].return_type, Some(TypeNode::Float));
+                assert_eq!(
+                    methods[1].params,
+                    vec![
+                        (
+                            "self".to_string(),
+                            Some(TypeNode::TypeRef("Self".to_string()))
+                        ),
+                        ("by".to_string(), Some(TypeNode::Float)),
+                    ]
+                );
+            }
+            other => panic!("Expected TraitDecl, got {:?}", other),
+        }
+        match parser.parse_statement().unwrap() {
+            AstNode::ImplBlock {
+                struct_name,
+                trait_name,
+                methods,
+            } => {
+                assert_eq!(struct_name, "Circle");
+                assert_eq!(trait_name.as_deref(), Some("Shape"));
+                assert_eq!(methods.len(), 1);
+            }
+            other => panic!("Expected ImplBlock, got {:?}", other),
+        }
+        match parser.parse_statement().unwrap() {
+            AstNode::FunctionDecl {
+                type_params,
+                params,
+                ..
+            } => {
+                assert_eq!(
+                    type_params,
+                    vec![
+                        ("T".to_string(), Some("Shape".to_string())),
+                        ("U".to_string(), None),
+                    ]
+                );
+                assert_eq!(params[0].1, Some(TypeNode::TypeRef("T".to_string())));
+            }
+            other => panic!("Expected FunctionDecl, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_trait_method_with_body_is_rejected() {
+        let input = "trait Shape { fn area(self) -> Float { return 1.0; } }";
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        assert!(parser.parse_statement().is_err());
+    }
+
+    #[test]
+    fn test_enum_values_and_variant_patterns() {
+        let input = r#"
+            let s = Shape::Circle(2.0);
+            match s {
+                Shape::Circle(r) => { print(r); }
+                Shape::Square(_) | Shape::Empty => { print("other"); }
+            }
+        "#;
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_statement().unwrap() {
+            AstNode::LetDecl { value, .. } => match *value {
+                AstNode::EnumValue {
+                    enum_name,
+                    variant,
+                    payload,
+                } => {
+                    assert_eq!(enum_name, "Shape");
+                    assert_eq!(variant, "Circle");
+                    assert!(matches!(payload.as_deref(), Some(AstNode::FloatLiteral(_))));
+                }
+                other => panic!("Expected EnumValue, got {:?}", other),
+            },
+            other => panic!("Expected LetDecl, got {:?}", other),
+        }
+        match parser.parse_statement().unwrap() {
+            AstNode::Match { arms, .. } => {
+                assert!(matches!(
+                    arms[0].patterns.as_slice(),
+                    [MatchPattern::Variant { variant, binding: Some(b), .. }]
+                        if variant == "Circle" && b == "r"
+                ));
+                assert!(matches!(
+                    arms[1].patterns.as_slice(),
+                    [
+                        MatchPattern::Variant { binding: None, .. },
+                        MatchPattern::Variant { variant, binding: None, .. }
+                    ] if variant == "Empty"
+                ));
+            }
+            other => panic!("Expected Match, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_module_qualified_calls() {
+        // One argument reads like an enum payload; the analyzer tells them apart
+        let tokens = lex("let a = math::Add(1, 2); let b = math::Pi(); let c = math::Neg(1);");
+        let mut parser = Parser::new(&tokens);
+        for expected in ["math::Add", "math::Pi"] {
+            match parser.parse_statement().unwrap() {
+                AstNode::LetDecl { value, .. } => match *value {
+                    AstNode::FunctionCall { func, .. } => {
+                        assert!(matches!(*func, AstNode::Identifier(name, _) if name == expected))
+                    }
+                    other => panic!("Expected FunctionCall, got {:?}", other),
+                },
+                other => panic!("Expected LetDecl, got {:?}", other),
+            }
+        }
+        assert!(matches!(
+            parser.parse_statement().unwrap(),
+            AstNode::LetDecl { value, .. } if matches!(*value, AstNode::EnumValue { .. })
+        ));
+    }
+
+    #[test]
+    fn test_result_types_and_try() {
+        let input = r#"
+            fn half(n: Int) -> Result<Int, Str> {
+                let m = check(n)?;
+                match parse(m) {
+                    Ok(v) => { return Ok(v / 2); }
+                    Err(e) => { return Err(e); }
+                }
+            }
+            fn log(r: Result<Int, Str>, ok: Result<Void, Str>) {}
+        "#;
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        let AstNode::Program(nodes) = parser.parse_program().unwrap() else {
+            panic!("Expected Program");
+        };
+        // Each Result type is declared once, ahead of the program
+        match &nodes[0] {
+            AstNode::EnumDecl { name, variants } => {
+                assert_eq!(name, "Result<Int, Str>");
+                assert_eq!(variants[0], ("Ok".to_string(), Some(TypeNode::Int)));
+                assert_eq!(variants[1], ("Err".to_string(), Some(TypeNode::String)));
+            }
+            other => panic!("Expected EnumDecl, got {:?}", other),
+        }
+        assert!(matches!(
+            &nodes[1],
+            AstNode::EnumDecl { name, variants } if name == "Result<Void, Str>" && variants[0].1.is_none()
+        ));
+        assert_eq!(nodes.len(), 4);
+
+        let AstNode::FunctionDecl {
+            return_type, body, ..
+        } = &nodes[2]
+        else {
+            panic!("Expected FunctionDecl, got {:?}", nodes[2]);
+        };
+        assert_eq!(
+            return_type,
+            &Some(TypeNode::TypeRef("Result<Int, Str>".to_string()))
+        );
+        assert!(matches!(
+            &body[0],
+            AstNode::LetDecl { value, .. }
+                if matches!(&**value, AstNode::Try { expr, expr_type: None, .. }
+                    if matches!(&**expr, AstNode::FunctionCall { .. }))
+        ));
+        // `Ok(v)` and `Err(e)` patterns name the Result enum generically
+        match &body[1] {
+            AstNode::Match { arms, .. } => assert!(matches!(
+                arms[1].patterns.as_slice(),
+                [MatchPattern::Variant { enum_name, variant, binding: Some(b) }]
+                    if enum_name == "Result" && variant == "Err" && b == "e"
+            )),
+            other => panic!("Expected Match, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_statement_spans() {
+        let input = "fn main() {\n    let x = 1;\n    print(x);\n}\nfn f() -> Result<Int, Str> { return Ok(1); }";
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        let AstNode::Program(statements) = parser.parse_program().unwrap() else {
+            panic!("Expected Program");
+        };
+        // Top-level statements, with the made-up Result enum first
+        assert_eq!(statements.len(), 3);
+        let spans = &parser.statement_spans;
+        assert_eq!(spans[0], Span::default());
+        assert_eq!((spans[1].line, spans[1].col), (1, 1));
+        assert_eq!((spans[2].line, spans[2].col), (5, 1));
+        assert!(input[spans[1].start..spans[1].end].starts_with("fn main() {"));
+        assert!(input[spans[1].start..spans[1].end].ends_with("print(x);\n}"));
+        assert_eq!(spans[2].end, input.len());
+        // Function bodies record where each of their statements is
+        let AstNode::FunctionDecl { body_spans, .. } = &statements[1] else {
+            panic!("Expected FunctionDecl");
+        };
+        let texts: Vec<&str> = body_spans
+            .iter()
+            .map(|span| &input[span.start..span.end])
+            .collect();
+        assert_eq!(texts, vec!["let x = 1;", "print(x);"]);
+        assert_eq!((body_spans[1].line, body_spans[1].col), (3, 5));
+    }
+
+    #[test]
+    fn test_expression_spans() {
+        let input = "let v = items[i].size + -count(xs) * u.area();";
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        let AstNode::LetDecl { value, .. } = parser.parse_statement().unwrap() else {
+            panic!("Expected LetDecl");
+        };
+        let text = |node: &AstNode| {
+            let span = node.span().unwrap();
+            assert_eq!(span.line, 1);
+            assert_eq!(span.col, span.start + 1);
+            &input[span.start..span.end]
+        };
+        // Operations are located at their operator, and everything else at its name
+        let AstNode::BinaryExpr { left, right, .. } = &*value else {
+            panic!("Expected BinaryExpr");
+        };
+        assert_eq!(text(&value), "+");
+        let AstNode::FieldAccess { object, .. } = &**left else {
+            panic!("Expected FieldAccess");
+        };
+        assert_eq!(text(left), "size");
+        let AstNode::ElementAccess { array, index, .. } = &**object else {
+            panic!("Expected ElementAccess");
+        };
+        assert_eq!(text(object), "[");
+        assert_eq!((text(array), text(index)), ("items", "i"));
+        let AstNode::BinaryExpr { left, right, .. } = &**right else {
+            panic!("Expected BinaryExpr");
+        };
+        assert_eq!(text(left), "-");
+        assert_eq!(text(right), "area");
+        let AstNode::UnaryExpr { expr, .. } = &**left else {
+            panic!("Expected UnaryExpr");
+        };
+        assert_eq!(text(expr), "count");
+        // Literals have no span of their own
+        assert!(AstNode::NumberLiteral(1).span().is_none());
+    }
+
+    #[test]
+    fn test_ast_json() {
+        let tokens = lex("let x: Int? = -y + 2;");
+        let mut parser = Parser::new(&tokens);
+        let node = parser.parse_statement().unwrap();
+        assert_eq!(
+            node_to_json(&node),
+            "{\"kind\":\"LetDecl\",\"mutable\":false,\"type_annotation\":\"Int?\",\
+             \"pattern\":{\"kind\":\"Identifier\",\"name\":\"x\"},\
+             \"value\":{\"kind\":\"BinaryExpr\",\
+             \"left\":{\"kind\":\"UnaryExpr\",\"op\":\"Minus\",\
+             \"expr\":{\"kind\":\"Identifier\",\"name\":\"y\",\
+             \"span\":{\"start\":15,\"end\":16,\"line\":1,\"col\":16}},\
+             \"span\":{\"start\":14,\"end\":15,\"line\":1,\"col\":15}},\
+             \"op\":\"Plus\",\"right\":{\"kind\":\"NumberLiteral\",\"value\":2},\
+             \"span\":{\"start\":17,\"end\":18,\"line\":1,\"col\":18}}}"
+        );
+
+        // Programs carry each statement's span; strings are escaped
+        let input = "print(\"a\\\"b\", 1.5);";
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        let AstNode::Program(statements) = parser.parse_program().unwrap() else {
+            panic!("Expected Program");
+        };
+        let json = program_to_json(&statements, &parser.statement_spans);
+        assert!(json.starts_with("{\"kind\":\"Program\",\"statements\":[{\"kind\":\"Print\""));
+        assert!(json.contains("{\"kind\":\"StringLiteral\",\"value\":\"a\\\"b\"}"));
+        assert!(json.contains("{\"kind\":\"FloatLiteral\",\"value\":1.5}"));
+        assert!(json.ends_with("\"spans\":[{\"start\":0,\"end\":19,\"line\":1,\"col\":1}]}"));
+    }
+
+    #[test]
+    fn test_parse_error_recovery() {
+        let input = "fn main() {\n    let x = ;\n    print(x);\n    if x > { print(1); }\n    let y = 2;\n}\nlet = 3;\nfn other() -> Int { return 1 }\nstruct P { x: Int }";
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        let program = parser.parse_program_with_recovery();
+
+        // Every syntax error is recorded, in order
+        let lines: Vec<usize> = parser
+            .errors
+            .iter()
+            .map(|e| match e {
+                ParseError::UnexpectedTokenAt { line, .. } => *line,
+                _ => 0,
+            })
+            .collect();
+        assert_eq!(lines, vec![2, 4, 7, 8]);
+
+        // Statements around the errors still parse
+        let AstNode::Program(nodes) = program else {
+            panic!("Expected Program");
+        };
+        assert_eq!(nodes.len(), 3);
+        let AstNode::FunctionDecl { body, .. } = &nodes[0] else {
+            panic!("Expected main");
+        };
+        assert_eq!(body.len(), 2); // print(x) and let y
+        assert!(
+            matches!(&nodes[1], AstNode::FunctionDecl { name, body, .. } if name == "other" && body.is_empty())
+        );
+        assert!(matches!(&nodes[2], AstNode::StructDecl { .. }));
+
+        // parse_program fails with the first of them
+        let mut parser = Parser::new(&tokens);
+        assert!(matches!(
+            parser.parse_program(),
+            Err(ParseError::UnexpectedTokenAt { line: 2, .. })
+        ));
+        assert_eq!(parser.errors.len(), 4);
+
+        // Trial parses of block expressions don't record errors
+        let tokens = lex("fn main() { let y = { let t = 1; t + 1 }; let m = {}; }");
+        let mut parser = Parser::new(&tokens);
+        assert!(parser.parse_program().is_ok());
+        assert!(parser.errors.is_empty());
+    }
+
     // ---------------------
     // Invalid Element Access Tests
     // ---------------------
@@ -682,4 +1528,207 @@ mod parser_tests {
         let result = parser.parse_statement();
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_allow_attribute() {
+        let input = "#[allow(unused)] #[allow(unreachable)] fn main() { }";
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        let AstNode::Program(nodes) = parser.parse_program().unwrap() else {
+            panic!("Expected Program");
+        };
+        assert!(matches!(
+            &nodes[0],
+            AstNode::FunctionDecl { name, allow, .. }
+                if name == "main" && allow == &["unused", "unreachable"]
+        ));
+
+        // Only `allow` and `arena` are known, and only before a function
+        for input in [
+            "#[inline] fn f() { }",
+            "#[allow(unused)] let x = 1;",
+            "#[arena(1)] fn f() { }",
+        ] {
+            let tokens = lex(input);
+            assert!(Parser::new(&tokens).parse_program().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_arena_attribute() {
+        let input = "#[arena] #[allow(unused)] fn work() -> Int { return 1; } fn main() { }";
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        let AstNode::Program(nodes) = parser.parse_program().unwrap() else {
+            panic!("Expected Program");
+        };
+        assert!(matches!(
+            &nodes[0],
+            AstNode::FunctionDecl { name, allow, arena: true, .. }
+                if name == "work" && allow == &["unused"]
+        ));
+        assert!(matches!(
+            &nodes[1],
+            AstNode::FunctionDecl { arena: false, .. }
+        ));
+    }
+
+    #[test]
+    fn test_test_attribute() {
+        let input = "#[test] #[allow(unused)] fn addsUp() { } fn main() { }";
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        let AstNode::Program(nodes) = parser.parse_program().unwrap() else {
+            panic!("Expected Program");
+        };
+        assert!(matches!(
+            &nodes[0],
+            AstNode::FunctionDecl { name, allow, test: true, .. }
+                if name == "addsUp" && allow == &["unused"]
+        ));
+        assert!(matches!(
+            &nodes[1],
+            AstNode::FunctionDecl { test: false, .. }
+        ));
+
+        let tokens = lex("#[test] extern fn f();");
+        assert!(Parser::new(&tokens).parse_program().is_err());
+    }
+
+    #[test]
+    fn test_extern_function_decl() {
+        let input = r#"extern "C" fn puts(s: Str) -> Int; extern fn abort(); fn main() { }"#;
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        let AstNode::Program(nodes) = parser.parse_program().unwrap() else {
+            panic!("Expected Program");
+        };
+        assert!(matches!(
+            &nodes[0],
+            AstNode::ExternFunctionDecl { name, params, return_type: Some(TypeNode::Int), intrinsic: None }
+                if name == "puts" && params.len() == 1
+        ));
+        assert!(matches!(
+            &nodes[1],
+            AstNode::ExternFunctionDecl { name, params, return_type: None, intrinsic: None }
+                if name == "abort" && params.is_empty()
+        ));
+
+        for input in [
+            r#"extern "Rust" fn f();"#,
+            r#"extern "C" fn f() { }"#,
+            r#"extern "C" let x = 1;"#,
+            r#"extern "C" fn f(x);"#,
+        ] {
+            let tokens = lex(input);
+            assert!(Parser::new(&tokens).parse_program().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_intrinsic_attribute() {
+        let input =
+            r#"#[intrinsic("llvm.ctpop.i32")] extern fn popcount(x: Int) -> Int; fn main() { }"#;
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        let AstNode::Program(nodes) = parser.parse_program().unwrap() else {
+            panic!("Expected Program");
+        };
+        assert!(matches!(
+            &nodes[0],
+            AstNode::ExternFunctionDecl { name, intrinsic: Some(intrinsic), .. }
+                if name == "popcount" && intrinsic == "llvm.ctpop.i32"
+        ));
+
+        for input in [
+            r#"#[intrinsic("llvm.ctpop.i32")] fn f() { }"#,
+            r#"#[arena] extern fn f();"#,
+            r#"#[intrinsic(llvm)] extern fn f();"#,
+        ] {
+            let tokens = lex(input);
+            assert!(Parser::new(&tokens).parse_program().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_export_function_decl() {
+        let input = r#"
+            export "C" fn add(a: Int, b: Int) -> Int { return a + b; }
+            #[allow(unused)] export fn reset() { }
+            fn main() { }
+        "#;
+        let tokens = lex(input);
+        let mut parser = Parser::new(&tokens);
+        let AstNode::Program(nodes) = parser.parse_program().unwrap() else {
+            panic!("Expected Program");
+        };
+        assert!(matches!(
+            &nodes[0],
+            AstNode::FunctionDecl { name, export: true, .. } if name == "add"
+        ));
+        assert!(matches!(
+            &nodes[1],
+            AstNode::FunctionDecl { name, allow, export: true, .. }
+                if name == "reset" && allow == &["unused"]
+        ));
+        assert!(matches!(
+            &nodes[2],
+            AstNode::FunctionDecl { export: false, .. }
+        ));
+
+        for input in [
+            r#"export "C" fn f();"#,
+            r#"export "C" let x = 1;"#,
+            r#"export "Rust" fn f() { }"#,
+        ] {
+            let tokens = lex(input);
+            assert!(Parser::new(&tokens).parse_program().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_trailing_commas() {
+        let inputs = [
+            "fn main() { let a = [1, 2, 3,]; }",
+            "fn main() { let m = {\"a\": 1, \"b\": 2,}; }",
+            "fn f(a: Int, b: Int,) -> Int { return a; }",
+            "fn main() { f(1, 2,); print(1, 2,); }",
+            "struct P { x: Int, y: Int, } fn main() { let p = P { x: 1, y: 2, }; }",
+            "struct U { n: Int } impl U { fn get(self,) -> Int { return 1; } }",
+            "enum E { A(Int,), B, } fn main() { let e = E::A(1,); }",
+            "fn first<T,>(a: [T]) -> T { return a[0]; }",
+            "fn main() { let r: Result<Int, Str,> = Ok(1,); }",
+        ];
+        for input in inputs {
+            let tokens = lex(input);
+            let result = Parser::new(&tokens).parse_program();
+            assert!(result.is_ok(), "{}: {:?}", input, result);
+        }
+
+        // A comma still has to follow an item
+        for input in ["fn main() { let a = [,]; }", "fn main() { f(1,,); }"] {
+            let tokens = lex(input);
+            assert!(Parser::new(&tokens).parse_program().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_unterminated_block_comment_error() {
+        // The error points at the comment's opening, wherever the parser meets it
+        for (input, col) in [
+            ("fn main() {\n    let x = 1; /* not closed\n}", 16),
+            ("fn main() {\n    let x = /* not closed\n}", 13),
+            ("fn main()\n /* not closed", 2),
+        ] {
+            let tokens = lex(input);
+            let error = Parser::new(&tokens).parse_program().unwrap_err();
+            match error {
+                ParseError::UnexpectedTokenAt { msg, line, col: at } => {
+                    assert!(msg.starts_with("Unterminated block comment"), "{}", msg);
+                    assert_eq!((line, at), (2, col), "{}", input);
+                }
+                other => panic!("{}: {:?}", input, other),
+            }
+        }
+    }
 }
@@ -0,0 +1,77 @@
+//! Entry points for the browser playground, built with `--features
+//! playground` for `wasm32-unknown-unknown` (see the README). Only the front
+//! end runs there, as LLVM doesn't: a program is checked and lowered to MIR
+//! in the browser, with the files it imports and includes held in memory.
+
+use crate::analyzer::files::MemoryFiles;
+use crate::frontend::{front_end, FrontEndOptions};
+use std::path::Path;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+
+/// The program being edited, as `main.doo`, next to the files added to it.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct Playground {
+    files: MemoryFiles,
+}
+
+/// What the playground shows for a program.
+#[wasm_bindgen(getter_with_clone)]
+pub struct PlaygroundResult {
+    /// Whether it compiled without errors.
+    pub success: bool,
+    /// One JSON object per line, as `doo check --message-format=json` prints them.
+    pub diagnostics: String,
+    /// The optimized MIR in its text form, empty when checking or with errors.
+    pub mir: String,
+}
+
+#[wasm_bindgen]
+impl Playground {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file for `main.doo` to import or include, as `math.doo` for
+    /// `import math::*;`.
+    #[wasm_bindgen(js_name = addFile)]
+    pub fn add_file(&mut self, path: &str, contents: &str) {
+        self.files.insert(path, contents);
+    }
+
+    /// The diagnostics for `source`.
+    pub fn check(&self, source: &str) -> PlaygroundResult {
+        self.run(source, true)
+    }
+
+    /// The diagnostics for `source` and, when it has no errors, its MIR.
+    pub fn compile(&self, source: &str) -> PlaygroundResult {
+        self.run(source, false)
+    }
+
+    fn run(&self, source: &str, check_only: bool) -> PlaygroundResult {
+        let opts = FrontEndOptions {
+            check_only,
+            ..Default::default()
+        };
+        let (result, lowered) = front_end(
+            Path::new("main.doo"),
+            source,
+            &opts,
+            Rc::new(self.files.clone()),
+        );
+        PlaygroundResult {
+            success: result.success,
+            diagnostics: result
+                .diagnostics
+                .iter()
+                .map(|d| d.to_json() + "\n")
+                .collect(),
+            mir: lowered
+                .map(|lowered| lowered.program.to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
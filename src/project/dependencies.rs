@@ -0,0 +1,240 @@
+// Finds the packages `[dependencies]` names, fetching git ones, and adds
+// dependencies to a manifest for `doo add`. A dependency is a directory whose
+// root module its importers see under the dependency's name: the `entry` its
+// own doo.toml names, or else lib.doo or src/lib.doo.
+
+use super::{Dependency, DependencySource, Manifest, MANIFEST_FILE};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Where git dependencies are fetched to, in the project's directory.
+pub const DEPS_DIR: &str = ".doo/deps";
+
+/// The dependencies of the project in `dir`, its dependencies' own included,
+/// as each name and the root module file it's imported from. Git ones not
+/// fetched yet are cloned into `DEPS_DIR`, one directory per `rev`.
+pub fn resolve_dependencies(
+    dir: &Path,
+    manifest: &Manifest,
+) -> Result<Vec<(String, PathBuf)>, String> {
+    let mut resolved = Vec::new();
+    resolve_into(dir, dir, manifest, &mut resolved)?;
+    Ok(resolved)
+}
+
+/// Resolves the dependencies of the package in `package_dir` into `resolved`,
+/// fetching git ones under `project_dir`. A package required twice is one
+/// package when both lead to the same root.
+fn resolve_into(
+    project_dir: &Path,
+    package_dir: &Path,
+    manifest: &Manifest,
+    resolved: &mut Vec<(String, PathBuf)>,
+) -> Result<(), String> {
+    for dependency in &manifest.dependencies {
+        let dir = match &dependency.source {
+            DependencySource::Path(path) => package_dir.join(path),
+            DependencySource::Git { url, rev } => {
+                fetch_git(project_dir, &dependency.name, url, rev.as_deref())?
+            }
+        };
+        let (root, own_manifest) = library_root(&dependency.name, &dir)?;
+        if let Some((_, seen)) = resolved.iter().find(|(name, _)| *name == dependency.name) {
+            if !same_file(seen, &root) {
+                return Err(format!(
+                    "Error: dependency '{}' is required from two places: {} and {}",
+                    dependency.name,
+                    seen.display(),
+                    root.display()
+                ));
+            }
+            continue;
+        }
+        resolved.push((dependency.name.clone(), root));
+        if let Some(own_manifest) = own_manifest {
+            resolve_into(project_dir, &dir, &own_manifest, resolved)?;
+        }
+    }
+    Ok(())
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// The root module of the dependency `name` in `dir`, and its manifest if it
+/// has one.
+fn library_root(name: &str, dir: &Path) -> Result<(PathBuf, Option<Manifest>), String> {
+    if !dir.is_dir() {
+        return Err(format!(
+            "Error: dependency '{}' not found: {} is not a directory",
+            name,
+            dir.display()
+        ));
+    }
+    let manifest = Manifest::load(dir)?;
+    let candidates = match manifest.as_ref().and_then(|m| m.package.entry.as_ref()) {
+        Some(entry) => vec![dir.join(entry)],
+        None => vec![dir.join("lib.doo"), dir.join("src").join("lib.doo")],
+    };
+    match candidates.iter().find(|file| file.is_file()) {
+        Some(root) => Ok((root.clone(), manifest)),
+        None => Err(format!(
+            "Error: dependency '{}' has no root module: expected {}",
+            name,
+            candidates
+                .iter()
+                .map(|file| file.display().to_string())
+                .collect::<Vec<_>>()
+                .join(" or ")
+        )),
+    }
+}
+
+/// The directory a `rev` is checked out into: the hex FNV-1a hash of it, as
+/// a branch may have a `/` in it and no two revs may share a directory.
+pub(crate) fn checkout_dir(rev: &str) -> String {
+    let hash = rev.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// The directory the git dependency `name` is checked out in, cloning `url`
+/// at `rev` (or its default branch) there first if it isn't yet.
+fn fetch_git(
+    project_dir: &Path,
+    name: &str,
+    url: &str,
+    rev: Option<&str>,
+) -> Result<PathBuf, String> {
+    // Git would take a url or rev starting with `-` for an option
+    for (field, value) in [("git", Some(url)), ("rev", rev)] {
+        if value.is_some_and(|value| value.starts_with('-')) {
+            return Err(format!(
+                "Error: dependency '{}' has {} = \"{}\", which can't start with '-'",
+                name,
+                field,
+                value.unwrap_or_default()
+            ));
+        }
+    }
+    let checkout = rev.map_or("default".to_string(), checkout_dir);
+    let dest = project_dir.join(DEPS_DIR).join(name).join(checkout);
+    if dest.is_dir() {
+        return Ok(dest);
+    }
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+
+    let git = |args: &[&str]| -> Result<(), String> {
+        let output = Command::new("git")
+            .args(args)
+            .output()
+            .map_err(|e| format!("Error: fetching dependency '{}' needs git: {}", name, e))?;
+        if output.status.success() {
+            return Ok(());
+        }
+        Err(format!(
+            "Error: failed to fetch dependency '{}' from {}: {}",
+            name,
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    };
+    let dest_str = dest.to_string_lossy();
+    let fetched = git(&["clone", "--quiet", "--", url, &dest_str]).and_then(|()| match rev {
+        Some(rev) => git(&["-C", &dest_str, "checkout", "--quiet", rev, "--"]),
+        None => Ok(()),
+    });
+    // A failed fetch leaves nothing behind for the next build to take as done
+    if fetched.is_err() {
+        fs::remove_dir_all(&dest).ok();
+    }
+    fetched.map(|()| dest)
+}
+
+/// Adds `dependency` to the `[dependencies]` of the manifest in `dir`, after
+/// checking that it can be found (fetching it if it's a git one). The rest of
+/// the manifest is kept as written.
+pub fn add_dependency(dir: &Path, dependency: &Dependency) -> Result<(), String> {
+    let path = dir.join(MANIFEST_FILE);
+    let text = fs::read_to_string(&path).map_err(|_| {
+        format!(
+            "Error: no {} in {}: `doo add` adds to a project's manifest",
+            MANIFEST_FILE,
+            dir.display()
+        )
+    })?;
+    let manifest = Manifest::parse(&text).map_err(|e| format!("Error: {}", e))?;
+    if manifest
+        .dependencies
+        .iter()
+        .any(|existing| existing.name == dependency.name)
+    {
+        return Err(format!(
+            "Error: dependency '{}' is already in {}",
+            dependency.name, MANIFEST_FILE
+        ));
+    }
+
+    let text = with_dependency(&text, &dependency_line(dependency));
+    let manifest = Manifest::parse(&text).map_err(|e| format!("Error: {}", e))?;
+    resolve_dependencies(dir, &manifest)?;
+    fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// The manifest line declaring `dependency`.
+fn dependency_line(dependency: &Dependency) -> String {
+    let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+    let fields = match &dependency.source {
+        DependencySource::Path(path) => format!("path = {}", quote(&path.to_string_lossy())),
+        DependencySource::Git { url, rev: None } => format!("git = {}", quote(url)),
+        DependencySource::Git {
+            url,
+            rev: Some(rev),
+        } => {
+            format!("git = {}, rev = {}", quote(url), quote(rev))
+        }
+    };
+    format!("{} = {{ {} }}", dependency.name, fields)
+}
+
+/// `text` with `line` added at the end of its `[dependencies]` table, which
+/// is added at the end when there is none.
+fn with_dependency(text: &str, line: &str) -> String {
+    let mut lines: Vec<&str> = text.lines().collect();
+    let is_header = |l: &str| l.trim_start().starts_with('[');
+    let is_filler = |l: &str| l.trim().is_empty() || l.trim_start().starts_with('#');
+    match lines.iter().position(|l| l.trim() == "[dependencies]") {
+        Some(header) => {
+            // After the table's last entry, before the blank lines and
+            // comments leading into the next table
+            let end = lines[header + 1..]
+                .iter()
+                .position(|l| is_header(l))
+                .map_or(lines.len(), |i| header + 1 + i);
+            let mut at = end;
+            while at > header + 1 && is_filler(lines[at - 1]) {
+                at -= 1;
+            }
+            lines.insert(at, line);
+        }
+        None => {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                lines.push("");
+            }
+            lines.push("[dependencies]");
+            lines.push(line);
+        }
+    }
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
@@ -0,0 +1,369 @@
+// A project's manifest, `doo.toml` in its directory: the package's name, its
+// entry point and output, the build profiles and the dependencies.
+//
+//     [package]
+//     name = "hello"
+//     entry = "src/app.doo"    # main.doo, then src/main.doo, when left out
+//
+//     [profile.release]
+//     opt-level = 3
+//
+//     [dependencies]
+//     mathlib = { path = "../mathlib" }
+
+mod dependencies;
+mod toml;
+
+#[cfg(test)]
+mod tests;
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Entry, Value};
+
+pub use dependencies::{add_dependency, resolve_dependencies, DEPS_DIR};
+
+/// The manifest's file name.
+pub const MANIFEST_FILE: &str = "doo.toml";
+
+/// Why a manifest can't be used, and on which line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestError {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", MANIFEST_FILE, line, self.message),
+            None => write!(f, "{}: {}", MANIFEST_FILE, self.message),
+        }
+    }
+}
+
+/// A parsed `doo.toml`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    pub package: Package,
+    pub dev: Profile,     // `[profile.dev]`, for builds without `--release`
+    pub release: Profile, // `[profile.release]`
+    pub dependencies: Vec<Dependency>,
+}
+
+/// The `[package]` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Package {
+    pub name: String,
+    pub version: Option<String>,
+    pub entry: Option<PathBuf>, // Relative to the manifest's directory
+    pub output: Option<String>, // The binary's name; the package's name when left out
+}
+
+/// How a profile builds. Each setting only turns on what the command line
+/// may also turn on, and its `opt-level` applies when `-O` is not given.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profile {
+    pub opt_level: Option<String>, // `0`, `1`, `2`, `3` or `s`, as `-O` takes
+    pub debug: bool,
+    pub overflow_checks: bool,
+    pub lto: bool,
+}
+
+/// A package the project uses, from `[dependencies]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    pub name: String, // What the project imports it as
+    pub source: DependencySource,
+}
+
+/// Where a dependency comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DependencySource {
+    Path(PathBuf), // `{ path = "../mathlib" }`, relative to the manifest's directory
+    Git { url: String, rev: Option<String> }, // `{ git = "https://...", rev = "v1.0" }`
+}
+
+impl Manifest {
+    /// The manifest in the directory `dir`, or None when it has none.
+    pub fn load(dir: &Path) -> Result<Option<Manifest>, String> {
+        let path = dir.join(MANIFEST_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Manifest::parse(&text)
+            .map(Some)
+            .map_err(|e| format!("Error: {}", e))
+    }
+
+    /// Parses the text of a manifest. Tables and keys it doesn't know are
+    /// errors, so a misspelled one isn't silently ignored.
+    pub fn parse(text: &str) -> Result<Manifest, ManifestError> {
+        let mut package = None;
+        let mut dev = Profile::default();
+        let mut release = Profile::default();
+        let mut dependencies = Vec::new();
+        for table in toml::parse(text)? {
+            match table.name.as_str() {
+                "" => {
+                    if let Some(entry) = table.entries.first() {
+                        return Err(error(
+                            entry.line,
+                            format!("key '{}' is outside of a table", entry.key),
+                        ));
+                    }
+                }
+                "package" => package = Some(parse_package(&table.entries, table.line)?),
+                "profile.dev" => dev = parse_profile(&table.name, &table.entries)?,
+                "profile.release" => release = parse_profile(&table.name, &table.entries)?,
+                "dependencies" => {
+                    for entry in &table.entries {
+                        dependencies.push(parse_dependency(entry)?);
+                    }
+                }
+                name => {
+                    return Err(error(
+                        table.line,
+                        format!(
+                            "unknown table [{}], expected [package], [profile.dev], [profile.release] or [dependencies]",
+                            name
+                        ),
+                    ))
+                }
+            }
+        }
+        let package = package.ok_or_else(|| ManifestError {
+            line: None,
+            message: "missing the [package] table".to_string(),
+        })?;
+        Ok(Manifest {
+            package,
+            dev,
+            release,
+            dependencies,
+        })
+    }
+
+    /// The profile a build with or without `--release` uses.
+    pub fn profile(&self, release: bool) -> &Profile {
+        if release {
+            &self.release
+        } else {
+            &self.dev
+        }
+    }
+
+    /// The name of the binary: `output`, or else the package's name.
+    pub fn output_name(&self) -> &str {
+        self.package.output.as_deref().unwrap_or(&self.package.name)
+    }
+}
+
+fn error(line: usize, message: impl Into<String>) -> ManifestError {
+    ManifestError {
+        line: Some(line),
+        message: message.into(),
+    }
+}
+
+fn unknown_key(entry: &Entry, table: &str, expected: &str) -> ManifestError {
+    error(
+        entry.line,
+        format!(
+            "unknown key '{}' in [{}], expected {}",
+            entry.key, table, expected
+        ),
+    )
+}
+
+fn string(entry: &Entry) -> Result<String, ManifestError> {
+    match &entry.value {
+        Value::String(s) => Ok(s.clone()),
+        value => Err(error(
+            entry.line,
+            format!("'{}' must be a string, found {}", entry.key, value.kind()),
+        )),
+    }
+}
+
+fn boolean(entry: &Entry) -> Result<bool, ManifestError> {
+    match &entry.value {
+        Value::Boolean(b) => Ok(*b),
+        value => Err(error(
+            entry.line,
+            format!(
+                "'{}' must be true or false, found {}",
+                entry.key,
+                value.kind()
+            ),
+        )),
+    }
+}
+
+/// Whether `name` can name a package: letters, digits and `-`, starting
+/// with a letter.
+fn is_package_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn parse_package(entries: &[Entry], line: usize) -> Result<Package, ManifestError> {
+    let mut name = None;
+    let mut package = Package {
+        name: String::new(),
+        version: None,
+        entry: None,
+        output: None,
+    };
+    for entry in entries {
+        match entry.key.as_str() {
+            "name" => {
+                let value = string(entry)?;
+                if !is_package_name(&value) {
+                    return Err(error(
+                        entry.line,
+                        format!(
+                            "invalid package name '{}': use letters, digits and '-', starting with a letter",
+                            value
+                        ),
+                    ));
+                }
+                name = Some(value);
+            }
+            "version" => package.version = Some(string(entry)?),
+            "entry" => package.entry = Some(PathBuf::from(string(entry)?)),
+            "output" => package.output = Some(string(entry)?),
+            _ => {
+                return Err(unknown_key(
+                    entry,
+                    "package",
+                    "'name', 'version', 'entry' or 'output'",
+                ))
+            }
+        }
+    }
+    package.name = name.ok_or_else(|| error(line, "[package] has no 'name'"))?;
+    Ok(package)
+}
+
+fn parse_profile(table: &str, entries: &[Entry]) -> Result<Profile, ManifestError> {
+    let mut profile = Profile::default();
+    for entry in entries {
+        match entry.key.as_str() {
+            "opt-level" => {
+                let level = match &entry.value {
+                    Value::Integer(n) => n.to_string(),
+                    Value::String(s) => s.clone(),
+                    value => {
+                        return Err(error(
+                            entry.line,
+                            format!(
+                                "'opt-level' must be 0, 1, 2, 3 or \"s\", found {}",
+                                value.kind()
+                            ),
+                        ))
+                    }
+                };
+                if !["0", "1", "2", "3", "s"].contains(&level.as_str()) {
+                    return Err(error(
+                        entry.line,
+                        format!("'opt-level' must be 0, 1, 2, 3 or \"s\", found {}", level),
+                    ));
+                }
+                profile.opt_level = Some(level);
+            }
+            "debug" => profile.debug = boolean(entry)?,
+            "overflow-checks" => profile.overflow_checks = boolean(entry)?,
+            "lto" => profile.lto = boolean(entry)?,
+            _ => {
+                return Err(unknown_key(
+                    entry,
+                    table,
+                    "'opt-level', 'debug', 'overflow-checks' or 'lto'",
+                ))
+            }
+        }
+    }
+    Ok(profile)
+}
+
+/// A `name = { path = ... }` or `name = { git = ..., rev = ... }` line.
+fn parse_dependency(entry: &Entry) -> Result<Dependency, ManifestError> {
+    // Imported by its name, so it has to be an identifier
+    if !entry.key.starts_with(|c: char| c.is_ascii_alphabetic())
+        || !entry.key.chars().all(|c| c.is_ascii_alphanumeric())
+    {
+        return Err(error(
+            entry.line,
+            format!(
+                "invalid dependency name '{}': it is imported by name, so use letters and digits, starting with a letter",
+                entry.key
+            ),
+        ));
+    }
+    if entry.key == "std" {
+        return Err(error(
+            entry.line,
+            "invalid dependency name 'std': it is the standard library's",
+        ));
+    }
+    let Value::Table(fields) = &entry.value else {
+        return Err(error(
+            entry.line,
+            format!(
+                "dependency '{}' must be a table, as in {{ path = \"../{}\" }}",
+                entry.key, entry.key
+            ),
+        ));
+    };
+    let mut path = None;
+    let mut git = None;
+    let mut rev = None;
+    for (key, value) in fields {
+        let field = Entry {
+            key: key.clone(),
+            value: value.clone(),
+            line: entry.line,
+        };
+        match key.as_str() {
+            "path" => path = Some(PathBuf::from(string(&field)?)),
+            "git" => git = Some(string(&field)?),
+            "rev" => rev = Some(string(&field)?),
+            _ => {
+                return Err(error(
+                    entry.line,
+                    format!(
+                        "unknown key '{}' in dependency '{}', expected 'path', 'git' or 'rev'",
+                        key, entry.key
+                    ),
+                ))
+            }
+        }
+    }
+    let source = match (path, git) {
+        (Some(path), None) if rev.is_none() => DependencySource::Path(path),
+        (None, Some(url)) => DependencySource::Git { url, rev },
+        (Some(_), None) => {
+            return Err(error(
+                entry.line,
+                format!("dependency '{}' has a 'rev' but no 'git'", entry.key),
+            ))
+        }
+        _ => {
+            return Err(error(
+                entry.line,
+                format!(
+                    "dependency '{}' needs either a 'path' or a 'git'",
+                    entry.key
+                ),
+            ))
+        }
+    };
+    Ok(Dependency {
+        name: entry.key.clone(),
+        source,
+    })
+}
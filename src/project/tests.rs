@@ -0,0 +1,304 @@
+#[cfg(test)]
+mod project_tests {
+    use crate::project::dependencies::checkout_dir;
+    use crate::project::{
+        add_dependency, resolve_dependencies, Dependency, DependencySource, Manifest, Profile,
+    };
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    /// An empty directory for one test.
+    fn temp_project(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("doo_{}_{}", name, std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(path: &Path, text: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = Manifest::parse(
+            r#"
+            # A comment
+            [package]
+            name = "hello-world"
+            version = "0.1.0"
+            entry = "src/app.doo"   # Not main.doo
+
+            [profile.release]
+            opt-level = "s"
+            lto = true
+
+            [profile.dev]
+            opt-level = 0
+            overflow-checks = true
+
+            [dependencies]
+            mathlib = { path = "../mathlib" }
+            json = { git = "https://example.com/json.git", rev = "v1.2" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(manifest.package.name, "hello-world");
+        assert_eq!(manifest.package.version.as_deref(), Some("0.1.0"));
+        assert_eq!(manifest.package.entry, Some(PathBuf::from("src/app.doo")));
+        assert_eq!(manifest.output_name(), "hello-world");
+        assert_eq!(
+            manifest.profile(true),
+            &Profile {
+                opt_level: Some("s".to_string()),
+                lto: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(manifest.profile(false).opt_level.as_deref(), Some("0"));
+        assert!(manifest.profile(false).overflow_checks);
+        assert_eq!(manifest.dependencies.len(), 2);
+        assert_eq!(manifest.dependencies[0].name, "mathlib");
+        assert_eq!(
+            manifest.dependencies[0].source,
+            DependencySource::Path(PathBuf::from("../mathlib"))
+        );
+        assert_eq!(
+            manifest.dependencies[1].source,
+            DependencySource::Git {
+                url: "https://example.com/json.git".to_string(),
+                rev: Some("v1.2".to_string()),
+            }
+        );
+
+        // Only the name is required
+        let manifest = Manifest::parse("[package]\nname = \"app\"\noutput = \"bin\"").unwrap();
+        assert_eq!(manifest.output_name(), "bin");
+        assert_eq!(manifest.profile(true), &Profile::default());
+        assert!(manifest.dependencies.is_empty());
+    }
+
+    #[test]
+    fn test_manifest_errors() {
+        let cases = [
+            ("name = \"app\"", "doo.toml:1: key 'name' is outside of a table"),
+            ("[dependencies]", "doo.toml: missing the [package] table"),
+            ("[package]\nversion = \"1\"", "doo.toml:1: [package] has no 'name'"),
+            (
+                "[package]\nname = app",
+                "doo.toml:2: expected a value, found 'app': strings are quoted",
+            ),
+            (
+                "[package]\nname = \"app\"\nname = \"other\"",
+                "doo.toml:3: key 'name' is given twice",
+            ),
+            (
+                "[package]\nname = \"my_app\"",
+                "doo.toml:2: invalid package name 'my_app': use letters, digits and '-', starting with a letter",
+            ),
+            (
+                "[package]\nname = \"app\"\n[profile.release]\nopt-level = 4",
+                "doo.toml:4: 'opt-level' must be 0, 1, 2, 3 or \"s\", found 4",
+            ),
+            (
+                "[package]\nname = \"app\"\n[profile.dev]\ndebug = \"yes\"",
+                "doo.toml:4: 'debug' must be true or false, found a string",
+            ),
+            (
+                "[package]\nname = \"app\"\n[profile]\n",
+                "doo.toml:3: unknown table [profile], expected [package], [profile.dev], [profile.release] or [dependencies]",
+            ),
+            (
+                "[package]\nname = \"app\"\nentry-point = \"a.doo\"",
+                "doo.toml:3: unknown key 'entry-point' in [package], expected 'name', 'version', 'entry' or 'output'",
+            ),
+            (
+                "[package]\nname = \"app\"\n[dependencies]\nmathlib = \"1.0\"",
+                "doo.toml:4: dependency 'mathlib' must be a table, as in { path = \"../mathlib\" }",
+            ),
+            (
+                "[package]\nname = \"app\"\n[dependencies]\nmathlib = { path = \"a\", git = \"b\" }",
+                "doo.toml:4: dependency 'mathlib' needs either a 'path' or a 'git'",
+            ),
+            (
+                "[package]\nname = \"app\"\n[dependencies]\nmath-lib = { path = \"a\" }",
+                "doo.toml:4: invalid dependency name 'math-lib': it is imported by name, so use letters and digits, starting with a letter",
+            ),
+            (
+                "[package]\nname = \"app",
+                "doo.toml:2: string is never closed",
+            ),
+        ];
+        for (text, expected) in cases {
+            let error = Manifest::parse(text).unwrap_err();
+            assert_eq!(error.to_string(), expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_resolve_dependencies() {
+        let dir = temp_project("deps");
+        write(
+            &dir.join("app/doo.toml"),
+            "[package]\nname = \"app\"\n[dependencies]\nmathlib = { path = \"../mathlib\" }\n",
+        );
+        // A dependency's own dependencies come along, relative to it
+        write(
+            &dir.join("mathlib/doo.toml"),
+            "[package]\nname = \"mathlib\"\n[dependencies]\nutil = { path = \"../util\" }\n",
+        );
+        write(
+            &dir.join("mathlib/lib.doo"),
+            "fn Half(x: Int) -> Int { return x / 2; }\n",
+        );
+        write(
+            &dir.join("util/src/lib.doo"),
+            "fn Id(x: Int) -> Int { return x; }\n",
+        );
+
+        let app = dir.join("app");
+        let manifest = Manifest::load(&app).unwrap().unwrap();
+        let resolved = resolve_dependencies(&app, &manifest).unwrap();
+        assert_eq!(
+            resolved,
+            vec![
+                (
+                    "mathlib".to_string(),
+                    app.join("../mathlib").join("lib.doo")
+                ),
+                (
+                    "util".to_string(),
+                    app.join("../mathlib")
+                        .join("../util")
+                        .join("src")
+                        .join("lib.doo")
+                ),
+            ]
+        );
+
+        fs::remove_file(dir.join("util/src/lib.doo")).unwrap();
+        let error = resolve_dependencies(&app, &manifest).unwrap_err();
+        assert!(
+            error.contains("dependency 'util' has no root module"),
+            "{}",
+            error
+        );
+        let manifest = Manifest::parse(
+            "[package]\nname = \"app\"\n[dependencies]\nnone = { path = \"../none\" }\n",
+        )
+        .unwrap();
+        let error = resolve_dependencies(&app, &manifest).unwrap_err();
+        assert!(error.contains("dependency 'none' not found"), "{}", error);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_add_dependency() {
+        let dir = temp_project("add");
+        let app = dir.join("app");
+        write(
+            &app.join("doo.toml"),
+            "[package]\nname = \"app\"\n\n[dependencies]\nutil = { path = \"../util\" }\n\n# Builds\n[profile.dev]\ndebug = true\n",
+        );
+        write(
+            &dir.join("util/lib.doo"),
+            "fn Id(x: Int) -> Int { return x; }\n",
+        );
+        write(
+            &dir.join("mathlib/lib.doo"),
+            "fn Half(x: Int) -> Int { return x / 2; }\n",
+        );
+
+        let mathlib = Dependency {
+            name: "mathlib".to_string(),
+            source: DependencySource::Path(PathBuf::from("../mathlib")),
+        };
+        add_dependency(&app, &mathlib).unwrap();
+        let text = fs::read_to_string(app.join("doo.toml")).unwrap();
+        assert!(
+            text.contains(
+                "util = { path = \"../util\" }\nmathlib = { path = \"../mathlib\" }\n\n# Builds\n"
+            ),
+            "{}",
+            text
+        );
+        let error = add_dependency(&app, &mathlib).unwrap_err();
+        assert!(error.contains("already in doo.toml"), "{}", error);
+
+        // Nothing is written for a dependency that can't be found
+        let missing = Dependency {
+            name: "missing".to_string(),
+            source: DependencySource::Path(PathBuf::from("../missing")),
+        };
+        assert!(add_dependency(&app, &missing).is_err());
+        assert_eq!(fs::read_to_string(app.join("doo.toml")).unwrap(), text);
+
+        // A git dependency is fetched into the project, here from a local repository
+        let repo = dir.join("repo");
+        write(&repo.join("lib.doo"), "fn Two() -> Int { return 2; }\n");
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .args(["-c", "user.name=doo", "-c", "user.email=doo@example.com"])
+                .args(args)
+                .current_dir(&repo)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?}", args);
+        };
+        git(&["init", "--quiet"]);
+        git(&["add", "lib.doo"]);
+        git(&["commit", "--quiet", "-m", "Add Two"]);
+        git(&["tag", "v1"]);
+        let two = Dependency {
+            name: "two".to_string(),
+            source: DependencySource::Git {
+                url: repo.to_string_lossy().into_owned(),
+                rev: Some("v1".to_string()),
+            },
+        };
+        add_dependency(&app, &two).unwrap();
+        assert!(app
+            .join(".doo/deps/two")
+            .join(checkout_dir("v1"))
+            .join("lib.doo")
+            .is_file());
+        let manifest = Manifest::load(&app).unwrap().unwrap();
+        assert_eq!(manifest.dependencies.len(), 3);
+        assert_eq!(manifest.dependencies[2], two);
+
+        // A url or rev that git would read as an option is refused before
+        // git runs
+        for (url, rev) in [
+            ("--upload-pack=touch pwned", None),
+            (repo.to_str().unwrap(), Some("--orphan=pwned")),
+        ] {
+            let dependency = Dependency {
+                name: "evil".to_string(),
+                source: DependencySource::Git {
+                    url: url.to_string(),
+                    rev: rev.map(str::to_string),
+                },
+            };
+            let error = add_dependency(&app, &dependency).unwrap_err();
+            assert!(error.contains("can't start with '-'"), "{}", error);
+            assert!(!app.join(".doo/deps/evil").exists());
+        }
+
+        // Revs that only differ in characters a path can't hold still get
+        // directories of their own
+        assert_ne!(checkout_dir("a/b"), checkout_dir("a_b"));
+        assert_ne!(checkout_dir("default"), "default");
+
+        // Without a [dependencies] table, one is added
+        write(&app.join("doo.toml"), "[package]\nname = \"app\"\n");
+        add_dependency(&app, &mathlib).unwrap();
+        assert_eq!(
+            fs::read_to_string(app.join("doo.toml")).unwrap(),
+            "[package]\nname = \"app\"\n\n[dependencies]\nmathlib = { path = \"../mathlib\" }\n"
+        );
+        fs::remove_dir_all(&dir).ok();
+    }
+}
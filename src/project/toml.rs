@@ -0,0 +1,275 @@
+// The part of TOML a manifest uses: `[table]` headers, `key = value` lines and
+// `#` comments. Values are strings, integers, booleans, arrays and inline
+// tables, each on one line.
+
+use super::ManifestError;
+
+/// A value on the right of `=`.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+    Table(Vec<(String, Value)>), // `{ path = "../lib" }`, keys in order
+}
+
+impl Value {
+    /// What the value is, for errors: `a string`, `an integer`, ...
+    pub(super) fn kind(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Integer(_) => "an integer",
+            Value::Boolean(_) => "a boolean",
+            Value::Array(_) => "an array",
+            Value::Table(_) => "a table",
+        }
+    }
+}
+
+/// A `key = value` line.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Entry {
+    pub key: String,
+    pub value: Value,
+    pub line: usize,
+}
+
+/// A `[name]` header and the entries under it. The entries before the first
+/// header are in a table named "".
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Table {
+    pub name: String, // Dotted as written: `profile.release`
+    pub line: usize,
+    pub entries: Vec<Entry>,
+}
+
+/// The tables of `text`, in order. A table or a key given twice is an error.
+pub(super) fn parse(text: &str) -> Result<Vec<Table>, ManifestError> {
+    let mut tables = vec![Table {
+        name: String::new(),
+        line: 0,
+        entries: Vec::new(),
+    }];
+    for (index, line) in text.lines().enumerate() {
+        let mut cursor = Cursor {
+            chars: line.chars().collect(),
+            pos: 0,
+            line: index + 1,
+        };
+        cursor.skip_space();
+        if cursor.at_end() {
+            continue;
+        }
+        if cursor.eat('[') {
+            let mut name = vec![cursor.key()?];
+            while cursor.eat('.') {
+                name.push(cursor.key()?);
+            }
+            cursor.expect(']')?;
+            cursor.expect_end()?;
+            let name = name.join(".");
+            if tables.iter().any(|table| table.name == name) {
+                return Err(cursor.error(format!("table [{}] is given twice", name)));
+            }
+            tables.push(Table {
+                name,
+                line: cursor.line,
+                entries: Vec::new(),
+            });
+            continue;
+        }
+        let key = cursor.key()?;
+        cursor.expect('=')?;
+        let value = cursor.value()?;
+        cursor.expect_end()?;
+        let table = tables.last_mut().expect("the root table is never removed");
+        if table.entries.iter().any(|entry| entry.key == key) {
+            return Err(cursor.error(format!("key '{}' is given twice", key)));
+        }
+        table.entries.push(Entry {
+            key,
+            value,
+            line: cursor.line,
+        });
+    }
+    Ok(tables)
+}
+
+/// A position in one line of the manifest.
+struct Cursor {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Cursor {
+    fn error(&self, message: impl Into<String>) -> ManifestError {
+        ManifestError {
+            line: Some(self.line),
+            message: message.into(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    /// Skips spaces and tabs, and a comment running to the end of the line.
+    fn skip_space(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+        if self.peek() == Some('#') {
+            self.pos = self.chars.len();
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.chars.len()
+    }
+
+    /// Consumes `c`, and the space after it, if it comes next.
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() != Some(c) {
+            return false;
+        }
+        self.pos += 1;
+        self.skip_space();
+        true
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ManifestError> {
+        if self.eat(c) {
+            return Ok(());
+        }
+        Err(self.error(format!("expected '{}', {}", c, self.found())))
+    }
+
+    fn expect_end(&self) -> Result<(), ManifestError> {
+        if self.at_end() {
+            return Ok(());
+        }
+        Err(self.error(format!("expected the end of the line, {}", self.found())))
+    }
+
+    /// What comes next, for errors.
+    fn found(&self) -> String {
+        match self.peek() {
+            Some(c) => format!("found '{}'", c),
+            None => "found the end of the line".to_string(),
+        }
+    }
+
+    /// A bare key (`opt-level`) or a quoted one (`"my key"`).
+    fn key(&mut self) -> Result<String, ManifestError> {
+        if self.peek() == Some('"') {
+            return self.string();
+        }
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(self.error(format!("expected a key, {}", self.found())));
+        }
+        let key = self.chars[start..self.pos].iter().collect();
+        self.skip_space();
+        Ok(key)
+    }
+
+    fn value(&mut self) -> Result<Value, ManifestError> {
+        match self.peek() {
+            Some('"') => self.string().map(Value::String),
+            Some('[') => {
+                self.eat('[');
+                let mut items = Vec::new();
+                while !self.eat(']') {
+                    items.push(self.value()?);
+                    if !self.eat(',') {
+                        self.expect(']')?;
+                        break;
+                    }
+                }
+                Ok(Value::Array(items))
+            }
+            Some('{') => {
+                self.eat('{');
+                let mut entries: Vec<(String, Value)> = Vec::new();
+                if self.eat('}') {
+                    return Ok(Value::Table(entries));
+                }
+                loop {
+                    let key = self.key()?;
+                    if entries.iter().any(|(k, _)| *k == key) {
+                        return Err(self.error(format!("key '{}' is given twice", key)));
+                    }
+                    self.expect('=')?;
+                    entries.push((key, self.value()?));
+                    if !self.eat(',') {
+                        self.expect('}')?;
+                        return Ok(Value::Table(entries));
+                    }
+                }
+            }
+            _ => {
+                let start = self.pos;
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || c == '-' || c == '+' || c == '_')
+                {
+                    self.pos += 1;
+                }
+                let word: String = self.chars[start..self.pos].iter().collect();
+                let value = match word.as_str() {
+                    "true" => Value::Boolean(true),
+                    "false" => Value::Boolean(false),
+                    _ => match word.replace('_', "").parse() {
+                        Ok(n) => Value::Integer(n),
+                        Err(_) if word.is_empty() => {
+                            return Err(self.error(format!("expected a value, {}", self.found())))
+                        }
+                        Err(_) => {
+                            return Err(self.error(format!(
+                                "expected a value, found '{}': strings are quoted",
+                                word
+                            )))
+                        }
+                    },
+                };
+                self.skip_space();
+                Ok(value)
+            }
+        }
+    }
+
+    /// A `"`-quoted string, with the escapes `\"`, `\\`, `\n` and `\t`.
+    fn string(&mut self) -> Result<String, ManifestError> {
+        self.pos += 1; // The opening quote
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("string is never closed")),
+                Some('"') => break,
+                Some('\\') => {
+                    self.pos += 1;
+                    text.push(match self.peek() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        _ => return Err(self.error(format!("unknown escape, {}", self.found()))),
+                    });
+                }
+                Some(c) => text.push(c),
+            }
+            self.pos += 1;
+        }
+        self.pos += 1; // The closing quote
+        self.skip_space();
+        Ok(text)
+    }
+}
@@ -0,0 +1,171 @@
+//! Python bindings, built with `--features python` (`maturin develop` reads
+//! the feature from `pyproject.toml`). `import doo` gives the lexer, parser,
+//! checker and compiler as functions from source text to Python values, so
+//! nothing has to be written to a file.
+
+use crate::compiler::{compile_source, Artifact, CompileOptions, CompileResult, Emit, OptLevel};
+use crate::lexar::lexer;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyList, PyString};
+use std::path::Path;
+
+create_exception!(
+    doo,
+    CompileError,
+    PyException,
+    "A program that doesn't compile. `args` holds the message and the diagnostics."
+);
+
+/// The tokens of `source` as `(kind, text, line, col)` tuples. Text that
+/// isn't a token is skipped; `check` reports it.
+#[pyfunction]
+fn lex(source: &str) -> Vec<(String, String, usize, usize)> {
+    lexer::lex(source)
+        .iter()
+        .map(|token| {
+            (
+                format!("{:?}", token.kind),
+                token.value.to_string(),
+                token.line,
+                token.col,
+            )
+        })
+        .collect()
+}
+
+/// The parsed program as a dict, in the format of `doo check
+/// --emit=ast-json`. Raises `CompileError` when it has syntax errors.
+#[pyfunction]
+#[pyo3(signature = (source, file_name = "main.doo"))]
+fn parse<'py>(py: Python<'py>, source: &str, file_name: &str) -> PyResult<Bound<'py, PyAny>> {
+    let result = run(
+        py,
+        source,
+        file_name,
+        CompileOptions {
+            check_only: true,
+            emit: vec![Emit::AstJson],
+            ..Default::default()
+        },
+    )?;
+    if result.diagnostics.iter().any(|d| d.is_parse) {
+        return Err(compile_error(py, "syntax errors", &result));
+    }
+    json_value(py, result.ast_json.as_deref().unwrap_or("null"))
+}
+
+/// The errors and warnings in `source`, as dicts in the format of `doo check
+/// --message-format=json`.
+#[pyfunction]
+#[pyo3(signature = (source, file_name = "main.doo"))]
+fn check<'py>(py: Python<'py>, source: &str, file_name: &str) -> PyResult<Bound<'py, PyList>> {
+    let result = run(
+        py,
+        source,
+        file_name,
+        CompileOptions {
+            check_only: true,
+            ..Default::default()
+        },
+    )?;
+    diagnostics(py, &result)
+}
+
+/// `source` compiled to `emit`: `"mir"` or `"llvm-ir"` text, or `"object"`
+/// file bytes. Raises `CompileError` when it doesn't compile.
+#[pyfunction]
+#[pyo3(name = "compile", signature = (source, file_name = "main.doo", emit = "llvm-ir", opt_level = "2", release = false))]
+fn compile_program<'py>(
+    py: Python<'py>,
+    source: &str,
+    file_name: &str,
+    emit: &str,
+    opt_level: &str,
+    release: bool,
+) -> PyResult<Bound<'py, PyAny>> {
+    let artifact = Artifact::from_name(emit).ok_or_else(|| {
+        PyValueError::new_err(format!(
+            "unknown emit '{}', expected 'mir', 'llvm-ir' or 'object'",
+            emit
+        ))
+    })?;
+    let opt_level = OptLevel::from_name(opt_level).ok_or_else(|| {
+        PyValueError::new_err(format!(
+            "unknown opt_level '{}', expected '0', '1', '2', '3' or 's'",
+            opt_level
+        ))
+    })?;
+    let result = run(
+        py,
+        source,
+        file_name,
+        CompileOptions {
+            release,
+            opt_level: Some(opt_level),
+            artifact: Some(artifact),
+            ..Default::default()
+        },
+    )?;
+    let Some(bytes) = result.artifact.as_deref().filter(|_| result.success) else {
+        let message = format!("{} errors", result.error_count);
+        return Err(compile_error(py, &message, &result));
+    };
+    Ok(match artifact {
+        Artifact::Object => PyBytes::new(py, bytes).into_any(),
+        _ => PyString::new(py, &String::from_utf8_lossy(bytes)).into_any(),
+    })
+}
+
+/// Runs the compiler on `source` as the file `file_name`, with the GIL
+/// released. An error that stops it early becomes a `CompileError` with no
+/// diagnostics.
+fn run(
+    py: Python<'_>,
+    source: &str,
+    file_name: &str,
+    opts: CompileOptions,
+) -> PyResult<CompileResult> {
+    let path = Path::new(file_name);
+    let opts = CompileOptions {
+        input_path: path.to_path_buf(),
+        dev_mode: false,
+        ..opts
+    };
+    py.allow_threads(|| compile_source(path, source, opts))
+        .map_err(|message| CompileError::new_err((message, PyList::empty(py).unbind())))
+}
+
+/// A `CompileError` carrying the diagnostics of `result`.
+fn compile_error(py: Python<'_>, message: &str, result: &CompileResult) -> PyErr {
+    match diagnostics(py, result) {
+        Ok(list) => CompileError::new_err((message.to_string(), list.unbind())),
+        Err(e) => e,
+    }
+}
+
+/// The diagnostics of `result` as a list of dicts.
+fn diagnostics<'py>(py: Python<'py>, result: &CompileResult) -> PyResult<Bound<'py, PyList>> {
+    let records = result
+        .diagnostics
+        .iter()
+        .map(|d| json_value(py, &d.to_json()))
+        .collect::<PyResult<Vec<_>>>()?;
+    PyList::new(py, records)
+}
+
+/// `json` as Python values, read by the `json` module.
+fn json_value<'py>(py: Python<'py>, json: &str) -> PyResult<Bound<'py, PyAny>> {
+    py.import("json")?.call_method1("loads", (json,))
+}
+
+#[pymodule]
+fn doo(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("CompileError", m.py().get_type::<CompileError>())?;
+    m.add_function(wrap_pyfunction!(lex, m)?)?;
+    m.add_function(wrap_pyfunction!(parse, m)?)?;
+    m.add_function(wrap_pyfunction!(check, m)?)?;
+    m.add_function(wrap_pyfunction!(compile_program, m)?)?;
+    Ok(())
+}
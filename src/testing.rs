@@ -0,0 +1,102 @@
+// Runs the `#[test]` functions of a program for `doo test`. A test passes when
+// it returns and fails when it panics, a failed `assert` included; what it
+// printed is kept so a failure can be shown with its output.
+
+use crate::mir::interp::Interpreter;
+use crate::mir::MirProgram;
+use std::path::Path;
+use std::process::Command;
+
+/// How one test went.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestOutcome {
+    pub name: String,
+    pub passed: bool,
+    pub output: String, // What it printed, then why it failed if it did
+}
+
+/// Runs each of `tests` with the interpreter, the global-scope statements
+/// first, so one test can't see what another changed.
+pub fn run_interpreted(
+    program: &MirProgram,
+    tests: &[String],
+    overflow_checks: bool,
+) -> Vec<TestOutcome> {
+    tests
+        .iter()
+        .map(|name| {
+            let mut out = Vec::new();
+            let mut err = Vec::new();
+            let mut interpreter = Interpreter::new(program, &mut out, &mut err);
+            interpreter.overflow_checks = overflow_checks;
+            let code = interpreter.run_from(name);
+            out.extend(err);
+            let mut output = String::from_utf8_lossy(&out).into_owned();
+            if let Err(e) = &code {
+                output.push_str(&format!("error: {}\n", e));
+            }
+            TestOutcome {
+                name: name.clone(),
+                passed: code == Ok(0),
+                output,
+            }
+        })
+        .collect()
+}
+
+/// Runs each of `tests` in its own process of the test build `exe`, which
+/// takes the index of the test to run (see `CodeGen::generate_test_main`).
+pub fn run_harness(exe: &Path, tests: &[String]) -> Vec<TestOutcome> {
+    tests
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let (passed, output) = match Command::new(exe).arg(i.to_string()).output() {
+                Ok(run) => {
+                    let mut output = String::from_utf8_lossy(&run.stdout).into_owned();
+                    output.push_str(&String::from_utf8_lossy(&run.stderr));
+                    if !run.status.success() && run.status.code() != Some(101) {
+                        output.push_str(&format!("error: test exited with {}\n", run.status));
+                    }
+                    (run.status.success(), output)
+                }
+                Err(e) => (
+                    false,
+                    format!("error: failed to start {}: {}\n", exe.display(), e),
+                ),
+            };
+            TestOutcome {
+                name: name.clone(),
+                passed,
+                output,
+            }
+        })
+        .collect()
+}
+
+/// The lines `doo test` prints for `outcomes`, as `test name ... ok` or
+/// `FAILED`, then the output of the failures and a summary.
+pub fn report(outcomes: &[TestOutcome]) -> String {
+    let mut report = format!("running {} tests\n", outcomes.len());
+    for outcome in outcomes {
+        let status = if outcome.passed { "ok" } else { "FAILED" };
+        report.push_str(&format!("test {} ... {}\n", outcome.name, status));
+    }
+    let failed: Vec<&TestOutcome> = outcomes.iter().filter(|o| !o.passed).collect();
+    if !failed.is_empty() {
+        report.push_str("\nfailures:\n");
+        for outcome in &failed {
+            report.push_str(&format!("\n---- {} ----\n{}", outcome.name, outcome.output));
+            if !outcome.output.is_empty() && !outcome.output.ends_with('\n') {
+                report.push('\n');
+            }
+        }
+    }
+    report.push_str(&format!(
+        "\ntest result: {}. {} passed; {} failed\n",
+        if failed.is_empty() { "ok" } else { "FAILED" },
+        outcomes.len() - failed.len(),
+        failed.len()
+    ));
+    report
+}
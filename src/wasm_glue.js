@@ -0,0 +1,192 @@
+// The C library a wasm32-unknown-unknown doo module imports from "env":
+// printf and friends write `print` and panics to the terminal, and the rest
+// is what the runtime needs (an allocator, string and memory functions,
+// strtod and qsort). Variadic arguments arrive as a pointer to a buffer
+// holding them, each aligned to its size.
+
+class Exit {
+  constructor(code) {
+    this.code = code;
+  }
+}
+
+let memory;
+let table;
+let heapTop;
+const freeLists = new Map();
+const decoder = new TextDecoder();
+const encoder = new TextEncoder();
+
+const bytes = () => new Uint8Array(memory.buffer);
+const view = () => new DataView(memory.buffer);
+
+function strlen(ptr) {
+  const mem = bytes();
+  let end = ptr;
+  while (mem[end] !== 0) end++;
+  return end - ptr;
+}
+
+const cString = (ptr) => decoder.decode(bytes().subarray(ptr, ptr + strlen(ptr)));
+
+function writeFd(fd, text) {
+  (fd === 2 ? process.stderr : process.stdout).write(text);
+  return encoder.encode(text).length;
+}
+
+// The conversions the compiler emits: %d, %lld, %f, %.*f, %s and %%.
+function format(fmt, args) {
+  const next = (size) => {
+    args = (args + size - 1) & -size;
+    const at = args;
+    args += size;
+    return at;
+  };
+  return cString(fmt).replace(/%(\.\*)?(ll)?([dfs%])/g, (_, star, long, conversion) => {
+    const data = view();
+    if (conversion === "%") return "%";
+    if (conversion === "s") return cString(data.getUint32(next(4), true));
+    if (conversion === "d") {
+      return long ? String(data.getBigInt64(next(8), true)) : String(data.getInt32(next(4), true));
+    }
+    const precision = star ? data.getInt32(next(4), true) : 6;
+    const value = data.getFloat64(next(8), true);
+    if (Number.isNaN(value)) return "nan";
+    if (!Number.isFinite(value)) return value < 0 ? "-inf" : "inf";
+    return value.toFixed(Math.min(Math.max(precision, 0), 100));
+  });
+}
+
+// Blocks are powers of two from 16 bytes, after an 8-byte header holding
+// their size; freed ones are reused for the next request of that size.
+function malloc(size) {
+  let block = 16;
+  while (block < size + 8) block *= 2;
+  const free = freeLists.get(block);
+  if (free && free.length > 0) return free.pop();
+  const ptr = heapTop + 8;
+  heapTop += block;
+  const missing = heapTop - memory.buffer.byteLength;
+  if (missing > 0) memory.grow(Math.ceil(missing / 65536));
+  view().setUint32(ptr - 4, block, true);
+  return ptr;
+}
+
+function free(ptr) {
+  if (ptr === 0) return;
+  const block = view().getUint32(ptr - 4, true);
+  if (!freeLists.has(block)) freeLists.set(block, []);
+  freeLists.get(block).push(ptr);
+}
+
+function realloc(ptr, size) {
+  if (ptr === 0) return malloc(size);
+  const block = view().getUint32(ptr - 4, true);
+  if (size + 8 <= block) return ptr;
+  const moved = malloc(size);
+  bytes().copyWithin(moved, ptr, ptr + block - 8);
+  free(ptr);
+  return moved;
+}
+
+function compare(a, b, length) {
+  const mem = bytes();
+  for (let i = 0; i < length; i++) {
+    if (mem[a + i] !== mem[b + i]) return mem[a + i] - mem[b + i];
+  }
+  return 0;
+}
+
+const env = {
+  printf: (fmt, args) => writeFd(1, format(fmt, args)),
+  dprintf: (fd, fmt, args) => writeFd(fd, format(fmt, args)),
+  snprintf: (buf, size, fmt, args) => {
+    const text = encoder.encode(format(fmt, args));
+    if (size > 0) {
+      const length = Math.min(text.length, size - 1);
+      bytes().set(text.subarray(0, length), buf);
+      bytes()[buf + length] = 0;
+    }
+    return text.length;
+  },
+  exit: (code) => {
+    throw new Exit(code);
+  },
+  malloc,
+  realloc,
+  free,
+  strlen,
+  strcmp: (a, b) => compare(a, b, Math.min(strlen(a), strlen(b)) + 1),
+  memcmp: compare,
+  strstr: (haystack, needle) => {
+    const at = cString(haystack).indexOf(cString(needle));
+    return at < 0 ? 0 : haystack + encoder.encode(cString(haystack).slice(0, at)).length;
+  },
+  memcpy: (dest, src, length) => {
+    bytes().copyWithin(dest, src, src + length);
+    return dest;
+  },
+  memmove: (dest, src, length) => {
+    bytes().copyWithin(dest, src, src + length);
+    return dest;
+  },
+  memset: (dest, value, length) => {
+    bytes().fill(value, dest, dest + length);
+    return dest;
+  },
+  strtod: (ptr, end) => {
+    const text = /^\s*[+-]?(inf(inity)?|nan|(\d+\.?\d*|\.\d+)(e[+-]?\d+)?)/i.exec(cString(ptr));
+    if (end !== 0) view().setUint32(end, ptr + (text ? text[0].length : 0), true);
+    if (!text) return 0;
+    const number = text[0].trim().toLowerCase().replace(/inf(inity)?/, "Infinity");
+    return number.endsWith("nan") ? NaN : Number(number);
+  },
+  strtoll: (ptr, end, _base) => {
+    const text = /^\s*[+-]?\d+/.exec(cString(ptr));
+    if (end !== 0) view().setUint32(end, ptr + (text ? text[0].length : 0), true);
+    if (!text) return 0n;
+    return BigInt.asIntN(64, BigInt(text[0].trim()));
+  },
+  atoi: (ptr) => {
+    const text = /^\s*[+-]?\d+/.exec(cString(ptr));
+    return text ? Number(BigInt.asIntN(32, BigInt(text[0].trim()))) : 0;
+  },
+  fmod: (a, b) => a % b,
+  // The 128-bit product LLVM asks for to check 64-bit multiplications
+  __multi3: (out, aLow, aHigh, bLow, bHigh) => {
+    const a = (BigInt.asUintN(64, aHigh) << 64n) | BigInt.asUintN(64, aLow);
+    const b = (BigInt.asUintN(64, bHigh) << 64n) | BigInt.asUintN(64, bLow);
+    const product = BigInt.asUintN(128, a * b);
+    view().setBigUint64(out, BigInt.asUintN(64, product), true);
+    view().setBigUint64(out + 8, product >> 64n, true);
+  },
+  qsort: (base, count, size, cmp) => {
+    const compareFn = table.get(cmp);
+    const elements = [];
+    for (let i = 0; i < count; i++) {
+      elements.push(bytes().slice(base + i * size, base + (i + 1) * size));
+    }
+    const left = malloc(size);
+    const right = malloc(size);
+    elements.sort((a, b) => {
+      bytes().set(a, left);
+      bytes().set(b, right);
+      return compareFn(left, right);
+    });
+    free(left);
+    free(right);
+    elements.forEach((element, i) => bytes().set(element, base + i * size));
+  },
+};
+
+const wasm = require("node:fs").readFileSync(require("node:path").join(__dirname, WASM_FILE));
+WebAssembly.instantiate(wasm, { env }).then(({ instance }) => {
+  ({ memory, __indirect_function_table: table } = instance.exports);
+  heapTop = (instance.exports.__heap_base.value + 7) & -8;
+  try {
+    process.exitCode = instance.exports.main();
+  } catch (error) {
+    if (!(error instanceof Exit)) throw error;
+    process.exitCode = error.code;
+  }
+});
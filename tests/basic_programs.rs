@@ -1,6 +1,14 @@
-use doo::compiler::{compile_project, CompileOptions};
+use doo::capi::{
+    doo_compile_source, doo_result_artifact, doo_result_diagnostics, doo_result_error,
+    doo_result_free, doo_result_success, DooOptions, DOO_EMIT_LLVM_IR, DOO_EMIT_OBJECT,
+};
+use doo::compiler::{
+    compile_project, compile_source, Artifact, CompileOptions, Emit, LinkLib, LinkOptions,
+};
+use std::ffi::{CStr, CString};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::ptr;
 
 fn test_program_file(filename: &str) -> bool {
     let path = PathBuf::from(format!("tests/programs/valid/{}", filename));
@@ -214,7 +222,374 @@ fn test_string_concat() {
     assert!(test_program_file("string_concat.doo"));
 }
 
+#[test]
+fn test_include_files() {
+    // The files are found next to the program, not in the working directory
+    assert!(test_program_file("include_files.doo"));
+}
+
 #[test]
 fn test_type_operations() {
     assert!(test_program_file("type_operations.doo"));
 }
+
+// =====================
+// Diagnostics
+// =====================
+
+#[test]
+fn test_compile_result_diagnostics() {
+    let check = |path: &str| {
+        compile_project(CompileOptions {
+            input_path: PathBuf::from(path),
+            check_only: true,
+            ..Default::default()
+        })
+        .unwrap()
+    };
+
+    let result = check("tests/programs/invalid/type_error.doo");
+    assert!(!result.success);
+    assert_eq!(result.error_count, 1);
+    assert_eq!(result.diagnostics[0].code, "E0003");
+    assert_eq!(result.diagnostics[0].line, Some(2));
+    assert!(result
+        .sources
+        .contains_key("tests/programs/invalid/type_error.doo"));
+
+    // Warnings are returned too, but don't fail the compile
+    let result = check("tests/programs/valid/sorting.doo");
+    assert!(result.success);
+    assert_eq!(result.warning_count, 1);
+    assert!(result.diagnostics[0].is_warning);
+}
+
+#[test]
+fn test_project_manifest() {
+    // doo.toml names the entry point, which is neither main.doo nor src/main.doo
+    let result = compile_project(CompileOptions {
+        input_path: PathBuf::from("tests/programs/project"),
+        check_only: true,
+        ..Default::default()
+    })
+    .unwrap();
+    assert!(result.success);
+
+    let dir = std::env::temp_dir().join(format!("doo_manifest_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("doo.toml"),
+        "[package]\nname = \"app\"\nentry = \"app.doo\"\n",
+    )
+    .unwrap();
+    let error = compile_project(CompileOptions {
+        input_path: dir.clone(),
+        check_only: true,
+        ..Default::default()
+    })
+    .err()
+    .unwrap();
+    assert!(
+        error.contains("entry point") && error.contains("app.doo"),
+        "{}",
+        error
+    );
+
+    std::fs::write(dir.join("doo.toml"), "[package]\nname = \"app\"\nopt = 1\n").unwrap();
+    let error = compile_project(CompileOptions {
+        input_path: dir.clone(),
+        check_only: true,
+        ..Default::default()
+    })
+    .err()
+    .unwrap();
+    assert!(
+        error.starts_with("Error: doo.toml:3: unknown key 'opt'"),
+        "{}",
+        error
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_emit_ast_json() {
+    let check = |path: &str, emit: Emit| {
+        compile_project(CompileOptions {
+            input_path: PathBuf::from(path),
+            check_only: true,
+            emit: vec![emit],
+            ..Default::default()
+        })
+        .unwrap()
+    };
+
+    let result = check("tests/programs/valid/function_basic.doo", Emit::AstJson);
+    let json = result.ast_json.unwrap();
+    assert!(json.starts_with(
+        "{\"kind\":\"Program\",\"statements\":[{\"kind\":\"FunctionDecl\",\"name\":\"add\""
+    ));
+    assert!(json.contains("\"params\":[[\"x\",\"Int\"],[\"y\",\"Int\"]]"));
+
+    // The parsed AST is there despite type errors; the analyzed one isn't
+    let result = check("tests/programs/invalid/type_error.doo", Emit::AstJson);
+    assert!(result.ast_json.is_some());
+    let result = check("tests/programs/invalid/type_error.doo", Emit::TypedAstJson);
+    assert!(result.ast_json.is_none());
+}
+
+#[test]
+fn test_link_options() {
+    let lib = |arg: &str| LinkLib::from_arg(arg).unwrap();
+    assert_eq!(
+        lib("static=ssl"),
+        LinkLib {
+            name: "ssl".to_string(),
+            static_lib: true
+        }
+    );
+    assert!(!lib("dylib=m").static_lib);
+    assert!(!lib("m").static_lib);
+    assert!(LinkLib::from_arg("framework=Cocoa").is_err());
+    assert!(LinkLib::from_arg("static=").is_err());
+
+    let link = LinkOptions {
+        libs: vec![lib("m"), lib("static=sqlite3")],
+        search_paths: vec![PathBuf::from("vendor/lib")],
+        args: vec!["-Wl,--as-needed".to_string()],
+        static_link: false,
+    };
+    assert_eq!(
+        link.driver_args(false).unwrap(),
+        [
+            "-Lvendor/lib",
+            "-lm",
+            "-Wl,-Bstatic",
+            "-lsqlite3",
+            "-Wl,-Bdynamic",
+            "-Wl,--as-needed"
+        ]
+    );
+    // Apple's linker has no switch to archives
+    assert!(link.driver_args(true).is_err());
+
+    // A static executable takes every library from its archive anyway
+    let link = LinkOptions {
+        static_link: true,
+        ..link
+    };
+    assert_eq!(
+        link.driver_args(false).unwrap(),
+        [
+            "-Lvendor/lib",
+            "-lm",
+            "-lsqlite3",
+            "-static",
+            "-Wl,--as-needed"
+        ]
+    );
+}
+
+#[test]
+fn test_compile_source_artifact() {
+    // Text that was never written to a file compiles as the file it names
+    let source = "fn main() {\n    let x = 6 * 7;\n    print(x);\n}\n";
+    let result = compile_source(
+        Path::new("scratch.doo"),
+        source,
+        CompileOptions {
+            artifact: Artifact::from_name("mir"),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert!(result.success);
+    let mir = String::from_utf8(result.artifact.unwrap()).unwrap();
+    assert!(mir.contains("fn main("));
+    assert!(result.exe_path.is_none());
+
+    let result = compile_source(
+        Path::new("scratch.doo"),
+        "fn main() {\n    print(y);\n}\n",
+        CompileOptions {
+            artifact: Some(Artifact::LlvmIr),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert!(!result.success);
+    assert_eq!(result.diagnostics[0].filename, "scratch.doo");
+    assert!(result.artifact.is_none());
+}
+
+// =====================
+// Running Programs
+// =====================
+
+/// Builds `source` into an executable and runs it.
+fn run_program(name: &str, source: &str) -> std::process::Output {
+    run_program_with(name, source, CompileOptions::default())
+}
+
+/// Builds `source` with `opts` into an executable and runs it.
+fn run_program_with(name: &str, source: &str, opts: CompileOptions) -> std::process::Output {
+    let output = std::env::temp_dir().join(format!("doo_{}_{}", name, std::process::id()));
+    let result = compile_source(
+        Path::new("main.doo"),
+        source,
+        CompileOptions {
+            output_name: output.to_string_lossy().into_owned(),
+            ..opts
+        },
+    )
+    .unwrap();
+    assert!(result.success, "{:?}", result.diagnostics);
+    let exe = result.exe_path.unwrap();
+    let run = std::process::Command::new(&exe).output().unwrap();
+    fs::remove_file(exe).ok();
+    run
+}
+
+#[test]
+fn test_array_insert_remove_bounds() {
+    // Out of range, both panic like indexing instead of moving memory
+    let run = run_program(
+        "insert_bounds",
+        "fn main() {\n    let mut xs = [1, 2, 3];\n    xs.insert(3, 4);\n    print(xs);\n    xs.insert(5, 6);\n    print(xs);\n}\n",
+    );
+    assert_eq!(run.status.code(), Some(101));
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "[1, 2, 3, 4]\n");
+    assert!(String::from_utf8_lossy(&run.stderr).contains("index 5 is out of bounds for length 4"));
+
+    let run = run_program(
+        "remove_bounds",
+        "fn main() {\n    let mut xs = [1, 2, 3];\n    print(xs.remove(0));\n    let i = 0 - 1;\n    print(xs.remove(i));\n}\n",
+    );
+    assert_eq!(run.status.code(), Some(101));
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "1\n");
+    assert!(String::from_utf8_lossy(&run.stderr).contains("index -1 is out of bounds for length 2"));
+}
+
+#[test]
+fn test_float_remainder() {
+    // Float `%` calls the C library's fmod, which is in libm; a value parsed
+    // at runtime keeps it from being folded away
+    let run = run_program(
+        "float_rem",
+        "fn main() {\n    let x = toFloat(\"7.5\");\n    print(x % 2.0, 0.0 - x % 2.0);\n}\n",
+    );
+    assert_eq!(run.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "1.500000 -1.500000\n");
+}
+
+#[test]
+fn test_division_overflow() {
+    // The minimum divided by -1 doesn't fit: it wraps, or panics with
+    // overflow checks, instead of trapping
+    let source = "fn main() {\n    let min = toInt(\"-2147483648\");\n    let m = toInt(\"-1\");\n    print(min / m, min % m);\n}\n";
+    let run = run_program("div_overflow", source);
+    assert_eq!(run.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "-2147483648 0\n");
+
+    let run = run_program_with(
+        "div_overflow_checked",
+        source,
+        CompileOptions {
+            overflow_checks: true,
+            ..Default::default()
+        },
+    );
+    assert_eq!(run.status.code(), Some(101));
+    assert!(String::from_utf8_lossy(&run.stderr).contains("integer overflow"));
+}
+
+#[test]
+fn test_map_hash_index() {
+    // Enough keys to regrow the index several times, and removes that
+    // renumber the pairs after them; pairs keep their insertion order
+    let source = "fn main() {\n    let mut squares = {0: 0};\n    for i in 1..1000 {\n        squares.insert(i, i * i);\n    }\n    squares.insert(7, 0 - 7);\n    let gone = squares.remove(500);\n    let mut names: {Str: Int} = {};\n    for i in 0..300 {\n        names.insert(\"n\" + toStr(i), i);\n    }\n    let again = names.remove(\"n0\");\n    print(names[\"n299\"] ?? 0 - 1, names[\"n0\"] ?? 0 - 1, again ?? 0 - 1);\n    names.clear();\n    names.insert(\"back\", 1);\n    print(squares[999] ?? 0, squares[7] ?? 0, squares.containsKey(500), gone ?? 0);\n    print(squares[501] ?? 0, squares[1000] ?? 0 - 1, names, names[\"n5\"] ?? 0 - 1);\n    let keys = squares.keys();\n    print(keys[0], keys[499], keys[500], squares.values()[998]);\n}\n";
+    let run = run_program("map_hash_index", source);
+    assert_eq!(run.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&run.stdout),
+        "299 -1 0\n998001 -7 false 250000\n251001 -1 {\"back\": 1} -1\n0 499 501 998001\n"
+    );
+}
+
+#[test]
+fn test_closure_values() {
+    // Closures capture by value, are passed and returned like other values,
+    // and capture other closures; those made in a loop are released each time
+    let source = "struct Point { x: Int, y: Int }\n\nfn apply(f: fn(Int) -> Int, v: Int) -> Int {\n    return f(v);\n}\n\nfn adder(n: Int) -> fn(Int) -> Int {\n    return |x: Int| x + n;\n}\n\nfn main() {\n    let name = \"doo\";\n    let greet = |greeting: Str| greeting + \", \" + name;\n    let p = Point { x: 1, y: 2 };\n    let norm = || p.x * p.x + p.y * p.y;\n    let mut total = 0;\n    for i in 0..3 {\n        let add = adder(i);\n        let twice = |x: Int| apply(add, apply(add, x));\n        total = total + twice(10);\n    }\n    print(greet(\"hi\"), norm(), total);\n}\n";
+    let run = run_program("closure_values", source);
+    assert_eq!(run.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "hi, doo 5 36\n");
+}
+
+// =====================
+// C API
+// =====================
+
+#[test]
+fn test_c_api() {
+    let compile = |source: &str, emit: i32| {
+        let source = CString::new(source).unwrap();
+        let options = DooOptions {
+            file_name: c"main.doo".as_ptr(),
+            emit,
+            opt_level: 2,
+            release: 0,
+        };
+        unsafe { doo_compile_source(source.as_ptr(), &options) }
+    };
+
+    unsafe {
+        let result = compile(
+            "fn main() {\n    print(\"Hello, World!\");\n}\n",
+            DOO_EMIT_LLVM_IR,
+        );
+        assert_eq!(doo_result_success(result), 1);
+        assert!(doo_result_error(result).is_null());
+        let mut len = 0;
+        let ir = doo_result_artifact(result, &mut len);
+        let ir = std::str::from_utf8(std::slice::from_raw_parts(ir, len)).unwrap();
+        assert!(ir.contains("define") && ir.contains(" @main("));
+        doo_result_free(result);
+
+        // Errors come back as JSON lines, and nothing is built
+        let source = fs::read_to_string("tests/programs/invalid/type_error.doo").unwrap();
+        let result = compile(&source, DOO_EMIT_OBJECT);
+        assert_eq!(doo_result_success(result), 0);
+        let diagnostics = CStr::from_ptr(doo_result_diagnostics(result))
+            .to_str()
+            .unwrap();
+        assert_eq!(diagnostics.lines().count(), 1);
+        assert!(diagnostics.contains("\"code\":\"E0003\""));
+        assert!(doo_result_artifact(result, ptr::null_mut()).is_null());
+        doo_result_free(result);
+
+        // A program that can't be built at all has an error instead
+        let result = compile("fn helper() {}\n", DOO_EMIT_OBJECT);
+        let error = CStr::from_ptr(doo_result_error(result)).to_str().unwrap();
+        assert!(error.contains("main() function not found"));
+        doo_result_free(result);
+    }
+}
+
+// `doo_compile_source` turns a compiler panic into an error by catching it,
+// which a release build compiled with `panic = "abort"` can't do. Tests are
+// always built to unwind, so the profile itself is what's checked.
+#[test]
+fn test_release_profile_unwinds() {
+    let manifest =
+        fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml")).unwrap();
+    let release = manifest
+        .split("[profile.release]")
+        .nth(1)
+        .and_then(|rest| rest.split("\n[").next())
+        .unwrap();
+    let panic = release
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("panic"))
+        .map(|value| value.trim_start_matches([' ', '=']));
+    assert!(panic.is_none_or(|value| value.starts_with("\"unwind\"")));
+}
@@ -1005,7 +1005,7 @@ fn regression_float_type() {
         }
     "#;
     let result = compile_full_pipeline(input);
-    assert!(result.is_err());
+    assert!(result.is_ok());
 }
 
 #[test]
@@ -1447,3 +1447,39 @@ fn regression_nested_boolean_negation() {
     let result = compile_full_pipeline(input);
     assert!(result.is_err());
 }
+
+#[test]
+fn regression_match_arms_all_return() {
+    let input = r#"
+        fn f(b: Bool) -> Int {
+            match b { true => { return 1; }, false => { return 0; } }
+        }
+
+        fn main() {
+            print(f(true), f(false));
+        }
+    "#;
+    let result = compile_full_pipeline(input);
+    assert!(result.is_ok());
+}
+
+#[test]
+fn regression_enum_match_arms_all_return() {
+    let input = r#"
+        enum Shape { Circle(Int), Square(Int), Empty }
+
+        fn area(s: Shape) -> Int {
+            match s {
+                Shape::Circle(r) => { return 3 * r * r; },
+                Shape::Square(w) => { return w * w; },
+                Shape::Empty => { return 0; }
+            }
+        }
+
+        fn main() {
+            print(area(Shape::Circle(2)), area(Shape::Empty));
+        }
+    "#;
+    let result = compile_full_pipeline(input);
+    assert!(result.is_ok());
+}